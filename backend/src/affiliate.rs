@@ -0,0 +1,53 @@
+use crate::model::{Loaded, Page, Product, ProductDetail, Wishlist};
+
+/// Adds the affiliate parameters of their sources to the shop links of the
+/// products in a response, like `ConvertPrices` converts their prices.
+pub trait ApplyAffiliate {
+    fn apply_affiliate(&mut self);
+}
+
+impl ApplyAffiliate for Product {
+    fn apply_affiliate(&mut self) {
+        Product::apply_affiliate(self)
+    }
+}
+
+impl ApplyAffiliate for ProductDetail {
+    fn apply_affiliate(&mut self) {
+        self.get_product_mut().apply_affiliate()
+    }
+}
+
+impl<T: ApplyAffiliate> ApplyAffiliate for Vec<T> {
+    fn apply_affiliate(&mut self) {
+        self.iter_mut().for_each(T::apply_affiliate)
+    }
+}
+
+impl<T: ApplyAffiliate> ApplyAffiliate for Loaded<T> {
+    fn apply_affiliate(&mut self) {
+        self.get_value_mut().apply_affiliate()
+    }
+}
+
+impl<T> ApplyAffiliate for Page<T> where Vec<T>: ApplyAffiliate {
+    fn apply_affiliate(&mut self) {
+        self.get_items_mut().apply_affiliate()
+    }
+}
+
+impl ApplyAffiliate for Wishlist {
+    fn apply_affiliate(&mut self) {
+        if let Some(products) = self.get_products_mut() {
+            products.apply_affiliate();
+        }
+    }
+}
+
+/// Applies the affiliate parameters unless the raw links were asked for.
+pub fn apply_affiliate<T: ApplyAffiliate>(raw_links: bool, mut output: T) -> T {
+    if !raw_links {
+        output.apply_affiliate();
+    }
+    output
+}
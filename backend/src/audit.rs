@@ -0,0 +1,36 @@
+use mongodb::bson::{document::Document, oid::ObjectId};
+
+use crate::auth::Identity;
+use crate::model::AuditEvent;
+use crate::repository::WishlistRepository;
+
+/// Actor of the changes made with the admin API key.
+pub const ADMIN: &str = "admin";
+/// Actor of the changes made by the scraper and the jobs following up on it.
+pub const SCRAPER: &str = "scraper";
+/// Actor of the changes made by visitors without an identity, e.g. reservations.
+pub const ANONYMOUS: &str = "anonymous";
+
+/// Names `identity` in the audit log, users by their id and share links by their list.
+pub fn actor_of(identity: Option<&Identity>) -> String {
+    match identity {
+        Some(Identity::Admin) => ADMIN.to_string(),
+        Some(Identity::User { id }) => format!("user:{}", id.to_hex()),
+        Some(Identity::Share { list, .. }) => format!("share:{}", list),
+        None => ANONYMOUS.to_string(),
+    }
+}
+
+/// Id of the user named by `actor`, `None` for all other actors.
+pub fn user_of(actor: &str) -> Option<ObjectId> {
+    actor.strip_prefix("user:").and_then(|id| ObjectId::with_string(id).ok())
+}
+
+/// Appends a change to the audit log. The change is made already, so failing
+/// to log it is only reported instead of failing the request.
+pub async fn record_event(repo: &dyn WishlistRepository, actor: &str, action: &str, entity: &str, entity_id: Option<&ObjectId>, payload: Document) {
+    let event = AuditEvent::to_document(actor, action, entity, entity_id, payload, chrono::Utc::now().timestamp() as i32);
+    if let Err(e) = repo.insert_event(event).await {
+        warn!("Could not log {} of {} by {}: {}", action, entity, actor, e);
+    }
+}
@@ -0,0 +1,99 @@
+use serde::Deserialize;
+use std::fmt;
+use std::sync::Arc;
+use subtle::ConstantTimeEq;
+
+use crate::config::ServerConfig;
+use crate::{Error, Result};
+
+/// What an API key may be used for.
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ApiKeyScope {
+    /// Acting as admin on lists and products.
+    Write,
+    /// Changing categories and reading the job statuses.
+    Admin,
+}
+
+const ALL_SCOPES: [ApiKeyScope; 2] = [ApiKeyScope::Write, ApiKeyScope::Admin];
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct ApiKeyConfig {
+    pub key: String,
+    #[serde(default = "default_scopes")]
+    pub scopes: Vec<ApiKeyScope>,
+}
+
+/// The configured API keys, `api_key` is granted every scope.
+#[derive(Clone, Default)]
+pub struct ApiKeys {
+    keys: Arc<Vec<ApiKeyConfig>>,
+}
+
+impl ApiKeys {
+    pub fn new(keys: Vec<ApiKeyConfig>) -> Self {
+        Self { keys: Arc::new(keys) }
+    }
+
+    pub fn from_config(config: &ServerConfig) -> Self {
+        let legacy = config.api_key.iter().map(|key| ApiKeyConfig {
+            key: key.clone(),
+            scopes: default_scopes(),
+        });
+        let keys = legacy
+            .chain(config.api_keys.iter().cloned())
+            .filter(|entry| {
+                if entry.key.is_empty() {
+                    warn!("Ignoring empty API key");
+                }
+                !entry.key.is_empty()
+            })
+            .collect();
+        Self::new(keys)
+    }
+
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    /// Fails with `Unauthorized` for unknown keys and `Forbidden` for keys lacking `scope`.
+    pub fn authorize(&self, key: &str, scope: ApiKeyScope) -> Result<()> {
+        match self.get_scopes(key) {
+            Some(scopes) if scopes.contains(&scope) => Ok(()),
+            Some(_) => Err(Error::Forbidden(format!("API key lacks the {} scope", scope))),
+            None => Err(Error::Unauthorized),
+        }
+    }
+
+    /// Compares `key` against every configured key in constant time, so the
+    /// response time doesn't tell how much of a guess was right.
+    fn get_scopes(&self, key: &str) -> Option<&[ApiKeyScope]> {
+        let mut found = None;
+        for entry in self.keys.iter() {
+            let matches: bool = entry.key.as_bytes().ct_eq(key.as_bytes()).into();
+            if matches && found.is_none() {
+                found = Some(entry.scopes.as_slice());
+            }
+        }
+        found
+    }
+}
+
+impl fmt::Display for ApiKeyScope {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ApiKeyScope::Write => write!(f, "write"),
+            ApiKeyScope::Admin => write!(f, "admin"),
+        }
+    }
+}
+
+fn default_scopes() -> Vec<ApiKeyScope> {
+    ALL_SCOPES.to_vec()
+}
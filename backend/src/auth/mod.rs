@@ -0,0 +1,165 @@
+use mongodb::bson::oid::ObjectId;
+use warp::{Filter, Rejection};
+
+use crate::model::{CollaboratorRole, List, ShareScope, Visibility};
+use crate::query::TokenQuery;
+use crate::{Error, Result};
+
+mod api_key;
+mod password;
+mod session;
+
+pub use self::api_key::{ApiKeyConfig, ApiKeyScope, ApiKeys};
+pub use self::password::{hash_password, verify_password};
+pub use self::session::SessionKeys;
+
+/// Sender of a request: an API key with the `write` scope grants admin access, a session token acts
+/// as the user it was issued to and a share token grants access to one list.
+#[derive(Clone, Debug)]
+pub enum Identity {
+    Admin,
+    User { id: ObjectId },
+    Share { list: String, scope: ShareScope },
+}
+
+impl Identity {
+    pub fn get_user_id(&self) -> Option<&ObjectId> {
+        match self {
+            Identity::User { id } => Some(id),
+            _ => None,
+        }
+    }
+
+    /// Whether the identity may change the products of `list`: its owner and
+    /// editors, lists without an owner are left to the admin.
+    pub fn can_manage(&self, list: &List) -> bool {
+        self.can_administer(list) || self.get_role(list) == Some(CollaboratorRole::Editor)
+    }
+
+    /// Whether the identity may change `list` itself, share it and invite
+    /// collaborators, which is left to its owner and the admin.
+    pub fn can_administer(&self, list: &List) -> bool {
+        match self {
+            Identity::Admin => true,
+            Identity::User { id } => list.get_owner() == Some(id),
+            Identity::Share { .. } => false,
+        }
+    }
+
+    /// Role the identity was invited to `list` with, `None` for everybody else.
+    pub fn get_role(&self, list: &List) -> Option<CollaboratorRole> {
+        self.get_user_id().and_then(|id| list.get_role(id))
+    }
+
+    /// Whether the identity owns `list`, the admin owns the lists without an owner.
+    pub fn is_owner(&self, list: &List) -> bool {
+        match self {
+            Identity::Admin => list.get_owner().is_none(),
+            Identity::User { id } => list.get_owner() == Some(id),
+            Identity::Share { .. } => false,
+        }
+    }
+
+    /// Whether the identity may read `list` whatever its visibility.
+    fn has_access(&self, list: &List, min_scope: ShareScope) -> bool {
+        self.can_manage(list) || self.get_role(list).is_some() || self.is_shared(list, min_scope)
+    }
+
+    fn is_shared(&self, list: &List, min_scope: ShareScope) -> bool {
+        match self {
+            Identity::Share { list: slug, scope } => {
+                slug == list.get_slug() && (min_scope == ShareScope::Read || *scope == ShareScope::Reserve)
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Public and unlisted lists can be read by anybody, private ones only by
+/// whoever may manage them, collaborators or holders of a share token for them.
+pub fn can_read(identity: Option<&Identity>, list: &List) -> bool {
+    list.get_visibility() != Visibility::Private
+        || identity.map(|i| i.has_access(list, ShareScope::Read)).unwrap_or(false)
+}
+
+/// Whether `list` and its products show up in listings and searches spanning
+/// all lists. Unlisted lists are readable, but only found with their slug.
+pub fn can_discover(identity: Option<&Identity>, list: &List) -> bool {
+    list.get_visibility() == Visibility::Public
+        || identity.map(|i| i.has_access(list, ShareScope::Read)).unwrap_or(false)
+}
+
+/// Products of private lists can only be reserved with a share token of scope `reserve`.
+pub fn can_reserve(identity: Option<&Identity>, list: &List) -> bool {
+    list.get_visibility() != Visibility::Private
+        || identity.map(|i| i.has_access(list, ShareScope::Reserve)).unwrap_or(false)
+}
+
+/// Requires an `X-Api-Key` holding `scope`, sessions are not accepted.
+pub fn with_api_key(api_keys: ApiKeys, scope: ApiKeyScope) -> impl Filter<Extract = (), Error = Rejection> + Clone {
+    warp::header::optional::<String>("x-api-key")
+        .and_then(move |key: Option<String>| {
+            let api_keys = api_keys.clone();
+            async move {
+                let key = key.ok_or_else(|| warp::reject::custom(Error::Unauthorized))?;
+                api_keys.authorize(&key, scope).map_err(warp::reject::custom)
+            }
+        })
+        .untuple_one()
+}
+
+/// Resolves the sender from `X-Api-Key`, `Authorization: Bearer <token>` or
+/// a share token in `?token=`, `None` for anonymous requests. Invalid
+/// credentials are rejected rather than treated as anonymous.
+pub fn with_optional_identity(api_keys: ApiKeys, sessions: SessionKeys) -> impl Filter<Extract = (Option<Identity>,), Error = Rejection> + Clone {
+    with_credentials(api_keys, sessions.clone())
+        .and(warp::query::<TokenQuery>())
+        .and_then(move |identity: Option<Identity>, query: TokenQuery| {
+            let sessions = sessions.clone();
+            async move {
+                match (identity, query.get_token()) {
+                    (Some(identity), _) => Ok::<_, Rejection>(Some(identity)),
+                    (None, Some(token)) => {
+                        let (list, scope) = sessions.verify_share(token).map_err(warp::reject::custom)?;
+                        Ok(Some(Identity::Share { list, scope }))
+                    }
+                    (None, None) => Ok(None),
+                }
+            }
+        })
+}
+
+/// Requires the API key or a session, share tokens are not accepted.
+pub fn with_identity(api_keys: ApiKeys, sessions: SessionKeys) -> impl Filter<Extract = (Identity,), Error = Rejection> + Clone {
+    with_credentials(api_keys, sessions)
+        .and_then(|identity: Option<Identity>| async move {
+            identity.ok_or_else(|| warp::reject::custom(Error::Unauthorized))
+        })
+}
+
+fn with_credentials(api_keys: ApiKeys, sessions: SessionKeys) -> impl Filter<Extract = (Option<Identity>,), Error = Rejection> + Clone {
+    warp::header::optional::<String>("x-api-key")
+        .and(warp::header::optional::<String>("authorization"))
+        .and_then(move |key: Option<String>, authorization: Option<String>| {
+            let api_keys = api_keys.clone();
+            let sessions = sessions.clone();
+            async move {
+                resolve_identity(&api_keys, &sessions, key, authorization).map_err(warp::reject::custom)
+            }
+        })
+}
+
+fn resolve_identity(api_keys: &ApiKeys, sessions: &SessionKeys, key: Option<String>, authorization: Option<String>) -> Result<Option<Identity>> {
+    if let Some(key) = key {
+        api_keys.authorize(&key, ApiKeyScope::Write)?;
+        return Ok(Some(Identity::Admin));
+    }
+    match authorization {
+        Some(value) => {
+            let token = value.strip_prefix("Bearer ").ok_or(Error::Unauthorized)?;
+            let id = sessions.verify(token)?;
+            Ok(Some(Identity::User { id }))
+        }
+        None => Ok(None),
+    }
+}
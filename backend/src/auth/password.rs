@@ -0,0 +1,21 @@
+use argon2::Config;
+
+use crate::{Error, Result};
+
+/// Hashes `password` with Argon2id and a random salt, the returned string
+/// encodes the parameters and salt needed to verify it.
+pub fn hash_password(password: &str) -> Result<String> {
+    let salt: [u8; 16] = rand::random();
+    argon2::hash_encoded(password.as_bytes(), &salt, &Config::default())
+        .map_err(|e| Error::Internal(format!("could not hash password: {}", e)))
+}
+
+pub fn verify_password(hash: &str, password: &str) -> bool {
+    match argon2::verify_encoded(hash, password.as_bytes()) {
+        Ok(matches) => matches,
+        Err(e) => {
+            warn!("Could not verify password hash: {}", e);
+            false
+        }
+    }
+}
@@ -0,0 +1,115 @@
+use jsonwebtoken::{decode, encode, Algorithm, DecodingKey, EncodingKey, Header, Validation};
+use mongodb::bson::oid::ObjectId;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::config::ServerConfig;
+use crate::model::{Session, ShareLink, ShareScope};
+use crate::{Error, Result};
+
+/// Audiences keeping session and share tokens from being used for one another.
+const SESSION_AUDIENCE: &str = "session";
+const SHARE_AUDIENCE: &str = "share";
+
+/// Signs and verifies the HS256 session tokens handed out on login and the
+/// share tokens granting access to a single list.
+#[derive(Clone)]
+pub struct SessionKeys {
+    encoding: Arc<EncodingKey>,
+    decoding: Arc<DecodingKey>,
+    ttl: Duration,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Claims {
+    aud: String,
+    sub: String,
+    name: String,
+    exp: i64,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ShareClaims {
+    aud: String,
+    list: String,
+    scope: ShareScope,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    exp: Option<i64>,
+}
+
+impl SessionKeys {
+    pub fn new(secret: &[u8], ttl: Duration) -> Self {
+        Self {
+            encoding: Arc::new(EncodingKey::from_secret(secret)),
+            decoding: Arc::new(DecodingKey::from_secret(secret)),
+            ttl,
+        }
+    }
+
+    /// Uses the configured secret, or a random one so that sessions and share
+    /// tokens end when the backend restarts.
+    pub fn from_config(config: &ServerConfig) -> Self {
+        match &config.jwt_secret {
+            Some(secret) => Self::new(secret.as_bytes(), config.get_session_ttl()),
+            None => {
+                warn!("No JWT_SECRET supplied, sessions and share links end when the backend restarts");
+                let secret: [u8; 32] = rand::random();
+                Self::new(&secret, config.get_session_ttl())
+            }
+        }
+    }
+
+    pub fn issue(&self, user_id: &ObjectId, username: &str) -> Result<Session> {
+        let expires = chrono::Utc::now().timestamp() + self.ttl.as_secs() as i64;
+        let claims = Claims {
+            aud: SESSION_AUDIENCE.to_string(),
+            sub: user_id.to_hex(),
+            name: username.to_string(),
+            exp: expires,
+        };
+        Ok(Session::new(self.sign(&claims)?, username.to_string(), expires))
+    }
+
+    /// Returns the user id of a valid, unexpired session token.
+    pub fn verify(&self, token: &str) -> Result<ObjectId> {
+        let data = decode::<Claims>(token, &self.decoding, &validation(SESSION_AUDIENCE, true))
+            .map_err(|_| Error::Unauthorized)?;
+        ObjectId::with_string(&data.claims.sub).map_err(|_| Error::Unauthorized)
+    }
+
+    /// Mints a token for `list`, expiring at the unix timestamp `expires` if given.
+    pub fn issue_share(&self, list: &str, scope: ShareScope, expires: Option<i64>) -> Result<ShareLink> {
+        let claims = ShareClaims {
+            aud: SHARE_AUDIENCE.to_string(),
+            list: list.to_string(),
+            scope,
+            exp: expires,
+        };
+        Ok(ShareLink::new(self.sign(&claims)?, list.to_string(), scope, expires))
+    }
+
+    /// Returns the list and scope of a valid share token that hasn't expired.
+    pub fn verify_share(&self, token: &str) -> Result<(String, ShareScope)> {
+        let data = decode::<ShareClaims>(token, &self.decoding, &validation(SHARE_AUDIENCE, false))
+            .map_err(|_| Error::Unauthorized)?;
+        Ok((data.claims.list, data.claims.scope))
+    }
+
+    fn sign<T: Serialize>(&self, claims: &T) -> Result<String> {
+        encode(&Header::new(Algorithm::HS256), claims, &self.encoding)
+            .map_err(|e| Error::Internal(format!("could not sign token: {}", e)))
+    }
+}
+
+/// Expiry is always checked when present, `require_exp` makes it mandatory.
+fn validation(audience: &str, require_exp: bool) -> Validation {
+    let mut validation = Validation::new(Algorithm::HS256);
+    validation.set_audience(&[audience]);
+    if require_exp {
+        validation.set_required_spec_claims(&["aud", "exp"]);
+    } else {
+        validation.set_required_spec_claims(&["aud"]);
+    }
+    validation
+}
@@ -0,0 +1,196 @@
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use mongodb::bson::{document::Document, oid::ObjectId, Bson};
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, BTreeSet};
+use std::convert::TryFrom;
+use std::io::{Read, Write};
+
+use crate::model::{AuditEvent, Budget, Category, CategoryRule, Comment, ExchangeRates, List, PledgePool, PriceAlert, PricePoint, Product, PushSubscription, Reservation, SchemaMigration, Source, Stored, User, Wishlist, DEFAULT_LIST};
+use crate::repository::WishlistRepository;
+use crate::{Error, Result};
+
+const BACKUP_VERSION: u64 = 1;
+const MAX_REPORTED_PROBLEMS: usize = 10;
+
+/// Collections a backup contains, referenced collections first.
+const COLLECTIONS: [&str; 17] = [
+    User::COLLECTION,
+    List::COLLECTION,
+    Source::COLLECTION,
+    Category::COLLECTION,
+    CategoryRule::COLLECTION,
+    Product::COLLECTION,
+    PricePoint::COLLECTION,
+    Reservation::COLLECTION,
+    PledgePool::COLLECTION,
+    Comment::COLLECTION,
+    PushSubscription::COLLECTION,
+    PriceAlert::COLLECTION,
+    Budget::COLLECTION,
+    Wishlist::COLLECTION,
+    ExchangeRates::COLLECTION,
+    AuditEvent::COLLECTION,
+    // Restoring older data along with its migration state has it migrated on the next start.
+    SchemaMigration::COLLECTION,
+];
+
+/// All stored documents, written as gzipped MongoDB extended JSON so that
+/// ids and number types survive the round trip.
+pub struct Backup {
+    created: i32,
+    collections: BTreeMap<String, Vec<Document>>,
+}
+
+impl Backup {
+    pub async fn create(repo: &dyn WishlistRepository, created: i32) -> Result<Self> {
+        let mut collections = BTreeMap::new();
+        for name in COLLECTIONS.iter() {
+            collections.insert(name.to_string(), repo.dump_collection(name).await?);
+        }
+        Ok(Self { created, collections })
+    }
+
+    pub fn get_created(&self) -> i32 {
+        self.created
+    }
+
+    pub fn get_document_count(&self) -> usize {
+        self.collections.values().map(Vec::len).sum()
+    }
+
+    pub fn write<W: Write>(&self, writer: W) -> Result<()> {
+        let collections: serde_json::Map<String, Value> = self.collections.iter()
+            .map(|(name, documents)| {
+                let documents = documents.iter().map(|d| Bson::Document(d.clone()).into_canonical_extjson()).collect();
+                (name.clone(), Value::Array(documents))
+            })
+            .collect();
+        let backup = json!({"version": BACKUP_VERSION, "created": self.created, "collections": collections});
+        let mut encoder = GzEncoder::new(writer, Compression::default());
+        serde_json::to_writer(&mut encoder, &backup).map_err(|e| Error::Backup(format!("could not write backup: {}", e)))?;
+        encoder.finish().map_err(|e| Error::Backup(format!("could not write backup: {}", e)))?;
+        Ok(())
+    }
+
+    pub fn read<R: Read>(reader: R) -> Result<Self> {
+        let backup: Value = serde_json::from_reader(GzDecoder::new(reader))
+            .map_err(|e| Error::Backup(format!("could not read backup: {}", e)))?;
+        match backup.get("version").and_then(Value::as_u64) {
+            Some(BACKUP_VERSION) => {}
+            Some(version) => return Err(Error::Backup(format!("unsupported backup version {}", version))),
+            None => return Err(Error::Backup("backup has no version".to_string())),
+        }
+        let created = backup.get("created")
+            .and_then(Value::as_i64)
+            .ok_or_else(|| Error::Backup("backup has no creation time".to_string()))?;
+        let mut collections = BTreeMap::new();
+        let stored = backup.get("collections")
+            .and_then(Value::as_object)
+            .ok_or_else(|| Error::Backup("backup has no collections".to_string()))?;
+        for (name, documents) in stored {
+            if !COLLECTIONS.contains(&name.as_str()) {
+                return Err(Error::Backup(format!("backup contains the unknown collection '{}'", name)));
+            }
+            let documents = documents.as_array()
+                .ok_or_else(|| Error::Backup(format!("collection '{}' is not an array", name)))?
+                .iter()
+                .map(|document| match Bson::try_from(document.clone()) {
+                    Ok(Bson::Document(document)) => Ok(document),
+                    Ok(_) => Err(Error::Backup(format!("collection '{}' contains a value that is not a document", name))),
+                    Err(e) => Err(Error::Backup(format!("collection '{}' contains an invalid document: {}", name, e))),
+                })
+                .collect::<Result<Vec<Document>>>()?;
+            collections.insert(name.clone(), documents);
+        }
+        Ok(Self { created: created as i32, collections })
+    }
+
+    /// Checks that every document has a unique id and that all ids the
+    /// documents reference exist in the backup.
+    pub fn check_integrity(&self) -> Result<()> {
+        let mut problems = Vec::new();
+        let mut ids = BTreeMap::new();
+        for name in COLLECTIONS.iter() {
+            let mut seen = BTreeSet::new();
+            for document in self.get_documents(name) {
+                match document.get("_id") {
+                    Some(Bson::ObjectId(id)) if !seen.insert(id.clone()) => problems.push(format!("{} {} is stored twice", name, id)),
+                    Some(Bson::ObjectId(_)) => {}
+                    _ => problems.push(format!("{} has a document without an object id", name)),
+                }
+            }
+            ids.insert(*name, seen);
+        }
+
+        let slugs: BTreeSet<&str> = self.get_documents(List::COLLECTION).iter().filter_map(|l| l.get_str("slug").ok()).collect();
+        let references = [
+            (List::COLLECTION, "owner", User::COLLECTION),
+            (Category::COLLECTION, "parent", Category::COLLECTION),
+            (CategoryRule::COLLECTION, "category", Category::COLLECTION),
+            (Product::COLLECTION, "source", Source::COLLECTION),
+            (Product::COLLECTION, "category", Category::COLLECTION),
+            (PricePoint::COLLECTION, "product", Product::COLLECTION),
+            (Reservation::COLLECTION, "product", Product::COLLECTION),
+            (PriceAlert::COLLECTION, "product", Product::COLLECTION),
+            (PledgePool::COLLECTION, "product", Product::COLLECTION),
+            (Comment::COLLECTION, "product", Product::COLLECTION),
+            (Comment::COLLECTION, "author", User::COLLECTION),
+            (Wishlist::COLLECTION, "products", Product::COLLECTION),
+        ];
+        for (name, field, target) in references.iter() {
+            for document in self.get_documents(name) {
+                let referenced = match document.get(field) {
+                    None | Some(Bson::Null) => Vec::new(),
+                    Some(Bson::Array(values)) => values.iter().collect(),
+                    Some(value) => vec![value],
+                };
+                for value in referenced {
+                    match value {
+                        Bson::ObjectId(id) if ids[target].contains(id) => {}
+                        Bson::ObjectId(id) => problems.push(format!("{} {} references the missing {} {}", name, describe(document), target, id)),
+                        _ => problems.push(format!("{} {} has an invalid '{}'", name, describe(document), field)),
+                    }
+                }
+            }
+        }
+        for name in [Product::COLLECTION, Wishlist::COLLECTION].iter() {
+            for document in self.get_documents(name) {
+                match document.get_str("list") {
+                    Ok(slug) if slug != DEFAULT_LIST && !slugs.contains(slug) => {
+                        problems.push(format!("{} {} belongs to the missing list '{}'", name, describe(document), slug))
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        if problems.is_empty() {
+            return Ok(());
+        }
+        let mut message = problems.iter().take(MAX_REPORTED_PROBLEMS).cloned().collect::<Vec<_>>().join("; ");
+        if problems.len() > MAX_REPORTED_PROBLEMS {
+            message.push_str(&format!("; and {} more problems", problems.len() - MAX_REPORTED_PROBLEMS));
+        }
+        Err(Error::Backup(format!("backup is inconsistent: {}", message)))
+    }
+
+    /// Replaces all stored documents with the ones of the backup, once it
+    /// passed the integrity check. Collections missing in the backup are emptied.
+    pub async fn restore(&self, repo: &dyn WishlistRepository) -> Result<()> {
+        self.check_integrity()?;
+        for name in COLLECTIONS.iter() {
+            repo.replace_collection(name, self.get_documents(name).to_vec()).await?;
+        }
+        Ok(())
+    }
+
+    fn get_documents(&self, collection: &str) -> &[Document] {
+        self.collections.get(collection).map(Vec::as_slice).unwrap_or_default()
+    }
+}
+
+fn describe(document: &Document) -> String {
+    document.get_object_id("_id").map(ObjectId::to_hex).unwrap_or_else(|_| "without id".to_string())
+}
@@ -1,26 +1,66 @@
+#![recursion_limit = "512"]
+
 extern crate chrono;
 #[macro_use]
-extern crate log;
+extern crate tracing;
 extern crate dotenv;
 extern crate log4rs;
 
 extern crate wishlist;
 
-use log::{LevelFilter};
+use log::LevelFilter;
 use log4rs::append::file::FileAppender;
-use log4rs::config::{Appender, Config, Root};
+use log4rs::config::{Appender, Config as Log4rsConfig, Logger, Root};
+use log4rs::encode::json::JsonEncoder;
 use log4rs::encode::pattern::PatternEncoder;
-use std::env;
+use log4rs::encode::Encode;
+use std::fs::File;
 use std::net::SocketAddr;
+use std::path::PathBuf;
 use std::sync::Arc;
-use mongodb::Client;
+use mongodb::{options::ClientOptions, Client};
+use tokio::signal::unix::{signal, SignalKind};
+use tokio::sync::oneshot;
+use tokio::time::timeout;
+
+use wishlist::{check_consistency, init_tracing, ApiKeys, Backup, CachedRepository, ChangeWatcher, CheckLinksJob, Config, DeduplicateProductsJob, EnrichProductsJob, Enricher, FlushViewsJob, ImportSteamWishlistJob, JobsConfig, LogConfig, LogFormat, Maintenance, Migrator, MongoRepository, Notifier, PoliteClient, QueryTracer, PruneSnapshotsJob, RefreshExchangeRatesJob, RefreshPricesJob, ResilientRepository, RetentionPolicy, RunScraperJob, Scheduler, Scraper, SendEmailDigestJob, SteamClient, TlsTerminator, ViewCounter, WishlistRepository, update_archived_products};
+
+const USAGE: &str = "usage: app [dump --out FILE.json.gz | restore --in FILE.json.gz | migrate [--dry-run] | check [--fix]]";
+
+/// What the binary was started to do, serving the API unless a subcommand is given.
+enum Command {
+    Serve,
+    Dump(PathBuf),
+    Restore(PathBuf),
+    /// Applies the pending migrations, or only lists them with `--dry-run`.
+    Migrate { dry_run: bool },
+    /// Reports dangling references and duplicate items, repairing the references with `--fix`.
+    Check { fix: bool },
+}
 
 #[tokio::main]
 async fn main() {
-    if init_logger() == false {
+    let command = match parse_command(std::env::args().skip(1).collect()) {
+        Some(c) => c,
+        None => {
+            eprintln!("{}", USAGE);
+            std::process::exit(2);
+        }
+    };
+    // The config can come from the dotenv file and decides how to log, so both
+    // are loaded before the logger and their outcome is logged afterwards.
+    let dotenv = dotenv::dotenv();
+    let config = match Config::load() {
+        Ok(c) => c,
+        Err(e) => {
+            println!("Could not load config: {}", e);
+            return;
+        }
+    };
+    if !init_logger(&config.log) {
         return;
     }
-    match dotenv::dotenv() {
+    match dotenv {
         Ok(_) => {
             info!("Loaded dotenv!");
         }
@@ -29,40 +69,168 @@ async fn main() {
         }
     }
 
-    let server_addr = match env::var("BACKEND_ADDRESS") {
-        Ok(server_addr) => server_addr,
-        Err(_) => {
-            info!("No BACKEND_ADDRESS supplied, using default");
-            String::from("0.0.0.0:8080")
+    let socket_addr: SocketAddr = match config.server.address.parse() {
+        Ok(a) => a,
+        Err(e) => {
+            error!("Could not parse server addr '{}': {}", config.server.address, e);
+            return;
         }
     };
 
-    let socket_addr: SocketAddr = match server_addr.parse() {
-        Ok(a) => a,
+    info!("Server address: {}", config.server.address);
+    let tls = match TlsTerminator::from_config(&config.server.tls) {
+        Ok(tls) => tls,
         Err(e) => {
-            error!("Could not parse server addr '{}': {}", server_addr, e);
+            error!("Could not set up TLS: {}", e);
             return;
         }
     };
+    info!("TLS: {}", if tls.is_some() { "enabled" } else { "disabled" });
 
-    info!("Server address: {}", server_addr);
-
-    let mongo_url = match env::var("DATABASE_URL") {
-        Ok(url) => url,
-        Err(_) => {
+    let mongo_url = match &config.database.url {
+        Some(url) => url,
+        None => {
             error!("DATABASE_URL for MongoDB not set");
             return; 
         }
     };
-    let mongo_client = match Client::with_uri_str(&mongo_url).await {
-        Ok(c) => Arc::new(c),
+    let mut mongo_options = match ClientOptions::parse(mongo_url).await {
+        Ok(o) => o,
+        Err(e) => {
+            error!("{}", e);
+            return;
+        }
+    };
+    mongo_options.command_event_handler = Some(Arc::new(QueryTracer::new(&config.log)));
+    mongo_options.server_selection_timeout = Some(config.database.get_server_selection_timeout());
+    let mongo_client = match Client::with_options(mongo_options) {
+        Ok(c) => c,
         Err(e) => {
             error!("{}", e);
             return;
         }
     };
+    info!("Database: {}", config.database.name);
+
+    match command {
+        Command::Serve => {}
+        Command::Dump(path) => {
+            let repository = MongoRepository::new(mongo_client, &config.database.name);
+            exit_with(dump(&repository, &path).await.map(|count| format!("Dumped {} documents to {}", count, path.display())));
+        }
+        Command::Restore(path) => {
+            let repository = MongoRepository::new(mongo_client, &config.database.name);
+            exit_with(restore(&repository, &path).await.map(|count| format!("Restored {} documents from {}", count, path.display())));
+        }
+        Command::Migrate { dry_run } => {
+            let migrator = Migrator::new(mongo_client.database(&config.database.name));
+            exit_with(migrate(&migrator, dry_run).await);
+        }
+        Command::Check { fix } => {
+            let repository = MongoRepository::new(mongo_client, &config.database.name);
+            exit_with(check(&repository, fix).await);
+        }
+    }
+
+    let migrator = Migrator::new(mongo_client.database(&config.database.name));
+    if config.database.migrate_on_startup {
+        match migrator.run(false).await {
+            Ok(results) => {
+                for result in results {
+                    info!("Applied {}", result);
+                }
+            }
+            Err(e) => {
+                error!("Could not migrate the database: {}", e);
+                return;
+            }
+        }
+    } else {
+        match migrator.run(true).await {
+            Ok(results) if !results.is_empty() => warn!("{} migrations are pending, run `app migrate` to apply them", results.len()),
+            Ok(_) => {}
+            Err(e) => warn!("Could not check for pending migrations: {}", e),
+        }
+    }
+
+    let api_keys = ApiKeys::from_config(&config.server);
+    if api_keys.is_empty() {
+        warn!("No API_KEY supplied, categories, jobs and unowned lists can't be changed");
+    } else {
+        info!("API keys: {}", api_keys.len());
+    }
+
+    info!("Cache TTL: {}s", config.cache.ttl);
+    let rate_limit = &config.server.rate_limit;
+    if rate_limit.requests_per_second > 0. {
+        info!("Rate limit: {}/s, burst {}", rate_limit.requests_per_second, rate_limit.burst);
+    } else {
+        info!("Rate limit: disabled");
+    }
+
+    let maintenance = Maintenance::new(&config.server);
+    if maintenance.is_read_only() {
+        warn!("Maintenance mode: enabled, the API is read-only");
+    }
+    let database = mongo_client.database(&config.database.name);
+    let cache = Arc::new(CachedRepository::new(
+        Arc::new(ResilientRepository::new(
+            Arc::new(MongoRepository::new(mongo_client, &config.database.name)),
+            &config.database,
+            maintenance.clone(),
+        )),
+        config.cache.get_ttl(),
+    ));
+    let repository: Arc<dyn WishlistRepository> = cache.clone();
+    info!("Price drop webhooks: {}", config.notify.webhooks.len());
+    let notifier = Notifier::new(config.notify.webhooks.clone()).with_telegram(&config.notify.telegram);
+    info!("Telegram snapshot summaries: {}", if notifier.has_telegram() { "enabled" } else { "disabled" });
+    let notifier = match notifier.with_email(&config.notify.email) {
+        Ok(notifier) => notifier,
+        Err(e) => {
+            error!("Could not set up email notifications: {}", e);
+            return;
+        }
+    };
+    info!("Email notifications: {}", if notifier.has_email() { format!("enabled, to {}", config.notify.email.to.join(", ")) } else { "disabled".to_string() });
+    let notifier = match notifier.with_push(&config.notify.push, repository.clone()) {
+        Ok(notifier) => notifier,
+        Err(e) => {
+            error!("Could not set up push notifications: {}", e);
+            return;
+        }
+    };
+    info!("Push notifications: {}", if notifier.get_push_key().is_some() { "enabled" } else { "disabled" });
+    if config.database.watch_changes {
+        ChangeWatcher::new(database, cache, notifier.clone()).spawn();
+    } else {
+        info!("Change streams: disabled, direct writes to the database show up after the cache TTL");
+    }
+
+    info!("Swagger UI: {}", if config.server.swagger_ui { "enabled at /api/v1/docs" } else { "disabled" });
+
+    // Backfills the archived flag of products stored before it existed, and
+    // catches up on snapshots written while the backend was down.
+    match update_archived_products(repository.as_ref()).await {
+        Ok(updated) => info!("Updated archived flag of {} products", updated),
+        Err(e) => {
+            error!("Could not update archived products: {}", e);
+            return;
+        }
+    }
 
-    let routes = match wishlist::create_routes(mongo_client).await {
+    let views = ViewCounter::new();
+    let scheduler = Scheduler::new(repository.clone(), maintenance.clone());
+    let scraper = Scraper::new(config.jobs.scrape_command.clone(), config.jobs.scrape_user_agent.clone(), notifier.clone());
+    let client = PoliteClient::new(&config.jobs.scrape_user_agent);
+    let enricher = Enricher::new(config.jobs.open_library_url.clone(), config.jobs.ean_lookup_url.clone());
+    let steam = SteamClient::new(config.jobs.steam_api_url.clone(), config.jobs.steam_store_url.clone(), config.jobs.steam_country.clone());
+    if let Err(e) = schedule_jobs(&scheduler, &scraper, &client, &enricher, &steam, &notifier, &views, &config.jobs) {
+        error!("Could not schedule jobs: {}", e);
+        return;
+    }
+
+    let routes = match wishlist::create_routes(repository.clone(), &config.server, scheduler.clone(), scraper, client, enricher, steam, notifier, views.clone(), maintenance, RetentionPolicy::from_config(&config.jobs)).await {
         Ok(r) => r,
         Err(e) => {
             error!("Could not create routes: {}", e);
@@ -71,14 +239,209 @@ async fn main() {
     };
     info!("Created server routes");
 
-    warp::serve(routes).run(socket_addr).await;
+    let (shutdown_sender, shutdown_receiver) = oneshot::channel::<()>();
+    let shutdown = async move {
+        shutdown_receiver.await.ok();
+    };
+    let server = match tls {
+        Some(tls) => {
+            reload_tls_on_hangup(tls.clone());
+            match wishlist::serve_tls(routes, socket_addr, tls, shutdown).await {
+                Ok(server) => tokio::spawn(server),
+                Err(e) => {
+                    error!("Could not serve TLS: {}", e);
+                    return;
+                }
+            }
+        }
+        None => match warp::serve(routes).try_bind_with_graceful_shutdown(socket_addr, shutdown) {
+            Ok((_, server)) => tokio::spawn(server),
+            Err(e) => {
+                error!("Could not bind to {}: {}", socket_addr, e);
+                return;
+            }
+        },
+    };
+
+    wait_for_signal().await;
+    info!("Shutting down, waiting up to {}s for requests and jobs", config.server.shutdown_timeout);
+    let _ = shutdown_sender.send(());
+    let drain = async {
+        if let Err(e) = server.await {
+            error!("Server task failed: {}", e);
+        }
+        scheduler.shutdown().await;
+        match views.flush(repository.as_ref()).await {
+            Ok(added) => info!("Stored {} pending product views", added),
+            Err(e) => error!("Could not store pending product views: {}", e),
+        }
+    };
+    match timeout(config.server.get_shutdown_timeout(), drain).await {
+        Ok(()) => info!("Drained all requests and jobs"),
+        Err(_) => warn!("Shutdown timed out, aborting remaining requests and jobs"),
+    }
+    // Dropping the last handle to the repository closes the database connections.
+    drop(scheduler);
+    drop(repository);
+    info!("Shut down");
+}
+
+fn parse_command(args: Vec<String>) -> Option<Command> {
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    match args.as_slice() {
+        [] => Some(Command::Serve),
+        ["dump", "--out", path] => Some(Command::Dump(PathBuf::from(path))),
+        ["restore", "--in", path] => Some(Command::Restore(PathBuf::from(path))),
+        ["migrate"] => Some(Command::Migrate { dry_run: false }),
+        ["migrate", "--dry-run"] => Some(Command::Migrate { dry_run: true }),
+        ["check"] => Some(Command::Check { fix: false }),
+        ["check", "--fix"] => Some(Command::Check { fix: true }),
+        _ => None,
+    }
+}
+
+/// Writes all collections to `path`, returns the number of documents written.
+async fn dump(repository: &dyn WishlistRepository, path: &PathBuf) -> wishlist::Result<usize> {
+    let backup = Backup::create(repository, chrono::Utc::now().timestamp() as i32).await?;
+    let file = File::create(path).map_err(|e| wishlist::Error::Backup(format!("could not create {}: {}", path.display(), e)))?;
+    backup.write(file)?;
+    Ok(backup.get_document_count())
+}
+
+/// Replaces all collections with the backup at `path`, returns the number of documents restored.
+async fn restore(repository: &dyn WishlistRepository, path: &PathBuf) -> wishlist::Result<usize> {
+    let file = File::open(path).map_err(|e| wishlist::Error::Backup(format!("could not open {}: {}", path.display(), e)))?;
+    let backup = Backup::read(file)?;
+    info!("Restoring backup created at {}", backup.get_created());
+    backup.restore(repository).await?;
+    Ok(backup.get_document_count())
+}
+
+/// Applies the pending migrations, or lists what they would change on `dry_run`.
+async fn migrate(migrator: &Migrator, dry_run: bool) -> wishlist::Result<String> {
+    let results = migrator.run(dry_run).await?;
+    if results.is_empty() {
+        return Ok("No migrations are pending".to_string());
+    }
+    let action = if dry_run { "Would apply" } else { "Applied" };
+    Ok(results.iter().map(|result| format!("{} {}", action, result)).collect::<Vec<_>>().join("\n"))
+}
+
+/// Checks the stored data for consistency, returns the report as JSON and a summary.
+async fn check(repository: &dyn WishlistRepository, fix: bool) -> wishlist::Result<String> {
+    let report = check_consistency(repository, fix).await?;
+    let details = serde_json::to_string_pretty(&report)
+        .map_err(|e| wishlist::Error::Internal(format!("could not serialize report: {}", e)))?;
+    Ok(format!("{}\nFound {} consistency problems, fixed {}", details, report.get_problem_count(), report.get_fixed_count()))
+}
+
+/// Reports the outcome of a subcommand on the console and in the log, then exits.
+fn exit_with(result: wishlist::Result<String>) -> ! {
+    match result {
+        Ok(message) => {
+            info!("{}", message);
+            println!("{}", message);
+            std::process::exit(0);
+        }
+        Err(e) => {
+            error!("{}", e);
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Resolves on the first SIGINT or SIGTERM.
+async fn wait_for_signal() {
+    let mut terminate = match signal(SignalKind::terminate()) {
+        Ok(s) => s,
+        Err(e) => {
+            error!("Could not listen for SIGTERM: {}", e);
+            let _ = tokio::signal::ctrl_c().await;
+            return;
+        }
+    };
+    tokio::select! {
+        _ = tokio::signal::ctrl_c() => info!("Received SIGINT"),
+        _ = terminate.recv() => info!("Received SIGTERM"),
+    }
+}
+
+/// Reads the TLS certificate and key again on every `SIGHUP`, keeping the
+/// current ones if that fails.
+fn reload_tls_on_hangup(tls: TlsTerminator) {
+    let mut hangup = match signal(SignalKind::hangup()) {
+        Ok(s) => s,
+        Err(e) => {
+            error!("Could not listen for SIGHUP, the TLS certificate won't be reloaded: {}", e);
+            return;
+        }
+    };
+    tokio::spawn(async move {
+        while hangup.recv().await.is_some() {
+            match tls.reload() {
+                Ok(()) => info!("Reloaded the TLS certificate and key"),
+                Err(e) => error!("Could not reload the TLS certificate, keeping the current one: {}", e),
+            }
+        }
+    });
 }
 
-fn init_logger() -> bool {
+/// Schedules the jobs that have a schedule configured.
+#[allow(clippy::too_many_arguments)]
+fn schedule_jobs(scheduler: &Scheduler, scraper: &Scraper, client: &PoliteClient, enricher: &Enricher, steam: &SteamClient, notifier: &Notifier, views: &ViewCounter, config: &JobsConfig) -> wishlist::Result<()> {
+    if let Some(schedule) = &config.scrape_schedule {
+        match &config.scrape_command {
+            Some(_) => scheduler.schedule(schedule, RunScraperJob::new(scraper.clone()))?,
+            None => warn!("Scrape schedule set without a scrape command, not scheduling scraper"),
+        }
+    }
+    if let Some(schedule) = &config.refresh_prices_schedule {
+        scheduler.schedule(schedule, RefreshPricesJob::new(notifier.clone()))?;
+    }
+    if let Some(schedule) = &config.prune_snapshots_schedule {
+        scheduler.schedule(schedule, PruneSnapshotsJob::new(RetentionPolicy::from_config(config)))?;
+    }
+    if let Some(schedule) = &config.exchange_rates_schedule {
+        scheduler.schedule(schedule, RefreshExchangeRatesJob::new(config.exchange_rates_url.clone()))?;
+    }
+    if let Some(schedule) = &config.email_digest_schedule {
+        match notifier.has_email() {
+            true => scheduler.schedule(schedule, SendEmailDigestJob::new(notifier.clone(), config.get_email_digest_period()))?,
+            false => warn!("Email digest schedule set without email configured, not scheduling digest"),
+        }
+    }
+    match &config.flush_views_schedule {
+        Some(schedule) => scheduler.schedule(schedule, FlushViewsJob::new(views.clone()))?,
+        None => warn!("No flush views schedule set, product views are only stored on shutdown"),
+    }
+    if let Some(schedule) = &config.deduplicate_schedule {
+        scheduler.schedule(schedule, DeduplicateProductsJob)?;
+    }
+    if let Some(schedule) = &config.check_links_schedule {
+        scheduler.schedule(schedule, CheckLinksJob::new(client.clone()))?;
+    }
+    if let Some(schedule) = &config.enrich_schedule {
+        scheduler.schedule(schedule, EnrichProductsJob::new(enricher.clone()))?;
+    }
+    if let Some(schedule) = &config.steam_schedule {
+        match &config.steam_id {
+            Some(steam_id) => scheduler.schedule(schedule, ImportSteamWishlistJob::new(steam.clone(), notifier.clone(), steam_id.clone(), config.steam_list.clone()))?,
+            None => warn!("Steam schedule set without a Steam id, not scheduling the Steam import"),
+        }
+    }
+    Ok(())
+}
+
+fn init_logger(config: &LogConfig) -> bool {
+    let encoder: Box<dyn Encode> = match config.format {
+        LogFormat::Text => Box::new(PatternEncoder::new(
+            "{d(%Y-%m-%d %H:%M:%S)} {h({l})} {X(request_id)(-)} - {m}\n",
+        )),
+        LogFormat::Json => Box::new(JsonEncoder::new()),
+    };
     let logfile = match FileAppender::builder()
-        .encoder(Box::new(PatternEncoder::new(
-            "{d(%Y-%m-%d %H:%M:%S)} {h({l})} - {m}\n",
-        )))
+        .encoder(encoder)
         .build("log/output.log")
     {
         Ok(lf) => lf,
@@ -88,9 +451,11 @@ fn init_logger() -> bool {
         }
     };
 
-    let config = match Config::builder()
+    let log_config = match Log4rsConfig::builder()
         .appender(Appender::builder().build("logfile", Box::new(logfile)))
-        .build(Root::builder().appender("logfile").build(LevelFilter::Info))
+        // The access log line already covers what warp logs for each request.
+        .logger(Logger::builder().build("warp::filters::trace", LevelFilter::Warn))
+        .build(Root::builder().appender("logfile").build(config.level))
     {
         Ok(c) => c,
         Err(e) => {
@@ -99,9 +464,13 @@ fn init_logger() -> bool {
         }
     };
 
-    if let Err(e) = log4rs::init_config(config) {
+    if let Err(e) = log4rs::init_config(log_config) {
         println!("Could not init logging config: {}", e);
         return false;
     }
+    if let Err(e) = init_tracing(config) {
+        println!("Could not init tracing: {}", e);
+        return false;
+    }
     true
 }
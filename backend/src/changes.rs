@@ -0,0 +1,143 @@
+use mongodb::bson::{doc, document::Document, Bson};
+use mongodb::error::{Error as MongoError, ErrorKind};
+use mongodb::Database;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::stream::StreamExt;
+
+use crate::auth::can_discover;
+use crate::model::{Category, List, Product, Stored, Wishlist, WishlistEvent, DEFAULT_LIST};
+use crate::notify::Notifier;
+use crate::repository::{CachedRepository, WishlistRepository};
+use crate::Result;
+
+/// Error code of a change stream opened on a standalone server.
+const NO_REPLICA_SET: i32 = 40573;
+/// Wait before reopening a change stream that failed.
+const REOPEN_DELAY: Duration = Duration::from_secs(5);
+const WATCHED_COLLECTIONS: [&str; 3] = [Product::COLLECTION, Wishlist::COLLECTION, Category::COLLECTION];
+
+/// Follows the change streams of the products, snapshots and categories, so
+/// the cache and the event stream also pick up writes made directly in the
+/// database, e.g. by a script.
+///
+/// Change streams need a replica set. On a standalone server the watcher
+/// logs a warning and stops, leaving direct writes to the cache TTL.
+pub struct ChangeWatcher {
+    database: Database,
+    cache: Arc<CachedRepository>,
+    notifier: Notifier,
+}
+
+impl ChangeWatcher {
+    pub fn new(database: Database, cache: Arc<CachedRepository>, notifier: Notifier) -> Self {
+        Self { database, cache, notifier }
+    }
+
+    /// Watches every collection in a task of its own.
+    pub fn spawn(self) {
+        let watcher = Arc::new(self);
+        for collection in WATCHED_COLLECTIONS.iter() {
+            let watcher = watcher.clone();
+            tokio::spawn(async move { watcher.watch(collection).await });
+        }
+    }
+
+    /// Follows the change stream of `collection`, reopening it after the last
+    /// change seen when it fails.
+    async fn watch(&self, collection: &str) {
+        let mut resume_token: Option<Bson> = None;
+        loop {
+            let mut stage = doc! {"fullDocument": "updateLookup"};
+            if let Some(token) = &resume_token {
+                stage.insert("resumeAfter", token.clone());
+            }
+            let mut changes = match self.database.collection(collection).aggregate(vec![doc! {"$changeStream": stage}], None).await {
+                Ok(changes) => changes,
+                Err(e) if is_standalone(&e) => {
+                    warn!("Not watching '{}' for changes, change streams need a replica set", collection);
+                    return;
+                }
+                Err(e) => {
+                    warn!("Could not watch '{}' for changes, retrying in {}s: {}", collection, REOPEN_DELAY.as_secs(), e);
+                    tokio::time::delay_for(REOPEN_DELAY).await;
+                    continue;
+                }
+            };
+            info!("Watching '{}' for changes", collection);
+            while let Some(change) = changes.next().await {
+                match change {
+                    Ok(change) => {
+                        resume_token = change.get("_id").cloned();
+                        self.apply(collection, &change).await;
+                    }
+                    Err(e) => {
+                        warn!("Change stream of '{}' failed, reopening it in {}s: {}", collection, REOPEN_DELAY.as_secs(), e);
+                        break;
+                    }
+                }
+            }
+            tokio::time::delay_for(REOPEN_DELAY).await;
+        }
+    }
+
+    async fn apply(&self, collection: &str, change: &Document) {
+        debug!("'{}' changed: {}", collection, change.get_str("operationType").unwrap_or("-"));
+        self.cache.invalidate(collection);
+        if !self.notifier.has_subscribers() {
+            return;
+        }
+        if let Err(e) = self.publish(collection, change).await {
+            warn!("Could not publish a change of '{}': {}", collection, e);
+        }
+    }
+
+    /// Publishes the change on the event stream. The event stream is public,
+    /// so changes of private lists are left out, like deleted documents whose
+    /// list is unknown.
+    async fn publish(&self, collection: &str, change: &Document) -> Result<()> {
+        let id = change.get_document("documentKey").ok().and_then(|key| key.get("_id"));
+        let document = change.get_document("fullDocument").ok();
+        match collection {
+            Product::COLLECTION => {
+                let id = match id.and_then(Bson::as_object_id) {
+                    Some(id) => id,
+                    None => return Ok(()),
+                };
+                if let Some(product) = self.cache.get_product_by_id(id).await? {
+                    if self.is_public(product.get_list()).await? {
+                        self.notifier.publish(WishlistEvent::ProductChanged { product });
+                    }
+                }
+            }
+            Wishlist::COLLECTION => {
+                let list = match document {
+                    Some(wishlist) => wishlist.get_str("list").unwrap_or(DEFAULT_LIST),
+                    None => return Ok(()),
+                };
+                if self.is_public(list).await? {
+                    self.notifier.publish(WishlistEvent::SnapshotChanged { list: list.to_string() });
+                }
+            }
+            _ => {
+                if let Some(id) = id.and_then(Bson::as_object_id) {
+                    self.notifier.publish(WishlistEvent::CategoryChanged { category_id: id.to_hex() });
+                }
+            }
+        }
+        Ok(())
+    }
+
+    async fn is_public(&self, slug: &str) -> Result<bool> {
+        let list = if slug == DEFAULT_LIST {
+            Some(List::default_list())
+        } else {
+            self.cache.get_list_by_slug(slug).await?
+        };
+        Ok(list.map(|list| can_discover(None, &list)).unwrap_or(false))
+    }
+}
+
+fn is_standalone(error: &MongoError) -> bool {
+    matches!(error.kind.as_ref(), ErrorKind::CommandError(e) if e.code == NO_REPLICA_SET)
+}
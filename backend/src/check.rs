@@ -0,0 +1,107 @@
+use mongodb::bson::{doc, document::Document, oid::ObjectId, Bson};
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::handler::resolve_source_for_url;
+use crate::model::{Category, ConsistencyReport, DuplicateItem, Orphan, Product, Source, Stored, Wishlist, DEFAULT_LIST};
+use crate::repository::WishlistRepository;
+use crate::{Error, Result};
+
+/// Scans the stored documents for products referencing missing sources or
+/// categories, snapshots referencing missing products and item ids stored
+/// twice for a source on a list, which otherwise only show up as failing requests.
+///
+/// With `fix`, products get the source named after the host of their url
+/// and lose a missing category, and snapshots drop the missing products.
+/// Duplicates are only reported, as it takes a person to tell which one to keep.
+pub async fn check_consistency(repo: &dyn WishlistRepository, fix: bool) -> Result<ConsistencyReport> {
+    let sources = get_ids(&repo.dump_collection(Source::COLLECTION).await?);
+    let categories = get_ids(&repo.dump_collection(Category::COLLECTION).await?);
+    let products = repo.dump_collection(Product::COLLECTION).await?;
+    let product_ids = get_ids(&products);
+
+    let mut report = ConsistencyReport::default();
+    let mut items: BTreeMap<(&str, Option<String>, &str), Vec<ObjectId>> = BTreeMap::new();
+    for product in products.iter() {
+        let id = match product.get_object_id("_id") {
+            Ok(id) => id.clone(),
+            Err(_) => continue,
+        };
+        let list = get_list(product);
+        match product.get("source") {
+            Some(Bson::ObjectId(source)) if sources.contains(source) => {}
+            source => {
+                let missing = match source {
+                    Some(Bson::ObjectId(source)) => vec![source.clone()],
+                    _ => Vec::new(),
+                };
+                let fixed = fix && fix_source(repo, &id, product).await?;
+                report.add_missing_source(Orphan::new(id.clone(), list, missing, fixed));
+            }
+        }
+        if let Some(Bson::ObjectId(category)) = product.get("category") {
+            if !categories.contains(category) {
+                let fixed = fix && unless_conflict(repo.update_product(&id, doc! {"category": Bson::Null}).await)?;
+                report.add_missing_category(Orphan::new(id.clone(), list, vec![category.clone()], fixed));
+            }
+        }
+        if let Ok(item_id) = product.get_str("item_id") {
+            let source = product.get_object_id("source").ok().map(ObjectId::to_hex);
+            items.entry((list, source, item_id)).or_default().push(id);
+        }
+    }
+    for ((list, _, item_id), ids) in items.into_iter().filter(|(_, ids)| ids.len() > 1) {
+        report.add_duplicate(DuplicateItem::new(list, item_id, ids));
+    }
+
+    for snapshot in repo.dump_collection(Wishlist::COLLECTION).await? {
+        let id = match snapshot.get_object_id("_id") {
+            Ok(id) => id.clone(),
+            Err(_) => continue,
+        };
+        let missing: Vec<ObjectId> = snapshot.get_array("products")
+            .map(|ids| ids.iter()
+                .filter_map(|id| match id {
+                    Bson::ObjectId(id) if !product_ids.contains(id) => Some(id.clone()),
+                    _ => None,
+                })
+                .collect())
+            .unwrap_or_default();
+        if !missing.is_empty() {
+            let fixed = fix && repo.remove_wishlist_products(&id, &missing).await?;
+            report.add_missing_products(Orphan::new(id, get_list(&snapshot), missing, fixed));
+        }
+    }
+    Ok(report)
+}
+
+/// Points a product at the source named after the host of its url, returns
+/// false if it has no usable url.
+async fn fix_source(repo: &dyn WishlistRepository, id: &ObjectId, product: &Document) -> Result<bool> {
+    let url = match product.get_str("url") {
+        Ok(url) if url.contains("://") => url,
+        _ => return Ok(false),
+    };
+    let source = match resolve_source_for_url(repo, url).await {
+        Ok(source) => source,
+        Err(Error::Validation(_)) => return Ok(false),
+        Err(e) => return Err(e),
+    };
+    unless_conflict(repo.update_product(id, doc! {"source": source}).await)
+}
+
+/// Treats a fix clashing with a duplicate item as not applied, the duplicate
+/// is reported on its own.
+fn unless_conflict(result: Result<bool>) -> Result<bool> {
+    match result {
+        Err(Error::Conflict(_)) => Ok(false),
+        result => result,
+    }
+}
+
+fn get_ids(documents: &[Document]) -> BTreeSet<ObjectId> {
+    documents.iter().filter_map(|d| d.get_object_id("_id").ok().cloned()).collect()
+}
+
+fn get_list(document: &Document) -> &str {
+    document.get_str("list").unwrap_or(DEFAULT_LIST)
+}
@@ -0,0 +1,70 @@
+use mongodb::bson::{doc, oid::ObjectId};
+use regex::Regex;
+
+use crate::model::CategoryRule;
+use crate::query::SortQuery;
+use crate::repository::WishlistRepository;
+use crate::Result;
+
+/// Compiled category rules, matching product names and descriptions.
+pub struct Classifier {
+    rules: Vec<(Regex, ObjectId)>,
+}
+
+impl Classifier {
+    /// Compiles `rules`, skipping stored rules whose pattern doesn't compile.
+    pub fn new(rules: &[CategoryRule]) -> Self {
+        let rules = rules.iter()
+            .filter_map(|rule| match rule.compile() {
+                Ok(regex) => Some((regex, rule.get_category_id().clone())),
+                Err(e) => {
+                    warn!("Skipping category rule '{}': {}", rule.get_pattern(), e);
+                    None
+                }
+            })
+            .collect();
+        Self { rules }
+    }
+
+    pub async fn load(repo: &dyn WishlistRepository) -> Result<Self> {
+        Ok(Self::new(&repo.get_rules().await?.into_value()))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// Returns the category of the first rule matching `name` or `description`.
+    pub fn classify(&self, name: &str, description: Option<&str>) -> Option<&ObjectId> {
+        self.rules.iter()
+            .find(|(regex, _)| regex.is_match(name) || description.map(|d| regex.is_match(d)).unwrap_or(false))
+            .map(|(_, category)| category)
+    }
+}
+
+/// Assigns categories to the uncategorized products by the category rules,
+/// returns the number of products categorized.
+pub async fn classify_uncategorized(repo: &dyn WishlistRepository) -> Result<u64> {
+    let classifier = Classifier::load(repo).await?;
+    if classifier.is_empty() {
+        return Ok(0);
+    }
+    let count = repo.count_products_by_category(None, None).await?;
+    if count == 0 {
+        return Ok(0);
+    }
+    let products = repo.get_products_by_category(None, None, &SortQuery::default(), 0, count).await?.into_value();
+    let mut classified = 0;
+    for product in products {
+        let (id, name) = match (product.get_id(), product.get_name()) {
+            (Some(id), Some(name)) => (id, name),
+            _ => continue,
+        };
+        if let Some(category) = classifier.classify(name, product.get_description()) {
+            if repo.update_product(id, doc! {"category": category.clone()}).await? {
+                classified += 1;
+            }
+        }
+    }
+    Ok(classified)
+}
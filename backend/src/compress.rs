@@ -0,0 +1,116 @@
+use flate2::write::GzEncoder;
+use std::convert::Infallible;
+use std::io::Write;
+use warp::http::{header, HeaderValue, Response};
+use warp::hyper::{body, Body};
+
+/// Smaller bodies aren't worth the time compressing them.
+const MIN_COMPRESS_SIZE: usize = 1024;
+const BROTLI_QUALITY: u32 = 5;
+const BROTLI_WINDOW: u32 = 22;
+const COMPRESSIBLE_TYPES: [&str; 6] = [
+    "application/json",
+    "application/msgpack",
+    "application/cbor",
+    "application/rss+xml",
+    "text/html",
+    "text/csv",
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Encoding {
+    Brotli,
+    Gzip,
+}
+
+/// Compresses JSON, MessagePack, CBOR, RSS and HTML responses with the best encoding the client
+/// accepts. Event streams are left alone, as their body never ends.
+pub async fn apply_compression(accept_encoding: Option<String>, response: Response<Body>) -> Result<Response<Body>, Infallible> {
+    if !is_compressible(&response) {
+        return Ok(response);
+    }
+    let (mut parts, body) = response.into_parts();
+    parts.headers.append(header::VARY, HeaderValue::from_static("accept-encoding"));
+    let encoding = match accept_encoding.as_deref().and_then(negotiate) {
+        Some(encoding) => encoding,
+        None => return Ok(Response::from_parts(parts, body)),
+    };
+
+    let bytes = match body::to_bytes(body).await {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            error!("Could not read response body for compression: {}", e);
+            return Ok(Response::from_parts(parts, Body::empty()));
+        }
+    };
+    if bytes.len() < MIN_COMPRESS_SIZE {
+        return Ok(Response::from_parts(parts, Body::from(bytes)));
+    }
+    match compress(&bytes, encoding) {
+        Ok(compressed) => {
+            parts.headers.insert(header::CONTENT_ENCODING, HeaderValue::from_static(encoding.name()));
+            parts.headers.remove(header::CONTENT_LENGTH);
+            Ok(Response::from_parts(parts, Body::from(compressed)))
+        }
+        Err(e) => {
+            error!("Could not compress response body: {}", e);
+            Ok(Response::from_parts(parts, Body::from(bytes)))
+        }
+    }
+}
+
+impl Encoding {
+    fn name(self) -> &'static str {
+        match self {
+            Encoding::Brotli => "br",
+            Encoding::Gzip => "gzip",
+        }
+    }
+}
+
+fn is_compressible(response: &Response<Body>) -> bool {
+    let content_type = response.headers().get(header::CONTENT_TYPE).map(HeaderValue::as_bytes).unwrap_or_default();
+    response.status().is_success()
+        && !response.headers().contains_key(header::CONTENT_ENCODING)
+        && COMPRESSIBLE_TYPES.iter().any(|t| content_type.starts_with(t.as_bytes()))
+}
+
+/// Picks the encoding with the highest `q` value, brotli wins ties.
+fn negotiate(accept_encoding: &str) -> Option<Encoding> {
+    let mut best: Option<(Encoding, f32)> = None;
+    for entry in accept_encoding.split(',') {
+        let mut params = entry.split(';');
+        let encoding = match params.next().unwrap_or_default().trim() {
+            "br" => Encoding::Brotli,
+            "gzip" => Encoding::Gzip,
+            _ => continue,
+        };
+        let quality = params
+            .find_map(|param| param.trim().strip_prefix("q="))
+            .and_then(|q| q.trim().parse().ok())
+            .unwrap_or(1.);
+        let better = match best {
+            Some((_, best_quality)) => quality > best_quality || (quality == best_quality && encoding == Encoding::Brotli),
+            None => true,
+        };
+        if quality > 0. && better {
+            best = Some((encoding, quality));
+        }
+    }
+    best.map(|(encoding, _)| encoding)
+}
+
+fn compress(bytes: &[u8], encoding: Encoding) -> std::io::Result<Vec<u8>> {
+    match encoding {
+        Encoding::Brotli => {
+            let mut writer = brotli::CompressorWriter::new(Vec::new(), 4096, BROTLI_QUALITY, BROTLI_WINDOW);
+            writer.write_all(bytes)?;
+            Ok(writer.into_inner())
+        }
+        Encoding::Gzip => {
+            let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::default());
+            encoder.write_all(bytes)?;
+            encoder.finish()
+        }
+    }
+}
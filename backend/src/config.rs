@@ -0,0 +1,649 @@
+use log::LevelFilter;
+use serde::Deserialize;
+use std::env;
+use std::fs;
+use std::io::ErrorKind;
+use std::str::FromStr;
+use std::time::Duration;
+
+use crate::auth::ApiKeyConfig;
+use crate::currency::ECB_RATES_URL;
+use crate::enrichment::{EAN_LOOKUP_URL, OPEN_LIBRARY_URL};
+use crate::steam::{STEAM_API_URL, STEAM_STORE_URL};
+use crate::{Error, Result};
+
+const DEFAULT_CONFIG_FILE: &str = "config.toml";
+
+/// Settings of the backend, read from a TOML file and overridden by
+/// environment variables (see [`Config::load`]).
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub server: ServerConfig,
+    pub database: DatabaseConfig,
+    pub cache: CacheConfig,
+    pub notify: NotifyConfig,
+    pub jobs: JobsConfig,
+    pub log: LogConfig,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    pub address: String,
+    /// Key granted every scope, kept besides `api_keys` for single key setups.
+    pub api_key: Option<String>,
+    pub api_keys: Vec<ApiKeyConfig>,
+    pub swagger_ui: bool,
+    /// Seconds to wait for in-flight requests and jobs when shutting down.
+    pub shutdown_timeout: u64,
+    /// Secret signing the session tokens, a random one is used if unset.
+    pub jwt_secret: Option<String>,
+    /// Seconds a session token stays valid.
+    pub session_ttl: u64,
+    pub rate_limit: RateLimitConfig,
+    /// Comments a client may post per minute, on top of `rate_limit`. 0
+    /// disables the limit.
+    pub comments_per_minute: u32,
+    pub images: ImagesConfig,
+    pub cors: CorsConfig,
+    pub tls: TlsConfig,
+    /// Starts in read-only maintenance mode, which can be switched at `/api/v1/admin/maintenance`.
+    pub read_only: bool,
+    /// Message mutating requests get in read-only mode.
+    pub maintenance_message: String,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct RateLimitConfig {
+    /// Requests a client may send per second on average, 0 disables the limit.
+    pub requests_per_second: f64,
+    /// Requests a client may send at once before being limited.
+    pub burst: u32,
+    /// Identifies clients by `X-Forwarded-For`, only safe behind a proxy setting it.
+    pub trust_forwarded_for: bool,
+}
+
+/// HTTPS is served instead of HTTP if both the certificate and the key are set.
+/// Both are read again on `SIGHUP`, e.g. after renewing the certificate.
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct TlsConfig {
+    /// PEM file with the certificate chain, the server certificate first.
+    pub cert_path: Option<String>,
+    /// PEM file with the PKCS#8 or RSA private key of the certificate.
+    pub key_path: Option<String>,
+}
+
+/// Cross-origin requests from browsers, for frontends served from another origin.
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct CorsConfig {
+    /// Origins like `https://wishlist.example.com` allowed to call the API, `*` allows any, none disables CORS.
+    pub allowed_origins: Vec<String>,
+    pub allowed_methods: Vec<String>,
+    /// Request headers the allowed origins may send.
+    pub allowed_headers: Vec<String>,
+    /// Response headers the allowed origins may read besides the basic ones.
+    pub exposed_headers: Vec<String>,
+    /// Seconds browsers may cache the answer to a preflight request.
+    pub max_age: u64,
+    /// Lets browsers send cookies and `Authorization`, not possible with `*`.
+    pub allow_credentials: bool,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct ImagesConfig {
+    /// Directory the downloaded product images and their thumbnails are stored in.
+    pub dir: String,
+    /// Largest image in bytes that is downloaded.
+    pub max_size: u64,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct DatabaseConfig {
+    pub url: Option<String>,
+    pub name: String,
+    /// Seconds the driver waits for a reachable server before a query fails.
+    pub server_selection_timeout: u64,
+    pub retry: RetryConfig,
+    pub circuit_breaker: CircuitBreakerConfig,
+    /// Applies pending migrations on startup, otherwise only `app migrate` does.
+    pub migrate_on_startup: bool,
+    /// Follows the change streams of products, snapshots and categories to pick
+    /// up direct writes to the database, requires a replica set.
+    pub watch_changes: bool,
+}
+
+/// Retries of queries failing because the database could not be reached.
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct RetryConfig {
+    /// Tries of each query, 1 disables retrying.
+    pub attempts: u32,
+    /// Milliseconds before the first retry, doubled for every further one.
+    pub backoff_ms: u64,
+    pub max_backoff_ms: u64,
+    /// Fraction of the backoff randomly taken off, so clients don't retry in lockstep.
+    pub jitter: f64,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct CircuitBreakerConfig {
+    /// Queries failing in a row, after their retries, before queries fail fast, 0 disables the breaker.
+    pub failure_threshold: u32,
+    /// Seconds queries fail fast before a single one is let through to probe the database.
+    pub open_seconds: u64,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct CacheConfig {
+    /// Seconds reads are cached for, 0 disables the cache.
+    pub ttl: u64,
+}
+
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct NotifyConfig {
+    pub webhooks: Vec<String>,
+    pub telegram: TelegramConfig,
+    pub email: EmailConfig,
+    pub push: PushConfig,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct TelegramConfig {
+    /// Snapshot summaries are only sent if both the token and the chat id are set.
+    pub bot_token: Option<String>,
+    pub chat_id: Option<String>,
+    pub api_url: String,
+}
+
+/// Mails are only sent if the SMTP host, the sender and a recipient are set.
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct EmailConfig {
+    pub smtp_host: Option<String>,
+    pub smtp_port: u16,
+    pub smtp_security: SmtpSecurity,
+    pub smtp_username: Option<String>,
+    pub smtp_password: Option<String>,
+    pub from: Option<String>,
+    pub to: Vec<String>,
+    /// Mail the products added by a new snapshot.
+    pub new_products: bool,
+    /// Mail price drops below a threshold or to an all-time low.
+    pub price_drops: bool,
+}
+
+/// Browser push notifications are only sent if the VAPID keys and subject are set.
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct PushConfig {
+    /// Key pair as printed by `npx web-push generate-vapid-keys`, base64url encoded.
+    pub vapid_public_key: Option<String>,
+    pub vapid_private_key: Option<String>,
+    /// Contact for the push services, a `mailto:` or `https:` url.
+    pub vapid_subject: Option<String>,
+}
+
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SmtpSecurity {
+    /// TLS from the start of the connection, usually on port 465.
+    Tls,
+    /// Plain connection upgraded with `STARTTLS`, usually on port 587.
+    StartTls,
+    /// Unencrypted, only meant for a relay on the same host.
+    None,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct LogConfig {
+    pub level: LevelFilter,
+    pub format: LogFormat,
+    /// Milliseconds after which a MongoDB query is logged as a warning with its filter, faster ones at debug level.
+    pub slow_query_ms: u64,
+    /// Milliseconds after which a request handler is logged as a warning.
+    pub slow_handler_ms: u64,
+}
+
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    /// One line per record, with the request id after the level.
+    Text,
+    /// One JSON object per record, the fields of the request and query spans under `mdc`.
+    Json,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct JobsConfig {
+    pub scrape_schedule: Option<String>,
+    pub scrape_command: Option<String>,
+    /// Sent to the sources that don't set their own in their politeness.
+    pub scrape_user_agent: String,
+    pub refresh_prices_schedule: Option<String>,
+    pub prune_snapshots_schedule: Option<String>,
+    /// Days all snapshots are kept, after that only the last one of each day.
+    pub snapshot_keep_all_days: u64,
+    /// Days snapshots are kept at all, the latest one of a list is never deleted.
+    pub snapshot_retention_days: u64,
+    pub exchange_rates_schedule: Option<String>,
+    /// Mails a digest of the changes of all lists, requires `notify.email`.
+    pub email_digest_schedule: Option<String>,
+    /// Days of changes summarized by the digest.
+    pub email_digest_days: u64,
+    /// Product views are counted in memory and written to the database on this schedule.
+    pub flush_views_schedule: Option<String>,
+    /// Groups the offers of the same item by several sources, also done after every scrape.
+    pub deduplicate_schedule: Option<String>,
+    /// Requests the URL of every product on a list, flagging the dead ones.
+    pub check_links_schedule: Option<String>,
+    /// Feed in the format of the ECB daily reference rates.
+    pub exchange_rates_url: String,
+    /// Looks up the details of the products by their ISBN or EAN.
+    pub enrich_schedule: Option<String>,
+    /// Books API in the format of Open Library, ISBNs are looked up at.
+    pub open_library_url: String,
+    /// Lookup API in the format of UPCitemdb, other EANs are looked up at.
+    pub ean_lookup_url: String,
+    /// Imports the Steam wishlist of `steam_id` into `steam_list`.
+    pub steam_schedule: Option<String>,
+    /// SteamID64 of the profile, its wishlist must be public.
+    pub steam_id: Option<String>,
+    pub steam_list: String,
+    /// Country of the Steam store, deciding the currency of the prices.
+    pub steam_country: String,
+    pub steam_api_url: String,
+    pub steam_store_url: String,
+}
+
+impl Config {
+    /// Reads the TOML file named by `CONFIG_FILE` (default `config.toml`,
+    /// which may be missing) and applies the environment overrides on top.
+    pub fn load() -> Result<Self> {
+        let (path, required) = match env::var("CONFIG_FILE") {
+            Ok(path) => (path, true),
+            Err(_) => (DEFAULT_CONFIG_FILE.to_string(), false),
+        };
+        let mut config = match fs::read_to_string(&path) {
+            Ok(content) => Self::from_toml(&content).map_err(|e| Error::Config(format!("{}: {}", path, e)))?,
+            Err(e) if e.kind() == ErrorKind::NotFound && !required => Self::default(),
+            Err(e) => return Err(Error::Config(format!("could not read {}: {}", path, e))),
+        };
+        config.apply_env()?;
+        Ok(config)
+    }
+
+    pub fn from_toml(content: &str) -> Result<Self> {
+        toml::from_str(content).map_err(|e| Error::Config(e.to_string()))
+    }
+
+    fn apply_env(&mut self) -> Result<()> {
+        override_string("BACKEND_ADDRESS", &mut self.server.address);
+        override_option("API_KEY", &mut self.server.api_key);
+        override_parsed("SWAGGER_UI", &mut self.server.swagger_ui)?;
+        override_parsed("SHUTDOWN_TIMEOUT", &mut self.server.shutdown_timeout)?;
+        override_option("JWT_SECRET", &mut self.server.jwt_secret);
+        override_parsed("SESSION_TTL", &mut self.server.session_ttl)?;
+        override_parsed("RATE_LIMIT_RPS", &mut self.server.rate_limit.requests_per_second)?;
+        override_parsed("RATE_LIMIT_BURST", &mut self.server.rate_limit.burst)?;
+        override_parsed("RATE_LIMIT_TRUST_FORWARDED_FOR", &mut self.server.rate_limit.trust_forwarded_for)?;
+        override_parsed("COMMENTS_PER_MINUTE", &mut self.server.comments_per_minute)?;
+        override_option("TLS_CERT_PATH", &mut self.server.tls.cert_path);
+        override_option("TLS_KEY_PATH", &mut self.server.tls.key_path);
+        override_list("CORS_ALLOWED_ORIGINS", &mut self.server.cors.allowed_origins);
+        override_list("CORS_ALLOWED_METHODS", &mut self.server.cors.allowed_methods);
+        override_list("CORS_ALLOWED_HEADERS", &mut self.server.cors.allowed_headers);
+        override_list("CORS_EXPOSED_HEADERS", &mut self.server.cors.exposed_headers);
+        override_parsed("CORS_MAX_AGE", &mut self.server.cors.max_age)?;
+        override_parsed("CORS_ALLOW_CREDENTIALS", &mut self.server.cors.allow_credentials)?;
+        override_string("IMAGE_DIR", &mut self.server.images.dir);
+        override_parsed("IMAGE_MAX_SIZE", &mut self.server.images.max_size)?;
+        override_parsed("READ_ONLY", &mut self.server.read_only)?;
+        override_string("MAINTENANCE_MESSAGE", &mut self.server.maintenance_message);
+        override_option("DATABASE_URL", &mut self.database.url);
+        override_string("DATABASE_NAME", &mut self.database.name);
+        override_parsed("DATABASE_SERVER_SELECTION_TIMEOUT", &mut self.database.server_selection_timeout)?;
+        override_parsed("DATABASE_MIGRATE_ON_STARTUP", &mut self.database.migrate_on_startup)?;
+        override_parsed("DATABASE_WATCH_CHANGES", &mut self.database.watch_changes)?;
+        override_parsed("DATABASE_RETRY_ATTEMPTS", &mut self.database.retry.attempts)?;
+        override_parsed("DATABASE_RETRY_BACKOFF_MS", &mut self.database.retry.backoff_ms)?;
+        override_parsed("DATABASE_RETRY_MAX_BACKOFF_MS", &mut self.database.retry.max_backoff_ms)?;
+        override_parsed("DATABASE_RETRY_JITTER", &mut self.database.retry.jitter)?;
+        override_parsed("DATABASE_BREAKER_THRESHOLD", &mut self.database.circuit_breaker.failure_threshold)?;
+        override_parsed("DATABASE_BREAKER_OPEN_SECONDS", &mut self.database.circuit_breaker.open_seconds)?;
+        override_parsed("CACHE_TTL", &mut self.cache.ttl)?;
+        override_list("NOTIFY_WEBHOOKS", &mut self.notify.webhooks);
+        override_option("TELEGRAM_BOT_TOKEN", &mut self.notify.telegram.bot_token);
+        override_option("TELEGRAM_CHAT_ID", &mut self.notify.telegram.chat_id);
+        override_string("TELEGRAM_API_URL", &mut self.notify.telegram.api_url);
+        override_option("SMTP_HOST", &mut self.notify.email.smtp_host);
+        override_parsed("SMTP_PORT", &mut self.notify.email.smtp_port)?;
+        override_parsed("SMTP_SECURITY", &mut self.notify.email.smtp_security)?;
+        override_option("SMTP_USERNAME", &mut self.notify.email.smtp_username);
+        override_option("SMTP_PASSWORD", &mut self.notify.email.smtp_password);
+        override_option("EMAIL_FROM", &mut self.notify.email.from);
+        override_list("EMAIL_TO", &mut self.notify.email.to);
+        override_parsed("EMAIL_NEW_PRODUCTS", &mut self.notify.email.new_products)?;
+        override_parsed("EMAIL_PRICE_DROPS", &mut self.notify.email.price_drops)?;
+        override_option("VAPID_PUBLIC_KEY", &mut self.notify.push.vapid_public_key);
+        override_option("VAPID_PRIVATE_KEY", &mut self.notify.push.vapid_private_key);
+        override_option("VAPID_SUBJECT", &mut self.notify.push.vapid_subject);
+        override_option("JOB_SCRAPE_SCHEDULE", &mut self.jobs.scrape_schedule);
+        override_option("SCRAPE_COMMAND", &mut self.jobs.scrape_command);
+        override_string("SCRAPE_USER_AGENT", &mut self.jobs.scrape_user_agent);
+        override_option("JOB_REFRESH_PRICES_SCHEDULE", &mut self.jobs.refresh_prices_schedule);
+        override_option("JOB_PRUNE_SNAPSHOTS_SCHEDULE", &mut self.jobs.prune_snapshots_schedule);
+        override_parsed("SNAPSHOT_KEEP_ALL_DAYS", &mut self.jobs.snapshot_keep_all_days)?;
+        override_parsed("SNAPSHOT_RETENTION_DAYS", &mut self.jobs.snapshot_retention_days)?;
+        override_option("JOB_EXCHANGE_RATES_SCHEDULE", &mut self.jobs.exchange_rates_schedule);
+        override_option("JOB_EMAIL_DIGEST_SCHEDULE", &mut self.jobs.email_digest_schedule);
+        override_parsed("EMAIL_DIGEST_DAYS", &mut self.jobs.email_digest_days)?;
+        override_option("JOB_FLUSH_VIEWS_SCHEDULE", &mut self.jobs.flush_views_schedule);
+        override_option("JOB_DEDUPLICATE_SCHEDULE", &mut self.jobs.deduplicate_schedule);
+        override_option("JOB_CHECK_LINKS_SCHEDULE", &mut self.jobs.check_links_schedule);
+        override_string("EXCHANGE_RATES_URL", &mut self.jobs.exchange_rates_url);
+        override_option("JOB_ENRICH_SCHEDULE", &mut self.jobs.enrich_schedule);
+        override_string("OPEN_LIBRARY_URL", &mut self.jobs.open_library_url);
+        override_string("EAN_LOOKUP_URL", &mut self.jobs.ean_lookup_url);
+        override_option("JOB_STEAM_SCHEDULE", &mut self.jobs.steam_schedule);
+        override_option("STEAM_ID", &mut self.jobs.steam_id);
+        override_string("STEAM_LIST", &mut self.jobs.steam_list);
+        override_string("STEAM_COUNTRY", &mut self.jobs.steam_country);
+        override_string("STEAM_API_URL", &mut self.jobs.steam_api_url);
+        override_string("STEAM_STORE_URL", &mut self.jobs.steam_store_url);
+        override_parsed("LOG_LEVEL", &mut self.log.level)?;
+        override_parsed("LOG_FORMAT", &mut self.log.format)?;
+        override_parsed("LOG_SLOW_QUERY_MS", &mut self.log.slow_query_ms)?;
+        override_parsed("LOG_SLOW_HANDLER_MS", &mut self.log.slow_handler_ms)?;
+        Ok(())
+    }
+}
+
+impl ServerConfig {
+    pub fn get_shutdown_timeout(&self) -> Duration {
+        Duration::from_secs(self.shutdown_timeout)
+    }
+    pub fn get_session_ttl(&self) -> Duration {
+        Duration::from_secs(self.session_ttl)
+    }
+}
+
+impl DatabaseConfig {
+    pub fn get_server_selection_timeout(&self) -> Duration {
+        Duration::from_secs(self.server_selection_timeout)
+    }
+}
+
+impl RetryConfig {
+    pub fn get_backoff(&self) -> Duration {
+        Duration::from_millis(self.backoff_ms)
+    }
+    pub fn get_max_backoff(&self) -> Duration {
+        Duration::from_millis(self.max_backoff_ms)
+    }
+}
+
+impl CircuitBreakerConfig {
+    pub fn get_open_duration(&self) -> Duration {
+        Duration::from_secs(self.open_seconds)
+    }
+}
+
+impl CacheConfig {
+    pub fn get_ttl(&self) -> Duration {
+        Duration::from_secs(self.ttl)
+    }
+}
+
+impl JobsConfig {
+    pub fn get_snapshot_keep_all(&self) -> Duration {
+        Duration::from_secs(self.snapshot_keep_all_days * 24 * 60 * 60)
+    }
+    pub fn get_snapshot_retention(&self) -> Duration {
+        Duration::from_secs(self.snapshot_retention_days * 24 * 60 * 60)
+    }
+    pub fn get_email_digest_period(&self) -> Duration {
+        Duration::from_secs(self.email_digest_days * 24 * 60 * 60)
+    }
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            address: String::from("0.0.0.0:8080"),
+            api_key: None,
+            api_keys: Vec::new(),
+            swagger_ui: false,
+            shutdown_timeout: 30,
+            jwt_secret: None,
+            session_ttl: 7 * 24 * 60 * 60,
+            rate_limit: RateLimitConfig::default(),
+            comments_per_minute: 5,
+            images: ImagesConfig::default(),
+            cors: CorsConfig::default(),
+            tls: TlsConfig::default(),
+            read_only: false,
+            maintenance_message: String::from("The wishlist is in maintenance, changes are not possible right now"),
+        }
+    }
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self {
+            requests_per_second: 5.,
+            burst: 20,
+            trust_forwarded_for: false,
+        }
+    }
+}
+
+impl CorsConfig {
+    pub fn get_max_age(&self) -> Duration {
+        Duration::from_secs(self.max_age)
+    }
+}
+
+impl Default for CorsConfig {
+    fn default() -> Self {
+        Self {
+            allowed_origins: Vec::new(),
+            allowed_methods: ["GET", "POST", "PUT", "DELETE"].iter().map(|m| m.to_string()).collect(),
+            allowed_headers: ["content-type", "authorization", "x-api-key"].iter().map(|h| h.to_string()).collect(),
+            exposed_headers: ["x-total-count", "x-skipped-documents", "x-request-id", "link", "etag"].iter().map(|h| h.to_string()).collect(),
+            max_age: 600,
+            allow_credentials: false,
+        }
+    }
+}
+
+impl Default for ImagesConfig {
+    fn default() -> Self {
+        Self {
+            dir: String::from("images"),
+            max_size: 10 * 1024 * 1024,
+        }
+    }
+}
+
+impl Default for DatabaseConfig {
+    fn default() -> Self {
+        Self {
+            url: None,
+            name: String::from("wishlist"),
+            server_selection_timeout: 5,
+            retry: RetryConfig::default(),
+            circuit_breaker: CircuitBreakerConfig::default(),
+            migrate_on_startup: true,
+            watch_changes: true,
+        }
+    }
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        Self {
+            attempts: 3,
+            backoff_ms: 100,
+            max_backoff_ms: 2000,
+            jitter: 0.5,
+        }
+    }
+}
+
+impl Default for CircuitBreakerConfig {
+    fn default() -> Self {
+        Self {
+            failure_threshold: 5,
+            open_seconds: 30,
+        }
+    }
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        Self { ttl: 30 }
+    }
+}
+
+impl Default for TelegramConfig {
+    fn default() -> Self {
+        Self {
+            bot_token: None,
+            chat_id: None,
+            api_url: String::from("https://api.telegram.org"),
+        }
+    }
+}
+
+impl Default for EmailConfig {
+    fn default() -> Self {
+        Self {
+            smtp_host: None,
+            smtp_port: 587,
+            smtp_security: SmtpSecurity::StartTls,
+            smtp_username: None,
+            smtp_password: None,
+            from: None,
+            to: Vec::new(),
+            new_products: true,
+            price_drops: true,
+        }
+    }
+}
+
+impl FromStr for SmtpSecurity {
+    type Err = String;
+
+    fn from_str(value: &str) -> std::result::Result<Self, Self::Err> {
+        match value {
+            "tls" => Ok(SmtpSecurity::Tls),
+            "starttls" => Ok(SmtpSecurity::StartTls),
+            "none" => Ok(SmtpSecurity::None),
+            _ => Err(format!("'{}' is not one of tls, starttls or none", value)),
+        }
+    }
+}
+
+impl LogConfig {
+    pub fn get_slow_query(&self) -> Duration {
+        Duration::from_millis(self.slow_query_ms)
+    }
+    pub fn get_slow_handler(&self) -> Duration {
+        Duration::from_millis(self.slow_handler_ms)
+    }
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        Self {
+            level: LevelFilter::Info,
+            format: LogFormat::Text,
+            slow_query_ms: 500,
+            slow_handler_ms: 1000,
+        }
+    }
+}
+
+impl FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(value: &str) -> std::result::Result<Self, Self::Err> {
+        match value {
+            "text" => Ok(LogFormat::Text),
+            "json" => Ok(LogFormat::Json),
+            _ => Err(format!("'{}' is not one of text or json", value)),
+        }
+    }
+}
+
+impl Default for JobsConfig {
+    fn default() -> Self {
+        Self {
+            scrape_schedule: None,
+            scrape_command: None,
+            scrape_user_agent: String::from("wishlist/1.0"),
+            refresh_prices_schedule: None,
+            prune_snapshots_schedule: None,
+            snapshot_keep_all_days: 30,
+            snapshot_retention_days: 365,
+            exchange_rates_schedule: None,
+            email_digest_schedule: None,
+            email_digest_days: 7,
+            flush_views_schedule: Some(String::from("0 * * * * *")),
+            deduplicate_schedule: None,
+            check_links_schedule: None,
+            exchange_rates_url: ECB_RATES_URL.to_string(),
+            enrich_schedule: None,
+            open_library_url: OPEN_LIBRARY_URL.to_string(),
+            ean_lookup_url: EAN_LOOKUP_URL.to_string(),
+            steam_schedule: None,
+            steam_id: None,
+            steam_list: String::from("steam"),
+            steam_country: String::from("de"),
+            steam_api_url: STEAM_API_URL.to_string(),
+            steam_store_url: STEAM_STORE_URL.to_string(),
+        }
+    }
+}
+
+fn override_string(name: &str, value: &mut String) {
+    if let Ok(v) = env::var(name) {
+        *value = v;
+    }
+}
+
+fn override_option(name: &str, value: &mut Option<String>) {
+    if let Ok(v) = env::var(name) {
+        *value = Some(v);
+    }
+}
+
+/// Replaces `values` with the comma separated entries of the variable.
+fn override_list(name: &str, values: &mut Vec<String>) {
+    if let Ok(v) = env::var(name) {
+        *values = v.split(',').map(str::trim).filter(|v| !v.is_empty()).map(String::from).collect();
+    }
+}
+
+fn override_parsed<T>(name: &str, value: &mut T) -> Result<()>
+where
+    T: FromStr,
+    T::Err: std::fmt::Display,
+{
+    if let Ok(v) = env::var(name) {
+        *value = v.parse().map_err(|e| Error::Config(format!("could not parse {}: {}", name, e)))?;
+    }
+    Ok(())
+}
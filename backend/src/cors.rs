@@ -0,0 +1,129 @@
+use std::convert::Infallible;
+use std::sync::Arc;
+use warp::http::{header, HeaderValue, Response, StatusCode, Uri};
+use warp::hyper::Body;
+use warp::{Filter, Rejection};
+
+use crate::config::CorsConfig;
+use crate::{Error, Result};
+
+/// Answers cross-origin requests of browsers from the configured origins.
+/// Responses to other origins go out without CORS headers, so browsers keep
+/// their content from the calling page.
+#[derive(Clone)]
+pub struct Cors {
+    origins: Arc<Vec<String>>,
+    any_origin: bool,
+    methods: HeaderValue,
+    headers: HeaderValue,
+    exposed_headers: Option<HeaderValue>,
+    max_age: HeaderValue,
+    credentials: bool,
+}
+
+impl Cors {
+    /// Fails with `Error::Config` on origins that aren't a scheme and host,
+    /// and on `*` together with credentials, which browsers refuse.
+    pub fn from_config(config: &CorsConfig) -> Result<Self> {
+        let any_origin = config.allowed_origins.iter().any(|origin| origin == "*");
+        if any_origin && config.allow_credentials {
+            return Err(Error::Config("CORS credentials can't be allowed for any origin".to_string()));
+        }
+        let mut origins = Vec::new();
+        for origin in config.allowed_origins.iter().filter(|origin| *origin != "*") {
+            origins.push(normalize_origin(origin).ok_or_else(|| Error::Config(format!("'{}' is no CORS origin", origin)))?);
+        }
+        Ok(Self {
+            origins: Arc::new(origins),
+            any_origin,
+            methods: join_header("methods", &config.allowed_methods)?,
+            headers: join_header("headers", &config.allowed_headers)?,
+            exposed_headers: Some(join_header("exposed headers", &config.exposed_headers)?).filter(|h| !h.is_empty()),
+            max_age: config.get_max_age().as_secs().into(),
+            credentials: config.allow_credentials,
+        })
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.any_origin || !self.origins.is_empty()
+    }
+
+    fn is_allowed(&self, origin: &str) -> bool {
+        self.any_origin || normalize_origin(origin).map(|o| self.origins.contains(&o)).unwrap_or(false)
+    }
+}
+
+/// Answers preflight requests, `OPTIONS` with an `Access-Control-Request-Method`,
+/// with `204 No Content` listing what may be sent. Rejects other requests, and
+/// all requests if CORS is disabled.
+pub fn with_preflight(cors: Cors) -> impl Filter<Extract = (Response<Body>,), Error = Rejection> + Clone {
+    warp::options()
+        .and(warp::header::<String>("access-control-request-method"))
+        .and_then(move |_: String| {
+            let cors = cors.clone();
+            async move {
+                if !cors.is_enabled() {
+                    return Err(warp::reject::not_found());
+                }
+                let mut response = Response::new(Body::empty());
+                *response.status_mut() = StatusCode::NO_CONTENT;
+                let headers = response.headers_mut();
+                headers.insert(header::ACCESS_CONTROL_ALLOW_METHODS, cors.methods.clone());
+                headers.insert(header::ACCESS_CONTROL_ALLOW_HEADERS, cors.headers.clone());
+                headers.insert(header::ACCESS_CONTROL_MAX_AGE, cors.max_age.clone());
+                Ok(response)
+            }
+        })
+}
+
+/// Adds `Access-Control-Allow-Origin` and the other CORS headers to the
+/// responses to allowed origins.
+pub async fn apply_cors(origin: Option<String>, cors: Cors, mut response: Response<Body>) -> std::result::Result<Response<Body>, Infallible> {
+    if !cors.is_enabled() {
+        return Ok(response);
+    }
+    let headers = response.headers_mut();
+    if !cors.any_origin {
+        headers.append(header::VARY, HeaderValue::from_static("origin"));
+    }
+    let origin = match origin.filter(|origin| cors.is_allowed(origin)) {
+        Some(origin) => origin,
+        None => return Ok(response),
+    };
+    let allowed_origin = if cors.any_origin {
+        HeaderValue::from_static("*")
+    } else {
+        match HeaderValue::from_str(&origin) {
+            Ok(value) => value,
+            Err(_) => return Ok(response),
+        }
+    };
+    headers.insert(header::ACCESS_CONTROL_ALLOW_ORIGIN, allowed_origin);
+    if cors.credentials {
+        headers.insert(header::ACCESS_CONTROL_ALLOW_CREDENTIALS, HeaderValue::from_static("true"));
+    }
+    if let Some(exposed) = &cors.exposed_headers {
+        headers.insert(header::ACCESS_CONTROL_EXPOSE_HEADERS, exposed.clone());
+    }
+    Ok(response)
+}
+
+/// Origin as browsers send it, like `https://example.com:8443`, lowercase
+/// and without a trailing slash. `None` if `origin` has no scheme and host.
+fn normalize_origin(origin: &str) -> Option<String> {
+    let uri: Uri = origin.trim().trim_end_matches('/').parse().ok()?;
+    if !matches!(uri.path(), "" | "/") || uri.query().is_some() {
+        return None;
+    }
+    let scheme = uri.scheme_str()?;
+    let host = uri.host()?;
+    let origin = match uri.port_u16() {
+        Some(port) => format!("{}://{}:{}", scheme, host, port),
+        None => format!("{}://{}", scheme, host),
+    };
+    Some(origin.to_lowercase())
+}
+
+fn join_header(name: &str, values: &[String]) -> Result<HeaderValue> {
+    HeaderValue::from_str(&values.join(", ")).map_err(|_| Error::Config(format!("invalid CORS {}: {:?}", name, values)))
+}
@@ -0,0 +1,169 @@
+use mongodb::bson::{doc, document::Document};
+
+use crate::model::{Currency, ExchangeRates, Loaded, Money, Page, Product, ProductDetail, Stats, Wishlist, RATE_SCALE};
+use crate::repository::WishlistRepository;
+use crate::{Error, Result};
+
+/// Daily euro reference rates of the European Central Bank.
+pub const ECB_RATES_URL: &str = "https://www.ecb.europa.eu/stats/eurofxref/eurofxref-daily.xml";
+
+/// Reference rates read from the ECB feed, in millionths as stored.
+pub struct EcbRates {
+    date: String,
+    rates: Vec<(Currency, i64)>,
+}
+
+impl EcbRates {
+    pub fn get_date(&self) -> &str {
+        &self.date
+    }
+
+    pub fn get_rate_count(&self) -> usize {
+        self.rates.len()
+    }
+
+    /// Converts the rates into the document stored in the `exchange_rates` collection.
+    pub fn to_document(&self, fetched: i32) -> Document {
+        let mut rates = Document::new();
+        for (currency, rate) in &self.rates {
+            rates.insert(currency.get_code(), *rate);
+        }
+        doc! {"date": self.date.as_str(), "fetched": fetched, "rates": rates}
+    }
+}
+
+/// Reads the `<Cube time="..">` and `<Cube currency=".." rate=".."/>`
+/// elements of the ECB feed. Currencies the backend doesn't know are skipped.
+pub fn parse_ecb_rates(xml: &str) -> Result<EcbRates> {
+    let mut date = None;
+    let mut rates = Vec::new();
+    for element in xml.split("<Cube").skip(1) {
+        let element = element.split('>').next().unwrap_or_default();
+        if let Some(time) = get_attribute(element, "time") {
+            date = Some(time.to_string());
+        }
+        if let (Some(code), Some(rate)) = (get_attribute(element, "currency"), get_attribute(element, "rate")) {
+            let rate = parse_rate(rate).ok_or_else(|| Error::Job(format!("invalid exchange rate '{}' for {}", rate, code)))?;
+            match Currency::from_code(code) {
+                Some(currency) => rates.push((currency, rate)),
+                None => debug!("Skipping exchange rate of unknown currency {}", code),
+            }
+        }
+    }
+    let date = date.ok_or_else(|| Error::Job("exchange rates have no date".to_string()))?;
+    if rates.is_empty() {
+        return Err(Error::Job("no exchange rates found".to_string()));
+    }
+    Ok(EcbRates { date, rates })
+}
+
+fn get_attribute<'a>(element: &'a str, name: &str) -> Option<&'a str> {
+    let start = element.find(&format!("{}=", name))? + name.len() + 1;
+    let quote = element[start..].chars().next().filter(|c| *c == '\'' || *c == '"')?;
+    let value = &element[start + 1..];
+    value.find(quote).map(|end| &value[..end])
+}
+
+/// Parses a decimal like `1.0845` into millionths without going through floats.
+fn parse_rate(rate: &str) -> Option<i64> {
+    let (whole, fraction) = rate.split_once('.').unwrap_or((rate, ""));
+    let digits = RATE_SCALE.to_string().len() - 1;
+    if whole.is_empty() || !whole.chars().all(|c| c.is_ascii_digit()) || !fraction.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let fraction: String = fraction.chars().chain(std::iter::repeat('0')).take(digits).collect();
+    let rate = whole.parse::<i64>().ok()?.checked_mul(RATE_SCALE)?.checked_add(fraction.parse().ok()?)?;
+    Some(rate).filter(|rate| *rate > 0)
+}
+
+/// Shows prices in one currency, failing only if a price actually needs a
+/// rate that hasn't been fetched.
+pub struct Converter {
+    currency: Currency,
+    rates: Option<ExchangeRates>,
+}
+
+impl Converter {
+    pub async fn load(repo: &dyn WishlistRepository, currency: Currency) -> Result<Self> {
+        let rates = repo.get_exchange_rates().await?;
+        Ok(Self { currency, rates })
+    }
+
+    pub fn get_currency(&self) -> Currency {
+        self.currency
+    }
+
+    pub fn convert(&self, money: Money) -> Result<Money> {
+        if money.get_currency() == self.currency {
+            return Ok(money);
+        }
+        match &self.rates {
+            Some(rates) => rates.convert(money, self.currency),
+            None => Err(Error::Unavailable("exchange rates haven't been fetched yet".to_string())),
+        }
+    }
+}
+
+/// Responses whose prices can be shown in another currency.
+pub trait ConvertPrices {
+    fn convert_prices(&mut self, converter: &Converter) -> Result<()>;
+}
+
+impl ConvertPrices for Product {
+    fn convert_prices(&mut self, converter: &Converter) -> Result<()> {
+        Product::convert_prices(self, converter.get_currency(), |price| converter.convert(price))
+    }
+}
+
+/// The price history is stored in the currency of the product, so it is
+/// converted before the product is.
+impl ConvertPrices for ProductDetail {
+    fn convert_prices(&mut self, converter: &Converter) -> Result<()> {
+        let from = self.get_product_mut().get_currency();
+        if let Some(history) = self.get_price_history_mut() {
+            history.iter_mut().try_for_each(|point| point.convert_price(from, |price| converter.convert(price)))?;
+        }
+        ConvertPrices::convert_prices(self.get_product_mut(), converter)
+    }
+}
+
+impl<T: ConvertPrices> ConvertPrices for Vec<T> {
+    fn convert_prices(&mut self, converter: &Converter) -> Result<()> {
+        self.iter_mut().try_for_each(|item| item.convert_prices(converter))
+    }
+}
+
+impl<T: ConvertPrices> ConvertPrices for Loaded<T> {
+    fn convert_prices(&mut self, converter: &Converter) -> Result<()> {
+        self.get_value_mut().convert_prices(converter)
+    }
+}
+
+impl<T> ConvertPrices for Page<T> where Vec<T>: ConvertPrices {
+    fn convert_prices(&mut self, converter: &Converter) -> Result<()> {
+        self.get_items_mut().convert_prices(converter)
+    }
+}
+
+impl ConvertPrices for Wishlist {
+    fn convert_prices(&mut self, converter: &Converter) -> Result<()> {
+        match self.get_products_mut() {
+            Some(products) => products.convert_prices(converter),
+            None => Ok(()),
+        }
+    }
+}
+
+impl ConvertPrices for Stats {
+    fn convert_prices(&mut self, converter: &Converter) -> Result<()> {
+        Stats::compute_totals(self, converter.get_currency(), |total| converter.convert(total))
+    }
+}
+
+/// Shows the prices of `output` in `currency` if one was requested.
+pub async fn convert_prices<T: ConvertPrices>(repo: &dyn WishlistRepository, currency: Option<Currency>, mut output: T) -> Result<T> {
+    if let Some(currency) = currency {
+        output.convert_prices(&Converter::load(repo, currency).await?)?;
+    }
+    Ok(output)
+}
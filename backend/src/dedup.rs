@@ -0,0 +1,108 @@
+use mongodb::bson::{doc, oid::ObjectId, Bson};
+use std::collections::BTreeMap;
+
+use crate::audit::{record_event, SCRAPER};
+use crate::model::{Product, Stored};
+use crate::query::SortQuery;
+use crate::repository::{ProductFilter, WishlistRepository};
+use crate::Result;
+
+const PAGE_SIZE: u64 = 100;
+
+/// Groups the products of a list offered by different sources that are the
+/// same item, by their EAN or, for products without one, their normalized
+/// name. Every product of a group but the cheapest offer gets `duplicate_of`
+/// set to the cheapest one, the products of no group have it removed.
+///
+/// Current and archived products are grouped apart, so collapsing the
+/// duplicates of a listing never hides its only offer of an item.
+/// Returns the number of products whose `duplicate_of` changed.
+pub async fn deduplicate_products(repo: &dyn WishlistRepository) -> Result<u64> {
+    let mut changed = 0;
+    for archived in [false, true].iter() {
+        let products = load_products(repo, ProductFilter::default().with_archived(*archived)).await?;
+        let mut groups: BTreeMap<(String, String), Vec<&Product>> = BTreeMap::new();
+        for product in products.iter() {
+            if let Some(key) = get_item_key(product) {
+                groups.entry((product.get_list().to_string(), key)).or_default().push(product);
+            }
+        }
+
+        let mut cheapest: BTreeMap<ObjectId, Option<ObjectId>> = BTreeMap::new();
+        for group in groups.values().filter(|group| has_several_sources(group)) {
+            let primary = match group.iter().min_by_key(|p| (p.get_current_price().unwrap_or(i32::MAX), p.get_id().cloned())) {
+                Some(primary) => primary.get_id().cloned(),
+                None => continue,
+            };
+            for product in group.iter().filter_map(|p| p.get_id()) {
+                let duplicate_of = primary.clone().filter(|primary| primary != product);
+                cheapest.insert(product.clone(), duplicate_of);
+            }
+        }
+
+        for product in products.iter() {
+            let id = match product.get_id() {
+                Some(id) => id,
+                None => continue,
+            };
+            let duplicate_of = cheapest.get(id).cloned().flatten();
+            if product.get_duplicate_of() == duplicate_of.as_ref() {
+                continue;
+            }
+            let value = duplicate_of.map(Bson::ObjectId).unwrap_or(Bson::Null);
+            if repo.update_product(id, doc! {"duplicate_of": value}).await? {
+                changed += 1;
+            }
+        }
+    }
+    if changed > 0 {
+        record_event(repo, SCRAPER, "deduplicate", Product::COLLECTION, None, doc! {"changed": changed as i64}).await;
+    }
+    Ok(changed)
+}
+
+async fn load_products(repo: &dyn WishlistRepository, filter: ProductFilter) -> Result<Vec<Product>> {
+    let sort = SortQuery::default();
+    let mut products = Vec::new();
+    let mut offset = 0;
+    loop {
+        let page = repo.get_products(&filter, &sort, offset, PAGE_SIZE).await?.into_value();
+        let full = page.len() as u64 == PAGE_SIZE;
+        products.extend(page);
+        if !full {
+            break;
+        }
+        offset += PAGE_SIZE;
+    }
+    Ok(products)
+}
+
+/// Identifies the item a product offers, `None` for products without a name.
+fn get_item_key(product: &Product) -> Option<String> {
+    if let Some(ean) = product.get_ean() {
+        return Some(format!("ean:{}", ean));
+    }
+    let name = normalize_name(product.get_name()?);
+    if name.is_empty() {
+        None
+    } else {
+        Some(format!("name:{}", name))
+    }
+}
+
+/// Lowercases `name` and reduces it to its words, so `Sony WH-1000XM4 (Black)`
+/// and `sony wh 1000xm4 black` are the same.
+fn normalize_name(name: &str) -> String {
+    name.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Duplicates are only offers from different shops, products of one source
+/// with the same name are usually variants.
+fn has_several_sources(group: &[&Product]) -> bool {
+    let first = group.first().and_then(|p| p.get_source_id());
+    group.iter().any(|p| p.get_source_id() != first)
+}
@@ -0,0 +1,176 @@
+use mongodb::bson::{doc, Bson};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::cache::TtlCache;
+use crate::model::{Enrichment, Product};
+use crate::repository::WishlistRepository;
+use crate::{Error, Result};
+
+pub const OPEN_LIBRARY_URL: &str = "https://openlibrary.org/api/books";
+pub const EAN_LOOKUP_URL: &str = "https://api.upcitemdb.com/prod/trial/lookup";
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+/// How long a lookup is kept, the details of an item hardly change.
+const CACHE_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+/// Characters of a description taken over, longer ones are cut.
+const MAX_DESCRIPTION_LENGTH: usize = 1000;
+
+/// Looks up books by their ISBN at the books API of Open Library and other
+/// items by their EAN at an API answering like the one of UPCitemdb. Books
+/// Open Library doesn't know are looked up by their EAN too. Lookups are
+/// cached, the ones that found nothing included.
+#[derive(Clone)]
+pub struct Enricher {
+    client: reqwest::Client,
+    open_library_url: String,
+    ean_lookup_url: String,
+    cache: Arc<TtlCache<String, Option<Enrichment>>>,
+}
+
+#[derive(Deserialize)]
+struct OpenLibraryBook {
+    title: Option<String>,
+    #[serde(default)]
+    authors: Vec<Named>,
+    #[serde(default)]
+    publishers: Vec<Named>,
+    publish_date: Option<String>,
+    number_of_pages: Option<u32>,
+    cover: Option<OpenLibraryCover>,
+}
+
+#[derive(Deserialize)]
+struct Named {
+    name: String,
+}
+
+#[derive(Deserialize)]
+struct OpenLibraryCover {
+    large: Option<String>,
+    medium: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct EanLookup {
+    #[serde(default)]
+    items: Vec<EanItem>,
+}
+
+#[derive(Deserialize)]
+struct EanItem {
+    title: Option<String>,
+    description: Option<String>,
+    brand: Option<String>,
+    #[serde(default)]
+    images: Vec<String>,
+}
+
+impl Enricher {
+    pub fn new(open_library_url: String, ean_lookup_url: String) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            open_library_url,
+            ean_lookup_url,
+            cache: Arc::new(TtlCache::new(CACHE_TTL)),
+        }
+    }
+
+    /// Returns the details of the item with the normalized `ean`, `None` if
+    /// no provider knows it. Fails with `Error::Unavailable` if a provider
+    /// can't be reached, which isn't cached.
+    pub async fn lookup(&self, ean: &str) -> Result<Option<Enrichment>> {
+        if let Some(cached) = self.cache.get(&ean.to_string()) {
+            return Ok(cached);
+        }
+        let mut found = None;
+        if is_isbn(ean) {
+            found = self.lookup_book(ean).await?;
+        }
+        if found.is_none() {
+            found = self.lookup_item(ean).await?;
+        }
+        self.cache.insert(ean.to_string(), found.clone());
+        Ok(found)
+    }
+
+    async fn lookup_book(&self, isbn: &str) -> Result<Option<Enrichment>> {
+        let key = format!("ISBN:{}", isbn);
+        let query = [("bibkeys", key.as_str()), ("format", "json"), ("jscmd", "data")];
+        let books: HashMap<String, OpenLibraryBook> = match self.fetch(&self.open_library_url, &query).await? {
+            Some(books) => books,
+            None => return Ok(None),
+        };
+        Ok(books.into_iter().next().map(|(_, book)| {
+            Enrichment::new("openlibrary", book.title)
+                .with_authors(book.authors.into_iter().map(|author| author.name).collect())
+                .with_publisher(book.publishers.into_iter().next().map(|publisher| publisher.name))
+                .with_published(book.publish_date)
+                .with_pages(book.number_of_pages)
+                .with_cover(book.cover.and_then(|cover| cover.large.or(cover.medium)))
+        }))
+    }
+
+    async fn lookup_item(&self, ean: &str) -> Result<Option<Enrichment>> {
+        let lookup: EanLookup = match self.fetch(&self.ean_lookup_url, &[("upc", ean)]).await? {
+            Some(lookup) => lookup,
+            None => return Ok(None),
+        };
+        Ok(lookup.items.into_iter().next().map(|item| {
+            let description = item.description
+                .filter(|d| !d.trim().is_empty())
+                .map(|d| d.trim().chars().take(MAX_DESCRIPTION_LENGTH).collect());
+            Enrichment::new("ean", item.title)
+                .with_publisher(item.brand.filter(|b| !b.is_empty()))
+                .with_description(description)
+                .with_cover(item.images.into_iter().find(|image| image.starts_with("https://")))
+        }))
+    }
+
+    /// `None` if the provider answers that it doesn't know the item.
+    async fn fetch<T: serde::de::DeserializeOwned>(&self, url: &str, query: &[(&str, &str)]) -> Result<Option<T>> {
+        let unavailable = |e: reqwest::Error| Error::Unavailable(format!("could not look up item at {}: {}", url, e));
+        let response = self.client.get(url)
+            .query(query)
+            .timeout(REQUEST_TIMEOUT)
+            .send()
+            .await
+            .map_err(unavailable)?;
+        let status = response.status();
+        if status.is_client_error() && status != reqwest::StatusCode::TOO_MANY_REQUESTS {
+            return Ok(None);
+        }
+        let response = response.error_for_status().map_err(unavailable)?;
+        Ok(Some(response.json().await.map_err(unavailable)?))
+    }
+}
+
+/// Looks up the details of `product` if its EAN changed since the last
+/// lookup, taking over the cover and description if it has none.
+/// Returns whether the product was updated.
+pub(crate) async fn enrich_product(repo: &dyn WishlistRepository, enricher: &Enricher, product: &Product) -> Result<bool> {
+    let (id, ean) = match (product.get_id(), product.get_ean()) {
+        (Some(id), Some(ean)) if product.get_enriched_ean() != Some(ean) => (id, ean),
+        _ => return Ok(false),
+    };
+    let enrichment = enricher.lookup(ean).await?;
+    let mut update = doc! {
+        "enriched_ean": ean,
+        "enrichment": enrichment.as_ref().map(|e| Bson::from(e.to_document())).unwrap_or(Bson::Null),
+    };
+    if let Some(enrichment) = &enrichment {
+        if let (None, Some(cover)) = (product.get_url_img().filter(|url| !url.is_empty()), enrichment.get_cover()) {
+            update.insert("url_img", cover);
+        }
+        if let (None, Some(description)) = (product.get_description().filter(|d| !d.is_empty()), enrichment.get_description()) {
+            update.insert("description", description);
+        }
+    }
+    repo.update_product(id, update).await
+}
+
+/// ISBN-13 are EAN-13 of the "Bookland" prefixes 978 and 979.
+fn is_isbn(ean: &str) -> bool {
+    ean.len() == 13 && (ean.starts_with("978") || ean.starts_with("979"))
+}
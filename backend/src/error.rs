@@ -1,4 +1,3 @@
-use std;
 use thiserror::Error;
 
 use bson::document::ValueAccessError;
@@ -6,7 +5,8 @@ use bson::oid::Error as BsonError;
 use mongodb::error::Error as MongoError;
 
 use crate::model::ErrorMessage;
-use crate::reject::get_internal_error_message;
+use crate::reject::{get_error_message, get_internal_error_message};
+use warp::http::StatusCode;
 
 pub type Result<T> = std::result::Result<T, Error>;
 
@@ -29,19 +29,62 @@ pub enum Error {
     },
     #[error("Received an empty result")]
     EmptyResult,
+    #[error("Deserialize: '{model}' has an invalid field '{field}': {reason}")]
+    Deserialize {
+        model: &'static str,
+        field: String,
+        reason: String,
+    },
     #[error("Persistence: Field not loaded: '{0}' is missing '{1}'")]
     FieldNotLoaded(&'static str, &'static str),
+    #[error("Not found: {0}")]
+    NotFound(&'static str),
+    #[error("Validation: {0}")]
+    Validation(String),
+    #[error("Unauthorized")]
+    Unauthorized,
+    #[error("Forbidden: {0}")]
+    Forbidden(String),
+    #[error("Conflict: {0}")]
+    Conflict(String),
+    #[error("Config: {0}")]
+    Config(String),
+    #[error("Job: {0}")]
+    Job(String),
+    #[error("Backup: {0}")]
+    Backup(String),
+    #[error("Migration: {0}")]
+    Migration(String),
+    #[error("Rate limited, retry after {0}s")]
+    RateLimited(u64),
+    #[error("Unavailable: {0}")]
+    Unavailable(String),
+    #[error("Internal: {0}")]
+    Internal(String),
 }
 
 impl warp::reject::Reject for Error {}
 
-impl Into<ErrorMessage> for &Error {
-    fn into(self) -> ErrorMessage {
-        match self {
-            Error::EmptyResult => ErrorMessage {
-                code: 200,
-                message: "Empty Result".to_string(),
-            },
+impl From<&Error> for ErrorMessage {
+    fn from(err: &Error) -> Self {
+        match err {
+            Error::EmptyResult => get_error_message(StatusCode::NOT_FOUND, "Empty Result"),
+            Error::NotFound(what) => get_error_message(StatusCode::NOT_FOUND, &format!("Could not find {}", what)),
+            Error::Bson { .. } => get_error_message(StatusCode::BAD_REQUEST, "Invalid id"),
+            Error::Validation(reason) => get_error_message(StatusCode::BAD_REQUEST, reason),
+            Error::Conflict(reason) => get_error_message(StatusCode::CONFLICT, reason),
+            Error::Unauthorized => get_error_message(StatusCode::UNAUTHORIZED, "Unauthorized"),
+            Error::Forbidden(reason) => get_error_message(StatusCode::FORBIDDEN, reason),
+            Error::RateLimited(_) => get_error_message(StatusCode::TOO_MANY_REQUESTS, "Too many requests"),
+            Error::Unavailable(reason) => get_error_message(StatusCode::SERVICE_UNAVAILABLE, reason),
+            Error::FieldNotLoaded(model, field) => get_error_message(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                &format!("Stored data is incomplete: '{}' is missing '{}'", model, field),
+            ),
+            Error::Deserialize { model, .. } => get_error_message(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                &format!("Stored data is invalid: could not read '{}'", model),
+            ),
             _ => get_internal_error_message(),
         }
     }
@@ -0,0 +1,61 @@
+use std::collections::hash_map::DefaultHasher;
+use std::convert::Infallible;
+use std::hash::{Hash, Hasher};
+use warp::http::{header, HeaderValue, Method, Response, StatusCode};
+use warp::hyper::{body, Body};
+use warp::Reply;
+
+/// Adds a weak ETag computed from the body to successful GET responses, and
+/// replaces the response with `304 Not Modified` if the client already has it.
+pub async fn apply_etag<R: Reply>(if_none_match: Option<String>, method: Method, reply: R) -> Result<Response<Body>, Infallible> {
+    let response = reply.into_response();
+    if method != Method::GET || !response.status().is_success() || is_event_stream(&response) {
+        return Ok(response);
+    }
+
+    let (mut parts, body) = response.into_parts();
+    let bytes = match body::to_bytes(body).await {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            error!("Could not read response body for ETag: {}", e);
+            return Ok(Response::from_parts(parts, Body::empty()));
+        }
+    };
+    let etag = compute_etag(&bytes);
+    let etag_value = match HeaderValue::from_str(&etag) {
+        Ok(value) => value,
+        Err(_) => return Ok(Response::from_parts(parts, Body::from(bytes))),
+    };
+
+    if if_none_match.map(|tags| matches_etag(&tags, &etag)).unwrap_or(false) {
+        let mut not_modified = Response::new(Body::empty());
+        *not_modified.status_mut() = StatusCode::NOT_MODIFIED;
+        not_modified.headers_mut().insert(header::ETAG, etag_value);
+        return Ok(not_modified);
+    }
+    parts.headers.insert(header::ETAG, etag_value);
+    Ok(Response::from_parts(parts, Body::from(bytes)))
+}
+
+/// Event streams never end, so their body cannot be buffered for hashing.
+fn is_event_stream(response: &Response<Body>) -> bool {
+    response.headers()
+        .get(header::CONTENT_TYPE)
+        .map(|v| v.as_bytes().starts_with(b"text/event-stream"))
+        .unwrap_or(false)
+}
+
+fn compute_etag(body: &[u8]) -> String {
+    let mut hasher = DefaultHasher::new();
+    body.hash(&mut hasher);
+    format!("W/\"{:016x}\"", hasher.finish())
+}
+
+/// Weak comparison against the comma separated tags of an `If-None-Match` header.
+fn matches_etag(if_none_match: &str, etag: &str) -> bool {
+    let opaque = etag.trim_start_matches("W/");
+    if_none_match
+        .split(',')
+        .map(str::trim)
+        .any(|tag| tag == "*" || tag.trim_start_matches("W/") == opaque)
+}
@@ -0,0 +1,294 @@
+use flate2::write::DeflateEncoder;
+use flate2::{Compression, Crc};
+use serde::Deserialize;
+use std::io::Write;
+
+use crate::feed::escape;
+use crate::model::{Category, Money, Product};
+use crate::{Error, Result};
+
+const CSV_CONTENT_TYPE: &str = "text/csv; charset=utf-8";
+const XLSX_CONTENT_TYPE: &str = "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet";
+const COLUMNS: [&str; 6] = ["Name", "Price", "Currency", "Source", "URL", "Category"];
+
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    Csv,
+    Xlsx,
+}
+
+/// Downloadable file an export is served as.
+pub struct ExportFile {
+    content_type: &'static str,
+    filename: String,
+    body: Vec<u8>,
+}
+
+/// One product of the export, with its source and category resolved to names.
+struct ExportRow<'a> {
+    name: &'a str,
+    price: Option<Money>,
+    source: &'a str,
+    url: &'a str,
+    category: &'a str,
+}
+
+impl ExportFormat {
+    pub fn get_extension(self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::Xlsx => "xlsx",
+        }
+    }
+}
+
+impl ExportFile {
+    pub fn get_content_type(&self) -> &'static str {
+        self.content_type
+    }
+
+    /// `Content-Disposition` header value offering the file as a download.
+    pub fn get_disposition(&self) -> String {
+        format!("attachment; filename=\"{}\"", self.filename)
+    }
+
+    pub fn into_body(self) -> Vec<u8> {
+        self.body
+    }
+}
+
+/// Renders the products of a wishlist in `format`, `name` being the file
+/// name without its extension.
+pub fn render_export(format: ExportFormat, name: &str, products: &[Product], categories: &[Category]) -> Result<ExportFile> {
+    let rows: Vec<ExportRow> = products.iter().map(|p| ExportRow::new(p, categories)).collect();
+    let (content_type, body) = match format {
+        ExportFormat::Csv => (CSV_CONTENT_TYPE, render_csv(&rows)),
+        ExportFormat::Xlsx => (XLSX_CONTENT_TYPE, render_xlsx(&rows)?),
+    };
+    Ok(ExportFile {
+        content_type,
+        filename: format!("{}.{}", name, format.get_extension()),
+        body,
+    })
+}
+
+impl<'a> ExportRow<'a> {
+    fn new(product: &'a Product, categories: &'a [Category]) -> Self {
+        let category = product.get_category_id()
+            .and_then(|id| categories.iter().find(|c| c.get_id() == Some(id)))
+            .and_then(|c| c.get_name());
+        ExportRow {
+            name: product.get_name().unwrap_or_default(),
+            price: product.get_price().map(|price| Money::new(price.into(), product.get_currency())),
+            source: product.get_source().and_then(|s| s.get_name()).unwrap_or_default(),
+            url: product.get_url().unwrap_or_default(),
+            category: category.unwrap_or_default(),
+        }
+    }
+}
+
+/// Renders the rows as RFC 4180 CSV with a header line.
+fn render_csv(rows: &[ExportRow]) -> Vec<u8> {
+    let mut csv = String::new();
+    push_csv_line(&mut csv, COLUMNS.iter().map(|c| c.to_string()));
+    for row in rows {
+        push_csv_line(&mut csv, vec![
+            csv_text(row.name),
+            row.price.map(|p| p.format_amount()).unwrap_or_default(),
+            row.price.map(|p| p.get_currency().get_code().to_string()).unwrap_or_default(),
+            csv_text(row.source),
+            csv_text(row.url),
+            csv_text(row.category),
+        ]);
+    }
+    csv.into_bytes()
+}
+
+fn push_csv_line<I: IntoIterator<Item = String>>(csv: &mut String, fields: I) {
+    let fields: Vec<String> = fields.into_iter().map(|f| quote_csv(&f)).collect();
+    csv.push_str(&fields.join(","));
+    csv.push_str("\r\n");
+}
+
+fn quote_csv(field: &str) -> String {
+    if field.contains([',', '"', '\r', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// Keeps spreadsheets from evaluating scraped texts like `=HYPERLINK(..)` as formulas.
+fn csv_text(text: &str) -> String {
+    if text.starts_with(['=', '+', '-', '@']) {
+        format!("'{}", text)
+    } else {
+        text.to_string()
+    }
+}
+
+/// Renders the rows as a workbook with a single sheet, the prices as numbers.
+fn render_xlsx(rows: &[ExportRow]) -> Result<Vec<u8>> {
+    let mut sheet = String::new();
+    sheet.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>\n");
+    sheet.push_str("<worksheet xmlns=\"http://schemas.openxmlformats.org/spreadsheetml/2006/main\"><sheetData>");
+    push_xlsx_row(&mut sheet, 1, COLUMNS.iter().map(|c| XlsxCell::Text(c)).collect());
+    for (i, row) in rows.iter().enumerate() {
+        let price = row.price.map(|p| p.format_amount());
+        push_xlsx_row(&mut sheet, i + 2, vec![
+            XlsxCell::Text(row.name),
+            price.as_deref().map(XlsxCell::Number).unwrap_or(XlsxCell::Empty),
+            row.price.map(|p| XlsxCell::Text(p.get_currency().get_code())).unwrap_or(XlsxCell::Empty),
+            XlsxCell::Text(row.source),
+            XlsxCell::Text(row.url),
+            XlsxCell::Text(row.category),
+        ]);
+    }
+    sheet.push_str("</sheetData></worksheet>\n");
+
+    let mut zip = ZipWriter::default();
+    zip.add_file("[Content_Types].xml", XLSX_CONTENT_TYPES.as_bytes())?;
+    zip.add_file("_rels/.rels", XLSX_RELS.as_bytes())?;
+    zip.add_file("xl/workbook.xml", XLSX_WORKBOOK.as_bytes())?;
+    zip.add_file("xl/_rels/workbook.xml.rels", XLSX_WORKBOOK_RELS.as_bytes())?;
+    zip.add_file("xl/worksheets/sheet1.xml", sheet.as_bytes())?;
+    Ok(zip.finish())
+}
+
+enum XlsxCell<'a> {
+    Text(&'a str),
+    Number(&'a str),
+    Empty,
+}
+
+fn push_xlsx_row(sheet: &mut String, number: usize, cells: Vec<XlsxCell>) {
+    sheet.push_str(&format!("<row r=\"{}\">", number));
+    for (column, cell) in (b'A'..).zip(cells) {
+        let reference = format!("{}{}", column as char, number);
+        match cell {
+            XlsxCell::Text(text) if !text.is_empty() => sheet.push_str(&format!(
+                "<c r=\"{}\" t=\"inlineStr\"><is><t>{}</t></is></c>",
+                reference,
+                escape(&strip_control_characters(text))
+            )),
+            XlsxCell::Number(number) => sheet.push_str(&format!("<c r=\"{}\"><v>{}</v></c>", reference, number)),
+            _ => {}
+        }
+    }
+    sheet.push_str("</row>");
+}
+
+/// XML 1.0 can't contain control characters other than tabs and line breaks.
+fn strip_control_characters(text: &str) -> String {
+    text.chars().filter(|c| !c.is_control() || matches!(c, '\t' | '\n' | '\r')).collect()
+}
+
+const XLSX_CONTENT_TYPES: &str = concat!(
+    "<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>\n",
+    "<Types xmlns=\"http://schemas.openxmlformats.org/package/2006/content-types\">",
+    "<Default Extension=\"rels\" ContentType=\"application/vnd.openxmlformats-package.relationships+xml\"/>",
+    "<Default Extension=\"xml\" ContentType=\"application/xml\"/>",
+    "<Override PartName=\"/xl/workbook.xml\" ContentType=\"application/vnd.openxmlformats-officedocument.spreadsheetml.sheet.main+xml\"/>",
+    "<Override PartName=\"/xl/worksheets/sheet1.xml\" ContentType=\"application/vnd.openxmlformats-officedocument.spreadsheetml.worksheet+xml\"/>",
+    "</Types>\n",
+);
+
+const XLSX_RELS: &str = concat!(
+    "<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>\n",
+    "<Relationships xmlns=\"http://schemas.openxmlformats.org/package/2006/relationships\">",
+    "<Relationship Id=\"rId1\" Type=\"http://schemas.openxmlformats.org/officeDocument/2006/relationships/officeDocument\" Target=\"xl/workbook.xml\"/>",
+    "</Relationships>\n",
+);
+
+const XLSX_WORKBOOK: &str = concat!(
+    "<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>\n",
+    "<workbook xmlns=\"http://schemas.openxmlformats.org/spreadsheetml/2006/main\" ",
+    "xmlns:r=\"http://schemas.openxmlformats.org/officeDocument/2006/relationships\">",
+    "<sheets><sheet name=\"Wishlist\" sheetId=\"1\" r:id=\"rId1\"/></sheets>",
+    "</workbook>\n",
+);
+
+const XLSX_WORKBOOK_RELS: &str = concat!(
+    "<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>\n",
+    "<Relationships xmlns=\"http://schemas.openxmlformats.org/package/2006/relationships\">",
+    "<Relationship Id=\"rId1\" Type=\"http://schemas.openxmlformats.org/officeDocument/2006/relationships/worksheet\" Target=\"worksheets/sheet1.xml\"/>",
+    "</Relationships>\n",
+);
+
+/// Writes a zip archive of deflated files, as much of the format as
+/// an xlsx workbook needs.
+#[derive(Default)]
+struct ZipWriter {
+    data: Vec<u8>,
+    directory: Vec<u8>,
+    count: u16,
+}
+
+impl ZipWriter {
+    // 1980-01-01 00:00 in MS-DOS format, the files have no meaningful modification time.
+    const DOS_TIME: u16 = 0;
+    const DOS_DATE: u16 = (1 << 5) | 1;
+
+    fn add_file(&mut self, name: &str, content: &[u8]) -> Result<()> {
+        let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+        let compressed = encoder.write_all(content)
+            .and_then(|_| encoder.finish())
+            .map_err(|e| Error::Internal(format!("couldn't compress {}: {}", name, e)))?;
+        let mut crc = Crc::new();
+        crc.update(content);
+        let offset = self.data.len() as u32;
+
+        let mut header = Vec::new();
+        push_u16(&mut header, 20);
+        push_u16(&mut header, 0);
+        push_u16(&mut header, 8);
+        push_u16(&mut header, Self::DOS_TIME);
+        push_u16(&mut header, Self::DOS_DATE);
+        push_u32(&mut header, crc.sum());
+        push_u32(&mut header, compressed.len() as u32);
+        push_u32(&mut header, content.len() as u32);
+        push_u16(&mut header, name.len() as u16);
+        push_u16(&mut header, 0);
+
+        push_u32(&mut self.data, 0x0403_4b50);
+        self.data.extend_from_slice(&header);
+        self.data.extend_from_slice(name.as_bytes());
+        self.data.extend_from_slice(&compressed);
+
+        push_u32(&mut self.directory, 0x0201_4b50);
+        push_u16(&mut self.directory, 20);
+        self.directory.extend_from_slice(&header);
+        push_u16(&mut self.directory, 0);
+        push_u16(&mut self.directory, 0);
+        push_u16(&mut self.directory, 0);
+        push_u32(&mut self.directory, 0);
+        push_u32(&mut self.directory, offset);
+        self.directory.extend_from_slice(name.as_bytes());
+        self.count += 1;
+        Ok(())
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        let offset = self.data.len() as u32;
+        let size = self.directory.len() as u32;
+        self.data.append(&mut self.directory);
+        push_u32(&mut self.data, 0x0605_4b50);
+        push_u16(&mut self.data, 0);
+        push_u16(&mut self.data, 0);
+        push_u16(&mut self.data, self.count);
+        push_u16(&mut self.data, self.count);
+        push_u32(&mut self.data, size);
+        push_u32(&mut self.data, offset);
+        push_u16(&mut self.data, 0);
+        self.data
+    }
+}
+
+fn push_u16(data: &mut Vec<u8>, value: u16) {
+    data.extend_from_slice(&value.to_le_bytes());
+}
+
+fn push_u32(data: &mut Vec<u8>, value: u32) {
+    data.extend_from_slice(&value.to_le_bytes());
+}
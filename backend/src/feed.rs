@@ -0,0 +1,66 @@
+use chrono::{TimeZone, Utc};
+
+use crate::model::{Money, Product};
+
+/// Renders products as an RSS 2.0 feed, newest first.
+pub fn render_rss(title: &str, link: &str, description: &str, products: &[Product]) -> String {
+    let mut xml = String::new();
+    xml.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    xml.push_str("<rss version=\"2.0\">\n<channel>\n");
+    push_element(&mut xml, "title", title);
+    push_element(&mut xml, "link", link);
+    push_element(&mut xml, "description", description);
+    if let Some(newest) = products.iter().filter_map(|p| p.get_first_seen()).max() {
+        push_element(&mut xml, "lastBuildDate", &format_date(newest));
+    }
+    for product in products {
+        xml.push_str("<item>\n");
+        push_element(&mut xml, "title", product.get_name().unwrap_or_default());
+        if let Some(url) = product.get_url() {
+            push_element(&mut xml, "link", url);
+        }
+        if let Some(id) = product.get_id() {
+            xml.push_str(&format!("<guid isPermaLink=\"false\">{}</guid>\n", id.to_hex()));
+        }
+        push_element(&mut xml, "description", &render_item_description(product));
+        if let Some(first_seen) = product.get_first_seen() {
+            push_element(&mut xml, "pubDate", &format_date(first_seen));
+        }
+        xml.push_str("</item>\n");
+    }
+    xml.push_str("</channel>\n</rss>\n");
+    xml
+}
+
+fn render_item_description(product: &Product) -> String {
+    let mut html = String::new();
+    if let Some(url_img) = product.get_url_img().filter(|u| !u.is_empty()) {
+        html.push_str(&format!("<img src=\"{}\"/><br/>", escape(url_img)));
+    }
+    if let Some(price) = product.get_price() {
+        html.push_str(&format!("Price: {}", Money::new(price.into(), product.get_currency())));
+    }
+    if let Some(source) = product.get_source().and_then(|s| s.get_name()) {
+        html.push_str(&format!(" ({})", escape(source)));
+    }
+    html
+}
+
+fn push_element(xml: &mut String, name: &str, content: &str) {
+    xml.push_str(&format!("<{0}>{1}</{0}>\n", name, escape(content)));
+}
+
+fn format_date(timestamp: i32) -> String {
+    Utc.timestamp_opt(timestamp as i64, 0)
+        .single()
+        .map(|d| d.to_rfc2822())
+        .unwrap_or_default()
+}
+
+pub(crate) fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
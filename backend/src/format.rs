@@ -0,0 +1,99 @@
+use std::convert::Infallible;
+use warp::http::{header, HeaderValue, Response};
+use warp::hyper::{body, Body};
+use warp::Reply;
+
+const JSON_TYPE: &[u8] = b"application/json";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Format {
+    Json,
+    MessagePack,
+    Cbor,
+}
+
+/// Sends JSON responses as MessagePack or CBOR if the client prefers one of
+/// them in `Accept`, falling back to JSON. The body keeps its structure, only
+/// the encoding changes.
+pub async fn apply_format<R: Reply>(accept: Option<String>, reply: R) -> Result<Response<Body>, Infallible> {
+    let response = reply.into_response();
+    if !is_json(&response) {
+        return Ok(response);
+    }
+    let (mut parts, body) = response.into_parts();
+    parts.headers.append(header::VARY, HeaderValue::from_static("accept"));
+    let format = accept.as_deref().map(negotiate).unwrap_or(Format::Json);
+    if format == Format::Json {
+        return Ok(Response::from_parts(parts, body));
+    }
+
+    let bytes = match body::to_bytes(body).await {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            error!("Could not read response body for {}: {}", format.content_type(), e);
+            return Ok(Response::from_parts(parts, Body::empty()));
+        }
+    };
+    match transcode(&bytes, format) {
+        Ok(encoded) => {
+            parts.headers.insert(header::CONTENT_TYPE, HeaderValue::from_static(format.content_type()));
+            parts.headers.remove(header::CONTENT_LENGTH);
+            Ok(Response::from_parts(parts, Body::from(encoded)))
+        }
+        Err(e) => {
+            error!("Could not encode response body as {}: {}", format.content_type(), e);
+            Ok(Response::from_parts(parts, Body::from(bytes)))
+        }
+    }
+}
+
+impl Format {
+    fn content_type(self) -> &'static str {
+        match self {
+            Format::Json => "application/json",
+            Format::MessagePack => "application/msgpack",
+            Format::Cbor => "application/cbor",
+        }
+    }
+}
+
+fn is_json(response: &Response<Body>) -> bool {
+    response.headers()
+        .get(header::CONTENT_TYPE)
+        .map(|v| v.as_bytes().starts_with(JSON_TYPE))
+        .unwrap_or(false)
+}
+
+/// Picks the format with the highest `q` value, the first one listed wins
+/// ties. Wildcards and unknown types count as JSON.
+fn negotiate(accept: &str) -> Format {
+    let mut best: Option<(Format, f32)> = None;
+    for entry in accept.split(',') {
+        let mut params = entry.split(';');
+        let format = match params.next().unwrap_or_default().trim() {
+            "application/msgpack" | "application/x-msgpack" | "application/vnd.msgpack" => Format::MessagePack,
+            "application/cbor" => Format::Cbor,
+            "application/json" | "application/*" | "*/*" => Format::Json,
+            _ => continue,
+        };
+        let quality = params
+            .find_map(|param| param.trim().strip_prefix("q="))
+            .and_then(|q| q.trim().parse().ok())
+            .unwrap_or(1.);
+        if quality > 0. && best.map(|(_, best_quality)| quality > best_quality).unwrap_or(true) {
+            best = Some((format, quality));
+        }
+    }
+    best.map(|(format, _)| format).unwrap_or(Format::Json)
+}
+
+fn transcode(json: &[u8], format: Format) -> Result<Vec<u8>, String> {
+    let value: serde_json::Value = serde_json::from_slice(json).map_err(|e| e.to_string())?;
+    let mut encoded = Vec::new();
+    match format {
+        Format::Json => return Ok(json.to_vec()),
+        Format::MessagePack => rmp_serde::encode::write_named(&mut encoded, &value).map_err(|e| e.to_string())?,
+        Format::Cbor => ciborium::ser::into_writer(&value, &mut encoded).map_err(|e| e.to_string())?,
+    }
+    Ok(encoded)
+}
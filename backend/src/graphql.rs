@@ -0,0 +1,454 @@
+use async_graphql::{Context, EmptyMutation, EmptySubscription, ErrorExtensions, Object, Schema, ID};
+use mongodb::bson::oid::ObjectId;
+use std::sync::Arc;
+
+use crate::auth::Identity;
+use crate::handler::*;
+use crate::model::{Availability, Category, Enrichment, ErrorMessage, List, Loaded, Offer, Page, PricePoint, Priority, Product, Source, Wishlist, DEFAULT_LIST};
+use crate::policy::hide_reservations;
+use crate::query::{ArchiveFilterQuery, FieldsQuery, ListQuery, OfferQuery, SearchQuery, SortQuery, TagQuery};
+use crate::repository::WishlistRepository;
+use crate::views::ViewCounter;
+use crate::Error;
+
+/// Queries nested deeper than this are rejected before they are executed.
+const MAX_DEPTH: usize = 8;
+const MAX_COMPLEXITY: usize = 500;
+
+pub type WishlistSchema = Schema<QueryRoot, EmptyMutation, EmptySubscription>;
+
+/// Builds the read-only schema over `Wishlist`, `Product`, `Category` and `Source`.
+///
+/// Resolvers go through the same handlers as the REST routes, so products
+/// come with their source and reservation state already loaded. The sender's
+/// `Identity`, if any, is passed as request data and decides which private
+/// lists are visible.
+pub fn create_schema(repo: Arc<dyn WishlistRepository>, views: ViewCounter) -> WishlistSchema {
+    Schema::build(QueryRoot, EmptyMutation, EmptySubscription)
+        .data(repo)
+        .data(views)
+        .limit_depth(MAX_DEPTH)
+        .limit_complexity(MAX_COMPLEXITY)
+        .finish()
+}
+
+pub struct QueryRoot;
+
+#[Object]
+impl QueryRoot {
+    /// The default list followed by the named ones.
+    async fn lists(&self, ctx: &Context<'_>) -> async_graphql::Result<Vec<List>> {
+        value(handle_get_lists(identity(ctx), repo(ctx)).await)
+    }
+
+    /// Newest snapshot of the list with the slug `list`.
+    async fn last_wishlist(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(default_with = "default_list()")] list: String,
+        #[graphql(default)] collapse_duplicates: bool,
+        in_stock: Option<bool>,
+    ) -> async_graphql::Result<Wishlist> {
+        value(handle_get_last_wishlist(list, identity(ctx), OfferQuery::new(collapse_duplicates, in_stock), views(ctx), repo(ctx)).await)
+    }
+
+    /// Products added with the newest snapshots of `list`, only those tagged `tag` if set.
+    async fn newest_products(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(default_with = "default_list()")] list: String,
+        tag: Option<String>,
+    ) -> async_graphql::Result<Vec<Product>> {
+        value(handle_get_newest_products(list, identity(ctx), TagQuery::new(tag), views(ctx), repo(ctx)).await)
+    }
+
+    /// Current products of `list`, most viewed first.
+    async fn popular_products(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(default_with = "default_list()")] list: String,
+        #[graphql(default)] offset: u64,
+        #[graphql(default = 10)] size: u64,
+        #[graphql(default)] collapse_duplicates: bool,
+        in_stock: Option<bool>,
+    ) -> async_graphql::Result<Vec<Product>> {
+        let offers = OfferQuery::new(collapse_duplicates, in_stock);
+        value(handle_get_popular_products(list, identity(ctx), ListQuery::new(offset, size), offers, FieldsQuery::default(), repo(ctx)).await.map(Page::into_items))
+    }
+
+    /// Products no longer on `list`. Prices are euro cents, `source` is a source name.
+    #[allow(clippy::too_many_arguments)]
+    async fn archived_products(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(default_with = "default_list()")] list: String,
+        #[graphql(default)] offset: u64,
+        #[graphql(default = 10)] size: u64,
+        min_price: Option<i32>,
+        max_price: Option<i32>,
+        source: Option<String>,
+        tag: Option<String>,
+        #[graphql(default)] collapse_duplicates: bool,
+        in_stock: Option<bool>,
+    ) -> async_graphql::Result<Vec<Product>> {
+        let page = ListQuery::new(offset, size);
+        let filter = ArchiveFilterQuery::new(min_price, max_price, source, tag)
+            .with_collapsed_duplicates(collapse_duplicates)
+            .with_in_stock(in_stock);
+        value(handle_get_archived_products(list, identity(ctx), page, filter, SortQuery::default(), FieldsQuery::default(), views(ctx), repo(ctx)).await.map(Page::into_items))
+    }
+
+    /// Full text search over product names and descriptions.
+    async fn search_products(
+        &self,
+        ctx: &Context<'_>,
+        query: String,
+        #[graphql(default)] offset: u64,
+        #[graphql(default = 10)] size: u64,
+    ) -> async_graphql::Result<Vec<Product>> {
+        value(handle_search_products(identity(ctx), SearchQuery::new(query, offset, size), views(ctx), repo(ctx)).await)
+    }
+
+    async fn product(&self, ctx: &Context<'_>, id: ID) -> async_graphql::Result<Option<Product>> {
+        let repo = repo(ctx);
+        let id = parse_id(&id)?;
+        let product = match repo.get_product_by_id(&id).await.map_err(to_graphql_error)? {
+            Some(product) => product,
+            None => return Ok(None),
+        };
+        let products = retain_readable(repo.as_ref(), identity(ctx).as_ref(), Loaded::new(vec![product], 0))
+            .await
+            .map_err(to_graphql_error)?;
+        let products = with_details(repo.as_ref(), products).await.map_err(to_graphql_error)?;
+        let mut products = value(hide_reservations(repo.as_ref(), identity(ctx).as_ref(), products).await)?;
+        Ok(products.pop())
+    }
+
+    async fn categories(&self, ctx: &Context<'_>) -> async_graphql::Result<Vec<Category>> {
+        value(repo(ctx).get_categories().await)
+    }
+
+    async fn category(&self, ctx: &Context<'_>, name: String) -> async_graphql::Result<Option<Category>> {
+        repo(ctx).get_category_by_name(&name).await.map_err(to_graphql_error)
+    }
+}
+
+#[Object]
+impl List {
+    async fn slug(&self) -> &str {
+        self.get_slug()
+    }
+    async fn name(&self) -> Option<&str> {
+        self.get_name()
+    }
+    /// Whether the reservations are hidden from the owner.
+    async fn secret_santa(&self) -> bool {
+        self.is_secret_santa()
+    }
+}
+
+#[Object]
+impl Wishlist {
+    /// Unix timestamp of the snapshot.
+    async fn timestamp(&self) -> Option<i32> {
+        self.get_timestamp()
+    }
+
+    async fn products(&self) -> Vec<Product> {
+        self.get_products().map(|p| p.to_vec()).unwrap_or_default()
+    }
+}
+
+#[Object]
+impl Product {
+    async fn id(&self) -> Option<ID> {
+        self.get_id().map(to_id)
+    }
+    async fn name(&self) -> Option<&str> {
+        self.get_name()
+    }
+    async fn description(&self) -> Option<&str> {
+        self.get_description()
+    }
+    /// Remarks of the owner for gift-givers, e.g. the size or preferred color.
+    async fn notes(&self) -> Option<&str> {
+        self.get_notes()
+    }
+    /// Price when first seen in the minor unit of `currency`.
+    async fn price(&self) -> Option<i32> {
+        self.get_price()
+    }
+    /// Newest known price in the minor unit of `currency`.
+    async fn current_price(&self) -> Option<i32> {
+        self.get_current_price()
+    }
+    /// Lowest price ever seen in the minor unit of `currency`.
+    async fn lowest_price(&self) -> Option<i32> {
+        self.get_lowest_price()
+    }
+    /// Whether the last recorded price was a new all-time low.
+    async fn at_all_time_low(&self) -> bool {
+        self.is_at_all_time_low()
+    }
+    async fn price_threshold(&self) -> Option<i32> {
+        self.get_price_threshold()
+    }
+    /// Percent taken off the regular price during a sale, `null` if the source doesn't show it.
+    async fn discount_percent(&self) -> Option<i32> {
+        self.get_discount_percent()
+    }
+    async fn tags(&self) -> &[String] {
+        self.get_tags()
+    }
+    /// ISO 4217 code of the currency of the prices.
+    async fn currency(&self) -> &'static str {
+        self.get_currency().get_code()
+    }
+    async fn quantity(&self) -> Option<i32> {
+        self.get_quantity()
+    }
+    /// `must-have` or `nice-to-have`, `null` if not set.
+    async fn priority(&self) -> Option<&'static str> {
+        self.get_priority().map(Priority::get_code)
+    }
+    /// How much the owner wants the product, from 1 to 5.
+    async fn rating(&self) -> Option<i32> {
+        self.get_rating()
+    }
+    async fn stars(&self) -> Option<i32> {
+        self.get_stars()
+    }
+    async fn url(&self) -> Option<&str> {
+        self.get_url()
+    }
+    async fn url_img(&self) -> Option<&str> {
+        self.get_url_img()
+    }
+    /// European article number, identifying the same item across shops.
+    async fn ean(&self) -> Option<&str> {
+        self.get_ean()
+    }
+    /// Details looked up by the `ean`, `null` if no provider knows the item.
+    async fn enrichment(&self) -> Option<&Enrichment> {
+        self.get_enrichment()
+    }
+    /// Cheapest offer of the same item from another source, `null` if this is the cheapest.
+    async fn duplicate_of(&self) -> Option<ID> {
+        self.get_duplicate_of().map(to_id)
+    }
+    async fn first_seen(&self) -> Option<i32> {
+        self.get_first_seen()
+    }
+    async fn last_seen(&self) -> Option<i32> {
+        self.get_last_seen()
+    }
+    async fn reserved(&self) -> bool {
+        self.is_reserved()
+    }
+    /// Share of the target pledged toward a group gift, `null` without a pledge pool.
+    async fn funded_percent(&self) -> Option<i32> {
+        self.get_funded_percent().map(|percent| percent as i32)
+    }
+    /// `in-stock` or `out-of-stock` as last seen by the scraper, `null` if unknown.
+    async fn availability(&self) -> Option<&'static str> {
+        self.get_availability().map(Availability::get_code)
+    }
+    /// Whether the product can be bought, i.e. isn't out of stock.
+    async fn buyable(&self) -> bool {
+        self.is_buyable()
+    }
+    /// Whether the link check found the `url` gone.
+    async fn link_dead(&self) -> bool {
+        self.is_link_dead()
+    }
+    /// Slug of the list the product belongs to.
+    async fn list(&self) -> &str {
+        self.get_list()
+    }
+
+    async fn source(&self) -> Option<&Source> {
+        self.get_source()
+    }
+    /// Every shop selling the product, the cheapest available offer first.
+    async fn offers(&self) -> &[Offer] {
+        self.get_offers()
+    }
+    async fn cheapest_offer(&self) -> Option<&Offer> {
+        self.get_cheapest_offer()
+    }
+
+    async fn category(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<Category>> {
+        match self.get_category_id() {
+            Some(id) => repo(ctx).get_category_by_id(id).await.map_err(to_graphql_error),
+            None => Ok(None),
+        }
+    }
+
+    /// Price history, oldest first.
+    async fn prices(&self, ctx: &Context<'_>) -> async_graphql::Result<Vec<PricePoint>> {
+        match self.get_id() {
+            Some(id) => value(handle_get_product_prices(id.to_hex(), identity(ctx), repo(ctx)).await),
+            None => Ok(Vec::new()),
+        }
+    }
+}
+
+#[Object]
+impl Category {
+    async fn id(&self) -> Option<ID> {
+        self.get_id().map(to_id)
+    }
+    async fn name(&self) -> Option<&str> {
+        self.get_name()
+    }
+    /// Name of the icon or an emoji to show along with the name.
+    async fn icon(&self) -> Option<&str> {
+        self.get_icon()
+    }
+    /// Hex color like `#1e90ff`.
+    async fn color(&self) -> Option<&str> {
+        self.get_color()
+    }
+    async fn description(&self) -> Option<&str> {
+        self.get_description()
+    }
+    /// Position among its siblings, lower first.
+    async fn sort_order(&self) -> i32 {
+        self.get_sort_order()
+    }
+    /// Category this one is nested in, `null` for top level categories.
+    async fn parent(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<Category>> {
+        match self.get_parent_id() {
+            Some(id) => repo(ctx).get_category_by_id(id).await.map_err(to_graphql_error),
+            None => Ok(None),
+        }
+    }
+
+    async fn products(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(default)] offset: u64,
+        #[graphql(default = 10)] size: u64,
+    ) -> async_graphql::Result<Vec<Product>> {
+        let repo = repo(ctx);
+        let lists = get_readable_slugs(repo.as_ref(), identity(ctx).as_ref()).await.map_err(to_graphql_error)?;
+        let products = repo.get_products_by_category(self.get_id().map(std::slice::from_ref), Some(&lists), &SortQuery::default(), offset, size)
+            .await
+            .map_err(to_graphql_error)?;
+        let products = with_details(repo.as_ref(), products).await.map_err(to_graphql_error)?;
+        value(hide_reservations(repo.as_ref(), identity(ctx).as_ref(), products).await)
+    }
+
+    async fn product_count(&self, ctx: &Context<'_>) -> async_graphql::Result<u64> {
+        let repo = repo(ctx);
+        let lists = get_readable_slugs(repo.as_ref(), identity(ctx).as_ref()).await.map_err(to_graphql_error)?;
+        repo.count_products_by_category(self.get_id().map(std::slice::from_ref), Some(&lists)).await.map_err(to_graphql_error)
+    }
+}
+
+#[Object]
+impl Source {
+    async fn name(&self) -> Option<&str> {
+        self.get_name()
+    }
+    async fn url(&self) -> Option<&str> {
+        self.get_url()
+    }
+}
+
+#[Object]
+impl Offer {
+    async fn source(&self) -> Option<&Source> {
+        self.get_source()
+    }
+    async fn url(&self) -> Option<&str> {
+        self.get_url()
+    }
+    /// Price in the minor unit of the product's currency.
+    async fn price(&self) -> Option<i32> {
+        self.get_price()
+    }
+    /// `in-stock` or `out-of-stock`, `null` if unknown.
+    async fn availability(&self) -> Option<&'static str> {
+        self.get_availability().map(Availability::get_code)
+    }
+}
+
+#[Object]
+impl Enrichment {
+    /// `openlibrary` or `ean`.
+    async fn provider(&self) -> &str {
+        self.get_provider()
+    }
+    async fn title(&self) -> Option<&str> {
+        self.get_title()
+    }
+    async fn authors(&self) -> &[String] {
+        self.get_authors()
+    }
+    /// Publisher of a book, or brand of another item.
+    async fn publisher(&self) -> Option<&str> {
+        self.get_publisher()
+    }
+    async fn published(&self) -> Option<&str> {
+        self.get_published()
+    }
+    async fn pages(&self) -> Option<u32> {
+        self.get_pages()
+    }
+    async fn description(&self) -> Option<&str> {
+        self.get_description()
+    }
+    async fn cover(&self) -> Option<&str> {
+        self.get_cover()
+    }
+}
+
+#[Object]
+impl PricePoint {
+    /// Price in euro cents.
+    async fn price(&self) -> Option<i32> {
+        self.get_price()
+    }
+    async fn timestamp(&self) -> Option<i32> {
+        self.get_timestamp()
+    }
+}
+
+fn default_list() -> String {
+    DEFAULT_LIST.to_string()
+}
+
+fn repo(ctx: &Context<'_>) -> Arc<dyn WishlistRepository> {
+    ctx.data_unchecked::<Arc<dyn WishlistRepository>>().clone()
+}
+
+fn views(ctx: &Context<'_>) -> ViewCounter {
+    ctx.data_unchecked::<ViewCounter>().clone()
+}
+
+fn identity(ctx: &Context<'_>) -> Option<Identity> {
+    ctx.data_opt::<Identity>().cloned()
+}
+
+fn value<T>(result: crate::Result<Loaded<T>>) -> async_graphql::Result<T> {
+    result.map(Loaded::into_value).map_err(to_graphql_error)
+}
+
+fn parse_id(id: &ID) -> async_graphql::Result<ObjectId> {
+    ObjectId::with_string(id.as_str()).map_err(|e| to_graphql_error(e.into()))
+}
+
+fn to_id(id: &ObjectId) -> ID {
+    ID(id.to_hex())
+}
+
+/// Exposes the same message and status code as the REST error responses.
+fn to_graphql_error(err: Error) -> async_graphql::Error {
+    let message = ErrorMessage::from(&err);
+    if message.code >= 500 {
+        error!("GraphQL: {}", err);
+    }
+    let code = message.code;
+    async_graphql::Error::new(message.message).extend_with(|_, e| e.set("code", code))
+}
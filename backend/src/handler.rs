@@ -1,27 +1,346 @@
-use std;
-use std::collections::btree_map::{Entry, BTreeMap};
-use mongodb::{bson::{doc, oid::ObjectId, document::Document} , options::{FindOptions, FindOneOptions}, Client, Cursor, Collection};
+use std::collections::btree_map::Entry;
+use std::collections::{BTreeMap, BTreeSet};
+use mongodb::bson::{doc, oid::ObjectId, document::Document, Bson};
 use std::sync::Arc;
-use tokio::stream::StreamExt;
+use std::time::Duration;
+use chrono::TimeZone;
+use percent_encoding::percent_decode_str;
 
 use super::{Result, Error};
-use crate::query::{CategoryQuery, ListQuery};
-use crate::model::{Category, Source, Wishlist, Product};
+use crate::audit::{actor_of, record_event, user_of, ADMIN, ANONYMOUS, SCRAPER};
+use crate::auth::{can_discover, can_read, can_reserve, hash_password, verify_password, Identity, SessionKeys};
+use crate::query::{ArchiveFilterQuery, ArchiveSearchQuery, CategoryListQuery, CategoryQuery, CurrencyQuery, DeleteCategoryQuery, DiffQuery, EventQuery, ExpandQuery, ExportQuery, FieldsQuery, IdsQuery, ImportQuery, LinkQuery, ListQuery, OfferQuery, Relation, ScrapeQuery, SearchQuery, SortField, SortOrder, SortQuery, SteamImportQuery, SuggestQuery, TagQuery, TimelineQuery};
+use crate::model::{normalize_tag, ActivityEntry, Affiliate, AffiliateInput, AuditEvent, Availability, Budget, BudgetInput, BulkUpdateInput, BulkUpdateReport, BulkUpdateStatus, Category, CategoryCount, CategoryInput, CategoryListing, CategoryMerge, CategoryNode, CategoryRule, ClickCount, Collaborator, CollaboratorInput, Comment, CommentInput, CommentReceipt, ConsistencyReport, Health, ImportReport, ImportRow, ImportStatus, IngestInput, List, ListInput, ListUpdateInput, Loaded, MaintenanceInput, MaintenanceStatus, Money, Page, PledgeInput, PledgePoolInput, PledgeReceipt, PledgeStatus, Politeness, PolitenessInput, PriceAlert, PriceAlertInput, PricePoint, PriceThresholdInput, Reservation, RuleInput, Session, ShareInput, ShareLink, Source, Stats, Stored, Suggestions, TagCount, TimelineEntry, User, UserInput, Wishlist, WishlistDiff, WishlistSummary, WishlistEvent, WishlistValue, WishInput, Product, ProductDetail, ProductInput, PushKey, PushSubscription, PushSubscriptionInput, PushUnsubscribeInput, RetentionPreview, ScrapeStatus, DEFAULT_LIST};
+use crate::repository::{current_timestamp, ProductFilter, WishlistRepository};
+use crate::check::check_consistency;
+use crate::classify::{classify_uncategorized, Classifier};
+use crate::currency::{convert_prices, Converter};
+use crate::export::{render_export, ExportFile};
+use crate::feed::render_rss;
+use crate::enrichment::{enrich_product, Enricher};
+use crate::images::{Image, ImageSize, ImageStore};
+use crate::ingest::{parse_shown_price, PageMetadata, MAX_PAGE_SIZE};
+use crate::jobs::Scraper;
+use crate::import::read_import_rows;
+use crate::maintenance::Maintenance;
+use crate::net::check_public_host;
+use crate::retention::RetentionPolicy;
+use crate::notify::Notifier;
+use crate::policy::{hide_reservations, ReservationPolicy};
+use crate::politeness::PoliteClient;
+use crate::steam::{is_steam_id, item_id_of, SteamClient, SteamPrice, STEAM_SOURCE};
+use crate::views::ViewCounter;
 
-pub async fn handle_get_last_wishlist(client: Arc<Client>) -> Result<Wishlist> {
-    let mut last_wishlist = get_last_wishlist(&client).await?;
-    load_wishlist(&client, &mut last_wishlist).await?;
-    Ok(last_wishlist)
+const FEED_SIZE: usize = 20;
+/// Snapshots the timeline looks back at, older changes aren't listed.
+const MAX_TIMELINE_SNAPSHOTS: u64 = 100;
+const MAX_SOURCE_NAME_LENGTH: usize = 100;
+const MAX_PREFIX_LENGTH: usize = 100;
+
+/// Reports whether requests can be served, i.e. the database is reachable.
+pub async fn handle_get_readiness(repo: Arc<dyn WishlistRepository>) -> Result<Health> {
+    match repo.ping().await {
+        Ok(()) => Ok(Health::new("ready")),
+        Err(e) => {
+            warn!("Readiness check failed: {}", e);
+            Err(Error::Unavailable("database is not reachable".to_string()))
+        }
+    }
+}
+
+pub async fn handle_register(input: UserInput, repo: Arc<dyn WishlistRepository>) -> Result<User> {
+    input.validate()?;
+    let password_hash = hash_password(input.get_password())?;
+    let id = repo.insert_user(input.to_document(password_hash, current_timestamp())?).await?;
+    record_event(repo.as_ref(), &format!("user:{}", id.to_hex()), "create", User::COLLECTION, Some(&id), doc! {"username": input.get_username()}).await;
+    repo.get_user_by_id(&id).await?
+        .ok_or(Error::NotFound("user"))
+}
+
+/// Issues a session for valid credentials. Unknown users and wrong passwords
+/// are rejected alike.
+pub async fn handle_login(input: UserInput, sessions: SessionKeys, repo: Arc<dyn WishlistRepository>) -> Result<Session> {
+    let user = repo.get_user_by_name(input.get_username()).await?
+        .ok_or(Error::Unauthorized)?;
+    if !verify_password(user.get_password_hash(), input.get_password()) {
+        return Err(Error::Unauthorized);
+    }
+    let id = user.get_id().ok_or(Error::FieldNotLoaded("user", "id"))?;
+    sessions.issue(id, user.get_username())
+}
+
+pub async fn handle_get_current_user(identity: Identity, repo: Arc<dyn WishlistRepository>) -> Result<User> {
+    let id = identity.get_user_id().ok_or(Error::NotFound("user"))?;
+    repo.get_user_by_id(id).await?
+        .ok_or(Error::NotFound("user"))
+}
+
+/// Returns the lists `identity` can find, the default list first. Unlisted
+/// lists are left out unless `identity` manages them or holds a share token.
+pub async fn handle_get_lists(identity: Option<Identity>, repo: Arc<dyn WishlistRepository>) -> Result<Loaded<Vec<List>>> {
+    let (mut lists, skipped) = get_all_lists(repo.as_ref()).await?.split();
+    lists.retain(|list| can_discover(identity.as_ref(), list));
+    Ok(Loaded::new(lists, skipped))
+}
+
+/// Changes the name, visibility or secret-santa mode of a list, only its
+/// owner or the admin may do so. The default list can't be changed.
+pub async fn handle_update_list(list: String, identity: Identity, input: ListUpdateInput, repo: Arc<dyn WishlistRepository>) -> Result<List> {
+    if list == DEFAULT_LIST {
+        return Err(Error::Forbidden("the default list can't be changed".to_string()));
+    }
+    get_administered_list(repo.as_ref(), &identity, &list).await?;
+    let document = input.to_document()?;
+    if !repo.update_list(&list, document.clone()).await? {
+        return Err(Error::NotFound("list"));
+    }
+    let mut payload = document;
+    payload.insert("slug", list.as_str());
+    record_event(repo.as_ref(), &actor_of(Some(&identity)), "update", List::COLLECTION, None, payload).await;
+    get_list(repo.as_ref(), &list).await
+}
+
+/// Creates a list owned by the user creating it, lists created with the API key have no owner.
+pub async fn handle_create_list(identity: Identity, input: ListInput, repo: Arc<dyn WishlistRepository>) -> Result<List> {
+    let document = input.to_document(identity.get_user_id())?;
+    if get_list(repo.as_ref(), input.get_slug()).await.is_ok() {
+        return Err(Error::Conflict(format!("list '{}' already exists", input.get_slug())));
+    }
+    let id = repo.insert_list(document.clone()).await?;
+    record_event(repo.as_ref(), &actor_of(Some(&identity)), "create", List::COLLECTION, Some(&id), document).await;
+    get_list(repo.as_ref(), input.get_slug()).await
+}
+
+/// Mints a share token for `list`, only its owner or the admin may do so.
+pub async fn handle_share_list(list: String, identity: Identity, input: ShareInput, sessions: SessionKeys, repo: Arc<dyn WishlistRepository>) -> Result<ShareLink> {
+    get_administered_list(repo.as_ref(), &identity, &list).await?;
+    let expires = input.get_expires(current_timestamp() as i64)?;
+    sessions.issue_share(&list, input.get_scope(), expires)
+}
+
+/// Lists the users invited to `list`, shown to its owner, the admin and the
+/// collaborators themselves.
+pub async fn handle_get_collaborators(list: String, identity: Identity, repo: Arc<dyn WishlistRepository>) -> Result<Vec<Collaborator>> {
+    let list = get_list(repo.as_ref(), &list).await?;
+    if !identity.can_administer(&list) && identity.get_role(&list).is_none() {
+        return Err(Error::Forbidden(format!("only the owner and collaborators can see who helps with the list '{}'", list.get_slug())));
+    }
+    Ok(list.get_collaborators().to_vec())
+}
+
+/// Invites the user `username` to `list` with the role of the input, or
+/// changes the role of a collaborator. Only the owner or the admin may do so.
+pub async fn handle_set_collaborator(list: String, username: String, identity: Identity, input: CollaboratorInput, repo: Arc<dyn WishlistRepository>) -> Result<Collaborator> {
+    if list == DEFAULT_LIST {
+        return Err(Error::Forbidden("the default list can't have collaborators".to_string()));
+    }
+    let stored = get_administered_list(repo.as_ref(), &identity, &list).await?;
+    let user = repo.get_user_by_name(&username).await?
+        .ok_or(Error::NotFound("user"))?;
+    let user_id = user.get_id().ok_or(Error::FieldNotLoaded("user", "id"))?;
+    if stored.get_owner() == Some(user_id) {
+        return Err(Error::Validation("the owner of a list can't be its collaborator".to_string()));
+    }
+    let document = input.to_document(user_id, user.get_username(), current_timestamp());
+    if !repo.set_collaborator(&list, document.clone()).await? {
+        return Err(Error::NotFound("list"));
+    }
+    let mut payload = document;
+    payload.insert("slug", list.as_str());
+    record_event(repo.as_ref(), &actor_of(Some(&identity)), "invite", List::COLLECTION, None, payload).await;
+    get_collaborator(repo.as_ref(), &list, user_id).await
+}
+
+/// Removes the user `username` from the collaborators of `list`. The owner
+/// and the admin may remove anybody, collaborators only themselves.
+pub async fn handle_remove_collaborator(list: String, username: String, identity: Identity, repo: Arc<dyn WishlistRepository>) -> Result<Collaborator> {
+    let stored = get_list(repo.as_ref(), &list).await?;
+    let collaborator = stored.get_collaborators().iter()
+        .find(|collaborator| collaborator.get_username() == username)
+        .cloned()
+        .ok_or(Error::NotFound("collaborator"))?;
+    if !identity.can_administer(&stored) && identity.get_user_id() != Some(collaborator.get_user()) {
+        return Err(Error::Forbidden(format!("only the owner can remove collaborators from the list '{}'", list)));
+    }
+    if !repo.remove_collaborator(&list, collaborator.get_user()).await? {
+        return Err(Error::NotFound("collaborator"));
+    }
+    let payload = doc! {"slug": &list, "user": collaborator.get_user().clone(), "username": &username};
+    record_event(repo.as_ref(), &actor_of(Some(&identity)), "remove", List::COLLECTION, None, payload).await;
+    Ok(collaborator)
+}
+
+pub async fn handle_get_last_wishlist(list: String, identity: Option<Identity>, offers: OfferQuery, views: ViewCounter, repo: Arc<dyn WishlistRepository>) -> Result<Loaded<Wishlist>> {
+    get_readable_list(repo.as_ref(), &list, identity.as_ref()).await?;
+    let mut last_wishlist = get_last_snapshot(repo.as_ref(), &list).await?;
+    let skipped = load_wishlist(repo.as_ref(), &mut last_wishlist).await?;
+    if let Some(products) = last_wishlist.get_products_mut() {
+        if offers.is_collapsing() {
+            collapse_duplicates(products);
+        }
+        if let Some(in_stock) = offers.get_in_stock() {
+            products.retain(|p| p.is_buyable() == in_stock);
+        }
+    }
+    views.record(last_wishlist.get_products().unwrap_or_default());
+    hide_reservations(repo.as_ref(), identity.as_ref(), Loaded::new(last_wishlist, skipped)).await
+}
+
+/// Exports the products of the latest snapshot of `list` as a CSV or XLSX download.
+pub async fn handle_export_wishlist(list: String, identity: Option<Identity>, query: ExportQuery, repo: Arc<dyn WishlistRepository>) -> Result<ExportFile> {
+    get_readable_list(repo.as_ref(), &list, identity.as_ref()).await?;
+    let mut last_wishlist = get_last_snapshot(repo.as_ref(), &list).await?;
+    load_wishlist(repo.as_ref(), &mut last_wishlist).await?;
+    let categories = repo.get_categories().await?.into_value();
+    let name = match last_wishlist.get_timestamp().and_then(|t| chrono::Utc.timestamp_opt(t as i64, 0).single()) {
+        Some(date) => format!("wishlist-{}-{}", list, date.format("%Y-%m-%d")),
+        None => format!("wishlist-{}", list),
+    };
+    render_export(query.get_format(), &name, last_wishlist.get_products().unwrap_or_default(), &categories)
+}
+
+/// Lists the snapshots of `list` newest first, without their products.
+pub async fn handle_get_wishlist_history(list: String, identity: Option<Identity>, query: ListQuery, repo: Arc<dyn WishlistRepository>) -> Result<Loaded<Vec<WishlistSummary>>> {
+    get_readable_list(repo.as_ref(), &list, identity.as_ref()).await?;
+    repo.get_wishlist_history(&list, query.get_offset(), query.get_size()).await
+}
+
+/// Who changed what on `list` and when, newest first, named by username.
+/// The reservations, pledges and comments of a secret-santa list are left out
+/// for its owner, so a page may come back with fewer entries than requested.
+pub async fn handle_get_list_activity(list: String, identity: Option<Identity>, query: ListQuery, repo: Arc<dyn WishlistRepository>) -> Result<Loaded<Vec<ActivityEntry>>> {
+    get_readable_list(repo.as_ref(), &list, identity.as_ref()).await?;
+    let policy = ReservationPolicy::load(repo.as_ref(), identity.as_ref()).await?;
+    let (mut events, skipped) = repo.get_list_events(&list, query.get_offset(), query.get_size()).await?.split();
+    events.retain(|event| !policy.hides_event(&list, event));
+
+    let product_ids: Vec<ObjectId> = events.iter()
+        .filter(|event| event.get_entity() == Product::COLLECTION)
+        .filter_map(|event| event.get_entity_id().cloned())
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect();
+    let product_names: BTreeMap<ObjectId, String> = repo.get_products_by_id(&product_ids).await?
+        .into_value()
+        .into_iter()
+        .filter_map(|product| Some((product.get_id()?.clone(), product.get_name()?.to_string())))
+        .collect();
+    let mut usernames: BTreeMap<ObjectId, Option<String>> = BTreeMap::new();
+    for user in events.iter().filter_map(|event| user_of(event.get_actor())) {
+        if let Entry::Vacant(entry) = usernames.entry(user) {
+            let username = repo.get_user_by_id(entry.key()).await?.map(|u| u.get_username().to_string());
+            entry.insert(username);
+        }
+    }
+
+    let entries = events.iter()
+        .map(|event| {
+            let author = user_of(event.get_actor()).and_then(|user| usernames.get(&user).cloned().flatten());
+            let product_name = event.get_entity_id()
+                .filter(|_| event.get_entity() == Product::COLLECTION)
+                .and_then(|id| product_names.get(id).cloned());
+            ActivityEntry::new(event, author, product_name)
+        })
+        .collect();
+    Ok(Loaded::new(entries, skipped))
+}
+
+/// Returns the snapshot of `list` in effect at `timestamp`, with its products loaded.
+pub async fn handle_get_wishlist_at(list: String, timestamp: i32, identity: Option<Identity>, repo: Arc<dyn WishlistRepository>) -> Result<Loaded<Wishlist>> {
+    get_readable_list(repo.as_ref(), &list, identity.as_ref()).await?;
+    let mut wishlist = repo.get_wishlist_at(&list, timestamp).await?
+        .ok_or(Error::NotFound("wishlist"))?;
+    let skipped = load_wishlist(repo.as_ref(), &mut wishlist).await?;
+    hide_reservations(repo.as_ref(), identity.as_ref(), Loaded::new(wishlist, skipped)).await
+}
+
+pub async fn handle_get_newest_products(list: String, identity: Option<Identity>, query: TagQuery, views: ViewCounter, repo: Arc<dyn WishlistRepository>) -> Result<Loaded<Vec<Product>>> {
+    get_readable_list(repo.as_ref(), &list, identity.as_ref()).await?;
+    let tag = query.get_tag().map(normalize_tag).transpose()?;
+    let (mut products, skipped) = get_newest_products(repo.as_ref(), &list).await?.split();
+    if let Some(tag) = tag {
+        products.retain(|p| p.get_tags().contains(&tag));
+    }
+    views.record(&products);
+    hide_reservations(repo.as_ref(), identity.as_ref(), Loaded::new(products, skipped)).await
+}
+
+/// Lists the current products of `list` most viewed first. Showing them
+/// doesn't count as a view, so they can't keep themselves on top.
+pub async fn handle_get_popular_products(list: String, identity: Option<Identity>, page: ListQuery, offers: OfferQuery, fields: FieldsQuery, repo: Arc<dyn WishlistRepository>) -> Result<Page<Product>> {
+    let fields = fields.get_fields()?;
+    get_readable_list(repo.as_ref(), &list, identity.as_ref()).await?;
+    let filter = ProductFilter::new(Some(list), None, None, None)
+        .with_archived(false)
+        .with_collapsed_duplicates(offers.is_collapsing())
+        .with_in_stock(offers.get_in_stock());
+    let sort = SortQuery::new(SortField::Views, SortOrder::Desc);
+    let products = repo.get_products(&filter.clone().with_fields(fields.clone()), &sort, page.get_offset(), page.get_size()).await?;
+    let products = with_details(repo.as_ref(), products).await?;
+    let total = repo.count_products(&filter).await?;
+    let page = Page::new(products, page.get_offset(), page.get_size(), total).with_fields(fields);
+    hide_reservations(repo.as_ref(), identity.as_ref(), page).await
+}
+
+pub async fn handle_get_feed(list: String, identity: Option<Identity>, host: Option<String>, repo: Arc<dyn WishlistRepository>) -> Result<String> {
+    let (title, description) = match get_readable_list(repo.as_ref(), &list, identity.as_ref()).await?.get_name() {
+        Some(name) => (format!("Wishlist: {}", name), format!("Products newly added to the wishlist {}", name)),
+        None => ("Wishlist".to_string(), "Products newly added to the wishlist".to_string()),
+    };
+    let products = get_newest_products(repo.as_ref(), &list).await?.into_value();
+    Ok(render_rss(&title, &site_link(host.as_deref()), &description, &products))
+}
+
+/// Lists the newest products of a category on the lists `identity` can discover.
+pub async fn handle_get_category_feed(category_name: String, identity: Option<Identity>, host: Option<String>, repo: Arc<dyn WishlistRepository>) -> Result<String> {
+    let name = percent_decode_str(&category_name)
+        .decode_utf8()
+        .map_err(|_| Error::Validation("category name is not valid UTF-8".to_string()))?;
+    let category = repo.get_category_by_name(&name).await?
+        .ok_or(Error::NotFound("category"))?;
+    let category_id = category.get_id().ok_or(Error::FieldNotLoaded("category", "id"))?;
+
+    let newest_first = SortQuery::new(SortField::Added, SortOrder::Desc);
+    let lists = get_discoverable_slugs(repo.as_ref(), identity.as_ref()).await?;
+    let mut products = repo.get_products_by_category(Some(std::slice::from_ref(category_id)), Some(&lists), &newest_first, 0, FEED_SIZE as u64).await?.into_value();
+    load_source_for_products(repo.as_ref(), &mut products).await?;
+    Ok(render_rss(
+        &format!("Wishlist: {}", name),
+        &site_link(host.as_deref()),
+        &format!("Products newly added to the category {}", name),
+        &products,
+    ))
+}
+
+fn site_link(host: Option<&str>) -> String {
+    match host {
+        Some(host) => format!("https://{}/", host),
+        None => "/".to_string(),
+    }
+}
+
+/// Leaves out the products whose cheaper offer is among `products`. Products
+/// whose cheaper offer isn't are kept, so every item keeps an offer.
+fn collapse_duplicates(products: &mut Vec<Product>) {
+    let ids: BTreeSet<ObjectId> = products.iter().filter_map(Product::get_id).cloned().collect();
+    products.retain(|p| p.get_duplicate_of().map(|id| !ids.contains(id)).unwrap_or(true));
 }
 
-pub async fn handle_get_newest_products(client: Arc<Client>) -> Result<Vec<Product>> {
+/// Collects the products that were new in the last three snapshots adding any,
+/// looking back at most ten snapshots.
+async fn get_newest_products(repo: &dyn WishlistRepository, list: &str) -> Result<Loaded<Vec<Product>>> {
     let mut product_list = Vec::new();
+    let mut skipped = 0;
 
     let mut c = 0;
     let mut i = 0;
     while c < 3 && i < 10 {
-        let mut last_wishlist = get_nth_wishlist_reverse(&client, i).await?;
-        load_wishlist(&client, &mut last_wishlist).await?;
+        let mut last_wishlist = match repo.get_nth_wishlist_reverse(list, i).await {
+            Ok(wishlist) => wishlist,
+            Err(Error::EmptyResult) if i > 0 => break,
+            Err(e) => return Err(e),
+        };
+        skipped += load_wishlist(repo, &mut last_wishlist).await?;
 
         let wl_timestamp = last_wishlist.get_timestamp();
         let new_products = match last_wishlist.get_products() {
@@ -37,189 +356,1963 @@ pub async fn handle_get_newest_products(client: Arc<Client>) -> Result<Vec<Produ
                 Vec::new()
             }
         };
-        if new_products.len() > 0 {
+        if !new_products.is_empty() {
             c+= 1;
             product_list.extend(new_products);
         }
         i+= 1;
     }
-    Ok(product_list)
+    Ok(Loaded::new(product_list, skipped))
+}
+
+pub async fn handle_get_wishlist_diff(list: String, identity: Option<Identity>, query: DiffQuery, repo: Arc<dyn WishlistRepository>) -> Result<Loaded<WishlistDiff>> {
+    get_readable_list(repo.as_ref(), &list, identity.as_ref()).await?;
+    let from = repo.get_wishlist_at(&list, query.get_from()).await?
+        .ok_or(Error::NotFound("wishlist"))?;
+    let to = repo.get_wishlist_at(&list, query.get_to()).await?
+        .ok_or(Error::NotFound("wishlist"))?;
+    compare_wishlists(repo.as_ref(), &from, &to).await
+}
+
+/// Lists the changes between consecutive snapshots of `list`, newest first,
+/// with the products they concern.
+pub async fn handle_get_timeline(list: String, identity: Option<Identity>, query: TimelineQuery, repo: Arc<dyn WishlistRepository>) -> Result<Loaded<Vec<TimelineEntry>>> {
+    get_readable_list(repo.as_ref(), &list, identity.as_ref()).await?;
+    let (offset, per_page) = (query.get_offset() as usize, query.get_per_page() as usize);
+    let mut later = match repo.get_nth_wishlist_reverse(&list, 0).await {
+        Ok(wishlist) => wishlist,
+        Err(Error::EmptyResult) => return Ok(Loaded::new(Vec::new(), 0)),
+        Err(e) => return Err(e),
+    };
+    let mut entries = Vec::new();
+    let mut skipped = 0;
+    for n in 1..MAX_TIMELINE_SNAPSHOTS {
+        if entries.len() >= offset + per_page {
+            break;
+        }
+        let earlier = match repo.get_nth_wishlist_reverse(&list, n).await {
+            Ok(wishlist) => wishlist,
+            Err(Error::EmptyResult) => break,
+            Err(e) => return Err(e),
+        };
+        let (diff, skipped_products) = compare_wishlists(repo.as_ref(), &earlier, &later).await?.split();
+        skipped += skipped_products;
+        entries.extend(diff.into_timeline());
+        later = earlier;
+    }
+    Ok(Loaded::new(entries.into_iter().skip(offset).take(per_page).collect(), skipped))
 }
 
-pub async fn handle_get_archived_products(list: ListQuery, client: Arc<Client>) -> Result<Vec<Product>> {
-    let last_wishlist = get_last_wishlist(&client).await?;
-    let product_ids = last_wishlist
-        .get_product_ids()
-        .ok_or(Error::FieldNotLoaded("wishlist", "product_ids"))?;
-    let filter = doc! {
-    "_id": {"$not": {"$in": product_ids} } };
+/// Lists the products added and removed between two snapshots and the price
+/// changes of the products in both.
+async fn compare_wishlists(repo: &dyn WishlistRepository, from: &Wishlist, to: &Wishlist) -> Result<Loaded<WishlistDiff>> {
+    let from_ids: BTreeSet<ObjectId> = from.get_product_ids().unwrap_or_default().iter().cloned().collect();
+    let to_ids: BTreeSet<ObjectId> = to.get_product_ids().unwrap_or_default().iter().cloned().collect();
+
+    let ids: Vec<ObjectId> = from_ids.union(&to_ids).cloned().collect();
+    let (mut products, mut skipped) = repo.get_products_by_id(&ids).await?.split();
+    skipped += load_product_details(repo, &mut products).await?;
+
+    let kept_ids: Vec<ObjectId> = from_ids.intersection(&to_ids).cloned().collect();
+    let (points, skipped_points) = repo.get_price_histories(&kept_ids).await?.split();
+    skipped += skipped_points;
+    let mut histories: BTreeMap<ObjectId, Vec<PricePoint>> = BTreeMap::new();
+    for point in points {
+        if let Some(id) = point.get_product_id() {
+            histories.entry(id.clone()).or_default().push(point);
+        }
+    }
 
-    let options = FindOptions::builder()
-        .sort(doc! { "_id": -1})
-        .projection(doc! {"_id": false, "item_id": false})
-        .skip(list.get_offset() as i64)
-        .limit(list.get_size() as i64)
-        .build();
-    load_products(&client, Some(filter), Some(options)).await
+    let mut diff = WishlistDiff::new(from.get_timestamp(), to.get_timestamp());
+    for product in products {
+        let id = match product.get_id() {
+            Some(id) => id.clone(),
+            None => continue,
+        };
+        match (from_ids.contains(&id), to_ids.contains(&id)) {
+            (false, true) => diff.add_added(product),
+            (true, false) => diff.add_removed(product),
+            _ => {
+                let history = histories.get(&id).map(Vec::as_slice).unwrap_or_default();
+                let old_price = price_at(history, from.get_timestamp());
+                let new_price = price_at(history, to.get_timestamp());
+                if old_price.is_some() && new_price.is_some() && old_price != new_price {
+                    diff.add_price_change(product, old_price, new_price);
+                }
+            }
+        }
+    }
+    Ok(Loaded::new(diff, skipped))
 }
 
-pub async fn handle_get_archive_product_count(client: Arc<Client>) -> Result<u64> {
-    let last_wishlist = get_last_wishlist(&client).await?;
-    let product_ids = last_wishlist
-        .get_product_ids()
-        .ok_or(Error::FieldNotLoaded("wishlist", "product_ids"))?;
-    let filter = doc! {
-    "_id": {"$not": {"$in": product_ids} } };
+#[allow(clippy::too_many_arguments)]
+pub async fn handle_get_archived_products(list: String, identity: Option<Identity>, page: ListQuery, filter: ArchiveFilterQuery, sort: SortQuery, fields: FieldsQuery, views: ViewCounter, repo: Arc<dyn WishlistRepository>) -> Result<Page<Product>> {
+    let fields = fields.get_fields()?;
+    get_readable_list(repo.as_ref(), &list, identity.as_ref()).await?;
+    let filter = get_product_filter(repo.as_ref(), &list, &filter).await?.with_archived(true);
+    let products = repo.get_products(&filter.clone().with_fields(fields.clone()), &sort, page.get_offset(), page.get_size()).await?;
+    let products = with_details(repo.as_ref(), products).await?;
+    views.record(products.get_value());
+    let total = repo.count_products(&filter).await?;
+    let page = Page::new(products, page.get_offset(), page.get_size(), total).with_fields(fields);
+    hide_reservations(repo.as_ref(), identity.as_ref(), page).await
+}
 
-    count_documents(&client.database("wishlist").collection("product"), Some(filter)).await
+/// Searches the names and descriptions of the archived products of `list`,
+/// most recently removed first unless another sort is requested.
+#[allow(clippy::too_many_arguments)]
+pub async fn handle_search_archived_products(list: String, identity: Option<Identity>, search: ArchiveSearchQuery, page: ListQuery, sort: SortQuery, fields: FieldsQuery, views: ViewCounter, repo: Arc<dyn WishlistRepository>) -> Result<Page<Product>> {
+    let fields = fields.get_fields()?;
+    if search.get_query().is_empty() {
+        return Err(Error::Validation("q must not be empty".to_string()));
+    }
+    let (after, before) = (search.get_removed_after(), search.get_removed_before());
+    if let (Some(after), Some(before)) = (after, before) {
+        if after > before {
+            return Err(Error::Validation("removed_after must not be later than removed_before".to_string()));
+        }
+    }
+    get_readable_list(repo.as_ref(), &list, identity.as_ref()).await?;
+    let filter = ProductFilter::new(Some(list), None, None, None)
+        .with_archived(true)
+        .with_text(Some(search.get_query().to_string()))
+        .with_last_seen(after, before);
+    let sort = if sort == SortQuery::default() { SortQuery::new(SortField::Removed, SortOrder::Desc) } else { sort };
+    let products = repo.get_products(&filter.clone().with_fields(fields.clone()), &sort, page.get_offset(), page.get_size()).await?;
+    let products = with_details(repo.as_ref(), products).await?;
+    views.record(products.get_value());
+    let total = repo.count_products(&filter).await?;
+    let page = Page::new(products, page.get_offset(), page.get_size(), total).with_fields(fields);
+    hide_reservations(repo.as_ref(), identity.as_ref(), page).await
 }
 
-pub async fn handle_get_categories(client: Arc<Client>) -> Result<Vec<Category>> {
-    get_categories(&client).await
+pub async fn handle_get_archive_product_count(list: String, identity: Option<Identity>, filter: ArchiveFilterQuery, repo: Arc<dyn WishlistRepository>) -> Result<u64> {
+    get_readable_list(repo.as_ref(), &list, identity.as_ref()).await?;
+    let filter = get_product_filter(repo.as_ref(), &list, &filter).await?.with_archived(true);
+    repo.count_products(&filter).await
 }
 
-pub async fn handle_get_products_by_category_name(query: CategoryQuery, client: Arc<Client>) -> Result<Vec<Product>> {
-    get_products_by_category_name(&client, query.get_category()).await
+/// Sums up the products of `list`, served in one request for dashboards.
+/// The prices are totaled in euro unless another currency is requested.
+pub async fn handle_get_stats(list: String, identity: Option<Identity>, query: CurrencyQuery, repo: Arc<dyn WishlistRepository>) -> Result<Stats> {
+    get_readable_list(repo.as_ref(), &list, identity.as_ref()).await?;
+    let stats = repo.get_stats(&list).await?;
+    convert_prices(repo.as_ref(), Some(query.get_currency().unwrap_or_default()), stats).await
 }
 
-async fn count_documents(collection: &Collection, filter: Option<Document>) -> Result<u64> {
-    collection.count_documents(filter, None).await
-        .map(|n| n as u64)
-        .map_err(Error::from)
+/// Sums up the prices of the products in the latest snapshot of `list`, per
+/// category and priority, and what is left of its budget. Offers of the same
+/// item are counted once. The prices are totaled in euro unless another
+/// currency is requested.
+pub async fn handle_get_wishlist_value(list: String, identity: Option<Identity>, query: CurrencyQuery, repo: Arc<dyn WishlistRepository>) -> Result<Loaded<WishlistValue>> {
+    get_readable_list(repo.as_ref(), &list, identity.as_ref()).await?;
+    let (mut products, skipped) = match get_last_snapshot(repo.as_ref(), &list).await {
+        Ok(wishlist) => {
+            let ids = wishlist.get_product_ids().ok_or(Error::FieldNotLoaded("wishlist", "product_ids"))?;
+            repo.get_products_by_id(ids).await?.split()
+        }
+        Err(Error::EmptyResult) => (Vec::new(), 0),
+        Err(e) => return Err(e),
+    };
+    collapse_duplicates(&mut products);
+    let categories = repo.get_categories().await?.into_value();
+    let budget = repo.get_budget(&list).await?.map(|budget| budget.get_amount());
+    let converter = Converter::load(repo.as_ref(), query.get_currency().unwrap_or_default()).await?;
+    let value = WishlistValue::compute(&products, &categories, budget, converter.get_currency(), |money| converter.convert(money))?;
+    Ok(Loaded::new(value, skipped))
 }
 
-async fn extract_cursor_results<T: From<Document> + std::fmt::Debug>(mut cursor: Cursor) -> Vec<T> {
-    let mut results = Vec::new();
-    while let Some(entry) = cursor.next().await {
-        if let Ok(result) = entry {
-            results.push(T::from(result));
-        } else {
-            warn!("Couldn't extract bson result as Product");
+/// Sets how much the owner plans to spend on `list`, an input without an
+/// amount removes the budget. Returns the budget, `None` once removed.
+pub async fn handle_set_budget(list: String, identity: Identity, input: BudgetInput, repo: Arc<dyn WishlistRepository>) -> Result<Option<Budget>> {
+    get_managed_list(repo.as_ref(), &identity, &list).await?;
+    match input.to_document(&list, current_timestamp())? {
+        Some(document) => {
+            repo.upsert_budget(document.clone()).await?;
+            record_event(repo.as_ref(), &actor_of(Some(&identity)), "update", Budget::COLLECTION, None, document).await;
+        }
+        None => {
+            if repo.delete_budget(&list).await? {
+                record_event(repo.as_ref(), &actor_of(Some(&identity)), "delete", Budget::COLLECTION, None, doc! {"list": &list}).await;
+            }
         }
     }
-    results
+    repo.get_budget(&list).await
 }
 
-
-async fn get_categories(client: &Client) -> Result<Vec<Category>> {
-    let coll = client.database("wishlist").collection("category");
-    let cursor = coll.find(None, None).await?;
-    let categories = extract_cursor_results(cursor).await;
-    Ok(categories)
+/// Click counts of the products of `list`, only shown to whoever manages the list.
+pub async fn handle_get_click_stats(list: String, identity: Identity, repo: Arc<dyn WishlistRepository>) -> Result<Loaded<Vec<ClickCount>>> {
+    get_managed_list(repo.as_ref(), &identity, &list).await?;
+    repo.get_click_counts(&list).await
 }
 
-async fn get_category_by_name(client: &Client, name: &str) -> Result<Category> {
-    let coll = client.database("wishlist").collection("category");
-    let filter = doc! {
-        "name": name
+/// Counts a click on the shop link of a product and returns the url to redirect to,
+/// with the affiliate parameter of its source unless `?raw_links=true`.
+/// Clicks that can't be counted, e.g. in maintenance mode, still redirect.
+pub async fn handle_follow_product(product_id: String, identity: Option<Identity>, links: LinkQuery, repo: Arc<dyn WishlistRepository>) -> Result<String> {
+    let id = ObjectId::with_string(&product_id)?;
+    let product = get_product_by_id(repo.as_ref(), &id).await?;
+    let list = get_list(repo.as_ref(), product.get_list()).await?;
+    if !can_read(identity.as_ref(), &list) {
+        return Err(Error::NotFound("product"));
+    }
+    let url = product.get_url()
+        .filter(|url| url.starts_with("https://") || url.starts_with("http://"))
+        .ok_or(Error::NotFound("product url"))?;
+    let url = match product.get_source().and_then(Source::get_affiliate) {
+        Some(affiliate) if !links.is_raw() => affiliate.apply(url),
+        _ => url.to_string(),
     };
-    coll.find_one(Some(filter), None).await
-        .map_err(Error::from)
-        .and_then(|r| r.ok_or(Error::EmptyResult))
-        .map(|r| Category::from(&r))
+    match repo.record_click(&id).await {
+        Err(Error::Unavailable(reason)) => debug!("Not counting click on product {}: {}", product_id, reason),
+        result => {
+            result?;
+        }
+    }
+    Ok(url)
+}
+
+/// Counts the products of `list` per tag, most used tag first.
+pub async fn handle_get_tags(list: String, identity: Option<Identity>, repo: Arc<dyn WishlistRepository>) -> Result<Loaded<Vec<TagCount>>> {
+    get_readable_list(repo.as_ref(), &list, identity.as_ref()).await?;
+    repo.get_tags(&list).await
 }
 
-async fn get_products_by_category_name(client: &Client, name: Option<&str>) -> Result<Vec<Product>> {
-    let category = match name {
-        Some(n) => Some(get_category_by_name(client,n).await?),
-        None => None
+/// Validates the archive filter parameters of `list`, resolves the source name
+/// to its id and normalizes the tag.
+async fn get_product_filter(repo: &dyn WishlistRepository, list: &str, query: &ArchiveFilterQuery) -> Result<ProductFilter> {
+    let (min_price, max_price) = (query.get_min_price(), query.get_max_price());
+    if min_price.map(|p| p < 0).unwrap_or(false) || max_price.map(|p| p < 0).unwrap_or(false) {
+        return Err(Error::Validation("price filters must not be negative".to_string()));
+    }
+    if let (Some(min), Some(max)) = (min_price, max_price) {
+        if min > max {
+            return Err(Error::Validation("min_price must not be greater than max_price".to_string()));
+        }
+    }
+    let source = match query.get_source() {
+        Some(name) => {
+            let source = repo.get_source_by_name(name).await?
+                .ok_or(Error::NotFound("source"))?;
+            Some(source.get_id().cloned().ok_or(Error::FieldNotLoaded("source", "id"))?)
+        }
+        None => None,
     };
-    get_products_by_category(client, category.as_ref()).await
+    let tag = query.get_tag().map(normalize_tag).transpose()?;
+    Ok(ProductFilter::new(Some(list.to_string()), min_price, max_price, source)
+        .with_tag(tag)
+        .with_collapsed_duplicates(query.is_collapsing_duplicates())
+        .with_in_stock(query.get_in_stock()))
 }
 
-async fn get_products_by_category(client: &Client, category: Option<&Category>) -> Result<Vec<Product>> {
-    let filter = match category {
-        Some(c) => doc! {
-            "category": c.get_id().ok_or(Error::FieldNotLoaded("wishlist", "product_ids"))?,
-        },
-        None => doc! {
-            "category": mongodb::bson::Bson::Null
+/// Suggests names of products of `list` and of categories starting with the
+/// prefix typed into a search box.
+pub async fn handle_suggest(list: String, identity: Option<Identity>, query: SuggestQuery, repo: Arc<dyn WishlistRepository>) -> Result<Suggestions> {
+    get_readable_list(repo.as_ref(), &list, identity.as_ref()).await?;
+    let prefix = query.get_prefix();
+    if prefix.is_empty() {
+        return Ok(Suggestions::default());
+    }
+    if prefix.chars().count() > MAX_PREFIX_LENGTH {
+        return Err(Error::Validation(format!("q must be at most {} characters", MAX_PREFIX_LENGTH)));
+    }
+    let products = repo.suggest_product_names(&list, prefix, query.get_size()).await?;
+    let prefix = prefix.to_lowercase();
+    let mut categories: Vec<String> = repo.get_categories().await?.into_value().iter()
+        .filter_map(Category::get_name)
+        .filter(|name| name.to_lowercase().starts_with(&prefix))
+        .map(String::from)
+        .collect();
+    categories.sort_by_key(|name| name.to_lowercase());
+    categories.truncate(query.get_size() as usize);
+    Ok(Suggestions::new(products, categories))
+}
+
+pub async fn handle_search_products(identity: Option<Identity>, query: SearchQuery, views: ViewCounter, repo: Arc<dyn WishlistRepository>) -> Result<Loaded<Vec<Product>>> {
+    let lists = get_discoverable_slugs(repo.as_ref(), identity.as_ref()).await?;
+    let products = repo.search_products(query.get_query(), Some(&lists), query.get_offset(), query.get_size()).await?;
+    let products = with_details(repo.as_ref(), products).await?;
+    views.record(products.get_value());
+    hide_reservations(repo.as_ref(), identity.as_ref(), products).await
+}
+
+/// Returns the products with the given ids in the order they were asked for,
+/// to compare them side by side. Missing products and products of lists the
+/// caller can't read are left out.
+pub async fn handle_get_products_batch(identity: Option<Identity>, query: IdsQuery, repo: Arc<dyn WishlistRepository>) -> Result<Loaded<Vec<Product>>> {
+    let ids = query.get_ids()?;
+    let (products, skipped) = repo.get_products_by_id(&ids).await?.split();
+    let mut readable_lists: BTreeMap<String, bool> = BTreeMap::new();
+    let mut readable = Vec::with_capacity(products.len());
+    for product in products {
+        let slug = product.get_list().to_string();
+        let can_read_list = match readable_lists.get(&slug) {
+            Some(can_read_list) => *can_read_list,
+            None => {
+                let can_read_list = match get_list(repo.as_ref(), &slug).await {
+                    Ok(list) => can_read(identity.as_ref(), &list),
+                    Err(Error::NotFound(_)) => false,
+                    Err(e) => return Err(e),
+                };
+                readable_lists.insert(slug, can_read_list);
+                can_read_list
+            }
+        };
+        if can_read_list {
+            readable.push(product);
         }
+    }
+    readable.sort_by_key(|p| p.get_id().and_then(|id| ids.iter().position(|i| i == id)));
+    let products = with_details(repo.as_ref(), Loaded::new(readable, skipped)).await?;
+    hide_reservations(repo.as_ref(), identity.as_ref(), products).await
+}
+
+/// Returns one product with the relations listed in `?expand=`, whether it
+/// is reserved is always included.
+pub async fn handle_get_product(product_id: String, identity: Option<Identity>, query: ExpandQuery, repo: Arc<dyn WishlistRepository>) -> Result<Loaded<ProductDetail>> {
+    let relations = query.get_relations()?;
+    let id = ObjectId::with_string(&product_id)?;
+    let mut product = repo.get_product_by_id(&id).await?
+        .ok_or(Error::NotFound("product"))?;
+    let list = get_list(repo.as_ref(), product.get_list()).await?;
+    if !can_read(identity.as_ref(), &list) {
+        return Err(Error::NotFound("product"));
+    }
+
+    let mut skipped = 0;
+    if relations.contains(&Relation::Source) {
+        skipped += load_source_for_products(repo.as_ref(), std::slice::from_mut(&mut product)).await?;
+    }
+    product.set_reserved(!repo.get_reserved_product_ids(std::slice::from_ref(&id)).await?.is_empty());
+    product.set_funded_percent(repo.get_pledge_pool(&id).await?.map(|pool| pool.get_funded_percent()));
+    let category = match (relations.contains(&Relation::Category), product.get_category_id()) {
+        (true, Some(category_id)) => Some(repo.get_category_by_id(category_id).await?),
+        (true, None) => Some(None),
+        (false, _) => None,
     };
-    load_products(client, Some(filter), None).await
+    let mut detail = ProductDetail::new(product);
+    if let Some(category) = category {
+        detail = detail.with_category(category);
+    }
+    if relations.contains(&Relation::PriceHistory) {
+        let (history, skipped_history) = repo.get_price_history(&id).await?.split();
+        skipped += skipped_history;
+        detail = detail.with_price_history(history);
+    }
+    hide_reservations(repo.as_ref(), identity.as_ref(), Loaded::new(detail, skipped)).await
 }
 
-async fn get_wishlist(client: &Client, filter: Option<Document>, options: Option<FindOneOptions>) -> Result<Wishlist> {
-    let coll = client.database("wishlist").collection("wishlist");
-    coll.find_one(filter, options).await
-        .map_err(Error::from)
-        .and_then(|r| r.ok_or(Error::EmptyResult))
-        .map(|r| Wishlist::from(&r))
+pub async fn handle_get_product_prices(product_id: String, identity: Option<Identity>, repo: Arc<dyn WishlistRepository>) -> Result<Loaded<Vec<PricePoint>>> {
+    let id = ObjectId::with_string(&product_id)?;
+    get_readable_product(repo.as_ref(), identity.as_ref(), &id).await?;
+    repo.get_price_history(&id).await
 }
 
-async fn get_nth_wishlist_reverse(client: &Client, skip_count: i64) -> Result<Wishlist> {
-    let options = FindOneOptions::builder()
-        .sort(doc! {"timestamp": -1})
-        .skip(Some(skip_count))
-        .projection(doc! {"_id": false})
-        .build();
-    get_wishlist(client, None, Some(options)).await
+/// Serves the image of a product in `size`, downloaded from its image url on first request.
+pub async fn handle_get_product_image(product_id: String, size: String, identity: Option<Identity>, images: ImageStore, repo: Arc<dyn WishlistRepository>) -> Result<Image> {
+    let size = ImageSize::from_name(&size).ok_or(Error::NotFound("image size"))?;
+    let id = ObjectId::with_string(&product_id)?;
+    let product = get_product_by_id(repo.as_ref(), &id).await?;
+    let list = get_list(repo.as_ref(), product.get_list()).await?;
+    if !can_read(identity.as_ref(), &list) {
+        return Err(Error::NotFound("product"));
+    }
+    let url = product.get_url_img().filter(|url| !url.is_empty()).ok_or(Error::NotFound("image"))?;
+    images.get(&id.to_hex(), url, size).await
 }
 
-async fn get_last_wishlist(client: &Client) -> Result<Wishlist> {
-    get_nth_wishlist_reverse(client, 0).await
+pub async fn handle_create_product(identity: Identity, input: ProductInput, notifier: Notifier, repo: Arc<dyn WishlistRepository>) -> Result<Product> {
+    let document = input.to_document()?;
+    validate_product_references(repo.as_ref(), &document).await?;
+    get_managed_list(repo.as_ref(), &identity, document.get_str("list").unwrap_or(DEFAULT_LIST)).await?;
+    let item_id = document.get_str("item_id").ok().map(String::from);
+    let classifier = Classifier::load(repo.as_ref()).await?;
+    let (id, created) = insert_new_product(repo.as_ref(), &notifier, &classifier, &actor_of(Some(&identity)), document, input.get_price()).await?;
+    if !created {
+        return Err(Error::Conflict(format!("item '{}' is already on the list", item_id.unwrap_or_default())));
+    }
+    get_product_by_id(repo.as_ref(), &id).await
 }
 
-async fn load_wishlist(client: &Client, wishlist: &mut Wishlist) -> Result<()> {
-    let mut products = match wishlist.get_product_ids() {
-        Some(ids) => get_products_by_id(&client, ids).await?,
-        None => {
-            return Err(Error::FieldNotLoaded("wishlist", "product_ids"));
+/// Adds the product on a page of a shop sent by the bookmarklet or browser
+/// extension. What the input lacks is read from the meta tags of the page,
+/// requested as politely as its source asks for. The source is the one whose
+/// URL has the domain of the page, or one named after the host if none has.
+/// A product of the list with the same URL is returned instead of adding it
+/// again, with the price recorded and `last_seen` updated.
+pub async fn handle_ingest_product(identity: Identity, input: IngestInput, client: PoliteClient, notifier: Notifier, repo: Arc<dyn WishlistRepository>) -> Result<Product> {
+    let url = reqwest::Url::parse(input.get_url()).map_err(|_| Error::Validation("url is invalid".to_string()))?;
+    check_public_host(&url, "product").await?;
+    let list = input.get_list().unwrap_or(DEFAULT_LIST);
+    get_managed_list(repo.as_ref(), &identity, list).await?;
+
+    let source = find_source_by_domain(repo.as_ref(), &url).await?;
+    let metadata = match input.is_complete() {
+        true => PageMetadata::default(),
+        false => {
+            let (key, politeness) = match &source {
+                Some(source) => (source.get_id().map(ObjectId::to_hex), source.get_politeness().clone()),
+                None => (None, Politeness::default()),
+            };
+            let key = key.unwrap_or_else(|| url.host_str().unwrap_or_default().to_string());
+            client.get_page(&key, &politeness, &url, MAX_PAGE_SIZE).await
+                .map(|page| PageMetadata::parse(&page))
+                .unwrap_or_default()
         }
     };
-    load_source_for_products(&client, &mut products).await?;
-    wishlist.set_products(products);
-    Ok(())
+
+    let name = input.get_title().or_else(|| metadata.get_title())
+        .ok_or_else(|| Error::Validation("title is missing and the page has none".to_string()))?;
+    let currency = input.get_currency().or_else(|| metadata.get_currency()).unwrap_or_default();
+    let shown_price = input.get_price().or_else(|| metadata.get_price())
+        .ok_or_else(|| Error::Validation("price is missing and the page has none".to_string()))?;
+    let price = parse_shown_price(shown_price, currency)
+        .ok_or_else(|| Error::Validation(format!("price '{}' is not an amount like 12.99", shown_price)))?;
+    let actor = actor_of(Some(&identity));
+    if let Some(duplicate) = repo.find_duplicate_product(list, None, url.as_str()).await? {
+        if duplicate.is_deleted() {
+            return Err(Error::Conflict("the product is in the trash, restore or purge it first".to_string()));
+        }
+        let id = duplicate.get_id().cloned().ok_or(Error::FieldNotLoaded("product", "id"))?;
+        let now = current_timestamp();
+        repo.update_product(&id, doc! {"price": price, "last_seen": now}).await?;
+        record_price(repo.as_ref(), &notifier, &actor, &id, price, now).await?;
+        return get_product_by_id(repo.as_ref(), &id).await;
+    }
+    // Pages often name their image relative to themselves.
+    let image = input.get_image().or_else(|| metadata.get_image())
+        .and_then(|image| url.join(image).ok())
+        .map(String::from);
+    let product = |source: &ObjectId| {
+        ProductInput::new(name.to_string(), price, url.to_string(), source)
+            .with_currency(Some(currency))
+            .with_url_img(image.clone())
+            .with_list(Some(list.to_string()).filter(|l| l != DEFAULT_LIST))
+    };
+    // Validates the product before creating a source for it.
+    product(&ObjectId::new()).to_document()?;
+
+    let source = match source.as_ref().and_then(Source::get_id) {
+        Some(id) => id.clone(),
+        None => resolve_source_for_url(repo.as_ref(), url.as_str()).await?,
+    };
+    let classifier = Classifier::load(repo.as_ref()).await?;
+    let (id, _) = insert_new_product(repo.as_ref(), &notifier, &classifier, &actor, product(&source).to_document()?, price).await?;
+    get_product_by_id(repo.as_ref(), &id).await
+}
+
+/// Imports products from a CSV or JSON body into a list, reporting the outcome
+/// per row. Categories and sources are looked up by name and created if missing,
+/// products already in the list with the same item id or URL are skipped.
+pub async fn handle_import_products(query: ImportQuery, content_type: Option<String>, body: Vec<u8>, notifier: Notifier, repo: Arc<dyn WishlistRepository>) -> Result<ImportReport> {
+    let list = query.get_list();
+    get_list(repo.as_ref(), list).await?;
+    let rows = read_import_rows(content_type.as_deref(), &body)?;
+    let classifier = Classifier::load(repo.as_ref()).await?;
+    let mut names = ImportedNames::default();
+    let mut report = ImportReport::default();
+    for row in rows {
+        let outcome = match row {
+            Ok(row) => import_product(repo.as_ref(), &notifier, &classifier, list, &row, &mut names).await,
+            Err(e) => Err(e),
+        };
+        match outcome {
+            Ok((status, id)) => report.add(status, Some(id), None),
+            Err(Error::Validation(message)) | Err(Error::Conflict(message)) => report.add(ImportStatus::Failed, None, Some(message)),
+            Err(e) => return Err(e),
+        }
+    }
+    if report.get_created() > 0 {
+        info!("Imported {} products into the list '{}'", report.get_created(), list);
+    }
+    Ok(report)
 }
 
-async fn load_products(client: &Client, filter: Option<Document>, options: Option<FindOptions>) -> Result<Vec<Product>> {
-    let coll = client.database("wishlist").collection("product");
-    let cursor = coll.find(filter, options).await?;
-    let mut products = extract_cursor_results(cursor).await;
-    load_source_for_products(&client, &mut products).await?;
-    Ok(products)
+/// Imports the public Steam wishlist of the profile in `query` into a list,
+/// see `import_steam_wishlist`.
+pub async fn handle_import_steam_wishlist(query: SteamImportQuery, steam: SteamClient, notifier: Notifier, repo: Arc<dyn WishlistRepository>) -> Result<ImportReport> {
+    import_steam_wishlist(repo.as_ref(), &notifier, &steam, ADMIN, query.get_steam_id(), query.get_list()).await
 }
 
-async fn load_source_for_products(client: &Client, products: &mut [Product]) -> Result<()> {
-    let mut sources = BTreeMap::new();
-        for product in products.iter_mut() {
-            let source_id = product
-                .get_source_id()
-                .map(|sid| sid.clone())
-                .ok_or(Error::FieldNotLoaded("product", "source_id"))?;
-            match sources.entry(source_id.clone()) {
-                Entry::Vacant(e) => {
-                    let source = e.insert(get_source_by_id(client, &source_id).await?);
-                    product.set_source(source.clone());
-                }
-                Entry::Occupied(e) => {
-                    let source = e.get().clone();
-                    product.set_source(source);
-                }
-            }
+pub async fn handle_update_product(product_id: String, identity: Identity, input: ProductInput, notifier: Notifier, repo: Arc<dyn WishlistRepository>) -> Result<Product> {
+    let id = ObjectId::with_string(&product_id)?;
+    let document = input.to_document()?;
+    validate_product_references(repo.as_ref(), &document).await?;
+    ensure_can_manage_product(repo.as_ref(), &identity, &id).await?;
+    if let Ok(list) = document.get_str("list") {
+        get_managed_list(repo.as_ref(), &identity, list).await?;
+    }
+
+    if !repo.update_product(&id, document.clone()).await? {
+        return Err(Error::NotFound("product"));
+    }
+    let actor = actor_of(Some(&identity));
+    record_event(repo.as_ref(), &actor, "update", Product::COLLECTION, Some(&id), document).await;
+
+    record_price(repo.as_ref(), &notifier, &actor, &id, input.get_price(), current_timestamp()).await?;
+    get_product_by_id(repo.as_ref(), &id).await
+}
+
+/// Sets the price below which a price drop of the product is notified, `null` removes it.
+pub async fn handle_set_price_threshold(product_id: String, identity: Identity, input: PriceThresholdInput, repo: Arc<dyn WishlistRepository>) -> Result<Product> {
+    let id = ObjectId::with_string(&product_id)?;
+    ensure_can_manage_product(repo.as_ref(), &identity, &id).await?;
+    let document = input.to_document()?;
+    if !repo.update_product(&id, document.clone()).await? {
+        return Err(Error::NotFound("product"));
+    }
+    record_event(repo.as_ref(), &actor_of(Some(&identity)), "update", Product::COLLECTION, Some(&id), document).await;
+    get_product_by_id(repo.as_ref(), &id).await
+}
+
+/// Sets an alert for the price of the product dropping to the target, sent
+/// when a new price is recorded. Anyone who can read the product's list may
+/// set alerts, only the user who set one and the admin see it.
+pub async fn handle_create_price_alert(product_id: String, identity: Identity, input: PriceAlertInput, notifier: Notifier, repo: Arc<dyn WishlistRepository>) -> Result<PriceAlert> {
+    let id = ObjectId::with_string(&product_id)?;
+    let user = match &identity {
+        Identity::Admin => None,
+        Identity::User { id: user } => Some(user),
+        Identity::Share { .. } => return Err(Error::Forbidden("share tokens can't set price alerts".to_string())),
+    };
+    let product = get_product_by_id(repo.as_ref(), &id).await?;
+    if !can_read(Some(&identity), &get_list(repo.as_ref(), product.get_list()).await?) {
+        return Err(Error::NotFound("product"));
+    }
+    let document = input.to_document(&id, user, current_timestamp())?;
+    if input.get_email().is_some() && !notifier.has_email() {
+        return Err(Error::Unavailable("email notifications are not configured".to_string()));
+    }
+    if let Some(webhook) = input.get_webhook()? {
+        check_public_host(&webhook, "webhook").await?;
+    }
+
+    let alert_id = repo.insert_price_alert(document.clone()).await?;
+    record_event(repo.as_ref(), &actor_of(Some(&identity)), "create", PriceAlert::COLLECTION, Some(&alert_id), document).await;
+    get_price_alert(repo.as_ref(), &identity, &alert_id).await
+}
+
+/// Returns the price alerts of the identity, newest first, the admin gets all.
+pub async fn handle_get_price_alerts(identity: Identity, repo: Arc<dyn WishlistRepository>) -> Result<Loaded<Vec<PriceAlert>>> {
+    let (mut alerts, skipped) = repo.get_price_alerts().await?.split();
+    alerts.retain(|alert| owns_price_alert(&identity, alert));
+    Ok(Loaded::new(alerts, skipped))
+}
+
+/// Removes a price alert of the identity, the admin may remove any.
+pub async fn handle_delete_price_alert(alert_id: String, identity: Identity, repo: Arc<dyn WishlistRepository>) -> Result<PriceAlert> {
+    let id = ObjectId::with_string(&alert_id)?;
+    let alert = get_price_alert(repo.as_ref(), &identity, &id).await?;
+    repo.delete_price_alert(&id).await?;
+    record_event(repo.as_ref(), &actor_of(Some(&identity)), "delete", PriceAlert::COLLECTION, Some(&id), Document::new()).await;
+    Ok(alert)
+}
+
+/// Sets how much the product is wanted, how many of it and the notes on it,
+/// for gift-givers to go by.
+pub async fn handle_set_wish(product_id: String, identity: Identity, input: WishInput, repo: Arc<dyn WishlistRepository>) -> Result<Product> {
+    let id = ObjectId::with_string(&product_id)?;
+    ensure_can_manage_product(repo.as_ref(), &identity, &id).await?;
+    let document = input.to_document()?;
+    if !repo.update_product(&id, document.clone()).await? {
+        return Err(Error::NotFound("product"));
+    }
+    record_event(repo.as_ref(), &actor_of(Some(&identity)), "update", Product::COLLECTION, Some(&id), document).await;
+    get_product_by_id(repo.as_ref(), &id).await
+}
+
+/// Sets the category, tags or priority of several products in one update,
+/// reporting the outcome per id. Ids that are invalid or not found are left out.
+pub async fn handle_bulk_update_products(input: BulkUpdateInput, repo: Arc<dyn WishlistRepository>) -> Result<BulkUpdateReport> {
+    let document = input.to_document()?;
+    if let Some(category) = input.get_category()? {
+        get_category_by_id(repo.as_ref(), &category).await?;
+    }
+
+    let mut ids: Vec<ObjectId> = Vec::new();
+    for id in input.get_ids().iter().filter_map(|id| ObjectId::with_string(id).ok()) {
+        if !ids.contains(&id) {
+            ids.push(id);
         }
+    }
+    let found: Vec<ObjectId> = repo.get_products_by_id(&ids).await?
+        .into_value()
+        .iter()
+        .filter_map(|product| product.get_id().cloned())
+        .collect();
+    if !found.is_empty() {
+        repo.update_products(&found, document.clone()).await?;
+    }
+    for id in &found {
+        record_event(repo.as_ref(), ADMIN, "update", Product::COLLECTION, Some(id), document.clone()).await;
+    }
 
-    Ok(())
+    let mut report = BulkUpdateReport::default();
+    for id in input.get_ids() {
+        match ObjectId::with_string(id) {
+            Ok(parsed) if found.contains(&parsed) => report.add(id, BulkUpdateStatus::Updated, None),
+            Ok(_) => report.add(id, BulkUpdateStatus::NotFound, None),
+            Err(_) => report.add(id, BulkUpdateStatus::Failed, Some("not a valid id".to_string())),
+        }
+    }
+    Ok(report)
+}
+
+/// Looks up the details of a product by its ISBN or EAN right away, instead
+/// of leaving it to the enrichment job.
+pub async fn handle_enrich_product(product_id: String, identity: Identity, enricher: Enricher, repo: Arc<dyn WishlistRepository>) -> Result<Product> {
+    let id = ObjectId::with_string(&product_id)?;
+    ensure_can_manage_product(repo.as_ref(), &identity, &id).await?;
+    let product = get_product_by_id(repo.as_ref(), &id).await?;
+    if product.get_ean().is_none() {
+        return Err(Error::Validation("the product has no ISBN or EAN to look up".to_string()));
+    }
+    enrich_product(repo.as_ref(), &enricher, &product).await?;
+    get_product_by_id(repo.as_ref(), &id).await
+}
+
+/// Moves the product to the trash, from where an admin can restore it.
+pub async fn handle_delete_product(product_id: String, identity: Identity, repo: Arc<dyn WishlistRepository>) -> Result<Product> {
+    let id = ObjectId::with_string(&product_id)?;
+    ensure_can_manage_product(repo.as_ref(), &identity, &id).await?;
+    let product = repo.delete_product(&id, current_timestamp()).await?
+        .ok_or(Error::NotFound("product"))?;
+    record_event(repo.as_ref(), &actor_of(Some(&identity)), "delete", Product::COLLECTION, Some(&id), Document::new()).await;
+    Ok(product)
+}
+
+/// Lists the audit log newest first.
+pub async fn handle_get_events(query: EventQuery, page: ListQuery, repo: Arc<dyn WishlistRepository>) -> Result<Loaded<Vec<AuditEvent>>> {
+    let entity_id = query.get_entity_id()?;
+    if entity_id.is_some() && query.get_entity().is_none() {
+        return Err(Error::Validation("entity_id needs an entity".to_string()));
+    }
+    repo.get_events(query.get_entity(), entity_id.as_ref(), page.get_offset(), page.get_size()).await
+}
+
+/// Starts scraping the source named in `query`, or all sources, in the
+/// background. The outcome shows up in the scrape status.
+pub async fn handle_start_scrape(query: ScrapeQuery, scraper: Scraper, repo: Arc<dyn WishlistRepository>) -> Result<ScrapeStatus> {
+    let source = match query.get_source() {
+        Some(name) => {
+            let source = repo.get_source_by_name(name).await?
+                .ok_or(Error::NotFound("source"))?;
+            source.get_name().map(String::from)
+        }
+        None => None,
+    };
+    scraper.start(repo, source)?;
+    Ok(scraper.get_status())
+}
+
+/// Lists the products on a list whose link was found dead by the link check.
+pub async fn handle_get_dead_links(query: ListQuery, repo: Arc<dyn WishlistRepository>) -> Result<Loaded<Vec<Product>>> {
+    let filter = ProductFilter::default().with_archived(false).with_link_dead(true);
+    let products = repo.get_products(&filter, &SortQuery::default(), query.get_offset(), query.get_size()).await?;
+    with_details(repo.as_ref(), products).await
+}
+
+pub async fn handle_get_source_politeness(source_id: String, repo: Arc<dyn WishlistRepository>) -> Result<Politeness> {
+    let id = ObjectId::with_string(&source_id)?;
+    Ok(get_source_by_id(repo.as_ref(), &id).await?.get_politeness().clone())
+}
+
+/// Replaces the politeness of a source, unset limits fall back to the defaults.
+pub async fn handle_set_source_politeness(source_id: String, input: PolitenessInput, repo: Arc<dyn WishlistRepository>) -> Result<Politeness> {
+    let id = ObjectId::with_string(&source_id)?;
+    let document = input.to_document()?;
+    if !repo.update_source(&id, document.clone()).await? {
+        return Err(Error::NotFound("source"));
+    }
+    record_event(repo.as_ref(), ADMIN, "update", Source::COLLECTION, Some(&id), document).await;
+    Ok(get_source_by_id(repo.as_ref(), &id).await?.get_politeness().clone())
+}
+
+/// Sets the affiliate parameter added to the links of a source, or removes it
+/// if no parameter is given.
+pub async fn handle_set_source_affiliate(source_id: String, input: AffiliateInput, repo: Arc<dyn WishlistRepository>) -> Result<Option<Affiliate>> {
+    let id = ObjectId::with_string(&source_id)?;
+    let document = input.to_document()?;
+    if !repo.update_source(&id, document.clone()).await? {
+        return Err(Error::NotFound("source"));
+    }
+    record_event(repo.as_ref(), ADMIN, "update", Source::COLLECTION, Some(&id), document).await;
+    Ok(get_source_by_id(repo.as_ref(), &id).await?.get_affiliate().cloned())
+}
+
+/// Lists the products in the trash, most recently deleted first.
+pub async fn handle_get_trash(query: ListQuery, repo: Arc<dyn WishlistRepository>) -> Result<Loaded<Vec<Product>>> {
+    let products = repo.get_deleted_products(query.get_offset(), query.get_size()).await?;
+    with_details(repo.as_ref(), products).await
+}
+
+pub async fn handle_restore_product(product_id: String, repo: Arc<dyn WishlistRepository>) -> Result<Product> {
+    let id = ObjectId::with_string(&product_id)?;
+    if repo.restore_product(&id).await?.is_none() {
+        return Err(Error::NotFound("product"));
+    }
+    record_event(repo.as_ref(), ADMIN, "restore", Product::COLLECTION, Some(&id), Document::new()).await;
+    get_product_by_id(repo.as_ref(), &id).await
+}
+
+/// Removes a product in the trash for good, together with its price history.
+pub async fn handle_purge_product(product_id: String, repo: Arc<dyn WishlistRepository>) -> Result<Product> {
+    let id = ObjectId::with_string(&product_id)?;
+    let product = repo.purge_product(&id).await?
+        .ok_or(Error::NotFound("product"))?;
+    record_event(repo.as_ref(), ADMIN, "purge", Product::COLLECTION, Some(&id), Document::new()).await;
+    Ok(product)
+}
+
+/// Reserves a product for whoever calls this, the returned token is needed to
+/// undo the reservation.
+pub async fn handle_reserve_product(product_id: String, identity: Option<Identity>, repo: Arc<dyn WishlistRepository>) -> Result<Reservation> {
+    let id = ObjectId::with_string(&product_id)?;
+    get_reservable_product(repo.as_ref(), identity.as_ref(), &id).await?;
+    if repo.get_pledge_pool(&id).await?.is_some() {
+        return Err(Error::Conflict("product is already funded by a pledge pool".to_string()));
+    }
+
+    let token = generate_token();
+    let now = current_timestamp();
+    if !repo.insert_reservation(&id, &token, now).await? {
+        return Err(Error::Conflict("product is already reserved".to_string()));
+    }
+    // The token stays out of the log, it allows canceling the reservation.
+    record_event(repo.as_ref(), &actor_of(identity.as_ref()), "reserve", Product::COLLECTION, Some(&id), Document::new()).await;
+    Ok(Reservation::new(&id, token, now))
+}
+
+pub async fn handle_cancel_reservation(product_id: String, token: String, repo: Arc<dyn WishlistRepository>) -> Result<Product> {
+    let id = ObjectId::with_string(&product_id)?;
+    if !repo.delete_reservation(&id, &token).await? {
+        return Err(Error::NotFound("reservation"));
+    }
+    record_event(repo.as_ref(), ANONYMOUS, "cancel_reservation", Product::COLLECTION, Some(&id), Document::new()).await;
+    get_product_by_id(repo.as_ref(), &id).await
 }
 
-async fn get_source_by_id(client: &Client, id: &ObjectId) -> Result<Source> {
-        let coll = client.database("wishlist").collection("source");
+/// Starts a group gift of a product, to which several people pledge parts of
+/// its target. Whoever may reserve the product may start one, a reserved
+/// product can't be pooled and a pooled one can't be reserved.
+pub async fn handle_create_pledge_pool(product_id: String, identity: Option<Identity>, input: PledgePoolInput, repo: Arc<dyn WishlistRepository>) -> Result<PledgeStatus> {
+    let id = ObjectId::with_string(&product_id)?;
+    let product = get_reservable_product(repo.as_ref(), identity.as_ref(), &id).await?;
+    if product.is_reserved() {
+        return Err(Error::Conflict("product is already reserved".to_string()));
+    }
+    let quantity = product.get_quantity().unwrap_or(1).max(1);
+    let price = product.get_current_price()
+        .or_else(|| product.get_price())
+        .and_then(|price| Money::new(price.into(), product.get_currency()).checked_mul(quantity.into()));
+    let document = input.to_document(&id, price, current_timestamp())?;
+    if !repo.insert_pledge_pool(document.clone()).await? {
+        return Err(Error::Conflict("product already has a pledge pool".to_string()));
+    }
+    record_event(repo.as_ref(), &actor_of(identity.as_ref()), "create_pledge_pool", Product::COLLECTION, Some(&id), document).await;
+    let pool = repo.get_pledge_pool(&id).await?
+        .ok_or(Error::NotFound("pledge pool"))?;
+    Ok(pool.to_status(true))
+}
 
-        coll.find_one(Some(doc! {"_id": id}), None).await
-            .map_err(Error::from)
-            .and_then(|r| r.ok_or(Error::EmptyResult).map(|r| Source::from(&r)))
+/// Shows how much of the target of a pledge pool is funded. The names of the
+/// pledgers are hidden from whoever manages the list.
+pub async fn handle_get_pledge_pool(product_id: String, identity: Option<Identity>, repo: Arc<dyn WishlistRepository>) -> Result<PledgeStatus> {
+    let id = ObjectId::with_string(&product_id)?;
+    let product = get_product_by_id(repo.as_ref(), &id).await?;
+    let list = get_list(repo.as_ref(), product.get_list()).await?;
+    if !can_read(identity.as_ref(), &list) {
+        return Err(Error::NotFound("product"));
+    }
+    // The owner of a secret-santa list doesn't learn that the product is pooled.
+    let policy = ReservationPolicy::load(repo.as_ref(), identity.as_ref()).await?;
+    let pool = repo.get_pledge_pool(&id).await?
+        .filter(|_| !policy.hides(list.get_slug()))
+        .ok_or(Error::NotFound("pledge pool"))?;
+    let is_owner = identity.map(|i| i.can_manage(&list)).unwrap_or(false);
+    Ok(pool.to_status(!is_owner))
 }
 
-async fn get_products_by_id(client: &Client, product_ids: &[ObjectId]) -> Result<Vec<Product>> {
-    let coll = client.database("wishlist").collection("product");
-        let filter = doc! {
-            "_id": { "$in": product_ids}
+/// Removes a pledge pool nobody pledged to yet, so that the product can be reserved again.
+pub async fn handle_delete_pledge_pool(product_id: String, identity: Option<Identity>, repo: Arc<dyn WishlistRepository>) -> Result<Product> {
+    let id = ObjectId::with_string(&product_id)?;
+    get_reservable_product(repo.as_ref(), identity.as_ref(), &id).await?;
+    if !repo.delete_empty_pledge_pool(&id).await? {
+        return match repo.get_pledge_pool(&id).await? {
+            Some(_) => Err(Error::Conflict("pledge pools with pledges can't be removed".to_string())),
+            None => Err(Error::NotFound("pledge pool")),
         };
-        let options = FindOptions::builder()
-            .sort(doc! {"timestamp": -1})
-            .projection(doc! {"_id": false, "item_id": false})
-            .build();
+    }
+    record_event(repo.as_ref(), &actor_of(identity.as_ref()), "delete_pledge_pool", Product::COLLECTION, Some(&id), Document::new()).await;
+    get_product_by_id(repo.as_ref(), &id).await
+}
+
+/// Pledges part of the target of a pledge pool, the returned token is needed
+/// to withdraw the pledge. Pledges can't exceed what is left to fund.
+pub async fn handle_pledge(product_id: String, identity: Option<Identity>, input: PledgeInput, repo: Arc<dyn WishlistRepository>) -> Result<PledgeReceipt> {
+    let id = ObjectId::with_string(&product_id)?;
+    get_reservable_product(repo.as_ref(), identity.as_ref(), &id).await?;
+    let pool = repo.get_pledge_pool(&id).await?
+        .ok_or(Error::NotFound("pledge pool"))?;
+
+    let token = generate_token();
+    let now = current_timestamp();
+    if !repo.add_pledge(&id, input.to_document(&token, now)?).await? {
+        return Err(Error::Conflict("pledge exceeds the amount left to fund".to_string()));
+    }
+    // The token and name stay out of the log, the token allows withdrawing the pledge.
+    record_event(repo.as_ref(), &actor_of(identity.as_ref()), "pledge", Product::COLLECTION, Some(&id), doc! {"amount": input.get_amount()}).await;
+    Ok(PledgeReceipt::new(&id, token, Money::new(input.get_amount(), pool.get_currency()), now))
+}
+
+/// Withdraws a pledge, the pool is removed along with its last pledge.
+pub async fn handle_withdraw_pledge(product_id: String, token: String, repo: Arc<dyn WishlistRepository>) -> Result<Product> {
+    let id = ObjectId::with_string(&product_id)?;
+    if !repo.remove_pledge(&id, &token).await? {
+        return Err(Error::NotFound("pledge"));
+    }
+    repo.delete_empty_pledge_pool(&id).await?;
+    record_event(repo.as_ref(), ANONYMOUS, "withdraw_pledge", Product::COLLECTION, Some(&id), Document::new()).await;
+    get_product_by_id(repo.as_ref(), &id).await
+}
+
+/// Lists the comments on a product, oldest first. On secret-santa lists the
+/// owner and editors only see their own comments, so a page may come back
+/// with fewer comments than requested.
+pub async fn handle_get_comments(product_id: String, identity: Option<Identity>, query: ListQuery, repo: Arc<dyn WishlistRepository>) -> Result<Loaded<Vec<Comment>>> {
+    let id = ObjectId::with_string(&product_id)?;
+    let (_, list) = get_readable_product(repo.as_ref(), identity.as_ref(), &id).await?;
+    let policy = ReservationPolicy::load(repo.as_ref(), identity.as_ref()).await?;
+    let (mut comments, skipped) = repo.get_comments(&id, query.get_offset(), query.get_size()).await?.split();
+    if policy.hides(list.get_slug()) {
+        let user = identity.as_ref().and_then(Identity::get_user_id);
+        comments.retain(|comment| user.is_some() && comment.get_author() == user);
+    }
+    Ok(Loaded::new(comments, skipped))
+}
+
+/// Comments on a product, anyone who can read its list may. The returned
+/// token is needed to delete the comment without a session.
+pub async fn handle_post_comment(product_id: String, identity: Option<Identity>, input: CommentInput, repo: Arc<dyn WishlistRepository>) -> Result<CommentReceipt> {
+    let id = ObjectId::with_string(&product_id)?;
+    get_readable_product(repo.as_ref(), identity.as_ref(), &id).await?;
+    let author = identity.as_ref().and_then(Identity::get_user_id);
+    let document = input.to_document(&id, author, &generate_token(), current_timestamp())?;
+    let comment_id = repo.insert_comment(document).await?;
+    // Neither the text nor the token go into the log, the token allows deleting the comment.
+    record_event(repo.as_ref(), &actor_of(identity.as_ref()), "comment", Product::COLLECTION, Some(&id), doc! {"comment": comment_id.clone()}).await;
+    get_comment(repo.as_ref(), &id, &comment_id).await.map(CommentReceipt::new)
+}
+
+/// Deletes a comment, which its author and whoever manages the list may do.
+pub async fn handle_delete_comment(product_id: String, comment_id: String, identity: Identity, repo: Arc<dyn WishlistRepository>) -> Result<Comment> {
+    let id = ObjectId::with_string(&product_id)?;
+    let comment = get_comment(repo.as_ref(), &id, &ObjectId::with_string(&comment_id)?).await?;
+    let is_author = comment.get_author().is_some() && comment.get_author() == identity.get_user_id();
+    if !is_author {
+        let product = get_product_by_id(repo.as_ref(), &id).await?;
+        get_managed_list(repo.as_ref(), &identity, product.get_list()).await?;
+    }
+    delete_comment(repo.as_ref(), &actor_of(Some(&identity)), comment).await
+}
+
+/// Deletes a comment with the token handed out when it was posted.
+pub async fn handle_delete_comment_with_token(product_id: String, comment_id: String, token: String, repo: Arc<dyn WishlistRepository>) -> Result<Comment> {
+    let id = ObjectId::with_string(&product_id)?;
+    let comment = get_comment(repo.as_ref(), &id, &ObjectId::with_string(&comment_id)?).await?;
+    if comment.get_token().is_empty() || comment.get_token() != token {
+        return Err(Error::NotFound("comment"));
+    }
+    delete_comment(repo.as_ref(), ANONYMOUS, comment).await
+}
+
+async fn get_comment(repo: &dyn WishlistRepository, product_id: &ObjectId, id: &ObjectId) -> Result<Comment> {
+    repo.get_comment(id).await?
+        .filter(|comment| comment.get_product_id() == product_id)
+        .ok_or(Error::NotFound("comment"))
+}
+
+async fn delete_comment(repo: &dyn WishlistRepository, actor: &str, comment: Comment) -> Result<Comment> {
+    let id = comment.get_id().ok_or(Error::FieldNotLoaded("comment", "id"))?;
+    if !repo.delete_comment(id).await? {
+        return Err(Error::NotFound("comment"));
+    }
+    record_event(repo, actor, "delete_comment", Product::COLLECTION, Some(comment.get_product_id()), doc! {"comment": id.clone()}).await;
+    Ok(comment)
+}
+
+/// Records an observed price for a product, called by the scraper for every
+/// product it sees. A new history entry is only written if the price differs
+/// from the last recorded one; returns whether that was the case.
+///
+/// Price drops below the product's threshold or all-time low are passed on to
+/// the `notifier`, as are the price alerts whose target the new price reached.
+/// A new all-time low flags the product until a higher price is recorded.
+/// New prices are logged as changes by `actor`.
+pub async fn record_price(repo: &dyn WishlistRepository, notifier: &Notifier, actor: &str, product_id: &ObjectId, price: i32, timestamp: i32) -> Result<bool> {
+    let last_price = repo.get_last_price_point(product_id).await?
+        .and_then(|p| p.get_price());
+    if last_price == Some(price) {
+        return Ok(false);
+    }
+
+    let product = repo.get_product_by_id(product_id).await?;
+    let old_lowest_price = product.as_ref().and_then(Product::get_lowest_price);
+    repo.insert_price_point(product_id, price, timestamp).await?;
+    record_event(repo, actor, "record_price", Product::COLLECTION, Some(product_id), doc! {"old_price": last_price.map(Bson::Int32).unwrap_or(Bson::Null), "price": price, "timestamp": timestamp}).await;
+    if let Some(product) = product {
+        notifier.notify_price_change(&product, last_price, price);
+        notify_price_alerts(repo, notifier, &product, last_price, price, timestamp).await?;
+    }
+    if notifier.has_subscribers() {
+        if let Some(mut product) = repo.get_product_by_id(product_id).await? {
+            if matches!(get_list(repo, product.get_list()).await, Ok(list) if can_discover(None, &list)) {
+                load_product_details(repo, std::slice::from_mut(&mut product)).await?;
+                let new_low = product.is_at_all_time_low();
+                notifier.publish(WishlistEvent::PriceChanged { product: product.clone(), old_price: last_price, new_price: price });
+                if let (true, Some(old_lowest_price)) = (new_low, old_lowest_price) {
+                    notifier.publish(WishlistEvent::AllTimeLow { product, old_lowest_price, new_price: price });
+                }
+            }
+        }
+    }
+    Ok(true)
+}
 
-    let cursor = coll.find(Some(filter), Some(options)).await?;
-    Ok(extract_cursor_results(cursor).await)
+/// Sends the alerts of `product` whose target the change from `old_price` to
+/// `new_price` reached, unless their user can't read the product's list anymore.
+async fn notify_price_alerts(repo: &dyn WishlistRepository, notifier: &Notifier, product: &Product, old_price: Option<i32>, new_price: i32, timestamp: i32) -> Result<()> {
+    let id = match product.get_id() {
+        Some(id) => id,
+        None => return Ok(()),
+    };
+    let alerts: Vec<PriceAlert> = repo.get_price_alerts_of_product(id).await?
+        .into_value()
+        .into_iter()
+        .filter(|alert| alert.is_reached(old_price, new_price))
+        .collect();
+    if alerts.is_empty() {
+        return Ok(());
+    }
+    let list = get_list(repo, product.get_list()).await?;
+    for alert in alerts {
+        let identity = match alert.get_user_id() {
+            Some(user) => Identity::User { id: user.clone() },
+            None => Identity::Admin,
+        };
+        if !can_read(Some(&identity), &list) {
+            continue;
+        }
+        notifier.notify_price_alert(&alert, product, old_price, new_price);
+        if let Some(alert_id) = alert.get_id() {
+            repo.set_price_alert_triggered(alert_id, timestamp).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Stores a new snapshot of `list`, called by the scraper after each run.
+pub async fn record_wishlist_snapshot(repo: &dyn WishlistRepository, notifier: &Notifier, list: &str, product_ids: &[ObjectId], timestamp: i32) -> Result<ObjectId> {
+    let list = get_list(repo, list).await?;
+    let id = repo.insert_wishlist(list.get_slug(), timestamp, product_ids).await?;
+    record_event(repo, SCRAPER, "record_snapshot", Wishlist::COLLECTION, Some(&id), doc! {"list": list.get_slug(), "timestamp": timestamp, "products": product_ids.to_vec()}).await;
+    let archived = repo.update_archived(list.get_slug(), product_ids).await?;
+    record_archived(repo, list.get_slug(), archived).await;
+    publish_list_changes(repo, notifier, &list).await?;
+    Ok(id)
+}
+
+/// Flags the products of every list as archived or current by its latest
+/// snapshot, for snapshots not written through `record_wishlist_snapshot`.
+/// Returns the number of products whose flag changed.
+pub async fn update_archived_products(repo: &dyn WishlistRepository) -> Result<u64> {
+    let mut updated = 0;
+    for list in get_all_lists(repo).await?.into_value() {
+        let latest = match repo.get_nth_wishlist_reverse(list.get_slug(), 0).await {
+            Ok(wishlist) => wishlist,
+            Err(Error::EmptyResult) => continue,
+            Err(e) => return Err(e),
+        };
+        let archived = repo.update_archived(list.get_slug(), latest.get_product_ids().unwrap_or_default()).await?;
+        record_archived(repo, list.get_slug(), archived).await;
+        updated += archived;
+    }
+    Ok(updated)
+}
+
+/// Logs that the archived flag of `changed` products of `list` changed, if any did.
+async fn record_archived(repo: &dyn WishlistRepository, list: &str, changed: u64) {
+    if changed > 0 {
+        record_event(repo, SCRAPER, "update_archived", Product::COLLECTION, None, doc! {"list": list, "changed": changed as i64}).await;
+    }
+}
+
+/// Publishes the products added and removed by the newest snapshot of every
+/// list compared to the one before it, and sends them to Telegram and by email.
+pub async fn publish_snapshot_changes(repo: &dyn WishlistRepository, notifier: &Notifier) -> Result<()> {
+    if !notifier.has_subscribers() && !notifier.notifies_snapshots() {
+        return Ok(());
+    }
+    for list in get_all_lists(repo).await?.into_value() {
+        publish_list_changes(repo, notifier, &list).await?;
+    }
+    Ok(())
+}
+
+/// Mails a digest comparing the newest snapshot of every list with the one
+/// from `period` ago. Lists without a snapshot that old are left out.
+/// Returns the number of lists that changed.
+pub async fn send_email_digest(repo: &dyn WishlistRepository, notifier: &Notifier, period: Duration) -> Result<usize> {
+    let since = (chrono::Utc::now().timestamp() - period.as_secs() as i64) as i32;
+    let mut changes = Vec::new();
+    for list in get_all_lists(repo).await?.into_value() {
+        let from = match repo.get_wishlist_at(list.get_slug(), since).await? {
+            Some(wishlist) => wishlist,
+            None => continue,
+        };
+        let to = repo.get_nth_wishlist_reverse(list.get_slug(), 0).await?;
+        let diff = compare_wishlists(repo, &from, &to).await?.into_value();
+        if !diff.is_empty() {
+            changes.push((list, diff));
+        }
+    }
+    notifier.send_digest(&changes, period.as_secs() / (24 * 60 * 60)).await?;
+    Ok(changes.len())
+}
+
+/// Publishes the changes of the newest snapshot of `list` on the event
+/// stream and sends them to Telegram and by email. The event stream is
+/// public, so changes of private and unlisted lists are only sent to the
+/// other channels.
+async fn publish_list_changes(repo: &dyn WishlistRepository, notifier: &Notifier, list: &List) -> Result<()> {
+    let publish = notifier.has_subscribers() && can_discover(None, list);
+    if !publish && !notifier.notifies_snapshots() {
+        return Ok(());
+    }
+    let newest = match repo.get_nth_wishlist_reverse(list.get_slug(), 0).await {
+        Ok(wishlist) => wishlist,
+        Err(Error::EmptyResult) => return Ok(()),
+        Err(e) => return Err(e),
+    };
+    let previous = match repo.get_nth_wishlist_reverse(list.get_slug(), 1).await {
+        Ok(wishlist) => wishlist,
+        Err(Error::EmptyResult) => return Ok(()),
+        Err(e) => return Err(e),
+    };
+    let diff = compare_wishlists(repo, &previous, &newest).await?.into_value();
+    if diff.is_empty() {
+        return Ok(());
+    }
+
+    if publish {
+        for product in diff.get_added() {
+            notifier.publish(WishlistEvent::ProductAdded { product: product.clone() });
+        }
+        for product in diff.get_removed() {
+            notifier.publish(WishlistEvent::ProductRemoved { product: product.clone() });
+        }
+    }
+    notifier.notify_snapshot(list, &diff);
+    Ok(())
+}
+
+/// Shows which snapshots the next run of the prune job deletes.
+pub async fn handle_preview_retention(retention: RetentionPolicy, repo: Arc<dyn WishlistRepository>) -> Result<Vec<RetentionPreview>> {
+    retention.preview(repo.as_ref(), current_timestamp().into()).await
+}
+
+pub async fn handle_set_maintenance(input: MaintenanceInput, maintenance: Maintenance) -> Result<MaintenanceStatus> {
+    let status = maintenance.set(&input)?;
+    if status.is_read_only() {
+        warn!("Maintenance mode enabled, the API is read-only");
+    } else {
+        info!("Maintenance mode disabled");
+    }
+    Ok(status)
+}
+
+pub async fn handle_get_push_key(notifier: Notifier) -> Result<PushKey> {
+    notifier.get_push_key()
+        .map(|key| PushKey::new(key.to_string()))
+        .ok_or_else(push_not_configured)
+}
+
+/// Stores the push subscription of a browser. Subscribing again with the same
+/// endpoint replaces the keys and the user it belongs to.
+pub async fn handle_push_subscribe(identity: Identity, input: PushSubscriptionInput, notifier: Notifier, repo: Arc<dyn WishlistRepository>) -> Result<PushSubscription> {
+    if notifier.get_push_key().is_none() {
+        return Err(push_not_configured());
+    }
+    let user = match &identity {
+        Identity::Admin => None,
+        Identity::User { id } => Some(id),
+        Identity::Share { .. } => return Err(Error::Forbidden("share tokens can't subscribe to push notifications".to_string())),
+    };
+    let document = input.to_document(user, current_timestamp())?;
+    check_public_host(&input.get_endpoint()?, "push endpoint").await?;
+    repo.upsert_push_subscription(document).await?;
+    get_push_subscription(repo.as_ref(), &identity, input.get_endpoint()?.as_str()).await
+}
+
+/// Removes a push subscription of the identity, the admin may remove any.
+pub async fn handle_push_unsubscribe(identity: Identity, input: PushUnsubscribeInput, repo: Arc<dyn WishlistRepository>) -> Result<PushSubscription> {
+    let subscription = get_push_subscription(repo.as_ref(), &identity, input.get_endpoint()).await?;
+    repo.delete_push_subscription(subscription.get_endpoint()).await?;
+    Ok(subscription)
+}
+
+/// Returns the categories, with the numbers of their products for `with_counts=true`.
+pub async fn handle_get_categories(identity: Option<Identity>, query: CategoryListQuery, repo: Arc<dyn WishlistRepository>) -> Result<Loaded<Vec<CategoryListing>>> {
+    let (categories, mut skipped) = repo.get_categories().await?.split();
+    let mut counts: Option<Vec<CategoryCount>> = None;
+    if query.with_counts() {
+        let lists = get_discoverable_slugs(repo.as_ref(), identity.as_ref()).await?;
+        let (category_counts, skipped_counts) = repo.count_products_per_category(Some(&lists)).await?.split();
+        skipped += skipped_counts;
+        counts = Some(category_counts);
+    }
+    let listings = categories.into_iter()
+        .map(|category| CategoryListing::new(category, counts.as_deref()))
+        .collect();
+    Ok(Loaded::new(listings, skipped))
+}
+
+/// Returns the categories nested under their parents.
+pub async fn handle_get_category_tree(repo: Arc<dyn WishlistRepository>) -> Result<Loaded<Vec<CategoryNode>>> {
+    let (categories, skipped) = repo.get_categories().await?.split();
+    Ok(Loaded::new(CategoryNode::build_tree(&categories), skipped))
+}
+
+pub async fn handle_create_category(input: CategoryInput, repo: Arc<dyn WishlistRepository>) -> Result<Category> {
+    let document = input.to_document()?;
+    ensure_category_name_free(repo.as_ref(), input.get_name(), None).await?;
+    if let Some(parent) = input.get_parent_id()? {
+        get_parent_category(repo.as_ref(), &parent).await?;
+    }
+
+    let id = repo.insert_category(document.clone()).await?;
+    record_event(repo.as_ref(), ADMIN, "create", Category::COLLECTION, Some(&id), document).await;
+    get_category_by_id(repo.as_ref(), &id).await
+}
+
+pub async fn handle_update_category(category_id: String, input: CategoryInput, repo: Arc<dyn WishlistRepository>) -> Result<Category> {
+    let id = ObjectId::with_string(&category_id)?;
+    let document = input.to_document()?;
+    ensure_category_name_free(repo.as_ref(), input.get_name(), Some(&id)).await?;
+    if let Some(parent) = input.get_parent_id()? {
+        get_parent_category(repo.as_ref(), &parent).await?;
+        let categories = repo.get_categories().await?.into_value();
+        if Category::is_ancestor(&categories, &id, &parent) {
+            return Err(Error::Validation("a category can't be nested in itself or its subcategories".to_string()));
+        }
+    }
+
+    if !repo.update_category(&id, document.clone()).await? {
+        return Err(Error::NotFound("category"));
+    }
+    record_event(repo.as_ref(), ADMIN, "update", Category::COLLECTION, Some(&id), document).await;
+    get_category_by_id(repo.as_ref(), &id).await
+}
+
+pub async fn handle_delete_category(category_id: String, query: DeleteCategoryQuery, repo: Arc<dyn WishlistRepository>) -> Result<Category> {
+    let id = ObjectId::with_string(&category_id)?;
+    let category = get_category_by_id(repo.as_ref(), &id).await?;
+
+    let categories = repo.get_categories().await?.into_value();
+    if categories.iter().any(|c| c.get_parent_id() == Some(&id)) {
+        return Err(Error::Conflict("category still has subcategories, move or delete them first".to_string()));
+    }
+    let rules = repo.get_rules().await?.into_value();
+    if rules.iter().any(|r| r.get_category_id() == &id) {
+        return Err(Error::Conflict("category is still assigned by category rules, change or delete them first".to_string()));
+    }
+
+    let mut payload = Document::new();
+    let product_count = repo.count_products_by_category(Some(std::slice::from_ref(&id)), None).await?;
+    if product_count > 0 {
+        let target = match query.get_reassign_to() {
+            Some(target) => ObjectId::with_string(target)?,
+            None => {
+                return Err(Error::Conflict(format!(
+                    "category is still referenced by {} products",
+                    product_count
+                )));
+            }
+        };
+        if target == id {
+            return Err(Error::Validation("cannot reassign products to the deleted category".to_string()));
+        }
+        get_category_by_id(repo.as_ref(), &target).await?;
+        let reassigned = repo.reassign_category(&id, &target).await?;
+        payload = doc! {"reassigned_to": target, "reassigned": reassigned as i64};
+    }
+
+    repo.delete_category(&id).await?;
+    record_event(repo.as_ref(), ADMIN, "delete", Category::COLLECTION, Some(&id), payload).await;
+    Ok(category)
+}
+
+/// Merges the category `from` into `into`, e.g. "games" into "Games". The
+/// products, subcategories and rules of `from` move to `into`, then `from`
+/// is deleted. MongoDB can't do this in one transaction, so the references
+/// written while the merge runs are moved as well and `from` is only deleted
+/// once none are left, otherwise the merge answers 409 and can be retried.
+pub async fn handle_merge_categories(from_id: String, into_id: String, repo: Arc<dyn WishlistRepository>) -> Result<CategoryMerge> {
+    let from_id = ObjectId::with_string(&from_id)?;
+    let into_id = ObjectId::with_string(&into_id)?;
+    let from = get_category_by_id(repo.as_ref(), &from_id).await?;
+    get_category_by_id(repo.as_ref(), &into_id).await?;
+    let categories = repo.get_categories().await?.into_value();
+    if Category::is_ancestor(&categories, &from_id, &into_id) {
+        return Err(Error::Validation("a category can't be merged into itself or its subcategories".to_string()));
+    }
+
+    let mut moved = repo.merge_category(&from_id, &into_id).await?;
+    if repo.count_category_references(&from_id).await? > 0 {
+        moved += repo.merge_category(&from_id, &into_id).await?;
+        if repo.count_category_references(&from_id).await? > 0 {
+            return Err(Error::Conflict("category is still being assigned, merge it again".to_string()));
+        }
+    }
+    repo.delete_category(&from_id).await?;
+    let payload = doc! {"merged_into": into_id.clone(), "moved": moved as i64};
+    record_event(repo.as_ref(), ADMIN, "merge", Category::COLLECTION, Some(&from_id), payload).await;
+    let into = get_category_by_id(repo.as_ref(), &into_id).await?;
+    Ok(CategoryMerge::new(from, into, moved))
+}
+
+/// Lists the category rules in the order they are tried.
+pub async fn handle_get_rules(repo: Arc<dyn WishlistRepository>) -> Result<Loaded<Vec<CategoryRule>>> {
+    repo.get_rules().await
+}
+
+pub async fn handle_create_rule(input: RuleInput, repo: Arc<dyn WishlistRepository>) -> Result<CategoryRule> {
+    let document = input.to_document()?;
+    validate_rule_category(repo.as_ref(), &input).await?;
+    let id = repo.insert_rule(document.clone()).await?;
+    record_event(repo.as_ref(), ADMIN, "create", CategoryRule::COLLECTION, Some(&id), document).await;
+    get_rule_by_id(repo.as_ref(), &id).await
+}
+
+pub async fn handle_update_rule(rule_id: String, input: RuleInput, repo: Arc<dyn WishlistRepository>) -> Result<CategoryRule> {
+    let id = ObjectId::with_string(&rule_id)?;
+    let document = input.to_document()?;
+    validate_rule_category(repo.as_ref(), &input).await?;
+    if !repo.update_rule(&id, document.clone()).await? {
+        return Err(Error::NotFound("category rule"));
+    }
+    record_event(repo.as_ref(), ADMIN, "update", CategoryRule::COLLECTION, Some(&id), document).await;
+    get_rule_by_id(repo.as_ref(), &id).await
+}
+
+pub async fn handle_delete_rule(rule_id: String, repo: Arc<dyn WishlistRepository>) -> Result<CategoryRule> {
+    let id = ObjectId::with_string(&rule_id)?;
+    let rule = get_rule_by_id(repo.as_ref(), &id).await?;
+    repo.delete_rule(&id).await?;
+    record_event(repo.as_ref(), ADMIN, "delete", CategoryRule::COLLECTION, Some(&id), Document::new()).await;
+    Ok(rule)
+}
+
+/// Runs the category rules over all uncategorized products, e.g. after adding
+/// rules. Returns the number of products categorized.
+pub async fn handle_apply_rules(repo: Arc<dyn WishlistRepository>) -> Result<u64> {
+    classify_uncategorized(repo.as_ref()).await
+}
+
+/// Reports products and snapshots referencing missing documents and duplicate items.
+pub async fn handle_check_consistency(repo: Arc<dyn WishlistRepository>) -> Result<ConsistencyReport> {
+    check_consistency(repo.as_ref(), false).await
+}
+
+/// Repairs the references to missing documents the consistency check finds.
+pub async fn handle_fix_consistency(repo: Arc<dyn WishlistRepository>) -> Result<ConsistencyReport> {
+    let report = check_consistency(repo.as_ref(), true).await?;
+    info!("Fixed {} of {} consistency problems", report.get_fixed_count(), report.get_problem_count());
+    Ok(report)
+}
+
+pub async fn handle_get_products_by_category_name(identity: Option<Identity>, query: CategoryQuery, list: ListQuery, sort: SortQuery, views: ViewCounter, repo: Arc<dyn WishlistRepository>) -> Result<Page<Product>> {
+    let category_ids = get_category_ids(repo.as_ref(), &query).await?;
+    get_category_page(repo.as_ref(), identity.as_ref(), Some(&category_ids), &list, &sort, &views).await
+}
+
+/// Returns the products without a category, one page at a time.
+pub async fn handle_get_uncategorized_products(identity: Option<Identity>, list: ListQuery, sort: SortQuery, views: ViewCounter, repo: Arc<dyn WishlistRepository>) -> Result<Page<Product>> {
+    get_category_page(repo.as_ref(), identity.as_ref(), None, &list, &sort, &views).await
+}
+
+/// Counts the products of the category on the lists `identity` can discover.
+pub async fn handle_get_product_count_by_category_name(identity: Option<Identity>, query: CategoryQuery, repo: Arc<dyn WishlistRepository>) -> Result<u64> {
+    let category_ids = get_category_ids(repo.as_ref(), &query).await?;
+    let lists = get_discoverable_slugs(repo.as_ref(), identity.as_ref()).await?;
+    repo.count_products_by_category(Some(&category_ids), Some(&lists)).await
+}
+
+/// Returns a page of the products of any of `categories`, or of the
+/// uncategorized ones for `None`, that `identity` may discover.
+async fn get_category_page(repo: &dyn WishlistRepository, identity: Option<&Identity>, categories: Option<&[ObjectId]>, list: &ListQuery, sort: &SortQuery, views: &ViewCounter) -> Result<Page<Product>> {
+    let lists = get_discoverable_slugs(repo, identity).await?;
+    let products = repo.get_products_by_category(categories, Some(&lists), sort, list.get_offset(), list.get_size()).await?;
+    let products = with_details(repo, products).await?;
+    views.record(products.get_value());
+    let total = repo.count_products_by_category(categories, Some(&lists)).await?;
+    hide_reservations(repo, identity, Page::new(products, list.get_offset(), list.get_size(), total)).await
+}
+
+/// Stores a product created through the API, categorized by the rules if it
+/// has no category, and records its first price, logged as changes by `actor`.
+/// A product of the list with the same source and item id is kept instead,
+/// with the price recorded and `last_seen` updated, returned along with
+/// `false` for not having been created.
+async fn insert_new_product(repo: &dyn WishlistRepository, notifier: &Notifier, classifier: &Classifier, actor: &str, mut document: Document, price: i32) -> Result<(ObjectId, bool)> {
+    if document.get_object_id("category").is_err() {
+        if let Some(category) = classifier.classify(document.get_str("name")?, document.get_str("description").ok()) {
+            document.insert("category", category.clone());
+        }
+    }
+    let now = current_timestamp();
+    document.insert("first_seen", now);
+    document.insert("last_seen", now);
+    // Products only become current once a snapshot lists them.
+    document.insert("archived", true);
+    let (id, created) = repo.upsert_product(document.clone()).await?;
+    if created {
+        record_event(repo, actor, "create", Product::COLLECTION, Some(&id), document).await;
+    }
+    record_price(repo, notifier, actor, &id, price, now).await?;
+    Ok((id, created))
+}
+
+/// Imports the Steam wishlist of the profile `steam_id` into `list`, which
+/// can't be the default list as its snapshots only hold the games. Games new
+/// on the wishlist are added as products of the source `Steam`, for the
+/// others the current price is recorded, the sale price during a sale, so
+/// that price drops are notified. Afterwards a snapshot of the wishlist is
+/// taken, archiving the games removed from it. Games that can't be bought
+/// yet are reported as failed and imported once they can.
+pub(crate) async fn import_steam_wishlist(repo: &dyn WishlistRepository, notifier: &Notifier, steam: &SteamClient, actor: &str, steam_id: &str, list: &str) -> Result<ImportReport> {
+    if !is_steam_id(steam_id) {
+        return Err(Error::Validation("steam_id must be the 17 digit SteamID64 of a profile".to_string()));
+    }
+    if list == DEFAULT_LIST {
+        return Err(Error::Validation("a Steam wishlist needs a list of its own".to_string()));
+    }
+    let list = get_list(repo, list).await?;
+    let app_ids = steam.get_wishlist(steam_id).await?;
+    let prices = steam.get_prices(&app_ids).await?;
+    let classifier = Classifier::load(repo).await?;
+    let mut names = ImportedNames::default();
+    let mut report = ImportReport::default();
+    let mut product_ids = Vec::with_capacity(app_ids.len());
+    for app_id in app_ids {
+        let price = prices.get(&app_id).cloned();
+        match import_steam_game(repo, notifier, steam, &classifier, actor, list.get_slug(), app_id, price, &mut names).await {
+            Ok((status, id)) => {
+                product_ids.push(id.clone());
+                report.add(status, Some(id), None);
+            }
+            Err(Error::Validation(message)) | Err(Error::Conflict(message)) => {
+                report.add(ImportStatus::Failed, None, Some(format!("app {}: {}", app_id, message)));
+            }
+            Err(e) => return Err(e),
+        }
+    }
+    record_wishlist_snapshot(repo, notifier, list.get_slug(), &product_ids, current_timestamp()).await?;
+    if report.get_created() > 0 {
+        info!("Imported {} games from Steam into the list '{}'", report.get_created(), list.get_slug());
+    }
+    Ok(report)
+}
+
+/// Adds the game `app_id` to `list` or, if it is on it already, updates its
+/// discount and availability and records its `price`.
+#[allow(clippy::too_many_arguments)]
+async fn import_steam_game(repo: &dyn WishlistRepository, notifier: &Notifier, steam: &SteamClient, classifier: &Classifier, actor: &str, list: &str, app_id: u32, price: Option<SteamPrice>, names: &mut ImportedNames) -> Result<(ImportStatus, ObjectId)> {
+    let url = steam.get_app_url(app_id);
+    let item_id = item_id_of(app_id);
+    if let Some(duplicate) = repo.find_duplicate_product(list, Some(&item_id), &url).await? {
+        if duplicate.is_deleted() {
+            return Err(Error::Conflict("the game is in the trash, restore or purge it first".to_string()));
+        }
+        let id = duplicate.get_id().cloned().ok_or(Error::FieldNotLoaded("product", "id"))?;
+        let availability = if price.is_some() { Availability::InStock } else { Availability::OutOfStock };
+        let discount = price.map(|p| p.get_discount_percent());
+        if duplicate.get_availability() != Some(availability) || duplicate.get_discount_percent() != discount {
+            let update = doc! {
+                "availability": availability.get_code(),
+                "discount_percent": discount.map(Bson::Int32).unwrap_or(Bson::Null),
+            };
+            repo.update_product(&id, update.clone()).await?;
+            record_event(repo, actor, "update", Product::COLLECTION, Some(&id), update).await;
+        }
+        if let Some(price) = price {
+            record_price(repo, notifier, actor, &id, price.get_price(), current_timestamp()).await?;
+        }
+        return Ok((ImportStatus::Duplicate, id));
+    }
+
+    let game = steam.get_game(app_id).await?
+        .ok_or_else(|| Error::Validation("the Steam store doesn't know the game".to_string()))?;
+    let price = game.get_price()
+        .ok_or_else(|| Error::Validation(format!("{} can't be bought yet", game.get_name())))?;
+    let input = |source: &ObjectId| {
+        ProductInput::new(game.get_name().to_string(), price.get_price(), url.clone(), source)
+            .with_currency(Some(price.get_currency()))
+            .with_item_id(Some(item_id.clone()))
+            .with_url_img(game.get_image().map(String::from))
+            .with_availability(Some(Availability::InStock))
+            .with_list(Some(list.to_string()))
+    };
+    // Validates the game before creating the source for it.
+    input(&ObjectId::new()).to_document()?;
+
+    let source = resolve_import_source(repo, Some(STEAM_SOURCE), &url, names).await?;
+    let mut document = input(&source).to_document()?;
+    document.insert("discount_percent", price.get_discount_percent());
+    // A concurrent import may have added the game since it was looked up.
+    match insert_new_product(repo, notifier, classifier, actor, document, price.get_price()).await? {
+        (id, true) => Ok((ImportStatus::Created, id)),
+        (id, false) => Ok((ImportStatus::Duplicate, id)),
+    }
+}
+
+/// Ids of the sources and categories an import resolved or created so far, by name.
+#[derive(Default)]
+struct ImportedNames {
+    sources: BTreeMap<String, ObjectId>,
+    categories: BTreeMap<String, ObjectId>,
+}
+
+async fn import_product(repo: &dyn WishlistRepository, notifier: &Notifier, classifier: &Classifier, list: &str, row: &ImportRow, names: &mut ImportedNames) -> Result<(ImportStatus, ObjectId)> {
+    let (name, url, price) = (row.get_name()?, row.get_url()?, row.get_price()?);
+    if let Some(duplicate) = repo.find_duplicate_product(list, row.get_item_id(), url).await? {
+        if duplicate.is_deleted() {
+            return Err(Error::Conflict("the product is in the trash, restore or purge it first".to_string()));
+        }
+        let id = duplicate.get_id().cloned().ok_or(Error::FieldNotLoaded("product", "id"))?;
+        return Ok((ImportStatus::Duplicate, id));
+    }
+    let input = |source: &ObjectId, category: Option<&ObjectId>| {
+        ProductInput::new(name.to_string(), price, url.to_string(), source)
+            .with_currency(row.get_currency())
+            .with_item_id(row.get_item_id().map(str::to_string))
+            .with_category(category)
+            .with_list(Some(list.to_string()).filter(|l| l != DEFAULT_LIST))
+    };
+    // Validates the row before creating a source or category for it.
+    input(&ObjectId::new(), None).to_document()?;
+
+    let source = resolve_import_source(repo, row.get_source(), url, names).await?;
+    let category = match row.get_category() {
+        Some(category) => Some(resolve_import_category(repo, category, names).await?),
+        None => None,
+    };
+    let document = input(&source, category.as_ref()).to_document()?;
+    match insert_new_product(repo, notifier, classifier, ADMIN, document, price).await? {
+        (id, true) => Ok((ImportStatus::Created, id)),
+        (id, false) => Ok((ImportStatus::Duplicate, id)),
+    }
+}
+
+/// Returns the source named `name`, or named after the host of `url` if no
+/// name is given, creating it if it doesn't exist.
+async fn resolve_import_source(repo: &dyn WishlistRepository, name: Option<&str>, url: &str, names: &mut ImportedNames) -> Result<ObjectId> {
+    let origin = url.splitn(4, '/').take(3).collect::<Vec<_>>().join("/");
+    let host = origin.rsplit('/').next().unwrap_or_default();
+    let name = name.unwrap_or_else(|| host.strip_prefix("www.").unwrap_or(host));
+    if name.is_empty() || name.len() > MAX_SOURCE_NAME_LENGTH {
+        return Err(Error::Validation(format!("source must be between 1 and {} characters", MAX_SOURCE_NAME_LENGTH)));
+    }
+    if let Some(id) = names.sources.get(name) {
+        return Ok(id.clone());
+    }
+    let id = match repo.get_source_by_name(name).await? {
+        Some(source) => source.get_id().cloned().ok_or(Error::FieldNotLoaded("source", "id"))?,
+        None => repo.insert_source(doc! {"name": name, "url": origin.as_str()}).await?,
+    };
+    names.sources.insert(name.to_string(), id.clone());
+    Ok(id)
+}
+
+/// Returns the source named after the host of `url`, creating it if it doesn't exist.
+pub(crate) async fn resolve_source_for_url(repo: &dyn WishlistRepository, url: &str) -> Result<ObjectId> {
+    resolve_import_source(repo, None, url, &mut ImportedNames::default()).await
+}
+
+/// Returns the source whose URL has the domain of `url`, like `shop.com` for
+/// `https://www.shop.com/item`, or of which the host of `url` is a subdomain.
+async fn find_source_by_domain(repo: &dyn WishlistRepository, url: &reqwest::Url) -> Result<Option<Source>> {
+    let domain = match domain_of(url) {
+        Some(domain) => domain,
+        None => return Ok(None),
+    };
+    let sources = repo.get_sources().await?.into_value();
+    Ok(sources.into_iter().find(|source| {
+        let source_domain = source.get_url()
+            .and_then(|url| reqwest::Url::parse(url).ok())
+            .and_then(|url| domain_of(&url));
+        match source_domain {
+            Some(source_domain) => domain == source_domain || domain.ends_with(&format!(".{}", source_domain)),
+            None => false,
+        }
+    }))
+}
+
+fn domain_of(url: &reqwest::Url) -> Option<String> {
+    let host = url.host_str()?.trim_end_matches('.').to_lowercase();
+    let domain = host.strip_prefix("www.").map(String::from).unwrap_or(host);
+    Some(domain).filter(|d| !d.is_empty())
+}
+
+/// Returns the category named `name`, creating it at the top level if it doesn't exist.
+async fn resolve_import_category(repo: &dyn WishlistRepository, name: &str, names: &mut ImportedNames) -> Result<ObjectId> {
+    if let Some(id) = names.categories.get(name) {
+        return Ok(id.clone());
+    }
+    let id = match repo.get_category_by_name(name).await? {
+        Some(category) => category.get_id().cloned().ok_or(Error::FieldNotLoaded("category", "id"))?,
+        None => repo.insert_category(CategoryInput::new(name.to_string()).to_document()?).await?,
+    };
+    names.categories.insert(name.to_string(), id.clone());
+    Ok(id)
+}
+
+async fn validate_product_references(repo: &dyn WishlistRepository, document: &Document) -> Result<()> {
+    let source = document.get_object_id("source")?;
+    if !repo.source_exists(source).await? {
+        return Err(Error::Validation("source does not exist".to_string()));
+    }
+    for offer in document.get_array("offers").map(|offers| offers.as_slice()).unwrap_or_default() {
+        let source = offer.as_document().and_then(|offer| offer.get_object_id("source").ok());
+        if let Some(source) = source {
+            if !repo.source_exists(source).await? {
+                return Err(Error::Validation(format!("source {} of an offer does not exist", source.to_hex())));
+            }
+        }
+    }
+    if let Ok(category) = document.get_object_id("category") {
+        if repo.get_category_by_id(category).await?.is_none() {
+            return Err(Error::Validation("category does not exist".to_string()));
+        }
+    }
+    if let Ok(list) = document.get_str("list") {
+        if get_list(repo, list).await.is_err() {
+            return Err(Error::Validation("list does not exist".to_string()));
+        }
+    }
+    Ok(())
+}
+
+/// Returns the last price recorded at or before `timestamp` from a history sorted oldest first.
+fn price_at(history: &[PricePoint], timestamp: Option<i32>) -> Option<i32> {
+    let timestamp = timestamp?;
+    history.iter()
+        .take_while(|p| p.get_timestamp().map(|t| t <= timestamp).unwrap_or(false))
+        .last()
+        .and_then(|p| p.get_price())
+}
+
+fn generate_token() -> String {
+    let bytes: [u8; 16] = rand::random();
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Returns the subscription with `endpoint` if it belongs to `identity`.
+async fn get_push_subscription(repo: &dyn WishlistRepository, identity: &Identity, endpoint: &str) -> Result<PushSubscription> {
+    repo.get_push_subscription(endpoint).await?
+        .filter(|s| matches!(identity, Identity::Admin) || s.get_user_id() == identity.get_user_id())
+        .ok_or(Error::NotFound("push subscription"))
+}
+
+async fn get_price_alert(repo: &dyn WishlistRepository, identity: &Identity, id: &ObjectId) -> Result<PriceAlert> {
+    repo.get_price_alert(id).await?
+        .filter(|alert| owns_price_alert(identity, alert))
+        .ok_or(Error::NotFound("price alert"))
+}
+
+fn owns_price_alert(identity: &Identity, alert: &PriceAlert) -> bool {
+    match identity {
+        Identity::Admin => true,
+        _ => alert.get_user_id().is_some() && alert.get_user_id() == identity.get_user_id(),
+    }
+}
+
+fn push_not_configured() -> Error {
+    Error::Unavailable("push notifications are not configured".to_string())
+}
+
+async fn get_category_by_id(repo: &dyn WishlistRepository, id: &ObjectId) -> Result<Category> {
+    repo.get_category_by_id(id).await?
+        .ok_or(Error::NotFound("category"))
+}
+
+async fn get_source_by_id(repo: &dyn WishlistRepository, id: &ObjectId) -> Result<Source> {
+    repo.get_sources_by_id(std::slice::from_ref(id)).await?
+        .into_value()
+        .into_iter()
+        .next()
+        .ok_or(Error::NotFound("source"))
+}
+
+async fn get_rule_by_id(repo: &dyn WishlistRepository, id: &ObjectId) -> Result<CategoryRule> {
+    repo.get_rule_by_id(id).await?
+        .ok_or(Error::NotFound("category rule"))
+}
+
+async fn validate_rule_category(repo: &dyn WishlistRepository, input: &RuleInput) -> Result<()> {
+    if repo.get_category_by_id(&input.get_category()?).await?.is_none() {
+        return Err(Error::Validation("category does not exist".to_string()));
+    }
+    Ok(())
+}
+
+async fn ensure_category_name_free(repo: &dyn WishlistRepository, name: &str, except: Option<&ObjectId>) -> Result<()> {
+    if repo.category_name_exists(name, except).await? {
+        return Err(Error::Conflict(format!("category '{}' already exists", name)));
+    }
+    Ok(())
+}
+
+/// A missing parent is a validation error rather than a 404 of the category itself.
+async fn get_parent_category(repo: &dyn WishlistRepository, id: &ObjectId) -> Result<Category> {
+    repo.get_category_by_id(id).await?
+        .ok_or_else(|| Error::Validation("parent category does not exist".to_string()))
+}
+
+/// Resolves the category of `query` to its id, followed by the ids of its
+/// subcategories if the query is recursive.
+async fn get_category_ids(repo: &dyn WishlistRepository, query: &CategoryQuery) -> Result<Vec<ObjectId>> {
+    let id = get_category_id_by_name(repo, query.get_category()).await?;
+    if !query.is_recursive() {
+        return Ok(vec![id]);
+    }
+    let categories = repo.get_categories().await?.into_value();
+    Ok(Category::collect_descendants(&categories, &id))
+}
+
+async fn get_category_id_by_name(repo: &dyn WishlistRepository, name: &str) -> Result<ObjectId> {
+    let category = repo.get_category_by_name(name).await?.ok_or(Error::NotFound("category"))?;
+    Ok(category.get_id().ok_or(Error::FieldNotLoaded("category", "id"))?.clone())
+}
+
+async fn get_last_snapshot(repo: &dyn WishlistRepository, list: &str) -> Result<Wishlist> {
+    repo.get_nth_wishlist_reverse(list, 0).await
+}
+
+/// Returns the list with `slug`, the default list always exists.
+async fn get_list(repo: &dyn WishlistRepository, slug: &str) -> Result<List> {
+    if slug == DEFAULT_LIST {
+        return Ok(List::default_list());
+    }
+    repo.get_list_by_slug(slug).await?
+        .ok_or(Error::NotFound("list"))
+}
+
+/// Returns the list with `slug` if `identity` may read it. Private lists are
+/// reported as missing so that their slugs don't leak.
+async fn get_readable_list(repo: &dyn WishlistRepository, slug: &str, identity: Option<&Identity>) -> Result<List> {
+    let list = get_list(repo, slug).await?;
+    if !can_read(identity, &list) {
+        return Err(Error::NotFound("list"));
+    }
+    Ok(list)
+}
+
+async fn get_managed_list(repo: &dyn WishlistRepository, identity: &Identity, slug: &str) -> Result<List> {
+    let list = get_list(repo, slug).await?;
+    if !identity.can_manage(&list) {
+        return Err(Error::Forbidden(format!("only the owner and editors can change the list '{}'", slug)));
+    }
+    Ok(list)
+}
+
+/// Returns the list with `slug` if `identity` may change the list itself,
+/// which editors may not.
+async fn get_administered_list(repo: &dyn WishlistRepository, identity: &Identity, slug: &str) -> Result<List> {
+    let list = get_list(repo, slug).await?;
+    if !identity.can_administer(&list) {
+        return Err(Error::Forbidden(format!("only the owner can change the list '{}'", slug)));
+    }
+    Ok(list)
+}
+
+async fn get_collaborator(repo: &dyn WishlistRepository, slug: &str, user: &ObjectId) -> Result<Collaborator> {
+    get_list(repo, slug).await?
+        .get_collaborators()
+        .iter()
+        .find(|collaborator| collaborator.get_user() == user)
+        .cloned()
+        .ok_or(Error::NotFound("collaborator"))
+}
+
+/// Returns the product with `id` along with its list if `identity` may read it.
+async fn get_readable_product(repo: &dyn WishlistRepository, identity: Option<&Identity>, id: &ObjectId) -> Result<(Product, List)> {
+    let product = get_product_by_id(repo, id).await?;
+    let list = get_list(repo, product.get_list()).await?;
+    if !can_read(identity, &list) {
+        return Err(Error::NotFound("product"));
+    }
+    Ok((product, list))
+}
+
+/// Returns the product with `id` if `identity` may reserve it or pledge toward it.
+async fn get_reservable_product(repo: &dyn WishlistRepository, identity: Option<&Identity>, id: &ObjectId) -> Result<Product> {
+    let product = get_product_by_id(repo, id).await?;
+    let list = get_list(repo, product.get_list()).await?;
+    if !can_read(identity, &list) {
+        return Err(Error::NotFound("product"));
+    }
+    if !can_reserve(identity, &list) {
+        return Err(Error::Forbidden("reserving products of this list needs a share link with the reserve scope".to_string()));
+    }
+    Ok(product)
+}
+
+async fn ensure_can_manage_product(repo: &dyn WishlistRepository, identity: &Identity, id: &ObjectId) -> Result<()> {
+    let product = get_product_by_id(repo, id).await?;
+    get_managed_list(repo, identity, product.get_list()).await?;
+    Ok(())
+}
+
+/// Drops the products of lists `identity` can't read. This happens after
+/// paging, so a page may come back with fewer products than requested.
+pub(crate) async fn retain_readable(repo: &dyn WishlistRepository, identity: Option<&Identity>, products: Loaded<Vec<Product>>) -> Result<Loaded<Vec<Product>>> {
+    let (mut products, skipped) = products.split();
+    let slugs: BTreeSet<String> = products.iter().map(|p| p.get_list().to_string()).collect();
+    let mut readable = BTreeSet::new();
+    for slug in slugs {
+        match get_list(repo, &slug).await {
+            Ok(list) if can_read(identity, &list) => {
+                readable.insert(slug);
+            }
+            Ok(_) | Err(Error::NotFound(_)) => {}
+            Err(e) => return Err(e),
+        }
+    }
+    products.retain(|p| readable.contains(p.get_list()));
+    Ok(Loaded::new(products, skipped))
+}
+
+/// Returns the slugs of the lists `identity` can read.
+pub(crate) async fn get_readable_slugs(repo: &dyn WishlistRepository, identity: Option<&Identity>) -> Result<Vec<String>> {
+    get_list_slugs(repo, identity, can_read).await
+}
+
+/// Returns the slugs of the lists `identity` can find, to filter results
+/// spanning all lists before paging them. Unlisted lists are only found by
+/// whoever knows their slug.
+pub(crate) async fn get_discoverable_slugs(repo: &dyn WishlistRepository, identity: Option<&Identity>) -> Result<Vec<String>> {
+    get_list_slugs(repo, identity, can_discover).await
+}
+
+async fn get_list_slugs(repo: &dyn WishlistRepository, identity: Option<&Identity>, allowed: fn(Option<&Identity>, &List) -> bool) -> Result<Vec<String>> {
+    let lists = get_all_lists(repo).await?.into_value();
+    Ok(lists.iter().filter(|list| allowed(identity, list)).map(|list| list.get_slug().to_string()).collect())
+}
+
+/// Returns the default list followed by the stored ones.
+async fn get_all_lists(repo: &dyn WishlistRepository) -> Result<Loaded<Vec<List>>> {
+    let (stored, skipped) = repo.get_lists().await?.split();
+    let mut lists = vec![List::default_list()];
+    lists.extend(stored);
+    Ok(Loaded::new(lists, skipped))
+}
+
+/// Loads the products of a wishlist, returns the number of skipped documents.
+async fn load_wishlist(repo: &dyn WishlistRepository, wishlist: &mut Wishlist) -> Result<u64> {
+    let (mut products, mut skipped) = match wishlist.get_product_ids() {
+        Some(ids) => repo.get_products_by_id(ids).await?.split(),
+        None => {
+            return Err(Error::FieldNotLoaded("wishlist", "product_ids"));
+        }
+    };
+    skipped += load_product_details(repo, &mut products).await?;
+    wishlist.set_products(products);
+    Ok(skipped)
+}
+
+pub(crate) async fn with_details(repo: &dyn WishlistRepository, products: Loaded<Vec<Product>>) -> Result<Loaded<Vec<Product>>> {
+    let (mut products, skipped) = products.split();
+    let skipped_details = load_product_details(repo, &mut products).await?;
+    Ok(Loaded::new(products, skipped + skipped_details))
+}
+
+/// Sets the source, reservation state and funded share of every product,
+/// returns the number of skipped documents.
+async fn load_product_details(repo: &dyn WishlistRepository, products: &mut [Product]) -> Result<u64> {
+    let mut skipped = load_source_for_products(repo, products).await?;
+
+    let ids: Vec<ObjectId> = products.iter().filter_map(|p| p.get_id().cloned()).collect();
+    if !ids.is_empty() {
+        let reserved: BTreeSet<ObjectId> = repo.get_reserved_product_ids(&ids).await?.into_iter().collect();
+        let (pools, skipped_pools) = repo.get_pledge_pools(&ids).await?.split();
+        let funded: BTreeMap<&ObjectId, u32> = pools.iter().map(|pool| (pool.get_product_id(), pool.get_funded_percent())).collect();
+        for product in products.iter_mut() {
+            let is_reserved = product.get_id().map(|id| reserved.contains(id)).unwrap_or(false);
+            let funded_percent = product.get_id().and_then(|id| funded.get(id)).copied();
+            product.set_reserved(is_reserved);
+            product.set_funded_percent(funded_percent);
+        }
+        skipped += skipped_pools;
+    }
+    Ok(skipped)
+}
+
+/// Sets the source of every product and of its offers, returns the number of
+/// skipped source documents. Offers of sources that don't exist anymore are
+/// left without one.
+async fn load_source_for_products(repo: &dyn WishlistRepository, products: &mut [Product]) -> Result<u64> {
+    let mut source_ids = BTreeSet::new();
+    for product in products.iter() {
+        let source_id = product
+            .get_source_id()
+            .cloned()
+            .ok_or(Error::FieldNotLoaded("product", "source_id"))?;
+        source_ids.insert(source_id);
+        source_ids.extend(product.get_offers().iter().filter_map(|offer| offer.get_source_id()).cloned());
+    }
+    if source_ids.is_empty() {
+        return Ok(0);
+    }
+
+    let ids: Vec<ObjectId> = source_ids.into_iter().collect();
+    let (sources, skipped) = repo.get_sources_by_id(&ids).await?.split();
+    let sources: BTreeMap<ObjectId, Source> = sources
+        .into_iter()
+        .filter_map(|s| s.get_id().cloned().map(|id| (id, s)))
+        .collect();
+
+    for product in products.iter_mut() {
+        let source = product
+            .get_source_id()
+            .and_then(|id| sources.get(id))
+            .cloned()
+            .ok_or(Error::FieldNotLoaded("product", "source"))?;
+        product.set_source(source);
+        for offer in product.get_offers_mut() {
+            if let Some(source) = offer.get_source_id().and_then(|id| sources.get(id)) {
+                offer.set_source(source.clone());
+            }
+        }
+    }
+    Ok(skipped)
+}
+
+async fn get_product_by_id(repo: &dyn WishlistRepository, id: &ObjectId) -> Result<Product> {
+    let mut product = repo.get_product_by_id(id).await?
+        .ok_or(Error::NotFound("product"))?;
+    load_product_details(repo, std::slice::from_mut(&mut product)).await?;
+    Ok(product)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::ShareScope;
+    use crate::repository::MemoryRepository;
+
+    fn add_list(repo: &MemoryRepository, slug: &str, visibility: &str, owner: &ObjectId) {
+        repo.add_list(doc! {"slug": slug, "name": slug, "visibility": visibility, "owner": owner.clone()});
+    }
+
+    fn share(list: &str) -> Identity {
+        Identity::Share { list: list.to_string(), scope: ShareScope::Read }
+    }
+
+    fn category_query(name: &str) -> CategoryQuery {
+        serde_json::from_value(serde_json::json!({"category": name})).unwrap()
+    }
+
+    #[tokio::test]
+    async fn private_list_is_only_readable_with_access() {
+        let repo = MemoryRepository::new();
+        let owner = ObjectId::new();
+        let other = Identity::User { id: ObjectId::new() };
+        add_list(&repo, "private", "private", &owner);
+        add_list(&repo, "unlisted", "unlisted", &owner);
+
+        assert!(matches!(get_readable_list(&repo, "private", None).await, Err(Error::NotFound("list"))));
+        assert!(matches!(get_readable_list(&repo, "private", Some(&other)).await, Err(Error::NotFound("list"))));
+        assert!(matches!(get_readable_list(&repo, "private", Some(&share("unlisted"))).await, Err(Error::NotFound("list"))));
+        assert!(get_readable_list(&repo, "private", Some(&Identity::User { id: owner })).await.is_ok());
+        assert!(get_readable_list(&repo, "private", Some(&share("private"))).await.is_ok());
+        assert!(get_readable_list(&repo, "unlisted", None).await.is_ok());
+        assert!(matches!(get_readable_list(&repo, "missing", None).await, Err(Error::NotFound("list"))));
+    }
+
+    #[tokio::test]
+    async fn list_is_only_managed_by_owner_and_admin() {
+        let repo = MemoryRepository::new();
+        let owner = ObjectId::new();
+        add_list(&repo, "games", "public", &owner);
+
+        assert!(get_managed_list(&repo, &Identity::User { id: owner }, "games").await.is_ok());
+        assert!(get_managed_list(&repo, &Identity::Admin, "games").await.is_ok());
+        assert!(matches!(get_managed_list(&repo, &Identity::User { id: ObjectId::new() }, "games").await, Err(Error::Forbidden(_))));
+        assert!(matches!(get_managed_list(&repo, &share("games"), "games").await, Err(Error::Forbidden(_))));
+    }
+
+    #[tokio::test]
+    async fn discoverable_slugs_leave_out_unlisted_and_foreign_private_lists() {
+        let repo = MemoryRepository::new();
+        let owner = ObjectId::new();
+        add_list(&repo, "public", "public", &owner);
+        add_list(&repo, "unlisted", "unlisted", &owner);
+        add_list(&repo, "private", "private", &owner);
+
+        assert_eq!(get_discoverable_slugs(&repo, None).await.unwrap(), vec![DEFAULT_LIST, "public"]);
+        assert_eq!(get_discoverable_slugs(&repo, Some(&Identity::User { id: ObjectId::new() })).await.unwrap(), vec![DEFAULT_LIST, "public"]);
+        assert_eq!(get_discoverable_slugs(&repo, Some(&share("private"))).await.unwrap(), vec![DEFAULT_LIST, "public", "private"]);
+        assert_eq!(get_discoverable_slugs(&repo, Some(&Identity::User { id: owner })).await.unwrap(), vec![DEFAULT_LIST, "public", "unlisted", "private"]);
+    }
+
+    #[tokio::test]
+    async fn snapshot_archives_products_left_out() {
+        let repo = MemoryRepository::new();
+        let notifier = Notifier::new(vec![]);
+        let owner = ObjectId::new();
+        add_list(&repo, "games", "private", &owner);
+        let kept = repo.add_product(doc! {"name": "kept", "list": "games"});
+        let removed = repo.add_product(doc! {"name": "removed", "list": "games"});
+        let filter: ArchiveFilterQuery = serde_json::from_value(serde_json::json!({})).unwrap();
+        let repo: Arc<dyn WishlistRepository> = Arc::new(repo);
+
+        record_wishlist_snapshot(repo.as_ref(), &notifier, "games", &[kept.clone(), removed], 100).await.unwrap();
+        let count = handle_get_archive_product_count("games".to_string(), Some(Identity::User { id: owner.clone() }), filter, repo.clone()).await.unwrap();
+        assert_eq!(count, 0);
+
+        record_wishlist_snapshot(repo.as_ref(), &notifier, "games", &[kept], 200).await.unwrap();
+        let filter: ArchiveFilterQuery = serde_json::from_value(serde_json::json!({})).unwrap();
+        let count = handle_get_archive_product_count("games".to_string(), Some(Identity::User { id: owner }), filter, repo.clone()).await.unwrap();
+        assert_eq!(count, 1);
+
+        let filter: ArchiveFilterQuery = serde_json::from_value(serde_json::json!({})).unwrap();
+        let result = handle_get_archive_product_count("games".to_string(), None, filter, repo).await;
+        assert!(matches!(result, Err(Error::NotFound("list"))));
+    }
+
+    #[tokio::test]
+    async fn category_counts_leave_out_private_lists() {
+        let repo = MemoryRepository::new();
+        let owner = ObjectId::new();
+        add_list(&repo, "public", "public", &owner);
+        add_list(&repo, "private", "private", &owner);
+        let category = repo.add_category(doc! {"name": "games"});
+        repo.add_product(doc! {"name": "shown", "list": "public", "category": category.clone()});
+        repo.add_product(doc! {"name": "hidden", "list": "private", "category": category.clone()});
+        let repo: Arc<dyn WishlistRepository> = Arc::new(repo);
+
+        let count = handle_get_product_count_by_category_name(None, category_query("games"), repo.clone()).await.unwrap();
+        assert_eq!(count, 1);
+        let count = handle_get_product_count_by_category_name(Some(Identity::User { id: owner.clone() }), category_query("games"), repo.clone()).await.unwrap();
+        assert_eq!(count, 2);
+
+        let current_count = |identity: Option<Identity>, repo: Arc<dyn WishlistRepository>| async move {
+            let query: CategoryListQuery = serde_json::from_value(serde_json::json!({"with_counts": true})).unwrap();
+            let listings = handle_get_categories(identity, query, repo).await.unwrap().into_value();
+            serde_json::to_value(&listings).unwrap()[0]["current_count"].clone()
+        };
+        assert_eq!(current_count(None, repo.clone()).await, 1);
+        assert_eq!(current_count(Some(Identity::User { id: owner }), repo).await, 2);
+    }
 }
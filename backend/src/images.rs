@@ -0,0 +1,261 @@
+use image::io::{Limits, Reader};
+use image::{DynamicImage, ImageFormat, ImageOutputFormat};
+use std::io::{Cursor, ErrorKind};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::config::ImagesConfig;
+use crate::net::{check_public_host, is_forbidden_host};
+use crate::{Error, Result};
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(15);
+const MAX_REDIRECTS: usize = 3;
+const MAX_DIMENSION: u32 = 10_000;
+const JPEG_QUALITY: u8 = 85;
+const SOURCE_FILE: &str = "source";
+
+/// Size an image is served in, thumbnails fit a square of the dimension.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ImageSize {
+    Small,
+    Medium,
+    Large,
+    Original,
+}
+
+pub struct Image {
+    content_type: &'static str,
+    data: Vec<u8>,
+}
+
+/// Downloads product images into `dir` and serves them with thumbnails.
+///
+/// Each product gets a directory named after its id, holding the url the
+/// image was downloaded from, the original and the thumbnails created so far.
+/// The image is downloaded again once the product's image url changes.
+#[derive(Clone)]
+pub struct ImageStore {
+    dir: PathBuf,
+    max_size: u64,
+    client: reqwest::Client,
+}
+
+impl ImageSize {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "small" => Some(ImageSize::Small),
+            "medium" => Some(ImageSize::Medium),
+            "large" => Some(ImageSize::Large),
+            "original" => Some(ImageSize::Original),
+            _ => None,
+        }
+    }
+
+    pub fn get_name(self) -> &'static str {
+        match self {
+            ImageSize::Small => "small",
+            ImageSize::Medium => "medium",
+            ImageSize::Large => "large",
+            ImageSize::Original => "original",
+        }
+    }
+
+    pub fn get_dimension(self) -> Option<u32> {
+        match self {
+            ImageSize::Small => Some(100),
+            ImageSize::Medium => Some(300),
+            ImageSize::Large => Some(800),
+            ImageSize::Original => None,
+        }
+    }
+}
+
+impl Image {
+    pub fn get_content_type(&self) -> &'static str {
+        self.content_type
+    }
+
+    pub fn into_data(self) -> Vec<u8> {
+        self.data
+    }
+}
+
+impl ImageStore {
+    pub fn new(config: &ImagesConfig) -> Self {
+        let policy = reqwest::redirect::Policy::custom(|attempt| {
+            if attempt.previous().len() >= MAX_REDIRECTS {
+                attempt.error("too many redirects")
+            } else if is_forbidden_host(attempt.url()) {
+                attempt.stop()
+            } else {
+                attempt.follow()
+            }
+        });
+        let client = reqwest::Client::builder()
+            .redirect(policy)
+            .timeout(REQUEST_TIMEOUT)
+            .build()
+            .unwrap_or_default();
+        Self {
+            dir: PathBuf::from(&config.dir),
+            max_size: config.max_size,
+            client,
+        }
+    }
+
+    /// Returns the image of product `key` from `url` in `size`, downloading
+    /// the image and creating the thumbnail if they aren't stored yet.
+    pub async fn get(&self, key: &str, url: &str, size: ImageSize) -> Result<Image> {
+        let dir = self.dir.join(key);
+        let file = dir.join(size.get_name());
+        let current = read_optional(dir.join(SOURCE_FILE)).await?.as_deref() == Some(url.as_bytes());
+        if current {
+            if let Some(data) = read_optional(file.clone()).await? {
+                return Ok(Image::new(data));
+            }
+        }
+
+        let original = match current {
+            true => read_optional(dir.join(ImageSize::Original.get_name())).await?,
+            false => None,
+        };
+        let original = match original {
+            Some(data) => data,
+            None => {
+                let data = self.download(url).await?;
+                decode(data.clone()).await?;
+                clear_dir(dir.clone()).await?;
+                write_atomic(dir.join(ImageSize::Original.get_name()), data.clone()).await?;
+                write_atomic(dir.join(SOURCE_FILE), url.as_bytes().to_vec()).await?;
+                data
+            }
+        };
+
+        let data = match size.get_dimension() {
+            Some(dimension) => {
+                let thumbnail = create_thumbnail(original, dimension).await?;
+                write_atomic(file, thumbnail.clone()).await?;
+                thumbnail
+            }
+            None => original,
+        };
+        Ok(Image::new(data))
+    }
+
+    async fn download(&self, url: &str) -> Result<Vec<u8>> {
+        let parsed = reqwest::Url::parse(url).map_err(|_| Error::Validation("image url is invalid".to_string()))?;
+        check_public_host(&parsed, "image").await?;
+        let unavailable = |e: reqwest::Error| Error::Unavailable(format!("could not download image: {}", e));
+        let mut response = self.client.get(parsed).send().await.map_err(unavailable)?;
+        if !response.status().is_success() {
+            return Err(Error::Unavailable(format!(
+                "could not download image: status {}",
+                response.status()
+            )));
+        }
+        if response.content_length().map(|l| l > self.max_size).unwrap_or(false) {
+            return Err(Error::Unavailable("image is too large".to_string()));
+        }
+        let mut data = Vec::new();
+        while let Some(chunk) = response.chunk().await.map_err(unavailable)? {
+            if data.len() as u64 + chunk.len() as u64 > self.max_size {
+                return Err(Error::Unavailable("image is too large".to_string()));
+            }
+            data.extend_from_slice(&chunk);
+        }
+        Ok(data)
+    }
+}
+
+impl Image {
+    fn new(data: Vec<u8>) -> Self {
+        let content_type = match image::guess_format(&data) {
+            Ok(ImageFormat::Png) => "image/png",
+            Ok(ImageFormat::Jpeg) => "image/jpeg",
+            Ok(ImageFormat::Gif) => "image/gif",
+            Ok(ImageFormat::WebP) => "image/webp",
+            _ => "application/octet-stream",
+        };
+        Self { content_type, data }
+    }
+}
+
+fn decode_blocking(data: &[u8]) -> Result<DynamicImage> {
+    let invalid = |e: &dyn std::fmt::Display| Error::Unavailable(format!("could not read image: {}", e));
+    let mut reader = Reader::new(Cursor::new(data)).with_guessed_format().map_err(|e| invalid(&e))?;
+    let mut limits = Limits::default();
+    limits.max_image_width = Some(MAX_DIMENSION);
+    limits.max_image_height = Some(MAX_DIMENSION);
+    reader.limits(limits);
+    reader.decode().map_err(|e| invalid(&e))
+}
+
+async fn decode(data: Vec<u8>) -> Result<()> {
+    spawn_blocking(move || decode_blocking(&data).map(|_| ())).await
+}
+
+/// Scales the image down to fit `dimension`, encoded as PNG if it has
+/// transparency and JPEG otherwise. Smaller images are kept as they are.
+async fn create_thumbnail(data: Vec<u8>, dimension: u32) -> Result<Vec<u8>> {
+    spawn_blocking(move || {
+        let image = decode_blocking(&data)?;
+        if image.width() <= dimension && image.height() <= dimension {
+            return Ok(data);
+        }
+        let thumbnail = image.thumbnail(dimension, dimension);
+        let mut encoded = Cursor::new(Vec::new());
+        let result = if thumbnail.color().has_alpha() {
+            thumbnail.write_to(&mut encoded, ImageOutputFormat::Png)
+        } else {
+            DynamicImage::ImageRgb8(thumbnail.to_rgb8()).write_to(&mut encoded, ImageOutputFormat::Jpeg(JPEG_QUALITY))
+        };
+        result.map_err(|e| Error::Internal(format!("could not encode thumbnail: {}", e)))?;
+        Ok(encoded.into_inner())
+    })
+    .await
+}
+
+async fn spawn_blocking<T, F>(f: F) -> Result<T>
+where
+    F: FnOnce() -> Result<T> + Send + 'static,
+    T: Send + 'static,
+{
+    tokio::task::spawn_blocking(f)
+        .await
+        .map_err(|e| Error::Internal(format!("image task failed: {}", e)))?
+}
+
+async fn read_optional(path: PathBuf) -> Result<Option<Vec<u8>>> {
+    spawn_blocking(move || match std::fs::read(&path) {
+        Ok(data) => Ok(Some(data)),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(io_error(&path, e)),
+    })
+    .await
+}
+
+/// Writes to a temporary file first, so a concurrent request never reads a partial image.
+async fn write_atomic(path: PathBuf, data: Vec<u8>) -> Result<()> {
+    spawn_blocking(move || {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| io_error(parent, e))?;
+        }
+        let tmp = path.with_extension(format!("tmp{}", std::process::id()));
+        std::fs::write(&tmp, &data).map_err(|e| io_error(&tmp, e))?;
+        std::fs::rename(&tmp, &path).map_err(|e| io_error(&path, e))
+    })
+    .await
+}
+
+/// Removes the images downloaded from a previous url.
+async fn clear_dir(dir: PathBuf) -> Result<()> {
+    spawn_blocking(move || match std::fs::remove_dir_all(&dir) {
+        Err(e) if e.kind() != ErrorKind::NotFound => Err(io_error(&dir, e)),
+        _ => Ok(()),
+    })
+    .await
+}
+
+fn io_error(path: &Path, e: std::io::Error) -> Error {
+    Error::Internal(format!("image storage '{}': {}", path.display(), e))
+}
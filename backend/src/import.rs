@@ -0,0 +1,72 @@
+use crate::model::ImportRow;
+use crate::{Error, Result};
+
+/// Most rows one import may contain.
+pub const MAX_IMPORT_ROWS: usize = 1000;
+
+/// Reads the rows of an import body, a CSV file with a header line if
+/// `content_type` says so and a JSON array otherwise. Rows that can't be read
+/// are returned as errors, so they show up in the report.
+pub fn read_import_rows(content_type: Option<&str>, body: &[u8]) -> Result<Vec<Result<ImportRow>>> {
+    let is_csv = content_type.map(|t| t.starts_with("text/csv")).unwrap_or(false);
+    let rows = if is_csv {
+        let text = std::str::from_utf8(body).map_err(|_| Error::Validation("CSV is not valid UTF-8".to_string()))?;
+        let mut lines = parse_csv(text)?.into_iter();
+        let header = lines.next().ok_or_else(|| Error::Validation("CSV has no header line".to_string()))?;
+        lines.map(|fields| ImportRow::from_fields(&header, &fields)).collect::<Vec<_>>()
+    } else {
+        let values: Vec<serde_json::Value> = serde_json::from_slice(body)
+            .map_err(|e| Error::Validation(format!("body is not a JSON array: {}", e)))?;
+        values.into_iter()
+            .map(|value| serde_json::from_value(value).map_err(|e| Error::Validation(format!("invalid product: {}", e))))
+            .collect()
+    };
+    if rows.len() > MAX_IMPORT_ROWS {
+        return Err(Error::Validation(format!("at most {} products can be imported at once", MAX_IMPORT_ROWS)));
+    }
+    Ok(rows)
+}
+
+/// Parses RFC 4180 CSV, skipping empty lines. Quoted fields may contain
+/// separators, doubled quotes and line breaks. Semicolons separate the
+/// fields instead of commas if the first line has no comma, as some
+/// spreadsheets export them.
+fn parse_csv(text: &str) -> Result<Vec<Vec<String>>> {
+    let text = text.strip_prefix('\u{feff}').unwrap_or(text);
+    let first_line = text.lines().next().unwrap_or_default();
+    let separator = if !first_line.contains(',') && first_line.contains(';') { ';' } else { ',' };
+
+    let mut lines = Vec::new();
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            '"' if quoted => quoted = false,
+            '"' if field.is_empty() => quoted = true,
+            c if quoted => field.push(c),
+            c if c == separator => fields.push(std::mem::take(&mut field)),
+            '\r' if chars.peek() == Some(&'\n') => {}
+            '\n' | '\r' => push_line(&mut lines, &mut fields, &mut field),
+            c => field.push(c),
+        }
+    }
+    if quoted {
+        return Err(Error::Validation("CSV has an unterminated quoted field".to_string()));
+    }
+    push_line(&mut lines, &mut fields, &mut field);
+    Ok(lines)
+}
+
+fn push_line(lines: &mut Vec<Vec<String>>, fields: &mut Vec<String>, field: &mut String) {
+    fields.push(std::mem::take(field));
+    let line = std::mem::take(fields);
+    if line.iter().any(|f| !f.is_empty()) {
+        lines.push(line);
+    }
+}
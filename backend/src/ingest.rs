@@ -0,0 +1,192 @@
+use std::convert::TryFrom;
+
+use crate::model::{Currency, Money};
+
+/// Bytes of a page read for its metadata, the meta tags are in its head.
+pub(crate) const MAX_PAGE_SIZE: usize = 512 * 1024;
+
+/// Product details a shop page announces in its Open Graph and product meta
+/// tags, with the `<title>` of the page if it has no `og:title`.
+#[derive(Default, Debug)]
+pub(crate) struct PageMetadata {
+    title: Option<String>,
+    image: Option<String>,
+    price: Option<String>,
+    currency: Option<Currency>,
+}
+
+impl PageMetadata {
+    /// Reads the meta tags of `html`, the first of each property counts.
+    pub fn parse(html: &str) -> Self {
+        let lower = html.to_ascii_lowercase();
+        let mut metadata = Self::default();
+        let mut position = 0;
+        while let Some(start) = lower[position..].find("<meta").map(|i| i + position) {
+            let end = match lower[start..].find('>') {
+                Some(end) => start + end,
+                None => break,
+            };
+            position = end;
+            let attributes = parse_attributes(&html[start + "<meta".len()..end]);
+            let property = attributes.iter()
+                .find(|(name, _)| name == "property" || name == "name")
+                .map(|(_, value)| value.to_ascii_lowercase());
+            let content = attributes.iter()
+                .find(|(name, _)| name == "content")
+                .map(|(_, value)| decode_entities(value.trim()))
+                .filter(|content| !content.is_empty());
+            let (property, content) = match (property, content) {
+                (Some(property), Some(content)) => (property, content),
+                _ => continue,
+            };
+            let field = match property.as_str() {
+                "og:title" => &mut metadata.title,
+                "og:image" | "og:image:secure_url" => &mut metadata.image,
+                "product:price:amount" | "og:price:amount" => &mut metadata.price,
+                "product:price:currency" | "og:price:currency" => {
+                    if metadata.currency.is_none() {
+                        metadata.currency = Currency::from_code(&content.to_ascii_uppercase());
+                    }
+                    continue;
+                }
+                _ => continue,
+            };
+            if field.is_none() {
+                *field = Some(content);
+            }
+        }
+        if metadata.title.is_none() {
+            metadata.title = read_title(html, &lower);
+        }
+        metadata
+    }
+
+    pub fn get_title(&self) -> Option<&str> {
+        self.title.as_deref()
+    }
+    pub fn get_image(&self) -> Option<&str> {
+        self.image.as_deref()
+    }
+    pub fn get_price(&self) -> Option<&str> {
+        self.price.as_deref()
+    }
+    pub fn get_currency(&self) -> Option<Currency> {
+        self.currency
+    }
+}
+
+/// Parses a price as shops show it, like `€ 1.299,00`, `1,299.00` or `12`,
+/// into the minor unit of `currency`. The last `.` or `,` separates the
+/// fraction if at most as many digits as the minor unit has follow it,
+/// the others group thousands.
+pub(crate) fn parse_shown_price(text: &str, currency: Currency) -> Option<i32> {
+    let number: String = text.chars().filter(|c| c.is_ascii_digit() || *c == '.' || *c == ',').collect();
+    let number = number.trim_matches(|c| c == '.' || c == ',');
+    let (whole, fraction) = match number.rfind(['.', ',']) {
+        Some(i) if number.len() - i - 1 <= currency.get_minor_digits() as usize => (&number[..i], &number[i + 1..]),
+        _ => (number, ""),
+    };
+    let whole: String = whole.chars().filter(char::is_ascii_digit).collect();
+    let amount = if fraction.is_empty() { whole } else { format!("{}.{}", whole, fraction) };
+    Money::parse_amount(&amount, currency)
+        .and_then(|money| i32::try_from(money.get_amount_minor()).ok())
+}
+
+/// Splits the attributes of a tag into lowercase names and their values,
+/// quoted or not.
+fn parse_attributes(tag: &str) -> Vec<(String, String)> {
+    let mut attributes = Vec::new();
+    let mut rest = tag.trim_start();
+    while !rest.is_empty() {
+        let name_end = rest.find(|c: char| c == '=' || c.is_whitespace() || c == '/').unwrap_or(rest.len());
+        let name = rest[..name_end].to_ascii_lowercase();
+        rest = rest[name_end..].trim_start();
+        let value = match rest.strip_prefix('=') {
+            Some(value) => {
+                let value = value.trim_start();
+                let (content, tail) = match value.chars().next() {
+                    Some(quote) if quote == '"' || quote == '\'' => {
+                        let end = value[1..].find(quote).map(|i| i + 1).unwrap_or(value.len());
+                        (&value[1..end], value.get(end + 1..).unwrap_or_default())
+                    }
+                    _ => {
+                        let end = value.find(char::is_whitespace).unwrap_or(value.len());
+                        (&value[..end], &value[end..])
+                    }
+                };
+                rest = tail;
+                content.to_string()
+            }
+            None => {
+                if name.is_empty() {
+                    // Skips a `/` or another character no attribute starts with.
+                    rest = rest.get(1..).unwrap_or_default();
+                }
+                String::new()
+            }
+        };
+        if !name.is_empty() {
+            attributes.push((name, value));
+        }
+        rest = rest.trim_start();
+    }
+    attributes
+}
+
+fn read_title(html: &str, lower: &str) -> Option<String> {
+    let start = lower.find("<title")?;
+    let start = start + lower[start..].find('>')? + 1;
+    let end = start + lower[start..].find("</title")?;
+    let title = decode_entities(html[start..end].trim());
+    Some(title).filter(|t| !t.is_empty())
+}
+
+/// Decodes the entities meta tags commonly use, others are kept as they are.
+fn decode_entities(text: &str) -> String {
+    if !text.contains('&') {
+        return text.to_string();
+    }
+    let mut decoded = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        decoded.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let end = match rest.find(';').filter(|end| *end <= 10) {
+            Some(end) => end,
+            None => {
+                decoded.push('&');
+                rest = &rest[1..];
+                continue;
+            }
+        };
+        let entity = &rest[1..end];
+        let character = match entity {
+            "amp" => Some('&'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "nbsp" => Some(' '),
+            _ => match entity.strip_prefix('#') {
+                Some(code) => match code.strip_prefix(|c| c == 'x' || c == 'X') {
+                    Some(hex) => u32::from_str_radix(hex, 16).ok(),
+                    None => code.parse().ok(),
+                }
+                .and_then(std::char::from_u32),
+                None => None,
+            },
+        };
+        match character {
+            Some(character) => {
+                decoded.push(character);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    decoded.push_str(rest);
+    decoded
+}
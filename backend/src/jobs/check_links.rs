@@ -0,0 +1,111 @@
+use async_trait::async_trait;
+use mongodb::bson::{doc, oid::ObjectId};
+use reqwest::{Method, StatusCode, Url};
+use std::collections::BTreeMap;
+
+use super::Job;
+use crate::audit::{record_event, SCRAPER};
+use crate::model::{Politeness, Product, Source, Stored};
+use crate::net::check_public_host;
+use crate::politeness::PoliteClient;
+use crate::query::SortQuery;
+use crate::repository::{ProductFilter, WishlistRepository};
+use crate::Result;
+
+const PAGE_SIZE: u64 = 100;
+
+/// Requests the URL of every product on a list and flags those answered with
+/// `404 Not Found` or `410 Gone` as `link_dead`, so their owners can replace
+/// them before someone tries to buy them. A link answered otherwise later on
+/// is unflagged again, links that can't be reached are left as they are.
+/// The shops are requested as politely as their source asks for.
+pub struct CheckLinksJob {
+    client: PoliteClient,
+}
+
+/// What requesting the URL of a product showed.
+enum LinkStatus {
+    Alive,
+    Dead,
+    Unknown,
+}
+
+impl CheckLinksJob {
+    pub fn new(client: PoliteClient) -> Self {
+        Self { client }
+    }
+
+    /// Sends a `HEAD` request, falling back to `GET` for shops that don't answer those.
+    async fn check(&self, source: &str, politeness: &Politeness, url: &str) -> LinkStatus {
+        let url = match Url::parse(url) {
+            Ok(url) => url,
+            Err(_) => return LinkStatus::Unknown,
+        };
+        if let Err(e) = check_public_host(&url, "product").await {
+            debug!("Not checking {}: {}", url, e);
+            return LinkStatus::Unknown;
+        }
+        let mut status = self.client.request(source, politeness, Method::HEAD, &url).await;
+        if matches!(status, Some(StatusCode::METHOD_NOT_ALLOWED) | Some(StatusCode::NOT_IMPLEMENTED)) {
+            status = self.client.request(source, politeness, Method::GET, &url).await;
+        }
+        match status {
+            Some(StatusCode::NOT_FOUND) | Some(StatusCode::GONE) => LinkStatus::Dead,
+            Some(status) if status.is_success() || status.is_redirection() => LinkStatus::Alive,
+            _ => LinkStatus::Unknown,
+        }
+    }
+}
+
+#[async_trait]
+impl Job for CheckLinksJob {
+    fn get_name(&self) -> &'static str {
+        "check_links"
+    }
+
+    async fn run(&self, repo: &dyn WishlistRepository) -> Result<String> {
+        let (filter, sort) = (ProductFilter::default().with_archived(false), SortQuery::default());
+        let (mut checked, mut dead, mut changed) = (0, 0, 0);
+        let mut offset = 0;
+        loop {
+            let products = repo.get_products(&filter, &sort, offset, PAGE_SIZE).await?.into_value();
+            let sources = load_sources(repo, &products).await?;
+            let default_politeness = Politeness::default();
+            for product in products.iter() {
+                let (id, url, source) = match (product.get_id(), product.get_url(), product.get_source_id()) {
+                    (Some(id), Some(url), Some(source)) => (id, url, source),
+                    _ => continue,
+                };
+                let politeness = sources.get(source).map(Source::get_politeness).unwrap_or(&default_politeness);
+                let link_dead = match self.check(&source.to_hex(), politeness, url).await {
+                    LinkStatus::Alive => false,
+                    LinkStatus::Dead => true,
+                    LinkStatus::Unknown => continue,
+                };
+                checked += 1;
+                if link_dead {
+                    dead += 1;
+                }
+                if link_dead != product.is_link_dead() && repo.update_product(id, doc! {"link_dead": link_dead}).await? {
+                    changed += 1;
+                }
+            }
+            if (products.len() as u64) < PAGE_SIZE {
+                break;
+            }
+            offset += PAGE_SIZE;
+        }
+        if changed > 0 {
+            record_event(repo, SCRAPER, "check_links", Product::COLLECTION, None, doc! {"changed": changed as i64, "dead": dead as i64}).await;
+        }
+        Ok(format!("checked {} links, {} dead, {} changed", checked, dead, changed))
+    }
+}
+
+async fn load_sources(repo: &dyn WishlistRepository, products: &[Product]) -> Result<BTreeMap<ObjectId, Source>> {
+    let mut ids: Vec<ObjectId> = products.iter().filter_map(|p| p.get_source_id()).cloned().collect();
+    ids.sort();
+    ids.dedup();
+    let sources = repo.get_sources_by_id(&ids).await?.into_value();
+    Ok(sources.into_iter().filter_map(|s| s.get_id().cloned().map(|id| (id, s))).collect())
+}
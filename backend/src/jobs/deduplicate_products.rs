@@ -0,0 +1,22 @@
+use async_trait::async_trait;
+
+use super::Job;
+use crate::dedup::deduplicate_products;
+use crate::repository::WishlistRepository;
+use crate::Result;
+
+/// Groups the products offered by several sources, marking all but the
+/// cheapest offer as duplicates.
+pub struct DeduplicateProductsJob;
+
+#[async_trait]
+impl Job for DeduplicateProductsJob {
+    fn get_name(&self) -> &'static str {
+        "deduplicate_products"
+    }
+
+    async fn run(&self, repo: &dyn WishlistRepository) -> Result<String> {
+        let changed = deduplicate_products(repo).await?;
+        Ok(format!("updated the duplicates of {} products", changed))
+    }
+}
@@ -0,0 +1,51 @@
+use async_trait::async_trait;
+
+use super::Job;
+use crate::enrichment::{enrich_product, Enricher};
+use crate::query::SortQuery;
+use crate::repository::{ProductFilter, WishlistRepository};
+use crate::{Error, Result};
+
+const PAGE_SIZE: u64 = 100;
+
+/// Looks up the details of the products with an ISBN or EAN that wasn't
+/// looked up yet, taking over their cover and description if they have none.
+/// Stops at the first provider that can't be reached, the products left are
+/// looked up by the next run.
+pub struct EnrichProductsJob {
+    enricher: Enricher,
+}
+
+impl EnrichProductsJob {
+    pub fn new(enricher: Enricher) -> Self {
+        Self { enricher }
+    }
+}
+
+#[async_trait]
+impl Job for EnrichProductsJob {
+    fn get_name(&self) -> &'static str {
+        "enrich_products"
+    }
+
+    async fn run(&self, repo: &dyn WishlistRepository) -> Result<String> {
+        let (filter, sort) = (ProductFilter::default(), SortQuery::default());
+        let mut enriched = 0;
+        let mut offset = 0;
+        loop {
+            let products = repo.get_products(&filter, &sort, offset, PAGE_SIZE).await?.into_value();
+            for product in products.iter() {
+                match enrich_product(repo, &self.enricher, product).await {
+                    Ok(true) => enriched += 1,
+                    Ok(false) => {}
+                    Err(e) => return Err(Error::Job(format!("enriched {} products, then: {}", enriched, e))),
+                }
+            }
+            if (products.len() as u64) < PAGE_SIZE {
+                break;
+            }
+            offset += PAGE_SIZE;
+        }
+        Ok(format!("enriched {} products", enriched))
+    }
+}
@@ -0,0 +1,29 @@
+use async_trait::async_trait;
+
+use super::Job;
+use crate::repository::WishlistRepository;
+use crate::views::ViewCounter;
+use crate::Result;
+
+/// Adds the product views counted in memory to the stored products.
+pub struct FlushViewsJob {
+    views: ViewCounter,
+}
+
+impl FlushViewsJob {
+    pub fn new(views: ViewCounter) -> Self {
+        Self { views }
+    }
+}
+
+#[async_trait]
+impl Job for FlushViewsJob {
+    fn get_name(&self) -> &'static str {
+        "flush_views"
+    }
+
+    async fn run(&self, repo: &dyn WishlistRepository) -> Result<String> {
+        let added = self.views.flush(repo).await?;
+        Ok(format!("added {} product views", added))
+    }
+}
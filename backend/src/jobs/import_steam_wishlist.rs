@@ -0,0 +1,38 @@
+use async_trait::async_trait;
+
+use super::Job;
+use crate::audit::SCRAPER;
+use crate::handler::import_steam_wishlist;
+use crate::notify::Notifier;
+use crate::repository::WishlistRepository;
+use crate::steam::SteamClient;
+use crate::Result;
+
+/// Imports the Steam wishlist of a profile into a list, adding the games new
+/// on it and recording the current prices of the others. Sale prices change
+/// far more often than those of the scraped shops, so this runs on a schedule
+/// of its own.
+pub struct ImportSteamWishlistJob {
+    steam: SteamClient,
+    notifier: Notifier,
+    steam_id: String,
+    list: String,
+}
+
+impl ImportSteamWishlistJob {
+    pub fn new(steam: SteamClient, notifier: Notifier, steam_id: String, list: String) -> Self {
+        Self { steam, notifier, steam_id, list }
+    }
+}
+
+#[async_trait]
+impl Job for ImportSteamWishlistJob {
+    fn get_name(&self) -> &'static str {
+        "import_steam_wishlist"
+    }
+
+    async fn run(&self, repo: &dyn WishlistRepository) -> Result<String> {
+        let report = import_steam_wishlist(repo, &self.notifier, &self.steam, SCRAPER, &self.steam_id, &self.list).await?;
+        Ok(format!("imported {} games into '{}', updated {}, {} failed", report.get_created(), self.list, report.get_duplicates(), report.get_failed()))
+    }
+}
@@ -0,0 +1,144 @@
+use async_trait::async_trait;
+use chrono::Utc;
+use cron::Schedule;
+use std::collections::BTreeMap;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Instant;
+use tokio::sync::{watch, RwLock};
+
+use crate::maintenance::Maintenance;
+use crate::model::JobStatus;
+use crate::repository::WishlistRepository;
+use crate::{Error, Result};
+
+mod check_links;
+mod deduplicate_products;
+mod enrich_products;
+mod flush_views;
+mod import_steam_wishlist;
+mod prune_snapshots;
+mod refresh_exchange_rates;
+mod refresh_prices;
+mod run_scraper;
+mod send_email_digest;
+
+pub use self::check_links::CheckLinksJob;
+pub use self::deduplicate_products::DeduplicateProductsJob;
+pub use self::enrich_products::EnrichProductsJob;
+pub use self::flush_views::FlushViewsJob;
+pub use self::import_steam_wishlist::ImportSteamWishlistJob;
+pub use self::prune_snapshots::PruneSnapshotsJob;
+pub use self::refresh_exchange_rates::RefreshExchangeRatesJob;
+pub use self::refresh_prices::RefreshPricesJob;
+pub use self::run_scraper::{RunScraperJob, Scraper};
+pub use self::send_email_digest::SendEmailDigestJob;
+
+/// Recurring task run by the `Scheduler`.
+#[async_trait]
+pub trait Job: Send + Sync {
+    fn get_name(&self) -> &'static str;
+    /// Runs the job once, returns a short summary of what it did.
+    async fn run(&self, repo: &dyn WishlistRepository) -> Result<String>;
+}
+
+/// Runs jobs in the background on cron schedules and keeps the status of
+/// their last run. Runs due in maintenance mode are skipped.
+///
+/// Schedules use the seconds-first cron syntax, e.g. `0 30 4 * * *` for every
+/// day at 04:30 UTC.
+#[derive(Clone)]
+pub struct Scheduler {
+    repo: Arc<dyn WishlistRepository>,
+    maintenance: Maintenance,
+    statuses: Arc<Mutex<BTreeMap<&'static str, JobStatus>>>,
+    shutdown_sender: Arc<watch::Sender<bool>>,
+    shutdown_receiver: watch::Receiver<bool>,
+    /// Held for reading while a job runs, so shutdown can wait for it.
+    running: Arc<RwLock<()>>,
+}
+
+impl Scheduler {
+    pub fn new(repo: Arc<dyn WishlistRepository>, maintenance: Maintenance) -> Self {
+        let (shutdown_sender, shutdown_receiver) = watch::channel(false);
+        Self {
+            repo,
+            maintenance,
+            statuses: Arc::new(Mutex::new(BTreeMap::new())),
+            shutdown_sender: Arc::new(shutdown_sender),
+            shutdown_receiver,
+            running: Arc::new(RwLock::new(())),
+        }
+    }
+
+    /// Spawns a task running `job` whenever `expression` is due.
+    pub fn schedule<J: Job + 'static>(&self, expression: &str, job: J) -> Result<()> {
+        let schedule = Schedule::from_str(expression)
+            .map_err(|e| Error::Config(format!("invalid schedule '{}' for job '{}': {}", expression, job.get_name(), e)))?;
+        let name = job.get_name();
+        self.lock().insert(name, JobStatus::new(name, expression));
+        info!("Scheduled job '{}' at '{}'", name, expression);
+
+        let scheduler = self.clone();
+        tokio::spawn(async move {
+            let mut shutdown = scheduler.shutdown_receiver.clone();
+            while let Some(next) = schedule.upcoming(Utc).next() {
+                scheduler.update(name, |s| s.set_next_run(next.timestamp() as i32));
+                let wait = (next - Utc::now()).to_std().unwrap_or_default();
+                tokio::select! {
+                    _ = tokio::time::delay_for(wait) => {},
+                    _ = wait_for_shutdown(&mut shutdown) => break,
+                }
+
+                let _running = scheduler.running.read().await;
+                if *shutdown.borrow() {
+                    break;
+                }
+                if scheduler.maintenance.is_read_only() {
+                    info!("Skipping job '{}' in maintenance mode", name);
+                    continue;
+                }
+                let started = Instant::now();
+                let timestamp = Utc::now().timestamp() as i32;
+                let result = job.run(scheduler.repo.as_ref()).await;
+                match &result {
+                    Ok(summary) => info!("Job '{}' finished: {}", name, summary),
+                    Err(e) => error!("Job '{}' failed: {}", name, e),
+                }
+                scheduler.update(name, |s| s.record_run(timestamp, started.elapsed(), &result));
+            }
+            if !*shutdown.borrow() {
+                warn!("Schedule of job '{}' has no upcoming runs", name);
+            }
+        });
+        Ok(())
+    }
+
+    /// Stops starting new job runs and waits until the running ones finished.
+    pub async fn shutdown(&self) {
+        let _ = self.shutdown_sender.broadcast(true);
+        let _ = self.running.write().await;
+    }
+
+    pub fn get_statuses(&self) -> Vec<JobStatus> {
+        self.lock().values().cloned().collect()
+    }
+
+    fn update<F: FnOnce(&mut JobStatus)>(&self, name: &'static str, f: F) {
+        if let Some(status) = self.lock().get_mut(name) {
+            f(status);
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, BTreeMap<&'static str, JobStatus>> {
+        self.statuses.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+async fn wait_for_shutdown(shutdown: &mut watch::Receiver<bool>) {
+    while let Some(stopping) = shutdown.recv().await {
+        if stopping {
+            return;
+        }
+    }
+}
@@ -0,0 +1,36 @@
+use async_trait::async_trait;
+use chrono::Utc;
+
+use super::Job;
+use crate::repository::WishlistRepository;
+use crate::retention::RetentionPolicy;
+use crate::Result;
+
+/// Deletes the wishlist snapshots the retention policy doesn't keep.
+pub struct PruneSnapshotsJob {
+    policy: RetentionPolicy,
+}
+
+impl PruneSnapshotsJob {
+    pub fn new(policy: RetentionPolicy) -> Self {
+        Self { policy }
+    }
+}
+
+#[async_trait]
+impl Job for PruneSnapshotsJob {
+    fn get_name(&self) -> &'static str {
+        "prune_snapshots"
+    }
+
+    async fn run(&self, repo: &dyn WishlistRepository) -> Result<String> {
+        let mut deleted = 0;
+        for preview in self.policy.preview(repo, Utc::now().timestamp()).await? {
+            let timestamps = preview.get_deleted_timestamps();
+            if !timestamps.is_empty() {
+                deleted += repo.delete_wishlists_at(preview.get_list(), &timestamps).await?;
+            }
+        }
+        Ok(format!("deleted {} snapshots", deleted))
+    }
+}
@@ -0,0 +1,51 @@
+use async_trait::async_trait;
+use chrono::Utc;
+use std::time::Duration;
+
+use super::Job;
+use crate::currency::parse_ecb_rates;
+use crate::repository::WishlistRepository;
+use crate::{Error, Result};
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Fetches the euro reference rates of the ECB and stores them for
+/// converting prices. The ECB publishes new rates once per working day.
+pub struct RefreshExchangeRatesJob {
+    client: reqwest::Client,
+    url: String,
+}
+
+impl RefreshExchangeRatesJob {
+    pub fn new(url: String) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            url,
+        }
+    }
+
+    async fn fetch(&self) -> std::result::Result<String, reqwest::Error> {
+        self.client.get(&self.url)
+            .timeout(REQUEST_TIMEOUT)
+            .send()
+            .await?
+            .error_for_status()?
+            .text()
+            .await
+    }
+}
+
+#[async_trait]
+impl Job for RefreshExchangeRatesJob {
+    fn get_name(&self) -> &'static str {
+        "refresh_exchange_rates"
+    }
+
+    async fn run(&self, repo: &dyn WishlistRepository) -> Result<String> {
+        let xml = self.fetch().await
+            .map_err(|e| Error::Job(format!("could not fetch exchange rates from {}: {}", self.url, e)))?;
+        let rates = parse_ecb_rates(&xml)?;
+        repo.set_exchange_rates(rates.to_document(Utc::now().timestamp() as i32)).await?;
+        Ok(format!("stored {} exchange rates of {}", rates.get_rate_count(), rates.get_date()))
+    }
+}
@@ -0,0 +1,60 @@
+use async_trait::async_trait;
+use chrono::Utc;
+
+use super::Job;
+use crate::audit::SCRAPER;
+use crate::handler::record_price;
+use crate::notify::Notifier;
+use crate::query::SortQuery;
+use crate::repository::{ProductFilter, WishlistRepository};
+use crate::Result;
+
+const PAGE_SIZE: u64 = 100;
+
+/// Records the current price of every product into its price history, so
+/// price changes written by the scraper show up even if it doesn't record
+/// them itself.
+pub struct RefreshPricesJob {
+    notifier: Notifier,
+}
+
+impl RefreshPricesJob {
+    pub fn new(notifier: Notifier) -> Self {
+        Self { notifier }
+    }
+}
+
+#[async_trait]
+impl Job for RefreshPricesJob {
+    fn get_name(&self) -> &'static str {
+        "refresh_prices"
+    }
+
+    async fn run(&self, repo: &dyn WishlistRepository) -> Result<String> {
+        let recorded = refresh_prices(repo, &self.notifier).await?;
+        Ok(format!("recorded {} price changes", recorded))
+    }
+}
+
+/// Records the current price of every product, returns the number of changed prices.
+pub async fn refresh_prices(repo: &dyn WishlistRepository, notifier: &Notifier) -> Result<u64> {
+    let now = Utc::now().timestamp() as i32;
+    let (filter, sort) = (ProductFilter::default(), SortQuery::default());
+    let mut offset = 0;
+    let mut recorded = 0;
+    loop {
+        let products = repo.get_products(&filter, &sort, offset, PAGE_SIZE).await?.into_value();
+        for product in products.iter() {
+            if let (Some(id), Some(price)) = (product.get_id(), product.get_price()) {
+                if record_price(repo, notifier, SCRAPER, id, price, now).await? {
+                    recorded += 1;
+                }
+            }
+        }
+        if (products.len() as u64) < PAGE_SIZE {
+            break;
+        }
+        offset += PAGE_SIZE;
+    }
+    Ok(recorded)
+}
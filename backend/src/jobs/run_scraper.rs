@@ -0,0 +1,204 @@
+use async_trait::async_trait;
+use chrono::Utc;
+use mongodb::bson::oid::ObjectId;
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Instant;
+use tokio::process::Command;
+
+use super::refresh_prices::refresh_prices;
+use crate::classify::classify_uncategorized;
+use crate::dedup::deduplicate_products;
+use crate::handler::{publish_snapshot_changes, update_archived_products};
+use super::Job;
+use crate::model::{ScrapeRun, ScrapeStatus, SourceItems};
+use crate::notify::Notifier;
+use crate::query::SortQuery;
+use crate::repository::{ProductFilter, WishlistRepository};
+use crate::{Error, Result};
+
+/// Environment variable the scrape command gets the name of the source to
+/// scrape in, it isn't set when all sources are to be scraped.
+const SCRAPE_SOURCE_VARIABLE: &str = "SCRAPE_SOURCE";
+/// Environment variable with the user agent for the sources that don't set
+/// their own, the scraper reads the politeness of each source from its document.
+const SCRAPE_USER_AGENT_VARIABLE: &str = "SCRAPE_USER_AGENT";
+/// Runs kept for the scrape status, older ones are dropped.
+const MAX_RUNS: usize = 50;
+const PAGE_SIZE: u64 = 100;
+
+/// Runs the external scraper through the shell, the scraper writes its results
+/// to the database itself. Afterwards the archived products are flagged, the
+/// new products are categorized by the category rules, the offers of the
+/// same item by several sources are grouped, the changes of the
+/// snapshot are published and the new prices are recorded, notifying about
+/// price drops.
+///
+/// Only one run at a time is allowed, whether started by the schedule or
+/// through the API. The latest runs are kept in memory for the scrape status.
+#[derive(Clone)]
+pub struct Scraper {
+    command: Option<String>,
+    user_agent: String,
+    notifier: Notifier,
+    running: Arc<AtomicBool>,
+    runs: Arc<Mutex<VecDeque<ScrapeRun>>>,
+}
+
+/// Runs the scraper on a schedule.
+pub struct RunScraperJob {
+    scraper: Scraper,
+}
+
+impl Scraper {
+    pub fn new(command: Option<String>, user_agent: String, notifier: Notifier) -> Self {
+        Self {
+            command,
+            user_agent,
+            notifier,
+            running: Arc::new(AtomicBool::new(false)),
+            runs: Arc::new(Mutex::new(VecDeque::new())),
+        }
+    }
+
+    pub fn get_status(&self) -> ScrapeStatus {
+        ScrapeStatus::new(self.running.load(Ordering::SeqCst), self.lock().iter().cloned().collect())
+    }
+
+    /// Starts a run scraping `source`, or all sources if `None`, in the
+    /// background. Fails with `Error::Conflict` if a run is going on.
+    pub fn start(&self, repo: Arc<dyn WishlistRepository>, source: Option<String>) -> Result<()> {
+        let command = self.claim()?;
+        let scraper = self.clone();
+        tokio::spawn(async move {
+            match scraper.run_claimed(repo.as_ref(), &command, source).await {
+                Ok(summary) => info!("Scrape finished: {}", summary),
+                Err(e) => error!("Scrape failed: {}", e),
+            }
+        });
+        Ok(())
+    }
+
+    /// Scrapes all sources, fails with `Error::Conflict` if a run is going on.
+    pub async fn run(&self, repo: &dyn WishlistRepository) -> Result<String> {
+        let command = self.claim()?;
+        self.run_claimed(repo, &command, None).await
+    }
+
+    fn claim(&self) -> Result<String> {
+        let command = self.command.clone()
+            .ok_or_else(|| Error::Unavailable("no scrape command is configured".to_string()))?;
+        if self.running.swap(true, Ordering::SeqCst) {
+            return Err(Error::Conflict("a scrape is already running".to_string()));
+        }
+        Ok(command)
+    }
+
+    async fn run_claimed(&self, repo: &dyn WishlistRepository, command: &str, source: Option<String>) -> Result<String> {
+        let timestamp = Utc::now().timestamp() as i32;
+        let started = Instant::now();
+        {
+            let mut runs = self.lock();
+            runs.push_front(ScrapeRun::new(timestamp, source.clone()));
+            runs.truncate(MAX_RUNS);
+        }
+        let result = self.scrape(repo, command, source.as_deref()).await;
+        let found = match count_found(repo, timestamp).await {
+            Ok(found) => found,
+            Err(e) => {
+                warn!("Could not count the products found by the scrape: {}", e);
+                Vec::new()
+            }
+        };
+        if let Some(run) = self.lock().iter_mut().find(|run| run.get_started() == timestamp) {
+            run.finish(started.elapsed(), &result, found);
+        }
+        self.running.store(false, Ordering::SeqCst);
+        result
+    }
+
+    async fn scrape(&self, repo: &dyn WishlistRepository, command: &str, source: Option<&str>) -> Result<String> {
+        let mut process = Command::new("sh");
+        process.arg("-c").arg(command).env(SCRAPE_USER_AGENT_VARIABLE, &self.user_agent);
+        if let Some(source) = source {
+            process.env(SCRAPE_SOURCE_VARIABLE, source);
+        }
+        let output = process.output()
+            .await
+            .map_err(|e| Error::Job(format!("could not run scraper: {}", e)))?;
+        if output.status.success() {
+            update_archived_products(repo).await?;
+            let classified = classify_uncategorized(repo).await?;
+            let deduplicated = deduplicate_products(repo).await?;
+            publish_snapshot_changes(repo, &self.notifier).await?;
+            let recorded = refresh_prices(repo, &self.notifier).await?;
+            Ok(format!(
+                "scraper exited with {}, categorized {} products, updated the duplicates of {} products, recorded {} price changes",
+                output.status, classified, deduplicated, recorded
+            ))
+        } else {
+            Err(Error::Job(format!(
+                "scraper exited with {}: {}",
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            )))
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, VecDeque<ScrapeRun>> {
+        self.runs.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl RunScraperJob {
+    pub fn new(scraper: Scraper) -> Self {
+        Self { scraper }
+    }
+}
+
+#[async_trait]
+impl Job for RunScraperJob {
+    fn get_name(&self) -> &'static str {
+        "scrape_sources"
+    }
+
+    async fn run(&self, repo: &dyn WishlistRepository) -> Result<String> {
+        self.scraper.run(repo).await
+    }
+}
+
+/// Counts the products seen since `since` per source, most first.
+async fn count_found(repo: &dyn WishlistRepository, since: i32) -> Result<Vec<SourceItems>> {
+    let (filter, sort) = (ProductFilter::default().with_last_seen(Some(since), None), SortQuery::default());
+    let mut counts: BTreeMap<ObjectId, u64> = BTreeMap::new();
+    let mut offset = 0;
+    loop {
+        let products = repo.get_products(&filter, &sort, offset, PAGE_SIZE).await?.into_value();
+        for source in products.iter().filter_map(|p| p.get_source_id()) {
+            *counts.entry(source.clone()).or_default() += 1;
+        }
+        if (products.len() as u64) < PAGE_SIZE {
+            break;
+        }
+        offset += PAGE_SIZE;
+    }
+    if counts.is_empty() {
+        return Ok(Vec::new());
+    }
+    let ids: Vec<ObjectId> = counts.keys().cloned().collect();
+    let sources = repo.get_sources_by_id(&ids).await?.into_value();
+    let mut found: Vec<SourceItems> = Vec::with_capacity(counts.len());
+    let mut by_count: Vec<(ObjectId, u64)> = counts.into_iter().collect();
+    by_count.sort_by_key(|(_, count)| Reverse(*count));
+    for (id, items) in by_count {
+        let name = sources.iter()
+            .find(|s| s.get_id() == Some(&id))
+            .and_then(|s| s.get_name())
+            .map(String::from)
+            .unwrap_or_else(|| id.to_hex());
+        found.push(SourceItems::new(name, items));
+    }
+    Ok(found)
+}
@@ -0,0 +1,35 @@
+use async_trait::async_trait;
+use std::time::Duration;
+
+use super::Job;
+use crate::handler::send_email_digest;
+use crate::notify::Notifier;
+use crate::repository::WishlistRepository;
+use crate::Result;
+
+/// Mails a digest of the products added and removed and the price changes
+/// of every list over the last `period`.
+pub struct SendEmailDigestJob {
+    notifier: Notifier,
+    period: Duration,
+}
+
+impl SendEmailDigestJob {
+    pub fn new(notifier: Notifier, period: Duration) -> Self {
+        Self { notifier, period }
+    }
+}
+
+#[async_trait]
+impl Job for SendEmailDigestJob {
+    fn get_name(&self) -> &'static str {
+        "email_digest"
+    }
+
+    async fn run(&self, repo: &dyn WishlistRepository) -> Result<String> {
+        match send_email_digest(repo, &self.notifier, self.period).await? {
+            0 => Ok("no list changed, no digest sent".to_string()),
+            changed => Ok(format!("mailed digest of {} changed lists", changed)),
+        }
+    }
+}
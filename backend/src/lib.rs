@@ -1,16 +1,76 @@
+#![recursion_limit = "256"]
+
 extern crate mongodb;
 extern crate warp;
 #[macro_use]
-extern crate log;
+extern crate tracing;
 extern crate bson;
 extern crate thiserror;
 
+mod affiliate;
+mod audit;
+mod auth;
+mod backup;
+mod cache;
+mod changes;
+mod check;
+mod classify;
+mod compress;
+mod config;
+mod cors;
+mod currency;
+mod dedup;
+mod enrichment;
 mod error;
+mod etag;
+mod export;
+mod feed;
+mod format;
+mod graphql;
 mod handler;
+mod images;
+mod import;
+mod ingest;
+mod jobs;
+mod maintenance;
+mod migrations;
 mod model;
+mod modified;
+mod net;
+mod notify;
+mod openapi;
+mod paging;
+mod policy;
+mod politeness;
 mod query;
+mod ratelimit;
 mod reject;
+mod retention;
+mod repository;
 mod routes;
+mod steam;
+mod tls;
+mod trace;
+mod versions;
+mod views;
 
+pub use self::auth::{ApiKeyConfig, ApiKeyScope, ApiKeys};
+pub use self::backup::Backup;
+pub use self::changes::ChangeWatcher;
+pub use self::check::check_consistency;
+pub use self::config::{CacheConfig, CircuitBreakerConfig, Config, CorsConfig, DatabaseConfig, EmailConfig, ImagesConfig, JobsConfig, LogConfig, LogFormat, NotifyConfig, PushConfig, RateLimitConfig, RetryConfig, ServerConfig, SmtpSecurity, TelegramConfig, TlsConfig};
+pub use self::enrichment::Enricher;
 pub use self::error::{Error, Result};
+pub use self::handler::{record_price, record_wishlist_snapshot, update_archived_products};
+pub use self::jobs::{CheckLinksJob, DeduplicateProductsJob, EnrichProductsJob, FlushViewsJob, ImportSteamWishlistJob, Job, PruneSnapshotsJob, RefreshExchangeRatesJob, RefreshPricesJob, RunScraperJob, Scheduler, Scraper, SendEmailDigestJob};
+pub use self::maintenance::Maintenance;
+pub use self::migrations::{MigrationResult, Migrator};
+pub use self::notify::Notifier;
+pub use self::politeness::PoliteClient;
+pub use self::repository::{CachedRepository, MemoryRepository, MongoRepository, QueryTracer, ResilientRepository, WishlistRepository};
+pub use self::retention::RetentionPolicy;
 pub use self::routes::create_routes;
+pub use self::steam::SteamClient;
+pub use self::tls::{serve_tls, TlsTerminator};
+pub use self::trace::{init as init_tracing, LogSubscriber};
+pub use self::views::ViewCounter;
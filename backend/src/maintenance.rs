@@ -0,0 +1,81 @@
+use chrono::Utc;
+use std::sync::{Arc, RwLock};
+use warp::http::Method;
+use warp::path::FullPath;
+use warp::{Filter, Rejection};
+
+use crate::config::ServerConfig;
+use crate::model::{MaintenanceInput, MaintenanceStatus};
+use crate::versions::strip_version;
+use crate::{Error, Result};
+
+/// Paths still accepting mutating requests in read-only mode: switching it
+/// off, and the ones that only read despite using `POST`. Given without the
+/// API version.
+const WRITABLE_PATHS: [&str; 3] = ["/api/admin/maintenance", "/api/user/login", "/api/graphql"];
+
+/// Runtime switch putting the API into read-only mode, e.g. during database
+/// migrations. Reads keep being served, mutating requests, writes to the
+/// database and scheduled jobs are refused until it is switched off.
+#[derive(Clone)]
+pub struct Maintenance {
+    status: Arc<RwLock<MaintenanceStatus>>,
+}
+
+impl Maintenance {
+    pub fn new(config: &ServerConfig) -> Self {
+        let since = if config.read_only { Some(Utc::now().timestamp()) } else { None };
+        Self {
+            status: Arc::new(RwLock::new(MaintenanceStatus::new(config.read_only, config.maintenance_message.clone(), since))),
+        }
+    }
+
+    pub fn get_status(&self) -> MaintenanceStatus {
+        self.status.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    pub fn is_read_only(&self) -> bool {
+        self.status.read().unwrap_or_else(|e| e.into_inner()).is_read_only()
+    }
+
+    /// Switches read-only mode, keeping the time it was turned on at if it already was.
+    pub fn set(&self, input: &MaintenanceInput) -> Result<MaintenanceStatus> {
+        let message = input.get_message()?;
+        let mut status = self.status.write().unwrap_or_else(|e| e.into_inner());
+        let since = match (input.is_read_only(), status.get_since()) {
+            (false, _) => None,
+            (true, Some(since)) => Some(since),
+            (true, None) => Some(Utc::now().timestamp()),
+        };
+        let message = message.map(String::from).unwrap_or_else(|| status.get_message().to_string());
+        *status = MaintenanceStatus::new(input.is_read_only(), message, since);
+        Ok(status.clone())
+    }
+
+    /// Fails with `Error::Unavailable` and the maintenance message in read-only mode.
+    pub fn check_writable(&self) -> Result<()> {
+        let status = self.status.read().unwrap_or_else(|e| e.into_inner());
+        if status.is_read_only() {
+            Err(Error::Unavailable(status.get_message().to_string()))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Rejects mutating requests with `Unavailable` in read-only mode.
+pub fn with_writable(maintenance: Maintenance) -> impl Filter<Extract = (), Error = Rejection> + Clone {
+    warp::method()
+        .and(warp::path::full())
+        .and_then(move |method: Method, path: FullPath| {
+            let maintenance = maintenance.clone();
+            async move {
+                let safe = method == Method::GET || method == Method::HEAD || method == Method::OPTIONS;
+                if safe || WRITABLE_PATHS.contains(&strip_version(path.as_str()).as_str()) {
+                    return Ok(());
+                }
+                maintenance.check_writable().map_err(warp::reject::custom)
+            }
+        })
+        .untuple_one()
+}
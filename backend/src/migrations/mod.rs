@@ -0,0 +1,128 @@
+use async_trait::async_trait;
+use chrono::Utc;
+use mongodb::bson::doc;
+use mongodb::Database;
+use std::fmt;
+use tokio::stream::StreamExt;
+
+use crate::model::{SchemaMigration, Stored};
+use crate::{Error, Result};
+
+mod product_currency;
+mod product_lowest_price;
+mod product_source_item_id;
+mod product_tags;
+
+use self::product_currency::ProductCurrency;
+use self::product_lowest_price::ProductLowestPrice;
+use self::product_source_item_id::ProductSourceItemId;
+use self::product_tags::ProductTags;
+
+/// Change of the stored data to the schema a newer version expects.
+///
+/// Migrations must be idempotent: one interrupted halfway is not recorded
+/// and runs again on the next start.
+#[async_trait]
+pub trait Migration: Send + Sync {
+    /// Position among the migrations, a released version is never reused.
+    fn get_version(&self) -> i32;
+    fn get_name(&self) -> &'static str;
+    /// Counts the documents `run` would change.
+    async fn count(&self, database: &Database) -> Result<u64>;
+    /// Applies the migration, returns the number of changed documents.
+    async fn run(&self, database: &Database) -> Result<u64>;
+}
+
+/// Every migration, ordered by version. New ones are only ever appended.
+fn get_migrations() -> Vec<Box<dyn Migration>> {
+    vec![
+        Box::new(ProductTags),
+        Box::new(ProductCurrency),
+        Box::new(ProductLowestPrice),
+        Box::new(ProductSourceItemId),
+    ]
+}
+
+/// Applies the migrations not yet recorded in `schema_migrations`, in order
+/// of their version.
+pub struct Migrator {
+    database: Database,
+}
+
+/// Migration that was applied, or would be by a dry run.
+pub struct MigrationResult {
+    version: i32,
+    name: &'static str,
+    changed: u64,
+}
+
+impl Migrator {
+    pub fn new(database: Database) -> Self {
+        Self { database }
+    }
+
+    /// Runs the pending migrations, stopping at the first failing one. On
+    /// `dry_run` nothing is written, the results count the documents each would change.
+    pub async fn run(&self, dry_run: bool) -> Result<Vec<MigrationResult>> {
+        let applied = self.get_applied().await?;
+        let migrations = get_migrations();
+        let latest = migrations.iter().map(|m| m.get_version()).max().unwrap_or_default();
+        if let Some(newer) = applied.iter().find(|m| m.get_version() > latest) {
+            warn!(
+                "Migration {} '{}' was applied by a newer version, the stored data may not match this one",
+                newer.get_version(),
+                newer.get_name()
+            );
+        }
+
+        let mut results = Vec::new();
+        for migration in migrations.iter().filter(|m| !applied.iter().any(|a| a.get_version() == m.get_version())) {
+            let result = if dry_run {
+                migration.count(&self.database).await
+            } else {
+                info!("Applying migration {} '{}'", migration.get_version(), migration.get_name());
+                self.apply(migration.as_ref()).await
+            };
+            let changed = result.map_err(|e| {
+                Error::Migration(format!("migration {} '{}' failed: {}", migration.get_version(), migration.get_name(), e))
+            })?;
+            results.push(MigrationResult {
+                version: migration.get_version(),
+                name: migration.get_name(),
+                changed,
+            });
+        }
+        Ok(results)
+    }
+
+    async fn apply(&self, migration: &dyn Migration) -> Result<u64> {
+        let changed = migration.run(&self.database).await?;
+        self.database.collection(SchemaMigration::COLLECTION)
+            .insert_one(
+                doc! {
+                    "version": migration.get_version(),
+                    "name": migration.get_name(),
+                    "applied": Utc::now().timestamp() as i32,
+                    "changed": changed as i64,
+                },
+                None,
+            )
+            .await?;
+        Ok(changed)
+    }
+
+    async fn get_applied(&self) -> Result<Vec<SchemaMigration>> {
+        let mut cursor = self.database.collection(SchemaMigration::COLLECTION).find(None, None).await?;
+        let mut applied = Vec::new();
+        while let Some(document) = cursor.next().await {
+            applied.push(SchemaMigration::from_document(document?)?);
+        }
+        Ok(applied)
+    }
+}
+
+impl fmt::Display for MigrationResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "migration {} '{}' ({} documents)", self.version, self.name, self.changed)
+    }
+}
@@ -0,0 +1,37 @@
+use async_trait::async_trait;
+use mongodb::bson::{doc, document::Document};
+use mongodb::Database;
+
+use super::Migration;
+use crate::model::{Currency, Product, Stored};
+use crate::Result;
+
+/// Stores the currency of products stored before prices had one, which were
+/// all in the default currency.
+pub struct ProductCurrency;
+
+fn filter() -> Document {
+    doc! {"currency": {"$exists": false}}
+}
+
+#[async_trait]
+impl Migration for ProductCurrency {
+    fn get_version(&self) -> i32 {
+        2
+    }
+
+    fn get_name(&self) -> &'static str {
+        "product_currency"
+    }
+
+    async fn count(&self, database: &Database) -> Result<u64> {
+        Ok(database.collection(Product::COLLECTION).count_documents(filter(), None).await? as u64)
+    }
+
+    async fn run(&self, database: &Database) -> Result<u64> {
+        let result = database.collection(Product::COLLECTION)
+            .update_many(filter(), doc! {"$set": {"currency": Currency::default().get_code()}}, None)
+            .await?;
+        Ok(result.modified_count as u64)
+    }
+}
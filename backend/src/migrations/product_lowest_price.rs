@@ -0,0 +1,63 @@
+use async_trait::async_trait;
+use mongodb::bson::{doc, document::Document, Bson};
+use mongodb::options::FindOptions;
+use mongodb::Database;
+use tokio::stream::StreamExt;
+
+use super::Migration;
+use crate::model::{PricePoint, Product, Stored};
+use crate::Result;
+
+/// Stores the lowest price of products stored before it was tracked, the
+/// lowest of their price history and the price they were added with.
+pub struct ProductLowestPrice;
+
+fn filter() -> Document {
+    doc! {"lowest_price": {"$exists": false}}
+}
+
+#[async_trait]
+impl Migration for ProductLowestPrice {
+    fn get_version(&self) -> i32 {
+        3
+    }
+
+    fn get_name(&self) -> &'static str {
+        "product_lowest_price"
+    }
+
+    async fn count(&self, database: &Database) -> Result<u64> {
+        Ok(database.collection(Product::COLLECTION).count_documents(filter(), None).await? as u64)
+    }
+
+    async fn run(&self, database: &Database) -> Result<u64> {
+        let products = database.collection(Product::COLLECTION);
+        let options = FindOptions::builder()
+            .projection(doc! {"_id": 1, "price": 1})
+            .build();
+        let mut cursor = products.find(filter(), options).await?;
+        let mut changed = 0;
+        while let Some(product) = cursor.next().await {
+            let product = product?;
+            let id = product.get_object_id("_id")?.clone();
+            let pipeline = vec![
+                doc! {"$match": {"product": &id}},
+                doc! {"$group": {"_id": Bson::Null, "lowest": {"$min": "$price"}}},
+            ];
+            let lowest_point = match database.collection(PricePoint::COLLECTION).aggregate(pipeline, None).await?.next().await {
+                Some(group) => group?.get_i32("lowest").ok(),
+                None => None,
+            };
+            let lowest = match (product.get_i32("price").ok(), lowest_point) {
+                (Some(price), Some(point)) => price.min(point),
+                (price, point) => match price.or(point) {
+                    Some(lowest) => lowest,
+                    None => continue,
+                },
+            };
+            products.update_one(doc! {"_id": id}, doc! {"$set": {"lowest_price": lowest}}, None).await?;
+            changed += 1;
+        }
+        Ok(changed)
+    }
+}
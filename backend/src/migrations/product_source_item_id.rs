@@ -0,0 +1,150 @@
+use async_trait::async_trait;
+use chrono::Utc;
+use mongodb::bson::{doc, document::Document, oid::ObjectId, Bson};
+use mongodb::error::ErrorKind;
+use mongodb::Database;
+use tokio::stream::StreamExt;
+
+use super::Migration;
+use crate::model::{Comment, PledgePool, PriceAlert, PricePoint, Product, Reservation, Stored, Wishlist, DEFAULT_LIST};
+use crate::Result;
+
+/// Unique index on the item id and list, replaced by the one on the source,
+/// item id and list.
+const REPLACED_INDEX: &str = "product_item_id";
+const NAMESPACE_NOT_FOUND: i32 = 26;
+const INDEX_NOT_FOUND: i32 = 27;
+
+/// Merges the products of a list sharing their source and item id into the
+/// oldest one not in the trash, so that the unique index on them can be
+/// built, and drops the index it replaces.
+///
+/// The snapshots, prices, alerts and comments of the duplicates are moved to
+/// the kept product, as are their reservation and pledge pool unless it has
+/// one already. The duplicates are moved to the trash without their item id.
+pub struct ProductSourceItemId;
+
+/// Groups the products sharing list, source and item id, the one to keep first.
+fn duplicates_pipeline() -> Vec<Document> {
+    vec![
+        doc! {"$match": {"item_id": {"$type": "string"}}},
+        doc! {"$sort": {"deleted_at": 1, "_id": 1}},
+        doc! {"$group": {
+            "_id": {"list": {"$ifNull": ["$list", DEFAULT_LIST]}, "source": "$source", "item_id": "$item_id"},
+            "ids": {"$push": "$_id"},
+            "first_seen": {"$min": "$first_seen"},
+            "last_seen": {"$max": "$last_seen"},
+            // Current if any of them is.
+            "archived": {"$min": "$archived"},
+        }},
+        doc! {"$match": {"ids.1": {"$exists": true}}},
+    ]
+}
+
+#[async_trait]
+impl Migration for ProductSourceItemId {
+    fn get_version(&self) -> i32 {
+        4
+    }
+
+    fn get_name(&self) -> &'static str {
+        "product_source_item_id"
+    }
+
+    async fn count(&self, database: &Database) -> Result<u64> {
+        let mut pipeline = duplicates_pipeline();
+        pipeline.push(doc! {"$group": {"_id": Bson::Null, "count": {"$sum": {"$subtract": [{"$size": "$ids"}, 1]}}}});
+        let mut cursor = database.collection(Product::COLLECTION).aggregate(pipeline, None).await?;
+        match cursor.next().await {
+            Some(total) => Ok(total?.get_i32("count").unwrap_or_default() as u64),
+            None => Ok(0),
+        }
+    }
+
+    async fn run(&self, database: &Database) -> Result<u64> {
+        let mut cursor = database.collection(Product::COLLECTION).aggregate(duplicates_pipeline(), None).await?;
+        let mut groups = Vec::new();
+        while let Some(group) = cursor.next().await {
+            groups.push(group?);
+        }
+        let mut changed = 0;
+        for group in groups {
+            let ids: Vec<ObjectId> = group.get_array("ids")?.iter().filter_map(|id| id.as_object_id().cloned()).collect();
+            if let Some((kept, duplicates)) = ids.split_first() {
+                merge(database, kept, duplicates, &group).await?;
+                changed += duplicates.len() as u64;
+            }
+        }
+        drop_replaced_index(database).await?;
+        Ok(changed)
+    }
+}
+
+async fn merge(database: &Database, kept: &ObjectId, duplicates: &[ObjectId], group: &Document) -> Result<()> {
+    // A snapshot listing the kept product and a duplicate lists it once.
+    let replaced = doc! {"$map": {
+        "input": "$products",
+        "as": "product",
+        "in": {"$cond": [{"$in": ["$$product", duplicates]}, kept.clone(), "$$product"]},
+    }};
+    let deduplicated = doc! {"$reduce": {
+        "input": replaced,
+        "initialValue": [],
+        "in": {"$cond": [{"$in": ["$$this", "$$value"]}, "$$value", {"$concatArrays": ["$$value", ["$$this"]]}]},
+    }};
+    database.collection(Wishlist::COLLECTION)
+        .update_many(doc! {"products": {"$in": duplicates}}, vec![doc! {"$set": {"products": deduplicated}}], None)
+        .await?;
+
+    for collection in [PricePoint::COLLECTION, PriceAlert::COLLECTION, Comment::COLLECTION].iter() {
+        database.collection(collection)
+            .update_many(doc! {"product": {"$in": duplicates}}, doc! {"$set": {"product": kept.clone()}}, None)
+            .await?;
+    }
+    // A product has at most one of each, the others stay with the duplicates in the trash.
+    for collection in [Reservation::COLLECTION, PledgePool::COLLECTION].iter() {
+        let collection = database.collection(collection);
+        if collection.count_documents(doc! {"product": kept.clone()}, None).await? == 0 {
+            collection
+                .update_one(doc! {"product": {"$in": duplicates}}, doc! {"$set": {"product": kept.clone()}}, None)
+                .await?;
+        }
+    }
+
+    let products = database.collection(Product::COLLECTION);
+    let mut update = Document::new();
+    for field in ["first_seen", "last_seen"].iter() {
+        if let Ok(timestamp) = group.get_i32(field) {
+            update.insert(*field, timestamp);
+        }
+    }
+    if let Ok(archived) = group.get_bool("archived") {
+        update.insert("archived", archived);
+    }
+    if !update.is_empty() {
+        products.update_one(doc! {"_id": kept.clone()}, doc! {"$set": update}, None).await?;
+    }
+    products
+        .update_many(doc! {"_id": {"$in": duplicates}}, doc! {"$unset": {"item_id": ""}}, None)
+        .await?;
+    products
+        .update_many(
+            doc! {"_id": {"$in": duplicates}, "deleted_at": Bson::Null},
+            doc! {"$set": {"deleted_at": Utc::now().timestamp() as i32}},
+            None,
+        )
+        .await?;
+    Ok(())
+}
+
+async fn drop_replaced_index(database: &Database) -> Result<()> {
+    let command = doc! {"dropIndexes": Product::COLLECTION, "index": REPLACED_INDEX};
+    match database.run_command(command, None).await {
+        Ok(_) => {
+            info!("Dropped index '{}' on '{}'", REPLACED_INDEX, Product::COLLECTION);
+            Ok(())
+        }
+        Err(e) if matches!(e.kind.as_ref(), ErrorKind::CommandError(error) if [NAMESPACE_NOT_FOUND, INDEX_NOT_FOUND].contains(&error.code)) => Ok(()),
+        Err(e) => Err(e.into()),
+    }
+}
@@ -0,0 +1,36 @@
+use async_trait::async_trait;
+use mongodb::bson::{doc, document::Document};
+use mongodb::Database;
+
+use super::Migration;
+use crate::model::{Product, Stored};
+use crate::Result;
+
+/// Gives products stored before tags existed an empty list of tags.
+pub struct ProductTags;
+
+fn filter() -> Document {
+    doc! {"tags": {"$exists": false}}
+}
+
+#[async_trait]
+impl Migration for ProductTags {
+    fn get_version(&self) -> i32 {
+        1
+    }
+
+    fn get_name(&self) -> &'static str {
+        "product_tags"
+    }
+
+    async fn count(&self, database: &Database) -> Result<u64> {
+        Ok(database.collection(Product::COLLECTION).count_documents(filter(), None).await? as u64)
+    }
+
+    async fn run(&self, database: &Database) -> Result<u64> {
+        let result = database.collection(Product::COLLECTION)
+            .update_many(filter(), doc! {"$set": {"tags": []}}, None)
+            .await?;
+        Ok(result.modified_count as u64)
+    }
+}
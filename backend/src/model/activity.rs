@@ -0,0 +1,36 @@
+use mongodb::bson::oid::ObjectId;
+use serde::Serialize;
+
+use super::AuditEvent;
+
+/// Entry of the activity feed of a list, an audit event named after whoever
+/// made the change and the product it was made to.
+#[derive(Serialize, Clone, Debug)]
+pub struct ActivityEntry {
+    /// Actor as in the audit log, e.g. `user:<id>`, `admin` or `anonymous`.
+    actor: String,
+    /// Username of the actor, `None` unless it is a user.
+    author: Option<String>,
+    action: String,
+    entity: String,
+    #[serde(serialize_with = "super::serialize_object_id")]
+    entity_id: Option<ObjectId>,
+    /// Name of the product changed, `None` for changes of the list itself and
+    /// of products no longer found.
+    product_name: Option<String>,
+    timestamp: i32,
+}
+
+impl ActivityEntry {
+    pub fn new(event: &AuditEvent, author: Option<String>, product_name: Option<String>) -> Self {
+        Self {
+            actor: event.get_actor().to_string(),
+            author,
+            action: event.get_action().to_string(),
+            entity: event.get_entity().to_string(),
+            entity_id: event.get_entity_id().cloned(),
+            product_name,
+            timestamp: event.get_timestamp(),
+        }
+    }
+}
@@ -0,0 +1,118 @@
+use mongodb::bson::{doc, document::Document, oid::ObjectId, Bson};
+use serde::{Deserialize, Serialize};
+
+use super::Stored;
+use crate::net::is_forbidden_host;
+use crate::notify::is_valid_address;
+use crate::{Error, Result};
+
+const MAX_DESTINATION_LENGTH: usize = 2048;
+
+/// Target price of a product a user waits for. Once a recorded price drops
+/// to or below it, the alert is sent to its email address or webhook, or to
+/// the configured notification channels if it has neither.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct PriceAlert {
+    #[serde(rename(deserialize = "_id"), serialize_with = "super::serialize_object_id")]
+    id: Option<ObjectId>,
+    #[serde(serialize_with = "super::serialize_object_id_hex")]
+    product: ObjectId,
+    /// Price in the minor unit of the product's currency.
+    target_price: i32,
+    email: Option<String>,
+    webhook: Option<String>,
+    /// User who set the alert, `None` for the admin.
+    #[serde(default, serialize_with = "super::serialize_object_id")]
+    user: Option<ObjectId>,
+    created: i32,
+    /// When the price last dropped to or below the target.
+    #[serde(default)]
+    triggered: Option<i32>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct PriceAlertInput {
+    target_price: i32,
+    email: Option<String>,
+    webhook: Option<String>,
+}
+
+impl PriceAlert {
+    pub fn get_id(&self) -> Option<&ObjectId> {
+        self.id.as_ref()
+    }
+    pub fn get_product_id(&self) -> &ObjectId {
+        &self.product
+    }
+    pub fn get_target_price(&self) -> i32 {
+        self.target_price
+    }
+    pub fn get_email(&self) -> Option<&str> {
+        self.email.as_deref()
+    }
+    pub fn get_webhook(&self) -> Option<&str> {
+        self.webhook.as_deref()
+    }
+    pub fn get_user_id(&self) -> Option<&ObjectId> {
+        self.user.as_ref()
+    }
+    pub fn get_triggered(&self) -> Option<i32> {
+        self.triggered
+    }
+
+    /// Whether a price change from `old_price` to `new_price` reaches the
+    /// target, a price that already did only triggers the alert once.
+    pub fn is_reached(&self, old_price: Option<i32>, new_price: i32) -> bool {
+        new_price <= self.target_price && old_price.map(|p| p > self.target_price).unwrap_or(true)
+    }
+}
+
+impl PriceAlertInput {
+    pub fn get_email(&self) -> Option<&str> {
+        self.email.as_deref().map(str::trim).filter(|e| !e.is_empty())
+    }
+
+    pub fn get_webhook(&self) -> Result<Option<reqwest::Url>> {
+        let webhook = match self.webhook.as_deref().map(str::trim).filter(|w| !w.is_empty()) {
+            Some(webhook) => webhook,
+            None => return Ok(None),
+        };
+        let invalid = || Error::Validation("webhook must be a https url of a public host".to_string());
+        if webhook.len() > MAX_DESTINATION_LENGTH {
+            return Err(Error::Validation(format!("webhook must be at most {} characters", MAX_DESTINATION_LENGTH)));
+        }
+        let url = reqwest::Url::parse(webhook).map_err(|_| invalid())?;
+        if url.scheme() != "https" || is_forbidden_host(&url) {
+            return Err(invalid());
+        }
+        Ok(Some(url))
+    }
+
+    /// Validates the input and converts it into the document stored in the
+    /// `price_alert` collection.
+    pub fn to_document(&self, product: &ObjectId, user: Option<&ObjectId>, timestamp: i32) -> Result<Document> {
+        if self.target_price < 0 {
+            return Err(Error::Validation("target_price must not be negative".to_string()));
+        }
+        let email = self.get_email();
+        if let Some(email) = email {
+            if email.len() > MAX_DESTINATION_LENGTH || !is_valid_address(email) {
+                return Err(Error::Validation("email must be a valid email address".to_string()));
+            }
+        }
+        let webhook = self.get_webhook()?;
+        Ok(doc! {
+            "product": product.clone(),
+            "target_price": self.target_price,
+            "email": email.map(Bson::from).unwrap_or(Bson::Null),
+            "webhook": webhook.map(|url| Bson::from(url.as_str())).unwrap_or(Bson::Null),
+            "user": user.map(|id| Bson::ObjectId(id.clone())).unwrap_or(Bson::Null),
+            "created": timestamp,
+            "triggered": Bson::Null,
+        })
+    }
+}
+
+impl Stored for PriceAlert {
+    const COLLECTION: &'static str = "price_alert";
+}
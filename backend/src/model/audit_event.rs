@@ -0,0 +1,53 @@
+use mongodb::bson::{doc, document::Document, oid::ObjectId, Bson};
+use serde::{Deserialize, Serialize};
+
+use super::Stored;
+
+/// Entry of the append-only audit log. `entity` names the collection of the
+/// changed document, `payload` holds what was written, depending on `action`.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct AuditEvent {
+    #[serde(rename(deserialize = "_id"), serialize_with = "super::serialize_object_id")]
+    id: Option<ObjectId>,
+    actor: String,
+    action: String,
+    entity: String,
+    #[serde(default, serialize_with = "super::serialize_object_id")]
+    entity_id: Option<ObjectId>,
+    #[serde(default)]
+    payload: Document,
+    timestamp: i32,
+}
+
+impl AuditEvent {
+    pub fn get_actor(&self) -> &str {
+        &self.actor
+    }
+    pub fn get_action(&self) -> &str {
+        &self.action
+    }
+    pub fn get_entity(&self) -> &str {
+        &self.entity
+    }
+    pub fn get_entity_id(&self) -> Option<&ObjectId> {
+        self.entity_id.as_ref()
+    }
+    pub fn get_timestamp(&self) -> i32 {
+        self.timestamp
+    }
+
+    pub fn to_document(actor: &str, action: &str, entity: &str, entity_id: Option<&ObjectId>, payload: Document, timestamp: i32) -> Document {
+        doc! {
+            "actor": actor,
+            "action": action,
+            "entity": entity,
+            "entity_id": entity_id.cloned().map(Bson::ObjectId).unwrap_or(Bson::Null),
+            "payload": payload,
+            "timestamp": timestamp,
+        }
+    }
+}
+
+impl Stored for AuditEvent {
+    const COLLECTION: &'static str = "events";
+}
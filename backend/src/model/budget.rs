@@ -0,0 +1,56 @@
+use mongodb::bson::{doc, document::Document};
+use serde::{Deserialize, Serialize};
+
+use super::{Currency, Money, Stored};
+use crate::{Error, Result};
+
+/// Amount the owner of a list plans to spend on it, lists without one have
+/// no document.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Budget {
+    list: String,
+    /// In the minor unit of `currency`.
+    amount: i64,
+    #[serde(default)]
+    currency: Currency,
+    updated_at: i32,
+}
+
+/// Budget in the minor unit of `currency`, the default currency if unset.
+/// A missing amount removes the budget.
+#[derive(Deserialize, Debug)]
+pub struct BudgetInput {
+    amount: Option<i64>,
+    currency: Option<Currency>,
+}
+
+impl Budget {
+    pub fn get_list(&self) -> &str {
+        &self.list
+    }
+    pub fn get_amount(&self) -> Money {
+        Money::new(self.amount, self.currency)
+    }
+}
+
+impl BudgetInput {
+    /// Validates the input and converts it into the document stored in the
+    /// `budget` collection, `None` if the budget is to be removed.
+    pub fn to_document(&self, list: &str, timestamp: i32) -> Result<Option<Document>> {
+        let amount = match self.amount {
+            Some(amount) if amount <= 0 => return Err(Error::Validation("amount must be positive".to_string())),
+            Some(amount) => amount,
+            None => return Ok(None),
+        };
+        Ok(Some(doc! {
+            "list": list,
+            "amount": amount,
+            "currency": self.currency.unwrap_or_default().get_code(),
+            "updated_at": timestamp,
+        }))
+    }
+}
+
+impl Stored for Budget {
+    const COLLECTION: &'static str = "budget";
+}
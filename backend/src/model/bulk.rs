@@ -0,0 +1,87 @@
+use mongodb::bson::{document::Document, oid::ObjectId};
+use serde::{Deserialize, Serialize};
+
+use super::{parse_object_id, product::normalize_tags, Priority};
+use crate::{Error, Result};
+
+/// Products changed at once, more are rejected.
+const MAX_IDS: usize = 500;
+
+/// Changes applied to several products at once. Unset fields are left alone,
+/// `tags` replaces the tags of the products.
+#[derive(Deserialize, Debug)]
+pub struct BulkUpdateInput {
+    ids: Vec<String>,
+    category: Option<String>,
+    tags: Option<Vec<String>>,
+    priority: Option<Priority>,
+}
+
+/// Outcome of a bulk update, with one result per id in input order.
+#[derive(Serialize, Debug, Default)]
+pub struct BulkUpdateReport {
+    updated: u64,
+    failed: u64,
+    results: Vec<BulkUpdateResult>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct BulkUpdateResult {
+    id: String,
+    status: BulkUpdateStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    message: Option<String>,
+}
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum BulkUpdateStatus {
+    Updated,
+    NotFound,
+    Failed,
+}
+
+impl BulkUpdateInput {
+    pub fn get_ids(&self) -> &[String] {
+        &self.ids
+    }
+
+    pub fn get_category(&self) -> Result<Option<ObjectId>> {
+        self.category.as_deref().map(|id| parse_object_id("category", id)).transpose()
+    }
+
+    /// Validates the input and converts it into the fields to set on every product.
+    pub fn to_document(&self) -> Result<Document> {
+        if self.ids.is_empty() || self.ids.len() > MAX_IDS {
+            return Err(Error::Validation(format!("ids must hold between 1 and {} products", MAX_IDS)));
+        }
+        let mut doc = Document::new();
+        if let Some(category) = self.get_category()? {
+            doc.insert("category", category);
+        }
+        if let Some(tags) = &self.tags {
+            doc.insert("tags", normalize_tags(tags)?);
+        }
+        if let Some(priority) = self.priority {
+            doc.insert("priority", priority.get_code());
+        }
+        if doc.is_empty() {
+            return Err(Error::Validation("at least one of category, tags and priority must be set".to_string()));
+        }
+        Ok(doc)
+    }
+}
+
+impl BulkUpdateReport {
+    pub fn add(&mut self, id: &str, status: BulkUpdateStatus, message: Option<String>) {
+        match status {
+            BulkUpdateStatus::Updated => self.updated += 1,
+            BulkUpdateStatus::NotFound | BulkUpdateStatus::Failed => self.failed += 1,
+        }
+        self.results.push(BulkUpdateResult {
+            id: id.to_string(),
+            status,
+            message,
+        });
+    }
+}
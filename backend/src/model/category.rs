@@ -1,39 +1,273 @@
-use mongodb::bson::{document::Document, oid::ObjectId};
-use serde::Serialize;
+use mongodb::bson::{doc, document::Document, oid::ObjectId, Bson};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
 
-#[derive(Serialize, Clone, Debug)]
+use super::{parse_object_id, Product, Stored};
+use crate::{Error, Result};
+
+const MAX_NAME_LENGTH: usize = 100;
+const MAX_ICON_LENGTH: usize = 50;
+const MAX_DESCRIPTION_LENGTH: usize = 500;
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
 pub struct Category {
-    #[serde(skip)]
+    #[serde(rename(deserialize = "_id"), serialize_with = "super::serialize_object_id")]
+    id: Option<ObjectId>,
+    name: Option<String>,
+    /// Category this one is nested in, `None` for top level categories.
+    #[serde(rename(deserialize = "parent"), default, serialize_with = "super::serialize_object_id")]
+    parent_id: Option<ObjectId>,
+    /// Name of the icon or an emoji the frontend shows along with the name.
+    icon: Option<String>,
+    /// Hex color like `#1e90ff`.
+    color: Option<String>,
+    description: Option<String>,
+    /// Position among its siblings, lower first. Ties are ordered by name.
+    #[serde(default)]
+    sort_order: i32,
+}
+
+/// Replaces a category, unset optional fields are removed.
+#[derive(Deserialize, Debug)]
+pub struct CategoryInput {
+    name: String,
+    parent_id: Option<String>,
+    icon: Option<String>,
+    color: Option<String>,
+    description: Option<String>,
+    #[serde(default)]
+    sort_order: i32,
+}
+
+/// Category as listed, with the numbers of its products if they were asked for.
+#[derive(Serialize, Clone, Debug)]
+pub struct CategoryListing {
+    #[serde(flatten)]
+    category: Category,
+    #[serde(flatten)]
+    counts: Option<CategoryCount>,
+}
+
+/// Numbers of current and archived products of a category, trashed ones
+/// left out. Uncategorized products are counted with a `None` category.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct CategoryCount {
+    #[serde(rename(deserialize = "_id"), skip_serializing)]
+    category: Option<ObjectId>,
+    current_count: u64,
+    archived_count: u64,
+}
+
+/// Outcome of merging the category `from` into `into`.
+#[derive(Serialize, Clone, Debug)]
+pub struct CategoryMerge {
+    from: Category,
+    into: Category,
+    /// Products moved to `into`, trashed ones included.
+    moved: u64,
+}
+
+/// Category with its subcategories, as served by the category tree.
+#[derive(Serialize, Clone, Debug)]
+pub struct CategoryNode {
+    #[serde(serialize_with = "super::serialize_object_id")]
     id: Option<ObjectId>,
     name: Option<String>,
+    icon: Option<String>,
+    color: Option<String>,
+    children: Vec<CategoryNode>,
 }
 
 impl Category {
     pub fn get_id(&self) -> Option<&ObjectId> {
         self.id.as_ref()
     }
+    pub fn get_name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+    pub fn get_parent_id(&self) -> Option<&ObjectId> {
+        self.parent_id.as_ref()
+    }
+    pub fn get_icon(&self) -> Option<&str> {
+        self.icon.as_deref()
+    }
+    pub fn get_color(&self) -> Option<&str> {
+        self.color.as_deref()
+    }
+    pub fn get_description(&self) -> Option<&str> {
+        self.description.as_deref()
+    }
+    pub fn get_sort_order(&self) -> i32 {
+        self.sort_order
+    }
+
+    /// Returns `root` followed by the ids of all categories nested in it at any depth.
+    pub fn collect_descendants(categories: &[Category], root: &ObjectId) -> Vec<ObjectId> {
+        let mut ids = vec![root.clone()];
+        let mut seen: BTreeSet<ObjectId> = ids.iter().cloned().collect();
+        let mut i = 0;
+        while i < ids.len() {
+            let parent = ids[i].clone();
+            for child in categories.iter().filter(|c| c.get_parent_id() == Some(&parent)) {
+                if let Some(id) = child.get_id() {
+                    if seen.insert(id.clone()) {
+                        ids.push(id.clone());
+                    }
+                }
+            }
+            i += 1;
+        }
+        ids
+    }
+
+    /// Checks if `id` is `category` or one of the categories it is nested in.
+    pub fn is_ancestor(categories: &[Category], id: &ObjectId, category: &ObjectId) -> bool {
+        let mut seen = BTreeSet::new();
+        let mut current = Some(category.clone());
+        while let Some(category) = current {
+            if &category == id {
+                return true;
+            }
+            if !seen.insert(category.clone()) {
+                return false;
+            }
+            current = categories.iter()
+                .find(|c| c.get_id() == Some(&category))
+                .and_then(|c| c.get_parent_id().cloned());
+        }
+        false
+    }
 }
 
-impl Default for Category {
-    fn default() -> Self {
-        Self {
-            id: None,
-            name: None,
+impl CategoryListing {
+    /// Lists `category` with its counts among `counts`, if given. Categories
+    /// missing from `counts` have no products.
+    pub fn new(category: Category, counts: Option<&[CategoryCount]>) -> Self {
+        let counts = counts.map(|counts| {
+            counts.iter()
+                .find(|c| c.category.is_some() && c.category.as_ref() == category.get_id())
+                .cloned()
+                .unwrap_or_default()
+        });
+        Self { category, counts }
+    }
+}
+
+impl CategoryMerge {
+    pub fn new(from: Category, into: Category, moved: u64) -> Self {
+        Self { from, into, moved }
+    }
+}
+
+impl CategoryNode {
+    /// Nests `categories` under their parents. Categories whose parent no longer
+    /// exists are shown at the top level, the order of `categories` is kept.
+    pub fn build_tree(categories: &[Category]) -> Vec<CategoryNode> {
+        let ids: BTreeSet<&ObjectId> = categories.iter().filter_map(|c| c.get_id()).collect();
+        let roots: Vec<&Category> = categories.iter()
+            .filter(|c| c.get_parent_id().map(|p| !ids.contains(p)).unwrap_or(true))
+            .collect();
+        let mut visited = BTreeSet::new();
+        roots.into_iter().map(|c| Self::build_node(categories, c, &mut visited)).collect()
+    }
+
+    fn build_node(categories: &[Category], category: &Category, visited: &mut BTreeSet<ObjectId>) -> CategoryNode {
+        let mut children = Vec::new();
+        if let Some(id) = category.get_id() {
+            if visited.insert(id.clone()) {
+                children = categories.iter()
+                    .filter(|c| c.get_parent_id() == Some(id))
+                    .map(|c| Self::build_node(categories, c, visited))
+                    .collect();
+            }
+        }
+        CategoryNode {
+            id: category.id.clone(),
+            name: category.name.clone(),
+            icon: category.icon.clone(),
+            color: category.color.clone(),
+            children,
         }
     }
 }
 
-impl From<&Document> for Category {
-    fn from(doc: &Document) -> Self {
+impl CategoryInput {
+    pub fn new(name: String) -> Self {
         Self {
-            id: doc.get_object_id("_id").map(|id| id.clone()).ok(),
-            name: doc.get_str("name").map(String::from).ok(),
+            name,
+            parent_id: None,
+            icon: None,
+            color: None,
+            description: None,
+            sort_order: 0,
         }
     }
+
+    pub fn get_name(&self) -> &str {
+        self.name.trim()
+    }
+
+    pub fn get_parent_id(&self) -> Result<Option<ObjectId>> {
+        self.parent_id.as_deref().map(|id| parse_object_id("parent_id", id)).transpose()
+    }
+
+    /// Validates the input and converts it into the document stored in the `category` collection.
+    pub fn to_document(&self) -> Result<Document> {
+        let name = self.get_name();
+        if name.is_empty() || name.len() > MAX_NAME_LENGTH {
+            return Err(Error::Validation(format!(
+                "name must be between 1 and {} characters",
+                MAX_NAME_LENGTH
+            )));
+        }
+        let parent = match self.get_parent_id()? {
+            Some(id) => Bson::ObjectId(id),
+            None => Bson::Null,
+        };
+        let icon = normalize_text("icon", self.icon.as_deref(), MAX_ICON_LENGTH)?;
+        let description = normalize_text("description", self.description.as_deref(), MAX_DESCRIPTION_LENGTH)?;
+        let color = match self.color.as_deref().map(str::trim).filter(|color| !color.is_empty()) {
+            Some(color) => Bson::String(normalize_color(color)?),
+            None => Bson::Null,
+        };
+        Ok(doc! {
+            "name": name,
+            "parent": parent,
+            "icon": icon,
+            "color": color,
+            "description": description,
+            "sort_order": self.sort_order,
+        })
+    }
 }
 
-impl From<Document> for Category {
-    fn from(doc: Document) -> Self {
-        Self::from(&doc)
+/// Trims `text`, empty text is stored as `null`.
+fn normalize_text(field: &str, text: Option<&str>, max_length: usize) -> Result<Bson> {
+    let text = text.map(str::trim).unwrap_or_default();
+    if text.chars().count() > max_length {
+        return Err(Error::Validation(format!("{} must be at most {} characters", field, max_length)));
     }
+    Ok(if text.is_empty() { Bson::Null } else { Bson::from(text) })
+}
+
+/// Accepts `#rgb` and `#rrggbb`, returned as lowercase `#rrggbb`.
+fn normalize_color(color: &str) -> Result<String> {
+    let hex = color.strip_prefix('#').unwrap_or_default();
+    if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(Error::Validation("color must be a hex color like #1e90ff".to_string()));
+    }
+    match hex.len() {
+        3 => Ok(format!("#{}", hex.chars().flat_map(|c| [c, c]).collect::<String>().to_ascii_lowercase())),
+        6 => Ok(format!("#{}", hex.to_ascii_lowercase())),
+        _ => Err(Error::Validation("color must be a hex color like #1e90ff".to_string())),
+    }
+}
+
+impl Stored for Category {
+    const COLLECTION: &'static str = "category";
+}
+
+/// Read from the products grouped by their categories.
+impl Stored for CategoryCount {
+    const COLLECTION: &'static str = Product::COLLECTION;
 }
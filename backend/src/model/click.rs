@@ -0,0 +1,26 @@
+use mongodb::bson::oid::ObjectId;
+use serde::{Deserialize, Serialize};
+
+use super::{Product, Stored};
+
+/// Number of times the shop link of a product was followed.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ClickCount {
+    #[serde(rename(deserialize = "_id"), serialize_with = "super::serialize_object_id")]
+    id: Option<ObjectId>,
+    name: Option<String>,
+    url: Option<String>,
+    #[serde(default)]
+    clicks: i64,
+}
+
+impl ClickCount {
+    pub fn get_clicks(&self) -> i64 {
+        self.clicks
+    }
+}
+
+/// Read from the products, which keep their click counter.
+impl Stored for ClickCount {
+    const COLLECTION: &'static str = Product::COLLECTION;
+}
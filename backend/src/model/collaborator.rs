@@ -0,0 +1,64 @@
+use mongodb::bson::{doc, document::Document, oid::ObjectId};
+use serde::{Deserialize, Serialize};
+
+/// User the owner of a list invited to help with it, stored along with the list.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Collaborator {
+    #[serde(serialize_with = "super::serialize_object_id_hex")]
+    user: ObjectId,
+    username: String,
+    role: CollaboratorRole,
+    added: i32,
+}
+
+/// What a collaborator may do on the list.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum CollaboratorRole {
+    /// Reading the list, even if it is private.
+    #[default]
+    Viewer,
+    /// Adding, changing and removing products, like the owner. Only the
+    /// owner may change the list itself, share it or invite others.
+    Editor,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct CollaboratorInput {
+    #[serde(default)]
+    role: CollaboratorRole,
+}
+
+impl Collaborator {
+    pub fn get_user(&self) -> &ObjectId {
+        &self.user
+    }
+    pub fn get_username(&self) -> &str {
+        &self.username
+    }
+    pub fn get_role(&self) -> CollaboratorRole {
+        self.role
+    }
+}
+
+impl CollaboratorRole {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CollaboratorRole::Viewer => "viewer",
+            CollaboratorRole::Editor => "editor",
+        }
+    }
+}
+
+impl CollaboratorInput {
+    /// Converts the input into the collaborator stored in the `collaborators`
+    /// array of a list.
+    pub fn to_document(&self, user: &ObjectId, username: &str, timestamp: i32) -> Document {
+        doc! {
+            "user": user.clone(),
+            "username": username,
+            "role": self.role.as_str(),
+            "added": timestamp,
+        }
+    }
+}
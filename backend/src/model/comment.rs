@@ -0,0 +1,102 @@
+use mongodb::bson::{doc, document::Document, oid::ObjectId, Bson};
+use serde::{Deserialize, Serialize};
+
+use super::Stored;
+use crate::{Error, Result};
+
+const MAX_TEXT_LENGTH: usize = 1000;
+const MAX_NAME_LENGTH: usize = 100;
+/// Links a comment may contain, comments full of links are mostly spam.
+const MAX_LINKS: usize = 2;
+
+/// Short comment on a product, e.g. a question about the size or a hint at
+/// a better offer. The token is only handed out once to whoever wrote it and
+/// is needed to delete it without a session.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Comment {
+    #[serde(rename(deserialize = "_id"), serialize_with = "super::serialize_object_id")]
+    id: Option<ObjectId>,
+    #[serde(serialize_with = "super::serialize_object_id_hex")]
+    product: ObjectId,
+    /// User who wrote the comment, `None` for visitors without a session.
+    #[serde(default, serialize_with = "super::serialize_object_id")]
+    author: Option<ObjectId>,
+    name: Option<String>,
+    text: String,
+    #[serde(default, skip_serializing)]
+    token: String,
+    timestamp: i32,
+}
+
+/// Comment as returned to whoever wrote it, along with the token to delete it.
+#[derive(Serialize, Debug)]
+pub struct CommentReceipt {
+    #[serde(flatten)]
+    comment: Comment,
+    token: String,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct CommentInput {
+    text: String,
+    name: Option<String>,
+    /// Honeypot left empty by people, as forms hide it, but filled in by
+    /// bots filling in every field.
+    #[serde(default)]
+    website: Option<String>,
+}
+
+impl Comment {
+    pub fn get_id(&self) -> Option<&ObjectId> {
+        self.id.as_ref()
+    }
+    pub fn get_product_id(&self) -> &ObjectId {
+        &self.product
+    }
+    pub fn get_author(&self) -> Option<&ObjectId> {
+        self.author.as_ref()
+    }
+    pub fn get_token(&self) -> &str {
+        &self.token
+    }
+}
+
+impl CommentReceipt {
+    pub fn new(comment: Comment) -> Self {
+        let token = comment.token.clone();
+        Self { comment, token }
+    }
+}
+
+impl CommentInput {
+    /// Validates the input and converts it into the document stored in the
+    /// `comment` collection, written by `author` if given.
+    pub fn to_document(&self, product: &ObjectId, author: Option<&ObjectId>, token: &str, timestamp: i32) -> Result<Document> {
+        if self.website.as_deref().map(|website| !website.trim().is_empty()).unwrap_or(false) {
+            return Err(Error::Validation("the comment looks like spam".to_string()));
+        }
+        let text = self.text.trim();
+        if text.is_empty() || text.chars().count() > MAX_TEXT_LENGTH {
+            return Err(Error::Validation(format!("text must be between 1 and {} characters", MAX_TEXT_LENGTH)));
+        }
+        if text.matches("://").count() > MAX_LINKS {
+            return Err(Error::Validation(format!("text must contain at most {} links", MAX_LINKS)));
+        }
+        let name = self.name.as_deref().map(str::trim).filter(|name| !name.is_empty());
+        if name.map(|name| name.chars().count() > MAX_NAME_LENGTH).unwrap_or(false) {
+            return Err(Error::Validation(format!("name must be at most {} characters", MAX_NAME_LENGTH)));
+        }
+        Ok(doc! {
+            "product": product.clone(),
+            "author": author.cloned().map(Bson::from).unwrap_or(Bson::Null),
+            "name": name.map(Bson::from).unwrap_or(Bson::Null),
+            "text": text,
+            "token": token,
+            "timestamp": timestamp,
+        })
+    }
+}
+
+impl Stored for Comment {
+    const COLLECTION: &'static str = "comment";
+}
@@ -0,0 +1,86 @@
+use mongodb::bson::oid::ObjectId;
+use serde::Serialize;
+
+/// Stored documents referencing documents that don't exist, and items stored
+/// twice on a list, as found by the consistency check.
+#[derive(Serialize, Debug, Default)]
+pub struct ConsistencyReport {
+    products_missing_source: Vec<Orphan>,
+    products_missing_category: Vec<Orphan>,
+    snapshots_missing_products: Vec<Orphan>,
+    duplicate_item_ids: Vec<DuplicateItem>,
+}
+
+/// Product or snapshot referencing missing documents.
+#[derive(Serialize, Debug)]
+pub struct Orphan {
+    #[serde(serialize_with = "super::serialize_object_id_hex")]
+    id: ObjectId,
+    list: String,
+    /// Ids of the missing documents, empty if the reference itself is missing.
+    #[serde(serialize_with = "super::serialize_object_ids_hex")]
+    missing: Vec<ObjectId>,
+    /// Whether the reference was repaired.
+    fixed: bool,
+}
+
+/// Products of a list sharing an item id.
+#[derive(Serialize, Debug)]
+pub struct DuplicateItem {
+    list: String,
+    item_id: String,
+    #[serde(serialize_with = "super::serialize_object_ids_hex")]
+    products: Vec<ObjectId>,
+}
+
+impl ConsistencyReport {
+    pub fn add_missing_source(&mut self, orphan: Orphan) {
+        self.products_missing_source.push(orphan);
+    }
+    pub fn add_missing_category(&mut self, orphan: Orphan) {
+        self.products_missing_category.push(orphan);
+    }
+    pub fn add_missing_products(&mut self, orphan: Orphan) {
+        self.snapshots_missing_products.push(orphan);
+    }
+    pub fn add_duplicate(&mut self, duplicate: DuplicateItem) {
+        self.duplicate_item_ids.push(duplicate);
+    }
+
+    /// Number of problems found, fixed or not.
+    pub fn get_problem_count(&self) -> usize {
+        self.products_missing_source.len()
+            + self.products_missing_category.len()
+            + self.snapshots_missing_products.len()
+            + self.duplicate_item_ids.len()
+    }
+
+    pub fn get_fixed_count(&self) -> usize {
+        self.products_missing_source.iter()
+            .chain(self.products_missing_category.iter())
+            .chain(self.snapshots_missing_products.iter())
+            .filter(|orphan| orphan.fixed)
+            .count()
+    }
+}
+
+impl Orphan {
+    pub fn new(id: ObjectId, list: &str, missing: Vec<ObjectId>, fixed: bool) -> Self {
+        Self {
+            id,
+            list: list.to_string(),
+            missing,
+            fixed,
+        }
+    }
+}
+
+impl DuplicateItem {
+    pub fn new(list: &str, item_id: &str, products: Vec<ObjectId>) -> Self {
+        Self {
+            list: list.to_string(),
+            item_id: item_id.to_string(),
+            products,
+        }
+    }
+}
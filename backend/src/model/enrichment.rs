@@ -0,0 +1,99 @@
+use mongodb::bson::{doc, document::Document, Bson};
+use serde::{Deserialize, Serialize};
+
+/// Details of a book or another item looked up by its ISBN or EAN, so they
+/// don't have to be entered by hand.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Enrichment {
+    /// Where the details are from, `openlibrary` or `ean`.
+    provider: String,
+    title: Option<String>,
+    #[serde(default)]
+    authors: Vec<String>,
+    /// Publisher of a book, or brand of another item.
+    publisher: Option<String>,
+    /// Date of publication as the provider gives it, like `June 2007`.
+    published: Option<String>,
+    pages: Option<u32>,
+    description: Option<String>,
+    /// URL of the cover or product image.
+    cover: Option<String>,
+}
+
+impl Enrichment {
+    pub fn new(provider: &str, title: Option<String>) -> Self {
+        Self {
+            provider: provider.to_string(),
+            title,
+            authors: Vec::new(),
+            publisher: None,
+            published: None,
+            pages: None,
+            description: None,
+            cover: None,
+        }
+    }
+    pub fn with_authors(mut self, authors: Vec<String>) -> Self {
+        self.authors = authors;
+        self
+    }
+    pub fn with_publisher(mut self, publisher: Option<String>) -> Self {
+        self.publisher = publisher;
+        self
+    }
+    pub fn with_published(mut self, published: Option<String>) -> Self {
+        self.published = published;
+        self
+    }
+    pub fn with_pages(mut self, pages: Option<u32>) -> Self {
+        self.pages = pages;
+        self
+    }
+    pub fn with_description(mut self, description: Option<String>) -> Self {
+        self.description = description;
+        self
+    }
+    pub fn with_cover(mut self, cover: Option<String>) -> Self {
+        self.cover = cover;
+        self
+    }
+
+    pub fn get_provider(&self) -> &str {
+        &self.provider
+    }
+    pub fn get_title(&self) -> Option<&str> {
+        self.title.as_deref()
+    }
+    pub fn get_authors(&self) -> &[String] {
+        &self.authors
+    }
+    pub fn get_publisher(&self) -> Option<&str> {
+        self.publisher.as_deref()
+    }
+    pub fn get_published(&self) -> Option<&str> {
+        self.published.as_deref()
+    }
+    pub fn get_pages(&self) -> Option<u32> {
+        self.pages
+    }
+    pub fn get_description(&self) -> Option<&str> {
+        self.description.as_deref()
+    }
+    pub fn get_cover(&self) -> Option<&str> {
+        self.cover.as_deref()
+    }
+
+    pub fn to_document(&self) -> Document {
+        let optional = |value: &Option<String>| value.clone().map(Bson::from).unwrap_or(Bson::Null);
+        doc! {
+            "provider": self.provider.as_str(),
+            "title": optional(&self.title),
+            "authors": self.authors.clone(),
+            "publisher": optional(&self.publisher),
+            "published": optional(&self.published),
+            "pages": self.pages.map(|pages| Bson::Int64(pages.into())).unwrap_or(Bson::Null),
+            "description": optional(&self.description),
+            "cover": optional(&self.cover),
+        }
+    }
+}
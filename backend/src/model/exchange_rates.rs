@@ -0,0 +1,57 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+
+use super::{Currency, Money, Stored};
+use crate::{Error, Result};
+
+/// Rates are stored in millionths, e.g. `1084500` for 1.0845.
+pub const RATE_SCALE: i64 = 1_000_000;
+
+/// Euro reference rates published by the ECB, kept as a single document
+/// replaced on every refresh.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ExchangeRates {
+    /// Day the rates were published for, e.g. `2024-05-10`.
+    date: String,
+    /// Unix timestamp of when the rates were fetched.
+    fetched: i32,
+    /// Units of each currency one euro buys, in millionths, by currency code.
+    rates: BTreeMap<String, i64>,
+}
+
+impl ExchangeRates {
+    pub fn get_date(&self) -> &str {
+        &self.date
+    }
+
+    /// Units of `currency` one euro buys, in millionths.
+    pub fn get_rate(&self, currency: Currency) -> Option<i64> {
+        match currency {
+            Currency::Eur => Some(RATE_SCALE),
+            other => self.rates.get(other.get_code()).copied().filter(|rate| *rate > 0),
+        }
+    }
+
+    /// Converts `money` into `to`, rounded half away from zero to the minor unit of `to`.
+    pub fn convert(&self, money: Money, to: Currency) -> Result<Money> {
+        let from = money.get_currency();
+        if from == to {
+            return Ok(money);
+        }
+        let missing = |currency: Currency| Error::Unavailable(format!("no exchange rate for {} is available", currency));
+        let from_rate = self.get_rate(from).ok_or_else(|| missing(from))?;
+        let to_rate = self.get_rate(to).ok_or_else(|| missing(to))?;
+
+        let numerator = i128::from(money.get_amount_minor()) * i128::from(to_rate) * 10i128.pow(to.get_minor_digits());
+        let denominator = i128::from(from_rate) * 10i128.pow(from.get_minor_digits());
+        let amount = (numerator.abs() * 2 + denominator) / (denominator * 2) * numerator.signum();
+        i64::try_from(amount)
+            .map(|amount| Money::new(amount, to))
+            .map_err(|_| Error::Validation(format!("{} is too large to be converted to {}", money, to)))
+    }
+}
+
+impl Stored for ExchangeRates {
+    const COLLECTION: &'static str = "exchange_rates";
+}
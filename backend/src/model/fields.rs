@@ -0,0 +1,91 @@
+use mongodb::bson::{doc, document::Document};
+use serde_json::Value;
+use std::collections::BTreeSet;
+
+use crate::{Error, Result};
+
+/// Fields of a product, as serialized, that can be selected. Nested fields
+/// are joined with a dot.
+const PRODUCT_FIELDS: &[&str] = &[
+    "id", "name", "description", "notes", "price", "current_price", "lowest_price", "at_all_time_low", "price_threshold", "discount_percent", "currency",
+    "quantity", "priority", "rating", "stars", "url", "url_img", "tags", "ean", "enrichment", "duplicate_of", "first_seen", "last_seen",
+    "updated_at", "source", "source.name", "source.url", "offers", "reserved", "funded_percent", "availability", "buyable", "list", "link_dead", "deleted_at",
+];
+
+/// Fields of the products a listing returns, as requested with `?fields=`.
+/// The id is always returned.
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct FieldSelection {
+    fields: BTreeSet<String>,
+}
+
+impl FieldSelection {
+    /// Parses comma-separated field names like `name,price,source.name`.
+    pub fn parse(fields: &str) -> Result<Self> {
+        let mut selected = BTreeSet::new();
+        for field in fields.split(',').map(str::trim).filter(|field| !field.is_empty()) {
+            if !PRODUCT_FIELDS.contains(&field) {
+                return Err(Error::Validation(format!(
+                    "unknown field '{}', expected one of {}",
+                    field,
+                    PRODUCT_FIELDS.join(", ")
+                )));
+            }
+            selected.insert(field.to_string());
+        }
+        if selected.is_empty() {
+            return Err(Error::Validation("fields must name at least one field".to_string()));
+        }
+        Ok(Self { fields: selected })
+    }
+
+    /// Projection loading the selected fields of the product documents, along
+    /// with the references needed to load the details, the currency to convert
+    /// the prices and the `price` the current and lowest price fall back to.
+    /// The offers need the fields the offer of the product's own source is made of.
+    pub fn to_projection(&self) -> Document {
+        let mut projection = doc! {"_id": 1, "source": 1, "list": 1, "currency": 1};
+        for field in self.fields.iter().map(|field| field.split('.').next().unwrap_or_default()) {
+            match field {
+                "id" | "source" | "reserved" | "funded_percent" => {}
+                "current_price" | "lowest_price" => {
+                    projection.insert(field, 1);
+                    projection.insert("price", 1);
+                }
+                "buyable" => {
+                    projection.insert("availability", 1);
+                }
+                "offers" => {
+                    for offer_field in ["offers", "url", "current_price", "price", "availability"].iter() {
+                        projection.insert(*offer_field, 1);
+                    }
+                }
+                field => {
+                    projection.insert(field, 1);
+                }
+            }
+        }
+        projection
+    }
+
+    /// Removes the fields that weren't selected from a serialized product.
+    pub fn apply(&self, product: &mut Value) {
+        let object = match product {
+            Value::Object(object) => object,
+            _ => return,
+        };
+        object.retain(|key, _| key == "id" || self.selects(key));
+        for (key, value) in object.iter_mut() {
+            if let Value::Object(nested) = value {
+                if !self.fields.contains(key) {
+                    nested.retain(|nested_key, _| self.fields.contains(&format!("{}.{}", key, nested_key)));
+                }
+            }
+        }
+    }
+
+    /// Whether `key` or any field nested in it is selected.
+    fn selects(&self, key: &str) -> bool {
+        self.fields.iter().any(|field| field == key || field.strip_prefix(key).map(|rest| rest.starts_with('.')).unwrap_or(false))
+    }
+}
@@ -0,0 +1,12 @@
+use serde::Serialize;
+
+#[derive(Serialize, Clone, Debug)]
+pub struct Health {
+    status: &'static str,
+}
+
+impl Health {
+    pub fn new(status: &'static str) -> Self {
+        Self { status }
+    }
+}
@@ -0,0 +1,161 @@
+use mongodb::bson::oid::ObjectId;
+use serde::{Deserialize, Deserializer, Serialize};
+use std::convert::TryFrom;
+
+use super::{Currency, Money};
+use crate::{Error, Result};
+
+/// Product to import, as a JSON object or a CSV line. `price` is a decimal
+/// amount like `12.99` in `currency`, so a CSV export can be imported again.
+#[derive(Deserialize, Debug, Default)]
+pub struct ImportRow {
+    name: Option<String>,
+    url: Option<String>,
+    #[serde(default, deserialize_with = "deserialize_amount")]
+    price: Option<String>,
+    currency: Option<Currency>,
+    source: Option<String>,
+    category: Option<String>,
+    item_id: Option<String>,
+}
+
+/// Outcome of an import, with one result per row in input order.
+#[derive(Serialize, Debug, Default)]
+pub struct ImportReport {
+    created: u64,
+    duplicates: u64,
+    failed: u64,
+    rows: Vec<ImportRowResult>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ImportRowResult {
+    /// Position of the row in the input, starting at 1 after the CSV header.
+    row: usize,
+    status: ImportStatus,
+    /// Product created, or the existing product for duplicates.
+    #[serde(serialize_with = "super::serialize_object_id")]
+    id: Option<ObjectId>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    message: Option<String>,
+}
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ImportStatus {
+    Created,
+    Duplicate,
+    Failed,
+}
+
+impl ImportRow {
+    /// Builds a row from the CSV columns named in `header`, matched
+    /// case-insensitively. Unknown columns are ignored.
+    pub fn from_fields(header: &[String], fields: &[String]) -> Result<Self> {
+        let mut row = Self::default();
+        for (column, value) in header.iter().zip(fields) {
+            let value = value.trim();
+            if value.is_empty() {
+                continue;
+            }
+            let text = unescape_formula(value);
+            let value = Some(text.to_string());
+            match column.trim().to_lowercase().as_str() {
+                "name" => row.name = value,
+                "url" => row.url = value,
+                "price" => row.price = value,
+                "currency" => {
+                    let code = text.to_uppercase();
+                    row.currency = Some(Currency::from_code(&code)
+                        .ok_or_else(|| Error::Validation(format!("unknown currency '{}'", code)))?);
+                }
+                "source" => row.source = value,
+                "category" => row.category = value,
+                "item_id" => row.item_id = value,
+                _ => {}
+            }
+        }
+        Ok(row)
+    }
+
+    pub fn get_name(&self) -> Result<&str> {
+        self.name.as_deref().map(str::trim).filter(|n| !n.is_empty())
+            .ok_or_else(|| Error::Validation("name is missing".to_string()))
+    }
+    pub fn get_url(&self) -> Result<&str> {
+        self.url.as_deref().map(str::trim).filter(|u| !u.is_empty())
+            .ok_or_else(|| Error::Validation("url is missing".to_string()))
+    }
+    pub fn get_currency(&self) -> Option<Currency> {
+        self.currency
+    }
+    pub fn get_source(&self) -> Option<&str> {
+        self.source.as_deref().map(str::trim).filter(|s| !s.is_empty())
+    }
+    pub fn get_category(&self) -> Option<&str> {
+        self.category.as_deref().map(str::trim).filter(|c| !c.is_empty())
+    }
+    pub fn get_item_id(&self) -> Option<&str> {
+        self.item_id.as_deref().map(str::trim).filter(|i| !i.is_empty())
+    }
+
+    /// Parses the price into the minor unit of the currency prices are stored in.
+    pub fn get_price(&self) -> Result<i32> {
+        let price = self.price.as_deref().ok_or_else(|| Error::Validation("price is missing".to_string()))?;
+        Money::parse_amount(price, self.currency.unwrap_or_default())
+            .filter(|m| m.get_amount_minor() >= 0)
+            .and_then(|m| i32::try_from(m.get_amount_minor()).ok())
+            .ok_or_else(|| Error::Validation(format!("price '{}' is not an amount like 12.99", price)))
+    }
+}
+
+impl ImportReport {
+    pub fn get_created(&self) -> u64 {
+        self.created
+    }
+
+    pub fn get_duplicates(&self) -> u64 {
+        self.duplicates
+    }
+
+    pub fn get_failed(&self) -> u64 {
+        self.failed
+    }
+
+    pub fn add(&mut self, status: ImportStatus, id: Option<ObjectId>, message: Option<String>) {
+        match status {
+            ImportStatus::Created => self.created += 1,
+            ImportStatus::Duplicate => self.duplicates += 1,
+            ImportStatus::Failed => self.failed += 1,
+        }
+        self.rows.push(ImportRowResult {
+            row: self.rows.len() + 1,
+            status,
+            id,
+            message,
+        });
+    }
+}
+
+/// Accepts prices as JSON numbers as well as strings.
+pub(super) fn deserialize_amount<'de, D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Option<String>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Amount {
+        Text(String),
+        Number(serde_json::Number),
+    }
+    Ok(Option::<Amount>::deserialize(deserializer)?.map(|amount| match amount {
+        Amount::Text(text) => text,
+        Amount::Number(number) => number.to_string(),
+    }))
+}
+
+/// Drops the `'` the CSV export puts in front of texts a spreadsheet would
+/// take for a formula.
+fn unescape_formula(value: &str) -> &str {
+    match value.strip_prefix('\'') {
+        Some(rest) if rest.starts_with(['=', '+', '-', '@']) => rest,
+        _ => value,
+    }
+}
@@ -0,0 +1,48 @@
+use serde::Deserialize;
+
+use super::import::deserialize_amount;
+use super::Currency;
+
+/// Product sent by the bookmarklet or browser extension from the page of a
+/// shop. Fields it couldn't read are taken from the metadata of the page.
+#[derive(Deserialize, Debug)]
+pub struct IngestInput {
+    url: String,
+    title: Option<String>,
+    /// Price as the page shows it, like `1.299,00 €` or `12.99`.
+    #[serde(default, deserialize_with = "deserialize_amount")]
+    price: Option<String>,
+    currency: Option<Currency>,
+    image: Option<String>,
+    list: Option<String>,
+}
+
+impl IngestInput {
+    pub fn get_url(&self) -> &str {
+        self.url.trim()
+    }
+    pub fn get_title(&self) -> Option<&str> {
+        non_empty(&self.title)
+    }
+    pub fn get_price(&self) -> Option<&str> {
+        non_empty(&self.price)
+    }
+    pub fn get_currency(&self) -> Option<Currency> {
+        self.currency
+    }
+    pub fn get_image(&self) -> Option<&str> {
+        non_empty(&self.image)
+    }
+    pub fn get_list(&self) -> Option<&str> {
+        non_empty(&self.list)
+    }
+
+    /// Whether the page has to be read for the fields the input lacks.
+    pub fn is_complete(&self) -> bool {
+        self.get_title().is_some() && self.get_price().is_some() && self.get_image().is_some()
+    }
+}
+
+fn non_empty(value: &Option<String>) -> Option<&str> {
+    value.as_deref().map(str::trim).filter(|v| !v.is_empty())
+}
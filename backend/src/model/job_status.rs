@@ -0,0 +1,43 @@
+use serde::Serialize;
+use std::time::Duration;
+
+use crate::Result;
+
+#[derive(Serialize, Clone, Debug)]
+pub struct JobStatus {
+    name: String,
+    schedule: String,
+    next_run: Option<i32>,
+    last_run: Option<i32>,
+    last_duration_ms: Option<u64>,
+    last_success: Option<bool>,
+    last_message: Option<String>,
+}
+
+impl JobStatus {
+    pub fn new(name: &str, schedule: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            schedule: schedule.to_string(),
+            next_run: None,
+            last_run: None,
+            last_duration_ms: None,
+            last_success: None,
+            last_message: None,
+        }
+    }
+
+    pub fn set_next_run(&mut self, timestamp: i32) {
+        self.next_run = Some(timestamp);
+    }
+
+    pub fn record_run(&mut self, timestamp: i32, duration: Duration, result: &Result<String>) {
+        self.last_run = Some(timestamp);
+        self.last_duration_ms = Some(duration.as_millis() as u64);
+        self.last_success = Some(result.is_ok());
+        self.last_message = Some(match result {
+            Ok(summary) => summary.clone(),
+            Err(e) => e.to_string(),
+        });
+    }
+}
@@ -0,0 +1,184 @@
+use mongodb::bson::{doc, document::Document, oid::ObjectId};
+use serde::{Deserialize, Serialize};
+
+use super::{Collaborator, CollaboratorRole, Stored};
+use crate::{Error, Result};
+
+/// Slug of the list that snapshots and products without a `list` field belong to.
+pub const DEFAULT_LIST: &str = "default";
+const MAX_SLUG_LENGTH: usize = 32;
+const MAX_NAME_LENGTH: usize = 100;
+
+/// Named wishlist, e.g. `birthday` or `christmas`, whose snapshots and
+/// products are kept apart from the other lists.
+///
+/// Only the owner may change a list, lists without an owner (like the default
+/// list) are managed with the API key. Collaborators invited by the owner may
+/// read it or, as editors, change its products.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct List {
+    slug: String,
+    name: Option<String>,
+    #[serde(serialize_with = "super::serialize_object_id")]
+    owner: Option<ObjectId>,
+    #[serde(default)]
+    visibility: Visibility,
+    /// Whether the reservations are hidden from the owner, who would
+    /// otherwise learn which gifts to expect.
+    #[serde(default)]
+    secret_santa: bool,
+    /// Left out of responses, so that the users helping with a list aren't
+    /// shown to everybody who can read it.
+    #[serde(default, skip_serializing)]
+    collaborators: Vec<Collaborator>,
+}
+
+/// Who can read a list: anybody, anybody who knows its slug, as it isn't
+/// listed or found by searches, or only the owner and holders of a share token.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Visibility {
+    #[default]
+    Public,
+    Unlisted,
+    Private,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ListInput {
+    slug: String,
+    name: String,
+    #[serde(default)]
+    visibility: Visibility,
+    #[serde(default)]
+    secret_santa: bool,
+}
+
+/// Changes of a list, fields left out are kept. The slug can't be changed as
+/// the products and snapshots of the list refer to it.
+#[derive(Deserialize, Debug)]
+pub struct ListUpdateInput {
+    name: Option<String>,
+    visibility: Option<Visibility>,
+    secret_santa: Option<bool>,
+}
+
+impl List {
+    /// The default list, which exists without being stored.
+    pub fn default_list() -> Self {
+        Self {
+            slug: DEFAULT_LIST.to_string(),
+            name: None,
+            owner: None,
+            visibility: Visibility::Public,
+            secret_santa: false,
+            collaborators: Vec::new(),
+        }
+    }
+    pub fn get_slug(&self) -> &str {
+        &self.slug
+    }
+    pub fn get_name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+    pub fn get_owner(&self) -> Option<&ObjectId> {
+        self.owner.as_ref()
+    }
+    pub fn get_visibility(&self) -> Visibility {
+        self.visibility
+    }
+    pub fn is_secret_santa(&self) -> bool {
+        self.secret_santa
+    }
+    pub fn get_collaborators(&self) -> &[Collaborator] {
+        &self.collaborators
+    }
+
+    /// Role of `user` on the list, `None` unless it was invited.
+    pub fn get_role(&self, user: &ObjectId) -> Option<CollaboratorRole> {
+        self.collaborators.iter()
+            .find(|collaborator| collaborator.get_user() == user)
+            .map(Collaborator::get_role)
+    }
+}
+
+impl Visibility {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Visibility::Public => "public",
+            Visibility::Unlisted => "unlisted",
+            Visibility::Private => "private",
+        }
+    }
+}
+
+impl ListInput {
+    pub fn get_slug(&self) -> &str {
+        &self.slug
+    }
+
+    /// Validates the input and converts it into the document stored in the
+    /// `list` collection, owned by `owner` if given.
+    pub fn to_document(&self, owner: Option<&ObjectId>) -> Result<Document> {
+        validate_slug(&self.slug)?;
+        let name = validate_name(&self.name)?;
+        let mut doc = doc! {
+            "slug": self.slug.as_str(),
+            "name": name,
+            "visibility": self.visibility.as_str(),
+            "secret_santa": self.secret_santa,
+        };
+        if let Some(owner) = owner {
+            doc.insert("owner", owner.clone());
+        }
+        Ok(doc)
+    }
+}
+
+impl ListUpdateInput {
+    /// Validates the input and converts it into the fields to set on the
+    /// stored list.
+    pub fn to_document(&self) -> Result<Document> {
+        let mut doc = Document::new();
+        if let Some(name) = &self.name {
+            doc.insert("name", validate_name(name)?);
+        }
+        if let Some(visibility) = self.visibility {
+            doc.insert("visibility", visibility.as_str());
+        }
+        if let Some(secret_santa) = self.secret_santa {
+            doc.insert("secret_santa", secret_santa);
+        }
+        if doc.is_empty() {
+            return Err(Error::Validation("at least one of name, visibility and secret_santa must be set".to_string()));
+        }
+        Ok(doc)
+    }
+}
+
+fn validate_name(name: &str) -> Result<&str> {
+    let name = name.trim();
+    if name.is_empty() || name.len() > MAX_NAME_LENGTH {
+        return Err(Error::Validation(format!(
+            "name must be between 1 and {} characters",
+            MAX_NAME_LENGTH
+        )));
+    }
+    Ok(name)
+}
+
+/// Slugs appear in URLs, so they are restricted to lowercase letters, digits and dashes.
+pub fn validate_slug(slug: &str) -> Result<()> {
+    let valid_chars = slug.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-');
+    if slug.is_empty() || slug.len() > MAX_SLUG_LENGTH || !valid_chars {
+        return Err(Error::Validation(format!(
+            "list slug must be 1 to {} lowercase letters, digits or dashes",
+            MAX_SLUG_LENGTH
+        )));
+    }
+    Ok(())
+}
+
+impl Stored for List {
+    const COLLECTION: &'static str = "list";
+}
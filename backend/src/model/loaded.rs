@@ -0,0 +1,39 @@
+use serde::{Serialize, Serializer};
+
+/// Value read from the database along with the number of documents that had to
+/// be skipped because they couldn't be deserialized.
+///
+/// Serializes as the bare value, the skipped count is reported out of band so
+/// response bodies keep their shape.
+#[derive(Clone, Debug)]
+pub struct Loaded<T> {
+    value: T,
+    skipped: u64,
+}
+
+impl<T> Loaded<T> {
+    pub fn new(value: T, skipped: u64) -> Self {
+        Self { value, skipped }
+    }
+    pub fn get_value(&self) -> &T {
+        &self.value
+    }
+    pub fn get_value_mut(&mut self) -> &mut T {
+        &mut self.value
+    }
+    pub fn get_skipped(&self) -> u64 {
+        self.skipped
+    }
+    pub fn into_value(self) -> T {
+        self.value
+    }
+    pub fn split(self) -> (T, u64) {
+        (self.value, self.skipped)
+    }
+}
+
+impl<T: Serialize> Serialize for Loaded<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.value.serialize(serializer)
+    }
+}
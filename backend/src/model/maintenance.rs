@@ -0,0 +1,51 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{Error, Result};
+
+const MAX_MESSAGE_LENGTH: usize = 500;
+
+/// Whether the API is read-only, and the message mutating requests get then.
+#[derive(Serialize, Clone, Debug)]
+pub struct MaintenanceStatus {
+    read_only: bool,
+    message: String,
+    /// Unix timestamp read-only mode was turned on at.
+    since: Option<i64>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct MaintenanceInput {
+    read_only: bool,
+    /// Replaces the message shown to mutating requests, kept if unset.
+    message: Option<String>,
+}
+
+impl MaintenanceStatus {
+    pub fn new(read_only: bool, message: String, since: Option<i64>) -> Self {
+        Self { read_only, message, since }
+    }
+
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+    pub fn get_message(&self) -> &str {
+        &self.message
+    }
+    pub fn get_since(&self) -> Option<i64> {
+        self.since
+    }
+}
+
+impl MaintenanceInput {
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    pub fn get_message(&self) -> Result<Option<&str>> {
+        match self.message.as_deref().map(str::trim) {
+            Some("") => Err(Error::Validation("message must not be empty".to_string())),
+            Some(message) if message.chars().count() > MAX_MESSAGE_LENGTH => Err(Error::Validation(format!("message must be at most {} characters", MAX_MESSAGE_LENGTH))),
+            message => Ok(message),
+        }
+    }
+}
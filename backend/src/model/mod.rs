@@ -1,13 +1,122 @@
+use mongodb::bson::oid::ObjectId;
+use serde::Serializer;
+
+use crate::{Error, Result};
+
+mod activity;
+mod alert;
+mod audit_event;
+mod budget;
+mod bulk;
 mod category;
+mod click;
+mod collaborator;
+mod comment;
+mod consistency;
 mod datapoint;
+mod enrichment;
 mod error_message;
+mod exchange_rates;
+mod fields;
+mod health;
+mod import;
+mod ingest;
+mod job_status;
+mod list;
+mod loaded;
+mod maintenance;
+mod money;
+mod offer;
+mod page;
+mod pledge;
+mod price_point;
 mod product;
+mod product_detail;
+mod push;
+mod reservation;
+mod retention;
+mod rule;
+mod schema_migration;
+mod scrape_run;
+mod session;
+mod share;
 mod source;
+mod stats;
+mod stored;
+mod suggestions;
+mod tag;
+mod timeline;
+mod user;
+mod value;
 mod wishlist;
+mod wishlist_diff;
+mod wishlist_event;
 
-pub use self::category::Category;
+pub use self::activity::ActivityEntry;
+pub use self::alert::{PriceAlert, PriceAlertInput};
+pub use self::audit_event::AuditEvent;
+pub use self::budget::{Budget, BudgetInput};
+pub use self::bulk::{BulkUpdateInput, BulkUpdateReport, BulkUpdateStatus};
+pub use self::category::{Category, CategoryCount, CategoryInput, CategoryListing, CategoryMerge, CategoryNode};
+pub use self::click::ClickCount;
+pub use self::collaborator::{Collaborator, CollaboratorInput, CollaboratorRole};
+pub use self::comment::{Comment, CommentInput, CommentReceipt};
+pub use self::consistency::{ConsistencyReport, DuplicateItem, Orphan};
+#[allow(unused_imports)]
 pub use self::datapoint::Datapoint;
+pub use self::enrichment::Enrichment;
 pub use self::error_message::ErrorMessage;
-pub use self::product::Product;
-pub use self::source::Source;
-pub use self::wishlist::Wishlist;
+pub use self::exchange_rates::{ExchangeRates, RATE_SCALE};
+pub use self::fields::FieldSelection;
+pub use self::health::Health;
+pub use self::import::{ImportReport, ImportRow, ImportStatus};
+pub use self::ingest::IngestInput;
+pub use self::job_status::JobStatus;
+pub use self::list::{validate_slug, List, ListInput, ListUpdateInput, Visibility, DEFAULT_LIST};
+pub use self::loaded::Loaded;
+pub use self::maintenance::{MaintenanceInput, MaintenanceStatus};
+pub use self::money::{Currency, Money};
+pub use self::offer::{Availability, Offer, OfferInput};
+pub use self::page::Page;
+pub use self::pledge::{PledgeInput, PledgePool, PledgePoolInput, PledgeReceipt, PledgeStatus};
+pub use self::price_point::PricePoint;
+pub use self::product::{normalize_tag, PriceThresholdInput, Priority, Product, ProductInput, WishInput};
+pub use self::product_detail::ProductDetail;
+pub use self::push::{PushKey, PushSubscription, PushSubscriptionInput, PushUnsubscribeInput};
+pub use self::reservation::Reservation;
+pub use self::retention::RetentionPreview;
+pub use self::rule::{CategoryRule, RuleInput};
+pub use self::schema_migration::SchemaMigration;
+pub use self::scrape_run::{ScrapeRun, ScrapeStatus, SourceItems};
+pub use self::session::Session;
+pub use self::share::{ShareInput, ShareLink, ShareScope};
+pub use self::source::{Affiliate, AffiliateInput, Politeness, PolitenessInput, Source};
+pub use self::stats::Stats;
+pub use self::stored::Stored;
+pub use self::suggestions::Suggestions;
+pub use self::tag::TagCount;
+pub use self::timeline::{TimelineEntry, TimelineKind};
+pub use self::user::{User, UserInput};
+pub use self::value::WishlistValue;
+pub use self::wishlist::{Wishlist, WishlistSummary};
+pub use self::wishlist_diff::WishlistDiff;
+pub use self::wishlist_event::WishlistEvent;
+
+fn serialize_object_id<S: Serializer>(id: &Option<ObjectId>, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+    match id {
+        Some(id) => serializer.serialize_str(&id.to_hex()),
+        None => serializer.serialize_none(),
+    }
+}
+
+fn serialize_object_id_hex<S: Serializer>(id: &ObjectId, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+    serializer.serialize_str(&id.to_hex())
+}
+
+fn serialize_object_ids_hex<S: Serializer>(ids: &[ObjectId], serializer: S) -> std::result::Result<S::Ok, S::Error> {
+    serializer.collect_seq(ids.iter().map(ObjectId::to_hex))
+}
+
+fn parse_object_id(field: &str, id: &str) -> Result<ObjectId> {
+    ObjectId::with_string(id).map_err(|_| Error::Validation(format!("{} is not a valid id", field)))
+}
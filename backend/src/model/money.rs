@@ -0,0 +1,228 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Currency of an amount, the euro and the currencies the ECB publishes
+/// reference rates for.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum Currency {
+    #[default]
+    Eur,
+    Usd,
+    Jpy,
+    Bgn,
+    Czk,
+    Dkk,
+    Gbp,
+    Huf,
+    Pln,
+    Ron,
+    Sek,
+    Chf,
+    Isk,
+    Nok,
+    Try,
+    Aud,
+    Brl,
+    Cad,
+    Cny,
+    Hkd,
+    Idr,
+    Ils,
+    Inr,
+    Krw,
+    Mxn,
+    Myr,
+    Nzd,
+    Php,
+    Sgd,
+    Thb,
+    Zar,
+}
+
+/// Amount of money in the minor unit of its currency, e.g. euro cents, so
+/// that sums never pick up rounding errors.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Money {
+    amount_minor: i64,
+    currency: Currency,
+}
+
+impl Currency {
+    pub const ALL: [Currency; 31] = [
+        Currency::Eur, Currency::Usd, Currency::Jpy, Currency::Bgn, Currency::Czk, Currency::Dkk,
+        Currency::Gbp, Currency::Huf, Currency::Pln, Currency::Ron, Currency::Sek, Currency::Chf,
+        Currency::Isk, Currency::Nok, Currency::Try, Currency::Aud, Currency::Brl, Currency::Cad,
+        Currency::Cny, Currency::Hkd, Currency::Idr, Currency::Ils, Currency::Inr, Currency::Krw,
+        Currency::Mxn, Currency::Myr, Currency::Nzd, Currency::Php, Currency::Sgd, Currency::Thb,
+        Currency::Zar,
+    ];
+
+    pub fn get_code(self) -> &'static str {
+        match self {
+            Currency::Eur => "EUR",
+            Currency::Usd => "USD",
+            Currency::Jpy => "JPY",
+            Currency::Bgn => "BGN",
+            Currency::Czk => "CZK",
+            Currency::Dkk => "DKK",
+            Currency::Gbp => "GBP",
+            Currency::Huf => "HUF",
+            Currency::Pln => "PLN",
+            Currency::Ron => "RON",
+            Currency::Sek => "SEK",
+            Currency::Chf => "CHF",
+            Currency::Isk => "ISK",
+            Currency::Nok => "NOK",
+            Currency::Try => "TRY",
+            Currency::Aud => "AUD",
+            Currency::Brl => "BRL",
+            Currency::Cad => "CAD",
+            Currency::Cny => "CNY",
+            Currency::Hkd => "HKD",
+            Currency::Idr => "IDR",
+            Currency::Ils => "ILS",
+            Currency::Inr => "INR",
+            Currency::Krw => "KRW",
+            Currency::Mxn => "MXN",
+            Currency::Myr => "MYR",
+            Currency::Nzd => "NZD",
+            Currency::Php => "PHP",
+            Currency::Sgd => "SGD",
+            Currency::Thb => "THB",
+            Currency::Zar => "ZAR",
+        }
+    }
+
+    /// Parses an ISO 4217 code like `USD`.
+    pub fn from_code(code: &str) -> Option<Currency> {
+        Self::ALL.iter().copied().find(|c| c.get_code() == code)
+    }
+
+    /// Symbol amounts are formatted with, the code for currencies without a common one.
+    pub fn get_symbol(self) -> &'static str {
+        match self {
+            Currency::Eur => "€",
+            Currency::Usd => "$",
+            Currency::Gbp => "£",
+            Currency::Jpy => "¥",
+            other => other.get_code(),
+        }
+    }
+
+    /// Number of decimal places of the minor unit.
+    pub fn get_minor_digits(self) -> u32 {
+        match self {
+            Currency::Jpy | Currency::Isk | Currency::Krw => 0,
+            _ => 2,
+        }
+    }
+}
+
+impl Money {
+    pub fn new(amount_minor: i64, currency: Currency) -> Self {
+        Self { amount_minor, currency }
+    }
+
+    pub fn zero(currency: Currency) -> Self {
+        Self::new(0, currency)
+    }
+
+    pub fn get_amount_minor(&self) -> i64 {
+        self.amount_minor
+    }
+
+    pub fn get_currency(&self) -> Currency {
+        self.currency
+    }
+
+    /// Adds `other`, `None` if the currencies differ or the sum overflows.
+    pub fn checked_add(self, other: Money) -> Option<Money> {
+        if self.currency != other.currency {
+            return None;
+        }
+        self.amount_minor.checked_add(other.amount_minor).map(|amount| Self::new(amount, self.currency))
+    }
+
+    /// Subtracts `other`, `None` if the currencies differ or the difference overflows.
+    pub fn checked_sub(self, other: Money) -> Option<Money> {
+        if self.currency != other.currency {
+            return None;
+        }
+        self.amount_minor.checked_sub(other.amount_minor).map(|amount| Self::new(amount, self.currency))
+    }
+
+    /// Price of `quantity` items costing this much each.
+    pub fn checked_mul(self, quantity: i64) -> Option<Money> {
+        self.amount_minor.checked_mul(quantity).map(|amount| Self::new(amount, self.currency))
+    }
+
+    /// Splits the amount into `count` shares, rounded half away from zero to the minor unit.
+    pub fn checked_div_rounded(self, count: i64) -> Option<Money> {
+        if count == 0 {
+            return None;
+        }
+        let quotient = self.amount_minor.checked_div(count)?;
+        let remainder = self.amount_minor % count;
+        let round_away = remainder.unsigned_abs() * 2 >= count.unsigned_abs();
+        let amount = if !round_away {
+            quotient
+        } else if (remainder < 0) == (count < 0) {
+            quotient + 1
+        } else {
+            quotient - 1
+        };
+        Some(Self::new(amount, self.currency))
+    }
+
+    /// Formats the amount as a plain decimal like `12.34`, without the currency.
+    pub fn format_amount(&self) -> String {
+        let digits = self.currency.get_minor_digits();
+        let unit = 10u64.pow(digits);
+        let amount = self.amount_minor.unsigned_abs();
+        let sign = if self.amount_minor < 0 { "-" } else { "" };
+        if digits == 0 {
+            return format!("{}{}", sign, amount);
+        }
+        format!("{}{}.{:0width$}", sign, amount / unit, amount % unit, width = digits as usize)
+    }
+
+    /// Parses a plain decimal like `12.34` or `12,34`, the inverse of `format_amount`.
+    /// More decimal places than the minor unit has are rejected.
+    pub fn parse_amount(amount: &str, currency: Currency) -> Option<Money> {
+        let amount = amount.trim();
+        let (negative, amount) = match amount.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, amount),
+        };
+        let (whole, fraction) = amount.split_once(['.', ',']).unwrap_or((amount, ""));
+        let digits = currency.get_minor_digits() as usize;
+        let is_number = |part: &str| part.chars().all(|c| c.is_ascii_digit());
+        if whole.is_empty() || !is_number(whole) || !is_number(fraction) || fraction.len() > digits {
+            return None;
+        }
+        let fraction: String = fraction.chars().chain(std::iter::repeat('0')).take(digits).collect();
+        let minor = whole.parse::<i64>().ok()?
+            .checked_mul(10i64.pow(digits as u32))?
+            .checked_add(if digits == 0 { 0 } else { fraction.parse().ok()? })?;
+        Some(Self::new(if negative { -minor } else { minor }, currency))
+    }
+
+    /// Sums up `amounts`, `None` if any is in another currency or the total overflows.
+    pub fn total<I: IntoIterator<Item = Money>>(currency: Currency, amounts: I) -> Option<Money> {
+        amounts.into_iter().try_fold(Self::zero(currency), Money::checked_add)
+    }
+}
+
+/// Formats like `12.34 €`.
+impl fmt::Display for Money {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {}", self.format_amount(), self.currency.get_symbol())
+    }
+}
+
+impl fmt::Display for Currency {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.get_code())
+    }
+}
@@ -0,0 +1,135 @@
+use mongodb::bson::{doc, document::Document, oid::ObjectId};
+use serde::{Deserialize, Serialize};
+
+use super::{parse_object_id, Source};
+use crate::{Error, Result};
+
+/// Offers a product can have besides the one of its own source.
+const MAX_OFFERS: usize = 20;
+
+/// A shop selling a product, at the price it was last seen for there.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Offer {
+    #[serde(rename(deserialize = "source"), skip_serializing)]
+    source_id: Option<ObjectId>,
+    #[serde(skip_deserializing)]
+    source: Option<Source>,
+    url: Option<String>,
+    /// In the minor unit of the product's currency.
+    price: Option<i32>,
+    availability: Option<Availability>,
+}
+
+/// Whether the shop of an offer has the product in stock, `None` if unknown.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Availability {
+    InStock,
+    OutOfStock,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct OfferInput {
+    source: String,
+    url: String,
+    price: i32,
+    availability: Option<Availability>,
+}
+
+impl Offer {
+    /// The offer of the product's own source, made of its `source`, `url`,
+    /// `current_price` and `availability` fields.
+    pub fn new(source_id: Option<ObjectId>, url: Option<String>, price: Option<i32>, availability: Option<Availability>) -> Self {
+        Self {
+            source_id,
+            source: None,
+            url,
+            price,
+            availability,
+        }
+    }
+
+    pub fn get_source_id(&self) -> Option<&ObjectId> {
+        self.source_id.as_ref()
+    }
+    pub fn get_source(&self) -> Option<&Source> {
+        self.source.as_ref()
+    }
+    pub fn set_source(&mut self, source: Source) {
+        self.source = Some(source);
+    }
+    pub fn get_url(&self) -> Option<&str> {
+        self.url.as_deref()
+    }
+    pub fn get_price(&self) -> Option<i32> {
+        self.price
+    }
+    pub fn set_price(&mut self, price: Option<i32>) {
+        self.price = price;
+    }
+    pub fn get_availability(&self) -> Option<Availability> {
+        self.availability
+    }
+    /// Adds the affiliate parameter of the source to the `url`, the source has to be loaded.
+    pub fn apply_affiliate(&mut self) {
+        if let (Some(url), Some(affiliate)) = (&self.url, self.source.as_ref().and_then(Source::get_affiliate)) {
+            self.url = Some(affiliate.apply(url));
+        }
+    }
+    /// Offers known to be out of stock can't be bought from.
+    pub fn is_available(&self) -> bool {
+        self.availability != Some(Availability::OutOfStock)
+    }
+}
+
+impl Availability {
+    pub fn get_code(self) -> &'static str {
+        match self {
+            Availability::InStock => "in-stock",
+            Availability::OutOfStock => "out-of-stock",
+        }
+    }
+}
+
+impl OfferInput {
+    pub fn get_source(&self) -> &str {
+        &self.source
+    }
+
+    /// Validates the input and converts it into the document stored in the `offers` of a product.
+    pub fn to_document(&self) -> Result<Document> {
+        if !self.url.starts_with("http://") && !self.url.starts_with("https://") {
+            return Err(Error::Validation("offer url must be an http(s) URL".to_string()));
+        }
+        if self.price < 0 {
+            return Err(Error::Validation("offer price must not be negative".to_string()));
+        }
+        let mut doc = doc! {
+            "source": parse_object_id("offer source", &self.source)?,
+            "url": self.url.as_str(),
+            "price": self.price,
+        };
+        if let Some(availability) = self.availability {
+            doc.insert("availability", availability.get_code());
+        }
+        Ok(doc)
+    }
+}
+
+/// Converts the offers of a product, at most one per source and none of
+/// `source`, the product's own.
+pub fn offers_to_documents(offers: &[OfferInput], source: &str) -> Result<Vec<Document>> {
+    if offers.len() > MAX_OFFERS {
+        return Err(Error::Validation(format!("a product can have at most {} other offers", MAX_OFFERS)));
+    }
+    let mut sources = vec![source];
+    let mut documents = Vec::with_capacity(offers.len());
+    for offer in offers {
+        if sources.contains(&offer.get_source()) {
+            return Err(Error::Validation("a product can only have one offer per source".to_string()));
+        }
+        sources.push(offer.get_source());
+        documents.push(offer.to_document()?);
+    }
+    Ok(documents)
+}
@@ -0,0 +1,71 @@
+use serde::ser::Error;
+use serde::{Serialize, Serializer};
+use serde_json::Value;
+
+use super::{FieldSelection, Loaded};
+
+/// Page of a listing along with where it starts and the number of items in
+/// the whole listing.
+///
+/// Serializes as the bare items like `Loaded`, the total and the links to the
+/// neighbouring pages are sent as headers. With a field selection, the items
+/// only keep the selected fields.
+#[derive(Debug)]
+pub struct Page<T> {
+    items: Loaded<Vec<T>>,
+    offset: u64,
+    size: u64,
+    total: u64,
+    fields: Option<FieldSelection>,
+}
+
+impl<T> Page<T> {
+    pub fn new(items: Loaded<Vec<T>>, offset: u64, size: u64, total: u64) -> Self {
+        Self {
+            items,
+            offset,
+            size,
+            total,
+            fields: None,
+        }
+    }
+    pub fn with_fields(mut self, fields: Option<FieldSelection>) -> Self {
+        self.fields = fields;
+        self
+    }
+    pub fn get_items_mut(&mut self) -> &mut Loaded<Vec<T>> {
+        &mut self.items
+    }
+    pub fn into_items(self) -> Loaded<Vec<T>> {
+        self.items
+    }
+    pub fn get_skipped(&self) -> u64 {
+        self.items.get_skipped()
+    }
+    pub fn get_total(&self) -> u64 {
+        self.total
+    }
+    /// Offset of the following page, `None` on the last one.
+    pub fn get_next_offset(&self) -> Option<u64> {
+        let next = self.offset.saturating_add(self.size);
+        Some(next).filter(|next| self.size > 0 && *next < self.total)
+    }
+    /// Offset of the preceding page, `None` on the first one.
+    pub fn get_prev_offset(&self) -> Option<u64> {
+        Some(self.offset.saturating_sub(self.size)).filter(|_| self.offset > 0)
+    }
+}
+
+impl<T: Serialize> Serialize for Page<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let fields = match &self.fields {
+            Some(fields) => fields,
+            None => return self.items.serialize(serializer),
+        };
+        let mut items = serde_json::to_value(self.items.get_value()).map_err(S::Error::custom)?;
+        if let Value::Array(items) = &mut items {
+            items.iter_mut().for_each(|item| fields.apply(item));
+        }
+        items.serialize(serializer)
+    }
+}
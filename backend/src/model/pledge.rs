@@ -0,0 +1,178 @@
+use mongodb::bson::{doc, document::Document, oid::ObjectId, Bson};
+use serde::{Deserialize, Serialize};
+
+use super::{Currency, Money, Stored};
+use crate::{Error, Result};
+
+const MAX_NAME_LENGTH: usize = 100;
+
+/// Group gift of a product, whose price several people pledge parts of.
+/// `funded` is the sum of the pledged amounts, kept along so that a pledge
+/// is checked against the target in the same update that adds it.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct PledgePool {
+    #[serde(serialize_with = "super::serialize_object_id_hex")]
+    product: ObjectId,
+    /// Amount to collect in the minor unit of `currency`.
+    target: i64,
+    #[serde(default)]
+    currency: Currency,
+    #[serde(default)]
+    funded: i64,
+    #[serde(default, skip_serializing)]
+    pledges: Vec<Pledge>,
+    created: i32,
+}
+
+/// Part of the target one person pledged, the token is needed to withdraw it.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Pledge {
+    token: String,
+    name: Option<String>,
+    amount: i64,
+    timestamp: i32,
+}
+
+/// Pledge pool as shown to gift-givers. The names of the pledgers are left
+/// out for the owner of the list, so that the gift stays a surprise.
+#[derive(Serialize, Debug)]
+pub struct PledgeStatus {
+    #[serde(serialize_with = "super::serialize_object_id_hex")]
+    product: ObjectId,
+    target: Money,
+    funded: Money,
+    remaining: Money,
+    /// Share of the target pledged, rounded down.
+    funded_percent: u32,
+    pledges: Vec<PledgeEntry>,
+    created: i32,
+}
+
+#[derive(Serialize, Debug)]
+pub struct PledgeEntry {
+    name: Option<String>,
+    amount: Money,
+    timestamp: i32,
+}
+
+/// Pledge as returned to whoever made it, the token is only handed out once
+/// and is needed to withdraw the pledge.
+#[derive(Serialize, Debug)]
+pub struct PledgeReceipt {
+    #[serde(serialize_with = "super::serialize_object_id_hex")]
+    product: ObjectId,
+    token: String,
+    amount: Money,
+    timestamp: i32,
+}
+
+/// Target of a new pool in the minor unit of the product's currency, the
+/// price of the product times its desired quantity if unset.
+#[derive(Deserialize, Debug)]
+pub struct PledgePoolInput {
+    target: Option<i64>,
+}
+
+/// Amount pledged in the minor unit of the pool's currency, along with the
+/// name the other gift-givers see.
+#[derive(Deserialize, Debug)]
+pub struct PledgeInput {
+    amount: i64,
+    name: Option<String>,
+}
+
+impl PledgePool {
+    pub fn get_product_id(&self) -> &ObjectId {
+        &self.product
+    }
+    pub fn get_currency(&self) -> Currency {
+        self.currency
+    }
+    pub fn get_funded_percent(&self) -> u32 {
+        if self.target <= 0 {
+            return 100;
+        }
+        (self.funded.max(0).saturating_mul(100) / self.target).min(100) as u32
+    }
+
+    /// Shows the pool, with the names of the pledgers unless `show_names` is false.
+    pub fn to_status(&self, show_names: bool) -> PledgeStatus {
+        PledgeStatus {
+            product: self.product.clone(),
+            target: Money::new(self.target, self.currency),
+            funded: Money::new(self.funded, self.currency),
+            remaining: Money::new((self.target - self.funded).max(0), self.currency),
+            funded_percent: self.get_funded_percent(),
+            pledges: self.pledges.iter()
+                .map(|pledge| PledgeEntry {
+                    name: pledge.name.clone().filter(|_| show_names),
+                    amount: Money::new(pledge.amount, self.currency),
+                    timestamp: pledge.timestamp,
+                })
+                .collect(),
+            created: self.created,
+        }
+    }
+}
+
+impl PledgeReceipt {
+    pub fn new(product: &ObjectId, token: String, amount: Money, timestamp: i32) -> Self {
+        Self {
+            product: product.clone(),
+            token,
+            amount,
+            timestamp,
+        }
+    }
+}
+
+impl PledgePoolInput {
+    /// Validates the input and converts it into the document stored in the
+    /// `pledge_pool` collection, `price` being the total price of the product.
+    pub fn to_document(&self, product: &ObjectId, price: Option<Money>, timestamp: i32) -> Result<Document> {
+        let currency = price.map(|price| price.get_currency()).unwrap_or_default();
+        let target = match (self.target, price) {
+            (Some(target), _) => target,
+            (None, Some(price)) => price.get_amount_minor(),
+            (None, None) => return Err(Error::Validation("target must be set for products without a price".to_string())),
+        };
+        if target <= 0 {
+            return Err(Error::Validation("target must be positive".to_string()));
+        }
+        Ok(doc! {
+            "product": product.clone(),
+            "target": target,
+            "currency": currency.get_code(),
+            "funded": 0i64,
+            "pledges": [],
+            "created": timestamp,
+        })
+    }
+}
+
+impl PledgeInput {
+    pub fn get_amount(&self) -> i64 {
+        self.amount
+    }
+
+    /// Validates the input and converts it into the pledge added to a pool.
+    pub fn to_document(&self, token: &str, timestamp: i32) -> Result<Document> {
+        if self.amount <= 0 {
+            return Err(Error::Validation("amount must be positive".to_string()));
+        }
+        let name = self.name.as_deref().map(str::trim).filter(|name| !name.is_empty());
+        if name.map(|name| name.chars().count() > MAX_NAME_LENGTH).unwrap_or(false) {
+            return Err(Error::Validation(format!("name must be at most {} characters", MAX_NAME_LENGTH)));
+        }
+        Ok(doc! {
+            "token": token,
+            "name": name.map(Bson::from).unwrap_or(Bson::Null),
+            "amount": self.amount,
+            "timestamp": timestamp,
+        })
+    }
+}
+
+impl Stored for PledgePool {
+    const COLLECTION: &'static str = "pledge_pool";
+}
@@ -0,0 +1,43 @@
+use mongodb::bson::oid::ObjectId;
+use serde::{Deserialize, Serialize};
+
+use std::convert::TryFrom;
+
+use super::{Currency, Money, Stored};
+use crate::{Error, Result};
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct PricePoint {
+    #[serde(rename(deserialize = "product"), skip_serializing)]
+    product_id: Option<ObjectId>,
+    price: Option<i32>,
+    timestamp: Option<i32>,
+}
+
+impl PricePoint {
+    pub fn get_product_id(&self) -> Option<&ObjectId> {
+        self.product_id.as_ref()
+    }
+    pub fn get_price(&self) -> Option<i32> {
+        self.price
+    }
+    pub fn get_timestamp(&self) -> Option<i32> {
+        self.timestamp
+    }
+    /// Replaces the price, given in `from`, with `convert` of it.
+    pub fn convert_price<F>(&mut self, from: Currency, convert: F) -> Result<()>
+    where
+        F: FnOnce(Money) -> Result<Money>,
+    {
+        if let Some(price) = self.price {
+            let converted = convert(Money::new(price.into(), from))?;
+            self.price = Some(i32::try_from(converted.get_amount_minor())
+                .map_err(|_| Error::Validation(format!("{} is too large to be shown", converted)))?);
+        }
+        Ok(())
+    }
+}
+
+impl Stored for PricePoint {
+    const COLLECTION: &'static str = "price_history";
+}
@@ -1,63 +1,617 @@
-use mongodb::bson::{document::Document, oid::ObjectId};
-use serde::Serialize;
+use mongodb::bson::{doc, document::Document, oid::ObjectId, Bson};
+use serde::{Deserialize, Serialize};
 
-use super::Source;
+use std::convert::TryFrom;
 
-#[derive(Serialize, Clone, Debug)]
+use super::offer::offers_to_documents;
+use super::{parse_object_id, validate_slug, Availability, Currency, Enrichment, Money, Offer, OfferInput, Source, Stored, DEFAULT_LIST};
+use crate::{Error, Result};
+
+const MAX_NAME_LENGTH: usize = 500;
+const MAX_DESCRIPTION_LENGTH: usize = 10_000;
+const MAX_NOTES_LENGTH: usize = 1000;
+/// Bounds of the want level, 5 being wanted most.
+const MIN_RATING: i32 = 1;
+const MAX_RATING: i32 = 5;
+const MAX_TAGS: usize = 20;
+const MAX_TAG_LENGTH: usize = 50;
+/// Digits of the EAN-8, UPC-A, EAN-13 and GTIN-14 article numbers.
+const EAN_LENGTHS: [usize; 4] = [8, 12, 13, 14];
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct Product {
-    #[serde(skip)]
+    #[serde(rename(deserialize = "_id"), serialize_with = "super::serialize_object_id")]
     id: Option<ObjectId>,
     name: Option<String>,
+    description: Option<String>,
+    /// Remarks of the owner for gift-givers, e.g. the size, the preferred
+    /// color or "already have the 2020 model".
+    notes: Option<String>,
     price: Option<i32>,
+    current_price: Option<i32>,
+    lowest_price: Option<i32>,
+    /// Whether the last recorded price set a new `lowest_price`, until a
+    /// higher one is recorded. Not set by the first price of a product.
+    #[serde(default)]
+    at_all_time_low: bool,
+    price_threshold: Option<i32>,
+    /// Percent the source takes off its regular price during a sale, set by
+    /// the Steam import. `None` for sources that don't show it.
+    discount_percent: Option<i32>,
+    /// Currency of all prices above, in its minor unit.
+    #[serde(default)]
+    currency: Currency,
+    /// Desired number of items.
     quantity: Option<i32>,
+    priority: Option<Priority>,
+    /// How much the owner wants the product, from 1 to 5.
+    rating: Option<i32>,
     stars: Option<i32>,
     url: Option<String>,
     url_img: Option<String>,
-    #[serde(skip)]
+    #[serde(default)]
+    tags: Vec<String>,
+    #[serde(skip_serializing)]
     item_id: Option<String>,
+    /// European article number, identifying the same item across shops.
+    ean: Option<String>,
+    /// Details looked up by the `ean`, set by the enrichment job. `None` if
+    /// they weren't looked up yet or no provider knows the item.
+    enrichment: Option<Enrichment>,
+    /// EAN the `enrichment` was looked up for, to look it up again once the `ean` changes.
+    #[serde(default, skip_serializing)]
+    enriched_ean: Option<String>,
+    /// Cheapest offer of the same item from another source, `None` if this
+    /// is the cheapest one or has no duplicates. Set by the deduplication job.
+    #[serde(default, serialize_with = "super::serialize_object_id")]
+    duplicate_of: Option<ObjectId>,
     first_seen: Option<i32>,
     last_seen: Option<i32>,
-    #[serde(skip)]
+    /// When the product was last changed, `None` if it wasn't since it was added.
+    updated_at: Option<i32>,
+    #[serde(rename(deserialize = "source"), skip_serializing)]
     source_id: Option<ObjectId>,
+    #[serde(skip_deserializing)]
     source: Option<Source>,
-    #[serde(skip)]
+    /// Every shop selling the product, the one of `source` included, the
+    /// cheapest available offer first.
+    #[serde(default)]
+    offers: Vec<Offer>,
+    #[serde(rename(deserialize = "category"), skip_serializing)]
     category_id: Option<ObjectId>,
+    #[serde(skip_deserializing)]
+    reserved: bool,
+    /// Share of the target pledged toward a group gift of the product, `None`
+    /// if nobody started a pledge pool for it.
+    #[serde(skip_deserializing)]
+    funded_percent: Option<u32>,
+    /// Whether the source has the product in stock, as last seen by the
+    /// scraper. `None` if unknown, e.g. for sources that don't show it.
+    availability: Option<Availability>,
+    /// Whether the product can be bought, i.e. isn't out of stock. Products
+    /// of unknown availability are taken as buyable.
+    #[serde(skip_deserializing)]
+    buyable: bool,
+    /// Slug of the list the product belongs to, `None` for the default list.
+    list: Option<String>,
+    /// Set by the link check if the `url` answered `404 Not Found` or `410 Gone`.
+    #[serde(default)]
+    link_dead: bool,
+    /// When the product was moved to the trash, `None` unless it is in there.
+    deleted_at: Option<i32>,
+}
+
+/// How much the owner of the list wants a product, for gift-givers to pick by.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Priority {
+    MustHave,
+    NiceToHave,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct PriceThresholdInput {
+    threshold: Option<i32>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ProductInput {
+    name: String,
+    description: Option<String>,
+    notes: Option<String>,
+    price: i32,
+    currency: Option<Currency>,
+    quantity: Option<i32>,
+    priority: Option<Priority>,
+    rating: Option<i32>,
+    stars: Option<i32>,
+    url: String,
+    url_img: Option<String>,
+    tags: Option<Vec<String>>,
+    item_id: Option<String>,
+    ean: Option<String>,
+    availability: Option<Availability>,
+    source: String,
+    /// Offers of other sources than `source`.
+    offers: Option<Vec<OfferInput>>,
+    category: Option<String>,
+    list: Option<String>,
+}
+
+/// Priority, want level, desired quantity and notes of a product, set apart
+/// from the other fields as they also apply to scraped products.
+#[derive(Deserialize, Debug)]
+pub struct WishInput {
+    priority: Option<Priority>,
+    rating: Option<i32>,
+    quantity: Option<i32>,
+    notes: Option<String>,
+}
+
+impl ProductInput {
+    pub fn new(name: String, price: i32, url: String, source: &ObjectId) -> Self {
+        Self {
+            name,
+            description: None,
+            notes: None,
+            price,
+            currency: None,
+            quantity: None,
+            priority: None,
+            rating: None,
+            stars: None,
+            url,
+            url_img: None,
+            tags: None,
+            item_id: None,
+            ean: None,
+            availability: None,
+            source: source.to_hex(),
+            offers: None,
+            category: None,
+            list: None,
+        }
+    }
+    pub fn with_currency(mut self, currency: Option<Currency>) -> Self {
+        self.currency = currency;
+        self
+    }
+    pub fn with_item_id(mut self, item_id: Option<String>) -> Self {
+        self.item_id = item_id;
+        self
+    }
+    pub fn with_category(mut self, category: Option<&ObjectId>) -> Self {
+        self.category = category.map(ObjectId::to_hex);
+        self
+    }
+    pub fn with_url_img(mut self, url_img: Option<String>) -> Self {
+        self.url_img = url_img;
+        self
+    }
+    pub fn with_list(mut self, list: Option<String>) -> Self {
+        self.list = list;
+        self
+    }
+    pub fn with_availability(mut self, availability: Option<Availability>) -> Self {
+        self.availability = availability;
+        self
+    }
+
+    pub fn get_price(&self) -> i32 {
+        self.price
+    }
+
+    /// Validates the input and converts it into the document stored in the `product` collection.
+    pub fn to_document(&self) -> Result<Document> {
+        let name = self.name.trim();
+        if name.is_empty() || name.len() > MAX_NAME_LENGTH {
+            return Err(Error::Validation(format!(
+                "name must be between 1 and {} characters",
+                MAX_NAME_LENGTH
+            )));
+        }
+        if let Some(description) = &self.description {
+            if description.len() > MAX_DESCRIPTION_LENGTH {
+                return Err(Error::Validation(format!(
+                    "description must be at most {} characters",
+                    MAX_DESCRIPTION_LENGTH
+                )));
+            }
+        }
+        if self.price < 0 {
+            return Err(Error::Validation("price must not be negative".to_string()));
+        }
+        let quantity = self.quantity.unwrap_or(1);
+        validate_quantity(quantity)?;
+        let stars = self.stars.unwrap_or(0);
+        if stars < 0 {
+            return Err(Error::Validation("stars must not be negative".to_string()));
+        }
+        validate_url("url", &self.url)?;
+        if let Some(url_img) = &self.url_img {
+            validate_url("url_img", url_img)?;
+        }
+        let source = parse_object_id("source", &self.source)?;
+        let category = match &self.category {
+            Some(c) => Bson::ObjectId(parse_object_id("category", c)?),
+            None => Bson::Null,
+        };
+
+        let mut doc = doc! {
+            "name": name,
+            "price": self.price,
+            "quantity": quantity,
+            "stars": stars,
+            "url": self.url.as_str(),
+            "url_img": self.url_img.as_deref().unwrap_or(""),
+            "source": source,
+            "category": category,
+        };
+        if let Some(description) = &self.description {
+            doc.insert("description", description.as_str());
+        }
+        if let Some(notes) = &self.notes {
+            doc.insert("notes", normalize_notes(notes)?);
+        }
+        if let Some(currency) = self.currency {
+            doc.insert("currency", currency.get_code());
+        }
+        if let Some(priority) = self.priority {
+            doc.insert("priority", priority.get_code());
+        }
+        if let Some(rating) = self.rating {
+            validate_rating(rating)?;
+            doc.insert("rating", rating);
+        }
+        if let Some(tags) = &self.tags {
+            doc.insert("tags", normalize_tags(tags)?);
+        }
+        if let Some(item_id) = &self.item_id {
+            doc.insert("item_id", item_id.as_str());
+        }
+        if let Some(ean) = &self.ean {
+            doc.insert("ean", normalize_ean(ean)?);
+        }
+        if let Some(availability) = self.availability {
+            doc.insert("availability", availability.get_code());
+        }
+        if let Some(offers) = &self.offers {
+            doc.insert("offers", offers_to_documents(offers, &self.source)?);
+        }
+        if let Some(list) = &self.list {
+            validate_slug(list)?;
+            doc.insert("list", list.as_str());
+        }
+        Ok(doc)
+    }
+}
+
+impl PriceThresholdInput {
+    /// Validates the input and converts it into the fields to set on the product document.
+    pub fn to_document(&self) -> Result<Document> {
+        match self.threshold {
+            Some(threshold) if threshold < 0 => Err(Error::Validation("threshold must not be negative".to_string())),
+            Some(threshold) => Ok(doc! { "price_threshold": threshold }),
+            None => Ok(doc! { "price_threshold": Bson::Null }),
+        }
+    }
+}
+
+impl WishInput {
+    /// Validates the input and converts it into the fields to set on the product
+    /// document. A missing priority or rating removes it, missing quantity and
+    /// notes keep them and empty notes remove them.
+    pub fn to_document(&self) -> Result<Document> {
+        let mut doc = match self.priority {
+            Some(priority) => doc! { "priority": priority.get_code() },
+            None => doc! { "priority": Bson::Null },
+        };
+        match self.rating {
+            Some(rating) => {
+                validate_rating(rating)?;
+                doc.insert("rating", rating);
+            }
+            None => {
+                doc.insert("rating", Bson::Null);
+            }
+        }
+        if let Some(quantity) = self.quantity {
+            validate_quantity(quantity)?;
+            doc.insert("quantity", quantity);
+        }
+        if let Some(notes) = &self.notes {
+            doc.insert("notes", normalize_notes(notes)?);
+        }
+        Ok(doc)
+    }
+}
+
+impl Priority {
+    pub fn get_code(self) -> &'static str {
+        match self {
+            Priority::MustHave => "must-have",
+            Priority::NiceToHave => "nice-to-have",
+        }
+    }
+}
+
+/// Trims the notes, empty notes are stored as `null`.
+fn normalize_notes(notes: &str) -> Result<Bson> {
+    let notes = notes.trim();
+    if notes.chars().count() > MAX_NOTES_LENGTH {
+        return Err(Error::Validation(format!("notes must be at most {} characters", MAX_NOTES_LENGTH)));
+    }
+    Ok(if notes.is_empty() { Bson::Null } else { Bson::from(notes) })
+}
+
+fn validate_rating(rating: i32) -> Result<()> {
+    if !(MIN_RATING..=MAX_RATING).contains(&rating) {
+        return Err(Error::Validation(format!("rating must be between {} and {}", MIN_RATING, MAX_RATING)));
+    }
+    Ok(())
+}
+
+fn validate_quantity(quantity: i32) -> Result<()> {
+    if quantity < 1 {
+        return Err(Error::Validation("quantity must be at least 1".to_string()));
+    }
+    Ok(())
+}
+
+fn validate_url(field: &str, url: &str) -> Result<()> {
+    if url.starts_with("http://") || url.starts_with("https://") {
+        Ok(())
+    } else {
+        Err(Error::Validation(format!("{} must be an http(s) URL", field)))
+    }
+}
+
+/// Trims and lowercases the tags and drops duplicates, keeping the first occurrence's position.
+pub(super) fn normalize_tags(tags: &[String]) -> Result<Vec<String>> {
+    if tags.len() > MAX_TAGS {
+        return Err(Error::Validation(format!("a product can have at most {} tags", MAX_TAGS)));
+    }
+    let mut normalized: Vec<String> = Vec::with_capacity(tags.len());
+    for tag in tags {
+        let tag = normalize_tag(tag)?;
+        if !normalized.contains(&tag) {
+            normalized.push(tag);
+        }
+    }
+    Ok(normalized)
+}
+
+/// Strips the spaces and dashes article numbers are often printed with, e.g.
+/// `4 006381 333931` into `4006381333931`.
+/// Removes the separators of `ean`. An ISBN-10 is converted into its EAN-13,
+/// so a book has the same number whichever one it was entered with.
+fn normalize_ean(ean: &str) -> Result<String> {
+    let ean: String = ean.chars().filter(|c| !c.is_whitespace() && *c != '-').collect();
+    if let Some(isbn) = isbn10_to_ean(&ean) {
+        return Ok(isbn);
+    }
+    if !EAN_LENGTHS.contains(&ean.len()) || !ean.chars().all(|c| c.is_ascii_digit()) {
+        return Err(Error::Validation("ean must be an ISBN-10, EAN-8, UPC-A, EAN-13 or GTIN-14 number".to_string()));
+    }
+    Ok(ean)
+}
+
+/// `None` unless `isbn` is an ISBN-10 with a valid check digit.
+fn isbn10_to_ean(isbn: &str) -> Option<String> {
+    if isbn.len() != 10 {
+        return None;
+    }
+    let digits: Vec<u32> = isbn.chars()
+        .enumerate()
+        .map(|(i, c)| match c {
+            'X' | 'x' if i == 9 => Some(10),
+            c => c.to_digit(10),
+        })
+        .collect::<Option<_>>()?;
+    let checksum: u32 = digits.iter().enumerate().map(|(i, digit)| (10 - i as u32) * digit).sum();
+    if !checksum.is_multiple_of(11) {
+        return None;
+    }
+    let body: Vec<u32> = [9, 7, 8].iter().chain(digits[..9].iter()).cloned().collect();
+    let sum: u32 = body.iter().enumerate().map(|(i, digit)| if i % 2 == 0 { *digit } else { digit * 3 }).sum();
+    let digits: String = body.iter().chain(std::iter::once(&((10 - sum % 10) % 10))).map(u32::to_string).collect();
+    Some(digits)
+}
+
+/// Brings a tag into the form it's stored and filtered by, e.g. `Under-50-Euro` into `under-50-euro`.
+pub fn normalize_tag(tag: &str) -> Result<String> {
+    let tag = tag.trim().to_lowercase();
+    let valid_chars = tag.chars().all(|c| c.is_alphanumeric() || c == '-');
+    if tag.is_empty() || tag.chars().count() > MAX_TAG_LENGTH || !valid_chars {
+        return Err(Error::Validation(format!(
+            "tags must be 1 to {} letters, digits or dashes",
+            MAX_TAG_LENGTH
+        )));
+    }
+    Ok(tag)
 }
 
 impl Product {
+    pub fn get_id(&self) -> Option<&ObjectId> {
+        self.id.as_ref()
+    }
+    pub fn get_name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+    pub fn get_description(&self) -> Option<&str> {
+        self.description.as_deref()
+    }
+    pub fn get_notes(&self) -> Option<&str> {
+        self.notes.as_deref()
+    }
+    pub fn get_quantity(&self) -> Option<i32> {
+        self.quantity
+    }
+    pub fn get_priority(&self) -> Option<Priority> {
+        self.priority
+    }
+    pub fn get_rating(&self) -> Option<i32> {
+        self.rating
+    }
+    pub fn get_stars(&self) -> Option<i32> {
+        self.stars
+    }
+    pub fn get_price(&self) -> Option<i32> {
+        self.price
+    }
+    pub fn get_url(&self) -> Option<&str> {
+        self.url.as_deref()
+    }
+    pub fn get_url_img(&self) -> Option<&str> {
+        self.url_img.as_deref()
+    }
+    pub fn get_tags(&self) -> &[String] {
+        &self.tags
+    }
+    pub fn get_source(&self) -> Option<&Source> {
+        self.source.as_ref()
+    }
+    pub fn get_item_id(&self) -> Option<&str> {
+        self.item_id.as_deref()
+    }
+    pub fn get_ean(&self) -> Option<&str> {
+        self.ean.as_deref()
+    }
+    pub fn get_enrichment(&self) -> Option<&Enrichment> {
+        self.enrichment.as_ref()
+    }
+    pub fn get_enriched_ean(&self) -> Option<&str> {
+        self.enriched_ean.as_deref()
+    }
+    pub fn get_duplicate_of(&self) -> Option<&ObjectId> {
+        self.duplicate_of.as_ref()
+    }
+    pub fn get_category_id(&self) -> Option<&ObjectId> {
+        self.category_id.as_ref()
+    }
     pub fn get_source_id(&self) -> Option<&ObjectId> {
         self.source_id.as_ref()
     }
     pub fn set_source(&mut self, source: Source) {
         self.source = Some(source);
     }
+    pub fn get_offers(&self) -> &[Offer] {
+        &self.offers
+    }
+    pub fn get_offers_mut(&mut self) -> &mut [Offer] {
+        &mut self.offers
+    }
+    /// Cheapest offer that isn't known to be out of stock.
+    pub fn get_cheapest_offer(&self) -> Option<&Offer> {
+        self.offers.first().filter(|offer| offer.is_available())
+    }
+    pub fn is_reserved(&self) -> bool {
+        self.reserved
+    }
+    pub fn set_reserved(&mut self, reserved: bool) {
+        self.reserved = reserved;
+    }
+    pub fn get_funded_percent(&self) -> Option<u32> {
+        self.funded_percent
+    }
+    pub fn set_funded_percent(&mut self, funded_percent: Option<u32>) {
+        self.funded_percent = funded_percent;
+    }
+    pub fn get_availability(&self) -> Option<Availability> {
+        self.availability
+    }
+    pub fn is_buyable(&self) -> bool {
+        self.buyable
+    }
+    pub fn is_link_dead(&self) -> bool {
+        self.link_dead
+    }
+    pub fn get_current_price(&self) -> Option<i32> {
+        self.current_price
+    }
+    pub fn get_lowest_price(&self) -> Option<i32> {
+        self.lowest_price
+    }
+    pub fn is_at_all_time_low(&self) -> bool {
+        self.at_all_time_low
+    }
+    pub fn get_price_threshold(&self) -> Option<i32> {
+        self.price_threshold
+    }
+    pub fn get_discount_percent(&self) -> Option<i32> {
+        self.discount_percent
+    }
+    pub fn get_currency(&self) -> Currency {
+        self.currency
+    }
+    /// Replaces every price with `convert` of it and sets `currency`, which
+    /// `convert` is expected to convert into.
+    pub fn convert_prices<F>(&mut self, currency: Currency, mut convert: F) -> Result<()>
+    where
+        F: FnMut(Money) -> Result<Money>,
+    {
+        let from = self.currency;
+        let mut convert_price = |price: Option<i32>| -> Result<Option<i32>> {
+            let price = match price {
+                Some(price) => price,
+                None => return Ok(None),
+            };
+            let converted = convert(Money::new(price.into(), from))?;
+            i32::try_from(converted.get_amount_minor())
+                .map(Some)
+                .map_err(|_| Error::Validation(format!("{} is too large to be shown", converted)))
+        };
+        self.price = convert_price(self.price)?;
+        self.current_price = convert_price(self.current_price)?;
+        self.lowest_price = convert_price(self.lowest_price)?;
+        self.price_threshold = convert_price(self.price_threshold)?;
+        for offer in self.offers.iter_mut() {
+            offer.set_price(convert_price(offer.get_price())?);
+        }
+        self.currency = currency;
+        Ok(())
+    }
+    /// Adds the affiliate parameters of the sources to the `url` and the
+    /// offers, the sources have to be loaded.
+    pub fn apply_affiliate(&mut self) {
+        if let (Some(url), Some(affiliate)) = (&self.url, self.source.as_ref().and_then(Source::get_affiliate)) {
+            self.url = Some(affiliate.apply(url));
+        }
+        self.offers.iter_mut().for_each(Offer::apply_affiliate);
+    }
     pub fn get_first_seen(&self) -> Option<i32> {
         self.first_seen
     }
+    pub fn get_last_seen(&self) -> Option<i32> {
+        self.last_seen
+    }
+    pub fn get_list(&self) -> &str {
+        self.list.as_deref().unwrap_or(DEFAULT_LIST)
+    }
+    pub fn is_deleted(&self) -> bool {
+        self.deleted_at.is_some()
+    }
 }
 
-impl From<&Document> for Product {
-    fn from(doc: &Document) -> Self {
-        Self {
-            id: doc.get_object_id("_id").map(|id| id.clone()).ok(),
-            name: doc.get_str("name").map(String::from).ok(),
-            price: doc.get_i32("price").ok(),
-            quantity: doc.get_i32("quantity").ok(),
-            stars: doc.get_i32("stars").ok(),
-            url: doc.get_str("url").map(String::from).ok(),
-            url_img: doc.get_str("url_img").map(String::from).ok(),
-            item_id: doc.get_str("item_id").map(String::from).ok(),
-            first_seen: doc.get_i32("first_seen").ok(),
-            last_seen: doc.get_i32("last_seen").ok(),
-            source_id: doc.get_object_id("source").map(|id| id.clone()).ok(),
-            source: None,
-            category_id: doc.get_object_id("category").map(|id| id.clone()).ok(),
-        }
-    }
-}
-
-impl From<Document> for Product {
-    fn from(doc: Document) -> Self {
-        Self::from(&doc)
+impl Stored for Product {
+    const COLLECTION: &'static str = "product";
+
+    /// Products stored before prices were tracked only have `price`. Only
+    /// the offers of other sources are stored, the one of `source` is added
+    /// from the product's fields.
+    fn complete(mut self) -> Self {
+        if self.current_price.is_none() {
+            self.current_price = self.price;
+        }
+        if self.lowest_price.is_none() {
+            self.lowest_price = self.price;
+        }
+        if !self.offers.iter().any(|offer| offer.get_source_id() == self.source_id.as_ref()) {
+            let own = Offer::new(self.source_id.clone(), self.url.clone(), self.current_price, self.availability);
+            self.offers.insert(0, own);
+        }
+        self.buyable = self.availability != Some(Availability::OutOfStock);
+        self.offers.sort_by_key(|offer| (!offer.is_available(), offer.get_price().is_none(), offer.get_price()));
+        self
     }
 }
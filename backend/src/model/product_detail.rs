@@ -0,0 +1,42 @@
+use serde::Serialize;
+
+use super::{Category, PricePoint, Product};
+
+/// Single product with the relations requested by `?expand=`. Relations that
+/// weren't requested are left out, an expanded but missing category is `null`.
+#[derive(Serialize, Debug)]
+pub struct ProductDetail {
+    #[serde(flatten)]
+    product: Product,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    category: Option<Option<Category>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    price_history: Option<Vec<PricePoint>>,
+}
+
+impl ProductDetail {
+    pub fn new(product: Product) -> Self {
+        Self {
+            product,
+            category: None,
+            price_history: None,
+        }
+    }
+
+    pub fn with_category(mut self, category: Option<Category>) -> Self {
+        self.category = Some(category);
+        self
+    }
+
+    pub fn with_price_history(mut self, price_history: Vec<PricePoint>) -> Self {
+        self.price_history = Some(price_history);
+        self
+    }
+
+    pub fn get_product_mut(&mut self) -> &mut Product {
+        &mut self.product
+    }
+    pub fn get_price_history_mut(&mut self) -> Option<&mut Vec<PricePoint>> {
+        self.price_history.as_mut()
+    }
+}
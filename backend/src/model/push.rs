@@ -0,0 +1,122 @@
+use mongodb::bson::{doc, document::Document, oid::ObjectId, Bson};
+use serde::{Deserialize, Serialize};
+
+use super::Stored;
+use crate::net::is_forbidden_host;
+use crate::{Error, Result};
+
+const MAX_ENDPOINT_LENGTH: usize = 2048;
+/// Length of an uncompressed P-256 public key.
+const P256DH_LENGTH: usize = 65;
+const AUTH_LENGTH: usize = 16;
+
+/// Browser push subscription of a user, price drops of the lists the user
+/// can read are pushed to its endpoint.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct PushSubscription {
+    #[serde(rename(deserialize = "_id"), serialize_with = "super::serialize_object_id")]
+    id: Option<ObjectId>,
+    endpoint: String,
+    #[serde(skip_serializing)]
+    p256dh: String,
+    #[serde(skip_serializing)]
+    auth: String,
+    /// User who subscribed, `None` for the admin.
+    #[serde(default, serialize_with = "super::serialize_object_id")]
+    user: Option<ObjectId>,
+    created: i32,
+}
+
+/// Subscription as returned by `PushManager.subscribe()` in the browser.
+#[derive(Deserialize, Debug)]
+pub struct PushSubscriptionInput {
+    endpoint: String,
+    keys: PushKeys,
+}
+
+#[derive(Deserialize, Debug)]
+struct PushKeys {
+    p256dh: String,
+    auth: String,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct PushUnsubscribeInput {
+    endpoint: String,
+}
+
+/// Key the browser needs as `applicationServerKey` to subscribe.
+#[derive(Serialize, Debug)]
+pub struct PushKey {
+    public_key: String,
+}
+
+impl PushSubscription {
+    pub fn get_endpoint(&self) -> &str {
+        &self.endpoint
+    }
+    pub fn get_user_id(&self) -> Option<&ObjectId> {
+        self.user.as_ref()
+    }
+
+    /// Returns the browser's public key and the authentication secret.
+    pub fn get_keys(&self) -> Result<(Vec<u8>, Vec<u8>)> {
+        Ok((
+            decode_key("p256dh", &self.p256dh, P256DH_LENGTH)?,
+            decode_key("auth", &self.auth, AUTH_LENGTH)?,
+        ))
+    }
+}
+
+impl PushSubscriptionInput {
+    pub fn get_endpoint(&self) -> Result<reqwest::Url> {
+        let invalid = || Error::Validation("endpoint must be a https url of a public host".to_string());
+        if self.endpoint.len() > MAX_ENDPOINT_LENGTH {
+            return Err(Error::Validation(format!("endpoint must be at most {} characters", MAX_ENDPOINT_LENGTH)));
+        }
+        let url = reqwest::Url::parse(&self.endpoint).map_err(|_| invalid())?;
+        if url.scheme() != "https" || is_forbidden_host(&url) {
+            return Err(invalid());
+        }
+        Ok(url)
+    }
+
+    /// Validates the input and converts it into the document stored in the
+    /// `push_subscription` collection.
+    pub fn to_document(&self, user: Option<&ObjectId>, timestamp: i32) -> Result<Document> {
+        self.get_endpoint()?;
+        decode_key("p256dh", &self.keys.p256dh, P256DH_LENGTH)?;
+        decode_key("auth", &self.keys.auth, AUTH_LENGTH)?;
+        Ok(doc! {
+            "endpoint": &self.endpoint,
+            "p256dh": &self.keys.p256dh,
+            "auth": &self.keys.auth,
+            "user": user.map(|id| Bson::ObjectId(id.clone())).unwrap_or(Bson::Null),
+            "created": timestamp,
+        })
+    }
+}
+
+impl PushUnsubscribeInput {
+    pub fn get_endpoint(&self) -> &str {
+        &self.endpoint
+    }
+}
+
+impl PushKey {
+    pub fn new(public_key: String) -> Self {
+        Self { public_key }
+    }
+}
+
+/// Decodes a base64url key, with or without padding, of `length` bytes.
+fn decode_key(name: &str, value: &str, length: usize) -> Result<Vec<u8>> {
+    base64::decode_config(value.trim_end_matches('='), base64::URL_SAFE_NO_PAD)
+        .ok()
+        .filter(|key| key.len() == length)
+        .ok_or_else(|| Error::Validation(format!("keys.{} must be a base64url encoded key of {} bytes", name, length)))
+}
+
+impl Stored for PushSubscription {
+    const COLLECTION: &'static str = "push_subscription";
+}
@@ -0,0 +1,32 @@
+use mongodb::bson::oid::ObjectId;
+use serde::{Deserialize, Serialize};
+
+use super::Stored;
+
+/// Reservation of a product, the token is only handed out once to whoever
+/// reserved it and is needed to undo the reservation.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Reservation {
+    #[serde(serialize_with = "super::serialize_object_id_hex")]
+    product: ObjectId,
+    token: String,
+    timestamp: i32,
+}
+
+impl Reservation {
+    pub fn new(product_id: &ObjectId, token: String, timestamp: i32) -> Self {
+        Self {
+            product: product_id.clone(),
+            token,
+            timestamp,
+        }
+    }
+
+    pub fn get_product_id(&self) -> &ObjectId {
+        &self.product
+    }
+}
+
+impl Stored for Reservation {
+    const COLLECTION: &'static str = "reservation";
+}
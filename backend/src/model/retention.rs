@@ -0,0 +1,28 @@
+use serde::Serialize;
+
+use super::WishlistSummary;
+
+/// Snapshots of a list the retention policy deletes, newest first.
+#[derive(Serialize, Debug)]
+pub struct RetentionPreview {
+    list: String,
+    kept: u64,
+    deleted: Vec<WishlistSummary>,
+}
+
+impl RetentionPreview {
+    pub fn new(list: &str, kept: u64, deleted: Vec<WishlistSummary>) -> Self {
+        Self {
+            list: list.to_string(),
+            kept,
+            deleted,
+        }
+    }
+
+    pub fn get_list(&self) -> &str {
+        &self.list
+    }
+    pub fn get_deleted_timestamps(&self) -> Vec<i32> {
+        self.deleted.iter().filter_map(WishlistSummary::get_timestamp).collect()
+    }
+}
@@ -0,0 +1,106 @@
+use mongodb::bson::{doc, document::Document, oid::ObjectId};
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+
+use super::{parse_object_id, Stored};
+use crate::{Error, Result};
+
+const MAX_PATTERN_LENGTH: usize = 200;
+const MAX_REGEX_SIZE: usize = 100_000;
+
+/// Assigns `category` to uncategorized products whose name or description
+/// matches `pattern`. Rules are tried oldest first, the first match wins.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct CategoryRule {
+    #[serde(rename(deserialize = "_id"), serialize_with = "super::serialize_object_id")]
+    id: Option<ObjectId>,
+    pattern: String,
+    kind: RuleKind,
+    #[serde(rename(deserialize = "category"), serialize_with = "super::serialize_object_id_hex")]
+    category_id: ObjectId,
+}
+
+/// How a rule's pattern is matched, case-insensitively in both cases.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum RuleKind {
+    /// The pattern occurs as a whole word.
+    Keyword,
+    Regex,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct RuleInput {
+    pattern: String,
+    kind: Option<RuleKind>,
+    category: String,
+}
+
+impl CategoryRule {
+    pub fn get_id(&self) -> Option<&ObjectId> {
+        self.id.as_ref()
+    }
+    pub fn get_pattern(&self) -> &str {
+        &self.pattern
+    }
+    pub fn get_kind(&self) -> RuleKind {
+        self.kind
+    }
+    pub fn get_category_id(&self) -> &ObjectId {
+        &self.category_id
+    }
+
+    /// Compiles the pattern into the regex products are matched with.
+    pub fn compile(&self) -> Result<Regex> {
+        compile_pattern(&self.pattern, self.kind)
+    }
+}
+
+impl RuleInput {
+    pub fn get_category(&self) -> Result<ObjectId> {
+        parse_object_id("category", &self.category)
+    }
+
+    /// Validates the input and converts it into the document stored in the `category_rule` collection.
+    pub fn to_document(&self) -> Result<Document> {
+        let pattern = self.pattern.trim();
+        if pattern.is_empty() || pattern.len() > MAX_PATTERN_LENGTH {
+            return Err(Error::Validation(format!(
+                "pattern must be between 1 and {} characters",
+                MAX_PATTERN_LENGTH
+            )));
+        }
+        let kind = self.kind.unwrap_or(RuleKind::Keyword);
+        compile_pattern(pattern, kind)?;
+        Ok(doc! {
+            "pattern": pattern,
+            "kind": kind.get_code(),
+            "category": self.get_category()?,
+        })
+    }
+}
+
+impl RuleKind {
+    pub fn get_code(self) -> &'static str {
+        match self {
+            RuleKind::Keyword => "keyword",
+            RuleKind::Regex => "regex",
+        }
+    }
+}
+
+fn compile_pattern(pattern: &str, kind: RuleKind) -> Result<Regex> {
+    let pattern = match kind {
+        RuleKind::Keyword => format!(r"\b{}\b", regex::escape(pattern)),
+        RuleKind::Regex => pattern.to_string(),
+    };
+    RegexBuilder::new(&pattern)
+        .case_insensitive(true)
+        .size_limit(MAX_REGEX_SIZE)
+        .build()
+        .map_err(|e| Error::Validation(format!("pattern is not a valid regex: {}", e)))
+}
+
+impl Stored for CategoryRule {
+    const COLLECTION: &'static str = "category_rule";
+}
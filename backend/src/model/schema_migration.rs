@@ -0,0 +1,29 @@
+use mongodb::bson::oid::ObjectId;
+use serde::{Deserialize, Serialize};
+
+use super::Stored;
+
+/// Record of a migration applied to the stored data, kept so it runs only once.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SchemaMigration {
+    #[serde(rename(deserialize = "_id"), serialize_with = "super::serialize_object_id")]
+    id: Option<ObjectId>,
+    version: i32,
+    name: String,
+    applied: i32,
+    /// Number of documents the migration changed.
+    changed: i64,
+}
+
+impl SchemaMigration {
+    pub fn get_version(&self) -> i32 {
+        self.version
+    }
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+}
+
+impl Stored for SchemaMigration {
+    const COLLECTION: &'static str = "schema_migrations";
+}
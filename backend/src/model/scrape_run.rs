@@ -0,0 +1,71 @@
+use serde::Serialize;
+use std::time::Duration;
+
+use crate::Result;
+
+/// One run of the scraper, started by its schedule or through the API.
+#[derive(Serialize, Clone, Debug)]
+pub struct ScrapeRun {
+    started: i32,
+    /// Name of the source scraped, `None` if all of them were.
+    source: Option<String>,
+    /// `None` while the run isn't finished.
+    duration_ms: Option<u64>,
+    success: Option<bool>,
+    /// Summary of a successful run, or why it failed.
+    message: Option<String>,
+    /// Products seen by the run per source, most first.
+    found: Vec<SourceItems>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct SourceItems {
+    source: String,
+    items: u64,
+}
+
+/// Whether a scrape is running, along with the latest runs, newest first.
+#[derive(Serialize, Clone, Debug)]
+pub struct ScrapeStatus {
+    running: bool,
+    runs: Vec<ScrapeRun>,
+}
+
+impl ScrapeRun {
+    pub fn new(started: i32, source: Option<String>) -> Self {
+        Self {
+            started,
+            source,
+            duration_ms: None,
+            success: None,
+            message: None,
+            found: Vec::new(),
+        }
+    }
+
+    pub fn get_started(&self) -> i32 {
+        self.started
+    }
+
+    pub fn finish(&mut self, duration: Duration, result: &Result<String>, found: Vec<SourceItems>) {
+        self.duration_ms = Some(duration.as_millis() as u64);
+        self.success = Some(result.is_ok());
+        self.message = Some(match result {
+            Ok(summary) => summary.clone(),
+            Err(e) => e.to_string(),
+        });
+        self.found = found;
+    }
+}
+
+impl SourceItems {
+    pub fn new(source: String, items: u64) -> Self {
+        Self { source, items }
+    }
+}
+
+impl ScrapeStatus {
+    pub fn new(running: bool, runs: Vec<ScrapeRun>) -> Self {
+        Self { running, runs }
+    }
+}
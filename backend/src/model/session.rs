@@ -0,0 +1,15 @@
+use serde::Serialize;
+
+/// Session handed out on login, `token` is sent back as `Authorization: Bearer <token>`.
+#[derive(Serialize, Clone, Debug)]
+pub struct Session {
+    token: String,
+    username: String,
+    expires: i64,
+}
+
+impl Session {
+    pub fn new(token: String, username: String, expires: i64) -> Self {
+        Self { token, username, expires }
+    }
+}
@@ -0,0 +1,51 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{Error, Result};
+
+/// What a share token allows on the list it was minted for.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ShareScope {
+    #[default]
+    Read,
+    /// Reading and reserving products.
+    Reserve,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ShareInput {
+    #[serde(default)]
+    scope: ShareScope,
+    /// Seconds until the token expires, it never does if unset.
+    expires_in: Option<u64>,
+}
+
+/// Minted share token, passed as `?token=` to read the list.
+#[derive(Serialize, Clone, Debug)]
+pub struct ShareLink {
+    token: String,
+    list: String,
+    scope: ShareScope,
+    expires: Option<i64>,
+}
+
+impl ShareInput {
+    pub fn get_scope(&self) -> ShareScope {
+        self.scope
+    }
+
+    /// Returns the unix timestamp the token expires at, relative to `now`.
+    pub fn get_expires(&self, now: i64) -> Result<Option<i64>> {
+        match self.expires_in {
+            Some(0) => Err(Error::Validation("expires_in must be positive".to_string())),
+            Some(seconds) => Ok(Some(now.saturating_add(seconds.min(i64::MAX as u64) as i64))),
+            None => Ok(None),
+        }
+    }
+}
+
+impl ShareLink {
+    pub fn new(token: String, list: String, scope: ShareScope, expires: Option<i64>) -> Self {
+        Self { token, list, scope, expires }
+    }
+}
@@ -1,20 +1,175 @@
-use mongodb::bson::{document::Document, oid::ObjectId};
-use serde::Serialize;
+use mongodb::bson::{doc, document::Document, oid::ObjectId, Bson};
+use serde::{Deserialize, Serialize};
 
-#[derive(Serialize, Clone, Debug)]
+use super::Stored;
+use crate::{Error, Result};
+
+const MAX_REQUESTS_PER_MINUTE: u32 = 600;
+const MAX_CONCURRENCY: u32 = 16;
+const MAX_USER_AGENT_LENGTH: usize = 200;
+const MAX_AFFILIATE_LENGTH: usize = 100;
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct Source {
-    #[serde(skip)]
+    #[serde(rename(deserialize = "_id"), skip_serializing)]
     id: Option<ObjectId>,
     name: Option<String>,
     url: Option<String>,
+    #[serde(default, skip_serializing)]
+    politeness: Politeness,
+    #[serde(default, skip_serializing)]
+    affiliate: Option<Affiliate>,
+}
+
+/// How gently the pages of a source are requested, read by the scraper and
+/// the jobs requesting product pages. Unset limits fall back to the defaults
+/// of the backend.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Politeness {
+    requests_per_minute: Option<u32>,
+    /// Requests to the source at the same time.
+    max_concurrency: Option<u32>,
+    user_agent: Option<String>,
+    /// Leaves out the pages the `robots.txt` of the source disallows.
+    #[serde(default)]
+    respect_robots: bool,
+}
+
+/// Query parameter added to the shop links of the products of a source when
+/// they are shown, like `tag=mylist-21` for Amazon. Stored URLs are kept as
+/// they were scraped.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct Affiliate {
+    parameter: String,
+    value: String,
+}
+
+/// Sets the affiliate parameter of a source, without `parameter` it is removed.
+#[derive(Deserialize, Debug)]
+pub struct AffiliateInput {
+    parameter: Option<String>,
+    value: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct PolitenessInput {
+    requests_per_minute: Option<u32>,
+    max_concurrency: Option<u32>,
+    user_agent: Option<String>,
+    #[serde(default)]
+    respect_robots: bool,
 }
 
-impl From<&Document> for Source {
-    fn from(doc: &Document) -> Self {
-        Self {
-            id: doc.get_object_id("_id").map(|id| id.clone()).ok(),
-            name: doc.get_str("name").map(String::from).ok(),
-            url: doc.get_str("url").map(String::from).ok(),
+impl Source {
+    pub fn get_id(&self) -> Option<&ObjectId> {
+        self.id.as_ref()
+    }
+    pub fn get_name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+    pub fn get_url(&self) -> Option<&str> {
+        self.url.as_deref()
+    }
+    pub fn get_politeness(&self) -> &Politeness {
+        &self.politeness
+    }
+    pub fn get_affiliate(&self) -> Option<&Affiliate> {
+        self.affiliate.as_ref()
+    }
+}
+
+impl Affiliate {
+    /// Adds the parameter to `url`, replacing one of the same name. URLs that
+    /// can't be parsed are returned as they are.
+    pub fn apply(&self, url: &str) -> String {
+        let mut parsed = match reqwest::Url::parse(url) {
+            Ok(parsed) if parsed.scheme() == "http" || parsed.scheme() == "https" => parsed,
+            _ => return url.to_string(),
+        };
+        let pairs: Vec<(String, String)> = parsed.query_pairs()
+            .filter(|(name, _)| *name != self.parameter)
+            .map(|(name, value)| (name.into_owned(), value.into_owned()))
+            .collect();
+        parsed.query_pairs_mut()
+            .clear()
+            .extend_pairs(pairs)
+            .append_pair(&self.parameter, &self.value);
+        parsed.into()
+    }
+}
+
+impl AffiliateInput {
+    /// Validates the input and converts it into the fields to set on the source document.
+    pub fn to_document(&self) -> Result<Document> {
+        let trimmed = |value: &Option<String>| value.as_deref().map(str::trim).filter(|v| !v.is_empty()).map(String::from);
+        let (parameter, value) = match (trimmed(&self.parameter), trimmed(&self.value)) {
+            (None, _) => return Ok(doc! {"affiliate": Bson::Null}),
+            (Some(_), None) => return Err(Error::Validation("value is required with parameter".to_string())),
+            (Some(parameter), Some(value)) => (parameter, value),
+        };
+        let valid_parameter = parameter.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-' || c == '.');
+        if !valid_parameter || parameter.len() > MAX_AFFILIATE_LENGTH {
+            return Err(Error::Validation(format!(
+                "parameter must be at most {} letters, digits, '_', '-' or '.'",
+                MAX_AFFILIATE_LENGTH
+            )));
+        }
+        if value.len() > MAX_AFFILIATE_LENGTH || value.chars().any(char::is_control) {
+            return Err(Error::Validation(format!("value must be at most {} printable characters", MAX_AFFILIATE_LENGTH)));
         }
+        Ok(doc! {"affiliate": {"parameter": parameter, "value": value}})
+    }
+}
+
+impl Politeness {
+    pub fn get_requests_per_minute(&self) -> Option<u32> {
+        self.requests_per_minute
+    }
+    pub fn get_max_concurrency(&self) -> Option<u32> {
+        self.max_concurrency
+    }
+    pub fn get_user_agent(&self) -> Option<&str> {
+        self.user_agent.as_deref()
+    }
+    pub fn is_respecting_robots(&self) -> bool {
+        self.respect_robots
     }
 }
+
+impl PolitenessInput {
+    /// Validates the input and converts it into the fields to set on the source document.
+    pub fn to_document(&self) -> Result<Document> {
+        if let Some(rate) = self.requests_per_minute {
+            if rate == 0 || rate > MAX_REQUESTS_PER_MINUTE {
+                return Err(Error::Validation(format!("requests_per_minute must be between 1 and {}", MAX_REQUESTS_PER_MINUTE)));
+            }
+        }
+        if let Some(concurrency) = self.max_concurrency {
+            if concurrency == 0 || concurrency > MAX_CONCURRENCY {
+                return Err(Error::Validation(format!("max_concurrency must be between 1 and {}", MAX_CONCURRENCY)));
+            }
+        }
+        let user_agent = self.user_agent.as_deref().map(str::trim).filter(|agent| !agent.is_empty());
+        if let Some(agent) = user_agent {
+            if agent.len() > MAX_USER_AGENT_LENGTH || agent.chars().any(char::is_control) {
+                return Err(Error::Validation(format!(
+                    "user_agent must be at most {} printable characters",
+                    MAX_USER_AGENT_LENGTH
+                )));
+            }
+        }
+        let optional = |value: Option<u32>| value.map(|v| Bson::Int64(v.into())).unwrap_or(Bson::Null);
+        Ok(doc! {
+            "politeness": {
+                "requests_per_minute": optional(self.requests_per_minute),
+                "max_concurrency": optional(self.max_concurrency),
+                "user_agent": user_agent.map(Bson::from).unwrap_or(Bson::Null),
+                "respect_robots": self.respect_robots,
+            }
+        })
+    }
+}
+
+impl Stored for Source {
+    const COLLECTION: &'static str = "source";
+}
@@ -0,0 +1,105 @@
+use mongodb::bson::{document::Document, oid::ObjectId, Bson};
+use serde::{Deserialize, Serialize};
+
+use super::{Currency, Money};
+use crate::{Error, Result};
+
+/// Overview of the products of a list, computed by the repository in one go.
+///
+/// The prices are summed up per currency, the totals are only set once
+/// `compute_totals` brought them into one currency.
+#[derive(Serialize, Clone, Debug)]
+pub struct Stats {
+    product_count: u64,
+    archived_count: u64,
+    categories: Vec<StatsGroup>,
+    sources: Vec<StatsGroup>,
+    #[serde(skip)]
+    current: Vec<CurrencyTotal>,
+    /// Sum of the prices of the products not archived.
+    wishlist_total_price: Money,
+    wishlist_average_price: Option<Money>,
+    /// Products with a want level, archived ones included.
+    rated_count: u64,
+    /// Average want level of the rated products rounded to two decimals,
+    /// `None` if there are none.
+    average_rating: Option<f64>,
+    /// Timestamp of the latest snapshot, `None` before the first one.
+    last_snapshot: Option<i32>,
+}
+
+/// Document the statistics aggregation outputs.
+#[derive(Deserialize)]
+struct StatsDocument {
+    product_count: u64,
+    archived_count: u64,
+    categories: Vec<StatsGroup>,
+    sources: Vec<StatsGroup>,
+    current: Vec<CurrencyTotal>,
+    rated_count: u64,
+    average_rating: Option<f64>,
+    last_snapshot: Option<i32>,
+}
+
+/// Number and summed up prices of the current products in one currency,
+/// products without a currency are priced in euro.
+#[derive(Deserialize, Clone, Debug)]
+struct CurrencyTotal {
+    #[serde(rename = "_id", default)]
+    currency: Option<Currency>,
+    count: i64,
+    total: i64,
+}
+
+/// Number of products sharing a category or source, most products first.
+/// Uncategorized products are counted with a `None` id.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct StatsGroup {
+    #[serde(rename(deserialize = "_id"), serialize_with = "super::serialize_object_id")]
+    id: Option<ObjectId>,
+    name: Option<String>,
+    count: u64,
+}
+
+impl Stats {
+    /// Deserializes the document the statistics aggregation outputs.
+    pub fn from_document(doc: Document) -> Result<Self> {
+        let deserializer = bson::Deserializer::new(Bson::Document(doc));
+        let stats: StatsDocument = serde_path_to_error::deserialize(deserializer).map_err(|e| Error::Deserialize {
+            model: "stats",
+            field: e.path().to_string(),
+            reason: e.inner().to_string(),
+        })?;
+        Ok(Self {
+            product_count: stats.product_count,
+            archived_count: stats.archived_count,
+            categories: stats.categories,
+            sources: stats.sources,
+            current: stats.current,
+            wishlist_total_price: Money::zero(Currency::default()),
+            wishlist_average_price: None,
+            rated_count: stats.rated_count,
+            average_rating: stats.average_rating.map(|average| (average * 100.0).round() / 100.0),
+            last_snapshot: stats.last_snapshot,
+        })
+    }
+
+    /// Sets the total and average price in `currency`, `convert` converting
+    /// the price totals of the other currencies into it.
+    pub fn compute_totals<F>(&mut self, currency: Currency, mut convert: F) -> Result<()>
+    where
+        F: FnMut(Money) -> Result<Money>,
+    {
+        let mut total = Money::zero(currency);
+        let mut count = 0;
+        for subtotal in &self.current {
+            let converted = convert(Money::new(subtotal.total, subtotal.currency.unwrap_or_default()))?;
+            total = total.checked_add(converted)
+                .ok_or_else(|| Error::Validation(format!("the total price can't be shown in {}", currency)))?;
+            count += subtotal.count;
+        }
+        self.wishlist_total_price = total;
+        self.wishlist_average_price = total.checked_div_rounded(count);
+        Ok(())
+    }
+}
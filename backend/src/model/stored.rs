@@ -0,0 +1,32 @@
+use mongodb::bson::{document::Document, Bson};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fmt::Debug;
+
+use crate::{Error, Result};
+
+/// Model read from a collection of its own, named by `COLLECTION`.
+///
+/// Documents are only ever read as the model. Writes go through the validated
+/// documents built from the inputs, as the `Serialize` impls give the API shape.
+pub trait Stored: Serialize + DeserializeOwned + Debug + Unpin + Send + Sync {
+    const COLLECTION: &'static str;
+
+    /// Fills in what older documents lack, applied to everything read.
+    fn complete(self) -> Self {
+        self
+    }
+
+    /// Deserializes a stored document, naming the offending field if the
+    /// document doesn't match.
+    fn from_document(doc: Document) -> Result<Self> {
+        let deserializer = bson::Deserializer::new(Bson::Document(doc));
+        serde_path_to_error::deserialize(deserializer)
+            .map(Self::complete)
+            .map_err(|e| Error::Deserialize {
+                model: Self::COLLECTION,
+                field: e.path().to_string(),
+                reason: e.inner().to_string(),
+            })
+    }
+}
@@ -0,0 +1,14 @@
+use serde::Serialize;
+
+/// Product and category names starting with a prefix, for search boxes.
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct Suggestions {
+    products: Vec<String>,
+    categories: Vec<String>,
+}
+
+impl Suggestions {
+    pub fn new(products: Vec<String>, categories: Vec<String>) -> Self {
+        Self { products, categories }
+    }
+}
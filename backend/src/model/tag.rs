@@ -0,0 +1,25 @@
+use serde::{Deserialize, Serialize};
+
+use super::{Product, Stored};
+
+/// Number of products carrying a tag.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct TagCount {
+    #[serde(rename(deserialize = "_id"))]
+    tag: String,
+    count: u64,
+}
+
+impl TagCount {
+    pub fn get_tag(&self) -> &str {
+        &self.tag
+    }
+    pub fn get_count(&self) -> u64 {
+        self.count
+    }
+}
+
+/// Read from the products grouped by their tags.
+impl Stored for TagCount {
+    const COLLECTION: &'static str = Product::COLLECTION;
+}
@@ -1,3 +1,40 @@
+use serde::Serialize;
 
+use super::Product;
 
+/// Change of a list from one snapshot to the next, at the time of the later one.
+#[derive(Serialize, Clone, Debug)]
+pub struct TimelineEntry {
+    timestamp: Option<i32>,
+    kind: TimelineKind,
+    product: Product,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    old_price: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    new_price: Option<i32>,
+}
 
+#[derive(Serialize, Clone, Copy, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum TimelineKind {
+    ProductAdded,
+    ProductRemoved,
+    PriceChanged,
+}
+
+impl TimelineEntry {
+    pub fn new(timestamp: Option<i32>, kind: TimelineKind, product: Product) -> Self {
+        Self {
+            timestamp,
+            kind,
+            product,
+            old_price: None,
+            new_price: None,
+        }
+    }
+    pub fn with_prices(mut self, old_price: Option<i32>, new_price: Option<i32>) -> Self {
+        self.old_price = old_price;
+        self.new_price = new_price;
+        self
+    }
+}
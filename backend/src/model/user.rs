@@ -0,0 +1,81 @@
+use mongodb::bson::{doc, document::Document, oid::ObjectId};
+use serde::{Deserialize, Serialize};
+
+use super::Stored;
+use crate::{Error, Result};
+
+const MIN_USERNAME_LENGTH: usize = 3;
+const MAX_USERNAME_LENGTH: usize = 32;
+const MIN_PASSWORD_LENGTH: usize = 8;
+const MAX_PASSWORD_LENGTH: usize = 128;
+
+/// Account owning wishlists. The password hash is stored but never serialized.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct User {
+    #[serde(rename(deserialize = "_id"), serialize_with = "super::serialize_object_id")]
+    id: Option<ObjectId>,
+    username: String,
+    #[serde(skip_serializing)]
+    password_hash: String,
+    created: Option<i32>,
+}
+
+/// Credentials sent to register and to log in.
+#[derive(Deserialize, Debug)]
+pub struct UserInput {
+    username: String,
+    password: String,
+}
+
+impl User {
+    pub fn get_id(&self) -> Option<&ObjectId> {
+        self.id.as_ref()
+    }
+    pub fn get_username(&self) -> &str {
+        &self.username
+    }
+    pub fn get_password_hash(&self) -> &str {
+        &self.password_hash
+    }
+}
+
+impl UserInput {
+    pub fn get_username(&self) -> &str {
+        &self.username
+    }
+    pub fn get_password(&self) -> &str {
+        &self.password
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        let valid_chars = self.username.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_');
+        if self.username.len() < MIN_USERNAME_LENGTH || self.username.len() > MAX_USERNAME_LENGTH || !valid_chars {
+            return Err(Error::Validation(format!(
+                "username must be {} to {} lowercase letters, digits, dashes or underscores",
+                MIN_USERNAME_LENGTH, MAX_USERNAME_LENGTH
+            )));
+        }
+        if self.password.chars().count() < MIN_PASSWORD_LENGTH || self.password.len() > MAX_PASSWORD_LENGTH {
+            return Err(Error::Validation(format!(
+                "password must be between {} and {} characters",
+                MIN_PASSWORD_LENGTH, MAX_PASSWORD_LENGTH
+            )));
+        }
+        Ok(())
+    }
+
+    /// Converts the input into the document stored in the `user` collection,
+    /// the password has to be hashed by the caller.
+    pub fn to_document(&self, password_hash: String, timestamp: i32) -> Result<Document> {
+        self.validate()?;
+        Ok(doc! {
+            "username": self.username.as_str(),
+            "password_hash": password_hash,
+            "created": timestamp,
+        })
+    }
+}
+
+impl Stored for User {
+    const COLLECTION: &'static str = "user";
+}
@@ -0,0 +1,118 @@
+use mongodb::bson::oid::ObjectId;
+use serde::Serialize;
+
+use super::{Category, Currency, Money, Priority, Product};
+use crate::{Error, Result};
+
+/// Worth of the current products of a list in one currency, each product
+/// counted as often as it is wanted, and what is left of the list's budget.
+/// Products without a price are left out.
+#[derive(Serialize, Debug)]
+pub struct WishlistValue {
+    product_count: u64,
+    total: Money,
+    categories: Vec<CategoryValue>,
+    priorities: Vec<PriorityValue>,
+    budget: Option<Money>,
+    /// Budget left after buying every product, negative if the products
+    /// exceed it.
+    remaining: Option<Money>,
+}
+
+/// Products of a category and their worth, most valuable first.
+/// Uncategorized products are summed up with a `None` id.
+#[derive(Serialize, Debug)]
+pub struct CategoryValue {
+    #[serde(serialize_with = "super::serialize_object_id")]
+    id: Option<ObjectId>,
+    name: Option<String>,
+    count: u64,
+    total: Money,
+}
+
+/// Products of a priority and their worth, must-haves first.
+#[derive(Serialize, Debug)]
+pub struct PriorityValue {
+    priority: Option<Priority>,
+    count: u64,
+    total: Money,
+}
+
+impl WishlistValue {
+    /// Sums up the current price of `products` in `currency`, `convert`
+    /// converting the prices of the other currencies into it. The names of
+    /// the groups are looked up in `categories`.
+    pub fn compute<F>(products: &[Product], categories: &[Category], budget: Option<Money>, currency: Currency, mut convert: F) -> Result<Self>
+    where
+        F: FnMut(Money) -> Result<Money>,
+    {
+        let overflow = || Error::Validation(format!("the total price can't be shown in {}", currency));
+        let mut value = Self {
+            product_count: 0,
+            total: Money::zero(currency),
+            categories: Vec::new(),
+            priorities: Vec::new(),
+            budget: None,
+            remaining: None,
+        };
+        for product in products {
+            let price = match product.get_current_price().or_else(|| product.get_price()) {
+                Some(price) => price,
+                None => continue,
+            };
+            let quantity = product.get_quantity().unwrap_or(1).max(1);
+            let worth = convert(Money::new(price.into(), product.get_currency()))?
+                .checked_mul(quantity.into())
+                .ok_or_else(overflow)?;
+            value.product_count += 1;
+            value.total = value.total.checked_add(worth).ok_or_else(overflow)?;
+
+            let category = product.get_category_id();
+            match value.categories.iter_mut().find(|group| group.id.as_ref() == category) {
+                Some(group) => group.add(worth).ok_or_else(overflow)?,
+                None => value.categories.push(CategoryValue {
+                    id: category.cloned(),
+                    name: category
+                        .and_then(|id| categories.iter().find(|c| c.get_id() == Some(id)))
+                        .and_then(Category::get_name)
+                        .map(String::from),
+                    count: 1,
+                    total: worth,
+                }),
+            }
+            let priority = product.get_priority();
+            match value.priorities.iter_mut().find(|group| group.priority == priority) {
+                Some(group) => group.add(worth).ok_or_else(overflow)?,
+                None => value.priorities.push(PriorityValue { priority, count: 1, total: worth }),
+            }
+        }
+        value.categories.sort_by(|a, b| b.total.get_amount_minor().cmp(&a.total.get_amount_minor()).then_with(|| a.name.cmp(&b.name)));
+        value.priorities.sort_by_key(|group| match group.priority {
+            Some(Priority::MustHave) => 0,
+            Some(Priority::NiceToHave) => 1,
+            None => 2,
+        });
+        if let Some(budget) = budget {
+            let budget = convert(budget)?;
+            value.remaining = Some(budget.checked_sub(value.total).ok_or_else(overflow)?);
+            value.budget = Some(budget);
+        }
+        Ok(value)
+    }
+}
+
+impl CategoryValue {
+    fn add(&mut self, worth: Money) -> Option<()> {
+        self.total = self.total.checked_add(worth)?;
+        self.count += 1;
+        Some(())
+    }
+}
+
+impl PriorityValue {
+    fn add(&mut self, worth: Money) -> Option<()> {
+        self.total = self.total.checked_add(worth)?;
+        self.count += 1;
+        Some(())
+    }
+}
@@ -1,25 +1,31 @@
-use mongodb::bson::{document::Document, oid::ObjectId};
-use serde::Serialize;
-use std::iter::Iterator;
+use mongodb::bson::oid::ObjectId;
+use serde::{Deserialize, Serialize};
 
-use super::Product;
+use super::{Product, Stored};
 
-#[derive(Serialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct Wishlist {
-    #[serde(skip)]
+    #[serde(rename(deserialize = "_id"), skip_serializing)]
     id: Option<ObjectId>,
     timestamp: Option<i32>,
-    #[serde(skip)]
+    #[serde(rename(deserialize = "products"), skip_serializing)]
     product_ids: Option<Vec<ObjectId>>,
+    #[serde(skip_deserializing)]
     products: Option<Vec<Product>>,
 }
 
 impl Wishlist {
+    pub fn get_id(&self) -> Option<&ObjectId> {
+        self.id.as_ref()
+    }
     pub fn get_product_ids(&self) -> Option<&[ObjectId]> {
-        self.product_ids.as_ref().map(|e| e.as_slice())
+        self.product_ids.as_deref()
     }
     pub fn get_products(&self) -> Option<&[Product]> {
-        self.products.as_ref().map(|e| e.as_slice())
+        self.products.as_deref()
+    }
+    pub fn get_products_mut(&mut self) -> Option<&mut Vec<Product>> {
+        self.products.as_mut()
     }
     pub fn set_products(&mut self, products: Vec<Product>) {
         self.products = Some(products);
@@ -30,20 +36,24 @@ impl Wishlist {
     }
 }
 
-impl From<&Document> for Wishlist {
-    fn from(doc: &Document) -> Self {
-        Self {
-            id: doc.get_object_id("_id").map(|id| id.clone()).ok(),
-            timestamp: doc.get_i32("timestamp").ok(),
-            product_ids: doc
-                .get_array("products")
-                .map(|list| {
-                    list.into_iter()
-                        .filter_map(|e| e.as_object_id().map(|id| id.clone()))
-                        .collect()
-                })
-                .ok(),
-            products: None,
-        }
+impl WishlistSummary {
+    pub fn get_timestamp(&self) -> Option<i32> {
+        self.timestamp
     }
 }
+
+/// Snapshot without its products, as listed in the history.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct WishlistSummary {
+    timestamp: Option<i32>,
+    product_count: u64,
+}
+
+impl Stored for Wishlist {
+    const COLLECTION: &'static str = "wishlist";
+}
+
+/// Read from the snapshots with their product ids replaced by `product_count`.
+impl Stored for WishlistSummary {
+    const COLLECTION: &'static str = Wishlist::COLLECTION;
+}
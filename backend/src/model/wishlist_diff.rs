@@ -0,0 +1,78 @@
+use serde::Serialize;
+
+use super::{Product, TimelineEntry, TimelineKind};
+
+#[derive(Serialize, Clone, Debug)]
+pub struct WishlistDiff {
+    from: Option<i32>,
+    to: Option<i32>,
+    added: Vec<Product>,
+    removed: Vec<Product>,
+    price_changed: Vec<PriceChange>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct PriceChange {
+    product: Product,
+    old_price: Option<i32>,
+    new_price: Option<i32>,
+}
+
+impl WishlistDiff {
+    pub fn new(from: Option<i32>, to: Option<i32>) -> Self {
+        Self {
+            from,
+            to,
+            added: Vec::new(),
+            removed: Vec::new(),
+            price_changed: Vec::new(),
+        }
+    }
+    pub fn get_added(&self) -> &[Product] {
+        &self.added
+    }
+    pub fn get_removed(&self) -> &[Product] {
+        &self.removed
+    }
+    pub fn get_price_changed(&self) -> &[PriceChange] {
+        &self.price_changed
+    }
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.price_changed.is_empty()
+    }
+    /// Turns the diff into timeline entries at the time of the later snapshot,
+    /// the added products first, then the price changes and the removed products.
+    pub fn into_timeline(self) -> Vec<TimelineEntry> {
+        let timestamp = self.to;
+        let added = self.added.into_iter().map(|p| TimelineEntry::new(timestamp, TimelineKind::ProductAdded, p));
+        let price_changed = self.price_changed.into_iter()
+            .map(|c| TimelineEntry::new(timestamp, TimelineKind::PriceChanged, c.product).with_prices(c.old_price, c.new_price));
+        let removed = self.removed.into_iter().map(|p| TimelineEntry::new(timestamp, TimelineKind::ProductRemoved, p));
+        added.chain(price_changed).chain(removed).collect()
+    }
+    pub fn add_added(&mut self, product: Product) {
+        self.added.push(product);
+    }
+    pub fn add_removed(&mut self, product: Product) {
+        self.removed.push(product);
+    }
+    pub fn add_price_change(&mut self, product: Product, old_price: Option<i32>, new_price: Option<i32>) {
+        self.price_changed.push(PriceChange {
+            product,
+            old_price,
+            new_price,
+        });
+    }
+}
+
+impl PriceChange {
+    pub fn get_product(&self) -> &Product {
+        &self.product
+    }
+    pub fn get_old_price(&self) -> Option<i32> {
+        self.old_price
+    }
+    pub fn get_new_price(&self) -> Option<i32> {
+        self.new_price
+    }
+}
@@ -0,0 +1,53 @@
+use serde::Serialize;
+
+use super::Product;
+
+/// Change of the wishlist pushed to the subscribers of the event stream. The
+/// data only holds the fields, the kind is sent as the SSE event name.
+#[derive(Serialize, Clone, Debug)]
+#[serde(untagged)]
+pub enum WishlistEvent {
+    ProductAdded {
+        product: Product,
+    },
+    ProductRemoved {
+        product: Product,
+    },
+    PriceChanged {
+        product: Product,
+        old_price: Option<i32>,
+        new_price: i32,
+    },
+    /// A recorded price is lower than any before, published along with the `PriceChanged`.
+    AllTimeLow {
+        product: Product,
+        old_lowest_price: i32,
+        new_price: i32,
+    },
+    /// A product was added or changed in the database, by the API or directly.
+    ProductChanged {
+        product: Product,
+    },
+    /// A snapshot of `list` was stored in the database, by the API or directly.
+    SnapshotChanged {
+        list: String,
+    },
+    /// A category was added, changed or deleted in the database.
+    CategoryChanged {
+        category_id: String,
+    },
+}
+
+impl WishlistEvent {
+    pub fn get_name(&self) -> &'static str {
+        match self {
+            WishlistEvent::ProductAdded { .. } => "product_added",
+            WishlistEvent::ProductRemoved { .. } => "product_removed",
+            WishlistEvent::PriceChanged { .. } => "price_changed",
+            WishlistEvent::AllTimeLow { .. } => "all_time_low",
+            WishlistEvent::ProductChanged { .. } => "product_changed",
+            WishlistEvent::SnapshotChanged { .. } => "snapshot_changed",
+            WishlistEvent::CategoryChanged { .. } => "category_changed",
+        }
+    }
+}
@@ -0,0 +1,100 @@
+use chrono::{DateTime, TimeZone, Utc};
+use std::convert::Infallible;
+use std::sync::Arc;
+use warp::filters::path::FullPath;
+use warp::http::{header, HeaderValue, Method, Response, StatusCode};
+use warp::hyper::Body;
+
+use crate::model::DEFAULT_LIST;
+use crate::repository::WishlistRepository;
+use crate::versions::strip_version;
+
+/// Listings of one list, after `/api` or `/api/list/{slug}`.
+const LIST_LISTINGS: [&str; 10] = [
+    "wishlist/last",
+    "wishlist/history",
+    "wishlist/diff",
+    "product/newest",
+    "product/popular",
+    "product/archive",
+    "product/archive/search",
+    "tags",
+    "timeline",
+    "feed.xml",
+];
+/// Listings of the products of all lists, after `/api`.
+const GLOBAL_LISTINGS: [&str; 3] = ["product/search", "product/category", "product/uncategorized"];
+const HTTP_DATE_FORMAT: &str = "%a, %d %b %Y %H:%M:%S GMT";
+
+/// Adds `Last-Modified` to successful GET responses of the listings, the
+/// latest snapshot or product change of the listed list, and replaces the
+/// response with `304 Not Modified` if it isn't newer than `If-Modified-Since`.
+/// Like HTTP says, `If-Modified-Since` is ignored if `If-None-Match` is sent.
+pub async fn apply_last_modified(
+    path: FullPath,
+    method: Method,
+    if_modified_since: Option<String>,
+    if_none_match: Option<String>,
+    repo: Arc<dyn WishlistRepository>,
+    mut response: Response<Body>,
+) -> Result<Response<Body>, Infallible> {
+    if method != Method::GET || !response.status().is_success() {
+        return Ok(response);
+    }
+    let list = match get_listed_list(path.as_str()) {
+        Some(list) => list,
+        None => return Ok(response),
+    };
+    let last_modified = match repo.get_last_modified(list.as_deref()).await {
+        Ok(Some(timestamp)) => match Utc.timestamp_opt(timestamp as i64, 0).single() {
+            Some(time) => time,
+            None => return Ok(response),
+        },
+        Ok(None) => return Ok(response),
+        Err(e) => {
+            error!("Could not get the last modification for Last-Modified: {}", e);
+            return Ok(response);
+        }
+    };
+    let last_modified_value = match HeaderValue::from_str(&last_modified.format(HTTP_DATE_FORMAT).to_string()) {
+        Ok(value) => value,
+        Err(_) => return Ok(response),
+    };
+
+    let since = if_modified_since.filter(|_| if_none_match.is_none()).and_then(|since| parse_http_date(&since));
+    if since.map(|since| last_modified <= since).unwrap_or(false) {
+        let mut not_modified = Response::new(Body::empty());
+        *not_modified.status_mut() = StatusCode::NOT_MODIFIED;
+        let headers = not_modified.headers_mut();
+        headers.insert(header::LAST_MODIFIED, last_modified_value);
+        if let Some(etag) = response.headers().get(header::ETAG) {
+            headers.insert(header::ETAG, etag.clone());
+        }
+        return Ok(not_modified);
+    }
+    response.headers_mut().insert(header::LAST_MODIFIED, last_modified_value);
+    Ok(response)
+}
+
+/// List whose changes `path` lists, `Some(None)` for the listings of all
+/// lists and `None` if `path` is no listing.
+fn get_listed_list(path: &str) -> Option<Option<String>> {
+    let rest = strip_version(path);
+    let rest = rest.strip_prefix("/api/")?;
+    if GLOBAL_LISTINGS.contains(&rest) {
+        return Some(None);
+    }
+    if LIST_LISTINGS.contains(&rest) {
+        return Some(Some(DEFAULT_LIST.to_string()));
+    }
+    let (slug, listing) = rest.strip_prefix("list/")?.split_once('/')?;
+    if LIST_LISTINGS.contains(&listing) {
+        Some(Some(slug.to_string()))
+    } else {
+        None
+    }
+}
+
+fn parse_http_date(date: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc2822(date.trim()).ok().map(|date| date.with_timezone(&Utc))
+}
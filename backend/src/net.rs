@@ -0,0 +1,72 @@
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+use crate::{Error, Result};
+
+/// Refuses urls that aren't http(s) or resolve to an address of the local
+/// network, so urls from outside can't be used to reach internal services.
+/// `what` names the url in errors.
+pub(crate) async fn check_public_host(url: &reqwest::Url, what: &str) -> Result<()> {
+    let forbidden = || Error::Validation(format!("{} url must point to a public http(s) host", what));
+    if is_forbidden_host(url) {
+        return Err(forbidden());
+    }
+    let host = url.host_str().ok_or_else(forbidden)?;
+    let port = url.port_or_known_default().unwrap_or(80);
+    let addresses = tokio::net::lookup_host((host.trim_start_matches('[').trim_end_matches(']'), port))
+        .await
+        .map_err(|e| Error::Unavailable(format!("could not resolve {} host: {}", what, e)))?;
+    let mut resolved = false;
+    for address in addresses {
+        if !is_public_ip(address.ip()) {
+            return Err(forbidden());
+        }
+        resolved = true;
+    }
+    if resolved {
+        Ok(())
+    } else {
+        Err(Error::Unavailable(format!("could not resolve {} host", what)))
+    }
+}
+
+pub(crate) fn is_forbidden_host(url: &reqwest::Url) -> bool {
+    if url.scheme() != "http" && url.scheme() != "https" {
+        return true;
+    }
+    let host = match url.host_str() {
+        Some(host) => host.trim_start_matches('[').trim_end_matches(']').trim_end_matches('.').to_lowercase(),
+        None => return true,
+    };
+    match host.parse::<IpAddr>() {
+        Ok(ip) => !is_public_ip(ip),
+        Err(_) => host == "localhost" || host.ends_with(".localhost"),
+    }
+}
+
+fn is_public_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_public_ipv4(ip),
+        IpAddr::V6(ip) => match ip.to_ipv4() {
+            Some(mapped) if ip.segments()[..5].iter().all(|s| *s == 0) => is_public_ipv4(mapped),
+            _ => is_public_ipv6(ip),
+        },
+    }
+}
+
+fn is_public_ipv4(ip: Ipv4Addr) -> bool {
+    let octets = ip.octets();
+    !(ip.is_private()
+        || ip.is_loopback()
+        || ip.is_link_local()
+        || ip.is_unspecified()
+        || ip.is_broadcast()
+        || ip.is_documentation()
+        || octets[0] == 0
+        || (octets[0] == 100 && (octets[1] & 0xc0) == 64)
+        || octets[0] >= 240)
+}
+
+fn is_public_ipv6(ip: Ipv6Addr) -> bool {
+    let first = ip.segments()[0];
+    !(ip.is_loopback() || ip.is_unspecified() || (first & 0xfe00) == 0xfc00 || (first & 0xffc0) == 0xfe80)
+}
@@ -0,0 +1,237 @@
+use super::smtp::{is_valid_address, Mail, SmtpClient};
+use crate::config::EmailConfig;
+use crate::feed::escape;
+use crate::model::{List, Money, Product, WishlistDiff};
+use crate::{Error, Result};
+
+const TEMPLATE: &str = include_str!("email.html");
+/// Products listed per section, the rest is only counted.
+const MAX_SECTION_ITEMS: usize = 50;
+
+/// Sends notification mails through the configured SMTP relay.
+#[derive(Clone)]
+pub struct EmailChannel {
+    client: SmtpClient,
+    from: String,
+    to: Vec<String>,
+    new_products: bool,
+    price_drops: bool,
+}
+
+/// Subject and content of a mail, in plain text and HTML.
+pub struct EmailContent {
+    subject: String,
+    text: String,
+    html: String,
+}
+
+struct Section {
+    title: String,
+    items: Vec<Item>,
+}
+
+struct Item {
+    name: String,
+    detail: String,
+    url: Option<String>,
+}
+
+impl EmailChannel {
+    /// Returns `None` unless the SMTP host, the sender and a recipient are configured.
+    pub fn from_config(config: &EmailConfig) -> Result<Option<Self>> {
+        let (host, from) = match (&config.smtp_host, &config.from) {
+            (Some(host), Some(from)) if !config.to.is_empty() => (host, from),
+            _ => return Ok(None),
+        };
+        if let Some(address) = config.to.iter().chain(std::iter::once(from)).find(|a| !is_valid_address(a)) {
+            return Err(Error::Config(format!("'{}' is not a valid email address", address)));
+        }
+        Ok(Some(Self {
+            client: SmtpClient::new(host.clone(), config),
+            from: from.clone(),
+            to: config.to.clone(),
+            new_products: config.new_products,
+            price_drops: config.price_drops,
+        }))
+    }
+
+    pub fn sends_new_products(&self) -> bool {
+        self.new_products
+    }
+    pub fn sends_price_drops(&self) -> bool {
+        self.price_drops
+    }
+
+    pub async fn send(&self, content: &EmailContent) -> std::result::Result<(), String> {
+        self.send_to(&self.to, content).await
+    }
+
+    /// Sends to `to` instead of the configured recipients.
+    pub async fn send_to(&self, to: &[String], content: &EmailContent) -> std::result::Result<(), String> {
+        let mail = Mail {
+            from: &self.from,
+            to,
+            subject: &content.subject,
+            text: &content.text,
+            html: &content.html,
+        };
+        self.client.send(&mail).await
+    }
+}
+
+impl EmailContent {
+    pub fn get_subject(&self) -> &str {
+        &self.subject
+    }
+
+    /// Mail about the products `list` got by its newest snapshot.
+    pub fn new_products(list: &List, products: &[Product]) -> Self {
+        let name = list_name(list);
+        let section = Section {
+            title: format!("New products ({})", products.len()),
+            items: products.iter().map(|p| Item::new(p, format_price(p))).collect(),
+        };
+        Self::render(
+            format!("New products on wishlist '{}'", name),
+            format!("The newest snapshot of wishlist '{}' has {} new products.", name, products.len()),
+            vec![section],
+        )
+    }
+
+    /// Mail about the price of `product` dropping, `note` explains why it is noteworthy.
+    pub fn price_drop(product: &Product, old_price: i32, new_price: i32, note: &str) -> Self {
+        let name = product.get_name().unwrap_or("A product");
+        let old_price = Money::new(old_price.into(), product.get_currency());
+        let new_price = Money::new(new_price.into(), product.get_currency());
+        let section = Section {
+            title: "Price drop".to_string(),
+            items: vec![Item::new(product, format!("{} → {}", old_price, new_price))],
+        };
+        Self::render(
+            format!("Price drop: {} now {}", name, new_price),
+            format!("{} dropped from {} to {} ({}).", name, old_price, new_price, note),
+            vec![section],
+        )
+    }
+
+    /// Digest of the changes of each list over the last `days`, `None` if nothing changed.
+    pub fn digest(changes: &[(List, WishlistDiff)], days: u64) -> Option<Self> {
+        let sections: Vec<Section> = changes.iter()
+            .filter(|(_, diff)| !diff.is_empty())
+            .map(|(list, diff)| {
+                let mut items: Vec<Item> = Vec::new();
+                items.extend(diff.get_added().iter().map(|p| Item::new(p, format!("added, {}", format_price(p)))));
+                items.extend(diff.get_removed().iter().map(|p| Item::new(p, "removed".to_string())));
+                items.extend(diff.get_price_changed().iter().map(|change| {
+                    let product = change.get_product();
+                    let format = |price: Option<i32>| match price {
+                        Some(price) => Money::new(price.into(), product.get_currency()).to_string(),
+                        None => "–".to_string(),
+                    };
+                    Item::new(product, format!("{} → {}", format(change.get_old_price()), format(change.get_new_price())))
+                }));
+                Section {
+                    title: format!(
+                        "{}: {} added, {} removed, {} price changes",
+                        list_name(list),
+                        diff.get_added().len(),
+                        diff.get_removed().len(),
+                        diff.get_price_changed().len()
+                    ),
+                    items,
+                }
+            })
+            .collect();
+        if sections.is_empty() {
+            return None;
+        }
+        Some(Self::render(
+            format!("Wishlist digest: {} lists changed", sections.len()),
+            format!("Changes of your wishlists over the last {} days.", days),
+            sections,
+        ))
+    }
+
+    fn render(subject: String, intro: String, sections: Vec<Section>) -> Self {
+        let mut text = format!("{}\n", intro);
+        let mut html = String::new();
+        for section in sections.iter() {
+            text.push_str(&format!("\n{}\n", section.title));
+            html.push_str(&format!(
+                "<h2 style=\"font-size: 16px; margin-bottom: 8px;\">{}</h2>\n<ul style=\"padding-left: 20px;\">\n",
+                escape(&section.title)
+            ));
+            for item in section.items.iter().take(MAX_SECTION_ITEMS) {
+                text.push_str(&format!("- {}: {}", item.name, item.detail));
+                let name = escape(&item.name);
+                let name = match &item.url {
+                    Some(url) => {
+                        text.push_str(&format!("\n  {}", url));
+                        format!("<a href=\"{}\" style=\"color: #1a73e8;\">{}</a>", escape(url), name)
+                    }
+                    None => name,
+                };
+                text.push('\n');
+                html.push_str(&format!("<li style=\"margin-bottom: 4px;\">{} <span style=\"color: #666;\">{}</span></li>\n", name, escape(&item.detail)));
+            }
+            if section.items.len() > MAX_SECTION_ITEMS {
+                let more = format!("… and {} more", section.items.len() - MAX_SECTION_ITEMS);
+                text.push_str(&format!("{}\n", more));
+                html.push_str(&format!("<li style=\"color: #666;\">{}</li>\n", more));
+            }
+            html.push_str("</ul>\n");
+        }
+        let html = fill_template(TEMPLATE, &[
+            ("title", &escape(&subject)),
+            ("intro", &escape(&intro)),
+            ("sections", &html),
+        ]);
+        Self { subject, text, html }
+    }
+}
+
+impl Item {
+    fn new(product: &Product, detail: String) -> Self {
+        Self {
+            name: product.get_name().unwrap_or("A product").to_string(),
+            detail,
+            url: product.get_url()
+                .filter(|url| url.starts_with("https://") || url.starts_with("http://"))
+                .map(String::from),
+        }
+    }
+}
+
+/// Replaces the `{{name}}` placeholders of `template` in one pass, so values
+/// containing placeholders are inserted as they are.
+fn fill_template(template: &str, values: &[(&str, &str)]) -> String {
+    let mut filled = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        filled.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        match after.find("}}").and_then(|end| values.iter().find(|(k, _)| *k == &after[..end]).map(|(_, v)| (end, v))) {
+            Some((end, value)) => {
+                filled.push_str(value);
+                rest = &after[end + 2..];
+            }
+            None => {
+                filled.push_str("{{");
+                rest = after;
+            }
+        }
+    }
+    filled.push_str(rest);
+    filled
+}
+
+fn list_name(list: &List) -> &str {
+    list.get_name().unwrap_or(list.get_slug())
+}
+
+fn format_price(product: &Product) -> String {
+    match product.get_price() {
+        Some(price) => Money::new(price.into(), product.get_currency()).to_string(),
+        None => "no price".to_string(),
+    }
+}
@@ -0,0 +1,366 @@
+use serde::Serialize;
+use std::time::Duration;
+use tokio::sync::broadcast;
+
+use std::sync::Arc;
+
+use crate::config::{EmailConfig, PushConfig, TelegramConfig};
+use crate::model::{List, Money, PriceAlert, Product, WishlistDiff, WishlistEvent};
+use crate::repository::WishlistRepository;
+use crate::{Error, Result};
+
+mod email;
+mod push;
+mod smtp;
+mod telegram;
+
+use self::email::{EmailChannel, EmailContent};
+use self::push::{PushMessage, PushSender};
+use self::telegram::TelegramBot;
+
+pub(crate) use self::smtp::is_valid_address;
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+/// Events a slow subscriber may fall behind before it misses some.
+const EVENT_BUFFER: usize = 64;
+/// Length limit of a Telegram message.
+const MAX_MESSAGE_LENGTH: usize = 4096;
+/// Products listed per section of a snapshot summary.
+const MAX_SUMMARY_LINES: usize = 20;
+
+/// Sends price drop notifications to webhooks and broadcasts wishlist events
+/// to the subscribers of the event stream.
+///
+/// The payload carries both `content` and `text`, so it can be posted to
+/// Discord and Slack incoming webhooks alike. A notifier without webhooks
+/// only broadcasts events. With a Telegram bot, a summary of every new
+/// snapshot is sent to its chat. With email, price drops, new products and
+/// the digests of `send_digest` are mailed. With VAPID keys, price drops are
+/// pushed to the browsers subscribed by users who can read the product's list.
+/// Price alerts go to their own email address or webhook, or to all of these
+/// if they have neither.
+#[derive(Clone)]
+pub struct Notifier {
+    client: reqwest::Client,
+    webhooks: Vec<String>,
+    events: broadcast::Sender<WishlistEvent>,
+    telegram: Option<TelegramBot>,
+    email: Option<EmailChannel>,
+    push: Option<PushSender>,
+}
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum DropReason {
+    BelowThreshold,
+    AllTimeLow,
+    TargetReached,
+}
+
+#[derive(Serialize)]
+struct Payload<'a> {
+    content: &'a str,
+    text: &'a str,
+    reason: DropReason,
+    product_id: Option<String>,
+    name: Option<&'a str>,
+    url: Option<&'a str>,
+    old_price: Option<i32>,
+    new_price: i32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    target_price: Option<i32>,
+}
+
+impl Notifier {
+    pub fn new(webhooks: Vec<String>) -> Self {
+        let (events, _) = broadcast::channel(EVENT_BUFFER);
+        Self {
+            client: reqwest::Client::new(),
+            webhooks,
+            events,
+            telegram: None,
+            email: None,
+            push: None,
+        }
+    }
+
+    /// Sends snapshot summaries to Telegram, if the bot token and chat id are configured.
+    pub fn with_telegram(mut self, config: &TelegramConfig) -> Self {
+        self.telegram = TelegramBot::from_config(self.client.clone(), config);
+        self
+    }
+
+    pub fn has_telegram(&self) -> bool {
+        self.telegram.is_some()
+    }
+
+    /// Sends mails, if the SMTP host, the sender and a recipient are configured.
+    pub fn with_email(mut self, config: &EmailConfig) -> Result<Self> {
+        self.email = EmailChannel::from_config(config)?;
+        Ok(self)
+    }
+
+    pub fn has_email(&self) -> bool {
+        self.email.is_some()
+    }
+
+    /// Pushes price drops to browsers, if the VAPID keys and subject are configured.
+    pub fn with_push(mut self, config: &PushConfig, repo: Arc<dyn WishlistRepository>) -> Result<Self> {
+        self.push = PushSender::from_config(config, repo)?;
+        Ok(self)
+    }
+
+    /// Returns the VAPID public key browsers subscribe with, `None` if push is not configured.
+    pub fn get_push_key(&self) -> Option<&str> {
+        self.push.as_ref().map(PushSender::get_public_key)
+    }
+
+    /// Whether new snapshots are sent anywhere besides the event stream.
+    pub fn notifies_snapshots(&self) -> bool {
+        self.telegram.is_some() || self.email.as_ref().map(EmailChannel::sends_new_products).unwrap_or(false)
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<WishlistEvent> {
+        self.events.subscribe()
+    }
+
+    /// Whether anyone listens for events, lets callers skip building them.
+    pub fn has_subscribers(&self) -> bool {
+        self.events.receiver_count() > 0
+    }
+
+    pub fn publish(&self, event: WishlistEvent) {
+        // Fails only if nobody is subscribed, in which case the event is not needed.
+        let _ = self.events.send(event);
+    }
+
+    /// Notifies about a price change of `product`, whose fields still hold the
+    /// state before the change, if the new price is noteworthy.
+    pub fn notify_price_change(&self, product: &Product, old_price: Option<i32>, new_price: i32) {
+        let mail_drops = self.email.as_ref().map(EmailChannel::sends_price_drops).unwrap_or(false);
+        if self.webhooks.is_empty() && !mail_drops && self.push.is_none() {
+            return;
+        }
+        let old_price = match old_price {
+            Some(p) if new_price < p => p,
+            _ => return,
+        };
+        let reason = match (product.get_price_threshold(), product.get_lowest_price()) {
+            (Some(threshold), _) if new_price < threshold && old_price >= threshold => DropReason::BelowThreshold,
+            (_, Some(lowest)) if new_price < lowest => DropReason::AllTimeLow,
+            _ => return,
+        };
+        let note = match reason {
+            DropReason::BelowThreshold => "below your threshold",
+            DropReason::AllTimeLow => "all-time low",
+            DropReason::TargetReached => "target price reached",
+        };
+        if mail_drops {
+            self.spawn_mail(EmailContent::price_drop(product, old_price, new_price, note), None);
+        }
+
+        let message = format!(
+            "{} dropped from {} to {} ({})",
+            product.get_name().unwrap_or("A product"),
+            Money::new(old_price.into(), product.get_currency()),
+            Money::new(new_price.into(), product.get_currency()),
+            note
+        );
+        self.push_price_drop(product, &message);
+        let payload = Payload {
+            content: &message,
+            text: &message,
+            reason,
+            product_id: product.get_id().map(|id| id.to_hex()),
+            name: product.get_name(),
+            url: product.get_url(),
+            old_price: Some(old_price),
+            new_price,
+            target_price: None,
+        };
+        for webhook in self.webhooks.iter() {
+            self.post_payload(webhook, &payload);
+        }
+    }
+
+    /// Sends `alert`, whose target the price of `product` reached by
+    /// changing from `old_price` to `new_price`, to the email address and
+    /// webhook of the alert. Alerts without either go to the configured
+    /// webhooks, email recipients and push subscriptions.
+    pub fn notify_price_alert(&self, alert: &PriceAlert, product: &Product, old_price: Option<i32>, new_price: i32) {
+        let target = Money::new(alert.get_target_price().into(), product.get_currency());
+        let message = format!(
+            "{} dropped to {}, reaching your target of {}",
+            product.get_name().unwrap_or("A product"),
+            Money::new(new_price.into(), product.get_currency()),
+            target
+        );
+        let mail = || EmailContent::price_drop(product, old_price.unwrap_or(new_price), new_price, &format!("target of {} reached", target));
+        let payload = Payload {
+            content: &message,
+            text: &message,
+            reason: DropReason::TargetReached,
+            product_id: product.get_id().map(|id| id.to_hex()),
+            name: product.get_name(),
+            url: product.get_url(),
+            old_price,
+            new_price,
+            target_price: Some(alert.get_target_price()),
+        };
+        if alert.get_email().is_none() && alert.get_webhook().is_none() {
+            self.spawn_mail(mail(), None);
+            self.push_price_drop(product, &message);
+            for webhook in self.webhooks.iter() {
+                self.post_payload(webhook, &payload);
+            }
+            return;
+        }
+        if let Some(email) = alert.get_email() {
+            self.spawn_mail(mail(), Some(email.to_string()));
+        }
+        if let Some(webhook) = alert.get_webhook() {
+            self.post_payload(webhook, &payload);
+        }
+    }
+
+    fn push_price_drop(&self, product: &Product, message: &str) {
+        if let Some(push) = &self.push {
+            push.push_to_readers(product.get_list(), PushMessage {
+                title: "Price drop".to_string(),
+                body: message.to_string(),
+                url: product.get_url().map(String::from),
+                product_id: product.get_id().map(|id| id.to_hex()),
+            });
+        }
+    }
+
+    /// Posts a price drop to `webhook` in the background.
+    fn post_payload(&self, webhook: &str, payload: &Payload) {
+        let body = match serde_json::to_vec(payload) {
+            Ok(body) => body,
+            Err(e) => {
+                error!("Could not serialize notification: {}", e);
+                return;
+            }
+        };
+        let request = self.client
+            .post(webhook)
+            .timeout(REQUEST_TIMEOUT)
+            .header("content-type", "application/json")
+            .body(body);
+        let webhook = webhook.to_string();
+        tokio::spawn(async move {
+            match request.send().await.and_then(|r| r.error_for_status()) {
+                Ok(_) => info!("Sent price drop notification to {}", webhook),
+                Err(e) => warn!("Could not send price drop notification to {}: {}", webhook, e),
+            }
+        });
+    }
+
+    /// Sends the products added and removed by a new snapshot of `list` and its
+    /// price drops to Telegram and mails the products added, in the background.
+    pub fn notify_snapshot(&self, list: &List, diff: &WishlistDiff) {
+        if let Some(email) = &self.email {
+            if email.sends_new_products() && !diff.get_added().is_empty() {
+                self.spawn_mail(EmailContent::new_products(list, diff.get_added()), None);
+            }
+        }
+        let bot = match &self.telegram {
+            Some(bot) => bot.clone(),
+            None => return,
+        };
+        let message = match format_snapshot(list, diff) {
+            Some(message) => message,
+            None => return,
+        };
+        let slug = list.get_slug().to_string();
+        tokio::spawn(async move {
+            match bot.send(&message).await {
+                Ok(()) => info!("Sent snapshot summary of list '{}' to Telegram", slug),
+                Err(e) => warn!("Could not send snapshot summary of list '{}' to Telegram: {}", slug, e),
+            }
+        });
+    }
+
+    /// Mails a digest of the changes of `changes` over the last `days`.
+    /// Returns `false` if nothing changed and no mail was sent.
+    pub async fn send_digest(&self, changes: &[(List, WishlistDiff)], days: u64) -> Result<bool> {
+        let email = self.email.as_ref().ok_or_else(|| Error::Config("email is not configured".to_string()))?;
+        let content = match EmailContent::digest(changes, days) {
+            Some(content) => content,
+            None => return Ok(false),
+        };
+        email.send(&content)
+            .await
+            .map_err(|e| Error::Unavailable(format!("could not mail digest: {}", e)))?;
+        Ok(true)
+    }
+
+    /// Mails `content` in the background, to `to` or else the configured recipients.
+    fn spawn_mail(&self, content: EmailContent, to: Option<String>) {
+        let email = match &self.email {
+            Some(email) => email.clone(),
+            None => return,
+        };
+        tokio::spawn(async move {
+            let sent = match to {
+                Some(to) => email.send_to(&[to], &content).await,
+                None => email.send(&content).await,
+            };
+            match sent {
+                Ok(()) => info!("Sent mail '{}'", content.get_subject()),
+                Err(e) => warn!("Could not send mail '{}': {}", content.get_subject(), e),
+            }
+        });
+    }
+}
+
+/// Summarizes the changes of a snapshot, `None` if none are worth a message.
+fn format_snapshot(list: &List, diff: &WishlistDiff) -> Option<String> {
+    let drops: Vec<String> = diff.get_price_changed().iter()
+        .filter_map(|change| match (change.get_old_price(), change.get_new_price()) {
+            (Some(old), Some(new)) if new < old => {
+                let product = change.get_product();
+                Some(format!(
+                    "{}: {} → {}",
+                    product.get_name().unwrap_or("A product"),
+                    Money::new(old.into(), product.get_currency()),
+                    Money::new(new.into(), product.get_currency())
+                ))
+            }
+            _ => None,
+        })
+        .collect();
+    if diff.get_added().is_empty() && diff.get_removed().is_empty() && drops.is_empty() {
+        return None;
+    }
+
+    let mut message = format!("Wishlist '{}' changed", list.get_name().unwrap_or(list.get_slug()));
+    let describe = |product: &Product| format!(
+        "{} ({})",
+        product.get_name().unwrap_or("A product"),
+        Money::new(product.get_price().unwrap_or_default().into(), product.get_currency())
+    );
+    append_section(&mut message, "Added", diff.get_added().iter().map(describe).collect());
+    append_section(&mut message, "Removed", diff.get_removed().iter().map(describe).collect());
+    append_section(&mut message, "Price drops", drops);
+    if message.chars().count() > MAX_MESSAGE_LENGTH {
+        message = message.chars().take(MAX_MESSAGE_LENGTH - 1).collect();
+        message.push('…');
+    }
+    Some(message)
+}
+
+fn append_section(message: &mut String, title: &str, lines: Vec<String>) {
+    if lines.is_empty() {
+        return;
+    }
+    message.push_str(&format!("\n\n{} ({}):", title, lines.len()));
+    for line in lines.iter().take(MAX_SUMMARY_LINES) {
+        message.push_str("\n• ");
+        message.push_str(line);
+    }
+    if lines.len() > MAX_SUMMARY_LINES {
+        message.push_str(&format!("\n… and {} more", lines.len() - MAX_SUMMARY_LINES));
+    }
+}
@@ -0,0 +1,231 @@
+use chrono::Utc;
+use ring::{aead, agreement, hkdf, rand::SecureRandom, rand::SystemRandom, signature};
+use serde::Serialize;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::auth::{can_discover, Identity};
+use crate::config::PushConfig;
+use crate::model::{List, PushSubscription, DEFAULT_LIST};
+use crate::net::check_public_host;
+use crate::repository::WishlistRepository;
+use crate::{Error, Result};
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+/// Seconds a push service keeps a message for an offline browser.
+const MESSAGE_TTL: u32 = 24 * 60 * 60;
+/// Seconds the VAPID token is valid, push services accept at most a day.
+const TOKEN_LIFETIME: i64 = 12 * 60 * 60;
+/// Record size announced in the encrypted body, the whole payload is one record.
+const RECORD_SIZE: u32 = 4096;
+
+/// Sends Web Push messages signed with the VAPID key to the stored
+/// subscriptions. Subscriptions the push service reports as gone are removed.
+#[derive(Clone)]
+pub struct PushSender {
+    client: reqwest::Client,
+    key_pair: Arc<signature::EcdsaKeyPair>,
+    public_key: String,
+    subject: String,
+    repo: Arc<dyn WishlistRepository>,
+}
+
+/// Payload of a push message, shown as a notification by the service worker.
+#[derive(Serialize, Debug)]
+pub struct PushMessage {
+    pub title: String,
+    pub body: String,
+    pub url: Option<String>,
+    pub product_id: Option<String>,
+}
+
+#[derive(Serialize)]
+struct Claims<'a> {
+    aud: &'a str,
+    exp: i64,
+    sub: &'a str,
+}
+
+enum Delivery {
+    Sent,
+    /// The subscription expired or was revoked in the browser.
+    Gone,
+}
+
+/// Output length of an HKDF expansion.
+struct Length(usize);
+
+impl hkdf::KeyType for Length {
+    fn len(&self) -> usize {
+        self.0
+    }
+}
+
+impl PushSender {
+    /// Returns `None` unless the VAPID keys and subject are configured.
+    pub fn from_config(config: &PushConfig, repo: Arc<dyn WishlistRepository>) -> Result<Option<Self>> {
+        let (public_key, private_key, subject) = match (&config.vapid_public_key, &config.vapid_private_key, &config.vapid_subject) {
+            (Some(public_key), Some(private_key), Some(subject)) => (public_key, private_key, subject),
+            _ => return Ok(None),
+        };
+        if !subject.starts_with("mailto:") && !subject.starts_with("https:") {
+            return Err(Error::Config("vapid_subject must be a mailto: or https: url".to_string()));
+        }
+        let invalid = || Error::Config("vapid_public_key and vapid_private_key are not a valid P-256 key pair".to_string());
+        let public_bytes = decode_base64url(public_key).ok_or_else(invalid)?;
+        let private_bytes = decode_base64url(private_key).ok_or_else(invalid)?;
+        let key_pair = signature::EcdsaKeyPair::from_private_key_and_public_key(
+            &signature::ECDSA_P256_SHA256_FIXED_SIGNING,
+            &private_bytes,
+            &public_bytes,
+        )
+        .map_err(|_| invalid())?;
+
+        let client = reqwest::Client::builder()
+            .redirect(reqwest::redirect::Policy::none())
+            .timeout(REQUEST_TIMEOUT)
+            .build()
+            .unwrap_or_default();
+        Ok(Some(Self {
+            client,
+            key_pair: Arc::new(key_pair),
+            public_key: encode_base64url(&public_bytes),
+            subject: subject.clone(),
+            repo,
+        }))
+    }
+
+    pub fn get_public_key(&self) -> &str {
+        &self.public_key
+    }
+
+    /// Pushes `message` about `list` to the subscribers who can read the list, in the background.
+    pub fn push_to_readers(&self, list: &str, message: PushMessage) {
+        let sender = self.clone();
+        let list = list.to_string();
+        tokio::spawn(async move {
+            if let Err(e) = sender.deliver(&list, &message).await {
+                warn!("Could not send push notification '{}': {}", message.title, e);
+            }
+        });
+    }
+
+    async fn deliver(&self, list: &str, message: &PushMessage) -> Result<()> {
+        let list = match list {
+            DEFAULT_LIST => List::default_list(),
+            slug => self.repo.get_list_by_slug(slug).await?.ok_or(Error::NotFound("list"))?,
+        };
+        let payload = serde_json::to_vec(message).map_err(|e| Error::Internal(format!("could not serialize push message: {}", e)))?;
+        let subscriptions = self.repo.get_push_subscriptions().await?.into_value();
+        for subscription in subscriptions.iter().filter(|s| can_discover(Some(&subscriber(s)), &list)) {
+            let host = reqwest::Url::parse(subscription.get_endpoint()).ok()
+                .and_then(|url| url.host_str().map(String::from))
+                .unwrap_or_default();
+            match self.send(subscription, &payload).await {
+                Ok(Delivery::Sent) => info!("Sent push notification '{}' via {}", message.title, host),
+                Ok(Delivery::Gone) => {
+                    info!("Removing push subscription via {}, it is gone", host);
+                    self.repo.delete_push_subscription(subscription.get_endpoint()).await?;
+                }
+                Err(e) => warn!("Could not send push notification '{}' via {}: {}", message.title, host, e),
+            }
+        }
+        Ok(())
+    }
+
+    async fn send(&self, subscription: &PushSubscription, payload: &[u8]) -> Result<Delivery> {
+        let endpoint = reqwest::Url::parse(subscription.get_endpoint())
+            .map_err(|_| Error::Validation("push endpoint is invalid".to_string()))?;
+        check_public_host(&endpoint, "push endpoint").await?;
+        let (browser_key, auth_secret) = subscription.get_keys()?;
+        let body = encrypt(&browser_key, &auth_secret, payload)
+            .map_err(|_| Error::Internal("could not encrypt push message".to_string()))?;
+        let token = self.sign_token(&endpoint.origin().ascii_serialization())?;
+
+        let response = self.client
+            .post(endpoint)
+            .header("authorization", format!("vapid t={}, k={}", token, self.public_key))
+            .header("content-encoding", "aes128gcm")
+            .header("content-type", "application/octet-stream")
+            .header("ttl", MESSAGE_TTL.to_string())
+            .header("urgency", "high")
+            .body(body)
+            .send()
+            .await
+            .map_err(|e| Error::Unavailable(format!("push service unreachable: {}", e)))?;
+        match response.status().as_u16() {
+            200..=299 => Ok(Delivery::Sent),
+            404 | 410 => Ok(Delivery::Gone),
+            status => Err(Error::Unavailable(format!("push service answered {}", status))),
+        }
+    }
+
+    /// Signs the VAPID token for the push service at `audience`, an ES256 JWT.
+    fn sign_token(&self, audience: &str) -> Result<String> {
+        let claims = Claims {
+            aud: audience,
+            exp: Utc::now().timestamp() + TOKEN_LIFETIME,
+            sub: &self.subject,
+        };
+        let claims = serde_json::to_vec(&claims).map_err(|e| Error::Internal(format!("could not serialize VAPID claims: {}", e)))?;
+        let input = format!("{}.{}", encode_base64url(br#"{"typ":"JWT","alg":"ES256"}"#), encode_base64url(&claims));
+        let signature = self.key_pair
+            .sign(&SystemRandom::new(), input.as_bytes())
+            .map_err(|_| Error::Internal("could not sign VAPID token".to_string()))?;
+        Ok(format!("{}.{}", input, encode_base64url(signature.as_ref())))
+    }
+}
+
+fn subscriber(subscription: &PushSubscription) -> Identity {
+    match subscription.get_user_id() {
+        Some(id) => Identity::User { id: id.clone() },
+        None => Identity::Admin,
+    }
+}
+
+/// Encrypts `payload` for the browser as a single `aes128gcm` record (RFC 8291).
+fn encrypt(browser_key: &[u8], auth_secret: &[u8], payload: &[u8]) -> std::result::Result<Vec<u8>, ring::error::Unspecified> {
+    let rng = SystemRandom::new();
+    let private_key = agreement::EphemeralPrivateKey::generate(&agreement::ECDH_P256, &rng)?;
+    let public_key = private_key.compute_public_key()?;
+    let public_key = public_key.as_ref();
+    let mut salt = [0u8; 16];
+    rng.fill(&mut salt)?;
+
+    let browser = agreement::UnparsedPublicKey::new(&agreement::ECDH_P256, browser_key);
+    let mut ikm = [0u8; 32];
+    agreement::agree_ephemeral(private_key, &browser, ring::error::Unspecified, |shared_secret| {
+        hkdf::Salt::new(hkdf::HKDF_SHA256, auth_secret)
+            .extract(shared_secret)
+            .expand(&[b"WebPush: info\0", browser_key, public_key], Length(ikm.len()))?
+            .fill(&mut ikm)
+    })?;
+
+    let prk = hkdf::Salt::new(hkdf::HKDF_SHA256, &salt).extract(&ikm);
+    let mut content_key = [0u8; 16];
+    prk.expand(&[b"Content-Encoding: aes128gcm\0"], Length(content_key.len()))?.fill(&mut content_key)?;
+    let mut nonce = [0u8; 12];
+    prk.expand(&[b"Content-Encoding: nonce\0"], Length(nonce.len()))?.fill(&mut nonce)?;
+
+    // The delimiter 2 marks the last record, no padding follows it.
+    let mut record = payload.to_vec();
+    record.push(2);
+    let key = aead::LessSafeKey::new(aead::UnboundKey::new(&aead::AES_128_GCM, &content_key)?);
+    key.seal_in_place_append_tag(aead::Nonce::assume_unique_for_key(nonce), aead::Aad::empty(), &mut record)?;
+
+    let mut body = Vec::with_capacity(salt.len() + 5 + public_key.len() + record.len());
+    body.extend_from_slice(&salt);
+    body.extend_from_slice(&RECORD_SIZE.to_be_bytes());
+    body.push(public_key.len() as u8);
+    body.extend_from_slice(public_key);
+    body.extend_from_slice(&record);
+    Ok(body)
+}
+
+fn encode_base64url(data: &[u8]) -> String {
+    base64::encode_config(data, base64::URL_SAFE_NO_PAD)
+}
+
+fn decode_base64url(value: &str) -> Option<Vec<u8>> {
+    base64::decode_config(value.trim().trim_end_matches('='), base64::URL_SAFE_NO_PAD).ok()
+}
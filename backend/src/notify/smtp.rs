@@ -0,0 +1,232 @@
+use chrono::Utc;
+use rand::Rng;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+use tokio_rustls::rustls::ClientConfig;
+use tokio_rustls::webpki::DNSNameRef;
+use tokio_rustls::TlsConnector;
+
+use crate::config::{EmailConfig, SmtpSecurity};
+
+const TIMEOUT: Duration = Duration::from_secs(30);
+/// Longest line of a base64 encoded body part.
+const LINE_LENGTH: usize = 76;
+
+/// Minimal SMTP client delivering mails to one relay, which takes care of
+/// forwarding them to the recipients.
+#[derive(Clone)]
+pub struct SmtpClient {
+    host: String,
+    port: u16,
+    security: SmtpSecurity,
+    credentials: Option<(String, String)>,
+    tls: TlsConnector,
+}
+
+/// Mail with a plain text and a HTML version of its content.
+pub struct Mail<'a> {
+    pub from: &'a str,
+    pub to: &'a [String],
+    pub subject: &'a str,
+    pub text: &'a str,
+    pub html: &'a str,
+}
+
+struct Connection<S> {
+    stream: BufReader<S>,
+}
+
+impl SmtpClient {
+    pub fn new(host: String, config: &EmailConfig) -> Self {
+        let mut tls = ClientConfig::new();
+        tls.root_store.add_server_trust_anchors(&webpki_roots::TLS_SERVER_ROOTS);
+        let credentials = match (&config.smtp_username, &config.smtp_password) {
+            (Some(username), Some(password)) => Some((username.clone(), password.clone())),
+            _ => None,
+        };
+        Self {
+            host,
+            port: config.smtp_port,
+            security: config.smtp_security,
+            credentials,
+            tls: TlsConnector::from(Arc::new(tls)),
+        }
+    }
+
+    pub async fn send(&self, mail: &Mail<'_>) -> Result<(), String> {
+        tokio::time::timeout(TIMEOUT, self.deliver(mail))
+            .await
+            .unwrap_or_else(|_| Err("timed out".to_string()))
+    }
+
+    async fn deliver(&self, mail: &Mail<'_>) -> Result<(), String> {
+        let address = (self.host.as_str(), self.port);
+        let tcp = TcpStream::connect(address)
+            .await
+            .map_err(|e| format!("could not connect to {}:{}: {}", self.host, self.port, e))?;
+        match self.security {
+            SmtpSecurity::Tls => {
+                let mut connection = Connection::new(self.wrap_tls(tcp).await?);
+                connection.expect(220, "greeting").await?;
+                connection.transfer(self.credentials.as_ref(), mail).await
+            }
+            SmtpSecurity::StartTls => {
+                let mut connection = Connection::new(tcp);
+                connection.expect(220, "greeting").await?;
+                connection.command("EHLO localhost", 250).await?;
+                connection.command("STARTTLS", 220).await?;
+                let mut connection = Connection::new(self.wrap_tls(connection.into_inner()).await?);
+                connection.transfer(self.credentials.as_ref(), mail).await
+            }
+            SmtpSecurity::None => {
+                let mut connection = Connection::new(tcp);
+                connection.expect(220, "greeting").await?;
+                connection.transfer(self.credentials.as_ref(), mail).await
+            }
+        }
+    }
+
+    async fn wrap_tls(&self, tcp: TcpStream) -> Result<tokio_rustls::client::TlsStream<TcpStream>, String> {
+        let name = DNSNameRef::try_from_ascii_str(&self.host)
+            .map_err(|_| format!("'{}' is not a valid host name for TLS", self.host))?;
+        self.tls.connect(name, tcp).await.map_err(|e| format!("TLS handshake failed: {}", e))
+    }
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin> Connection<S> {
+    fn new(stream: S) -> Self {
+        Self { stream: BufReader::new(stream) }
+    }
+
+    fn into_inner(self) -> S {
+        self.stream.into_inner()
+    }
+
+    /// Runs the SMTP transaction sending `mail`, after the greeting.
+    async fn transfer(&mut self, credentials: Option<&(String, String)>, mail: &Mail<'_>) -> Result<(), String> {
+        self.command("EHLO localhost", 250).await?;
+        if let Some((username, password)) = credentials {
+            let token = base64::encode(format!("\0{}\0{}", username, password));
+            self.send_line(&format!("AUTH PLAIN {}", token)).await?;
+            self.expect(235, "AUTH").await?;
+        }
+        self.command(&format!("MAIL FROM:<{}>", mail.from), 250).await?;
+        for recipient in mail.to {
+            self.send_line(&format!("RCPT TO:<{}>", recipient)).await?;
+            let (code, text) = self.read_reply().await?;
+            if code != 250 && code != 251 {
+                return Err(format!("recipient {} was refused: {} {}", recipient, code, text));
+            }
+        }
+        self.command("DATA", 354).await?;
+        let message = dot_stuff(&render_message(mail));
+        self.write(message.as_bytes()).await?;
+        self.write(b".\r\n").await?;
+        self.expect(250, "message").await?;
+        // The mail is accepted at this point, a failing QUIT doesn't matter.
+        let _ = self.command("QUIT", 221).await;
+        Ok(())
+    }
+
+    async fn command(&mut self, command: &str, code: u16) -> Result<String, String> {
+        self.send_line(command).await?;
+        let verb = command.split(' ').next().unwrap_or(command);
+        self.expect(code, verb).await
+    }
+
+    async fn expect(&mut self, code: u16, step: &str) -> Result<String, String> {
+        match self.read_reply().await? {
+            (received, text) if received == code => Ok(text),
+            (received, text) => Err(format!("server answered {} with {} {}", step, received, text)),
+        }
+    }
+
+    /// Reads a reply of possibly multiple `250-` lines ending with `250 `.
+    async fn read_reply(&mut self) -> Result<(u16, String), String> {
+        let mut text = String::new();
+        loop {
+            let mut line = String::new();
+            let read = self.stream.read_line(&mut line).await.map_err(|e| format!("could not read reply: {}", e))?;
+            if read == 0 {
+                return Err("server closed the connection".to_string());
+            }
+            let line = line.trim_end();
+            let code = line.get(..3).and_then(|c| c.parse().ok())
+                .ok_or_else(|| format!("invalid reply '{}'", line))?;
+            if !text.is_empty() {
+                text.push(' ');
+            }
+            text.push_str(line.get(4..).unwrap_or_default());
+            if line.as_bytes().get(3) != Some(&b'-') {
+                return Ok((code, text));
+            }
+        }
+    }
+
+    async fn send_line(&mut self, line: &str) -> Result<(), String> {
+        self.write(format!("{}\r\n", line).as_bytes()).await
+    }
+
+    async fn write(&mut self, data: &[u8]) -> Result<(), String> {
+        let stream = self.stream.get_mut();
+        stream.write_all(data).await.map_err(|e| format!("could not send: {}", e))?;
+        stream.flush().await.map_err(|e| format!("could not send: {}", e))
+    }
+}
+
+/// Builds a `multipart/alternative` message, both parts base64 encoded so
+/// neither line lengths nor non-ASCII characters are an issue.
+fn render_message(mail: &Mail) -> String {
+    let boundary = format!("wishlist-{:016x}", rand::thread_rng().gen::<u64>());
+    let domain = mail.from.rsplit('@').next().unwrap_or("localhost");
+    let mut message = String::new();
+    message.push_str(&format!("From: {}\r\n", mail.from));
+    message.push_str(&format!("To: {}\r\n", mail.to.join(", ")));
+    message.push_str(&format!("Subject: {}\r\n", encode_header(mail.subject)));
+    message.push_str(&format!("Date: {}\r\n", Utc::now().to_rfc2822()));
+    message.push_str(&format!("Message-ID: <{:016x}@{}>\r\n", rand::thread_rng().gen::<u64>(), domain));
+    message.push_str("MIME-Version: 1.0\r\n");
+    message.push_str(&format!("Content-Type: multipart/alternative; boundary=\"{}\"\r\n\r\n", boundary));
+    for (content_type, body) in [("text/plain", mail.text), ("text/html", mail.html)] {
+        message.push_str(&format!("--{}\r\n", boundary));
+        message.push_str(&format!("Content-Type: {}; charset=utf-8\r\n", content_type));
+        message.push_str("Content-Transfer-Encoding: base64\r\n\r\n");
+        let encoded = base64::encode(body);
+        for chunk in encoded.as_bytes().chunks(LINE_LENGTH) {
+            message.push_str(&String::from_utf8_lossy(chunk));
+            message.push_str("\r\n");
+        }
+    }
+    message.push_str(&format!("--{}--\r\n", boundary));
+    message
+}
+
+/// Encodes header values as RFC 2047 words unless they are printable ASCII,
+/// which also keeps line breaks in product names out of the headers.
+fn encode_header(value: &str) -> String {
+    if value.chars().all(|c| c.is_ascii() && !c.is_ascii_control()) {
+        value.to_string()
+    } else {
+        format!("=?UTF-8?B?{}?=", base64::encode(value))
+    }
+}
+
+/// Doubles the dots starting a line, which would end the DATA section otherwise.
+fn dot_stuff(message: &str) -> String {
+    message.split("\r\n")
+        .map(|line| if line.starts_with('.') { format!(".{}", line) } else { line.to_string() })
+        .collect::<Vec<_>>()
+        .join("\r\n")
+}
+
+/// Checks that `address` can be put into the envelope and headers as it is.
+pub fn is_valid_address(address: &str) -> bool {
+    let mut parts = address.splitn(2, '@');
+    let local = parts.next().unwrap_or_default();
+    let domain = parts.next().unwrap_or_default();
+    !local.is_empty()
+        && !domain.is_empty()
+        && address.chars().all(|c| c.is_ascii_graphic() && !"<>,;\"()[]\\".contains(c))
+}
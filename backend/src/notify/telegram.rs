@@ -0,0 +1,122 @@
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+use crate::config::TelegramConfig;
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+const MAX_ATTEMPTS: u32 = 4;
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+/// Longest wait accepted from a `retry_after` the API asks for.
+const MAX_RETRY_AFTER: u64 = 60;
+
+/// Sends messages to one chat through the Telegram Bot API.
+#[derive(Clone)]
+pub struct TelegramBot {
+    client: reqwest::Client,
+    url: String,
+    chat_id: String,
+}
+
+#[derive(Serialize)]
+struct SendMessage<'a> {
+    chat_id: &'a str,
+    text: &'a str,
+    disable_web_page_preview: bool,
+}
+
+/// Envelope of every Bot API response.
+#[derive(Deserialize)]
+struct ApiResponse {
+    ok: bool,
+    description: Option<String>,
+    parameters: Option<ResponseParameters>,
+}
+
+#[derive(Deserialize)]
+struct ResponseParameters {
+    retry_after: Option<u64>,
+}
+
+enum Failure {
+    /// Worth trying again, after the given wait if the API asked for one.
+    Temporary(String, Option<Duration>),
+    Permanent(String),
+}
+
+impl TelegramBot {
+    /// Returns `None` unless both the bot token and the chat id are configured.
+    pub fn from_config(client: reqwest::Client, config: &TelegramConfig) -> Option<Self> {
+        match (&config.bot_token, &config.chat_id) {
+            (Some(token), Some(chat_id)) => Some(Self {
+                client,
+                url: format!("{}/bot{}/sendMessage", config.api_url.trim_end_matches('/'), token),
+                chat_id: chat_id.clone(),
+            }),
+            _ => None,
+        }
+    }
+
+    /// Sends `text`, retrying with exponential backoff on rate limits, server
+    /// errors and network failures. Errors never contain the bot token.
+    pub async fn send(&self, text: &str) -> Result<(), String> {
+        let mut backoff = INITIAL_BACKOFF;
+        let mut attempt = 1;
+        loop {
+            match self.try_send(text).await {
+                Ok(()) => return Ok(()),
+                Err(Failure::Temporary(reason, retry_after)) if attempt < MAX_ATTEMPTS => {
+                    let wait = retry_after.unwrap_or(backoff);
+                    warn!("Telegram message failed ({}/{}): {}, retrying in {}s", attempt, MAX_ATTEMPTS, reason, wait.as_secs());
+                    tokio::time::delay_for(wait).await;
+                    backoff *= 2;
+                    attempt += 1;
+                }
+                Err(Failure::Temporary(reason, _)) | Err(Failure::Permanent(reason)) => return Err(reason),
+            }
+        }
+    }
+
+    async fn try_send(&self, text: &str) -> Result<(), Failure> {
+        let message = SendMessage {
+            chat_id: &self.chat_id,
+            text,
+            disable_web_page_preview: true,
+        };
+        // reqwest errors name the url, which holds the token, so only their kind is reported.
+        let response = self.client
+            .post(&self.url)
+            .timeout(REQUEST_TIMEOUT)
+            .json(&message)
+            .send()
+            .await
+            .map_err(|e| Failure::Temporary(describe_error(&e).to_string(), None))?;
+        let status = response.status();
+        let body: Option<ApiResponse> = response.json().await.ok();
+        if status.is_success() && body.as_ref().map(|b| b.ok).unwrap_or(false) {
+            return Ok(());
+        }
+
+        let description = body.as_ref()
+            .and_then(|b| b.description.clone())
+            .unwrap_or_else(|| status.to_string());
+        let retry_after = body.as_ref()
+            .and_then(|b| b.parameters.as_ref())
+            .and_then(|p| p.retry_after)
+            .map(|s| Duration::from_secs(s.min(MAX_RETRY_AFTER)));
+        if status.as_u16() == 429 || status.is_server_error() {
+            Err(Failure::Temporary(description, retry_after))
+        } else {
+            Err(Failure::Permanent(description))
+        }
+    }
+}
+
+fn describe_error(error: &reqwest::Error) -> &'static str {
+    if error.is_timeout() {
+        "request timed out"
+    } else if error.is_connect() {
+        "could not connect"
+    } else {
+        "request failed"
+    }
+}
@@ -0,0 +1,24 @@
+/// OpenAPI 3 document describing every route and the JSON shapes of the
+/// models. Kept next to `Cargo.toml` so it can be fed to client generators
+/// without running the backend; update it together with `routes.rs`.
+pub const OPENAPI_SPEC: &str = include_str!("../openapi.json");
+
+/// Swagger UI page rendering [`OPENAPI_SPEC`], with the assets loaded from a CDN.
+pub const SWAGGER_UI: &str = r##"<!DOCTYPE html>
+<html lang="en">
+<head>
+  <meta charset="utf-8">
+  <title>Wishlist API</title>
+  <link rel="stylesheet" href="https://unpkg.com/swagger-ui-dist@5/swagger-ui.css">
+</head>
+<body>
+  <div id="swagger-ui"></div>
+  <script src="https://unpkg.com/swagger-ui-dist@5/swagger-ui-bundle.js"></script>
+  <script>
+    window.onload = () => {
+      window.ui = SwaggerUIBundle({ url: "/api/v1/openapi.json", dom_id: "#swagger-ui" });
+    };
+  </script>
+</body>
+</html>
+"##;
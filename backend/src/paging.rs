@@ -0,0 +1,43 @@
+use std::convert::Infallible;
+use warp::filters::path::FullPath;
+use warp::Filter;
+
+use crate::model::Page;
+
+/// Path and query string of a paged request, to link to the neighbouring pages.
+pub struct PageLocation {
+    path: String,
+    query: String,
+}
+
+/// Extracts the `PageLocation` of the request, never rejects.
+pub fn with_page_location() -> impl Filter<Extract = (PageLocation,), Error = Infallible> + Clone {
+    warp::path::full()
+        .and(warp::query::raw().or(warp::any().map(String::new)).unify())
+        .map(|path: FullPath, query: String| PageLocation {
+            path: path.as_str().to_string(),
+            query,
+        })
+}
+
+impl PageLocation {
+    /// Builds the `Link` header pointing at the pages before and after `page`,
+    /// `None` if it is the only one.
+    pub fn get_links<T>(&self, page: &Page<T>) -> Option<String> {
+        let links: Vec<String> = [("prev", page.get_prev_offset()), ("next", page.get_next_offset())]
+            .iter()
+            .filter_map(|(rel, offset)| offset.map(|offset| format!("<{}>; rel=\"{}\"", self.with_offset(offset), rel)))
+            .collect();
+        Some(links.join(", ")).filter(|links| !links.is_empty())
+    }
+
+    /// The request url with its `offset` parameter replaced by `offset`.
+    fn with_offset(&self, offset: u64) -> String {
+        let mut params: Vec<String> = self.query.split('&')
+            .filter(|param| !param.is_empty() && !param.starts_with("offset="))
+            .map(String::from)
+            .collect();
+        params.push(format!("offset={}", offset));
+        format!("{}?{}", self.path, params.join("&"))
+    }
+}
@@ -0,0 +1,96 @@
+use std::collections::BTreeSet;
+
+use crate::auth::Identity;
+use crate::model::{AuditEvent, CollaboratorRole, Loaded, Page, Product, ProductDetail, Wishlist};
+use crate::repository::WishlistRepository;
+use crate::Result;
+
+/// Audit log actions revealing which products are given or talked about.
+const HIDDEN_ACTIONS: [&str; 8] = ["reserve", "cancel_reservation", "create_pledge_pool", "delete_pledge_pool", "pledge", "withdraw_pledge", "comment", "delete_comment"];
+
+/// Lists whose reservations are hidden from the sender of a request, the
+/// secret-santa lists it owns or edits. Everybody else sees which products are
+/// reserved, so that nobody buys the same gift twice.
+pub struct ReservationPolicy {
+    hidden: BTreeSet<String>,
+}
+
+impl ReservationPolicy {
+    pub async fn load(repo: &dyn WishlistRepository, identity: Option<&Identity>) -> Result<Self> {
+        let identity = match identity {
+            Some(identity @ Identity::Admin) | Some(identity @ Identity::User { .. }) => identity,
+            // Share tokens are handed to the gift-givers, who see the reservations.
+            Some(Identity::Share { .. }) | None => return Ok(Self { hidden: BTreeSet::new() }),
+        };
+        let hidden = repo.get_lists().await?
+            .into_value()
+            .into_iter()
+            .filter(|list| list.is_secret_santa() && (identity.is_owner(list) || identity.get_role(list) == Some(CollaboratorRole::Editor)))
+            .map(|list| list.get_slug().to_string())
+            .collect();
+        Ok(Self { hidden })
+    }
+
+    pub fn hides(&self, list: &str) -> bool {
+        self.hidden.contains(list)
+    }
+
+    /// Whether `event` of `list` tells of a reservation, pledge or comment the
+    /// sender of the request mustn't learn about.
+    pub fn hides_event(&self, list: &str, event: &AuditEvent) -> bool {
+        self.hides(list) && HIDDEN_ACTIONS.contains(&event.get_action())
+    }
+}
+
+/// Responses whose reservation state is hidden from the owner of a
+/// secret-santa list, like `ConvertPrices` converts their prices.
+pub trait HideReservations {
+    fn hide_reservations(&mut self, policy: &ReservationPolicy);
+}
+
+impl HideReservations for Product {
+    fn hide_reservations(&mut self, policy: &ReservationPolicy) {
+        if policy.hides(self.get_list()) {
+            self.set_reserved(false);
+            self.set_funded_percent(None);
+        }
+    }
+}
+
+impl HideReservations for ProductDetail {
+    fn hide_reservations(&mut self, policy: &ReservationPolicy) {
+        self.get_product_mut().hide_reservations(policy)
+    }
+}
+
+impl<T: HideReservations> HideReservations for Vec<T> {
+    fn hide_reservations(&mut self, policy: &ReservationPolicy) {
+        self.iter_mut().for_each(|item| item.hide_reservations(policy))
+    }
+}
+
+impl<T: HideReservations> HideReservations for Loaded<T> {
+    fn hide_reservations(&mut self, policy: &ReservationPolicy) {
+        self.get_value_mut().hide_reservations(policy)
+    }
+}
+
+impl<T> HideReservations for Page<T> where Vec<T>: HideReservations {
+    fn hide_reservations(&mut self, policy: &ReservationPolicy) {
+        self.get_items_mut().hide_reservations(policy)
+    }
+}
+
+impl HideReservations for Wishlist {
+    fn hide_reservations(&mut self, policy: &ReservationPolicy) {
+        if let Some(products) = self.get_products_mut() {
+            products.hide_reservations(policy);
+        }
+    }
+}
+
+/// Hides the reservations of `output` from `identity` where its policy asks for it.
+pub async fn hide_reservations<T: HideReservations>(repo: &dyn WishlistRepository, identity: Option<&Identity>, mut output: T) -> Result<T> {
+    output.hide_reservations(&ReservationPolicy::load(repo, identity).await?);
+    Ok(output)
+}
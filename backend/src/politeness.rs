@@ -0,0 +1,277 @@
+use reqwest::header::{ACCEPT, CONTENT_TYPE, USER_AGENT};
+use reqwest::{redirect, Method, Response, StatusCode, Url};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+use tokio::sync::Semaphore;
+
+use crate::model::Politeness;
+
+/// Limits of the sources that don't set their own.
+const DEFAULT_REQUESTS_PER_MINUTE: u32 = 30;
+const DEFAULT_CONCURRENCY: u32 = 2;
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+/// How long a fetched `robots.txt` is kept.
+const ROBOTS_TTL: Duration = Duration::from_secs(60 * 60);
+/// Fetched `robots.txt` rules per origin, along with when they were fetched.
+type RobotsCache = HashMap<String, (Instant, Arc<Robots>)>;
+
+/// Requests the pages of the sources, keeping to the politeness of each
+/// source: at most its rate of requests and concurrent requests, sent with
+/// its user agent and, if it wants so, only to the paths its `robots.txt`
+/// allows. Redirects aren't followed, they could lead into the local network.
+#[derive(Clone)]
+pub struct PoliteClient {
+    client: reqwest::Client,
+    user_agent: String,
+    gates: Arc<Mutex<HashMap<String, Arc<Gate>>>>,
+    robots: Arc<Mutex<RobotsCache>>,
+}
+
+/// Paces the requests to one source.
+struct Gate {
+    requests_per_minute: u32,
+    concurrency: u32,
+    permits: Semaphore,
+    next: Mutex<Instant>,
+}
+
+/// Rules of a `robots.txt` that apply to the user agent, `true` for `Allow`.
+#[derive(Default)]
+struct Robots {
+    rules: Vec<(bool, String)>,
+    disallow_all: bool,
+}
+
+impl PoliteClient {
+    /// `user_agent` is sent to the sources that don't set their own.
+    pub fn new(user_agent: &str) -> Self {
+        let client = reqwest::Client::builder()
+            .redirect(redirect::Policy::none())
+            .timeout(REQUEST_TIMEOUT)
+            .build()
+            .unwrap_or_else(|_| reqwest::Client::new());
+        Self {
+            client,
+            user_agent: user_agent.to_string(),
+            gates: Arc::new(Mutex::new(HashMap::new())),
+            robots: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Sends a request to a page of the source `source`, waiting for its
+    /// turn. `None` if its `robots.txt` disallows the page or the request failed.
+    pub async fn request(&self, source: &str, politeness: &Politeness, method: Method, url: &Url) -> Option<StatusCode> {
+        self.send(source, politeness, method, url).await.map(|response| response.status())
+    }
+
+    /// Reads the HTML page at `url` of the source `source`, at most its first
+    /// `max_size` bytes. `None` if the page can't be requested or isn't HTML.
+    pub async fn get_page(&self, source: &str, politeness: &Politeness, url: &Url, max_size: usize) -> Option<String> {
+        let mut response = self.send(source, politeness, Method::GET, url).await?;
+        let is_html = response.headers()
+            .get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .map(|value| value.starts_with("text/html") || value.starts_with("application/xhtml+xml"))
+            .unwrap_or(false);
+        if !response.status().is_success() || !is_html {
+            debug!("Not reading {}, it answered {} without HTML", url, response.status());
+            return None;
+        }
+        let mut data = Vec::new();
+        while data.len() < max_size {
+            match response.chunk().await {
+                Ok(Some(chunk)) => data.extend_from_slice(&chunk),
+                Ok(None) => break,
+                Err(e) => {
+                    debug!("Reading {} failed: {}", url, e);
+                    return None;
+                }
+            }
+        }
+        data.truncate(max_size);
+        Some(String::from_utf8_lossy(&data).into_owned())
+    }
+
+    async fn send(&self, source: &str, politeness: &Politeness, method: Method, url: &Url) -> Option<Response> {
+        let user_agent = politeness.get_user_agent().unwrap_or(&self.user_agent);
+        if politeness.is_respecting_robots() && !self.is_allowed(user_agent, url).await {
+            debug!("Not requesting {}, robots.txt disallows it", url);
+            return None;
+        }
+        let gate = self.get_gate(source, politeness);
+        let _permit = gate.permits.acquire().await;
+        gate.wait_turn().await;
+        let request = self.client.request(method, url.clone())
+            .header(USER_AGENT, user_agent)
+            .header(ACCEPT, "text/html,application/xhtml+xml,*/*;q=0.8");
+        match request.send().await {
+            Ok(response) => Some(response),
+            Err(e) => {
+                debug!("Request to {} failed: {}", url, e);
+                None
+            }
+        }
+    }
+
+    fn get_gate(&self, source: &str, politeness: &Politeness) -> Arc<Gate> {
+        let requests_per_minute = politeness.get_requests_per_minute().unwrap_or(DEFAULT_REQUESTS_PER_MINUTE).max(1);
+        let concurrency = politeness.get_max_concurrency().unwrap_or(DEFAULT_CONCURRENCY).max(1);
+        let mut gates = lock(&self.gates);
+        match gates.get(source) {
+            Some(gate) if gate.requests_per_minute == requests_per_minute && gate.concurrency == concurrency => gate.clone(),
+            _ => {
+                let gate = Arc::new(Gate {
+                    requests_per_minute,
+                    concurrency,
+                    permits: Semaphore::new(concurrency as usize),
+                    next: Mutex::new(Instant::now()),
+                });
+                gates.insert(source.to_string(), gate.clone());
+                gate
+            }
+        }
+    }
+
+    async fn is_allowed(&self, user_agent: &str, url: &Url) -> bool {
+        let origin = url.origin().ascii_serialization();
+        let cached = lock(&self.robots).get(&origin)
+            .filter(|(fetched, _)| fetched.elapsed() < ROBOTS_TTL)
+            .map(|(_, robots)| robots.clone());
+        let robots = match cached {
+            Some(robots) => robots,
+            None => {
+                let robots = Arc::new(self.fetch_robots(user_agent, &origin).await);
+                lock(&self.robots).insert(origin, (Instant::now(), robots.clone()));
+                robots
+            }
+        };
+        let path = match url.query() {
+            Some(query) => format!("{}?{}", url.path(), query),
+            None => url.path().to_string(),
+        };
+        robots.allows(&path)
+    }
+
+    /// A missing `robots.txt` allows everything. One that can't be read
+    /// because the server failed disallows everything, like the crawlers of
+    /// the search engines do.
+    async fn fetch_robots(&self, user_agent: &str, origin: &str) -> Robots {
+        let response = self.client.get(&format!("{}/robots.txt", origin)).header(USER_AGENT, user_agent).send().await;
+        match response {
+            Ok(response) if response.status().is_success() => match response.text().await {
+                Ok(content) => Robots::parse(&content, user_agent),
+                Err(_) => Robots::disallowing_all(),
+            },
+            Ok(response) if response.status().is_client_error() => Robots::default(),
+            _ => Robots::disallowing_all(),
+        }
+    }
+}
+
+impl Gate {
+    /// Waits until `requests_per_minute` allows the next request.
+    async fn wait_turn(&self) {
+        let interval = Duration::from_secs(60) / self.requests_per_minute;
+        let wait = {
+            let mut next = lock(&self.next);
+            let now = Instant::now();
+            let turn = (*next).max(now);
+            *next = turn + interval;
+            turn - now
+        };
+        if wait > Duration::from_secs(0) {
+            tokio::time::delay_for(wait).await;
+        }
+    }
+}
+
+impl Robots {
+    fn disallowing_all() -> Self {
+        Self { rules: Vec::new(), disallow_all: true }
+    }
+
+    /// Reads the rules of the groups naming the product token of
+    /// `user_agent`, e.g. `wishlist` of `wishlist/1.0`, or of the `*` group
+    /// if none does.
+    fn parse(content: &str, user_agent: &str) -> Self {
+        let token = user_agent.split(|c: char| c == '/' || c.is_whitespace()).next().unwrap_or_default().to_lowercase();
+        let (mut named, mut any) = (Vec::new(), Vec::new());
+        let mut agents: Vec<String> = Vec::new();
+        let mut in_rules = false;
+        for line in content.lines() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            let (key, value) = match line.split_once(':') {
+                Some((key, value)) => (key.trim().to_lowercase(), value.trim()),
+                None => continue,
+            };
+            match key.as_str() {
+                "user-agent" => {
+                    if in_rules {
+                        agents.clear();
+                        in_rules = false;
+                    }
+                    agents.push(value.to_lowercase());
+                }
+                "allow" | "disallow" => {
+                    in_rules = true;
+                    if value.is_empty() {
+                        continue;
+                    }
+                    let rule = (key == "allow", value.to_string());
+                    if agents.iter().any(|agent| !token.is_empty() && agent != "*" && token.starts_with(agent.as_str())) {
+                        named.push(rule);
+                    } else if agents.iter().any(|agent| agent == "*") {
+                        any.push(rule);
+                    }
+                }
+                _ => {}
+            }
+        }
+        Self {
+            rules: if named.is_empty() { any } else { named },
+            disallow_all: false,
+        }
+    }
+
+    /// The longest matching rule decides, `Allow` wins a tie.
+    fn allows(&self, path: &str) -> bool {
+        if self.disallow_all {
+            return false;
+        }
+        self.rules.iter()
+            .filter(|(_, pattern)| matches_rule(pattern, path))
+            .max_by_key(|(allow, pattern)| (pattern.len(), *allow))
+            .map(|(allow, _)| *allow)
+            .unwrap_or(true)
+    }
+}
+
+/// Matches `path` against a rule like `/search*?q=` or `/*.pdf$`, where `*`
+/// is any text and a trailing `$` the end of the path.
+fn matches_rule(pattern: &str, path: &str) -> bool {
+    let (pattern, anchored) = match pattern.strip_suffix('$') {
+        Some(pattern) => (pattern, true),
+        None => (pattern, false),
+    };
+    let mut parts = pattern.split('*');
+    let mut rest = match path.strip_prefix(parts.next().unwrap_or_default()) {
+        Some(rest) => rest,
+        None => return false,
+    };
+    let parts: Vec<&str> = parts.collect();
+    for (i, part) in parts.iter().enumerate() {
+        if anchored && i + 1 == parts.len() {
+            return rest.ends_with(part);
+        }
+        match rest.find(part) {
+            Some(position) => rest = &rest[position + part.len()..],
+            None => return false,
+        }
+    }
+    !anchored || rest.is_empty()
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
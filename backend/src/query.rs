@@ -1,5 +1,14 @@
+use mongodb::bson::oid::ObjectId;
 use serde::Deserialize;
 
+use crate::export::ExportFormat;
+use crate::model::{Currency, FieldSelection, DEFAULT_LIST};
+use crate::{Error, Result};
+
+const MAX_SUGGESTIONS: u64 = 20;
+const MAX_BATCH_IDS: usize = 50;
+const MAX_TIMELINE_PAGE: u64 = 100;
+
 #[derive(Deserialize)]
 pub struct ListQuery {
     #[serde(default = "default_offset")]
@@ -8,13 +17,224 @@ pub struct ListQuery {
     size: u64,
 }
 
+/// Share token granting access to a private list.
+#[derive(Deserialize)]
+pub struct TokenQuery {
+    #[serde(default = "Option::default")]
+    token: Option<String>,
+}
+
 #[derive(Deserialize)]
 pub struct CategoryQuery {
+    /// Name of the category, the uncategorized products have their own listing.
+    category: String,
+    /// Includes the products of the subcategories at any depth.
+    #[serde(default)]
+    recursive: bool,
+}
+
+#[derive(Deserialize)]
+pub struct SearchQuery {
+    q: String,
+    #[serde(default = "default_offset")]
+    offset: u64,
+    #[serde(default = "default_size")]
+    size: u64,
+}
+
+/// Source to scrape by name, all of them if not set.
+#[derive(Deserialize)]
+pub struct ScrapeQuery {
+    #[serde(default = "Option::default")]
+    source: Option<String>,
+}
+
+/// Restricts the audit log to the entries about one collection, or one document of it.
+#[derive(Deserialize)]
+pub struct EventQuery {
+    #[serde(default = "Option::default")]
+    entity: Option<String>,
+    #[serde(default = "Option::default")]
+    entity_id: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct TimelineQuery {
+    #[serde(default = "default_offset")]
+    offset: u64,
+    #[serde(default = "default_size")]
+    per_page: u64,
+}
+
+/// Prefix typed into a search box, `size` limits the suggestions of each kind.
+#[derive(Deserialize)]
+pub struct SuggestQuery {
+    q: String,
+    #[serde(default = "default_size")]
+    size: u64,
+}
+
+#[derive(Deserialize)]
+pub struct ArchiveFilterQuery {
+    #[serde(default = "Option::default")]
+    min_price: Option<i32>,
+    #[serde(default = "Option::default")]
+    max_price: Option<i32>,
+    #[serde(default = "Option::default")]
+    source: Option<String>,
+    #[serde(default = "Option::default")]
+    tag: Option<String>,
+    /// Shows only the cheapest offer of the products offered by several sources.
+    #[serde(default)]
+    collapse_duplicates: bool,
+    #[serde(default = "Option::default")]
+    in_stock: Option<bool>,
+}
+
+/// Restricts the listed offers to the cheapest offer of the products offered
+/// by several sources and to the products in or out of stock.
+#[derive(Deserialize, Default)]
+pub struct OfferQuery {
+    #[serde(default)]
+    collapse_duplicates: bool,
+    #[serde(default = "Option::default")]
+    in_stock: Option<bool>,
+}
+
+/// Search terms and bounds of when the searched archived products were
+/// removed, as unix timestamps.
+#[derive(Deserialize)]
+pub struct ArchiveSearchQuery {
+    q: String,
+    #[serde(default = "Option::default")]
+    removed_after: Option<i32>,
+    #[serde(default = "Option::default")]
+    removed_before: Option<i32>,
+}
+
+/// Tag the listed products must carry.
+#[derive(Deserialize)]
+pub struct TagQuery {
+    #[serde(default = "Option::default")]
+    tag: Option<String>,
+}
+
+#[derive(Deserialize, Default, Clone, PartialEq, Eq, Hash)]
+pub struct SortQuery {
+    #[serde(default = "Option::default")]
+    sort: Option<SortField>,
+    #[serde(default = "Option::default")]
+    order: Option<SortOrder>,
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum SortField {
+    Price,
+    Name,
+    Added,
+    Source,
+    Priority,
+    Rating,
+    Quantity,
+    Views,
+    Removed,
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    Asc,
+    Desc,
+}
+
+/// `with_counts=true` adds the numbers of current and archived products to
+/// the listed categories.
+#[derive(Deserialize)]
+pub struct CategoryListQuery {
+    #[serde(default)]
+    with_counts: bool,
+}
+
+#[derive(Deserialize)]
+pub struct DeleteCategoryQuery {
+    #[serde(default = "Option::default")]
+    reassign_to: Option<String>,
+}
+
+/// Currency to show the prices in, e.g. `USD`, the stored currency if unset.
+#[derive(Deserialize)]
+pub struct CurrencyQuery {
     #[serde(default = "Option::default")]
-    category: Option<String>,
+    currency: Option<Currency>,
+}
+
+/// `raw_links=true` leaves out the affiliate parameters of the sources in
+/// the shop links of the products.
+#[derive(Deserialize)]
+pub struct LinkQuery {
+    #[serde(default = "Option::default")]
+    raw_links: Option<bool>,
+}
+
+/// File format of a wishlist export, CSV if unset.
+#[derive(Deserialize)]
+pub struct ExportQuery {
+    #[serde(default = "Option::default")]
+    format: Option<ExportFormat>,
+}
+
+/// List imported products are added to, the default list if unset.
+#[derive(Deserialize)]
+pub struct ImportQuery {
+    #[serde(default = "Option::default")]
+    list: Option<String>,
+}
+
+/// Steam profile whose wishlist is imported and the list it is imported into.
+#[derive(Deserialize)]
+pub struct SteamImportQuery {
+    steam_id: String,
+    list: String,
+}
+
+/// Comma-separated fields of the listed products to return, e.g. `name,price,source.name`.
+#[derive(Deserialize, Default)]
+pub struct FieldsQuery {
+    #[serde(default = "Option::default")]
+    fields: Option<String>,
+}
+
+/// Comma-separated ids of the products to fetch at once.
+#[derive(Deserialize)]
+pub struct IdsQuery {
+    ids: String,
+}
+
+/// Comma-separated relations to include with a product, e.g. `source,category`.
+#[derive(Deserialize)]
+pub struct ExpandQuery {
+    #[serde(default = "Option::default")]
+    expand: Option<String>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Relation {
+    Source,
+    Category,
+    PriceHistory,
+}
+
+#[derive(Deserialize)]
+pub struct DiffQuery {
+    from: i32,
+    to: i32,
 }
 
 impl ListQuery {
+    pub fn new(offset: u64, size: u64) -> Self {
+        Self { offset, size }
+    }
     pub fn get_offset(&self) -> u64 {
         self.offset
     }
@@ -24,11 +244,270 @@ impl ListQuery {
 }
 
 impl CategoryQuery {
-    pub fn get_category(&self) -> Option<&str> {
-        match &self.category {
-            Some(s) => Some(s.as_ref()),
-            None => None,
+    pub fn get_category(&self) -> &str {
+        &self.category
+    }
+    pub fn is_recursive(&self) -> bool {
+        self.recursive
+    }
+}
+
+impl ArchiveFilterQuery {
+    pub fn new(min_price: Option<i32>, max_price: Option<i32>, source: Option<String>, tag: Option<String>) -> Self {
+        Self {
+            min_price,
+            max_price,
+            source,
+            tag,
+            collapse_duplicates: false,
+            in_stock: None,
+        }
+    }
+    pub fn with_collapsed_duplicates(mut self, collapse: bool) -> Self {
+        self.collapse_duplicates = collapse;
+        self
+    }
+    pub fn with_in_stock(mut self, in_stock: Option<bool>) -> Self {
+        self.in_stock = in_stock;
+        self
+    }
+    pub fn get_min_price(&self) -> Option<i32> {
+        self.min_price
+    }
+    pub fn get_max_price(&self) -> Option<i32> {
+        self.max_price
+    }
+    pub fn get_source(&self) -> Option<&str> {
+        self.source.as_deref()
+    }
+    pub fn get_tag(&self) -> Option<&str> {
+        self.tag.as_deref()
+    }
+    pub fn is_collapsing_duplicates(&self) -> bool {
+        self.collapse_duplicates
+    }
+    pub fn get_in_stock(&self) -> Option<bool> {
+        self.in_stock
+    }
+}
+
+impl OfferQuery {
+    pub fn new(collapse_duplicates: bool, in_stock: Option<bool>) -> Self {
+        Self { collapse_duplicates, in_stock }
+    }
+    pub fn is_collapsing(&self) -> bool {
+        self.collapse_duplicates
+    }
+    pub fn get_in_stock(&self) -> Option<bool> {
+        self.in_stock
+    }
+}
+
+impl ArchiveSearchQuery {
+    pub fn get_query(&self) -> &str {
+        self.q.trim()
+    }
+    pub fn get_removed_after(&self) -> Option<i32> {
+        self.removed_after
+    }
+    pub fn get_removed_before(&self) -> Option<i32> {
+        self.removed_before
+    }
+}
+
+impl TagQuery {
+    pub fn new(tag: Option<String>) -> Self {
+        Self { tag }
+    }
+    pub fn get_tag(&self) -> Option<&str> {
+        self.tag.as_deref()
+    }
+}
+
+impl SortQuery {
+    pub fn new(sort: SortField, order: SortOrder) -> Self {
+        Self {
+            sort: Some(sort),
+            order: Some(order),
+        }
+    }
+    /// Returns the product document key to sort by, `_id` if no sort was requested.
+    pub fn get_key(&self) -> &'static str {
+        match self.sort {
+            Some(SortField::Price) => "price",
+            Some(SortField::Name) => "name",
+            Some(SortField::Added) => "first_seen",
+            Some(SortField::Source) => "source",
+            Some(SortField::Priority) => "priority",
+            Some(SortField::Rating) => "rating",
+            Some(SortField::Quantity) => "quantity",
+            Some(SortField::Views) => "views",
+            Some(SortField::Removed) => "last_seen",
+            None => "_id",
+        }
+    }
+    /// Explicit sorts default to ascending, the fallback `_id` sort to newest first.
+    pub fn is_ascending(&self) -> bool {
+        match (self.sort, self.order) {
+            (_, Some(order)) => order == SortOrder::Asc,
+            (Some(_), None) => true,
+            (None, None) => false,
+        }
+    }
+}
+
+impl CategoryListQuery {
+    pub fn with_counts(&self) -> bool {
+        self.with_counts
+    }
+}
+
+impl DeleteCategoryQuery {
+    pub fn get_reassign_to(&self) -> Option<&str> {
+        self.reassign_to.as_deref()
+    }
+}
+
+impl CurrencyQuery {
+    pub fn get_currency(&self) -> Option<Currency> {
+        self.currency
+    }
+}
+
+impl LinkQuery {
+    pub fn is_raw(&self) -> bool {
+        self.raw_links.unwrap_or(false)
+    }
+}
+
+impl ExportQuery {
+    pub fn get_format(&self) -> ExportFormat {
+        self.format.unwrap_or(ExportFormat::Csv)
+    }
+}
+
+impl ImportQuery {
+    pub fn get_list(&self) -> &str {
+        self.list.as_deref().unwrap_or(DEFAULT_LIST)
+    }
+}
+
+impl SteamImportQuery {
+    pub fn get_steam_id(&self) -> &str {
+        self.steam_id.trim()
+    }
+    pub fn get_list(&self) -> &str {
+        self.list.trim()
+    }
+}
+
+impl FieldsQuery {
+    /// Parses the selected fields, `None` to return all of them.
+    pub fn get_fields(&self) -> Result<Option<FieldSelection>> {
+        self.fields.as_deref().map(FieldSelection::parse).transpose()
+    }
+}
+
+impl IdsQuery {
+    /// Parses the ids, dropping repeated ones, failing on none or more than `MAX_BATCH_IDS`.
+    pub fn get_ids(&self) -> Result<Vec<ObjectId>> {
+        let mut ids: Vec<ObjectId> = Vec::new();
+        for id in self.ids.split(',').map(str::trim).filter(|id| !id.is_empty()) {
+            let id = ObjectId::with_string(id).map_err(|_| Error::Validation(format!("'{}' is not a valid id", id)))?;
+            if !ids.contains(&id) {
+                ids.push(id);
+            }
+        }
+        if ids.is_empty() || ids.len() > MAX_BATCH_IDS {
+            return Err(Error::Validation(format!("ids must list 1 to {} products", MAX_BATCH_IDS)));
         }
+        Ok(ids)
+    }
+}
+
+impl ExpandQuery {
+    /// Parses the requested relations, failing on unknown ones.
+    pub fn get_relations(&self) -> Result<Vec<Relation>> {
+        self.expand.as_deref()
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .map(|name| match name {
+                "source" => Ok(Relation::Source),
+                "category" => Ok(Relation::Category),
+                "price_history" => Ok(Relation::PriceHistory),
+                _ => Err(Error::Validation(format!(
+                    "unknown relation '{}', expected source, category or price_history",
+                    name
+                ))),
+            })
+            .collect()
+    }
+}
+
+impl DiffQuery {
+    pub fn get_from(&self) -> i32 {
+        self.from
+    }
+    pub fn get_to(&self) -> i32 {
+        self.to
+    }
+}
+
+impl SearchQuery {
+    pub fn new(q: String, offset: u64, size: u64) -> Self {
+        Self { q, offset, size }
+    }
+    pub fn get_query(&self) -> &str {
+        self.q.as_ref()
+    }
+    pub fn get_offset(&self) -> u64 {
+        self.offset
+    }
+    pub fn get_size(&self) -> u64 {
+        self.size
+    }
+}
+
+impl ScrapeQuery {
+    pub fn get_source(&self) -> Option<&str> {
+        self.source.as_deref().map(str::trim).filter(|s| !s.is_empty())
+    }
+}
+
+impl EventQuery {
+    pub fn get_entity(&self) -> Option<&str> {
+        self.entity.as_deref()
+    }
+    pub fn get_entity_id(&self) -> Result<Option<ObjectId>> {
+        self.entity_id.as_deref()
+            .map(|id| ObjectId::with_string(id).map_err(|_| Error::Validation(format!("'{}' is not a valid id", id))))
+            .transpose()
+    }
+}
+
+impl TimelineQuery {
+    pub fn get_offset(&self) -> u64 {
+        self.offset
+    }
+    pub fn get_per_page(&self) -> u64 {
+        self.per_page.min(MAX_TIMELINE_PAGE)
+    }
+}
+
+impl SuggestQuery {
+    pub fn get_prefix(&self) -> &str {
+        self.q.trim_start()
+    }
+    pub fn get_size(&self) -> u64 {
+        self.size.min(MAX_SUGGESTIONS)
+    }
+}
+
+impl TokenQuery {
+    pub fn get_token(&self) -> Option<&str> {
+        self.token.as_deref()
     }
 }
 
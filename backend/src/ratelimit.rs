@@ -0,0 +1,108 @@
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use warp::{Filter, Rejection};
+
+use crate::config::RateLimitConfig;
+use crate::Error;
+
+/// Address of the client set by the TLS server, which warp doesn't know the
+/// address for. Never taken from clients, the server replaces it.
+pub(crate) const PEER_ADDRESS_HEADER: &str = "x-wishlist-peer-address";
+/// Buckets that have been refilled are dropped once this many clients are tracked.
+const MAX_TRACKED_CLIENTS: usize = 10_000;
+
+/// Token bucket per client address, every request takes one token and
+/// `requests_per_second` tokens are added back up to `burst`.
+#[derive(Clone)]
+pub struct RateLimiter {
+    rate: f64,
+    burst: f64,
+    trust_forwarded_for: bool,
+    buckets: Arc<Mutex<HashMap<IpAddr, Bucket>>>,
+}
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl RateLimiter {
+    pub fn new(config: &RateLimitConfig) -> Self {
+        Self {
+            rate: config.requests_per_second,
+            burst: f64::from(config.burst.max(1)),
+            trust_forwarded_for: config.trust_forwarded_for,
+            buckets: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.rate > 0.
+    }
+
+    /// Takes a token of `client`, returns how long to wait for the next one if none is left.
+    pub fn check(&self, client: IpAddr) -> std::result::Result<(), Duration> {
+        let now = Instant::now();
+        let mut buckets = self.buckets.lock().unwrap_or_else(|e| e.into_inner());
+        if buckets.len() >= MAX_TRACKED_CLIENTS && !buckets.contains_key(&client) {
+            let (rate, burst) = (self.rate, self.burst);
+            buckets.retain(|_, bucket| bucket.refilled(now, rate, burst) < burst);
+        }
+        let bucket = buckets.entry(client).or_insert(Bucket {
+            tokens: self.burst,
+            updated: now,
+        });
+        bucket.tokens = bucket.refilled(now, self.rate, self.burst);
+        bucket.updated = now;
+        if bucket.tokens >= 1. {
+            bucket.tokens -= 1.;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((1. - bucket.tokens) / self.rate))
+        }
+    }
+
+    /// The last `X-Forwarded-For` entry is the one added by the proxy in
+    /// front of the backend, the ones before it are up to the client.
+    fn get_client(&self, remote: Option<SocketAddr>, forwarded_for: Option<&str>) -> Option<IpAddr> {
+        let forwarded = forwarded_for
+            .filter(|_| self.trust_forwarded_for)
+            .and_then(|value| value.rsplit(',').next())
+            .and_then(|addr| addr.trim().parse().ok());
+        forwarded.or_else(|| remote.map(|addr| addr.ip()))
+    }
+}
+
+impl Bucket {
+    fn refilled(&self, now: Instant, rate: f64, burst: f64) -> f64 {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        (self.tokens + elapsed * rate).min(burst)
+    }
+}
+
+/// Rejects with `RateLimited` once the client ran out of tokens. Requests
+/// without a known address, which only happens outside of a TCP server,
+/// are let through.
+pub fn with_rate_limit(limiter: RateLimiter) -> impl Filter<Extract = (), Error = Rejection> + Clone {
+    warp::addr::remote()
+        .and(warp::header::optional::<SocketAddr>(PEER_ADDRESS_HEADER))
+        .and(warp::header::optional::<String>("x-forwarded-for"))
+        .and_then(move |remote: Option<SocketAddr>, peer: Option<SocketAddr>, forwarded_for: Option<String>| {
+            let limiter = limiter.clone();
+            async move {
+                if !limiter.is_enabled() {
+                    return Ok(());
+                }
+                // Only the TLS server leaves the remote address unknown, and sets the header instead.
+                match limiter.get_client(remote.or(peer), forwarded_for.as_deref()) {
+                    Some(client) => limiter.check(client).map_err(|wait| {
+                        warp::reject::custom(Error::RateLimited(wait.as_secs_f64().ceil() as u64))
+                    }),
+                    None => Ok(()),
+                }
+            }
+        })
+        .untuple_one()
+}
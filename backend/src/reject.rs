@@ -1,47 +1,74 @@
 use std::convert::Infallible;
-use warp::http::StatusCode;
+use warp::http::{header, StatusCode};
+use warp::reject;
+use warp::Reply;
 
 use crate::model::ErrorMessage;
 use crate::Error;
 
-pub async fn handle_rejection(rej: warp::Rejection) -> Result<impl warp::Reply, Infallible> {
+/// Turns every rejection into a JSON `ErrorMessage` with a matching HTTP status code.
+pub async fn handle_rejection(rej: warp::Rejection) -> Result<impl Reply, Infallible> {
     let msg;
     if rej.is_not_found() {
         msg = get_not_found_message();
     } else if let Some(err) = rej.find::<Error>() {
-        warn!("{}", err);
         msg = err.into();
+        if msg.code >= 500 {
+            error!("{}", err);
+        } else {
+            info!("{}", err);
+        }
+    } else if let Some(err) = rej.find::<reject::InvalidQuery>() {
+        info!("InvalidQuery: {}", err);
+        msg = get_bad_request_message();
     } else if let Some(err) = rej.find::<warp::filters::body::BodyDeserializeError>() {
         info!("BodyDeserializeError: {}", err);
         msg = get_bad_request_message();
+    } else if let Some(err) = rej.find::<reject::MissingHeader>() {
+        info!("MissingHeader: {}", err);
+        msg = get_bad_request_message();
+    } else if let Some(err) = rej.find::<reject::InvalidHeader>() {
+        info!("InvalidHeader: {}", err);
+        msg = get_bad_request_message();
+    } else if rej.find::<reject::MethodNotAllowed>().is_some() {
+        msg = get_error_message(StatusCode::METHOD_NOT_ALLOWED, "Method not allowed");
+    } else if rej.find::<reject::LengthRequired>().is_some() {
+        msg = get_error_message(StatusCode::LENGTH_REQUIRED, "Content length required");
+    } else if rej.find::<reject::PayloadTooLarge>().is_some() {
+        msg = get_error_message(StatusCode::PAYLOAD_TOO_LARGE, "Payload too large");
+    } else if rej.find::<reject::UnsupportedMediaType>().is_some() {
+        msg = get_error_message(StatusCode::UNSUPPORTED_MEDIA_TYPE, "Unsupported media type");
     } else {
-        error!("Unhandeled internal error");
+        error!("Unhandeled internal error: {:?}", rej);
         msg = get_internal_error_message();
     }
     let json = warp::reply::json(&msg);
-    Ok(warp::reply::with_status(
+    let mut response = warp::reply::with_status(
         json,
         StatusCode::from_u16(msg.code).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
-    ))
+    )
+    .into_response();
+    if let Some(Error::RateLimited(seconds)) = rej.find::<Error>() {
+        response.headers_mut().insert(header::RETRY_AFTER, (*seconds).into());
+    }
+    Ok(response)
 }
 
 fn get_not_found_message() -> ErrorMessage {
-    ErrorMessage {
-        code: 404,
-        message: "Page not found".to_string(),
-    }
+    get_error_message(StatusCode::NOT_FOUND, "Page not found")
 }
 
 fn get_bad_request_message() -> ErrorMessage {
-    ErrorMessage {
-        code: 400,
-        message: "Bad request".to_string(),
-    }
+    get_error_message(StatusCode::BAD_REQUEST, "Bad request")
 }
 
 pub fn get_internal_error_message() -> ErrorMessage {
+    get_error_message(StatusCode::INTERNAL_SERVER_ERROR, "Internal server error")
+}
+
+pub fn get_error_message(status: StatusCode, message: &str) -> ErrorMessage {
     ErrorMessage {
-        code: 500,
-        message: "Internal server error".to_string(),
+        code: status.as_u16(),
+        message: message.to_string(),
     }
 }
@@ -0,0 +1,567 @@
+use async_trait::async_trait;
+use mongodb::bson::{document::Document, oid::ObjectId};
+use std::sync::Arc;
+use std::time::Duration;
+
+use super::{ProductFilter, WishlistRepository};
+use crate::cache::TtlCache;
+use crate::model::{AuditEvent, Budget, Category, CategoryCount, CategoryRule, ClickCount, Comment, ExchangeRates, List, Loaded, PriceAlert, PledgePool, PricePoint, Product, PushSubscription, Source, Stats, Stored, TagCount, User, Wishlist, WishlistSummary};
+use crate::query::SortQuery;
+use crate::Result;
+
+/// Filter, sort, offset and size of a product page.
+type ProductPageKey = (ProductFilter, SortQuery, u64, u64);
+
+/// Repository decorator caching the reads every request does (wishlist
+/// snapshots, the products and sources they reference, product pages like the
+/// archive, their counts, the list statistics and tags and the exchange rates) for a
+/// fixed time to live.
+///
+/// Writes going through this repository invalidate the affected entries, so
+/// only changes made directly in the database are delayed by up to one TTL,
+/// unless a `ChangeWatcher` reports them through `invalidate`.
+pub struct CachedRepository {
+    inner: Arc<dyn WishlistRepository>,
+    wishlists: TtlCache<(String, u64), Wishlist>,
+    products: TtlCache<Vec<ObjectId>, Loaded<Vec<Product>>>,
+    sources: TtlCache<Vec<ObjectId>, Loaded<Vec<Source>>>,
+    product_pages: TtlCache<ProductPageKey, Loaded<Vec<Product>>>,
+    product_counts: TtlCache<ProductFilter, u64>,
+    stats: TtlCache<String, Stats>,
+    tags: TtlCache<String, Loaded<Vec<TagCount>>>,
+    exchange_rates: TtlCache<(), ExchangeRates>,
+}
+
+impl CachedRepository {
+    pub fn new(inner: Arc<dyn WishlistRepository>, ttl: Duration) -> Self {
+        Self {
+            inner,
+            wishlists: TtlCache::new(ttl),
+            products: TtlCache::new(ttl),
+            sources: TtlCache::new(ttl),
+            product_pages: TtlCache::new(ttl),
+            product_counts: TtlCache::new(ttl),
+            stats: TtlCache::new(ttl),
+            tags: TtlCache::new(ttl),
+            exchange_rates: TtlCache::new(ttl),
+        }
+    }
+
+    fn invalidate_products(&self) {
+        self.products.clear();
+        self.product_pages.clear();
+        self.product_counts.clear();
+        self.stats.clear();
+        self.tags.clear();
+    }
+
+    /// Drops the entries a write to `collection` made past this repository may have changed.
+    pub fn invalidate(&self, collection: &str) {
+        match collection {
+            Wishlist::COLLECTION => {
+                self.wishlists.clear();
+                self.invalidate_products();
+            }
+            Product::COLLECTION | Category::COLLECTION => self.invalidate_products(),
+            _ => self.invalidate_all(),
+        }
+    }
+
+    fn invalidate_all(&self) {
+        self.invalidate_products();
+        self.wishlists.clear();
+        self.sources.clear();
+        self.exchange_rates.clear();
+    }
+}
+
+#[async_trait]
+impl WishlistRepository for CachedRepository {
+    async fn ensure_indexes(&self) -> Result<()> {
+        self.inner.ensure_indexes().await
+    }
+
+    async fn ping(&self) -> Result<()> {
+        self.inner.ping().await
+    }
+
+    async fn get_lists(&self) -> Result<Loaded<Vec<List>>> {
+        self.inner.get_lists().await
+    }
+
+    async fn get_list_by_slug(&self, slug: &str) -> Result<Option<List>> {
+        self.inner.get_list_by_slug(slug).await
+    }
+
+    async fn insert_list(&self, list: Document) -> Result<ObjectId> {
+        self.inner.insert_list(list).await
+    }
+
+    async fn update_list(&self, slug: &str, list: Document) -> Result<bool> {
+        self.inner.update_list(slug, list).await
+    }
+
+    async fn set_collaborator(&self, slug: &str, collaborator: Document) -> Result<bool> {
+        self.inner.set_collaborator(slug, collaborator).await
+    }
+
+    async fn remove_collaborator(&self, slug: &str, user: &ObjectId) -> Result<bool> {
+        self.inner.remove_collaborator(slug, user).await
+    }
+
+    async fn get_user_by_id(&self, id: &ObjectId) -> Result<Option<User>> {
+        self.inner.get_user_by_id(id).await
+    }
+
+    async fn get_user_by_name(&self, username: &str) -> Result<Option<User>> {
+        self.inner.get_user_by_name(username).await
+    }
+
+    async fn insert_user(&self, user: Document) -> Result<ObjectId> {
+        self.inner.insert_user(user).await
+    }
+
+    async fn get_nth_wishlist_reverse(&self, list: &str, n: u64) -> Result<Wishlist> {
+        let key = (list.to_string(), n);
+        if let Some(wishlist) = self.wishlists.get(&key) {
+            return Ok(wishlist);
+        }
+        let wishlist = self.inner.get_nth_wishlist_reverse(list, n).await?;
+        self.wishlists.insert(key, wishlist.clone());
+        Ok(wishlist)
+    }
+
+    async fn get_wishlist_at(&self, list: &str, timestamp: i32) -> Result<Option<Wishlist>> {
+        self.inner.get_wishlist_at(list, timestamp).await
+    }
+
+    async fn get_wishlist_history(&self, list: &str, offset: u64, size: u64) -> Result<Loaded<Vec<WishlistSummary>>> {
+        self.inner.get_wishlist_history(list, offset, size).await
+    }
+
+    async fn insert_wishlist(&self, list: &str, timestamp: i32, product_ids: &[ObjectId]) -> Result<ObjectId> {
+        let id = self.inner.insert_wishlist(list, timestamp, product_ids).await?;
+        self.wishlists.clear();
+        self.invalidate_products();
+        Ok(id)
+    }
+
+    async fn delete_wishlists_at(&self, list: &str, timestamps: &[i32]) -> Result<u64> {
+        let deleted = self.inner.delete_wishlists_at(list, timestamps).await?;
+        self.wishlists.clear();
+        Ok(deleted)
+    }
+
+    async fn remove_wishlist_products(&self, id: &ObjectId, product_ids: &[ObjectId]) -> Result<bool> {
+        let found = self.inner.remove_wishlist_products(id, product_ids).await?;
+        self.wishlists.clear();
+        self.invalidate_products();
+        Ok(found)
+    }
+
+    async fn get_last_modified(&self, list: Option<&str>) -> Result<Option<i32>> {
+        self.inner.get_last_modified(list).await
+    }
+
+    async fn get_product_by_id(&self, id: &ObjectId) -> Result<Option<Product>> {
+        self.inner.get_product_by_id(id).await
+    }
+
+    async fn get_products_by_id(&self, ids: &[ObjectId]) -> Result<Loaded<Vec<Product>>> {
+        let key = ids.to_vec();
+        if let Some(products) = self.products.get(&key) {
+            return Ok(products);
+        }
+        let products = self.inner.get_products_by_id(ids).await?;
+        self.products.insert(key, products.clone());
+        Ok(products)
+    }
+
+    async fn get_products(&self, filter: &ProductFilter, sort: &SortQuery, offset: u64, size: u64) -> Result<Loaded<Vec<Product>>> {
+        let key = (filter.clone(), sort.clone(), offset, size);
+        if let Some(products) = self.product_pages.get(&key) {
+            return Ok(products);
+        }
+        let products = self.inner.get_products(filter, sort, offset, size).await?;
+        self.product_pages.insert(key, products.clone());
+        Ok(products)
+    }
+
+    async fn count_products(&self, filter: &ProductFilter) -> Result<u64> {
+        if let Some(count) = self.product_counts.get(filter) {
+            return Ok(count);
+        }
+        let count = self.inner.count_products(filter).await?;
+        self.product_counts.insert(filter.clone(), count);
+        Ok(count)
+    }
+
+    async fn update_archived(&self, list: &str, current_ids: &[ObjectId]) -> Result<u64> {
+        let updated = self.inner.update_archived(list, current_ids).await?;
+        self.invalidate_products();
+        Ok(updated)
+    }
+
+    async fn get_stats(&self, list: &str) -> Result<Stats> {
+        let key = list.to_string();
+        if let Some(stats) = self.stats.get(&key) {
+            return Ok(stats);
+        }
+        let stats = self.inner.get_stats(list).await?;
+        self.stats.insert(key, stats.clone());
+        Ok(stats)
+    }
+
+    async fn get_tags(&self, list: &str) -> Result<Loaded<Vec<TagCount>>> {
+        let key = list.to_string();
+        if let Some(tags) = self.tags.get(&key) {
+            return Ok(tags);
+        }
+        let tags = self.inner.get_tags(list).await?;
+        self.tags.insert(key, tags.clone());
+        Ok(tags)
+    }
+
+    async fn search_products(&self, query: &str, lists: Option<&[String]>, offset: u64, size: u64) -> Result<Loaded<Vec<Product>>> {
+        self.inner.search_products(query, lists, offset, size).await
+    }
+
+    async fn suggest_product_names(&self, list: &str, prefix: &str, size: u64) -> Result<Vec<String>> {
+        self.inner.suggest_product_names(list, prefix, size).await
+    }
+
+    async fn get_products_by_category(&self, categories: Option<&[ObjectId]>, lists: Option<&[String]>, sort: &SortQuery, offset: u64, size: u64) -> Result<Loaded<Vec<Product>>> {
+        self.inner.get_products_by_category(categories, lists, sort, offset, size).await
+    }
+
+    async fn count_products_by_category(&self, categories: Option<&[ObjectId]>, lists: Option<&[String]>) -> Result<u64> {
+        self.inner.count_products_by_category(categories, lists).await
+    }
+
+    async fn count_products_per_category(&self, lists: Option<&[String]>) -> Result<Loaded<Vec<CategoryCount>>> {
+        self.inner.count_products_per_category(lists).await
+    }
+
+    async fn insert_product(&self, product: Document) -> Result<ObjectId> {
+        let id = self.inner.insert_product(product).await?;
+        self.invalidate_products();
+        Ok(id)
+    }
+
+    async fn upsert_product(&self, product: Document) -> Result<(ObjectId, bool)> {
+        let upserted = self.inner.upsert_product(product).await?;
+        self.invalidate_products();
+        Ok(upserted)
+    }
+
+    async fn find_duplicate_product(&self, list: &str, item_id: Option<&str>, url: &str) -> Result<Option<Product>> {
+        self.inner.find_duplicate_product(list, item_id, url).await
+    }
+
+    async fn update_product(&self, id: &ObjectId, product: Document) -> Result<bool> {
+        let updated = self.inner.update_product(id, product).await?;
+        self.invalidate_products();
+        Ok(updated)
+    }
+
+    async fn update_products(&self, ids: &[ObjectId], product: Document) -> Result<u64> {
+        let updated = self.inner.update_products(ids, product).await?;
+        self.invalidate_products();
+        Ok(updated)
+    }
+
+    async fn delete_product(&self, id: &ObjectId, timestamp: i32) -> Result<Option<Product>> {
+        let deleted = self.inner.delete_product(id, timestamp).await?;
+        self.invalidate_products();
+        Ok(deleted)
+    }
+
+    async fn get_deleted_products(&self, offset: u64, size: u64) -> Result<Loaded<Vec<Product>>> {
+        self.inner.get_deleted_products(offset, size).await
+    }
+
+    async fn restore_product(&self, id: &ObjectId) -> Result<Option<Product>> {
+        let restored = self.inner.restore_product(id).await?;
+        self.invalidate_products();
+        Ok(restored)
+    }
+
+    async fn purge_product(&self, id: &ObjectId) -> Result<Option<Product>> {
+        self.inner.purge_product(id).await
+    }
+
+    /// Listings ordered by views may lag behind until their cache entries expire.
+    async fn add_product_views(&self, views: &[(ObjectId, i64)]) -> Result<()> {
+        self.inner.add_product_views(views).await
+    }
+
+    /// Clicks don't show in the cached products, so nothing is invalidated.
+    async fn record_click(&self, id: &ObjectId) -> Result<Option<Product>> {
+        self.inner.record_click(id).await
+    }
+
+    async fn get_click_counts(&self, list: &str) -> Result<Loaded<Vec<ClickCount>>> {
+        self.inner.get_click_counts(list).await
+    }
+
+    async fn get_reserved_product_ids(&self, product_ids: &[ObjectId]) -> Result<Vec<ObjectId>> {
+        self.inner.get_reserved_product_ids(product_ids).await
+    }
+
+    async fn insert_reservation(&self, product_id: &ObjectId, token: &str, timestamp: i32) -> Result<bool> {
+        self.inner.insert_reservation(product_id, token, timestamp).await
+    }
+
+    async fn delete_reservation(&self, product_id: &ObjectId, token: &str) -> Result<bool> {
+        self.inner.delete_reservation(product_id, token).await
+    }
+
+    async fn get_pledge_pool(&self, product_id: &ObjectId) -> Result<Option<PledgePool>> {
+        self.inner.get_pledge_pool(product_id).await
+    }
+
+    async fn get_pledge_pools(&self, product_ids: &[ObjectId]) -> Result<Loaded<Vec<PledgePool>>> {
+        self.inner.get_pledge_pools(product_ids).await
+    }
+
+    async fn insert_pledge_pool(&self, pool: Document) -> Result<bool> {
+        self.inner.insert_pledge_pool(pool).await
+    }
+
+    async fn add_pledge(&self, product_id: &ObjectId, pledge: Document) -> Result<bool> {
+        self.inner.add_pledge(product_id, pledge).await
+    }
+
+    async fn remove_pledge(&self, product_id: &ObjectId, token: &str) -> Result<bool> {
+        self.inner.remove_pledge(product_id, token).await
+    }
+
+    async fn delete_empty_pledge_pool(&self, product_id: &ObjectId) -> Result<bool> {
+        self.inner.delete_empty_pledge_pool(product_id).await
+    }
+
+    async fn get_comments(&self, product_id: &ObjectId, offset: u64, size: u64) -> Result<Loaded<Vec<Comment>>> {
+        self.inner.get_comments(product_id, offset, size).await
+    }
+
+    async fn get_comment(&self, id: &ObjectId) -> Result<Option<Comment>> {
+        self.inner.get_comment(id).await
+    }
+
+    async fn insert_comment(&self, comment: Document) -> Result<ObjectId> {
+        self.inner.insert_comment(comment).await
+    }
+
+    async fn delete_comment(&self, id: &ObjectId) -> Result<bool> {
+        self.inner.delete_comment(id).await
+    }
+
+    async fn get_push_subscriptions(&self) -> Result<Loaded<Vec<PushSubscription>>> {
+        self.inner.get_push_subscriptions().await
+    }
+
+    async fn get_push_subscription(&self, endpoint: &str) -> Result<Option<PushSubscription>> {
+        self.inner.get_push_subscription(endpoint).await
+    }
+
+    async fn upsert_push_subscription(&self, subscription: Document) -> Result<()> {
+        self.inner.upsert_push_subscription(subscription).await
+    }
+
+    async fn delete_push_subscription(&self, endpoint: &str) -> Result<bool> {
+        self.inner.delete_push_subscription(endpoint).await
+    }
+
+    async fn get_price_alerts(&self) -> Result<Loaded<Vec<PriceAlert>>> {
+        self.inner.get_price_alerts().await
+    }
+
+    async fn get_price_alerts_of_product(&self, product_id: &ObjectId) -> Result<Loaded<Vec<PriceAlert>>> {
+        self.inner.get_price_alerts_of_product(product_id).await
+    }
+
+    async fn get_price_alert(&self, id: &ObjectId) -> Result<Option<PriceAlert>> {
+        self.inner.get_price_alert(id).await
+    }
+
+    async fn insert_price_alert(&self, alert: Document) -> Result<ObjectId> {
+        self.inner.insert_price_alert(alert).await
+    }
+
+    async fn set_price_alert_triggered(&self, id: &ObjectId, timestamp: i32) -> Result<bool> {
+        self.inner.set_price_alert_triggered(id, timestamp).await
+    }
+
+    async fn delete_price_alert(&self, id: &ObjectId) -> Result<bool> {
+        self.inner.delete_price_alert(id).await
+    }
+
+    async fn get_budget(&self, list: &str) -> Result<Option<Budget>> {
+        self.inner.get_budget(list).await
+    }
+
+    async fn upsert_budget(&self, budget: Document) -> Result<()> {
+        self.inner.upsert_budget(budget).await
+    }
+
+    async fn delete_budget(&self, list: &str) -> Result<bool> {
+        self.inner.delete_budget(list).await
+    }
+
+    async fn get_sources(&self) -> Result<Loaded<Vec<Source>>> {
+        self.inner.get_sources().await
+    }
+
+    async fn get_sources_by_id(&self, ids: &[ObjectId]) -> Result<Loaded<Vec<Source>>> {
+        let key = ids.to_vec();
+        if let Some(sources) = self.sources.get(&key) {
+            return Ok(sources);
+        }
+        let sources = self.inner.get_sources_by_id(ids).await?;
+        self.sources.insert(key, sources.clone());
+        Ok(sources)
+    }
+
+    async fn get_source_by_name(&self, name: &str) -> Result<Option<Source>> {
+        self.inner.get_source_by_name(name).await
+    }
+
+    async fn source_exists(&self, id: &ObjectId) -> Result<bool> {
+        self.inner.source_exists(id).await
+    }
+
+    async fn insert_source(&self, source: Document) -> Result<ObjectId> {
+        self.inner.insert_source(source).await
+    }
+
+    async fn update_source(&self, id: &ObjectId, source: Document) -> Result<bool> {
+        let updated = self.inner.update_source(id, source).await?;
+        self.sources.clear();
+        Ok(updated)
+    }
+
+    async fn get_categories(&self) -> Result<Loaded<Vec<Category>>> {
+        self.inner.get_categories().await
+    }
+
+    async fn get_category_by_id(&self, id: &ObjectId) -> Result<Option<Category>> {
+        self.inner.get_category_by_id(id).await
+    }
+
+    async fn get_category_by_name(&self, name: &str) -> Result<Option<Category>> {
+        self.inner.get_category_by_name(name).await
+    }
+
+    async fn category_name_exists(&self, name: &str, except: Option<&ObjectId>) -> Result<bool> {
+        self.inner.category_name_exists(name, except).await
+    }
+
+    async fn insert_category(&self, category: Document) -> Result<ObjectId> {
+        self.inner.insert_category(category).await
+    }
+
+    async fn update_category(&self, id: &ObjectId, category: Document) -> Result<bool> {
+        let updated = self.inner.update_category(id, category).await?;
+        self.stats.clear();
+        Ok(updated)
+    }
+
+    async fn delete_category(&self, id: &ObjectId) -> Result<bool> {
+        let deleted = self.inner.delete_category(id).await?;
+        self.stats.clear();
+        Ok(deleted)
+    }
+
+    async fn reassign_category(&self, from: &ObjectId, to: &ObjectId) -> Result<u64> {
+        let moved = self.inner.reassign_category(from, to).await?;
+        self.invalidate_products();
+        Ok(moved)
+    }
+
+    async fn merge_category(&self, from: &ObjectId, into: &ObjectId) -> Result<u64> {
+        let moved = self.inner.merge_category(from, into).await?;
+        self.invalidate_products();
+        Ok(moved)
+    }
+
+    async fn count_category_references(&self, id: &ObjectId) -> Result<u64> {
+        self.inner.count_category_references(id).await
+    }
+
+    async fn get_rules(&self) -> Result<Loaded<Vec<CategoryRule>>> {
+        self.inner.get_rules().await
+    }
+
+    async fn get_rule_by_id(&self, id: &ObjectId) -> Result<Option<CategoryRule>> {
+        self.inner.get_rule_by_id(id).await
+    }
+
+    async fn insert_rule(&self, rule: Document) -> Result<ObjectId> {
+        self.inner.insert_rule(rule).await
+    }
+
+    async fn update_rule(&self, id: &ObjectId, rule: Document) -> Result<bool> {
+        self.inner.update_rule(id, rule).await
+    }
+
+    async fn delete_rule(&self, id: &ObjectId) -> Result<bool> {
+        self.inner.delete_rule(id).await
+    }
+
+    async fn get_price_history(&self, product_id: &ObjectId) -> Result<Loaded<Vec<PricePoint>>> {
+        self.inner.get_price_history(product_id).await
+    }
+
+    async fn get_price_histories(&self, product_ids: &[ObjectId]) -> Result<Loaded<Vec<PricePoint>>> {
+        self.inner.get_price_histories(product_ids).await
+    }
+
+    async fn get_last_price_point(&self, product_id: &ObjectId) -> Result<Option<PricePoint>> {
+        self.inner.get_last_price_point(product_id).await
+    }
+
+    async fn insert_price_point(&self, product_id: &ObjectId, price: i32, timestamp: i32) -> Result<()> {
+        self.inner.insert_price_point(product_id, price, timestamp).await?;
+        self.invalidate_products();
+        Ok(())
+    }
+
+    async fn get_exchange_rates(&self) -> Result<Option<ExchangeRates>> {
+        if let Some(rates) = self.exchange_rates.get(&()) {
+            return Ok(Some(rates));
+        }
+        let rates = self.inner.get_exchange_rates().await?;
+        if let Some(rates) = &rates {
+            self.exchange_rates.insert((), rates.clone());
+        }
+        Ok(rates)
+    }
+
+    async fn set_exchange_rates(&self, rates: Document) -> Result<()> {
+        self.inner.set_exchange_rates(rates).await?;
+        self.exchange_rates.clear();
+        Ok(())
+    }
+
+    async fn insert_event(&self, event: Document) -> Result<()> {
+        self.inner.insert_event(event).await
+    }
+
+    async fn get_events(&self, entity: Option<&str>, entity_id: Option<&ObjectId>, offset: u64, size: u64) -> Result<Loaded<Vec<AuditEvent>>> {
+        self.inner.get_events(entity, entity_id, offset, size).await
+    }
+
+    async fn get_list_events(&self, list: &str, offset: u64, size: u64) -> Result<Loaded<Vec<AuditEvent>>> {
+        self.inner.get_list_events(list, offset, size).await
+    }
+
+    async fn dump_collection(&self, collection: &str) -> Result<Vec<Document>> {
+        self.inner.dump_collection(collection).await
+    }
+
+    async fn replace_collection(&self, collection: &str, documents: Vec<Document>) -> Result<()> {
+        self.inner.replace_collection(collection, documents).await?;
+        self.invalidate_all();
+        Ok(())
+    }
+}
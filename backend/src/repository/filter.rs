@@ -0,0 +1,111 @@
+use mongodb::bson::oid::ObjectId;
+
+use crate::model::FieldSelection;
+
+/// Optional restrictions on the products returned by a listing.
+#[derive(Clone, Default, PartialEq, Eq, Hash, Debug)]
+pub struct ProductFilter {
+    /// Slug of the list the products belong to, products of all lists if `None`.
+    list: Option<String>,
+    min_price: Option<i32>,
+    max_price: Option<i32>,
+    source: Option<ObjectId>,
+    /// Only archived or only current products if set.
+    archived: Option<bool>,
+    /// Normalized tag the products must carry.
+    tag: Option<String>,
+    /// Search terms, any of them must be in the name or description.
+    text: Option<String>,
+    /// Bounds of `last_seen`, when the products were last in a snapshot.
+    last_seen_after: Option<i32>,
+    last_seen_before: Option<i32>,
+    /// Leaves out the products with a cheaper offer from another source.
+    collapse_duplicates: bool,
+    /// Only products in stock or only those out of stock if set. Products of
+    /// unknown availability count as in stock.
+    in_stock: Option<bool>,
+    /// Only products whose link was found dead, or only those whose wasn't, if set.
+    link_dead: Option<bool>,
+    /// Fields to load, all of them if `None`.
+    fields: Option<FieldSelection>,
+}
+
+impl ProductFilter {
+    pub fn new(list: Option<String>, min_price: Option<i32>, max_price: Option<i32>, source: Option<ObjectId>) -> Self {
+        Self { list, min_price, max_price, source, archived: None, tag: None, text: None, last_seen_after: None, last_seen_before: None, collapse_duplicates: false, in_stock: None, link_dead: None, fields: None }
+    }
+    pub fn with_archived(mut self, archived: bool) -> Self {
+        self.archived = Some(archived);
+        self
+    }
+    pub fn with_tag(mut self, tag: Option<String>) -> Self {
+        self.tag = tag;
+        self
+    }
+    pub fn with_text(mut self, text: Option<String>) -> Self {
+        self.text = text;
+        self
+    }
+    pub fn with_last_seen(mut self, after: Option<i32>, before: Option<i32>) -> Self {
+        self.last_seen_after = after;
+        self.last_seen_before = before;
+        self
+    }
+    pub fn with_collapsed_duplicates(mut self, collapse: bool) -> Self {
+        self.collapse_duplicates = collapse;
+        self
+    }
+    pub fn with_in_stock(mut self, in_stock: Option<bool>) -> Self {
+        self.in_stock = in_stock;
+        self
+    }
+    pub fn with_link_dead(mut self, link_dead: bool) -> Self {
+        self.link_dead = Some(link_dead);
+        self
+    }
+    /// Only loads the selected fields where the repository supports it, the
+    /// other fields may or may not be set on the loaded products.
+    pub fn with_fields(mut self, fields: Option<FieldSelection>) -> Self {
+        self.fields = fields;
+        self
+    }
+    pub fn get_list(&self) -> Option<&str> {
+        self.list.as_deref()
+    }
+    pub fn get_min_price(&self) -> Option<i32> {
+        self.min_price
+    }
+    pub fn get_max_price(&self) -> Option<i32> {
+        self.max_price
+    }
+    pub fn get_source(&self) -> Option<&ObjectId> {
+        self.source.as_ref()
+    }
+    pub fn get_archived(&self) -> Option<bool> {
+        self.archived
+    }
+    pub fn get_tag(&self) -> Option<&str> {
+        self.tag.as_deref()
+    }
+    pub fn get_text(&self) -> Option<&str> {
+        self.text.as_deref()
+    }
+    pub fn get_last_seen_after(&self) -> Option<i32> {
+        self.last_seen_after
+    }
+    pub fn get_last_seen_before(&self) -> Option<i32> {
+        self.last_seen_before
+    }
+    pub fn is_collapsing_duplicates(&self) -> bool {
+        self.collapse_duplicates
+    }
+    pub fn get_in_stock(&self) -> Option<bool> {
+        self.in_stock
+    }
+    pub fn get_link_dead(&self) -> Option<bool> {
+        self.link_dead
+    }
+    pub fn get_fields(&self) -> Option<&FieldSelection> {
+        self.fields.as_ref()
+    }
+}
@@ -0,0 +1,171 @@
+use mongodb::bson::{doc, document::Document};
+use mongodb::error::{Error as MongoError, ErrorKind};
+use mongodb::Database;
+
+use crate::model::{AuditEvent, Budget, Comment, List, PledgePool, PriceAlert, Product, PushSubscription, Reservation, SchemaMigration, Stored, User, Wishlist};
+use crate::Result;
+
+const DUPLICATE_KEY_ERROR: i32 = 11000;
+const INDEX_OPTIONS_CONFLICT: i32 = 85;
+const INDEX_KEY_SPECS_CONFLICT: i32 = 86;
+
+struct Index {
+    collection: &'static str,
+    spec: Document,
+}
+
+/// Every index the queries rely on, created on startup if missing.
+fn get_indexes() -> Vec<Index> {
+    vec![
+        Index {
+            collection: Product::COLLECTION,
+            spec: doc! {
+                "key": { "name": "text", "description": "text" },
+                "name": "product_text",
+                "weights": { "name": 10, "description": 1 },
+            },
+        },
+        Index {
+            collection: Product::COLLECTION,
+            spec: doc! { "key": { "category": 1 }, "name": "product_category" },
+        },
+        Index {
+            collection: Product::COLLECTION,
+            spec: doc! { "key": { "source": 1 }, "name": "product_source" },
+        },
+        Index {
+            collection: Product::COLLECTION,
+            spec: doc! { "key": { "offers.source": 1 }, "name": "product_offers_source" },
+        },
+        // Archive listings read the archived products of one list.
+        Index {
+            collection: Product::COLLECTION,
+            spec: doc! { "key": { "list": 1, "archived": 1 }, "name": "product_list_archived" },
+        },
+        // Tag counts and tag filters read the tags of one list.
+        Index {
+            collection: Product::COLLECTION,
+            spec: doc! { "key": { "list": 1, "tags": 1 }, "name": "product_list_tags" },
+        },
+        // Last-Modified of the listings reads the latest change to a product.
+        Index {
+            collection: Product::COLLECTION,
+            spec: doc! { "key": { "updated_at": -1 }, "name": "product_updated_at" },
+        },
+        Index {
+            collection: Product::COLLECTION,
+            spec: doc! { "key": { "list": 1, "updated_at": -1 }, "name": "product_list_updated_at" },
+        },
+        // Item ids are only unique within a source, and the same item can be on
+        // several lists. Products added by hand have no item id. Fails to build
+        // while duplicates are stored, which the `product_source_item_id`
+        // migration merges.
+        Index {
+            collection: Product::COLLECTION,
+            spec: doc! {
+                "key": { "source": 1, "item_id": 1, "list": 1 },
+                "name": "product_source_item_id",
+                "unique": true,
+                "partialFilterExpression": { "item_id": { "$type": "string" } },
+            },
+        },
+        // Search suggestions look up names by prefix, ignoring case.
+        Index {
+            collection: Product::COLLECTION,
+            spec: doc! {
+                "key": { "list": 1, "name": 1 },
+                "name": "product_list_name",
+                "collation": { "locale": "en", "strength": 2 },
+            },
+        },
+        // The audit log is read newest first, in total or for one document.
+        Index {
+            collection: AuditEvent::COLLECTION,
+            spec: doc! { "key": { "timestamp": -1 }, "name": "events_timestamp" },
+        },
+        Index {
+            collection: AuditEvent::COLLECTION,
+            spec: doc! { "key": { "entity": 1, "entity_id": 1, "timestamp": -1 }, "name": "events_entity" },
+        },
+        Index {
+            collection: Reservation::COLLECTION,
+            spec: doc! { "key": { "product": 1 }, "name": "reservation_product", "unique": true },
+        },
+        Index {
+            collection: PledgePool::COLLECTION,
+            spec: doc! { "key": { "product": 1 }, "name": "pledge_pool_product", "unique": true },
+        },
+        // Comments are listed per product, oldest first.
+        Index {
+            collection: Comment::COLLECTION,
+            spec: doc! { "key": { "product": 1, "timestamp": 1 }, "name": "comment_product" },
+        },
+        Index {
+            collection: PushSubscription::COLLECTION,
+            spec: doc! { "key": { "endpoint": 1 }, "name": "push_subscription_endpoint", "unique": true },
+        },
+        // The alerts of a product are checked whenever a new price of it is recorded.
+        Index {
+            collection: PriceAlert::COLLECTION,
+            spec: doc! { "key": { "product": 1 }, "name": "price_alert_product" },
+        },
+        Index {
+            collection: Budget::COLLECTION,
+            spec: doc! { "key": { "list": 1 }, "name": "budget_list", "unique": true },
+        },
+        Index {
+            collection: List::COLLECTION,
+            spec: doc! { "key": { "slug": 1 }, "name": "list_slug", "unique": true },
+        },
+        Index {
+            collection: User::COLLECTION,
+            spec: doc! { "key": { "username": 1 }, "name": "user_username", "unique": true },
+        },
+        Index {
+            collection: Wishlist::COLLECTION,
+            spec: doc! { "key": { "timestamp": -1 }, "name": "wishlist_timestamp" },
+        },
+        Index {
+            collection: Wishlist::COLLECTION,
+            spec: doc! { "key": { "list": 1, "timestamp": -1 }, "name": "wishlist_list_timestamp" },
+        },
+        Index {
+            collection: SchemaMigration::COLLECTION,
+            spec: doc! { "key": { "version": 1 }, "name": "schema_migration_version", "unique": true },
+        },
+    ]
+}
+
+/// Creates the missing indexes and logs each one created. Indexes that clash
+/// with the stored data or an existing index are logged and skipped, so the
+/// backend still starts.
+pub async fn ensure_indexes(database: &Database) -> Result<()> {
+    for index in get_indexes() {
+        let name = index.spec.get_str("name").unwrap_or_default().to_string();
+        let command = doc! {
+            "createIndexes": index.collection,
+            "indexes": [index.spec],
+        };
+        match database.run_command(command, None).await {
+            Ok(response) => {
+                let before = response.get_i32("numIndexesBefore").unwrap_or_default();
+                let after = response.get_i32("numIndexesAfter").unwrap_or_default();
+                if after > before {
+                    info!("Created index '{}' on '{}'", name, index.collection);
+                }
+            }
+            Err(e) if is_conflict(&e) => {
+                error!("Could not create index '{}' on '{}': {}", name, index.collection, e);
+            }
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Ok(())
+}
+
+fn is_conflict(error: &MongoError) -> bool {
+    matches!(
+        error.kind.as_ref(),
+        ErrorKind::CommandError(e) if [DUPLICATE_KEY_ERROR, INDEX_OPTIONS_CONFLICT, INDEX_KEY_SPECS_CONFLICT].contains(&e.code)
+    )
+}
@@ -0,0 +1,1268 @@
+use async_trait::async_trait;
+use mongodb::bson::{doc, document::Document, oid::ObjectId, Bson};
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+use super::{current_timestamp, ProductFilter, WishlistRepository};
+use crate::{Result, Error};
+use crate::model::{AuditEvent, Availability, Budget, Category, CategoryCount, CategoryRule, ClickCount, Comment, ExchangeRates, List, Loaded, PriceAlert, PledgePool, PricePoint, PushSubscription, Source, Stats, Stored, TagCount, User, Wishlist, WishlistSummary, Product, DEFAULT_LIST};
+use crate::query::SortQuery;
+
+/// Repository keeping all documents in memory, mainly meant for testing
+/// endpoint logic without a running MongoDB.
+///
+/// Documents are stored in the same shape as in MongoDB and read through the
+/// same `Stored` models.
+#[derive(Default)]
+pub struct MemoryRepository {
+    collections: RwLock<Collections>,
+}
+
+#[derive(Default)]
+struct Collections {
+    list: Vec<Document>,
+    wishlist: Vec<Document>,
+    product: Vec<Document>,
+    source: Vec<Document>,
+    category: Vec<Document>,
+    category_rule: Vec<Document>,
+    price_history: Vec<Document>,
+    reservation: Vec<Document>,
+    pledge_pool: Vec<Document>,
+    comment: Vec<Document>,
+    push_subscription: Vec<Document>,
+    price_alert: Vec<Document>,
+    budget: Vec<Document>,
+    user: Vec<Document>,
+    exchange_rates: Vec<Document>,
+    events: Vec<Document>,
+}
+
+impl Collections {
+    fn get(&self, name: &str) -> Result<&Vec<Document>> {
+        match name {
+            "list" => Ok(&self.list),
+            "wishlist" => Ok(&self.wishlist),
+            "product" => Ok(&self.product),
+            "source" => Ok(&self.source),
+            "category" => Ok(&self.category),
+            "category_rule" => Ok(&self.category_rule),
+            "price_history" => Ok(&self.price_history),
+            "reservation" => Ok(&self.reservation),
+            "pledge_pool" => Ok(&self.pledge_pool),
+            "comment" => Ok(&self.comment),
+            "push_subscription" => Ok(&self.push_subscription),
+            "price_alert" => Ok(&self.price_alert),
+            "budget" => Ok(&self.budget),
+            "user" => Ok(&self.user),
+            "exchange_rates" => Ok(&self.exchange_rates),
+            "events" => Ok(&self.events),
+            _ => Err(Error::NotFound("collection")),
+        }
+    }
+
+    fn get_mut(&mut self, name: &str) -> Result<&mut Vec<Document>> {
+        match name {
+            "list" => Ok(&mut self.list),
+            "wishlist" => Ok(&mut self.wishlist),
+            "product" => Ok(&mut self.product),
+            "source" => Ok(&mut self.source),
+            "category" => Ok(&mut self.category),
+            "category_rule" => Ok(&mut self.category_rule),
+            "price_history" => Ok(&mut self.price_history),
+            "reservation" => Ok(&mut self.reservation),
+            "pledge_pool" => Ok(&mut self.pledge_pool),
+            "comment" => Ok(&mut self.comment),
+            "push_subscription" => Ok(&mut self.push_subscription),
+            "price_alert" => Ok(&mut self.price_alert),
+            "budget" => Ok(&mut self.budget),
+            "user" => Ok(&mut self.user),
+            "exchange_rates" => Ok(&mut self.exchange_rates),
+            "events" => Ok(&mut self.events),
+            _ => Err(Error::NotFound("collection")),
+        }
+    }
+}
+
+impl MemoryRepository {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_list(&self, list: Document) -> ObjectId {
+        add_document(&mut self.write().list, list)
+    }
+
+    pub fn add_wishlist(&self, wishlist: Document) -> ObjectId {
+        add_document(&mut self.write().wishlist, wishlist)
+    }
+
+    pub fn add_product(&self, product: Document) -> ObjectId {
+        add_document(&mut self.write().product, product)
+    }
+
+    pub fn add_source(&self, source: Document) -> ObjectId {
+        add_document(&mut self.write().source, source)
+    }
+
+    pub fn add_category(&self, category: Document) -> ObjectId {
+        add_document(&mut self.write().category, category)
+    }
+
+    fn read(&self) -> RwLockReadGuard<'_, Collections> {
+        self.collections.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn write(&self) -> RwLockWriteGuard<'_, Collections> {
+        self.collections.write().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[async_trait]
+impl WishlistRepository for MemoryRepository {
+    async fn ensure_indexes(&self) -> Result<()> {
+        Ok(())
+    }
+
+    async fn ping(&self) -> Result<()> {
+        Ok(())
+    }
+
+    async fn get_lists(&self) -> Result<Loaded<Vec<List>>> {
+        Ok(load_all(self.read().list.iter()))
+    }
+
+    async fn get_list_by_slug(&self, slug: &str) -> Result<Option<List>> {
+        load_one(self.read().list.iter().find(|l| l.get_str("slug").ok() == Some(slug)))
+    }
+
+    async fn insert_list(&self, list: Document) -> Result<ObjectId> {
+        let slug = list.get_str("slug")?.to_string();
+        let mut collections = self.write();
+        if collections.list.iter().any(|l| l.get_str("slug").ok() == Some(slug.as_str())) {
+            return Err(Error::Conflict(format!("list '{}' already exists", slug)));
+        }
+        Ok(add_document(&mut collections.list, list))
+    }
+
+    async fn update_list(&self, slug: &str, list: Document) -> Result<bool> {
+        let mut collections = self.write();
+        match collections.list.iter_mut().find(|l| l.get_str("slug").ok() == Some(slug)) {
+            Some(stored) => {
+                stored.extend(list);
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    async fn set_collaborator(&self, slug: &str, collaborator: Document) -> Result<bool> {
+        let user = collaborator.get_object_id("user")?.clone();
+        let mut collections = self.write();
+        let stored = match collections.list.iter_mut().find(|l| l.get_str("slug").ok() == Some(slug)) {
+            Some(stored) => stored,
+            None => return Ok(false),
+        };
+        let mut collaborators: Vec<Bson> = stored.get_array("collaborators").cloned().unwrap_or_default()
+            .into_iter()
+            .filter(|c| c.as_document().and_then(|c| c.get_object_id("user").ok()) != Some(&user))
+            .collect();
+        collaborators.push(Bson::Document(collaborator));
+        stored.insert("collaborators", collaborators);
+        Ok(true)
+    }
+
+    async fn remove_collaborator(&self, slug: &str, user: &ObjectId) -> Result<bool> {
+        let mut collections = self.write();
+        let stored = match collections.list.iter_mut().find(|l| l.get_str("slug").ok() == Some(slug)) {
+            Some(stored) => stored,
+            None => return Ok(false),
+        };
+        let collaborators = stored.get_array("collaborators").cloned().unwrap_or_default();
+        let count = collaborators.len();
+        let kept: Vec<Bson> = collaborators.into_iter()
+            .filter(|c| c.as_document().and_then(|c| c.get_object_id("user").ok()) != Some(user))
+            .collect();
+        let removed = kept.len() < count;
+        stored.insert("collaborators", kept);
+        Ok(removed)
+    }
+
+    async fn get_user_by_id(&self, id: &ObjectId) -> Result<Option<User>> {
+        load_one(self.read().user.iter().find(|u| has_id(u, id)))
+    }
+
+    async fn get_user_by_name(&self, username: &str) -> Result<Option<User>> {
+        load_one(self.read().user.iter().find(|u| u.get_str("username").ok() == Some(username)))
+    }
+
+    async fn insert_user(&self, user: Document) -> Result<ObjectId> {
+        let username = user.get_str("username")?.to_string();
+        let mut collections = self.write();
+        if collections.user.iter().any(|u| u.get_str("username").ok() == Some(username.as_str())) {
+            return Err(Error::Conflict(format!("user '{}' already exists", username)));
+        }
+        Ok(add_document(&mut collections.user, user))
+    }
+
+    async fn get_nth_wishlist_reverse(&self, list: &str, n: u64) -> Result<Wishlist> {
+        let collections = self.read();
+        let mut wishlists: Vec<&Document> = collections.wishlist.iter().filter(|w| in_list(w, list)).collect();
+        wishlists.sort_by_key(|w| std::cmp::Reverse(w.get_i32("timestamp").ok()));
+        load_one(wishlists.into_iter().nth(n as usize))?.ok_or(Error::EmptyResult)
+    }
+
+    async fn get_wishlist_at(&self, list: &str, timestamp: i32) -> Result<Option<Wishlist>> {
+        let collections = self.read();
+        let wishlist = collections.wishlist.iter()
+            .filter(|w| in_list(w, list) && w.get_i32("timestamp").map(|t| t <= timestamp).unwrap_or(false))
+            .max_by_key(|w| w.get_i32("timestamp").ok());
+        load_one(wishlist)
+    }
+
+    async fn get_wishlist_history(&self, list: &str, offset: u64, size: u64) -> Result<Loaded<Vec<WishlistSummary>>> {
+        let collections = self.read();
+        let mut wishlists: Vec<&Document> = collections.wishlist.iter().filter(|w| in_list(w, list)).collect();
+        wishlists.sort_by_key(|w| std::cmp::Reverse(w.get_i32("timestamp").ok()));
+        let summaries: Vec<Document> = paginate(wishlists, offset, size).into_iter()
+            .map(|w| {
+                let product_count = w.get_array("products").map(|p| p.len()).unwrap_or_default();
+                let mut summary = doc! {"_id": w.get("_id").cloned().unwrap_or(Bson::Null), "product_count": product_count as i64};
+                if let Some(timestamp) = w.get("timestamp") {
+                    summary.insert("timestamp", timestamp.clone());
+                }
+                summary
+            })
+            .collect();
+        Ok(load_all(summaries.iter()))
+    }
+
+    async fn insert_wishlist(&self, list: &str, timestamp: i32, product_ids: &[ObjectId]) -> Result<ObjectId> {
+        let mut wishlist = Document::new();
+        wishlist.insert("list", list);
+        wishlist.insert("timestamp", timestamp);
+        wishlist.insert("products", product_ids.iter().cloned().map(Bson::ObjectId).collect::<Vec<_>>());
+        Ok(self.add_wishlist(wishlist))
+    }
+
+    async fn delete_wishlists_at(&self, list: &str, timestamps: &[i32]) -> Result<u64> {
+        let mut collections = self.write();
+        let before = collections.wishlist.len();
+        collections.wishlist.retain(|w| !in_list(w, list) || w.get_i32("timestamp").map(|t| !timestamps.contains(&t)).unwrap_or(true));
+        Ok((before - collections.wishlist.len()) as u64)
+    }
+
+    async fn remove_wishlist_products(&self, id: &ObjectId, product_ids: &[ObjectId]) -> Result<bool> {
+        let mut collections = self.write();
+        let wishlist = match collections.wishlist.iter_mut().find(|w| has_id(w, id)) {
+            Some(wishlist) => wishlist,
+            None => return Ok(false),
+        };
+        if let Ok(products) = wishlist.get_array_mut("products") {
+            products.retain(|p| !matches!(p, Bson::ObjectId(id) if product_ids.contains(id)));
+        }
+        Ok(true)
+    }
+
+    async fn get_last_modified(&self, list: Option<&str>) -> Result<Option<i32>> {
+        let collections = self.read();
+        let in_scope = |document: &&Document| list.map(|list| in_list(document, list)).unwrap_or(true);
+        let snapshot = collections.wishlist.iter().filter(in_scope).filter_map(|w| w.get_i32("timestamp").ok()).max();
+        let product = collections.product.iter().filter(in_scope).filter_map(|p| p.get_i32("updated_at").ok()).max();
+        Ok(snapshot.max(product))
+    }
+
+    async fn get_product_by_id(&self, id: &ObjectId) -> Result<Option<Product>> {
+        load_one(self.read().product.iter().find(|p| has_id(p, id) && !is_deleted(p)))
+    }
+
+    async fn get_products_by_id(&self, ids: &[ObjectId]) -> Result<Loaded<Vec<Product>>> {
+        Ok(load_all(self.read().product.iter().filter(|p| id_in(p, ids) && !is_deleted(p))))
+    }
+
+    async fn get_products(&self, filter: &ProductFilter, sort: &SortQuery, offset: u64, size: u64) -> Result<Loaded<Vec<Product>>> {
+        let collections = self.read();
+        let mut products: Vec<&Document> = collections.product.iter()
+            .filter(|p| matches_filter(p, filter))
+            .collect();
+        sort_documents(&mut products, sort);
+        Ok(load_all(paginate(products, offset, size)))
+    }
+
+    async fn count_products(&self, filter: &ProductFilter) -> Result<u64> {
+        Ok(self.read().product.iter().filter(|p| matches_filter(p, filter)).count() as u64)
+    }
+
+    async fn update_archived(&self, list: &str, current_ids: &[ObjectId]) -> Result<u64> {
+        let mut updated = 0;
+        for product in self.write().product.iter_mut().filter(|p| in_list(p, list)) {
+            let archived = !id_in(product, current_ids);
+            if product.get_bool("archived").ok() != Some(archived) {
+                product.insert("archived", archived);
+                updated += 1;
+            }
+        }
+        Ok(updated)
+    }
+
+    async fn get_stats(&self, list: &str) -> Result<Stats> {
+        let collections = self.read();
+        let products: Vec<&Document> = collections.product.iter().filter(|p| in_list(p, list) && !is_deleted(p)).collect();
+        let archived_count = products.iter().filter(|p| p.get_bool("archived").unwrap_or(false)).count() as i64;
+        let current: Vec<&Document> = products.iter()
+            .copied()
+            .filter(|p| !p.get_bool("archived").unwrap_or(false))
+            .collect();
+        let totals: Vec<Document> = count_by(&current, "currency", &[]).into_iter()
+            .map(|mut total| {
+                let sum: i64 = current.iter()
+                    .filter(|p| p.get("currency").unwrap_or(&Bson::Null) == total.get("_id").unwrap_or(&Bson::Null))
+                    .filter_map(|p| p.get_i32("price").ok())
+                    .map(i64::from)
+                    .sum();
+                total.insert("total", sum);
+                total
+            })
+            .collect();
+        let ratings: Vec<i32> = products.iter().filter_map(|p| p.get_i32("rating").ok()).collect();
+        let mut stats = doc! {
+            "product_count": products.len() as i64,
+            "archived_count": archived_count,
+            "categories": count_by(&products, "category", &collections.category),
+            "sources": count_by(&products, "source", &collections.source),
+            "current": totals,
+            "rated_count": ratings.len() as i64,
+        };
+        if !ratings.is_empty() {
+            let sum: i64 = ratings.iter().copied().map(i64::from).sum();
+            stats.insert("average_rating", sum as f64 / ratings.len() as f64);
+        }
+        let last_snapshot = collections.wishlist.iter()
+            .filter(|w| in_list(w, list))
+            .filter_map(|w| w.get_i32("timestamp").ok())
+            .max();
+        if let Some(timestamp) = last_snapshot {
+            stats.insert("last_snapshot", timestamp);
+        }
+        Stats::from_document(stats)
+    }
+
+    async fn get_tags(&self, list: &str) -> Result<Loaded<Vec<TagCount>>> {
+        let collections = self.read();
+        let mut counts: BTreeMap<&str, i64> = BTreeMap::new();
+        for product in collections.product.iter().filter(|p| in_list(p, list) && !is_deleted(p)) {
+            for tag in product.get_array("tags").map(|t| t.as_slice()).unwrap_or_default() {
+                if let Bson::String(tag) = tag {
+                    *counts.entry(tag.as_str()).or_default() += 1;
+                }
+            }
+        }
+        let mut counts: Vec<(&str, i64)> = counts.into_iter().collect();
+        counts.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+        let tags: Vec<Document> = counts.into_iter().map(|(tag, count)| doc! {"_id": tag, "count": count}).collect();
+        Ok(load_all(tags.iter()))
+    }
+
+    async fn search_products(&self, query: &str, lists: Option<&[String]>, offset: u64, size: u64) -> Result<Loaded<Vec<Product>>> {
+        let terms: Vec<String> = query.split_whitespace().map(str::to_lowercase).collect();
+        let collections = self.read();
+        let mut scored: Vec<(usize, &Document)> = collections.product.iter()
+            .filter(|p| in_any_list(p, lists) && !is_deleted(p))
+            .filter_map(|p| {
+                let text = format!(
+                    "{} {}",
+                    p.get_str("name").unwrap_or_default(),
+                    p.get_str("description").unwrap_or_default()
+                ).to_lowercase();
+                let score = terms.iter().filter(|t| text.contains(t.as_str())).count();
+                if score > 0 {
+                    Some((score, p))
+                } else {
+                    None
+                }
+            })
+            .collect();
+        scored.sort_by_key(|(score, _)| std::cmp::Reverse(*score));
+        Ok(load_all(paginate(scored.into_iter().map(|(_, p)| p).collect(), offset, size)))
+    }
+
+    async fn suggest_product_names(&self, list: &str, prefix: &str, size: u64) -> Result<Vec<String>> {
+        let prefix = prefix.to_lowercase();
+        let collections = self.read();
+        let mut names: Vec<&str> = collections.product.iter()
+            .filter(|p| in_list(p, list) && !is_deleted(p))
+            .filter_map(|p| p.get_str("name").ok())
+            .filter(|name| name.to_lowercase().starts_with(&prefix))
+            .collect();
+        names.sort_by_key(|name| name.to_lowercase());
+        names.dedup_by_key(|name| name.to_lowercase());
+        Ok(names.into_iter().take(size as usize).map(String::from).collect())
+    }
+
+    async fn get_products_by_category(&self, categories: Option<&[ObjectId]>, lists: Option<&[String]>, sort: &SortQuery, offset: u64, size: u64) -> Result<Loaded<Vec<Product>>> {
+        let collections = self.read();
+        let mut products: Vec<&Document> = collections.product.iter()
+            .filter(|p| in_category(p, categories) && in_any_list(p, lists) && !is_deleted(p))
+            .collect();
+        sort_documents(&mut products, sort);
+        Ok(load_all(paginate(products, offset, size)))
+    }
+
+    async fn count_products_by_category(&self, categories: Option<&[ObjectId]>, lists: Option<&[String]>) -> Result<u64> {
+        Ok(self.read().product.iter().filter(|p| in_category(p, categories) && in_any_list(p, lists) && !is_deleted(p)).count() as u64)
+    }
+
+    async fn count_products_per_category(&self, lists: Option<&[String]>) -> Result<Loaded<Vec<CategoryCount>>> {
+        let mut counts: BTreeMap<Option<ObjectId>, (i64, i64)> = BTreeMap::new();
+        for product in self.read().product.iter().filter(|p| in_any_list(p, lists) && !is_deleted(p)) {
+            let count = counts.entry(product.get_object_id("category").ok().cloned()).or_default();
+            if product.get_bool("archived").unwrap_or(false) {
+                count.1 += 1;
+            } else {
+                count.0 += 1;
+            }
+        }
+        let counts: Vec<Document> = counts.into_iter()
+            .map(|(category, (current, archived))| doc! {
+                "_id": category.map(Bson::ObjectId).unwrap_or(Bson::Null),
+                "current_count": current,
+                "archived_count": archived,
+            })
+            .collect();
+        Ok(load_all(counts.iter()))
+    }
+
+    async fn insert_product(&self, mut product: Document) -> Result<ObjectId> {
+        product.insert("updated_at", current_timestamp());
+        let mut collections = self.write();
+        if let Some(item_id) = find_duplicate_item(&collections.product, None, &product) {
+            return Err(Error::Conflict(format!("item '{}' is already on the list", item_id)));
+        }
+        Ok(add_document(&mut collections.product, product))
+    }
+
+    async fn upsert_product(&self, mut product: Document) -> Result<(ObjectId, bool)> {
+        product.insert("updated_at", current_timestamp());
+        let mut collections = self.write();
+        if let Ok(item_id) = product.get_str("item_id") {
+            let list = product.get_str("list").unwrap_or(DEFAULT_LIST);
+            let existing = collections.product.iter_mut().find(|p| {
+                in_list(p, list) && p.get("source") == product.get("source") && p.get_str("item_id").ok() == Some(item_id)
+            });
+            if let Some(existing) = existing {
+                if is_deleted(existing) {
+                    return Err(Error::Conflict("the product is in the trash, restore or purge it first".to_string()));
+                }
+                for field in ["price", "last_seen", "updated_at"].iter() {
+                    if let Some(value) = product.get(field) {
+                        existing.insert(*field, value.clone());
+                    }
+                }
+                return Ok((existing.get_object_id("_id")?.clone(), false));
+            }
+        }
+        Ok((add_document(&mut collections.product, product), true))
+    }
+
+    async fn find_duplicate_product(&self, list: &str, item_id: Option<&str>, url: &str) -> Result<Option<Product>> {
+        load_one(self.read().product.iter().find(|p| {
+            in_list(p, list) && (p.get_str("url").ok() == Some(url) || (item_id.is_some() && p.get_str("item_id").ok() == item_id))
+        }))
+    }
+
+    async fn update_product(&self, id: &ObjectId, mut product: Document) -> Result<bool> {
+        product.insert("updated_at", current_timestamp());
+        let mut collections = self.write();
+        let mut updated = match collections.product.iter().find(|p| has_id(p, id) && !is_deleted(p)) {
+            Some(updated) => updated.clone(),
+            None => return Ok(false),
+        };
+        updated.extend(product.clone());
+        if let Some(item_id) = find_duplicate_item(&collections.product, Some(id), &updated) {
+            return Err(Error::Conflict(format!("item '{}' is already on the list", item_id)));
+        }
+        Ok(update_document(&mut collections.product, id, product))
+    }
+
+    async fn update_products(&self, ids: &[ObjectId], mut product: Document) -> Result<u64> {
+        product.insert("updated_at", current_timestamp());
+        let mut updated = 0;
+        for existing in self.write().product.iter_mut().filter(|p| id_in(p, ids) && !is_deleted(p)) {
+            existing.extend(product.clone());
+            updated += 1;
+        }
+        Ok(updated)
+    }
+
+    async fn delete_product(&self, id: &ObjectId, timestamp: i32) -> Result<Option<Product>> {
+        let mut collections = self.write();
+        match collections.product.iter_mut().find(|p| has_id(p, id) && !is_deleted(p)) {
+            Some(product) => {
+                product.insert("deleted_at", timestamp);
+                product.insert("updated_at", timestamp);
+                load_one(Some(&*product))
+            }
+            None => Ok(None),
+        }
+    }
+
+    async fn get_deleted_products(&self, offset: u64, size: u64) -> Result<Loaded<Vec<Product>>> {
+        let collections = self.read();
+        let mut deleted: Vec<&Document> = collections.product.iter().filter(|p| is_deleted(p)).collect();
+        deleted.sort_by(|a, b| compare_values(b.get("deleted_at"), a.get("deleted_at")).then_with(|| compare_values(b.get("_id"), a.get("_id"))));
+        Ok(load_all(paginate(deleted, offset, size)))
+    }
+
+    async fn restore_product(&self, id: &ObjectId) -> Result<Option<Product>> {
+        let mut collections = self.write();
+        match collections.product.iter_mut().find(|p| has_id(p, id) && is_deleted(p)) {
+            Some(product) => {
+                product.remove("deleted_at");
+                product.insert("updated_at", current_timestamp());
+                load_one(Some(&*product))
+            }
+            None => Ok(None),
+        }
+    }
+
+    async fn purge_product(&self, id: &ObjectId) -> Result<Option<Product>> {
+        let mut collections = self.write();
+        if !collections.product.iter().any(|p| has_id(p, id) && is_deleted(p)) {
+            return Ok(None);
+        }
+        let deleted = load_one(remove_document(&mut collections.product, id).as_ref())?;
+        if deleted.is_some() {
+            collections.price_history.retain(|p| p.get_object_id("product").ok() != Some(id));
+            collections.reservation.retain(|r| r.get_object_id("product").ok() != Some(id));
+            collections.price_alert.retain(|a| a.get_object_id("product").ok() != Some(id));
+            collections.pledge_pool.retain(|p| p.get_object_id("product").ok() != Some(id));
+            collections.comment.retain(|c| c.get_object_id("product").ok() != Some(id));
+        }
+        Ok(deleted)
+    }
+
+    async fn add_product_views(&self, views: &[(ObjectId, i64)]) -> Result<()> {
+        let mut collections = self.write();
+        for (id, count) in views {
+            if let Some(product) = collections.product.iter_mut().find(|p| has_id(p, id) && !is_deleted(p)) {
+                let total = product.get_i64("views").unwrap_or(0) + count;
+                product.insert("views", total);
+            }
+        }
+        Ok(())
+    }
+
+    async fn record_click(&self, id: &ObjectId) -> Result<Option<Product>> {
+        let mut collections = self.write();
+        let product = match collections.product.iter_mut().find(|p| has_id(p, id) && !is_deleted(p)) {
+            Some(product) => product,
+            None => return Ok(None),
+        };
+        let clicks = product.get_i64("clicks").unwrap_or(0);
+        product.insert("clicks", clicks + 1);
+        load_one(Some(&*product))
+    }
+
+    async fn get_click_counts(&self, list: &str) -> Result<Loaded<Vec<ClickCount>>> {
+        let collections = self.read();
+        let mut clicked: Vec<&Document> = collections.product.iter()
+            .filter(|p| in_list(p, list) && !is_deleted(p) && p.get_i64("clicks").map(|c| c > 0).unwrap_or(false))
+            .collect();
+        clicked.sort_by_key(|p| std::cmp::Reverse(p.get_i64("clicks").ok()));
+        Ok(load_all(clicked))
+    }
+
+    async fn get_reserved_product_ids(&self, product_ids: &[ObjectId]) -> Result<Vec<ObjectId>> {
+        Ok(self.read().reservation.iter()
+            .filter_map(|r| r.get_object_id("product").ok())
+            .filter(|id| product_ids.contains(id))
+            .cloned()
+            .collect())
+    }
+
+    async fn insert_reservation(&self, product_id: &ObjectId, token: &str, timestamp: i32) -> Result<bool> {
+        let mut collections = self.write();
+        if collections.reservation.iter().any(|r| r.get_object_id("product").ok() == Some(product_id)) {
+            return Ok(false);
+        }
+        let mut reservation = Document::new();
+        reservation.insert("product", product_id.clone());
+        reservation.insert("token", token);
+        reservation.insert("timestamp", timestamp);
+        add_document(&mut collections.reservation, reservation);
+        Ok(true)
+    }
+
+    async fn delete_reservation(&self, product_id: &ObjectId, token: &str) -> Result<bool> {
+        let mut collections = self.write();
+        let index = collections.reservation.iter().position(|r| {
+            r.get_object_id("product").ok() == Some(product_id) && r.get_str("token").ok() == Some(token)
+        });
+        Ok(index.map(|i| collections.reservation.remove(i)).is_some())
+    }
+
+    async fn get_pledge_pool(&self, product_id: &ObjectId) -> Result<Option<PledgePool>> {
+        load_one(self.read().pledge_pool.iter().find(|p| p.get_object_id("product").ok() == Some(product_id)))
+    }
+
+    async fn get_pledge_pools(&self, product_ids: &[ObjectId]) -> Result<Loaded<Vec<PledgePool>>> {
+        Ok(load_all(self.read().pledge_pool.iter().filter(|p| {
+            p.get_object_id("product").map(|id| product_ids.contains(id)).unwrap_or(false)
+        })))
+    }
+
+    async fn insert_pledge_pool(&self, pool: Document) -> Result<bool> {
+        let product_id = pool.get_object_id("product")?.clone();
+        let mut collections = self.write();
+        if collections.pledge_pool.iter().any(|p| p.get_object_id("product").ok() == Some(&product_id)) {
+            return Ok(false);
+        }
+        add_document(&mut collections.pledge_pool, pool);
+        Ok(true)
+    }
+
+    async fn add_pledge(&self, product_id: &ObjectId, pledge: Document) -> Result<bool> {
+        let amount = pledge.get_i64("amount")?;
+        let mut collections = self.write();
+        let pool = match collections.pledge_pool.iter_mut().find(|p| p.get_object_id("product").ok() == Some(product_id)) {
+            Some(pool) => pool,
+            None => return Ok(false),
+        };
+        let funded = pool.get_i64("funded").unwrap_or(0);
+        if funded + amount > pool.get_i64("target")? {
+            return Ok(false);
+        }
+        let mut pledges = pool.get_array("pledges").cloned().unwrap_or_default();
+        pledges.push(Bson::Document(pledge));
+        pool.insert("funded", funded + amount);
+        pool.insert("pledges", pledges);
+        Ok(true)
+    }
+
+    async fn remove_pledge(&self, product_id: &ObjectId, token: &str) -> Result<bool> {
+        let mut collections = self.write();
+        let pool = match collections.pledge_pool.iter_mut().find(|p| p.get_object_id("product").ok() == Some(product_id)) {
+            Some(pool) => pool,
+            None => return Ok(false),
+        };
+        let (withdrawn, kept): (Vec<Bson>, Vec<Bson>) = pool.get_array("pledges").cloned().unwrap_or_default()
+            .into_iter()
+            .partition(|p| p.as_document().and_then(|p| p.get_str("token").ok()) == Some(token));
+        if withdrawn.is_empty() {
+            return Ok(false);
+        }
+        let amount: i64 = withdrawn.iter().filter_map(|p| p.as_document()?.get_i64("amount").ok()).sum();
+        let funded = pool.get_i64("funded").unwrap_or(0);
+        pool.insert("funded", funded - amount);
+        pool.insert("pledges", kept);
+        Ok(true)
+    }
+
+    async fn delete_empty_pledge_pool(&self, product_id: &ObjectId) -> Result<bool> {
+        let mut collections = self.write();
+        let index = collections.pledge_pool.iter().position(|p| {
+            p.get_object_id("product").ok() == Some(product_id) && p.get_array("pledges").map(Vec::is_empty).unwrap_or(true)
+        });
+        Ok(index.map(|i| collections.pledge_pool.remove(i)).is_some())
+    }
+
+    async fn get_comments(&self, product_id: &ObjectId, offset: u64, size: u64) -> Result<Loaded<Vec<Comment>>> {
+        let collections = self.read();
+        let mut comments: Vec<&Document> = collections.comment.iter()
+            .filter(|c| c.get_object_id("product").ok() == Some(product_id))
+            .collect();
+        comments.sort_by(|a, b| compare_values(a.get("timestamp"), b.get("timestamp")).then_with(|| compare_values(a.get("_id"), b.get("_id"))));
+        Ok(load_all(paginate(comments, offset, size)))
+    }
+
+    async fn get_comment(&self, id: &ObjectId) -> Result<Option<Comment>> {
+        load_one(self.read().comment.iter().find(|c| has_id(c, id)))
+    }
+
+    async fn insert_comment(&self, comment: Document) -> Result<ObjectId> {
+        Ok(add_document(&mut self.write().comment, comment))
+    }
+
+    async fn delete_comment(&self, id: &ObjectId) -> Result<bool> {
+        Ok(remove_document(&mut self.write().comment, id).is_some())
+    }
+
+    async fn get_push_subscriptions(&self) -> Result<Loaded<Vec<PushSubscription>>> {
+        Ok(load_all(self.read().push_subscription.iter()))
+    }
+
+    async fn get_push_subscription(&self, endpoint: &str) -> Result<Option<PushSubscription>> {
+        load_one(self.read().push_subscription.iter().find(|s| s.get_str("endpoint").ok() == Some(endpoint)))
+    }
+
+    async fn upsert_push_subscription(&self, subscription: Document) -> Result<()> {
+        let endpoint = subscription.get_str("endpoint")?.to_string();
+        let mut collections = self.write();
+        match collections.push_subscription.iter_mut().find(|s| s.get_str("endpoint").ok() == Some(endpoint.as_str())) {
+            Some(stored) => {
+                let id = stored.get("_id").cloned();
+                *stored = subscription;
+                if let Some(id) = id {
+                    stored.insert("_id", id);
+                }
+            }
+            None => {
+                add_document(&mut collections.push_subscription, subscription);
+            }
+        }
+        Ok(())
+    }
+
+    async fn delete_push_subscription(&self, endpoint: &str) -> Result<bool> {
+        let mut collections = self.write();
+        let index = collections.push_subscription.iter().position(|s| s.get_str("endpoint").ok() == Some(endpoint));
+        Ok(index.map(|i| collections.push_subscription.remove(i)).is_some())
+    }
+
+    async fn get_price_alerts(&self) -> Result<Loaded<Vec<PriceAlert>>> {
+        let collections = self.read();
+        let mut alerts: Vec<&Document> = collections.price_alert.iter().collect();
+        alerts.sort_by_key(|a| std::cmp::Reverse(a.get_i32("created").unwrap_or_default()));
+        Ok(load_all(alerts))
+    }
+
+    async fn get_price_alerts_of_product(&self, product_id: &ObjectId) -> Result<Loaded<Vec<PriceAlert>>> {
+        Ok(load_all(self.read().price_alert.iter().filter(|a| a.get_object_id("product").ok() == Some(product_id))))
+    }
+
+    async fn get_price_alert(&self, id: &ObjectId) -> Result<Option<PriceAlert>> {
+        load_one(self.read().price_alert.iter().find(|a| has_id(a, id)))
+    }
+
+    async fn insert_price_alert(&self, alert: Document) -> Result<ObjectId> {
+        Ok(add_document(&mut self.write().price_alert, alert))
+    }
+
+    async fn set_price_alert_triggered(&self, id: &ObjectId, timestamp: i32) -> Result<bool> {
+        Ok(update_document(&mut self.write().price_alert, id, doc! {"triggered": timestamp}))
+    }
+
+    async fn delete_price_alert(&self, id: &ObjectId) -> Result<bool> {
+        Ok(remove_document(&mut self.write().price_alert, id).is_some())
+    }
+
+    async fn get_budget(&self, list: &str) -> Result<Option<Budget>> {
+        load_one(self.read().budget.iter().find(|b| b.get_str("list").ok() == Some(list)))
+    }
+
+    async fn upsert_budget(&self, budget: Document) -> Result<()> {
+        let list = budget.get_str("list")?.to_string();
+        let mut collections = self.write();
+        match collections.budget.iter_mut().find(|b| b.get_str("list").ok() == Some(list.as_str())) {
+            Some(stored) => {
+                let id = stored.get("_id").cloned();
+                *stored = budget;
+                if let Some(id) = id {
+                    stored.insert("_id", id);
+                }
+            }
+            None => {
+                add_document(&mut collections.budget, budget);
+            }
+        }
+        Ok(())
+    }
+
+    async fn delete_budget(&self, list: &str) -> Result<bool> {
+        let mut collections = self.write();
+        let index = collections.budget.iter().position(|b| b.get_str("list").ok() == Some(list));
+        Ok(index.map(|i| collections.budget.remove(i)).is_some())
+    }
+
+    async fn get_sources(&self) -> Result<Loaded<Vec<Source>>> {
+        Ok(load_all(self.read().source.iter()))
+    }
+
+    async fn get_sources_by_id(&self, ids: &[ObjectId]) -> Result<Loaded<Vec<Source>>> {
+        Ok(load_all(self.read().source.iter().filter(|s| id_in(s, ids))))
+    }
+
+    async fn get_source_by_name(&self, name: &str) -> Result<Option<Source>> {
+        load_one(self.read().source.iter().find(|s| s.get_str("name").ok() == Some(name)))
+    }
+
+    async fn source_exists(&self, id: &ObjectId) -> Result<bool> {
+        Ok(self.read().source.iter().any(|s| has_id(s, id)))
+    }
+
+    async fn insert_source(&self, source: Document) -> Result<ObjectId> {
+        Ok(self.add_source(source))
+    }
+
+    async fn update_source(&self, id: &ObjectId, source: Document) -> Result<bool> {
+        Ok(update_document(&mut self.write().source, id, source))
+    }
+
+    async fn get_categories(&self) -> Result<Loaded<Vec<Category>>> {
+        let collections = self.read();
+        let mut categories: Vec<&Document> = collections.category.iter().collect();
+        categories.sort_by(|a, b| {
+            let order = |c: &Document| c.get_i32("sort_order").unwrap_or(0);
+            order(a).cmp(&order(b)).then_with(|| a.get_str("name").ok().cmp(&b.get_str("name").ok()))
+        });
+        Ok(load_all(categories))
+    }
+
+    async fn get_category_by_id(&self, id: &ObjectId) -> Result<Option<Category>> {
+        load_one(self.read().category.iter().find(|c| has_id(c, id)))
+    }
+
+    async fn get_category_by_name(&self, name: &str) -> Result<Option<Category>> {
+        load_one(self.read().category.iter().find(|c| c.get_str("name").ok() == Some(name)))
+    }
+
+    async fn category_name_exists(&self, name: &str, except: Option<&ObjectId>) -> Result<bool> {
+        Ok(self.read().category.iter().any(|c| {
+            c.get_str("name").ok() == Some(name) && except.map(|id| !has_id(c, id)).unwrap_or(true)
+        }))
+    }
+
+    async fn insert_category(&self, category: Document) -> Result<ObjectId> {
+        Ok(self.add_category(category))
+    }
+
+    async fn update_category(&self, id: &ObjectId, category: Document) -> Result<bool> {
+        Ok(update_document(&mut self.write().category, id, category))
+    }
+
+    async fn delete_category(&self, id: &ObjectId) -> Result<bool> {
+        Ok(remove_document(&mut self.write().category, id).is_some())
+    }
+
+    async fn reassign_category(&self, from: &ObjectId, to: &ObjectId) -> Result<u64> {
+        let mut moved = 0;
+        for product in self.write().product.iter_mut() {
+            if product.get_object_id("category").ok() == Some(from) {
+                product.insert("category", to.clone());
+                moved += 1;
+            }
+        }
+        Ok(moved)
+    }
+
+    async fn merge_category(&self, from: &ObjectId, into: &ObjectId) -> Result<u64> {
+        let mut collections = self.write();
+        let mut moved = 0;
+        for product in collections.product.iter_mut().filter(|p| p.get_object_id("category").ok() == Some(from)) {
+            product.insert("category", into.clone());
+            moved += 1;
+        }
+        for category in collections.category.iter_mut().filter(|c| c.get_object_id("parent").ok() == Some(from)) {
+            category.insert("parent", into.clone());
+        }
+        for rule in collections.category_rule.iter_mut().filter(|r| r.get_object_id("category").ok() == Some(from)) {
+            rule.insert("category", into.clone());
+        }
+        Ok(moved)
+    }
+
+    async fn count_category_references(&self, id: &ObjectId) -> Result<u64> {
+        let collections = self.read();
+        let products = collections.product.iter().filter(|p| p.get_object_id("category").ok() == Some(id)).count();
+        let subcategories = collections.category.iter().filter(|c| c.get_object_id("parent").ok() == Some(id)).count();
+        let rules = collections.category_rule.iter().filter(|r| r.get_object_id("category").ok() == Some(id)).count();
+        Ok((products + subcategories + rules) as u64)
+    }
+
+    async fn get_rules(&self) -> Result<Loaded<Vec<CategoryRule>>> {
+        Ok(load_all(self.read().category_rule.iter()))
+    }
+
+    async fn get_rule_by_id(&self, id: &ObjectId) -> Result<Option<CategoryRule>> {
+        load_one(self.read().category_rule.iter().find(|r| has_id(r, id)))
+    }
+
+    async fn insert_rule(&self, rule: Document) -> Result<ObjectId> {
+        Ok(add_document(&mut self.write().category_rule, rule))
+    }
+
+    async fn update_rule(&self, id: &ObjectId, rule: Document) -> Result<bool> {
+        Ok(update_document(&mut self.write().category_rule, id, rule))
+    }
+
+    async fn delete_rule(&self, id: &ObjectId) -> Result<bool> {
+        Ok(remove_document(&mut self.write().category_rule, id).is_some())
+    }
+
+    async fn get_price_history(&self, product_id: &ObjectId) -> Result<Loaded<Vec<PricePoint>>> {
+        self.get_price_histories(std::slice::from_ref(product_id)).await
+    }
+
+    async fn get_price_histories(&self, product_ids: &[ObjectId]) -> Result<Loaded<Vec<PricePoint>>> {
+        let collections = self.read();
+        let mut history: Vec<&Document> = collections.price_history.iter()
+            .filter(|p| p.get_object_id("product").map(|id| product_ids.contains(id)).unwrap_or(false))
+            .collect();
+        history.sort_by_key(|p| p.get_i32("timestamp").ok());
+        Ok(load_all(history))
+    }
+
+    async fn get_last_price_point(&self, product_id: &ObjectId) -> Result<Option<PricePoint>> {
+        let collections = self.read();
+        let point = collections.price_history.iter()
+            .filter(|p| p.get_object_id("product").ok() == Some(product_id))
+            .max_by_key(|p| p.get_i32("timestamp").ok());
+        load_one(point)
+    }
+
+    async fn insert_price_point(&self, product_id: &ObjectId, price: i32, timestamp: i32) -> Result<()> {
+        let mut collections = self.write();
+        let mut point = Document::new();
+        point.insert("product", product_id.clone());
+        point.insert("price", price);
+        point.insert("timestamp", timestamp);
+        add_document(&mut collections.price_history, point);
+
+        if let Some(product) = collections.product.iter_mut().find(|p| has_id(p, product_id)) {
+            let (lowest, at_all_time_low) = match product.get_i32("lowest_price") {
+                Ok(lowest) if lowest < price => (lowest, false),
+                Ok(lowest) if lowest == price => (lowest, product.get_bool("at_all_time_low").unwrap_or(false)),
+                Ok(_) => (price, true),
+                Err(_) => (price, false),
+            };
+            product.insert("current_price", price);
+            product.insert("lowest_price", lowest);
+            product.insert("at_all_time_low", at_all_time_low);
+            product.insert("updated_at", timestamp);
+        }
+        Ok(())
+    }
+
+    async fn get_exchange_rates(&self) -> Result<Option<ExchangeRates>> {
+        load_one(self.read().exchange_rates.first())
+    }
+
+    async fn set_exchange_rates(&self, rates: Document) -> Result<()> {
+        let mut collections = self.write();
+        collections.exchange_rates.clear();
+        add_document(&mut collections.exchange_rates, rates);
+        Ok(())
+    }
+
+    async fn insert_event(&self, event: Document) -> Result<()> {
+        add_document(&mut self.write().events, event);
+        Ok(())
+    }
+
+    async fn get_events(&self, entity: Option<&str>, entity_id: Option<&ObjectId>, offset: u64, size: u64) -> Result<Loaded<Vec<AuditEvent>>> {
+        let collections = self.read();
+        let mut events: Vec<&Document> = collections.events.iter()
+            .filter(|e| entity.map(|entity| e.get_str("entity").ok() == Some(entity)).unwrap_or(true))
+            .filter(|e| entity_id.map(|id| e.get_object_id("entity_id").ok() == Some(id)).unwrap_or(true))
+            .collect();
+        events.sort_by(|a, b| compare_values(b.get("timestamp"), a.get("timestamp")).then_with(|| compare_values(b.get("_id"), a.get("_id"))));
+        Ok(load_all(paginate(events, offset, size)))
+    }
+
+    async fn get_list_events(&self, list: &str, offset: u64, size: u64) -> Result<Loaded<Vec<AuditEvent>>> {
+        let collections = self.read();
+        let product_ids: Vec<&ObjectId> = collections.product.iter()
+            .filter(|p| in_list(p, list))
+            .filter_map(|p| p.get_object_id("_id").ok())
+            .collect();
+        let in_payload = |e: &Document, field: &str| e.get_document("payload").ok().and_then(|p| p.get_str(field).ok()) == Some(list);
+        let mut events: Vec<&Document> = collections.events.iter()
+            .filter(|e| match e.get_str("entity").unwrap_or_default() {
+                entity if entity == List::COLLECTION => in_payload(e, "slug"),
+                entity if entity == Budget::COLLECTION => in_payload(e, "list"),
+                entity if entity == Product::COLLECTION => e.get_object_id("entity_id").map(|id| product_ids.contains(&id)).unwrap_or(false),
+                _ => false,
+            })
+            .collect();
+        events.sort_by(|a, b| compare_values(b.get("timestamp"), a.get("timestamp")).then_with(|| compare_values(b.get("_id"), a.get("_id"))));
+        Ok(load_all(paginate(events, offset, size)))
+    }
+
+    async fn dump_collection(&self, collection: &str) -> Result<Vec<Document>> {
+        let collections = self.read();
+        let mut documents = collections.get(collection)?.clone();
+        documents.sort_by(|a, b| a.get_object_id("_id").ok().cmp(&b.get_object_id("_id").ok()));
+        Ok(documents)
+    }
+
+    async fn replace_collection(&self, collection: &str, documents: Vec<Document>) -> Result<()> {
+        let mut collections = self.write();
+        let stored = collections.get_mut(collection)?;
+        stored.clear();
+        for document in documents {
+            add_document(stored, document);
+        }
+        Ok(())
+    }
+}
+
+fn add_document(collection: &mut Vec<Document>, mut document: Document) -> ObjectId {
+    let id = match document.get_object_id("_id") {
+        Ok(id) => id.clone(),
+        Err(_) => {
+            let id = ObjectId::new();
+            document.insert("_id", id.clone());
+            id
+        }
+    };
+    collection.push(document);
+    id
+}
+
+fn update_document(collection: &mut [Document], id: &ObjectId, fields: Document) -> bool {
+    match collection.iter_mut().find(|d| has_id(d, id)) {
+        Some(document) => {
+            document.extend(fields);
+            true
+        }
+        None => false,
+    }
+}
+
+fn remove_document(collection: &mut Vec<Document>, id: &ObjectId) -> Option<Document> {
+    let index = collection.iter().position(|d| has_id(d, id))?;
+    Some(collection.remove(index))
+}
+
+/// Mirrors the unique index on `source`, `item_id` and `list`, returns the
+/// item id `product` shares with another product than `except`.
+fn find_duplicate_item<'a>(products: &[Document], except: Option<&ObjectId>, product: &'a Document) -> Option<&'a str> {
+    let item_id = product.get_str("item_id").ok()?;
+    let list = product.get_str("list").ok();
+    products.iter()
+        .filter(|p| except.map(|id| !has_id(p, id)).unwrap_or(true))
+        .any(|p| p.get_str("item_id").ok() == Some(item_id) && p.get_str("list").ok() == list && p.get("source") == product.get("source"))
+        .then_some(item_id)
+}
+
+/// Mirrors the grouping of the statistics aggregation, missing values are
+/// counted as `null`.
+fn count_by(products: &[&Document], field: &str, referenced: &[Document]) -> Vec<Document> {
+    let mut counts: Vec<(Bson, i64)> = Vec::new();
+    for product in products {
+        let value = product.get(field).cloned().unwrap_or(Bson::Null);
+        match counts.iter_mut().find(|(v, _)| *v == value) {
+            Some((_, count)) => *count += 1,
+            None => counts.push((value, 1)),
+        }
+    }
+    counts.sort_by(|(a, a_count), (b, b_count)| b_count.cmp(a_count).then_with(|| compare_values(Some(a), Some(b))));
+    counts.into_iter()
+        .map(|(value, count)| {
+            let mut group = doc! {"_id": value.clone(), "count": count};
+            let name = referenced.iter()
+                .find(|d| d.get("_id") == Some(&value))
+                .and_then(|d| d.get_str("name").ok());
+            if let Some(name) = name {
+                group.insert("name", name);
+            }
+            group
+        })
+        .collect()
+}
+
+fn has_id(document: &Document, id: &ObjectId) -> bool {
+    document.get_object_id("_id").ok() == Some(id)
+}
+
+fn id_in(document: &Document, ids: &[ObjectId]) -> bool {
+    document.get_object_id("_id").map(|id| ids.contains(id)).unwrap_or(false)
+}
+
+fn in_category(document: &Document, categories: Option<&[ObjectId]>) -> bool {
+    match (categories, document.get("category")) {
+        (Some(ids), Some(Bson::ObjectId(c))) => ids.contains(c),
+        (None, None) | (None, Some(Bson::Null)) => true,
+        _ => false,
+    }
+}
+
+/// Documents without a `list` field belong to the default list.
+fn in_list(document: &Document, list: &str) -> bool {
+    document.get_str("list").unwrap_or(DEFAULT_LIST) == list
+}
+
+fn in_any_list(document: &Document, lists: Option<&[String]>) -> bool {
+    lists.map(|lists| lists.iter().any(|list| in_list(document, list))).unwrap_or(true)
+}
+
+fn matches_filter(document: &Document, filter: &ProductFilter) -> bool {
+    let in_filtered_list = filter.get_list().map(|list| in_list(document, list)).unwrap_or(true);
+    let price = document.get_i32("price").ok();
+    let above_min = filter.get_min_price().map(|min| price.map(|p| p >= min).unwrap_or(false)).unwrap_or(true);
+    let below_max = filter.get_max_price().map(|max| price.map(|p| p <= max).unwrap_or(false)).unwrap_or(true);
+    let from_source = filter.get_source().map(|id| is_offered_by(document, id)).unwrap_or(true);
+    let archived = document.get_bool("archived").unwrap_or(false);
+    let in_archive = filter.get_archived().map(|a| a == archived).unwrap_or(true);
+    let tagged = filter.get_tag().map(|tag| has_tag(document, tag)).unwrap_or(true);
+    let matches_text = filter.get_text().map(|text| contains_any_term(document, text)).unwrap_or(true);
+    let last_seen = document.get_i32("last_seen").ok();
+    let seen_after = filter.get_last_seen_after().map(|after| last_seen.map(|s| s >= after).unwrap_or(false)).unwrap_or(true);
+    let seen_before = filter.get_last_seen_before().map(|before| last_seen.map(|s| s <= before).unwrap_or(false)).unwrap_or(true);
+    let shown = !filter.is_collapsing_duplicates() || !is_set(document.get("duplicate_of"));
+    let in_stock = document.get_str("availability").ok() != Some(Availability::OutOfStock.get_code());
+    let stocked = filter.get_in_stock().map(|s| s == in_stock).unwrap_or(true);
+    let link_dead = document.get_bool("link_dead").unwrap_or(false);
+    let link_matches = filter.get_link_dead().map(|d| d == link_dead).unwrap_or(true);
+    in_filtered_list && above_min && below_max && from_source && in_archive && tagged && matches_text && seen_after && seen_before && shown && stocked && link_matches && !is_deleted(document)
+}
+
+/// Like the `$text` search, matches if the name or description contains any of the terms.
+fn contains_any_term(document: &Document, text: &str) -> bool {
+    let content = format!(
+        "{} {}",
+        document.get_str("name").unwrap_or_default(),
+        document.get_str("description").unwrap_or_default()
+    ).to_lowercase();
+    text.split_whitespace().any(|term| content.contains(&term.to_lowercase()))
+}
+
+/// Like the source filter of MongoDB, the offers of other sources count as well.
+fn is_offered_by(document: &Document, source: &ObjectId) -> bool {
+    document.get_object_id("source").ok() == Some(source)
+        || document.get_array("offers").map(|offers| {
+            offers.iter().any(|offer| offer.as_document().and_then(|o| o.get_object_id("source").ok()) == Some(source))
+        }).unwrap_or(false)
+}
+
+fn is_deleted(document: &Document) -> bool {
+    is_set(document.get("deleted_at"))
+}
+
+fn has_tag(document: &Document, tag: &str) -> bool {
+    document.get_array("tags").map(|t| t.iter().any(|t| t.as_str() == Some(tag))).unwrap_or(false)
+}
+
+/// Sorts like the MongoDB sort document would, with ties broken newest first.
+fn sort_documents(documents: &mut [&Document], sort: &SortQuery) {
+    let key = sort.get_key();
+    documents.sort_by(|a, b| {
+        let order = compare_values(a.get(key), b.get(key));
+        let order = if sort.is_ascending() { order } else { order.reverse() };
+        order.then_with(|| b.get_object_id("_id").ok().cmp(&a.get_object_id("_id").ok()))
+    });
+}
+
+fn compare_values(a: Option<&Bson>, b: Option<&Bson>) -> Ordering {
+    match (a, b) {
+        (Some(Bson::Int32(a)), Some(Bson::Int32(b))) => a.cmp(b),
+        (Some(Bson::Int64(a)), Some(Bson::Int64(b))) => a.cmp(b),
+        (Some(Bson::String(a)), Some(Bson::String(b))) => a.cmp(b),
+        (Some(Bson::ObjectId(a)), Some(Bson::ObjectId(b))) => a.cmp(b),
+        _ => is_set(a).cmp(&is_set(b)),
+    }
+}
+
+fn is_set(value: Option<&Bson>) -> bool {
+    !matches!(value, None | Some(Bson::Null))
+}
+
+fn load_one<T: Stored>(document: Option<&Document>) -> Result<Option<T>> {
+    document.cloned().map(T::from_document).transpose()
+}
+
+fn load_all<'a, T, I>(documents: I) -> Loaded<Vec<T>>
+where
+    T: Stored,
+    I: IntoIterator<Item = &'a Document>,
+{
+    let mut results = Vec::new();
+    let mut skipped = 0;
+    for document in documents {
+        match T::from_document(document.clone()) {
+            Ok(result) => results.push(result),
+            Err(e) => {
+                warn!("Skipping document, couldn't extract it as {}: {}", std::any::type_name::<T>(), e);
+                skipped += 1;
+            }
+        }
+    }
+    Loaded::new(results, skipped)
+}
+
+fn paginate<T>(items: Vec<T>, offset: u64, size: u64) -> Vec<T> {
+    items.into_iter().skip(offset as usize).take(size as usize).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LIST: &str = "games";
+
+    /// Ids of the products of `LIST` that are not in its latest snapshot, as
+    /// the archive filter `{"_id": {"$not": {"$in": ids}}}` selected them.
+    async fn get_removed_ids(repo: &MemoryRepository) -> Vec<ObjectId> {
+        let latest = repo.get_nth_wishlist_reverse(LIST, 0).await.unwrap();
+        let current_ids = latest.get_product_ids().unwrap_or_default();
+        let mut ids: Vec<ObjectId> = repo.read().product.iter()
+            .filter(|p| in_list(p, LIST) && !is_deleted(p) && !id_in(p, current_ids))
+            .filter_map(|p| p.get_object_id("_id").ok().cloned())
+            .collect();
+        ids.sort();
+        ids
+    }
+
+    async fn get_archived_ids(repo: &MemoryRepository) -> Vec<ObjectId> {
+        let filter = ProductFilter::new(Some(LIST.to_string()), None, None, None).with_archived(true);
+        let products = repo.get_products(&filter, &SortQuery::default(), 0, 100).await.unwrap();
+        let mut ids: Vec<ObjectId> = products.into_value().iter().filter_map(|p| p.get_id().cloned()).collect();
+        ids.sort();
+        ids
+    }
+
+    async fn count_archived(repo: &MemoryRepository) -> u64 {
+        let filter = ProductFilter::new(Some(LIST.to_string()), None, None, None).with_archived(true);
+        repo.count_products(&filter).await.unwrap()
+    }
+
+    /// Takes a snapshot of `LIST` the way `record_wishlist_snapshot` does.
+    async fn rotate(repo: &MemoryRepository, timestamp: i32, product_ids: &[ObjectId]) {
+        repo.insert_wishlist(LIST, timestamp, product_ids).await.unwrap();
+        repo.update_archived(LIST, product_ids).await.unwrap();
+    }
+
+    fn add_product(repo: &MemoryRepository, name: &str, list: &str) -> ObjectId {
+        repo.add_product(doc! {"name": name, "list": list})
+    }
+
+    #[tokio::test]
+    async fn archive_matches_products_left_out_of_latest_snapshot() {
+        let repo = MemoryRepository::new();
+        let first = add_product(&repo, "first", LIST);
+        let second = add_product(&repo, "second", LIST);
+        let third = add_product(&repo, "third", LIST);
+        add_product(&repo, "elsewhere", "books");
+
+        rotate(&repo, 100, &[first.clone(), second.clone()]).await;
+        let expected = get_removed_ids(&repo).await;
+        assert_eq!(expected, vec![third.clone()]);
+        assert_eq!(get_archived_ids(&repo).await, expected);
+        assert_eq!(count_archived(&repo).await, expected.len() as u64);
+
+        rotate(&repo, 200, &[second.clone(), third.clone()]).await;
+        let expected = get_removed_ids(&repo).await;
+        assert_eq!(expected, vec![first]);
+        assert_eq!(get_archived_ids(&repo).await, expected);
+        assert_eq!(count_archived(&repo).await, expected.len() as u64);
+    }
+
+    #[tokio::test]
+    async fn archive_leaves_out_deleted_products() {
+        let repo = MemoryRepository::new();
+        let kept = add_product(&repo, "kept", LIST);
+        let removed = add_product(&repo, "removed", LIST);
+        let deleted = repo.add_product(doc! {"name": "deleted", "list": LIST, "deleted_at": 150});
+
+        rotate(&repo, 100, &[kept.clone(), removed.clone(), deleted]).await;
+        assert!(get_archived_ids(&repo).await.is_empty());
+        assert_eq!(count_archived(&repo).await, 0);
+
+        rotate(&repo, 200, &[kept]).await;
+        let expected = get_removed_ids(&repo).await;
+        assert_eq!(expected, vec![removed]);
+        assert_eq!(get_archived_ids(&repo).await, expected);
+        assert_eq!(count_archived(&repo).await, expected.len() as u64);
+    }
+}
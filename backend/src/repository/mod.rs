@@ -0,0 +1,253 @@
+use async_trait::async_trait;
+use mongodb::bson::{document::Document, oid::ObjectId};
+
+use crate::model::{AuditEvent, Budget, Category, CategoryCount, CategoryRule, ClickCount, Comment, ExchangeRates, List, Loaded, PriceAlert, PledgePool, PricePoint, Product, PushSubscription, Source, Stats, TagCount, User, Wishlist, WishlistSummary};
+use crate::query::SortQuery;
+use crate::Result;
+
+mod cached;
+mod filter;
+mod indexes;
+mod memory;
+mod mongo;
+mod resilient;
+mod tracer;
+
+pub use self::cached::CachedRepository;
+pub use self::filter::ProductFilter;
+pub use self::memory::MemoryRepository;
+pub use self::mongo::MongoRepository;
+pub use self::resilient::ResilientRepository;
+pub use self::tracer::QueryTracer;
+
+/// Storage backend used by the request handlers.
+///
+/// Implementations only deal with persistence. Joining products with their
+/// sources, assembling wishlists and validating input is left to the handlers,
+/// so every implementation behaves the same from an endpoint's point of view.
+///
+/// Documents that can't be deserialized fail single reads, while list reads
+/// skip them and report how many were skipped in the returned `Loaded`.
+///
+/// Snapshots and products without a `list` field belong to the default list,
+/// so data stored before lists existed keeps showing up there.
+///
+/// Products not in the latest snapshot of their list are archived. The
+/// `archived` flag caching that is kept up to date by whoever writes snapshots.
+///
+/// Inserting, updating, trashing and restoring a product and recording its
+/// price set its `updated_at`, so clients can tell when a listing changed.
+#[async_trait]
+pub trait WishlistRepository: Send + Sync {
+    /// Prepares the storage for use, e.g. by creating indexes.
+    async fn ensure_indexes(&self) -> Result<()>;
+    /// Checks that the storage is reachable, without relying on any cache.
+    async fn ping(&self) -> Result<()>;
+
+    /// Returns the stored lists, the implicit default list is not included.
+    async fn get_lists(&self) -> Result<Loaded<Vec<List>>>;
+    async fn get_list_by_slug(&self, slug: &str) -> Result<Option<List>>;
+    async fn insert_list(&self, list: Document) -> Result<ObjectId>;
+    /// Sets the fields of `list`, returns false if there is no such list.
+    async fn update_list(&self, slug: &str, list: Document) -> Result<bool>;
+    /// Adds `collaborator` to the list, replacing the one with the same user.
+    /// Returns false if there is no such list.
+    async fn set_collaborator(&self, slug: &str, collaborator: Document) -> Result<bool>;
+    /// Returns false if `user` doesn't collaborate on the list.
+    async fn remove_collaborator(&self, slug: &str, user: &ObjectId) -> Result<bool>;
+
+    async fn get_user_by_id(&self, id: &ObjectId) -> Result<Option<User>>;
+    async fn get_user_by_name(&self, username: &str) -> Result<Option<User>>;
+    /// Stores a new user, fails with `Error::Conflict` if the username is taken.
+    async fn insert_user(&self, user: Document) -> Result<ObjectId>;
+
+    /// Returns the snapshot of `list` taken `n` positions before the latest one, without products loaded.
+    async fn get_nth_wishlist_reverse(&self, list: &str, n: u64) -> Result<Wishlist>;
+    /// Returns the latest snapshot of `list` taken at or before `timestamp`, without products loaded.
+    async fn get_wishlist_at(&self, list: &str, timestamp: i32) -> Result<Option<Wishlist>>;
+    /// Returns the snapshots of `list` newest first, with their number of products instead of the products.
+    async fn get_wishlist_history(&self, list: &str, offset: u64, size: u64) -> Result<Loaded<Vec<WishlistSummary>>>;
+    /// Stores a new snapshot of `list` containing `product_ids`.
+    async fn insert_wishlist(&self, list: &str, timestamp: i32, product_ids: &[ObjectId]) -> Result<ObjectId>;
+    /// Deletes the snapshots of `list` taken at any of `timestamps`, returns the number of deleted snapshots.
+    async fn delete_wishlists_at(&self, list: &str, timestamps: &[i32]) -> Result<u64>;
+    /// Removes `product_ids` from the snapshot `id`, returns false if it doesn't exist.
+    async fn remove_wishlist_products(&self, id: &ObjectId, product_ids: &[ObjectId]) -> Result<bool>;
+    /// Returns the latest time a snapshot of `list` was taken or one of its
+    /// products changed, trash included, of all lists if `list` is `None`.
+    async fn get_last_modified(&self, list: Option<&str>) -> Result<Option<i32>>;
+
+    /// Products in the trash are left out of the reads and updates below,
+    /// unless stated otherwise.
+    async fn get_product_by_id(&self, id: &ObjectId) -> Result<Option<Product>>;
+    async fn get_products_by_id(&self, ids: &[ObjectId]) -> Result<Loaded<Vec<Product>>>;
+    /// Returns the products matching `filter`, ordered by `sort`.
+    async fn get_products(&self, filter: &ProductFilter, sort: &SortQuery, offset: u64, size: u64) -> Result<Loaded<Vec<Product>>>;
+    async fn count_products(&self, filter: &ProductFilter) -> Result<u64>;
+    /// Flags the products of `list` as archived unless they are in
+    /// `current_ids`, which are flagged as current. Returns the number of
+    /// products whose flag changed.
+    async fn update_archived(&self, list: &str, current_ids: &[ObjectId]) -> Result<u64>;
+    /// Counts the products of `list` in total and per category and source,
+    /// and sums up the prices of the current ones.
+    async fn get_stats(&self, list: &str) -> Result<Stats>;
+    /// Counts the products of `list` per tag, most used tag first.
+    async fn get_tags(&self, list: &str) -> Result<Loaded<Vec<TagCount>>>;
+    /// Returns products matching the full-text `query`, best match first.
+    /// Only the products of the lists with a slug in `lists` are searched, those of all lists for `None`.
+    async fn search_products(&self, query: &str, lists: Option<&[String]>, offset: u64, size: u64) -> Result<Loaded<Vec<Product>>>;
+    /// Returns up to `size` distinct names of products of `list` starting with
+    /// `prefix`, compared case-insensitively, in alphabetical order.
+    async fn suggest_product_names(&self, list: &str, prefix: &str, size: u64) -> Result<Vec<String>>;
+    /// Returns the products of any of `categories`, or the uncategorized ones for `None`, ordered by `sort`.
+    /// Only the products of the lists with a slug in `lists` are included, those of all lists for `None`.
+    async fn get_products_by_category(&self, categories: Option<&[ObjectId]>, lists: Option<&[String]>, sort: &SortQuery, offset: u64, size: u64) -> Result<Loaded<Vec<Product>>>;
+    async fn count_products_by_category(&self, categories: Option<&[ObjectId]>, lists: Option<&[String]>) -> Result<u64>;
+    /// Counts the current and archived products of every category in one go,
+    /// of the lists with a slug in `lists` or of all lists for `None`.
+    async fn count_products_per_category(&self, lists: Option<&[String]>) -> Result<Loaded<Vec<CategoryCount>>>;
+    async fn insert_product(&self, product: Document) -> Result<ObjectId>;
+    /// Inserts `product` unless its list has a product with the same source and
+    /// item id, in one step so that concurrent imports can't both insert it.
+    /// The existing product gets the `price` and `last_seen` of `product`, it
+    /// conflicts if it is in the trash. Returns the id of the inserted or
+    /// existing product and whether it was inserted.
+    async fn upsert_product(&self, product: Document) -> Result<(ObjectId, bool)>;
+    /// Returns a product of `list` with the same `item_id`, if given, or the
+    /// same `url`, including products in the trash.
+    async fn find_duplicate_product(&self, list: &str, item_id: Option<&str>, url: &str) -> Result<Option<Product>>;
+    /// Sets the fields of `product` on an existing product, returns false if it doesn't exist.
+    async fn update_product(&self, id: &ObjectId, product: Document) -> Result<bool>;
+    /// Sets the fields of `product` on the existing products of `ids` in one
+    /// update, returns the number of products found.
+    async fn update_products(&self, ids: &[ObjectId], product: Document) -> Result<u64>;
+    /// Moves a product to the trash at `timestamp`, keeping its price history
+    /// and reservation.
+    async fn delete_product(&self, id: &ObjectId, timestamp: i32) -> Result<Option<Product>>;
+    /// Returns the products in the trash, most recently deleted first.
+    async fn get_deleted_products(&self, offset: u64, size: u64) -> Result<Loaded<Vec<Product>>>;
+    /// Takes a product out of the trash, returns `None` if it isn't in there.
+    async fn restore_product(&self, id: &ObjectId) -> Result<Option<Product>>;
+    /// Removes a product in the trash together with its price history and
+    /// reservation, returns `None` if it isn't in there.
+    async fn purge_product(&self, id: &ObjectId) -> Result<Option<Product>>;
+    /// Adds the view counts to the products, ignoring products that no longer exist.
+    async fn add_product_views(&self, views: &[(ObjectId, i64)]) -> Result<()>;
+    /// Counts a click on the shop link of a product, returns `None` if it doesn't exist.
+    async fn record_click(&self, id: &ObjectId) -> Result<Option<Product>>;
+    /// Returns the click counts of the products of `list` clicked at least once, most clicked first.
+    async fn get_click_counts(&self, list: &str) -> Result<Loaded<Vec<ClickCount>>>;
+
+    /// Returns which of `product_ids` are reserved.
+    async fn get_reserved_product_ids(&self, product_ids: &[ObjectId]) -> Result<Vec<ObjectId>>;
+    /// Reserves a product, returns false if it already is reserved.
+    async fn insert_reservation(&self, product_id: &ObjectId, token: &str, timestamp: i32) -> Result<bool>;
+    /// Removes the reservation of a product if `token` matches, returns false otherwise.
+    async fn delete_reservation(&self, product_id: &ObjectId, token: &str) -> Result<bool>;
+
+    async fn get_pledge_pool(&self, product_id: &ObjectId) -> Result<Option<PledgePool>>;
+    async fn get_pledge_pools(&self, product_ids: &[ObjectId]) -> Result<Loaded<Vec<PledgePool>>>;
+    /// Starts a pledge pool, returns false if the product already has one.
+    async fn insert_pledge_pool(&self, pool: Document) -> Result<bool>;
+    /// Adds a pledge to the pool of a product, returns false if there is no
+    /// pool or the pledge would take it past its target.
+    async fn add_pledge(&self, product_id: &ObjectId, pledge: Document) -> Result<bool>;
+    /// Withdraws the pledge with `token`, returns false if there is none.
+    async fn remove_pledge(&self, product_id: &ObjectId, token: &str) -> Result<bool>;
+    /// Removes the pool of a product if nothing is pledged to it, returns false otherwise.
+    async fn delete_empty_pledge_pool(&self, product_id: &ObjectId) -> Result<bool>;
+
+    /// Returns the comments on a product, oldest first.
+    async fn get_comments(&self, product_id: &ObjectId, offset: u64, size: u64) -> Result<Loaded<Vec<Comment>>>;
+    async fn get_comment(&self, id: &ObjectId) -> Result<Option<Comment>>;
+    async fn insert_comment(&self, comment: Document) -> Result<ObjectId>;
+    async fn delete_comment(&self, id: &ObjectId) -> Result<bool>;
+
+    async fn get_push_subscriptions(&self) -> Result<Loaded<Vec<PushSubscription>>>;
+    async fn get_push_subscription(&self, endpoint: &str) -> Result<Option<PushSubscription>>;
+    /// Stores a push subscription, replacing the one with the same endpoint.
+    async fn upsert_push_subscription(&self, subscription: Document) -> Result<()>;
+    /// Removes the subscription with `endpoint`, returns false if there is none.
+    async fn delete_push_subscription(&self, endpoint: &str) -> Result<bool>;
+
+    async fn get_price_alerts(&self) -> Result<Loaded<Vec<PriceAlert>>>;
+    async fn get_price_alerts_of_product(&self, product_id: &ObjectId) -> Result<Loaded<Vec<PriceAlert>>>;
+    async fn get_price_alert(&self, id: &ObjectId) -> Result<Option<PriceAlert>>;
+    async fn insert_price_alert(&self, alert: Document) -> Result<ObjectId>;
+    /// Sets when the target of an alert was last reached, returns false if it doesn't exist.
+    async fn set_price_alert_triggered(&self, id: &ObjectId, timestamp: i32) -> Result<bool>;
+    /// Removes an alert, returns false if it doesn't exist.
+    async fn delete_price_alert(&self, id: &ObjectId) -> Result<bool>;
+
+    async fn get_budget(&self, list: &str) -> Result<Option<Budget>>;
+    /// Stores the budget of a list, replacing the one it had.
+    async fn upsert_budget(&self, budget: Document) -> Result<()>;
+    /// Removes the budget of a list, returns false if it had none.
+    async fn delete_budget(&self, list: &str) -> Result<bool>;
+
+    async fn get_sources(&self) -> Result<Loaded<Vec<Source>>>;
+    async fn get_sources_by_id(&self, ids: &[ObjectId]) -> Result<Loaded<Vec<Source>>>;
+    async fn get_source_by_name(&self, name: &str) -> Result<Option<Source>>;
+    async fn source_exists(&self, id: &ObjectId) -> Result<bool>;
+    async fn insert_source(&self, source: Document) -> Result<ObjectId>;
+    async fn update_source(&self, id: &ObjectId, source: Document) -> Result<bool>;
+
+    /// Returns the categories ordered by their sort order, then by name.
+    async fn get_categories(&self) -> Result<Loaded<Vec<Category>>>;
+    async fn get_category_by_id(&self, id: &ObjectId) -> Result<Option<Category>>;
+    async fn get_category_by_name(&self, name: &str) -> Result<Option<Category>>;
+    /// Checks if a category with `name` exists, ignoring the category `except`.
+    async fn category_name_exists(&self, name: &str, except: Option<&ObjectId>) -> Result<bool>;
+    async fn insert_category(&self, category: Document) -> Result<ObjectId>;
+    async fn update_category(&self, id: &ObjectId, category: Document) -> Result<bool>;
+    async fn delete_category(&self, id: &ObjectId) -> Result<bool>;
+    /// Moves all products of category `from` to `to`, returns the number of moved products.
+    async fn reassign_category(&self, from: &ObjectId, to: &ObjectId) -> Result<u64>;
+    /// Moves the products, subcategories and rules of category `from` to `into`,
+    /// returns the number of moved products. `from` is left to be deleted.
+    async fn merge_category(&self, from: &ObjectId, into: &ObjectId) -> Result<u64>;
+    /// Counts the products, including those in the trash, subcategories and
+    /// rules referencing category `id`.
+    async fn count_category_references(&self, id: &ObjectId) -> Result<u64>;
+
+    /// Returns the category rules oldest first, the order they are tried in.
+    async fn get_rules(&self) -> Result<Loaded<Vec<CategoryRule>>>;
+    async fn get_rule_by_id(&self, id: &ObjectId) -> Result<Option<CategoryRule>>;
+    async fn insert_rule(&self, rule: Document) -> Result<ObjectId>;
+    async fn update_rule(&self, id: &ObjectId, rule: Document) -> Result<bool>;
+    async fn delete_rule(&self, id: &ObjectId) -> Result<bool>;
+
+    /// Returns the price history of a product, oldest first.
+    async fn get_price_history(&self, product_id: &ObjectId) -> Result<Loaded<Vec<PricePoint>>>;
+    /// Returns the price histories of several products, oldest first.
+    async fn get_price_histories(&self, product_ids: &[ObjectId]) -> Result<Loaded<Vec<PricePoint>>>;
+    async fn get_last_price_point(&self, product_id: &ObjectId) -> Result<Option<PricePoint>>;
+    /// Appends a price point and updates the current and lowest price of the
+    /// product, flagging it as at its all-time low if `price` is below the lowest.
+    async fn insert_price_point(&self, product_id: &ObjectId, price: i32, timestamp: i32) -> Result<()>;
+
+    /// Returns the exchange rates stored last, `None` if none were fetched yet.
+    async fn get_exchange_rates(&self) -> Result<Option<ExchangeRates>>;
+    /// Replaces the stored exchange rates.
+    async fn set_exchange_rates(&self, rates: Document) -> Result<()>;
+
+    /// Appends an entry to the audit log, entries are never changed.
+    async fn insert_event(&self, event: Document) -> Result<()>;
+    /// Returns the audit log newest first, only the entries about `entity`
+    /// and the document `entity_id` of it if given.
+    async fn get_events(&self, entity: Option<&str>, entity_id: Option<&ObjectId>, offset: u64, size: u64) -> Result<Loaded<Vec<AuditEvent>>>;
+    /// Returns the audit log of `list` newest first: the changes of the list,
+    /// its budget and its products, trash included.
+    async fn get_list_events(&self, list: &str, offset: u64, size: u64) -> Result<Loaded<Vec<AuditEvent>>>;
+
+    /// Returns all documents of `collection` as stored, ordered by id.
+    async fn dump_collection(&self, collection: &str) -> Result<Vec<Document>>;
+    /// Deletes all documents of `collection` and stores `documents` in their
+    /// place, keeping their ids.
+    async fn replace_collection(&self, collection: &str, documents: Vec<Document>) -> Result<()>;
+}
+
+/// Unix time in seconds, as stored in `updated_at`.
+pub(crate) fn current_timestamp() -> i32 {
+    chrono::Utc::now().timestamp() as i32
+}
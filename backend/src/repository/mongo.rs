@@ -0,0 +1,1155 @@
+use async_trait::async_trait;
+use mongodb::{bson::{doc, oid::ObjectId, document::Document, Bson}, options::{Collation, FindOneAndUpdateOptions, FindOptions, FindOneOptions, ReplaceOptions, ReturnDocument, UpdateOptions}, Client, Cursor, Collection, Database};
+use mongodb::error::{Error as MongoError, ErrorKind, WriteFailure};
+use tokio::stream::StreamExt;
+
+use super::{current_timestamp, indexes, ProductFilter, WishlistRepository};
+use crate::{Result, Error};
+use crate::model::{AuditEvent, Availability, Budget, Category, CategoryCount, CategoryRule, ClickCount, Comment, ExchangeRates, List, Loaded, PriceAlert, PledgePool, PricePoint, PushSubscription, Reservation, Source, Stats, Stored, TagCount, User, Wishlist, WishlistSummary, Product, DEFAULT_LIST};
+use crate::query::SortQuery;
+
+const DUPLICATE_KEY_ERROR: i32 = 11000;
+
+pub struct MongoRepository {
+    database: Database,
+}
+
+impl MongoRepository {
+    pub fn new(client: Client, database_name: &str) -> Self {
+        Self {
+            database: client.database(database_name),
+        }
+    }
+
+    /// Collection of `T`, read as the model.
+    fn collection<T: Stored>(&self) -> Collection<T> {
+        self.database.collection_with_type(T::COLLECTION)
+    }
+
+    /// Collection of `T` for writing the documents built from the inputs.
+    fn documents<T: Stored>(&self) -> Collection {
+        self.database.collection(T::COLLECTION)
+    }
+
+    async fn find_one<T: Stored>(&self, filter: Document, options: Option<FindOneOptions>) -> Result<Option<T>> {
+        Ok(self.collection::<T>().find_one(Some(filter), options).await?.map(T::complete))
+    }
+
+    async fn find<T: Stored>(&self, filter: Option<Document>, options: Option<FindOptions>) -> Result<Loaded<Vec<T>>> {
+        let cursor = self.collection::<T>().find(filter, options).await?;
+        Ok(extract_cursor_results(cursor).await)
+    }
+
+    async fn load_products(&self, mut filter: Document, product_filter: &ProductFilter, mut options: Option<FindOptions>) -> Result<Loaded<Vec<Product>>> {
+        apply_product_filter(&mut filter, product_filter);
+        if let Some(fields) = product_filter.get_fields() {
+            let mut find_options = options.unwrap_or_default();
+            find_options.projection = Some(fields.to_projection());
+            options = Some(find_options);
+        }
+        self.find(Some(filter), options).await
+    }
+
+    async fn insert<T: Stored>(&self, document: Document) -> Result<ObjectId> {
+        self.documents::<T>().insert_one(document, None).await?
+            .inserted_id
+            .as_object_id()
+            .cloned()
+            .ok_or(Error::FieldNotLoaded("inserted document", "_id"))
+    }
+}
+
+#[async_trait]
+impl WishlistRepository for MongoRepository {
+    async fn ensure_indexes(&self) -> Result<()> {
+        indexes::ensure_indexes(&self.database).await
+    }
+
+    async fn ping(&self) -> Result<()> {
+        self.database.run_command(doc! {"ping": 1}, None).await?;
+        self.collection::<Wishlist>().find_one(None, None).await?;
+        Ok(())
+    }
+
+    async fn get_lists(&self) -> Result<Loaded<Vec<List>>> {
+        let options = FindOptions::builder()
+            .sort(doc! {"slug": 1})
+            .build();
+        self.find(None, Some(options)).await
+    }
+
+    async fn get_list_by_slug(&self, slug: &str) -> Result<Option<List>> {
+        self.find_one(doc! {"slug": slug}, None).await
+    }
+
+    async fn insert_list(&self, list: Document) -> Result<ObjectId> {
+        let slug = list.get_str("slug")?.to_string();
+        match self.insert::<List>(list).await {
+            Err(Error::MongoDB { source }) if is_duplicate_key(&source) => {
+                Err(Error::Conflict(format!("list '{}' already exists", slug)))
+            }
+            result => result,
+        }
+    }
+
+    async fn update_list(&self, slug: &str, list: Document) -> Result<bool> {
+        let result = self.documents::<List>()
+            .update_one(doc! {"slug": slug}, doc! {"$set": list}, None)
+            .await?;
+        Ok(result.matched_count > 0)
+    }
+
+    async fn set_collaborator(&self, slug: &str, collaborator: Document) -> Result<bool> {
+        // A pipeline, so that the previous role of the user is replaced in the same update.
+        let user = collaborator.get_object_id("user")?.clone();
+        let others = doc! {"$filter": {"input": {"$ifNull": ["$collaborators", []]}, "cond": {"$ne": ["$$this.user", user]}}};
+        let update = vec![doc! {"$set": {"collaborators": {"$concatArrays": [others, [collaborator]]}}}];
+        let result = self.documents::<List>()
+            .update_one(doc! {"slug": slug}, update, None)
+            .await?;
+        Ok(result.matched_count > 0)
+    }
+
+    async fn remove_collaborator(&self, slug: &str, user: &ObjectId) -> Result<bool> {
+        let result = self.documents::<List>()
+            .update_one(doc! {"slug": slug, "collaborators.user": user}, doc! {"$pull": {"collaborators": {"user": user}}}, None)
+            .await?;
+        Ok(result.modified_count > 0)
+    }
+
+    async fn get_user_by_id(&self, id: &ObjectId) -> Result<Option<User>> {
+        self.find_one(doc! {"_id": id}, None).await
+    }
+
+    async fn get_user_by_name(&self, username: &str) -> Result<Option<User>> {
+        self.find_one(doc! {"username": username}, None).await
+    }
+
+    async fn insert_user(&self, user: Document) -> Result<ObjectId> {
+        let username = user.get_str("username")?.to_string();
+        match self.insert::<User>(user).await {
+            Err(Error::MongoDB { source }) if is_duplicate_key(&source) => {
+                Err(Error::Conflict(format!("user '{}' already exists", username)))
+            }
+            result => result,
+        }
+    }
+
+    async fn get_nth_wishlist_reverse(&self, list: &str, n: u64) -> Result<Wishlist> {
+        let options = FindOneOptions::builder()
+            .sort(doc! {"timestamp": -1})
+            .skip(Some(n as i64))
+            .build();
+        self.find_one(doc! {"list": list_condition(list)}, Some(options)).await?
+            .ok_or(Error::EmptyResult)
+    }
+
+    async fn get_wishlist_at(&self, list: &str, timestamp: i32) -> Result<Option<Wishlist>> {
+        let options = FindOneOptions::builder()
+            .sort(doc! {"timestamp": -1})
+            .build();
+        self.find_one(doc! {"list": list_condition(list), "timestamp": {"$lte": timestamp}}, Some(options)).await
+    }
+
+    async fn get_wishlist_history(&self, list: &str, offset: u64, size: u64) -> Result<Loaded<Vec<WishlistSummary>>> {
+        let pipeline = vec![
+            doc! {"$match": {"list": list_condition(list)}},
+            doc! {"$sort": {"timestamp": -1}},
+            doc! {"$skip": offset as i64},
+            doc! {"$limit": size as i64},
+            doc! {"$project": {"timestamp": 1, "product_count": {"$size": {"$ifNull": ["$products", []]}}}},
+        ];
+        let cursor = self.documents::<Wishlist>().aggregate(pipeline, None).await?;
+        Ok(extract_aggregate_results(cursor).await)
+    }
+
+    async fn insert_wishlist(&self, list: &str, timestamp: i32, product_ids: &[ObjectId]) -> Result<ObjectId> {
+        self.insert::<Wishlist>(doc! {"list": list, "timestamp": timestamp, "products": product_ids}).await
+    }
+
+    async fn delete_wishlists_at(&self, list: &str, timestamps: &[i32]) -> Result<u64> {
+        let result = self.documents::<Wishlist>()
+            .delete_many(doc! {"list": list_condition(list), "timestamp": {"$in": timestamps}}, None)
+            .await?;
+        Ok(result.deleted_count as u64)
+    }
+
+    async fn remove_wishlist_products(&self, id: &ObjectId, product_ids: &[ObjectId]) -> Result<bool> {
+        let result = self.documents::<Wishlist>()
+            .update_one(doc! {"_id": id}, doc! {"$pull": {"products": {"$in": product_ids}}}, None)
+            .await?;
+        Ok(result.matched_count > 0)
+    }
+
+    async fn get_last_modified(&self, list: Option<&str>) -> Result<Option<i32>> {
+        let filter = list.map(|list| doc! {"list": list_condition(list)}).unwrap_or_default();
+        let snapshot = find_latest(&self.documents::<Wishlist>(), filter.clone(), "timestamp").await?;
+        let product = find_latest(&self.documents::<Product>(), filter, "updated_at").await?;
+        Ok(snapshot.max(product))
+    }
+
+    async fn get_product_by_id(&self, id: &ObjectId) -> Result<Option<Product>> {
+        self.find_one(doc! {"_id": id, "deleted_at": Bson::Null}, None).await
+    }
+
+    async fn get_products_by_id(&self, ids: &[ObjectId]) -> Result<Loaded<Vec<Product>>> {
+        let filter = doc! {
+            "_id": { "$in": ids }
+        };
+        let options = FindOptions::builder()
+            .sort(doc! {"timestamp": -1})
+            .build();
+        self.load_products(filter, &ProductFilter::default(), Some(options)).await
+    }
+
+    async fn get_products(&self, product_filter: &ProductFilter, sort: &SortQuery, offset: u64, size: u64) -> Result<Loaded<Vec<Product>>> {
+        let options = FindOptions::builder()
+            .sort(sort_document(sort))
+            .skip(offset as i64)
+            .limit(size as i64)
+            .build();
+        self.load_products(Document::new(), product_filter, Some(options)).await
+    }
+
+    async fn count_products(&self, product_filter: &ProductFilter) -> Result<u64> {
+        let mut filter = Document::new();
+        apply_product_filter(&mut filter, product_filter);
+        count_documents(&self.documents::<Product>(), Some(filter)).await
+    }
+
+    async fn count_products_per_category(&self, lists: Option<&[String]>) -> Result<Loaded<Vec<CategoryCount>>> {
+        let mut filter = doc! {"deleted_at": Bson::Null};
+        if let Some(lists) = lists {
+            filter.insert("list", lists_condition(lists));
+        }
+        let pipeline = vec![
+            doc! {"$match": filter},
+            doc! {"$group": {
+                "_id": "$category",
+                "current_count": {"$sum": {"$cond": [{"$eq": ["$archived", true]}, 0, 1]}},
+                "archived_count": {"$sum": {"$cond": [{"$eq": ["$archived", true]}, 1, 0]}},
+            }},
+        ];
+        let cursor = self.documents::<Product>().aggregate(pipeline, None).await?;
+        Ok(extract_aggregate_results(cursor).await)
+    }
+
+    async fn update_archived(&self, list: &str, current_ids: &[ObjectId]) -> Result<u64> {
+        let products = self.documents::<Product>();
+        let mut modified = 0;
+        for (filter, update) in archived_updates(list, current_ids).iter() {
+            modified += products.update_many(filter.clone(), update.clone(), None).await?.modified_count;
+        }
+        Ok(modified as u64)
+    }
+
+    async fn get_stats(&self, list: &str) -> Result<Stats> {
+        let pipeline = vec![
+            doc! {"$match": {"list": list_condition(list), "deleted_at": Bson::Null}},
+            doc! {"$facet": {
+                "totals": [{"$group": {
+                    "_id": Bson::Null,
+                    "count": {"$sum": 1},
+                    "archived": {"$sum": {"$cond": [{"$eq": ["$archived", true]}, 1, 0]}},
+                }}],
+                "categories": count_by("$category", Category::COLLECTION),
+                "sources": count_by("$source", Source::COLLECTION),
+                "current": [
+                    {"$match": {"archived": {"$ne": true}}},
+                    {"$group": {"_id": "$currency", "count": {"$sum": 1}, "total": {"$sum": "$price"}}},
+                ],
+                "ratings": [
+                    {"$match": {"rating": {"$type": "number"}}},
+                    {"$group": {"_id": Bson::Null, "count": {"$sum": 1}, "average": {"$avg": "$rating"}}},
+                ],
+            }},
+            doc! {"$lookup": {
+                "from": Wishlist::COLLECTION,
+                "pipeline": [
+                    {"$match": {"list": list_condition(list)}},
+                    {"$sort": {"timestamp": -1}},
+                    {"$limit": 1},
+                    {"$project": {"timestamp": 1}},
+                ],
+                "as": "last_snapshot",
+            }},
+            doc! {"$project": {
+                "_id": 0,
+                "product_count": {"$ifNull": [{"$arrayElemAt": ["$totals.count", 0]}, 0]},
+                "archived_count": {"$ifNull": [{"$arrayElemAt": ["$totals.archived", 0]}, 0]},
+                "categories": 1,
+                "sources": 1,
+                "current": 1,
+                "rated_count": {"$ifNull": [{"$arrayElemAt": ["$ratings.count", 0]}, 0]},
+                "average_rating": {"$arrayElemAt": ["$ratings.average", 0]},
+                "last_snapshot": {"$arrayElemAt": ["$last_snapshot.timestamp", 0]},
+            }},
+        ];
+        let mut cursor = self.documents::<Product>().aggregate(pipeline, None).await?;
+        let stats = cursor.next().await.ok_or(Error::EmptyResult)??;
+        Stats::from_document(stats)
+    }
+
+    async fn get_tags(&self, list: &str) -> Result<Loaded<Vec<TagCount>>> {
+        let pipeline = vec![
+            doc! {"$match": {"list": list_condition(list), "deleted_at": Bson::Null}},
+            doc! {"$unwind": "$tags"},
+            doc! {"$group": {"_id": "$tags", "count": {"$sum": 1}}},
+            doc! {"$sort": {"count": -1, "_id": 1}},
+        ];
+        let cursor = self.documents::<Product>().aggregate(pipeline, None).await?;
+        Ok(extract_aggregate_results(cursor).await)
+    }
+
+    async fn search_products(&self, query: &str, lists: Option<&[String]>, offset: u64, size: u64) -> Result<Loaded<Vec<Product>>> {
+        let mut filter = doc! {
+            "$text": { "$search": query }
+        };
+        if let Some(lists) = lists {
+            filter.insert("list", lists_condition(lists));
+        }
+        let options = FindOptions::builder()
+            .sort(doc! { "score": { "$meta": "textScore" } })
+            .projection(doc! {"score": { "$meta": "textScore" }})
+            .skip(offset as i64)
+            .limit(size as i64)
+            .build();
+        self.load_products(filter, &ProductFilter::default(), Some(options)).await
+    }
+
+    async fn suggest_product_names(&self, list: &str, prefix: &str, size: u64) -> Result<Vec<String>> {
+        // U+FFFF sorts after every other character, bounding the names with the prefix.
+        let filter = doc! {
+            "list": list_condition(list),
+            "name": { "$gte": prefix, "$lt": format!("{}\u{FFFF}", prefix) },
+            "deleted_at": Bson::Null,
+        };
+        // Names differing in case only come out next to each other and are merged.
+        let options = FindOptions::builder()
+            .collation(name_collation())
+            .sort(doc! {"name": 1})
+            .projection(doc! {"_id": 0, "name": 1})
+            .limit((size * 3) as i64)
+            .build();
+        let mut cursor = self.documents::<Product>().find(filter, options).await?;
+        let mut names: Vec<String> = Vec::new();
+        while let Some(document) = cursor.next().await {
+            if let Ok(name) = document?.get_str("name") {
+                if names.last().map(|last| last.to_lowercase() != name.to_lowercase()).unwrap_or(true) {
+                    names.push(name.to_string());
+                }
+            }
+            if names.len() as u64 >= size {
+                break;
+            }
+        }
+        Ok(names)
+    }
+
+    async fn get_products_by_category(&self, categories: Option<&[ObjectId]>, lists: Option<&[String]>, sort: &SortQuery, offset: u64, size: u64) -> Result<Loaded<Vec<Product>>> {
+        let options = FindOptions::builder()
+            .sort(sort_document(sort))
+            .skip(offset as i64)
+            .limit(size as i64)
+            .build();
+        self.load_products(category_filter(categories, lists), &ProductFilter::default(), Some(options)).await
+    }
+
+    async fn count_products_by_category(&self, categories: Option<&[ObjectId]>, lists: Option<&[String]>) -> Result<u64> {
+        let mut filter = category_filter(categories, lists);
+        apply_product_filter(&mut filter, &ProductFilter::default());
+        count_documents(&self.documents::<Product>(), Some(filter)).await
+    }
+
+    async fn insert_product(&self, mut product: Document) -> Result<ObjectId> {
+        let item_id = product.get_str("item_id").ok().map(String::from);
+        product.insert("updated_at", current_timestamp());
+        match self.insert::<Product>(product).await {
+            Err(Error::MongoDB { source }) if is_duplicate_key(&source) => Err(Error::Conflict(format!("item '{}' is already on the list", item_id.unwrap_or_default()))),
+            result => result,
+        }
+    }
+
+    async fn upsert_product(&self, mut product: Document) -> Result<(ObjectId, bool)> {
+        let item_id = match product.get_str("item_id") {
+            Ok(item_id) => item_id.to_string(),
+            Err(_) => return Ok((self.insert_product(product).await?, true)),
+        };
+        let list = product.get_str("list").unwrap_or(DEFAULT_LIST).to_string();
+        let source = product.get("source").cloned().unwrap_or(Bson::Null);
+        let filter = doc! {"list": list_condition(&list), "source": source, "item_id": &item_id};
+        // The values the filter matches on are inserted along, setting them too conflicts.
+        product.remove("source");
+        product.remove("item_id");
+        if list != DEFAULT_LIST {
+            product.remove("list");
+        }
+        let mut seen = doc! {"updated_at": current_timestamp()};
+        for field in ["price", "last_seen"].iter() {
+            if let Some(value) = product.remove(field) {
+                seen.insert(*field, value);
+            }
+        }
+        // Leaves out the products in the trash, inserting instead fails on the unique index.
+        let mut current = filter.clone();
+        current.insert("deleted_at", Bson::Null);
+        let options = UpdateOptions::builder().upsert(true).build();
+        let result = match self.documents::<Product>().update_one(current.clone(), doc! {"$set": seen, "$setOnInsert": product}, options).await {
+            Ok(result) => result,
+            Err(e) if is_duplicate_key(&e) => {
+                let mut trashed = filter;
+                trashed.insert("deleted_at", doc! {"$ne": Bson::Null});
+                return Err(match count_documents(&self.documents::<Product>(), Some(trashed)).await? {
+                    0 => Error::Conflict(format!("item '{}' is already on the list", item_id)),
+                    _ => Error::Conflict("the product is in the trash, restore or purge it first".to_string()),
+                });
+            }
+            Err(e) => return Err(e.into()),
+        };
+        if let Some(Bson::ObjectId(id)) = result.upserted_id {
+            return Ok((id, true));
+        }
+        let existing = self.documents::<Product>().find_one(current, FindOneOptions::builder().projection(doc! {"_id": 1}).build()).await?
+            .ok_or(Error::EmptyResult)?;
+        Ok((existing.get_object_id("_id")?.clone(), false))
+    }
+
+    async fn find_duplicate_product(&self, list: &str, item_id: Option<&str>, url: &str) -> Result<Option<Product>> {
+        let mut duplicate = vec![Bson::Document(doc! {"url": url})];
+        if let Some(item_id) = item_id {
+            duplicate.push(Bson::Document(doc! {"item_id": item_id}));
+        }
+        self.find_one(doc! {"list": list_condition(list), "$or": duplicate}, None).await
+    }
+
+    async fn update_product(&self, id: &ObjectId, mut product: Document) -> Result<bool> {
+        let item_id = product.get_str("item_id").ok().map(String::from);
+        product.insert("updated_at", current_timestamp());
+        match self.documents::<Product>().update_one(doc! {"_id": id, "deleted_at": Bson::Null}, doc! {"$set": product}, None).await {
+            Ok(result) => Ok(result.matched_count > 0),
+            Err(e) if is_duplicate_key(&e) => Err(Error::Conflict(format!("item '{}' is already on the list", item_id.unwrap_or_default()))),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn update_products(&self, ids: &[ObjectId], mut product: Document) -> Result<u64> {
+        product.insert("updated_at", current_timestamp());
+        let result = self.documents::<Product>()
+            .update_many(doc! {"_id": {"$in": ids}, "deleted_at": Bson::Null}, doc! {"$set": product}, None)
+            .await?;
+        Ok(result.matched_count as u64)
+    }
+
+    async fn delete_product(&self, id: &ObjectId, timestamp: i32) -> Result<Option<Product>> {
+        let options = FindOneAndUpdateOptions::builder()
+            .return_document(ReturnDocument::After)
+            .build();
+        Ok(self.collection::<Product>()
+            .find_one_and_update(doc! {"_id": id, "deleted_at": Bson::Null}, doc! {"$set": {"deleted_at": timestamp, "updated_at": timestamp}}, options)
+            .await?
+            .map(Product::complete))
+    }
+
+    async fn get_deleted_products(&self, offset: u64, size: u64) -> Result<Loaded<Vec<Product>>> {
+        let options = FindOptions::builder()
+            .sort(doc! {"deleted_at": -1, "_id": -1})
+            .skip(offset as i64)
+            .limit(size as i64)
+            .build();
+        self.find(Some(doc! {"deleted_at": {"$ne": Bson::Null}}), Some(options)).await
+    }
+
+    async fn restore_product(&self, id: &ObjectId) -> Result<Option<Product>> {
+        let options = FindOneAndUpdateOptions::builder()
+            .return_document(ReturnDocument::After)
+            .build();
+        Ok(self.collection::<Product>()
+            .find_one_and_update(doc! {"_id": id, "deleted_at": {"$ne": Bson::Null}}, doc! {"$unset": {"deleted_at": ""}, "$set": {"updated_at": current_timestamp()}}, options)
+            .await?
+            .map(Product::complete))
+    }
+
+    async fn purge_product(&self, id: &ObjectId) -> Result<Option<Product>> {
+        let deleted = self.collection::<Product>()
+            .find_one_and_delete(doc! {"_id": id, "deleted_at": {"$ne": Bson::Null}}, None)
+            .await?
+            .map(Product::complete);
+        if deleted.is_some() {
+            self.documents::<PricePoint>()
+                .delete_many(doc! {"product": id}, None)
+                .await?;
+            self.documents::<Reservation>()
+                .delete_many(doc! {"product": id}, None)
+                .await?;
+            self.documents::<PriceAlert>()
+                .delete_many(doc! {"product": id}, None)
+                .await?;
+            self.documents::<PledgePool>()
+                .delete_many(doc! {"product": id}, None)
+                .await?;
+            self.documents::<Comment>()
+                .delete_many(doc! {"product": id}, None)
+                .await?;
+        }
+        Ok(deleted)
+    }
+
+    async fn add_product_views(&self, views: &[(ObjectId, i64)]) -> Result<()> {
+        for (id, count) in views {
+            self.documents::<Product>()
+                .update_one(doc! {"_id": id, "deleted_at": Bson::Null}, doc! {"$inc": {"views": count}}, None)
+                .await?;
+        }
+        Ok(())
+    }
+
+    async fn record_click(&self, id: &ObjectId) -> Result<Option<Product>> {
+        let options = FindOneAndUpdateOptions::builder()
+            .return_document(ReturnDocument::After)
+            .build();
+        Ok(self.collection::<Product>()
+            .find_one_and_update(doc! {"_id": id, "deleted_at": Bson::Null}, doc! {"$inc": {"clicks": 1i64}}, options)
+            .await?
+            .map(Product::complete))
+    }
+
+    async fn get_click_counts(&self, list: &str) -> Result<Loaded<Vec<ClickCount>>> {
+        let options = FindOptions::builder()
+            .sort(doc! {"clicks": -1, "_id": 1})
+            .projection(doc! {"name": 1, "url": 1, "clicks": 1})
+            .build();
+        self.find(Some(doc! {"list": list_condition(list), "clicks": {"$gt": 0}, "deleted_at": Bson::Null}), Some(options)).await
+    }
+
+    async fn get_reserved_product_ids(&self, product_ids: &[ObjectId]) -> Result<Vec<ObjectId>> {
+        let mut cursor = self.collection::<Reservation>()
+            .find(Some(doc! {"product": {"$in": product_ids}}), None)
+            .await?;
+        let mut ids = Vec::new();
+        while let Some(reservation) = cursor.next().await {
+            ids.push(reservation?.get_product_id().clone());
+        }
+        Ok(ids)
+    }
+
+    async fn insert_reservation(&self, product_id: &ObjectId, token: &str, timestamp: i32) -> Result<bool> {
+        let reservation = doc! {"product": product_id, "token": token, "timestamp": timestamp};
+        match self.documents::<Reservation>().insert_one(reservation, None).await {
+            Ok(_) => Ok(true),
+            Err(e) if is_duplicate_key(&e) => Ok(false),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn delete_reservation(&self, product_id: &ObjectId, token: &str) -> Result<bool> {
+        let result = self.documents::<Reservation>()
+            .delete_one(doc! {"product": product_id, "token": token}, None)
+            .await?;
+        Ok(result.deleted_count > 0)
+    }
+
+    async fn get_pledge_pool(&self, product_id: &ObjectId) -> Result<Option<PledgePool>> {
+        self.find_one(doc! {"product": product_id}, None).await
+    }
+
+    async fn get_pledge_pools(&self, product_ids: &[ObjectId]) -> Result<Loaded<Vec<PledgePool>>> {
+        self.find(Some(doc! {"product": {"$in": product_ids}}), None).await
+    }
+
+    async fn insert_pledge_pool(&self, pool: Document) -> Result<bool> {
+        match self.documents::<PledgePool>().insert_one(pool, None).await {
+            Ok(_) => Ok(true),
+            Err(e) if is_duplicate_key(&e) => Ok(false),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn add_pledge(&self, product_id: &ObjectId, pledge: Document) -> Result<bool> {
+        let amount = pledge.get_i64("amount")?;
+        let filter = doc! {
+            "product": product_id,
+            "$expr": {"$lte": [{"$add": ["$funded", amount]}, "$target"]},
+        };
+        let result = self.documents::<PledgePool>()
+            .update_one(filter, doc! {"$inc": {"funded": amount}, "$push": {"pledges": pledge}}, None)
+            .await?;
+        Ok(result.matched_count > 0)
+    }
+
+    async fn remove_pledge(&self, product_id: &ObjectId, token: &str) -> Result<bool> {
+        // A pipeline, so the amount of the pledge is taken off the funded sum in the same update.
+        let withdrawn = doc! {"$filter": {"input": "$pledges", "cond": {"$eq": ["$$this.token", token]}}};
+        let update = vec![doc! {"$set": {
+            "funded": {"$subtract": ["$funded", {"$sum": {"$map": {"input": withdrawn, "in": "$$this.amount"}}}]},
+            "pledges": {"$filter": {"input": "$pledges", "cond": {"$ne": ["$$this.token", token]}}},
+        }}];
+        let result = self.documents::<PledgePool>()
+            .update_one(doc! {"product": product_id, "pledges.token": token}, update, None)
+            .await?;
+        Ok(result.matched_count > 0)
+    }
+
+    async fn delete_empty_pledge_pool(&self, product_id: &ObjectId) -> Result<bool> {
+        let result = self.documents::<PledgePool>()
+            .delete_one(doc! {"product": product_id, "pledges": {"$size": 0}}, None)
+            .await?;
+        Ok(result.deleted_count > 0)
+    }
+
+    async fn get_comments(&self, product_id: &ObjectId, offset: u64, size: u64) -> Result<Loaded<Vec<Comment>>> {
+        let options = FindOptions::builder()
+            .sort(doc! {"timestamp": 1, "_id": 1})
+            .skip(offset as i64)
+            .limit(size as i64)
+            .build();
+        self.find(Some(doc! {"product": product_id}), Some(options)).await
+    }
+
+    async fn get_comment(&self, id: &ObjectId) -> Result<Option<Comment>> {
+        self.find_one(doc! {"_id": id}, None).await
+    }
+
+    async fn insert_comment(&self, comment: Document) -> Result<ObjectId> {
+        self.insert::<Comment>(comment).await
+    }
+
+    async fn delete_comment(&self, id: &ObjectId) -> Result<bool> {
+        let result = self.documents::<Comment>()
+            .delete_one(doc! {"_id": id}, None)
+            .await?;
+        Ok(result.deleted_count > 0)
+    }
+
+    async fn get_push_subscriptions(&self) -> Result<Loaded<Vec<PushSubscription>>> {
+        self.find(None, None).await
+    }
+
+    async fn get_push_subscription(&self, endpoint: &str) -> Result<Option<PushSubscription>> {
+        self.find_one(doc! {"endpoint": endpoint}, None).await
+    }
+
+    async fn upsert_push_subscription(&self, subscription: Document) -> Result<()> {
+        let endpoint = subscription.get_str("endpoint")?.to_string();
+        let options = ReplaceOptions::builder().upsert(true).build();
+        self.documents::<PushSubscription>()
+            .replace_one(doc! {"endpoint": endpoint}, subscription, Some(options))
+            .await?;
+        Ok(())
+    }
+
+    async fn delete_push_subscription(&self, endpoint: &str) -> Result<bool> {
+        let result = self.documents::<PushSubscription>()
+            .delete_one(doc! {"endpoint": endpoint}, None)
+            .await?;
+        Ok(result.deleted_count > 0)
+    }
+
+    async fn get_price_alerts(&self) -> Result<Loaded<Vec<PriceAlert>>> {
+        let options = FindOptions::builder()
+            .sort(doc! {"created": -1})
+            .build();
+        self.find(None, Some(options)).await
+    }
+
+    async fn get_price_alerts_of_product(&self, product_id: &ObjectId) -> Result<Loaded<Vec<PriceAlert>>> {
+        self.find(Some(doc! {"product": product_id}), None).await
+    }
+
+    async fn get_price_alert(&self, id: &ObjectId) -> Result<Option<PriceAlert>> {
+        self.find_one(doc! {"_id": id}, None).await
+    }
+
+    async fn insert_price_alert(&self, alert: Document) -> Result<ObjectId> {
+        self.insert::<PriceAlert>(alert).await
+    }
+
+    async fn set_price_alert_triggered(&self, id: &ObjectId, timestamp: i32) -> Result<bool> {
+        let result = self.documents::<PriceAlert>()
+            .update_one(doc! {"_id": id}, doc! {"$set": {"triggered": timestamp}}, None)
+            .await?;
+        Ok(result.matched_count > 0)
+    }
+
+    async fn delete_price_alert(&self, id: &ObjectId) -> Result<bool> {
+        let result = self.documents::<PriceAlert>()
+            .delete_one(doc! {"_id": id}, None)
+            .await?;
+        Ok(result.deleted_count > 0)
+    }
+
+    async fn get_budget(&self, list: &str) -> Result<Option<Budget>> {
+        self.find_one(doc! {"list": list}, None).await
+    }
+
+    async fn upsert_budget(&self, budget: Document) -> Result<()> {
+        let list = budget.get_str("list")?.to_string();
+        let options = ReplaceOptions::builder().upsert(true).build();
+        self.documents::<Budget>()
+            .replace_one(doc! {"list": list}, budget, Some(options))
+            .await?;
+        Ok(())
+    }
+
+    async fn delete_budget(&self, list: &str) -> Result<bool> {
+        let result = self.documents::<Budget>()
+            .delete_one(doc! {"list": list}, None)
+            .await?;
+        Ok(result.deleted_count > 0)
+    }
+
+    async fn get_sources(&self) -> Result<Loaded<Vec<Source>>> {
+        self.find(None, None).await
+    }
+
+    async fn get_sources_by_id(&self, ids: &[ObjectId]) -> Result<Loaded<Vec<Source>>> {
+        let filter = doc! {
+            "_id": { "$in": ids }
+        };
+        self.find(Some(filter), None).await
+    }
+
+    async fn get_source_by_name(&self, name: &str) -> Result<Option<Source>> {
+        self.find_one(doc! {"name": name}, None).await
+    }
+
+    async fn source_exists(&self, id: &ObjectId) -> Result<bool> {
+        Ok(count_documents(&self.documents::<Source>(), Some(doc! {"_id": id})).await? > 0)
+    }
+
+    async fn insert_source(&self, source: Document) -> Result<ObjectId> {
+        self.insert::<Source>(source).await
+    }
+
+    async fn update_source(&self, id: &ObjectId, source: Document) -> Result<bool> {
+        let result = self.documents::<Source>()
+            .update_one(doc! {"_id": id}, doc! {"$set": source}, None)
+            .await?;
+        Ok(result.matched_count > 0)
+    }
+
+    async fn get_categories(&self) -> Result<Loaded<Vec<Category>>> {
+        let options = FindOptions::builder()
+            .sort(doc! {"sort_order": 1, "name": 1})
+            .build();
+        self.find(None, Some(options)).await
+    }
+
+    async fn get_category_by_id(&self, id: &ObjectId) -> Result<Option<Category>> {
+        self.find_one(doc! {"_id": id}, None).await
+    }
+
+    async fn get_category_by_name(&self, name: &str) -> Result<Option<Category>> {
+        self.find_one(doc! {"name": name}, None).await
+    }
+
+    async fn category_name_exists(&self, name: &str, except: Option<&ObjectId>) -> Result<bool> {
+        let filter = match except {
+            Some(id) => doc! {"name": name, "_id": {"$ne": id}},
+            None => doc! {"name": name},
+        };
+        Ok(count_documents(&self.documents::<Category>(), Some(filter)).await? > 0)
+    }
+
+    async fn insert_category(&self, category: Document) -> Result<ObjectId> {
+        self.insert::<Category>(category).await
+    }
+
+    async fn update_category(&self, id: &ObjectId, category: Document) -> Result<bool> {
+        let result = self.documents::<Category>()
+            .update_one(doc! {"_id": id}, doc! {"$set": category}, None)
+            .await?;
+        Ok(result.matched_count > 0)
+    }
+
+    async fn delete_category(&self, id: &ObjectId) -> Result<bool> {
+        let result = self.documents::<Category>()
+            .delete_one(doc! {"_id": id}, None)
+            .await?;
+        Ok(result.deleted_count > 0)
+    }
+
+    async fn reassign_category(&self, from: &ObjectId, to: &ObjectId) -> Result<u64> {
+        let result = self.documents::<Product>()
+            .update_many(doc! {"category": from}, doc! {"$set": {"category": to}}, None)
+            .await?;
+        Ok(result.modified_count as u64)
+    }
+
+    async fn merge_category(&self, from: &ObjectId, into: &ObjectId) -> Result<u64> {
+        let moved = self.reassign_category(from, into).await?;
+        self.documents::<Category>()
+            .update_many(doc! {"parent": from}, doc! {"$set": {"parent": into}}, None)
+            .await?;
+        self.documents::<CategoryRule>()
+            .update_many(doc! {"category": from}, doc! {"$set": {"category": into}}, None)
+            .await?;
+        Ok(moved)
+    }
+
+    async fn count_category_references(&self, id: &ObjectId) -> Result<u64> {
+        let products = count_documents(&self.documents::<Product>(), Some(doc! {"category": id})).await?;
+        let subcategories = count_documents(&self.documents::<Category>(), Some(doc! {"parent": id})).await?;
+        let rules = count_documents(&self.documents::<CategoryRule>(), Some(doc! {"category": id})).await?;
+        Ok(products + subcategories + rules)
+    }
+
+    async fn get_rules(&self) -> Result<Loaded<Vec<CategoryRule>>> {
+        let options = FindOptions::builder()
+            .sort(doc! {"_id": 1})
+            .build();
+        self.find(None, Some(options)).await
+    }
+
+    async fn get_rule_by_id(&self, id: &ObjectId) -> Result<Option<CategoryRule>> {
+        self.find_one(doc! {"_id": id}, None).await
+    }
+
+    async fn insert_rule(&self, rule: Document) -> Result<ObjectId> {
+        self.insert::<CategoryRule>(rule).await
+    }
+
+    async fn update_rule(&self, id: &ObjectId, rule: Document) -> Result<bool> {
+        let result = self.documents::<CategoryRule>()
+            .update_one(doc! {"_id": id}, doc! {"$set": rule}, None)
+            .await?;
+        Ok(result.matched_count > 0)
+    }
+
+    async fn delete_rule(&self, id: &ObjectId) -> Result<bool> {
+        let result = self.documents::<CategoryRule>()
+            .delete_one(doc! {"_id": id}, None)
+            .await?;
+        Ok(result.deleted_count > 0)
+    }
+
+    async fn get_price_history(&self, product_id: &ObjectId) -> Result<Loaded<Vec<PricePoint>>> {
+        let options = FindOptions::builder()
+            .sort(doc! {"timestamp": 1})
+            .build();
+        self.find(Some(doc! {"product": product_id}), Some(options)).await
+    }
+
+    async fn get_price_histories(&self, product_ids: &[ObjectId]) -> Result<Loaded<Vec<PricePoint>>> {
+        let options = FindOptions::builder()
+            .sort(doc! {"timestamp": 1})
+            .build();
+        self.find(Some(doc! {"product": {"$in": product_ids}}), Some(options)).await
+    }
+
+    async fn get_last_price_point(&self, product_id: &ObjectId) -> Result<Option<PricePoint>> {
+        let options = FindOneOptions::builder()
+            .sort(doc! {"timestamp": -1})
+            .build();
+        self.find_one(doc! {"product": product_id}, Some(options)).await
+    }
+
+    async fn insert_price_point(&self, product_id: &ObjectId, price: i32, timestamp: i32) -> Result<()> {
+        self.insert::<PricePoint>(doc! {"product": product_id, "price": price, "timestamp": timestamp}).await?;
+        // A pipeline, so the flag can be set by comparing with the lowest price before the update.
+        let update = vec![doc! {"$set": {
+            "at_all_time_low": {"$switch": {
+                "branches": [
+                    {"case": {"$eq": [{"$type": "$lowest_price"}, "missing"]}, "then": false},
+                    {"case": {"$lt": [price, "$lowest_price"]}, "then": true},
+                    {"case": {"$gt": [price, "$lowest_price"]}, "then": false},
+                ],
+                "default": {"$ifNull": ["$at_all_time_low", false]},
+            }},
+            "current_price": price,
+            "lowest_price": {"$min": ["$lowest_price", price]},
+            "updated_at": timestamp,
+        }}];
+        self.documents::<Product>()
+            .update_one(doc! {"_id": product_id}, update, None)
+            .await?;
+        Ok(())
+    }
+
+    async fn get_exchange_rates(&self) -> Result<Option<ExchangeRates>> {
+        self.find_one(doc! {}, None).await
+    }
+
+    async fn set_exchange_rates(&self, rates: Document) -> Result<()> {
+        let options = ReplaceOptions::builder().upsert(true).build();
+        self.documents::<ExchangeRates>().replace_one(doc! {}, rates, Some(options)).await?;
+        Ok(())
+    }
+
+    async fn insert_event(&self, event: Document) -> Result<()> {
+        self.insert::<AuditEvent>(event).await?;
+        Ok(())
+    }
+
+    async fn get_events(&self, entity: Option<&str>, entity_id: Option<&ObjectId>, offset: u64, size: u64) -> Result<Loaded<Vec<AuditEvent>>> {
+        let mut filter = Document::new();
+        if let Some(entity) = entity {
+            filter.insert("entity", entity);
+        }
+        if let Some(id) = entity_id {
+            filter.insert("entity_id", id.clone());
+        }
+        let options = FindOptions::builder()
+            .sort(doc! {"timestamp": -1, "_id": -1})
+            .skip(offset as i64)
+            .limit(size as i64)
+            .build();
+        self.find(Some(filter), Some(options)).await
+    }
+
+    async fn get_list_events(&self, list: &str, offset: u64, size: u64) -> Result<Loaded<Vec<AuditEvent>>> {
+        let product_ids = self.documents::<Product>()
+            .distinct("_id", doc! {"list": list_condition(list)}, None)
+            .await?;
+        let filter = doc! {"$or": [
+            {"entity": List::COLLECTION, "payload.slug": list},
+            {"entity": Budget::COLLECTION, "payload.list": list},
+            {"entity": Product::COLLECTION, "entity_id": {"$in": product_ids}},
+        ]};
+        let options = FindOptions::builder()
+            .sort(doc! {"timestamp": -1, "_id": -1})
+            .skip(offset as i64)
+            .limit(size as i64)
+            .build();
+        self.find(Some(filter), Some(options)).await
+    }
+
+    async fn dump_collection(&self, collection: &str) -> Result<Vec<Document>> {
+        let options = FindOptions::builder()
+            .sort(doc! {"_id": 1})
+            .build();
+        let mut cursor = self.database.collection(collection).find(None, Some(options)).await?;
+        let mut documents = Vec::new();
+        while let Some(document) = cursor.next().await {
+            documents.push(document?);
+        }
+        Ok(documents)
+    }
+
+    async fn replace_collection(&self, collection: &str, documents: Vec<Document>) -> Result<()> {
+        let collection = self.database.collection(collection);
+        collection.delete_many(doc! {}, None).await?;
+        if !documents.is_empty() {
+            collection.insert_many(documents, None).await?;
+        }
+        Ok(())
+    }
+}
+
+fn apply_product_filter(filter: &mut Document, product_filter: &ProductFilter) {
+    let mut price = Document::new();
+    if let Some(min_price) = product_filter.get_min_price() {
+        price.insert("$gte", min_price);
+    }
+    if let Some(max_price) = product_filter.get_max_price() {
+        price.insert("$lte", max_price);
+    }
+    if !price.is_empty() {
+        filter.insert("price", price);
+    }
+    // The offers of other sources count as well.
+    if let Some(source) = product_filter.get_source() {
+        filter.insert("$or", vec![doc! {"source": source.clone()}, doc! {"offers.source": source.clone()}]);
+    }
+    if let Some(list) = product_filter.get_list() {
+        filter.insert("list", list_condition(list));
+    }
+    match product_filter.get_archived() {
+        Some(true) => filter.insert("archived", true),
+        Some(false) => filter.insert("archived", doc! {"$ne": true}),
+        None => None,
+    };
+    if let Some(tag) = product_filter.get_tag() {
+        filter.insert("tags", tag);
+    }
+    if let Some(text) = product_filter.get_text() {
+        filter.insert("$text", doc! {"$search": text});
+    }
+    let mut last_seen = Document::new();
+    if let Some(after) = product_filter.get_last_seen_after() {
+        last_seen.insert("$gte", after);
+    }
+    if let Some(before) = product_filter.get_last_seen_before() {
+        last_seen.insert("$lte", before);
+    }
+    if !last_seen.is_empty() {
+        filter.insert("last_seen", last_seen);
+    }
+    if product_filter.is_collapsing_duplicates() {
+        filter.insert("duplicate_of", Bson::Null);
+    }
+    match product_filter.get_in_stock() {
+        Some(true) => filter.insert("availability", doc! {"$ne": Availability::OutOfStock.get_code()}),
+        Some(false) => filter.insert("availability", Availability::OutOfStock.get_code()),
+        None => None,
+    };
+    match product_filter.get_link_dead() {
+        Some(true) => filter.insert("link_dead", true),
+        Some(false) => filter.insert("link_dead", doc! {"$ne": true}),
+        None => None,
+    };
+    // Matches a missing `deleted_at` as well, leaving out the products in the trash.
+    filter.insert("deleted_at", Bson::Null);
+}
+
+/// Compares names case-insensitively, matching the `product_list_name` index.
+fn name_collation() -> Collation {
+    Collation::builder().locale("en".to_string()).strength(Some(2)).build()
+}
+
+/// Matches the documents of `list`. A missing `list` field matches `null`, so
+/// documents without one are included for the default list.
+fn list_condition(list: &str) -> Bson {
+    if list == DEFAULT_LIST {
+        Bson::Document(doc! {"$in": [Bson::Null, DEFAULT_LIST]})
+    } else {
+        Bson::String(list.to_string())
+    }
+}
+
+/// Filters and updates flagging the products of `list` in `current_ids` as
+/// current and the others as archived, leaving out those already flagged.
+fn archived_updates(list: &str, current_ids: &[ObjectId]) -> [(Document, Document); 2] {
+    [
+        (
+            doc! {"list": list_condition(list), "_id": {"$in": current_ids}, "archived": {"$ne": false}},
+            doc! {"$set": {"archived": false}},
+        ),
+        (
+            doc! {"list": list_condition(list), "_id": {"$nin": current_ids}, "archived": {"$ne": true}},
+            doc! {"$set": {"archived": true}},
+        ),
+    ]
+}
+
+/// Matches the documents of any of `lists`, like `list_condition` does for one.
+fn lists_condition(lists: &[String]) -> Bson {
+    let mut slugs: Vec<Bson> = lists.iter().map(|list| Bson::String(list.clone())).collect();
+    if lists.iter().any(|list| list == DEFAULT_LIST) {
+        slugs.push(Bson::Null);
+    }
+    Bson::Document(doc! {"$in": slugs})
+}
+
+/// Counts the products per value of `field`, most products first, named
+/// after the document of `collection` the value references.
+fn count_by(field: &str, collection: &str) -> Vec<Document> {
+    vec![
+        doc! {"$group": {"_id": field, "count": {"$sum": 1}}},
+        doc! {"$lookup": {"from": collection, "localField": "_id", "foreignField": "_id", "as": "referenced"}},
+        doc! {"$project": {"count": 1, "name": {"$arrayElemAt": ["$referenced.name", 0]}}},
+        doc! {"$sort": {"count": -1, "_id": 1}},
+    ]
+}
+
+/// Matches the products of any of `categories`, or the uncategorized ones for `None`.
+fn category_filter(categories: Option<&[ObjectId]>, lists: Option<&[String]>) -> Document {
+    let mut filter = match categories {
+        Some(ids) => doc! {
+            "category": {"$in": ids},
+        },
+        None => doc! {
+            "category": Bson::Null
+        }
+    };
+    if let Some(lists) = lists {
+        filter.insert("list", lists_condition(lists));
+    }
+    filter
+}
+
+/// Builds the sort document for `sort`, breaking ties newest first so pages stay stable.
+fn sort_document(sort: &SortQuery) -> Document {
+    let direction = if sort.is_ascending() { 1 } else { -1 };
+    let mut document = doc! { sort.get_key(): direction };
+    if sort.get_key() != "_id" {
+        document.insert("_id", -1);
+    }
+    document
+}
+
+fn is_duplicate_key(error: &MongoError) -> bool {
+    matches!(
+        error.kind.as_ref(),
+        ErrorKind::WriteError(WriteFailure::WriteError(e)) if e.code == DUPLICATE_KEY_ERROR
+    )
+}
+
+async fn count_documents(collection: &Collection, filter: Option<Document>) -> Result<u64> {
+    collection.count_documents(filter, None).await
+        .map(|n| n as u64)
+        .map_err(Error::from)
+}
+
+/// Returns the largest value of the integer `field` among the documents matching `filter`.
+async fn find_latest(collection: &Collection, filter: Document, field: &str) -> Result<Option<i32>> {
+    let options = FindOneOptions::builder()
+        .sort(doc! {field: -1})
+        .projection(doc! {field: 1})
+        .build();
+    Ok(collection.find_one(Some(filter), Some(options)).await?
+        .and_then(|document| document.get_i32(field).ok()))
+}
+
+/// Like `extract_cursor_results`, for aggregations shaping the documents into `T`.
+async fn extract_aggregate_results<T: Stored>(mut cursor: Cursor<Document>) -> Loaded<Vec<T>> {
+    let mut results = Vec::new();
+    let mut skipped = 0;
+    while let Some(entry) = cursor.next().await {
+        match entry.map_err(Error::from).and_then(T::from_document) {
+            Ok(result) => results.push(result),
+            Err(e) => {
+                warn!("Skipping document, couldn't extract it as {}: {}", std::any::type_name::<T>(), e);
+                skipped += 1;
+            }
+        }
+    }
+    Loaded::new(results, skipped)
+}
+
+async fn extract_cursor_results<T: Stored>(mut cursor: Cursor<T>) -> Loaded<Vec<T>> {
+    let mut results = Vec::new();
+    let mut skipped = 0;
+    while let Some(entry) = cursor.next().await {
+        match entry {
+            Ok(result) => results.push(result.complete()),
+            Err(e) => {
+                warn!("Skipping document, couldn't extract it as {}: {}", std::any::type_name::<T>(), e);
+                skipped += 1;
+            }
+        }
+    }
+    Loaded::new(results, skipped)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn archived_updates_split_the_list_by_the_snapshot() {
+        let ids = vec![ObjectId::new(), ObjectId::new()];
+        let [(current_filter, current_update), (archived_filter, archived_update)] = archived_updates("games", &ids);
+        assert_eq!(current_filter, doc! {"list": "games", "_id": {"$in": ids.clone()}, "archived": {"$ne": false}});
+        assert_eq!(current_update, doc! {"$set": {"archived": false}});
+        assert_eq!(archived_filter, doc! {"list": "games", "_id": {"$nin": ids.clone()}, "archived": {"$ne": true}});
+        assert_eq!(archived_update, doc! {"$set": {"archived": true}});
+    }
+
+    #[test]
+    fn archived_updates_match_products_without_list_on_the_default_list() {
+        let ids = vec![ObjectId::new()];
+        let [(current_filter, _), (archived_filter, _)] = archived_updates(DEFAULT_LIST, &ids);
+        let default_list = doc! {"$in": [Bson::Null, DEFAULT_LIST]};
+        assert_eq!(current_filter.get_document("list").ok(), Some(&default_list));
+        assert_eq!(archived_filter.get_document("list").ok(), Some(&default_list));
+    }
+
+    /// The `$nin` filter replaced `{"_id": {"$not": {"$in": ids}}}`, which
+    /// matches the same documents, an empty snapshot archiving every product.
+    #[test]
+    fn archived_updates_archive_everything_for_an_empty_snapshot() {
+        let [(current_filter, _), (archived_filter, _)] = archived_updates("games", &[]);
+        assert_eq!(current_filter.get_document("_id").ok(), Some(&doc! {"$in": Vec::<Bson>::new()}));
+        assert_eq!(archived_filter.get_document("_id").ok(), Some(&doc! {"$nin": Vec::<Bson>::new()}));
+    }
+}
@@ -0,0 +1,601 @@
+use async_trait::async_trait;
+use mongodb::bson::{document::Document, oid::ObjectId};
+use mongodb::error::{Error as MongoError, ErrorKind};
+use rand::Rng;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use super::{ProductFilter, WishlistRepository};
+use crate::config::DatabaseConfig;
+use crate::maintenance::Maintenance;
+use crate::model::{AuditEvent, Budget, Category, CategoryCount, CategoryRule, ClickCount, Comment, ExchangeRates, List, Loaded, PriceAlert, PledgePool, PricePoint, Product, PushSubscription, Source, Stats, TagCount, User, Wishlist, WishlistSummary};
+use crate::query::SortQuery;
+use crate::{Error, Result};
+
+/// Repository decorator retrying queries that fail because MongoDB can't be
+/// reached, with exponential backoff and jitter. Reads are retried on any
+/// network error, writes only if they never reached the server, so they are
+/// not applied twice.
+///
+/// When queries keep failing, a circuit breaker opens and queries fail fast
+/// with `Error::Unavailable` instead of each one waiting for the driver's
+/// timeout. Once the breaker was open long enough, a single query is let
+/// through, closing it again if the database answers.
+///
+/// In maintenance mode writes fail with `Error::Unavailable` right away.
+pub struct ResilientRepository {
+    inner: Arc<dyn WishlistRepository>,
+    maintenance: Maintenance,
+    attempts: u32,
+    backoff: Duration,
+    max_backoff: Duration,
+    jitter: f64,
+    failure_threshold: u32,
+    open_duration: Duration,
+    breaker: Mutex<Breaker>,
+}
+
+#[derive(Default)]
+struct Breaker {
+    /// Queries failed in a row since the last success.
+    failures: u32,
+    /// Queries fail fast until then, `None` while the breaker is closed.
+    open_until: Option<Instant>,
+    /// A query probing the database after the breaker was open is running.
+    probing: bool,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Access {
+    Read,
+    Write,
+}
+
+impl ResilientRepository {
+    pub fn new(inner: Arc<dyn WishlistRepository>, config: &DatabaseConfig, maintenance: Maintenance) -> Self {
+        Self {
+            inner,
+            maintenance,
+            attempts: config.retry.attempts.max(1),
+            backoff: config.retry.get_backoff(),
+            max_backoff: config.retry.get_max_backoff(),
+            jitter: config.retry.jitter.clamp(0., 1.),
+            failure_threshold: config.circuit_breaker.failure_threshold,
+            open_duration: config.circuit_breaker.get_open_duration(),
+            breaker: Mutex::new(Breaker::default()),
+        }
+    }
+
+    async fn read<T, F, Fut>(&self, query: F) -> Result<T>
+    where
+        F: Fn() -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        self.run(Access::Read, query).await
+    }
+
+    async fn write<T, F, Fut>(&self, query: F) -> Result<T>
+    where
+        F: Fn() -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        self.maintenance.check_writable()?;
+        self.run(Access::Write, query).await
+    }
+
+    async fn run<T, F, Fut>(&self, access: Access, query: F) -> Result<T>
+    where
+        F: Fn() -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        self.acquire()?;
+        let mut attempt = 1;
+        loop {
+            let result = query().await;
+            match &result {
+                Err(e) if is_retryable(e, access) && attempt < self.attempts => {
+                    let delay = self.get_delay(attempt);
+                    warn!("Database query failed on attempt {} of {}, retrying in {:?}: {}", attempt, self.attempts, delay, e);
+                    tokio::time::delay_for(delay).await;
+                    attempt += 1;
+                }
+                _ => {
+                    self.release(result.as_ref().err().map(is_unreachable).unwrap_or(false));
+                    return result;
+                }
+            }
+        }
+    }
+
+    /// Backoff before retry number `attempt`, doubling from `backoff` up to
+    /// `max_backoff`, with up to `jitter` of it randomly taken off.
+    fn get_delay(&self, attempt: u32) -> Duration {
+        let delay = self.backoff
+            .checked_mul(1 << (attempt - 1).min(16))
+            .unwrap_or(self.max_backoff)
+            .min(self.max_backoff);
+        let jitter = if self.jitter > 0. { rand::thread_rng().gen_range(0., self.jitter) } else { 0. };
+        delay.mul_f64(1. - jitter)
+    }
+
+    /// Fails fast while the breaker is open, lets a single probing query
+    /// through once it was open for `open_duration`.
+    fn acquire(&self) -> Result<()> {
+        let mut breaker = match self.breaker.lock() {
+            Ok(breaker) => breaker,
+            Err(_) => return Ok(()),
+        };
+        match breaker.open_until {
+            Some(until) if Instant::now() < until || breaker.probing => Err(unavailable()),
+            Some(_) => {
+                breaker.probing = true;
+                Ok(())
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Records the outcome of a query, `failed` if the database couldn't be
+    /// reached. Other errors mean the database answered, which counts as success.
+    fn release(&self, failed: bool) {
+        let mut breaker = match self.breaker.lock() {
+            Ok(breaker) => breaker,
+            Err(_) => return,
+        };
+        if !failed {
+            if breaker.open_until.is_some() {
+                info!("Database is reachable again, closing the circuit breaker");
+            }
+            *breaker = Breaker::default();
+            return;
+        }
+        breaker.failures += 1;
+        let reopen = breaker.probing;
+        if self.failure_threshold > 0 && (reopen || breaker.failures >= self.failure_threshold) {
+            if !reopen {
+                warn!("{} database queries failed in a row, failing fast for {:?}", breaker.failures, self.open_duration);
+            }
+            breaker.open_until = Some(Instant::now() + self.open_duration);
+            breaker.probing = false;
+        }
+    }
+}
+
+fn unavailable() -> Error {
+    Error::Unavailable("database is unavailable".to_string())
+}
+
+/// Checks if the query failed because the database couldn't be reached.
+fn is_unreachable(error: &Error) -> bool {
+    match error {
+        Error::MongoDB { source } => is_connection_error(source) || is_network_error(source),
+        _ => false,
+    }
+}
+
+/// Reads can be retried after any network error. Writes only if no
+/// connection was established, otherwise they may have been applied already.
+fn is_retryable(error: &Error, access: Access) -> bool {
+    match (error, access) {
+        (Error::MongoDB { source }, Access::Read) => is_connection_error(source) || is_network_error(source),
+        (Error::MongoDB { source }, Access::Write) => is_connection_error(source),
+        _ => false,
+    }
+}
+
+/// No server or connection was available, so the query was never sent.
+fn is_connection_error(error: &MongoError) -> bool {
+    matches!(
+        error.kind.as_ref(),
+        ErrorKind::ServerSelectionError { .. } | ErrorKind::WaitQueueTimeoutError { .. } | ErrorKind::ConnectionPoolClearedError { .. }
+    )
+}
+
+fn is_network_error(error: &MongoError) -> bool {
+    matches!(error.kind.as_ref(), ErrorKind::Io(_) | ErrorKind::TokioTimeoutElapsed(_))
+}
+
+#[async_trait]
+impl WishlistRepository for ResilientRepository {
+    async fn ensure_indexes(&self) -> Result<()> {
+        // Creating indexes is idempotent, so it is retried like a read and allowed in maintenance mode.
+        self.read(|| self.inner.ensure_indexes()).await
+    }
+
+    async fn ping(&self) -> Result<()> {
+        self.read(|| self.inner.ping()).await
+    }
+
+    async fn get_lists(&self) -> Result<Loaded<Vec<List>>> {
+        self.read(|| self.inner.get_lists()).await
+    }
+
+    async fn get_list_by_slug(&self, slug: &str) -> Result<Option<List>> {
+        self.read(|| self.inner.get_list_by_slug(slug)).await
+    }
+
+    async fn insert_list(&self, list: Document) -> Result<ObjectId> {
+        self.write(|| self.inner.insert_list(list.clone())).await
+    }
+
+    async fn update_list(&self, slug: &str, list: Document) -> Result<bool> {
+        self.write(|| self.inner.update_list(slug, list.clone())).await
+    }
+
+    async fn set_collaborator(&self, slug: &str, collaborator: Document) -> Result<bool> {
+        self.write(|| self.inner.set_collaborator(slug, collaborator.clone())).await
+    }
+
+    async fn remove_collaborator(&self, slug: &str, user: &ObjectId) -> Result<bool> {
+        self.write(|| self.inner.remove_collaborator(slug, user)).await
+    }
+
+    async fn get_user_by_id(&self, id: &ObjectId) -> Result<Option<User>> {
+        self.read(|| self.inner.get_user_by_id(id)).await
+    }
+
+    async fn get_user_by_name(&self, username: &str) -> Result<Option<User>> {
+        self.read(|| self.inner.get_user_by_name(username)).await
+    }
+
+    async fn insert_user(&self, user: Document) -> Result<ObjectId> {
+        self.write(|| self.inner.insert_user(user.clone())).await
+    }
+
+    async fn get_nth_wishlist_reverse(&self, list: &str, n: u64) -> Result<Wishlist> {
+        self.read(|| self.inner.get_nth_wishlist_reverse(list, n)).await
+    }
+
+    async fn get_wishlist_at(&self, list: &str, timestamp: i32) -> Result<Option<Wishlist>> {
+        self.read(|| self.inner.get_wishlist_at(list, timestamp)).await
+    }
+
+    async fn get_wishlist_history(&self, list: &str, offset: u64, size: u64) -> Result<Loaded<Vec<WishlistSummary>>> {
+        self.read(|| self.inner.get_wishlist_history(list, offset, size)).await
+    }
+
+    async fn insert_wishlist(&self, list: &str, timestamp: i32, product_ids: &[ObjectId]) -> Result<ObjectId> {
+        self.write(|| self.inner.insert_wishlist(list, timestamp, product_ids)).await
+    }
+
+    async fn delete_wishlists_at(&self, list: &str, timestamps: &[i32]) -> Result<u64> {
+        self.write(|| self.inner.delete_wishlists_at(list, timestamps)).await
+    }
+
+    async fn remove_wishlist_products(&self, id: &ObjectId, product_ids: &[ObjectId]) -> Result<bool> {
+        self.write(|| self.inner.remove_wishlist_products(id, product_ids)).await
+    }
+
+    async fn get_last_modified(&self, list: Option<&str>) -> Result<Option<i32>> {
+        self.read(|| self.inner.get_last_modified(list)).await
+    }
+
+    async fn get_product_by_id(&self, id: &ObjectId) -> Result<Option<Product>> {
+        self.read(|| self.inner.get_product_by_id(id)).await
+    }
+
+    async fn get_products_by_id(&self, ids: &[ObjectId]) -> Result<Loaded<Vec<Product>>> {
+        self.read(|| self.inner.get_products_by_id(ids)).await
+    }
+
+    async fn get_products(&self, filter: &ProductFilter, sort: &SortQuery, offset: u64, size: u64) -> Result<Loaded<Vec<Product>>> {
+        self.read(|| self.inner.get_products(filter, sort, offset, size)).await
+    }
+
+    async fn count_products(&self, filter: &ProductFilter) -> Result<u64> {
+        self.read(|| self.inner.count_products(filter)).await
+    }
+
+    async fn update_archived(&self, list: &str, current_ids: &[ObjectId]) -> Result<u64> {
+        self.write(|| self.inner.update_archived(list, current_ids)).await
+    }
+
+    async fn get_stats(&self, list: &str) -> Result<Stats> {
+        self.read(|| self.inner.get_stats(list)).await
+    }
+
+    async fn get_tags(&self, list: &str) -> Result<Loaded<Vec<TagCount>>> {
+        self.read(|| self.inner.get_tags(list)).await
+    }
+
+    async fn search_products(&self, query: &str, lists: Option<&[String]>, offset: u64, size: u64) -> Result<Loaded<Vec<Product>>> {
+        self.read(|| self.inner.search_products(query, lists, offset, size)).await
+    }
+
+    async fn suggest_product_names(&self, list: &str, prefix: &str, size: u64) -> Result<Vec<String>> {
+        self.read(|| self.inner.suggest_product_names(list, prefix, size)).await
+    }
+
+    async fn get_products_by_category(&self, categories: Option<&[ObjectId]>, lists: Option<&[String]>, sort: &SortQuery, offset: u64, size: u64) -> Result<Loaded<Vec<Product>>> {
+        self.read(|| self.inner.get_products_by_category(categories, lists, sort, offset, size)).await
+    }
+
+    async fn count_products_by_category(&self, categories: Option<&[ObjectId]>, lists: Option<&[String]>) -> Result<u64> {
+        self.read(|| self.inner.count_products_by_category(categories, lists)).await
+    }
+
+    async fn count_products_per_category(&self, lists: Option<&[String]>) -> Result<Loaded<Vec<CategoryCount>>> {
+        self.read(|| self.inner.count_products_per_category(lists)).await
+    }
+
+    async fn insert_product(&self, product: Document) -> Result<ObjectId> {
+        self.write(|| self.inner.insert_product(product.clone())).await
+    }
+
+    async fn upsert_product(&self, product: Document) -> Result<(ObjectId, bool)> {
+        self.write(|| self.inner.upsert_product(product.clone())).await
+    }
+
+    async fn find_duplicate_product(&self, list: &str, item_id: Option<&str>, url: &str) -> Result<Option<Product>> {
+        self.read(|| self.inner.find_duplicate_product(list, item_id, url)).await
+    }
+
+    async fn update_product(&self, id: &ObjectId, product: Document) -> Result<bool> {
+        self.write(|| self.inner.update_product(id, product.clone())).await
+    }
+
+    async fn update_products(&self, ids: &[ObjectId], product: Document) -> Result<u64> {
+        self.write(|| self.inner.update_products(ids, product.clone())).await
+    }
+
+    async fn delete_product(&self, id: &ObjectId, timestamp: i32) -> Result<Option<Product>> {
+        self.write(|| self.inner.delete_product(id, timestamp)).await
+    }
+
+    async fn get_deleted_products(&self, offset: u64, size: u64) -> Result<Loaded<Vec<Product>>> {
+        self.read(|| self.inner.get_deleted_products(offset, size)).await
+    }
+
+    async fn restore_product(&self, id: &ObjectId) -> Result<Option<Product>> {
+        self.write(|| self.inner.restore_product(id)).await
+    }
+
+    async fn purge_product(&self, id: &ObjectId) -> Result<Option<Product>> {
+        self.write(|| self.inner.purge_product(id)).await
+    }
+
+    async fn add_product_views(&self, views: &[(ObjectId, i64)]) -> Result<()> {
+        self.write(|| self.inner.add_product_views(views)).await
+    }
+
+    async fn record_click(&self, id: &ObjectId) -> Result<Option<Product>> {
+        self.write(|| self.inner.record_click(id)).await
+    }
+
+    async fn get_click_counts(&self, list: &str) -> Result<Loaded<Vec<ClickCount>>> {
+        self.read(|| self.inner.get_click_counts(list)).await
+    }
+
+    async fn get_reserved_product_ids(&self, product_ids: &[ObjectId]) -> Result<Vec<ObjectId>> {
+        self.read(|| self.inner.get_reserved_product_ids(product_ids)).await
+    }
+
+    async fn insert_reservation(&self, product_id: &ObjectId, token: &str, timestamp: i32) -> Result<bool> {
+        self.write(|| self.inner.insert_reservation(product_id, token, timestamp)).await
+    }
+
+    async fn delete_reservation(&self, product_id: &ObjectId, token: &str) -> Result<bool> {
+        self.write(|| self.inner.delete_reservation(product_id, token)).await
+    }
+
+    async fn get_pledge_pool(&self, product_id: &ObjectId) -> Result<Option<PledgePool>> {
+        self.read(|| self.inner.get_pledge_pool(product_id)).await
+    }
+
+    async fn get_pledge_pools(&self, product_ids: &[ObjectId]) -> Result<Loaded<Vec<PledgePool>>> {
+        self.read(|| self.inner.get_pledge_pools(product_ids)).await
+    }
+
+    async fn insert_pledge_pool(&self, pool: Document) -> Result<bool> {
+        self.write(|| self.inner.insert_pledge_pool(pool.clone())).await
+    }
+
+    async fn add_pledge(&self, product_id: &ObjectId, pledge: Document) -> Result<bool> {
+        self.write(|| self.inner.add_pledge(product_id, pledge.clone())).await
+    }
+
+    async fn remove_pledge(&self, product_id: &ObjectId, token: &str) -> Result<bool> {
+        self.write(|| self.inner.remove_pledge(product_id, token)).await
+    }
+
+    async fn delete_empty_pledge_pool(&self, product_id: &ObjectId) -> Result<bool> {
+        self.write(|| self.inner.delete_empty_pledge_pool(product_id)).await
+    }
+
+    async fn get_comments(&self, product_id: &ObjectId, offset: u64, size: u64) -> Result<Loaded<Vec<Comment>>> {
+        self.read(|| self.inner.get_comments(product_id, offset, size)).await
+    }
+
+    async fn get_comment(&self, id: &ObjectId) -> Result<Option<Comment>> {
+        self.read(|| self.inner.get_comment(id)).await
+    }
+
+    async fn insert_comment(&self, comment: Document) -> Result<ObjectId> {
+        self.write(|| self.inner.insert_comment(comment.clone())).await
+    }
+
+    async fn delete_comment(&self, id: &ObjectId) -> Result<bool> {
+        self.write(|| self.inner.delete_comment(id)).await
+    }
+
+    async fn get_push_subscriptions(&self) -> Result<Loaded<Vec<PushSubscription>>> {
+        self.read(|| self.inner.get_push_subscriptions()).await
+    }
+
+    async fn get_push_subscription(&self, endpoint: &str) -> Result<Option<PushSubscription>> {
+        self.read(|| self.inner.get_push_subscription(endpoint)).await
+    }
+
+    async fn upsert_push_subscription(&self, subscription: Document) -> Result<()> {
+        self.write(|| self.inner.upsert_push_subscription(subscription.clone())).await
+    }
+
+    async fn delete_push_subscription(&self, endpoint: &str) -> Result<bool> {
+        self.write(|| self.inner.delete_push_subscription(endpoint)).await
+    }
+
+    async fn get_price_alerts(&self) -> Result<Loaded<Vec<PriceAlert>>> {
+        self.read(|| self.inner.get_price_alerts()).await
+    }
+
+    async fn get_price_alerts_of_product(&self, product_id: &ObjectId) -> Result<Loaded<Vec<PriceAlert>>> {
+        self.read(|| self.inner.get_price_alerts_of_product(product_id)).await
+    }
+
+    async fn get_price_alert(&self, id: &ObjectId) -> Result<Option<PriceAlert>> {
+        self.read(|| self.inner.get_price_alert(id)).await
+    }
+
+    async fn insert_price_alert(&self, alert: Document) -> Result<ObjectId> {
+        self.write(|| self.inner.insert_price_alert(alert.clone())).await
+    }
+
+    async fn set_price_alert_triggered(&self, id: &ObjectId, timestamp: i32) -> Result<bool> {
+        self.write(|| self.inner.set_price_alert_triggered(id, timestamp)).await
+    }
+
+    async fn delete_price_alert(&self, id: &ObjectId) -> Result<bool> {
+        self.write(|| self.inner.delete_price_alert(id)).await
+    }
+
+    async fn get_budget(&self, list: &str) -> Result<Option<Budget>> {
+        self.read(|| self.inner.get_budget(list)).await
+    }
+
+    async fn upsert_budget(&self, budget: Document) -> Result<()> {
+        self.write(|| self.inner.upsert_budget(budget.clone())).await
+    }
+
+    async fn delete_budget(&self, list: &str) -> Result<bool> {
+        self.write(|| self.inner.delete_budget(list)).await
+    }
+
+    async fn get_sources(&self) -> Result<Loaded<Vec<Source>>> {
+        self.read(|| self.inner.get_sources()).await
+    }
+
+    async fn get_sources_by_id(&self, ids: &[ObjectId]) -> Result<Loaded<Vec<Source>>> {
+        self.read(|| self.inner.get_sources_by_id(ids)).await
+    }
+
+    async fn get_source_by_name(&self, name: &str) -> Result<Option<Source>> {
+        self.read(|| self.inner.get_source_by_name(name)).await
+    }
+
+    async fn source_exists(&self, id: &ObjectId) -> Result<bool> {
+        self.read(|| self.inner.source_exists(id)).await
+    }
+
+    async fn insert_source(&self, source: Document) -> Result<ObjectId> {
+        self.write(|| self.inner.insert_source(source.clone())).await
+    }
+
+    async fn update_source(&self, id: &ObjectId, source: Document) -> Result<bool> {
+        self.write(|| self.inner.update_source(id, source.clone())).await
+    }
+
+    async fn get_categories(&self) -> Result<Loaded<Vec<Category>>> {
+        self.read(|| self.inner.get_categories()).await
+    }
+
+    async fn get_category_by_id(&self, id: &ObjectId) -> Result<Option<Category>> {
+        self.read(|| self.inner.get_category_by_id(id)).await
+    }
+
+    async fn get_category_by_name(&self, name: &str) -> Result<Option<Category>> {
+        self.read(|| self.inner.get_category_by_name(name)).await
+    }
+
+    async fn category_name_exists(&self, name: &str, except: Option<&ObjectId>) -> Result<bool> {
+        self.read(|| self.inner.category_name_exists(name, except)).await
+    }
+
+    async fn insert_category(&self, category: Document) -> Result<ObjectId> {
+        self.write(|| self.inner.insert_category(category.clone())).await
+    }
+
+    async fn update_category(&self, id: &ObjectId, category: Document) -> Result<bool> {
+        self.write(|| self.inner.update_category(id, category.clone())).await
+    }
+
+    async fn delete_category(&self, id: &ObjectId) -> Result<bool> {
+        self.write(|| self.inner.delete_category(id)).await
+    }
+
+    async fn reassign_category(&self, from: &ObjectId, to: &ObjectId) -> Result<u64> {
+        self.write(|| self.inner.reassign_category(from, to)).await
+    }
+
+    async fn merge_category(&self, from: &ObjectId, into: &ObjectId) -> Result<u64> {
+        self.write(|| self.inner.merge_category(from, into)).await
+    }
+
+    async fn count_category_references(&self, id: &ObjectId) -> Result<u64> {
+        self.read(|| self.inner.count_category_references(id)).await
+    }
+
+    async fn get_rules(&self) -> Result<Loaded<Vec<CategoryRule>>> {
+        self.read(|| self.inner.get_rules()).await
+    }
+
+    async fn get_rule_by_id(&self, id: &ObjectId) -> Result<Option<CategoryRule>> {
+        self.read(|| self.inner.get_rule_by_id(id)).await
+    }
+
+    async fn insert_rule(&self, rule: Document) -> Result<ObjectId> {
+        self.write(|| self.inner.insert_rule(rule.clone())).await
+    }
+
+    async fn update_rule(&self, id: &ObjectId, rule: Document) -> Result<bool> {
+        self.write(|| self.inner.update_rule(id, rule.clone())).await
+    }
+
+    async fn delete_rule(&self, id: &ObjectId) -> Result<bool> {
+        self.write(|| self.inner.delete_rule(id)).await
+    }
+
+    async fn get_price_history(&self, product_id: &ObjectId) -> Result<Loaded<Vec<PricePoint>>> {
+        self.read(|| self.inner.get_price_history(product_id)).await
+    }
+
+    async fn get_price_histories(&self, product_ids: &[ObjectId]) -> Result<Loaded<Vec<PricePoint>>> {
+        self.read(|| self.inner.get_price_histories(product_ids)).await
+    }
+
+    async fn get_last_price_point(&self, product_id: &ObjectId) -> Result<Option<PricePoint>> {
+        self.read(|| self.inner.get_last_price_point(product_id)).await
+    }
+
+    async fn insert_price_point(&self, product_id: &ObjectId, price: i32, timestamp: i32) -> Result<()> {
+        self.write(|| self.inner.insert_price_point(product_id, price, timestamp)).await
+    }
+
+    async fn get_exchange_rates(&self) -> Result<Option<ExchangeRates>> {
+        self.read(|| self.inner.get_exchange_rates()).await
+    }
+
+    async fn set_exchange_rates(&self, rates: Document) -> Result<()> {
+        self.write(|| self.inner.set_exchange_rates(rates.clone())).await
+    }
+
+    async fn insert_event(&self, event: Document) -> Result<()> {
+        self.write(|| self.inner.insert_event(event.clone())).await
+    }
+
+    async fn get_events(&self, entity: Option<&str>, entity_id: Option<&ObjectId>, offset: u64, size: u64) -> Result<Loaded<Vec<AuditEvent>>> {
+        self.read(|| self.inner.get_events(entity, entity_id, offset, size)).await
+    }
+
+    async fn get_list_events(&self, list: &str, offset: u64, size: u64) -> Result<Loaded<Vec<AuditEvent>>> {
+        self.read(|| self.inner.get_list_events(list, offset, size)).await
+    }
+
+    async fn dump_collection(&self, collection: &str) -> Result<Vec<Document>> {
+        self.read(|| self.inner.dump_collection(collection)).await
+    }
+
+    async fn replace_collection(&self, collection: &str, documents: Vec<Document>) -> Result<()> {
+        self.write(|| self.inner.replace_collection(collection, documents.clone())).await
+    }
+}
@@ -0,0 +1,92 @@
+use mongodb::bson::{Bson, Document};
+use mongodb::event::command::{CommandEventHandler, CommandFailedEvent, CommandStartedEvent, CommandSucceededEvent};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+use tracing::Span;
+
+use crate::config::LogConfig;
+
+/// Longest rendered filter put into a log record.
+const MAX_FILTER_LENGTH: usize = 1000;
+/// Fields of a command selecting the documents it works on.
+const FILTER_FIELDS: [&str; 4] = ["filter", "query", "pipeline", "sort"];
+/// Fields of bulk commands holding statements, each selecting documents by `q`.
+const STATEMENT_FIELDS: [&str; 2] = ["updates", "deletes"];
+
+/// Opens a span for every command the MongoDB driver sends, as child of the
+/// request it is run for, and logs its duration and filter when the reply arrives.
+pub struct QueryTracer {
+    slow_query: Duration,
+    spans: Mutex<HashMap<i32, (Span, String)>>,
+}
+
+impl QueryTracer {
+    pub fn new(config: &LogConfig) -> Self {
+        Self {
+            slow_query: config.get_slow_query(),
+            spans: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn take_span(&self, request_id: i32) -> Option<(Span, String)> {
+        self.spans.lock().ok()?.remove(&request_id)
+    }
+}
+
+impl CommandEventHandler for QueryTracer {
+    fn handle_command_started_event(&self, event: CommandStartedEvent) {
+        // Most commands name their collection as value, `getMore` in a field of its own.
+        let collection = event.command.get(&event.command_name)
+            .and_then(Bson::as_str)
+            .or_else(|| event.command.get_str("collection").ok())
+            .unwrap_or("-");
+        let span = info_span!("mongo", command = %event.command_name, collection = %collection);
+        let filter = render_filter(&event.command);
+        if let Ok(mut spans) = self.spans.lock() {
+            spans.insert(event.request_id, (span, filter));
+        }
+    }
+
+    fn handle_command_succeeded_event(&self, event: CommandSucceededEvent) {
+        if let Some((span, filter)) = self.take_span(event.request_id) {
+            if event.duration >= self.slow_query {
+                warn!(parent: &span, "Slow MongoDB {} took {:?}: {}", event.command_name, event.duration, filter);
+            } else {
+                debug!(parent: &span, "MongoDB {} took {:?}: {}", event.command_name, event.duration, filter);
+            }
+        }
+    }
+
+    fn handle_command_failed_event(&self, event: CommandFailedEvent) {
+        if let Some((span, filter)) = self.take_span(event.request_id) {
+            warn!(parent: &span, "MongoDB {} failed after {:?}: {} ({})", event.command_name, event.duration, event.failure, filter);
+        }
+    }
+}
+
+/// Renders what a command selects documents by, leaving out the documents it writes.
+fn render_filter(command: &Document) -> String {
+    let mut shown = Document::new();
+    for field in FILTER_FIELDS.iter() {
+        if let Some(value) = command.get(field) {
+            shown.insert(*field, value.clone());
+        }
+    }
+    for field in STATEMENT_FIELDS.iter() {
+        if let Ok(statements) = command.get_array(field) {
+            let filters: Vec<Bson> = statements.iter()
+                .filter_map(Bson::as_document)
+                .filter_map(|statement| statement.get("q").cloned())
+                .collect();
+            shown.insert(*field, filters);
+        }
+    }
+    let mut rendered = shown.to_string();
+    if rendered.len() > MAX_FILTER_LENGTH {
+        let end = (0..=MAX_FILTER_LENGTH).rev().find(|i| rendered.is_char_boundary(*i)).unwrap_or(0);
+        rendered.truncate(end);
+        rendered.push('…');
+    }
+    rendered
+}
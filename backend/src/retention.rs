@@ -0,0 +1,81 @@
+use std::collections::BTreeSet;
+use std::time::Duration;
+
+use crate::config::JobsConfig;
+use crate::model::{RetentionPreview, WishlistSummary, DEFAULT_LIST};
+use crate::repository::WishlistRepository;
+use crate::Result;
+
+const SECONDS_PER_DAY: i64 = 24 * 60 * 60;
+/// Snapshots read from the history at once.
+const PAGE_SIZE: u64 = 1000;
+
+/// Which snapshots to keep: all of them for `keep_all`, then the last one of
+/// every day (UTC) until `retention`, older ones not at all. The latest
+/// snapshot of a list is always kept.
+#[derive(Clone, Copy, Debug)]
+pub struct RetentionPolicy {
+    keep_all: Duration,
+    retention: Duration,
+}
+
+impl RetentionPolicy {
+    pub fn from_config(config: &JobsConfig) -> Self {
+        Self {
+            keep_all: config.get_snapshot_keep_all(),
+            retention: config.get_snapshot_retention(),
+        }
+    }
+
+    /// Returns the snapshots of every list the policy deletes at `now`.
+    pub async fn preview(&self, repo: &dyn WishlistRepository, now: i64) -> Result<Vec<RetentionPreview>> {
+        let mut slugs = vec![DEFAULT_LIST.to_string()];
+        slugs.extend(repo.get_lists().await?.into_value().iter().map(|l| l.get_slug().to_string()));
+
+        let mut previews = Vec::with_capacity(slugs.len());
+        for list in slugs {
+            let mut snapshots = Vec::new();
+            loop {
+                let page = repo.get_wishlist_history(&list, snapshots.len() as u64, PAGE_SIZE).await?.into_value();
+                let done = (page.len() as u64) < PAGE_SIZE;
+                snapshots.extend(page);
+                if done {
+                    break;
+                }
+            }
+            let total = snapshots.len() as u64;
+            let deleted = self.select_deleted(snapshots, now);
+            previews.push(RetentionPreview::new(&list, total - deleted.len() as u64, deleted));
+        }
+        Ok(previews)
+    }
+
+    /// Picks the snapshots to delete from `snapshots`, ordered newest first.
+    /// Snapshots sharing a timestamp with a kept one are kept as well, as
+    /// snapshots are deleted by timestamp.
+    fn select_deleted(&self, snapshots: Vec<WishlistSummary>, now: i64) -> Vec<WishlistSummary> {
+        let keep_all_since = now - self.keep_all.as_secs() as i64;
+        let keep_daily_since = now - self.retention.as_secs() as i64;
+        let mut kept_days = BTreeSet::new();
+        let mut kept_timestamps = BTreeSet::new();
+        let mut deleted = Vec::new();
+        for (i, snapshot) in snapshots.into_iter().enumerate() {
+            let timestamp = match snapshot.get_timestamp() {
+                Some(timestamp) => timestamp,
+                None => continue,
+            };
+            let day = i64::from(timestamp).div_euclid(SECONDS_PER_DAY);
+            let keep = i == 0
+                || i64::from(timestamp) >= keep_all_since
+                || (i64::from(timestamp) >= keep_daily_since && !kept_days.contains(&day));
+            if keep {
+                kept_days.insert(day);
+                kept_timestamps.insert(timestamp);
+            } else {
+                deleted.push(snapshot);
+            }
+        }
+        deleted.retain(|s| s.get_timestamp().map(|t| !kept_timestamps.contains(&t)).unwrap_or(false));
+        deleted
+    }
+}
@@ -1,17 +1,52 @@
-use std;
+use mongodb::bson::oid::ObjectId;
+use std::convert::Infallible;
 use std::sync::Arc;
-use warp;
-use warp::Filter;
-use mongodb::Client;
+use tokio::stream::StreamExt;
+use warp::http::{HeaderValue, StatusCode};
+use warp::{Filter, Reply};
 
 use super::Result;
+use crate::affiliate::apply_affiliate;
+use crate::auth::{with_api_key, with_identity, with_optional_identity, ApiKeyScope, ApiKeys, Identity, SessionKeys};
+use crate::compress::apply_compression;
+use crate::enrichment::Enricher;
+use crate::config::{RateLimitConfig, ServerConfig};
+use crate::cors::{apply_cors, with_preflight, Cors};
+use crate::currency::convert_prices;
+use crate::etag::apply_etag;
+use crate::format::apply_format;
+use crate::graphql::create_schema;
 use crate::reject::handle_rejection;
 use crate::handler::*;
+use crate::images::ImageStore;
+use crate::jobs::{Scheduler, Scraper};
+use crate::maintenance::{with_writable, Maintenance};
+use crate::model::{Health, MaintenanceInput, DEFAULT_LIST};
+use crate::modified::apply_last_modified;
+use crate::notify::Notifier;
+use crate::openapi::{OPENAPI_SPEC, SWAGGER_UI};
+use crate::paging::{with_page_location, PageLocation};
+use crate::politeness::PoliteClient;
+use crate::query::{CurrencyQuery, LinkQuery};
+use crate::ratelimit::{with_rate_limit, RateLimiter};
+use crate::repository::WishlistRepository;
+use crate::retention::RetentionPolicy;
+use crate::steam::SteamClient;
+use crate::trace::{add_request_id, log_request, request_span, time_handler};
+use crate::versions::{apply_deprecation, v1};
+use crate::views::ViewCounter;
+
+const MAX_BODY_SIZE: u64 = 64 * 1024;
+const MAX_IMPORT_SIZE: u64 = 4 * 1024 * 1024;
+const RSS_CONTENT_TYPE: &str = "application/rss+xml; charset=utf-8";
+const SKIPPED_DOCUMENTS_HEADER: &str = "x-skipped-documents";
+const TOTAL_COUNT_HEADER: &str = "x-total-count";
+const IMAGE_CACHE_CONTROL: &str = "private, max-age=86400";
 
 macro_rules! reply_future {
-    ($function:ident) => {{
-        | db: Arc<Client> | async move  {
-            match $function(db).await {
+    ($function:ident $(, $arg:ident)*) => {{
+        | $($arg,)* repo: Arc<dyn WishlistRepository> | async move  {
+            match time_handler(stringify!($function), $function($($arg,)* repo)).await {
                 Ok(output) => Ok(warp::reply::json(&output)),
                 Err(e) => Err(warp::reject::custom(e)),
             }
@@ -19,73 +54,1477 @@ macro_rules! reply_future {
     };
 }
 
-macro_rules! reply_future_with_query {
-    ($function:ident) => {{
-        | query, db: Arc<Client> | async move  {
-            match $function(query, db).await {
-                Ok(output) => Ok(warp::reply::json(&output)),
+macro_rules! reply_loaded_future {
+    ($function:ident $(, $arg:ident)*) => {{
+        | $($arg,)* repo: Arc<dyn WishlistRepository> | async move  {
+            match time_handler(stringify!($function), $function($($arg,)* repo)).await {
+                Ok(output) => Ok(warp::reply::with_header(
+                    warp::reply::json(&output),
+                    SKIPPED_DOCUMENTS_HEADER,
+                    output.get_skipped().to_string(),
+                )),
                 Err(e) => Err(warp::reject::custom(e)),
             }
         }}
     };
 }
 
+/// Like `reply_loaded_future`, showing the prices in the currency requested with `?currency=`
+/// and the shop links with the affiliate parameters of their sources unless `?raw_links=true`.
+macro_rules! reply_converted_future {
+    ($function:ident $(, $arg:ident)*) => {{
+        | $($arg,)* currency: CurrencyQuery, link_query: LinkQuery, repo: Arc<dyn WishlistRepository> | async move  {
+            let output = match time_handler(stringify!($function), $function($($arg,)* repo.clone())).await {
+                Ok(output) => convert_prices(repo.as_ref(), currency.get_currency(), apply_affiliate(link_query.is_raw(), output)).await,
+                Err(e) => Err(e),
+            };
+            match output {
+                Ok(output) => Ok(warp::reply::with_header(
+                    warp::reply::json(&output),
+                    SKIPPED_DOCUMENTS_HEADER,
+                    output.get_skipped().to_string(),
+                )),
+                Err(e) => Err(warp::reject::custom(e)),
+            }
+        }}
+    };
+}
 
-pub async fn create_routes(db: Arc<Client>) -> Result<impl warp::Filter<Extract = impl warp::Reply> + Clone> {
+/// Like `reply_converted_future` for a `Page`, sending the total number of
+/// items as `X-Total-Count` and the neighbouring pages as `Link`.
+macro_rules! reply_paged_future {
+    ($function:ident $(, $arg:ident)*) => {{
+        | $($arg,)* currency: CurrencyQuery, link_query: LinkQuery, location: PageLocation, repo: Arc<dyn WishlistRepository> | async move  {
+            let output = match time_handler(stringify!($function), $function($($arg,)* repo.clone())).await {
+                Ok(output) => convert_prices(repo.as_ref(), currency.get_currency(), apply_affiliate(link_query.is_raw(), output)).await,
+                Err(e) => Err(e),
+            };
+            match output {
+                Ok(page) => {
+                    let mut response = warp::reply::json(&page).into_response();
+                    let headers = response.headers_mut();
+                    headers.insert(SKIPPED_DOCUMENTS_HEADER, page.get_skipped().into());
+                    headers.insert(TOTAL_COUNT_HEADER, page.get_total().into());
+                    if let Some(links) = location.get_links(&page).and_then(|l| HeaderValue::from_str(&l).ok()) {
+                        headers.insert("link", links);
+                    }
+                    Ok(response)
+                }
+                Err(e) => Err(warp::reject::custom(e)),
+            }
+        }}
+    };
+}
 
-    let with_db = warp::any().map(move || db.clone());
+macro_rules! reply_rss_future {
+    ($function:ident $(, $arg:ident)*) => {{
+        | $($arg,)* repo: Arc<dyn WishlistRepository> | async move  {
+            match time_handler(stringify!($function), $function($($arg,)* repo)).await {
+                Ok(output) => Ok(warp::reply::with_header(output, "content-type", RSS_CONTENT_TYPE)),
+                Err(e) => Err(warp::reject::custom(e)),
+            }
+        }}
+    };
+}
 
-    let log_filter = warp::log("api");
+/// Serves the `ExportFile` the handler returns as a download.
+macro_rules! reply_file_future {
+    ($function:ident $(, $arg:ident)*) => {{
+        | $($arg,)* repo: Arc<dyn WishlistRepository> | async move  {
+            match time_handler(stringify!($function), $function($($arg,)* repo)).await {
+                Ok(file) => {
+                    let (content_type, disposition) = (file.get_content_type(), file.get_disposition());
+                    Ok(warp::reply::with_header(
+                        warp::reply::with_header(file.into_body(), "content-type", content_type),
+                        "content-disposition",
+                        disposition,
+                    ))
+                }
+                Err(e) => Err(warp::reject::custom(e)),
+            }
+        }}
+    };
+}
 
-    let route_get_last_wishlist = warp::get()
-        .and(warp::path("api"))
-        .and(warp::path("wishlist"))
+/// Serves the `Image` the handler returns, letting clients cache it for a day.
+macro_rules! reply_image_future {
+    ($function:ident $(, $arg:ident)*) => {{
+        | $($arg,)* repo: Arc<dyn WishlistRepository> | async move  {
+            match time_handler(stringify!($function), $function($($arg,)* repo)).await {
+                Ok(image) => {
+                    let content_type = image.get_content_type();
+                    Ok(warp::reply::with_header(
+                        warp::reply::with_header(image.into_data(), "content-type", content_type),
+                        "cache-control",
+                        IMAGE_CACHE_CONTROL,
+                    ))
+                }
+                Err(e) => Err(warp::reject::custom(e)),
+            }
+        }}
+    };
+}
+
+/// Redirects to the url the handler returns with `302 Found`, uncached so
+/// every click reaches the handler.
+macro_rules! reply_redirect_future {
+    ($function:ident $(, $arg:ident)*) => {{
+        | $($arg,)* repo: Arc<dyn WishlistRepository> | async move  {
+            match time_handler(stringify!($function), $function($($arg,)* repo)).await {
+                Ok(url) => Ok(warp::reply::with_header(
+                    warp::reply::with_header(
+                        warp::reply::with_status(warp::reply(), StatusCode::FOUND),
+                        "location",
+                        url,
+                    ),
+                    "cache-control",
+                    "no-store",
+                )),
+                Err(e) => Err(warp::reject::custom(e)),
+            }
+        }}
+    };
+}
+
+#[allow(clippy::too_many_arguments)]
+pub async fn create_routes(repo: Arc<dyn WishlistRepository>, config: &ServerConfig, scheduler: Scheduler, scraper: Scraper, client: PoliteClient, enricher: Enricher, steam: SteamClient, notifier: Notifier, views: ViewCounter, maintenance: Maintenance, retention: RetentionPolicy) -> Result<impl warp::Filter<Extract = impl warp::Reply> + Clone> {
+    repo.ensure_indexes().await?;
+
+    let schema = create_schema(repo.clone(), views.clone());
+    let with_repo = warp::any().map(move || repo.clone());
+    let api_keys = ApiKeys::from_config(config);
+    let with_admin = with_api_key(api_keys.clone(), ApiKeyScope::Admin);
+    let sessions = SessionKeys::from_config(config);
+    let with_owner = with_identity(api_keys.clone(), sessions.clone());
+    let with_reader = with_optional_identity(api_keys, sessions.clone());
+    let with_sessions = warp::any().map(move || sessions.clone());
+    let swagger_ui = config.swagger_ui;
+    let with_notifier = warp::any().map(move || notifier.clone());
+    let status_scraper = scraper.clone();
+    let with_scraper = warp::any().map(move || scraper.clone());
+    let with_client = warp::any().map(move || client.clone());
+    let with_enricher = warp::any().map(move || enricher.clone());
+    let with_steam = warp::any().map(move || steam.clone());
+    let with_views = warp::any().map(move || views.clone());
+    let writable = with_writable(maintenance.clone());
+    let with_maintenance = warp::any().map(move || maintenance.clone());
+    let with_retention = warp::any().map(move || retention);
+    let images = ImageStore::new(&config.images);
+    let with_images = warp::any().map(move || images.clone());
+    // The unprefixed routes serve the default list, `/api/list/{slug}/...` the named ones.
+    let default_list = warp::any().map(|| DEFAULT_LIST.to_string());
+    let list_prefix = v1().and(warp::path("list")).and(warp::path::param::<String>());
+    // Leaves segments that aren't ids, like `/api/product/popular`, to the other routes.
+    let object_id = warp::path::param::<String>()
+        .and_then(|id: String| async move {
+            match ObjectId::with_string(&id) {
+                Ok(_) => Ok(id),
+                Err(_) => Err(warp::reject::not_found()),
+            }
+        });
+
+    let log_filter = warp::log::custom(log_request);
+    let rate_limit = with_rate_limit(RateLimiter::new(&config.rate_limit));
+    let comment_rate_limit = with_rate_limit(RateLimiter::new(&RateLimitConfig {
+        requests_per_second: f64::from(config.comments_per_minute) / 60.,
+        burst: config.comments_per_minute,
+        trust_forwarded_for: config.rate_limit.trust_forwarded_for,
+    }));
+    let cors = Cors::from_config(&config.cors)?;
+    let preflight = with_preflight(cors.clone());
+    let with_cors = warp::any().map(move || cors.clone());
+
+    let route_get_health = warp::path("healthz")
+        .and(warp::path::end())
+        .and(warp::get())
+        .map(|| warp::reply::json(&Health::new("ok")));
+
+    let route_get_readiness = warp::path("readyz")
+        .and(warp::path::end())
+        .and(warp::get())
+        .and(with_repo.clone())
+        .and_then(reply_future!(handle_get_readiness));
+
+    let route_get_lists = v1()
+        .and(warp::path("list"))
+        .and(warp::path("all"))
+        .and(warp::path::end())
+        .and(warp::get())
+        .and(with_reader.clone())
+        .and(with_repo.clone())
+        .and_then(reply_loaded_future!(handle_get_lists, identity));
+
+    let route_create_list = v1()
+        .and(warp::path("list"))
+        .and(warp::path::end())
+        .and(warp::post())
+        .and(with_owner.clone())
+        .and(warp::body::content_length_limit(MAX_BODY_SIZE))
+        .and(warp::body::json())
+        .and(with_repo.clone())
+        .and_then(reply_future!(handle_create_list, identity, body));
+
+    let route_update_list = list_prefix.clone()
+        .and(warp::path::end())
+        .and(warp::put())
+        .and(with_owner.clone())
+        .and(warp::body::content_length_limit(MAX_BODY_SIZE))
+        .and(warp::body::json())
+        .and(with_repo.clone())
+        .and_then(reply_future!(handle_update_list, list, identity, body));
+
+    let route_share_list = list_prefix.clone()
+        .and(warp::path("share"))
+        .and(warp::path::end())
+        .and(warp::post())
+        .and(with_owner.clone())
+        .and(warp::body::content_length_limit(MAX_BODY_SIZE))
+        .and(warp::body::json())
+        .and(with_sessions.clone())
+        .and(with_repo.clone())
+        .and_then(reply_future!(handle_share_list, list, identity, body, sessions));
+
+    let route_get_collaborators = list_prefix.clone()
+        .and(warp::path("collaborators"))
+        .and(warp::path::end())
+        .and(warp::get())
+        .and(with_owner.clone())
+        .and(with_repo.clone())
+        .and_then(reply_future!(handle_get_collaborators, list, identity));
+
+    let route_set_collaborator = list_prefix.clone()
+        .and(warp::path("collaborators"))
+        .and(warp::path::param::<String>())
+        .and(warp::path::end())
+        .and(warp::put())
+        .and(with_owner.clone())
+        .and(warp::body::content_length_limit(MAX_BODY_SIZE))
+        .and(warp::body::json())
+        .and(with_repo.clone())
+        .and_then(reply_future!(handle_set_collaborator, list, username, identity, body));
+
+    let route_remove_collaborator = list_prefix.clone()
+        .and(warp::path("collaborators"))
+        .and(warp::path::param::<String>())
+        .and(warp::path::end())
+        .and(warp::delete())
+        .and(with_owner.clone())
+        .and(with_repo.clone())
+        .and_then(reply_future!(handle_remove_collaborator, list, username, identity));
+
+    let route_register = v1()
+        .and(warp::path("user"))
+        .and(warp::path::end())
+        .and(warp::post())
+        .and(warp::body::content_length_limit(MAX_BODY_SIZE))
+        .and(warp::body::json())
+        .and(with_repo.clone())
+        .and_then(reply_future!(handle_register, body));
+
+    let route_login = v1()
+        .and(warp::path("user"))
+        .and(warp::path("login"))
+        .and(warp::path::end())
+        .and(warp::post())
+        .and(warp::body::content_length_limit(MAX_BODY_SIZE))
+        .and(warp::body::json())
+        .and(with_sessions)
+        .and(with_repo.clone())
+        .and_then(reply_future!(handle_login, body, sessions));
+
+    let route_get_current_user = v1()
+        .and(warp::path("user"))
+        .and(warp::path("me"))
+        .and(warp::path::end())
+        .and(warp::get())
+        .and(with_owner.clone())
+        .and(with_repo.clone())
+        .and_then(reply_future!(handle_get_current_user, identity));
+
+    let last_wishlist = warp::path("wishlist")
         .and(warp::path("last"))
         .and(warp::path::end())
-        .and(with_db.clone())
-        .and_then(reply_future!(handle_get_last_wishlist));
+        .and(warp::get());
+    let route_get_last_wishlist = v1()
+        .and(default_list)
+        .and(last_wishlist)
+        .or(list_prefix.clone().and(last_wishlist))
+        .unify()
+        .and(with_reader.clone())
+        .and(warp::query())
+        .and(with_views.clone())
+        .and(warp::query())
+        .and(warp::query())
+        .and(with_repo.clone())
+        .and_then(reply_converted_future!(handle_get_last_wishlist, list, identity, offers, views));
 
-    let route_get_newest_products = warp::get()
-        .and(warp::path("api"))
-        .and(warp::path("product"))
+    let wishlist_history = warp::path("wishlist")
+        .and(warp::path("history"))
+        .and(warp::path::end())
+        .and(warp::get());
+    let route_get_wishlist_history = v1()
+        .and(default_list)
+        .and(wishlist_history)
+        .or(list_prefix.clone().and(wishlist_history))
+        .unify()
+        .and(with_reader.clone())
+        .and(warp::query())
+        .and(with_repo.clone())
+        .and_then(reply_loaded_future!(handle_get_wishlist_history, list, identity, query));
+
+    let wishlist_at = warp::path("wishlist")
+        .and(warp::path("at"))
+        .and(warp::path::param::<i32>())
+        .and(warp::path::end())
+        .and(warp::get());
+    let route_get_wishlist_at = v1()
+        .and(default_list)
+        .and(wishlist_at)
+        .or(list_prefix.clone().and(wishlist_at))
+        .unify()
+        .and(with_reader.clone())
+        .and(warp::query())
+        .and(warp::query())
+        .and(with_repo.clone())
+        .and_then(reply_converted_future!(handle_get_wishlist_at, list, timestamp, identity));
+
+    let wishlist_export = warp::path("wishlist")
+        .and(warp::path("export"))
+        .and(warp::path::end())
+        .and(warp::get());
+    let route_get_wishlist_export = v1()
+        .and(default_list)
+        .and(wishlist_export)
+        .or(list_prefix.clone().and(wishlist_export))
+        .unify()
+        .and(with_reader.clone())
+        .and(warp::query())
+        .and(with_repo.clone())
+        .and_then(reply_file_future!(handle_export_wishlist, list, identity, query));
+
+    let wishlist_activity = warp::path("wishlist")
+        .and(warp::path("activity"))
+        .and(warp::path::end())
+        .and(warp::get());
+    let route_get_list_activity = v1()
+        .and(default_list)
+        .and(wishlist_activity)
+        .or(list_prefix.clone().and(wishlist_activity))
+        .unify()
+        .and(with_reader.clone())
+        .and(warp::query())
+        .and(with_repo.clone())
+        .and_then(reply_loaded_future!(handle_get_list_activity, list, identity, query));
+
+    let wishlist_value = warp::path("wishlist")
+        .and(warp::path("value"))
+        .and(warp::path::end())
+        .and(warp::get());
+    let route_get_wishlist_value = v1()
+        .and(default_list)
+        .and(wishlist_value)
+        .or(list_prefix.clone().and(wishlist_value))
+        .unify()
+        .and(with_reader.clone())
+        .and(warp::query())
+        .and(with_repo.clone())
+        .and_then(reply_loaded_future!(handle_get_wishlist_value, list, identity, query));
+
+    let wishlist_budget = warp::path("wishlist")
+        .and(warp::path("budget"))
+        .and(warp::path::end())
+        .and(warp::put());
+    let route_set_budget = v1()
+        .and(default_list)
+        .and(wishlist_budget)
+        .or(list_prefix.clone().and(wishlist_budget))
+        .unify()
+        .and(with_owner.clone())
+        .and(warp::body::content_length_limit(MAX_BODY_SIZE))
+        .and(warp::body::json())
+        .and(with_repo.clone())
+        .and_then(reply_future!(handle_set_budget, list, identity, body));
+
+    let wishlist_diff = warp::path("wishlist")
+        .and(warp::path("diff"))
+        .and(warp::path::end())
+        .and(warp::get());
+    let route_get_wishlist_diff = v1()
+        .and(default_list)
+        .and(wishlist_diff)
+        .or(list_prefix.clone().and(wishlist_diff))
+        .unify()
+        .and(with_reader.clone())
+        .and(warp::query())
+        .and(with_repo.clone())
+        .and_then(reply_loaded_future!(handle_get_wishlist_diff, list, identity, query));
+
+    let timeline = warp::path("timeline")
+        .and(warp::path::end())
+        .and(warp::get());
+    let route_get_timeline = v1()
+        .and(default_list)
+        .and(timeline)
+        .or(list_prefix.clone().and(timeline))
+        .unify()
+        .and(with_reader.clone())
+        .and(warp::query())
+        .and(with_repo.clone())
+        .and_then(reply_loaded_future!(handle_get_timeline, list, identity, query));
+
+    let newest_products = warp::path("product")
         .and(warp::path("newest"))
         .and(warp::path::end())
-        .and(with_db.clone())
-        .and_then(reply_future!(handle_get_newest_products));
+        .and(warp::get());
+    let route_get_newest_products = v1()
+        .and(default_list)
+        .and(newest_products)
+        .or(list_prefix.clone().and(newest_products))
+        .unify()
+        .and(with_reader.clone())
+        .and(warp::query())
+        .and(with_views.clone())
+        .and(warp::query())
+        .and(warp::query())
+        .and(with_repo.clone())
+        .and_then(reply_converted_future!(handle_get_newest_products, list, identity, query, views));
 
-    let route_get_archived_products = warp::get()
-        .and(warp::path("api"))
-        .and(warp::path("product"))
+    let feed = warp::path("feed.xml")
+        .and(warp::path::end())
+        .and(warp::get());
+    let route_get_feed = v1()
+        .and(default_list)
+        .and(feed)
+        .or(list_prefix.clone().and(feed))
+        .unify()
+        .and(with_reader.clone())
+        .and(warp::header::optional::<String>("host"))
+        .and(with_repo.clone())
+        .and_then(reply_rss_future!(handle_get_feed, list, identity, host));
+
+    let route_get_category_feed = v1()
+        .and(warp::path("category"))
+        .and(warp::path::param::<String>())
+        .and(warp::path("feed.xml"))
+        .and(warp::path::end())
+        .and(warp::get())
+        .and(with_reader.clone())
+        .and(warp::header::optional::<String>("host"))
+        .and(with_repo.clone())
+        .and_then(reply_rss_future!(handle_get_category_feed, param, identity, host));
+
+    let popular_products = warp::path("product")
+        .and(warp::path("popular"))
+        .and(warp::path::end())
+        .and(warp::get());
+    let route_get_popular_products = v1()
+        .and(default_list)
+        .and(popular_products)
+        .or(list_prefix.clone().and(popular_products))
+        .unify()
+        .and(with_reader.clone())
+        .and(warp::query())
+        .and(warp::query())
+        .and(warp::query())
+        .and(warp::query())
+        .and(warp::query())
+        .and(with_page_location())
+        .and(with_repo.clone())
+        .and_then(reply_paged_future!(handle_get_popular_products, list, identity, page, offers, fields));
+
+    let archived_products = warp::path("product")
+        .and(warp::path("archive"))
+        .and(warp::path::end())
+        .and(warp::get());
+    let route_get_archived_products = v1()
+        .and(default_list)
+        .and(archived_products)
+        .or(list_prefix.clone().and(archived_products))
+        .unify()
+        .and(with_reader.clone())
+        .and(warp::query())
+        .and(warp::query())
+        .and(warp::query())
+        .and(warp::query())
+        .and(with_views.clone())
+        .and(warp::query())
+        .and(warp::query())
+        .and(with_page_location())
+        .and(with_repo.clone())
+        .and_then(reply_paged_future!(handle_get_archived_products, list, identity, query, filter, sort, fields, views));
+
+    let archive_search = warp::path("product")
         .and(warp::path("archive"))
+        .and(warp::path("search"))
+        .and(warp::path::end())
+        .and(warp::get());
+    let route_search_archived_products = v1()
+        .and(default_list)
+        .and(archive_search)
+        .or(list_prefix.clone().and(archive_search))
+        .unify()
+        .and(with_reader.clone())
+        .and(warp::query())
+        .and(warp::query())
+        .and(warp::query())
+        .and(warp::query())
+        .and(with_views.clone())
+        .and(warp::query())
+        .and(warp::query())
+        .and(with_page_location())
+        .and(with_repo.clone())
+        .and_then(reply_paged_future!(handle_search_archived_products, list, identity, search, page, sort, fields, views));
+
+    let archive_product_count = warp::path("product")
+        .and(warp::path("archive"))
+        .and(warp::path("count"))
+        .and(warp::path::end())
+        .and(warp::get());
+    let route_get_archive_product_count = v1()
+        .and(default_list)
+        .and(archive_product_count)
+        .or(list_prefix.clone().and(archive_product_count))
+        .unify()
+        .and(with_reader.clone())
+        .and(warp::query())
+        .and(with_repo.clone())
+        .and_then(reply_future!(handle_get_archive_product_count, list, identity, filter));
+
+    let stats = warp::path("stats")
+        .and(warp::path::end())
+        .and(warp::get());
+    let route_get_stats = v1()
+        .and(default_list)
+        .and(stats)
+        .or(list_prefix.clone().and(stats))
+        .unify()
+        .and(with_reader.clone())
+        .and(warp::query())
+        .and(with_repo.clone())
+        .and_then(reply_future!(handle_get_stats, list, identity, query));
+
+    let click_stats = warp::path("stats")
+        .and(warp::path("clicks"))
+        .and(warp::path::end())
+        .and(warp::get());
+    let route_get_click_stats = v1()
+        .and(default_list)
+        .and(click_stats)
+        .or(list_prefix.clone().and(click_stats))
+        .unify()
+        .and(with_owner.clone())
+        .and(with_repo.clone())
+        .and_then(reply_loaded_future!(handle_get_click_stats, list, identity));
+
+    let tags = warp::path("tags")
+        .and(warp::path::end())
+        .and(warp::get());
+    let route_get_tags = v1()
+        .and(default_list)
+        .and(tags)
+        .or(list_prefix.clone().and(tags))
+        .unify()
+        .and(with_reader.clone())
+        .and(with_repo.clone())
+        .and_then(reply_loaded_future!(handle_get_tags, list, identity));
+
+    let suggest = warp::path("product")
+        .and(warp::path("suggest"))
+        .and(warp::path::end())
+        .and(warp::get());
+    let route_suggest = v1()
+        .and(default_list)
+        .and(suggest)
+        .or(list_prefix.clone().and(suggest))
+        .unify()
+        .and(with_reader.clone())
+        .and(warp::query())
+        .and(with_repo.clone())
+        .and_then(reply_future!(handle_suggest, list, identity, query));
+
+    let route_search_products = v1()
+        .and(warp::path("product"))
+        .and(warp::path("search"))
+        .and(warp::path::end())
+        .and(warp::get())
+        .and(with_reader.clone())
+        .and(warp::query())
+        .and(with_views.clone())
+        .and(warp::query())
+        .and(warp::query())
+        .and(with_repo.clone())
+        .and_then(reply_converted_future!(handle_search_products, identity, query, views));
+
+    let route_get_products_batch = v1()
+        .and(warp::path("product"))
+        .and(warp::path::end())
+        .and(warp::get())
+        .and(with_reader.clone())
+        .and(warp::query())
+        .and(warp::query())
+        .and(warp::query())
+        .and(with_repo.clone())
+        .and_then(reply_converted_future!(handle_get_products_batch, identity, query));
+
+    let route_get_product = v1()
+        .and(warp::path("product"))
+        .and(object_id)
         .and(warp::path::end())
+        .and(warp::get())
+        .and(with_reader.clone())
+        .and(warp::query())
+        .and(warp::query())
         .and(warp::query())
-        .and(with_db.clone())
-        .and_then(reply_future_with_query!(handle_get_archived_products));
+        .and(with_repo.clone())
+        .and_then(reply_converted_future!(handle_get_product, param, identity, query));
 
-    let route_get_products_by_category_name = warp::get()
-        .and(warp::path("api"))
+    let route_get_product_prices = v1()
+        .and(warp::path("product"))
+        .and(warp::path::param::<String>())
+        .and(warp::path("prices"))
+        .and(warp::path::end())
+        .and(warp::get())
+        .and(with_reader.clone())
+        .and(with_repo.clone())
+        .and_then(reply_loaded_future!(handle_get_product_prices, param, identity));
+
+    let route_follow_product = v1()
+        .and(warp::path("go"))
+        .and(warp::path::param::<String>())
+        .and(warp::path::end())
+        .and(warp::get())
+        .and(with_reader.clone())
+        .and(warp::query())
+        .and(with_repo.clone())
+        .and_then(reply_redirect_future!(handle_follow_product, param, identity, links));
+
+    let route_get_product_image = v1()
+        .and(warp::path("images"))
+        .and(warp::path::param::<String>())
+        .and(warp::path::param::<String>())
+        .and(warp::path::end())
+        .and(warp::get())
+        .and(with_reader.clone())
+        .and(with_images)
+        .and(with_repo.clone())
+        .and_then(reply_image_future!(handle_get_product_image, param, size, identity, images));
+
+    let route_create_product = v1()
+        .and(warp::path("product"))
+        .and(warp::path::end())
+        .and(warp::post())
+        .and(with_owner.clone())
+        .and(warp::body::content_length_limit(MAX_BODY_SIZE))
+        .and(warp::body::json())
+        .and(with_notifier.clone())
+        .and(with_repo.clone())
+        .and_then(reply_future!(handle_create_product, identity, body, notifier));
+
+    let route_ingest_product = v1()
+        .and(warp::path("ingest"))
+        .and(warp::path("product"))
+        .and(warp::path::end())
+        .and(warp::post())
+        .and(with_owner.clone())
+        .and(warp::body::content_length_limit(MAX_BODY_SIZE))
+        .and(warp::body::json())
+        .and(with_client.clone())
+        .and(with_notifier.clone())
+        .and(with_repo.clone())
+        .and_then(reply_future!(handle_ingest_product, identity, body, client, notifier));
+
+    let route_update_product = v1()
+        .and(warp::path("product"))
+        .and(warp::path::param::<String>())
+        .and(warp::path::end())
+        .and(warp::put())
+        .and(with_owner.clone())
+        .and(warp::body::content_length_limit(MAX_BODY_SIZE))
+        .and(warp::body::json())
+        .and(with_notifier.clone())
+        .and(with_repo.clone())
+        .and_then(reply_future!(handle_update_product, param, identity, body, notifier));
+
+    let route_set_price_threshold = v1()
+        .and(warp::path("product"))
+        .and(warp::path::param::<String>())
+        .and(warp::path("threshold"))
+        .and(warp::path::end())
+        .and(warp::put())
+        .and(with_owner.clone())
+        .and(warp::body::content_length_limit(MAX_BODY_SIZE))
+        .and(warp::body::json())
+        .and(with_repo.clone())
+        .and_then(reply_future!(handle_set_price_threshold, param, identity, body));
+
+    let route_create_price_alert = v1()
+        .and(warp::path("product"))
+        .and(warp::path::param::<String>())
+        .and(warp::path("alerts"))
+        .and(warp::path::end())
+        .and(warp::post())
+        .and(with_owner.clone())
+        .and(warp::body::content_length_limit(MAX_BODY_SIZE))
+        .and(warp::body::json())
+        .and(with_notifier.clone())
+        .and(with_repo.clone())
+        .and_then(reply_future!(handle_create_price_alert, param, identity, body, notifier));
+
+    let route_get_price_alerts = v1()
+        .and(warp::path("alerts"))
+        .and(warp::path::end())
+        .and(warp::get())
+        .and(with_owner.clone())
+        .and(with_repo.clone())
+        .and_then(reply_loaded_future!(handle_get_price_alerts, identity));
+
+    let route_delete_price_alert = v1()
+        .and(warp::path("alerts"))
+        .and(warp::path::param::<String>())
+        .and(warp::path::end())
+        .and(warp::delete())
+        .and(with_owner.clone())
+        .and(with_repo.clone())
+        .and_then(reply_future!(handle_delete_price_alert, param, identity));
+
+    let route_set_wish = v1()
+        .and(warp::path("product"))
+        .and(warp::path::param::<String>())
+        .and(warp::path("wish"))
+        .and(warp::path::end())
+        .and(warp::put())
+        .and(with_owner.clone())
+        .and(warp::body::content_length_limit(MAX_BODY_SIZE))
+        .and(warp::body::json())
+        .and(with_repo.clone())
+        .and_then(reply_future!(handle_set_wish, param, identity, body));
+
+    let route_enrich_product = v1()
+        .and(warp::path("product"))
+        .and(warp::path::param::<String>())
+        .and(warp::path("enrich"))
+        .and(warp::path::end())
+        .and(warp::post())
+        .and(with_owner.clone())
+        .and(with_enricher.clone())
+        .and(with_repo.clone())
+        .and_then(reply_future!(handle_enrich_product, param, identity, enricher));
+
+    let route_delete_product = v1()
+        .and(warp::path("product"))
+        .and(warp::path::param::<String>())
+        .and(warp::path::end())
+        .and(warp::delete())
+        .and(with_owner.clone())
+        .and(with_repo.clone())
+        .and_then(reply_future!(handle_delete_product, param, identity));
+
+    let route_reserve_product = v1()
+        .and(warp::path("product"))
+        .and(warp::path::param::<String>())
+        .and(warp::path("reserve"))
+        .and(warp::path::end())
+        .and(warp::post())
+        .and(with_reader.clone())
+        .and(with_repo.clone())
+        .and_then(reply_future!(handle_reserve_product, param, identity));
+
+    let route_cancel_reservation = v1()
+        .and(warp::path("product"))
+        .and(warp::path::param::<String>())
+        .and(warp::path("reserve"))
+        .and(warp::path::param::<String>())
+        .and(warp::path::end())
+        .and(warp::delete())
+        .and(with_repo.clone())
+        .and_then(reply_future!(handle_cancel_reservation, param, token));
+
+    let route_create_pledge_pool = v1()
+        .and(warp::path("product"))
+        .and(warp::path::param::<String>())
+        .and(warp::path("pool"))
+        .and(warp::path::end())
+        .and(warp::post())
+        .and(with_reader.clone())
+        .and(warp::body::content_length_limit(MAX_BODY_SIZE))
+        .and(warp::body::json())
+        .and(with_repo.clone())
+        .and_then(reply_future!(handle_create_pledge_pool, param, identity, body));
+
+    let route_get_pledge_pool = v1()
+        .and(warp::path("product"))
+        .and(warp::path::param::<String>())
+        .and(warp::path("pool"))
+        .and(warp::path::end())
+        .and(warp::get())
+        .and(with_reader.clone())
+        .and(with_repo.clone())
+        .and_then(reply_future!(handle_get_pledge_pool, param, identity));
+
+    let route_delete_pledge_pool = v1()
+        .and(warp::path("product"))
+        .and(warp::path::param::<String>())
+        .and(warp::path("pool"))
+        .and(warp::path::end())
+        .and(warp::delete())
+        .and(with_reader.clone())
+        .and(with_repo.clone())
+        .and_then(reply_future!(handle_delete_pledge_pool, param, identity));
+
+    let route_pledge = v1()
+        .and(warp::path("product"))
+        .and(warp::path::param::<String>())
+        .and(warp::path("pool"))
+        .and(warp::path("pledge"))
+        .and(warp::path::end())
+        .and(warp::post())
+        .and(with_reader.clone())
+        .and(warp::body::content_length_limit(MAX_BODY_SIZE))
+        .and(warp::body::json())
+        .and(with_repo.clone())
+        .and_then(reply_future!(handle_pledge, param, identity, body));
+
+    let route_withdraw_pledge = v1()
+        .and(warp::path("product"))
+        .and(warp::path::param::<String>())
+        .and(warp::path("pool"))
+        .and(warp::path("pledge"))
+        .and(warp::path::param::<String>())
+        .and(warp::path::end())
+        .and(warp::delete())
+        .and(with_repo.clone())
+        .and_then(reply_future!(handle_withdraw_pledge, param, token));
+
+    let route_get_comments = v1()
+        .and(warp::path("product"))
+        .and(warp::path::param::<String>())
+        .and(warp::path("comments"))
+        .and(warp::path::end())
+        .and(warp::get())
+        .and(with_reader.clone())
+        .and(warp::query())
+        .and(with_repo.clone())
+        .and_then(reply_loaded_future!(handle_get_comments, param, identity, query));
+
+    let route_post_comment = v1()
+        .and(warp::path("product"))
+        .and(warp::path::param::<String>())
+        .and(warp::path("comments"))
+        .and(warp::path::end())
+        .and(warp::post())
+        .and(comment_rate_limit.clone())
+        .and(with_reader.clone())
+        .and(warp::body::content_length_limit(MAX_BODY_SIZE))
+        .and(warp::body::json())
+        .and(with_repo.clone())
+        .and_then(reply_future!(handle_post_comment, param, identity, body));
+
+    let route_delete_comment = v1()
+        .and(warp::path("product"))
+        .and(warp::path::param::<String>())
+        .and(warp::path("comments"))
+        .and(warp::path::param::<String>())
+        .and(warp::path::end())
+        .and(warp::delete())
+        .and(with_owner.clone())
+        .and(with_repo.clone())
+        .and_then(reply_future!(handle_delete_comment, param, comment, identity));
+
+    let route_delete_comment_with_token = v1()
+        .and(warp::path("product"))
+        .and(warp::path::param::<String>())
+        .and(warp::path("comments"))
+        .and(warp::path::param::<String>())
+        .and(warp::path::param::<String>())
+        .and(warp::path::end())
+        .and(warp::delete())
+        .and(with_repo.clone())
+        .and_then(reply_future!(handle_delete_comment_with_token, param, comment, token));
+
+    let route_create_category = v1()
+        .and(warp::path("category"))
+        .and(warp::path::end())
+        .and(warp::post())
+        .and(with_admin.clone())
+        .and(warp::body::content_length_limit(MAX_BODY_SIZE))
+        .and(warp::body::json())
+        .and(with_repo.clone())
+        .and_then(reply_future!(handle_create_category, body));
+
+    let route_update_category = v1()
+        .and(warp::path("category"))
+        .and(warp::path::param::<String>())
+        .and(warp::path::end())
+        .and(warp::put())
+        .and(with_admin.clone())
+        .and(warp::body::content_length_limit(MAX_BODY_SIZE))
+        .and(warp::body::json())
+        .and(with_repo.clone())
+        .and_then(reply_future!(handle_update_category, param, body));
+
+    let route_delete_category = v1()
+        .and(warp::path("category"))
+        .and(warp::path::param::<String>())
+        .and(warp::path::end())
+        .and(warp::delete())
+        .and(with_admin.clone())
+        .and(warp::query())
+        .and(with_repo.clone())
+        .and_then(reply_future!(handle_delete_category, param, query));
+
+    let route_merge_categories = v1()
+        .and(warp::path("admin"))
+        .and(warp::path("categories"))
+        .and(warp::path::param::<String>())
+        .and(warp::path("merge"))
+        .and(warp::path::param::<String>())
+        .and(warp::path::end())
+        .and(warp::post())
+        .and(with_admin.clone())
+        .and(with_repo.clone())
+        .and_then(reply_future!(handle_merge_categories, from, into));
+
+    let route_get_products_by_category_name = v1()
         .and(warp::path("product"))
         .and(warp::path("category"))
         .and(warp::path::end())
+        .and(warp::get())
+        .and(with_reader.clone())
+        .and(warp::query())
+        .and(warp::query())
         .and(warp::query())
-        .and(with_db.clone())
-        .and_then(reply_future_with_query!(handle_get_products_by_category_name));
+        .and(with_views.clone())
+        .and(warp::query())
+        .and(warp::query())
+        .and(with_page_location())
+        .and(with_repo.clone())
+        .and_then(reply_paged_future!(handle_get_products_by_category_name, identity, query, list, sort, views));
 
-    let route_get_categories = warp::get()
-        .and(warp::path("api"))
+    let route_get_uncategorized_products = v1()
+        .and(warp::path("product"))
+        .and(warp::path("uncategorized"))
+        .and(warp::path::end())
+        .and(warp::get())
+        .and(with_reader.clone())
+        .and(warp::query())
+        .and(warp::query())
+        .and(with_views.clone())
+        .and(warp::query())
+        .and(warp::query())
+        .and(with_page_location())
+        .and(with_repo.clone())
+        .and_then(reply_paged_future!(handle_get_uncategorized_products, identity, list, sort, views));
+
+    let route_get_product_count_by_category_name = v1()
+        .and(warp::path("product"))
+        .and(warp::path("category"))
+        .and(warp::path("count"))
+        .and(warp::path::end())
+        .and(warp::get())
+        .and(with_reader.clone())
+        .and(warp::query())
+        .and(with_repo.clone())
+        .and_then(reply_future!(handle_get_product_count_by_category_name, identity, query));
+
+    let route_get_categories = v1()
         .and(warp::path("category"))
         .and(warp::path("list"))
         .and(warp::path::end())
-        .and(with_db.clone())
-        .and_then(reply_future!(handle_get_categories));
+        .and(warp::get())
+        .and(with_reader.clone())
+        .and(warp::query())
+        .and(with_repo.clone())
+        .and_then(reply_loaded_future!(handle_get_categories, identity, query));
+
+    let route_get_category_tree = v1()
+        .and(warp::path("category"))
+        .and(warp::path("tree"))
+        .and(warp::path::end())
+        .and(warp::get())
+        .and(with_repo.clone())
+        .and_then(reply_loaded_future!(handle_get_category_tree));
+
+    let route_bulk_update_products = v1()
+        .and(warp::path("admin"))
+        .and(warp::path("products"))
+        .and(warp::path("bulk"))
+        .and(warp::path::end())
+        .and(warp::post())
+        .and(with_admin.clone())
+        .and(warp::body::content_length_limit(MAX_BODY_SIZE))
+        .and(warp::body::json())
+        .and(with_repo.clone())
+        .and_then(reply_future!(handle_bulk_update_products, body));
+
+    let route_get_rules = v1()
+        .and(warp::path("admin"))
+        .and(warp::path("rules"))
+        .and(warp::path::end())
+        .and(warp::get())
+        .and(with_admin.clone())
+        .and(with_repo.clone())
+        .and_then(reply_loaded_future!(handle_get_rules));
+
+    let route_create_rule = v1()
+        .and(warp::path("admin"))
+        .and(warp::path("rules"))
+        .and(warp::path::end())
+        .and(warp::post())
+        .and(with_admin.clone())
+        .and(warp::body::content_length_limit(MAX_BODY_SIZE))
+        .and(warp::body::json())
+        .and(with_repo.clone())
+        .and_then(reply_future!(handle_create_rule, body));
+
+    let route_apply_rules = v1()
+        .and(warp::path("admin"))
+        .and(warp::path("rules"))
+        .and(warp::path("apply"))
+        .and(warp::path::end())
+        .and(warp::post())
+        .and(with_admin.clone())
+        .and(with_repo.clone())
+        .and_then(reply_future!(handle_apply_rules));
+
+    let route_update_rule = v1()
+        .and(warp::path("admin"))
+        .and(warp::path("rules"))
+        .and(warp::path::param::<String>())
+        .and(warp::path::end())
+        .and(warp::put())
+        .and(with_admin.clone())
+        .and(warp::body::content_length_limit(MAX_BODY_SIZE))
+        .and(warp::body::json())
+        .and(with_repo.clone())
+        .and_then(reply_future!(handle_update_rule, param, body));
+
+    let route_delete_rule = v1()
+        .and(warp::path("admin"))
+        .and(warp::path("rules"))
+        .and(warp::path::param::<String>())
+        .and(warp::path::end())
+        .and(warp::delete())
+        .and(with_admin.clone())
+        .and(with_repo.clone())
+        .and_then(reply_future!(handle_delete_rule, param));
+
+    let route_check_consistency = v1()
+        .and(warp::path("admin"))
+        .and(warp::path("check"))
+        .and(warp::path::end())
+        .and(warp::get())
+        .and(with_admin.clone())
+        .and(with_repo.clone())
+        .and_then(reply_future!(handle_check_consistency));
+
+    let route_fix_consistency = v1()
+        .and(warp::path("admin"))
+        .and(warp::path("check"))
+        .and(warp::path("fix"))
+        .and(warp::path::end())
+        .and(warp::post())
+        .and(with_admin.clone())
+        .and(with_repo.clone())
+        .and_then(reply_future!(handle_fix_consistency));
+
+    let route_import_products = v1()
+        .and(warp::path("admin"))
+        .and(warp::path("import"))
+        .and(warp::path::end())
+        .and(warp::post())
+        .and(with_admin.clone())
+        .and(warp::query())
+        .and(warp::header::optional::<String>("content-type"))
+        .and(warp::body::content_length_limit(MAX_IMPORT_SIZE))
+        .and(warp::body::bytes().map(|body: warp::hyper::body::Bytes| body.to_vec()))
+        .and(with_notifier.clone())
+        .and(with_repo.clone())
+        .and_then(reply_future!(handle_import_products, query, content_type, body, notifier));
+
+    let route_import_steam_wishlist = v1()
+        .and(warp::path("admin"))
+        .and(warp::path("import"))
+        .and(warp::path("steam"))
+        .and(warp::path::end())
+        .and(warp::post())
+        .and(with_admin.clone())
+        .and(warp::query())
+        .and(with_steam.clone())
+        .and(with_notifier.clone())
+        .and(with_repo.clone())
+        .and_then(reply_future!(handle_import_steam_wishlist, query, steam, notifier));
+
+    let route_get_jobs = v1()
+        .and(warp::path("admin"))
+        .and(warp::path("jobs"))
+        .and(warp::path::end())
+        .and(warp::get())
+        .and(with_admin.clone())
+        .map(move || warp::reply::json(&scheduler.get_statuses()));
+
+    let route_start_scrape = v1()
+        .and(warp::path("admin"))
+        .and(warp::path("scrape"))
+        .and(warp::path::end())
+        .and(warp::post())
+        .and(with_admin.clone())
+        .and(warp::query())
+        .and(with_scraper.clone())
+        .and(with_repo.clone())
+        .and_then(reply_future!(handle_start_scrape, query, scraper));
+
+    let route_get_scrape_status = v1()
+        .and(warp::path("admin"))
+        .and(warp::path("scrape"))
+        .and(warp::path("status"))
+        .and(warp::path::end())
+        .and(warp::get())
+        .and(with_admin.clone())
+        .map(move || warp::reply::json(&status_scraper.get_status()));
+
+    let route_get_audit_events = v1()
+        .and(warp::path("admin"))
+        .and(warp::path("events"))
+        .and(warp::path::end())
+        .and(warp::get())
+        .and(with_admin.clone())
+        .and(warp::query())
+        .and(warp::query())
+        .and(with_repo.clone())
+        .and_then(reply_loaded_future!(handle_get_events, query, page));
+
+    let route_get_dead_links = v1()
+        .and(warp::path("admin"))
+        .and(warp::path("dead-links"))
+        .and(warp::path::end())
+        .and(warp::get())
+        .and(with_admin.clone())
+        .and(warp::query())
+        .and(with_repo.clone())
+        .and_then(reply_future!(handle_get_dead_links, query));
+
+    let route_get_source_politeness = v1()
+        .and(warp::path("admin"))
+        .and(warp::path("sources"))
+        .and(warp::path::param::<String>())
+        .and(warp::path("politeness"))
+        .and(warp::path::end())
+        .and(warp::get())
+        .and(with_admin.clone())
+        .and(with_repo.clone())
+        .and_then(reply_future!(handle_get_source_politeness, param));
+
+    let route_set_source_politeness = v1()
+        .and(warp::path("admin"))
+        .and(warp::path("sources"))
+        .and(warp::path::param::<String>())
+        .and(warp::path("politeness"))
+        .and(warp::path::end())
+        .and(warp::put())
+        .and(with_admin.clone())
+        .and(warp::body::content_length_limit(MAX_BODY_SIZE))
+        .and(warp::body::json())
+        .and(with_repo.clone())
+        .and_then(reply_future!(handle_set_source_politeness, param, body));
+
+    let route_set_source_affiliate = v1()
+        .and(warp::path("admin"))
+        .and(warp::path("sources"))
+        .and(warp::path::param::<String>())
+        .and(warp::path("affiliate"))
+        .and(warp::path::end())
+        .and(warp::put())
+        .and(with_admin.clone())
+        .and(warp::body::content_length_limit(MAX_BODY_SIZE))
+        .and(warp::body::json())
+        .and(with_repo.clone())
+        .and_then(reply_future!(handle_set_source_affiliate, param, body));
+
+    let route_get_trash = v1()
+        .and(warp::path("admin"))
+        .and(warp::path("trash"))
+        .and(warp::path::end())
+        .and(warp::get())
+        .and(with_admin.clone())
+        .and(warp::query())
+        .and(with_repo.clone())
+        .and_then(reply_future!(handle_get_trash, query));
+
+    let route_restore_product = v1()
+        .and(warp::path("admin"))
+        .and(warp::path("trash"))
+        .and(warp::path::param::<String>())
+        .and(warp::path("restore"))
+        .and(warp::path::end())
+        .and(warp::post())
+        .and(with_admin.clone())
+        .and(with_repo.clone())
+        .and_then(reply_future!(handle_restore_product, param));
+
+    let route_purge_product = v1()
+        .and(warp::path("admin"))
+        .and(warp::path("trash"))
+        .and(warp::path::param::<String>())
+        .and(warp::path::end())
+        .and(warp::delete())
+        .and(with_admin.clone())
+        .and(with_repo.clone())
+        .and_then(reply_future!(handle_purge_product, param));
+
+    let route_preview_retention = v1()
+        .and(warp::path("admin"))
+        .and(warp::path("retention"))
+        .and(warp::path("preview"))
+        .and(warp::path::end())
+        .and(warp::get())
+        .and(with_admin.clone())
+        .and(with_retention)
+        .and(with_repo.clone())
+        .and_then(reply_future!(handle_preview_retention, retention));
+
+    let route_get_maintenance = v1()
+        .and(warp::path("maintenance"))
+        .and(warp::path::end())
+        .and(warp::get())
+        .and(with_maintenance.clone())
+        .map(|maintenance: Maintenance| warp::reply::json(&maintenance.get_status()));
+
+    let route_set_maintenance = v1()
+        .and(warp::path("admin"))
+        .and(warp::path("maintenance"))
+        .and(warp::path::end())
+        .and(warp::put())
+        .and(with_admin.clone())
+        .and(warp::body::content_length_limit(MAX_BODY_SIZE))
+        .and(warp::body::json())
+        .and(with_maintenance)
+        .and_then(|input: MaintenanceInput, maintenance: Maintenance| async move {
+            match handle_set_maintenance(input, maintenance).await {
+                Ok(status) => Ok(warp::reply::json(&status)),
+                Err(e) => Err(warp::reject::custom(e)),
+            }
+        });
+
+    let route_get_events = v1()
+        .and(warp::path("events"))
+        .and(warp::path::end())
+        .and(warp::get())
+        .and(with_notifier.clone())
+        .map(|notifier: Notifier| {
+            // Lagging subscribers skip the missed events instead of disconnecting.
+            let events = notifier.subscribe().filter_map(|event| match event {
+                Ok(event) => Some(Ok::<_, Infallible>((warp::sse::event(event.get_name()), warp::sse::json(event)))),
+                Err(_) => None,
+            });
+            warp::sse::reply(warp::sse::keep_alive().stream(events))
+        });
+
+    let route_get_push_key = v1()
+        .and(warp::path("push"))
+        .and(warp::path("key"))
+        .and(warp::path::end())
+        .and(warp::get())
+        .and(with_notifier.clone())
+        .and_then(|notifier: Notifier| async move {
+            match handle_get_push_key(notifier).await {
+                Ok(key) => Ok(warp::reply::json(&key)),
+                Err(e) => Err(warp::reject::custom(e)),
+            }
+        });
+
+    let route_push_subscribe = v1()
+        .and(warp::path("push"))
+        .and(warp::path("subscribe"))
+        .and(warp::path::end())
+        .and(warp::post())
+        .and(with_owner.clone())
+        .and(warp::body::content_length_limit(MAX_BODY_SIZE))
+        .and(warp::body::json())
+        .and(with_notifier.clone())
+        .and(with_repo.clone())
+        .and_then(reply_future!(handle_push_subscribe, identity, body, notifier));
+
+    let route_push_unsubscribe = v1()
+        .and(warp::path("push"))
+        .and(warp::path("unsubscribe"))
+        .and(warp::path::end())
+        .and(warp::post())
+        .and(with_owner.clone())
+        .and(warp::body::content_length_limit(MAX_BODY_SIZE))
+        .and(warp::body::json())
+        .and(with_repo.clone())
+        .and_then(reply_future!(handle_push_unsubscribe, identity, body));
+
+    let route_graphql = v1()
+        .and(warp::path("graphql"))
+        .and(warp::path::end())
+        .and(warp::post())
+        .and(with_reader)
+        .and(warp::body::content_length_limit(MAX_BODY_SIZE))
+        .and(warp::body::json())
+        .and_then(move |identity: Option<Identity>, request: async_graphql::Request| {
+            let schema = schema.clone();
+            let request = match identity {
+                Some(identity) => request.data(identity),
+                None => request,
+            };
+            async move { Ok::<_, warp::Rejection>(warp::reply::json(&schema.execute(request).await)) }
+        });
+
+    let route_get_openapi = v1()
+        .and(warp::path("openapi.json"))
+        .and(warp::path::end())
+        .and(warp::get())
+        .map(|| warp::reply::with_header(OPENAPI_SPEC, "content-type", "application/json"));
 
-    let routes = route_get_last_wishlist
+    let route_get_docs = v1()
+        .and(warp::path("docs"))
+        .and(warp::path::end())
+        .and(warp::get())
+        .and_then(move || async move {
+            if swagger_ui {
+                Ok(warp::reply::html(SWAGGER_UI))
+            } else {
+                Err(warp::reject::not_found())
+            }
+        });
+
+    // Probes are exempt from the rate limit, every other route shares one bucket per client.
+    let limited = route_get_lists
+        .or(route_create_list)
+        .or(route_update_list)
+        .or(route_share_list)
+        .or(route_get_collaborators)
+        .or(route_set_collaborator)
+        .or(route_remove_collaborator)
+        .or(route_get_push_key)
+        .or(route_push_subscribe)
+        .or(route_push_unsubscribe)
+        .or(route_register)
+        .or(route_login)
+        .or(route_get_current_user)
+        .or(route_get_last_wishlist)
+        .or(route_get_wishlist_diff)
+        .or(route_get_wishlist_history)
+        .or(route_get_wishlist_at)
+        .or(route_get_wishlist_export)
+        .or(route_get_list_activity)
+        .or(route_get_wishlist_value)
+        .or(route_set_budget)
+        .or(route_get_timeline)
         .or(route_get_newest_products)
+        .or(route_get_popular_products)
+        .or(route_get_feed)
+        .or(route_get_category_feed)
         .or(route_get_archived_products)
+        .or(route_get_archive_product_count)
+        .or(route_search_archived_products)
+        .or(route_get_stats)
+        .or(route_get_click_stats)
+        .or(route_get_tags)
+        .or(route_search_products)
+        .or(route_suggest)
+        .or(route_get_product_prices)
+        .or(route_get_product_image)
+        .or(route_follow_product)
+        .or(route_get_products_batch)
+        .or(route_get_product)
+        .or(route_create_product)
+        .or(route_ingest_product)
+        .or(route_update_product)
+        .or(route_set_price_threshold)
+        .or(route_create_price_alert)
+        .or(route_get_price_alerts)
+        .or(route_delete_price_alert)
+        .or(route_set_wish)
+        .or(route_enrich_product)
+        .or(route_delete_product)
+        .or(route_reserve_product)
+        .or(route_cancel_reservation)
+        .or(route_create_pledge_pool)
+        .or(route_get_pledge_pool)
+        .or(route_delete_pledge_pool)
+        .or(route_pledge)
+        .or(route_withdraw_pledge)
+        .or(route_get_comments)
+        .or(route_post_comment)
+        .or(route_delete_comment)
+        .or(route_delete_comment_with_token)
         .or(route_get_products_by_category_name)
+        .or(route_get_uncategorized_products)
+        .or(route_get_product_count_by_category_name)
         .or(route_get_categories)
-        .recover(handle_rejection)
-        .with(log_filter);
+        .or(route_get_category_tree)
+        .or(route_create_category)
+        .or(route_update_category)
+        .or(route_delete_category)
+        .or(route_merge_categories)
+        .or(route_bulk_update_products)
+        .or(route_get_rules)
+        .or(route_create_rule)
+        .or(route_apply_rules)
+        .or(route_update_rule)
+        .or(route_delete_rule)
+        .or(route_import_products)
+        .or(route_import_steam_wishlist)
+        .or(route_check_consistency)
+        .or(route_fix_consistency)
+        .or(route_get_jobs)
+        .or(route_start_scrape)
+        .or(route_get_scrape_status)
+        .or(route_get_audit_events)
+        .or(route_get_dead_links)
+        .or(route_get_source_politeness)
+        .or(route_set_source_politeness)
+        .or(route_set_source_affiliate)
+        .or(route_get_trash)
+        .or(route_restore_product)
+        .or(route_purge_product)
+        .or(route_preview_retention)
+        .or(route_get_maintenance)
+        .or(route_set_maintenance)
+        .or(route_get_events)
+        .or(route_graphql)
+        .or(route_get_openapi)
+        .or(route_get_docs);
+
+    let api = route_get_health
+        .or(route_get_readiness)
+        .or(rate_limit.and(writable).and(limited))
+        .recover(handle_rejection);
+
+    let formatted = warp::header::optional::<String>("accept")
+        .and(api)
+        .and_then(apply_format);
+
+    let tagged = warp::header::optional::<String>("if-none-match")
+        .and(warp::method())
+        .and(formatted)
+        .and_then(apply_etag);
+
+    let dated = warp::path::full()
+        .and(warp::method())
+        .and(warp::header::optional::<String>("if-modified-since"))
+        .and(warp::header::optional::<String>("if-none-match"))
+        .and(with_repo)
+        .and(tagged)
+        .and_then(apply_last_modified);
+
+    let versioned = warp::path::full()
+        .and(dated)
+        .and_then(apply_deprecation);
+
+    let cross_origin = warp::header::optional::<String>("origin")
+        .and(with_cors)
+        .and(preflight.or(versioned).unify())
+        .and_then(apply_cors);
+
+    let routes = warp::header::optional::<String>("accept-encoding")
+        .and(cross_origin)
+        .and_then(apply_compression)
+        .map(add_request_id)
+        .with(log_filter)
+        .with(warp::trace(request_span));
 
     Ok(routes)
 }
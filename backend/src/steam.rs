@@ -0,0 +1,233 @@
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::time::Duration;
+
+use crate::model::Currency;
+use crate::{Error, Result};
+
+pub const STEAM_API_URL: &str = "https://api.steampowered.com";
+pub const STEAM_STORE_URL: &str = "https://store.steampowered.com";
+/// Name of the source the games are stored with.
+pub(crate) const STEAM_SOURCE: &str = "Steam";
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+/// Prices asked for at once, the storefront answers several apps only for
+/// their prices.
+const PRICE_BATCH_SIZE: usize = 100;
+/// Pause between requests to the storefront, which answers `429 Too Many
+/// Requests` to more than about 200 requests in five minutes.
+const STORE_REQUEST_INTERVAL: Duration = Duration::from_millis(1500);
+
+/// Reads Steam wishlists through the Web API and the names and prices of
+/// their games through the storefront API, in the currency of the store of
+/// `country`. Only public wishlists can be read.
+#[derive(Clone)]
+pub struct SteamClient {
+    client: reqwest::Client,
+    api_url: String,
+    store_url: String,
+    country: String,
+}
+
+/// Game of the store, `price` is `None` for games that can't be bought yet.
+#[derive(Debug)]
+pub(crate) struct SteamGame {
+    name: String,
+    image: Option<String>,
+    price: Option<SteamPrice>,
+}
+
+/// Price of a game in the minor unit of its currency.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct SteamPrice {
+    currency: Currency,
+    price: i32,
+    discount_percent: i32,
+}
+
+#[derive(Deserialize)]
+struct WishlistResponse {
+    response: WishlistItems,
+}
+
+#[derive(Deserialize)]
+struct WishlistItems {
+    #[serde(default)]
+    items: Vec<WishlistItem>,
+}
+
+#[derive(Deserialize)]
+struct WishlistItem {
+    appid: u32,
+    #[serde(default)]
+    priority: u32,
+}
+
+#[derive(Deserialize)]
+struct AppDetails {
+    success: bool,
+    /// An empty array instead of an object if the filtered fields are missing.
+    #[serde(default)]
+    data: serde_json::Value,
+}
+
+#[derive(Deserialize)]
+struct AppData {
+    name: String,
+    header_image: Option<String>,
+    #[serde(default)]
+    is_free: bool,
+    price_overview: Option<PriceOverview>,
+}
+
+#[derive(Deserialize)]
+struct PriceOverview {
+    currency: String,
+    /// In cents of the currency, whether it has cents or not.
+    #[serde(rename = "final")]
+    final_price: i64,
+    #[serde(default)]
+    discount_percent: i32,
+}
+
+impl SteamClient {
+    pub fn new(api_url: String, store_url: String, country: String) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            api_url,
+            store_url,
+            country,
+        }
+    }
+
+    /// URL of the store page of a game.
+    pub fn get_app_url(&self, app_id: u32) -> String {
+        format!("{}/app/{}/", self.store_url.trim_end_matches('/'), app_id)
+    }
+
+    /// Returns the ids of the games on the wishlist of the profile with the
+    /// SteamID64 `steam_id`, ranked highest first.
+    pub async fn get_wishlist(&self, steam_id: &str) -> Result<Vec<u32>> {
+        let url = format!("{}/IWishlistService/GetWishlist/v1/", self.api_url.trim_end_matches('/'));
+        let response: WishlistResponse = self.fetch(&url, &[("steamid", steam_id)]).await?;
+        let mut items = response.response.items;
+        // Unranked games have priority 0 and come last.
+        items.sort_by_key(|item| (item.priority == 0, item.priority));
+        Ok(items.into_iter().map(|item| item.appid).collect())
+    }
+
+    /// Looks up the name, header image and price of a game, `None` if the
+    /// store doesn't know it.
+    pub(crate) async fn get_game(&self, app_id: u32) -> Result<Option<SteamGame>> {
+        let id = app_id.to_string();
+        let query = [("appids", id.as_str()), ("cc", self.country.as_str()), ("filters", "basic,price_overview")];
+        let details = self.fetch_app_details(&query).await?;
+        let data = match details.get(&id).filter(|d| d.success) {
+            Some(details) => serde_json::from_value::<AppData>(details.data.clone())
+                .map_err(|e| Error::Unavailable(format!("could not read Steam app {}: {}", app_id, e)))?,
+            None => return Ok(None),
+        };
+        let price = match (data.price_overview, data.is_free) {
+            (Some(overview), _) => overview.to_price(),
+            (None, true) => Some(SteamPrice { currency: Currency::default(), price: 0, discount_percent: 0 }),
+            (None, false) => None,
+        };
+        Ok(Some(SteamGame {
+            name: data.name,
+            image: data.header_image.filter(|image| image.starts_with("https://")),
+            price,
+        }))
+    }
+
+    /// Looks up the prices of several games, games without one are left out.
+    pub(crate) async fn get_prices(&self, app_ids: &[u32]) -> Result<HashMap<u32, SteamPrice>> {
+        let mut prices = HashMap::new();
+        for batch in app_ids.chunks(PRICE_BATCH_SIZE) {
+            let ids = batch.iter().map(u32::to_string).collect::<Vec<_>>().join(",");
+            let query = [("appids", ids.as_str()), ("cc", self.country.as_str()), ("filters", "price_overview")];
+            for (id, details) in self.fetch_app_details(&query).await? {
+                let overview = details.data.get("price_overview")
+                    .filter(|_| details.success)
+                    .and_then(|overview| serde_json::from_value::<PriceOverview>(overview.clone()).ok());
+                if let (Ok(id), Some(price)) = (id.parse(), overview.and_then(|o| o.to_price())) {
+                    prices.insert(id, price);
+                }
+            }
+        }
+        Ok(prices)
+    }
+
+    async fn fetch_app_details(&self, query: &[(&str, &str)]) -> Result<HashMap<String, AppDetails>> {
+        tokio::time::delay_for(STORE_REQUEST_INTERVAL).await;
+        let url = format!("{}/api/appdetails", self.store_url.trim_end_matches('/'));
+        // Unknown apps are answered with `null` instead of an object.
+        let details: HashMap<String, Option<AppDetails>> = self.fetch(&url, query).await?;
+        Ok(details.into_iter().filter_map(|(id, details)| details.map(|d| (id, d))).collect())
+    }
+
+    async fn fetch<T: serde::de::DeserializeOwned>(&self, url: &str, query: &[(&str, &str)]) -> Result<T> {
+        let unavailable = |e: reqwest::Error| Error::Unavailable(format!("could not reach Steam at {}: {}", url, e));
+        let response = self.client.get(url)
+            .query(query)
+            .timeout(REQUEST_TIMEOUT)
+            .send()
+            .await
+            .map_err(unavailable)?;
+        if response.status() == reqwest::StatusCode::FORBIDDEN || response.status() == reqwest::StatusCode::UNAUTHORIZED {
+            return Err(Error::Forbidden("the Steam wishlist is not public".to_string()));
+        }
+        response.error_for_status()
+            .map_err(unavailable)?
+            .json()
+            .await
+            .map_err(unavailable)
+    }
+}
+
+impl SteamGame {
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+    pub fn get_image(&self) -> Option<&str> {
+        self.image.as_deref()
+    }
+    pub fn get_price(&self) -> Option<SteamPrice> {
+        self.price
+    }
+}
+
+impl SteamPrice {
+    pub fn get_currency(&self) -> Currency {
+        self.currency
+    }
+    /// Price paid right now, the sale price during a sale.
+    pub fn get_price(&self) -> i32 {
+        self.price
+    }
+    pub fn get_discount_percent(&self) -> i32 {
+        self.discount_percent
+    }
+}
+
+impl PriceOverview {
+    /// `None` for currencies the backend doesn't know.
+    fn to_price(&self) -> Option<SteamPrice> {
+        let currency = Currency::from_code(&self.currency.to_ascii_uppercase())?;
+        let scale = 10i64.pow(2u32.saturating_sub(currency.get_minor_digits()));
+        Some(SteamPrice {
+            currency,
+            price: i32::try_from(self.final_price / scale).ok()?,
+            discount_percent: self.discount_percent.clamp(0, 100),
+        })
+    }
+}
+
+/// Whether `steam_id` is a SteamID64, the 17 digit id of a profile.
+pub(crate) fn is_steam_id(steam_id: &str) -> bool {
+    steam_id.len() == 17 && steam_id.starts_with("7656") && steam_id.chars().all(|c| c.is_ascii_digit())
+}
+
+/// Item id of a game, identifying its product on a list.
+pub(crate) fn item_id_of(app_id: u32) -> String {
+    format!("steam:{}", app_id)
+}
@@ -0,0 +1,164 @@
+use std::convert::Infallible;
+use std::fs::File;
+use std::future::Future;
+use std::io::{self, BufReader};
+use std::net::SocketAddr;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+use tokio_rustls::rustls::internal::pemfile::{certs, pkcs8_private_keys, rsa_private_keys};
+use tokio_rustls::rustls::{NoClientAuth, PrivateKey, ServerConfig};
+use tokio_rustls::server::TlsStream;
+use tokio_rustls::TlsAcceptor;
+use warp::http::HeaderValue;
+use warp::hyper::server::{accept, Server};
+use warp::hyper::service::{make_service_fn, service_fn, Service};
+use warp::{Filter, Reply};
+
+use crate::config::TlsConfig;
+use crate::ratelimit::PEER_ADDRESS_HEADER;
+use crate::{Error, Result};
+
+/// Longest a client may take for the handshake before its connection is dropped.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+/// Pause after failing to accept a connection, e.g. when out of file descriptors.
+const ACCEPT_ERROR_DELAY: Duration = Duration::from_millis(100);
+/// Connections that finished their handshake but weren't picked up by the server yet.
+const PENDING_CONNECTIONS: usize = 128;
+
+/// Certificate and key the server terminates TLS with, read from the PEM
+/// files named in the config. `reload` swaps them for the current content
+/// of the files, e.g. after the certificate was renewed, without dropping
+/// any connection.
+#[derive(Clone)]
+pub struct TlsTerminator {
+    cert_path: String,
+    key_path: String,
+    acceptor: Arc<RwLock<TlsAcceptor>>,
+}
+
+impl TlsTerminator {
+    /// `None` if the certificate or the key isn't configured, fails with
+    /// `Error::Config` if the files can't be read.
+    pub fn from_config(config: &TlsConfig) -> Result<Option<Self>> {
+        let (cert_path, key_path) = match (&config.cert_path, &config.key_path) {
+            (Some(cert_path), Some(key_path)) => (cert_path.clone(), key_path.clone()),
+            _ => return Ok(None),
+        };
+        let acceptor = load_acceptor(&cert_path, &key_path)?;
+        Ok(Some(Self {
+            cert_path,
+            key_path,
+            acceptor: Arc::new(RwLock::new(acceptor)),
+        }))
+    }
+
+    /// Reads the certificate and key again, keeping the current ones if that fails.
+    pub fn reload(&self) -> Result<()> {
+        let acceptor = load_acceptor(&self.cert_path, &self.key_path)?;
+        *self.acceptor.write().unwrap_or_else(|e| e.into_inner()) = acceptor;
+        Ok(())
+    }
+
+    fn get_acceptor(&self) -> TlsAcceptor {
+        self.acceptor.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// Accepts connections on `listener` and runs their handshakes, each in
+    /// a task of its own so a slow client doesn't hold up the others. The
+    /// listener stays open until the process exits.
+    fn incoming(&self, mut listener: TcpListener) -> mpsc::Receiver<io::Result<TlsStream<TcpStream>>> {
+        let (sender, receiver) = mpsc::channel(PENDING_CONNECTIONS);
+        let tls = self.clone();
+        tokio::spawn(async move {
+            loop {
+                let (tcp, peer) = match listener.accept().await {
+                    Ok(connection) => connection,
+                    Err(e) => {
+                        warn!("Could not accept a connection: {}", e);
+                        tokio::time::delay_for(ACCEPT_ERROR_DELAY).await;
+                        continue;
+                    }
+                };
+                let acceptor = tls.get_acceptor();
+                let mut sender = sender.clone();
+                tokio::spawn(async move {
+                    match tokio::time::timeout(HANDSHAKE_TIMEOUT, acceptor.accept(tcp)).await {
+                        Ok(Ok(stream)) => {
+                            let _ = sender.send(Ok(stream)).await;
+                        }
+                        Ok(Err(e)) => debug!("TLS handshake with {} failed: {}", peer, e),
+                        Err(_) => debug!("TLS handshake with {} timed out", peer),
+                    }
+                });
+            }
+        });
+        receiver
+    }
+}
+
+/// Serves `filter` over TLS on `address` until `shutdown` completes, then
+/// waits for the open requests like `warp::serve` does.
+///
+/// Filters don't see the address of the client when warp isn't accepting the
+/// connections itself, so it is passed on in the `PEER_ADDRESS_HEADER`,
+/// replacing whatever the client sent in there.
+pub async fn serve_tls<F>(filter: F, address: SocketAddr, tls: TlsTerminator, shutdown: impl Future<Output = ()> + Send + 'static) -> Result<impl Future<Output = ()>>
+where
+    F: Filter + Clone + Send + Sync + 'static,
+    F::Extract: Reply,
+{
+    let listener = TcpListener::bind(address).await
+        .map_err(|e| Error::Unavailable(format!("could not bind to {}: {}", address, e)))?;
+    let service = warp::service(filter);
+    let make_service = make_service_fn(move |stream: &TlsStream<TcpStream>| {
+        let peer = stream.get_ref().0.peer_addr().ok()
+            .and_then(|peer| HeaderValue::from_str(&peer.to_string()).ok());
+        let service = service.clone();
+        async move {
+            Ok::<_, Infallible>(service_fn(move |mut request| {
+                let headers = request.headers_mut();
+                headers.remove(PEER_ADDRESS_HEADER);
+                if let Some(peer) = &peer {
+                    headers.insert(PEER_ADDRESS_HEADER, peer.clone());
+                }
+                let mut service = service.clone();
+                service.call(request)
+            }))
+        }
+    });
+    let server = Server::builder(accept::from_stream(tls.incoming(listener)))
+        .serve(make_service)
+        .with_graceful_shutdown(shutdown);
+    Ok(async move {
+        if let Err(e) = server.await {
+            error!("TLS server failed: {}", e);
+        }
+    })
+}
+
+fn load_acceptor(cert_path: &str, key_path: &str) -> Result<TlsAcceptor> {
+    let certs = read_pem(cert_path, |reader| certs(reader))?;
+    if certs.is_empty() {
+        return Err(Error::Config(format!("no certificate in {}", cert_path)));
+    }
+    let key = read_key(key_path)?;
+    let mut config = ServerConfig::new(NoClientAuth::new());
+    config.set_single_cert(certs, key)
+        .map_err(|e| Error::Config(format!("invalid TLS certificate or key: {}", e)))?;
+    config.set_protocols(&[b"h2".to_vec(), b"http/1.1".to_vec()]);
+    Ok(TlsAcceptor::from(Arc::new(config)))
+}
+
+/// Reads the first PKCS#8 or, failing that, RSA private key of the file.
+fn read_key(path: &str) -> Result<PrivateKey> {
+    let keys = read_pem(path, |reader| pkcs8_private_keys(reader))?;
+    let keys = if keys.is_empty() { read_pem(path, |reader| rsa_private_keys(reader))? } else { keys };
+    keys.into_iter().next().ok_or_else(|| Error::Config(format!("no private key in {}", path)))
+}
+
+fn read_pem<T>(path: &str, parse: impl Fn(&mut dyn io::BufRead) -> std::result::Result<Vec<T>, ()>) -> Result<Vec<T>> {
+    let file = File::open(path).map_err(|e| Error::Config(format!("could not read {}: {}", path, e)))?;
+    parse(&mut BufReader::new(file)).map_err(|_| Error::Config(format!("could not parse {} as PEM", path)))
+}
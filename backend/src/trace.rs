@@ -0,0 +1,279 @@
+use rand::Rng;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt::{self, Write};
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::subscriber::{Interest, SetGlobalDefaultError, Subscriber};
+use tracing::{Event, Instrument, Level, Metadata, Span};
+use warp::http::{HeaderValue, Response};
+use warp::hyper::Body;
+use warp::trace::Info;
+
+use crate::config::LogConfig;
+
+/// Field of the request span holding the id returned as `X-Request-Id`.
+const REQUEST_ID: &str = "request_id";
+
+thread_local! {
+    /// Spans entered on this thread, entered spans are exited on the same thread.
+    static ENTERED: RefCell<Vec<Id>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Writes `tracing` events to the `log` logger. The fields of the enclosing
+/// spans are put into the MDC while a record is logged, so the log4rs
+/// encoders can output the request id.
+pub struct LogSubscriber {
+    slow_handler: Duration,
+    next_id: AtomicU64,
+    spans: Mutex<HashMap<u64, SpanData>>,
+}
+
+struct SpanData {
+    /// Fields of the span and its parents, later ones take precedence.
+    fields: Vec<(&'static str, String)>,
+    references: usize,
+}
+
+/// Collects the `message` of an event and its other fields.
+#[derive(Default)]
+struct FieldVisitor {
+    message: String,
+    fields: Vec<(&'static str, String)>,
+}
+
+/// Installs the [`LogSubscriber`] for the whole process, after the `log` logger is set up.
+pub fn init(config: &LogConfig) -> Result<(), SetGlobalDefaultError> {
+    tracing::subscriber::set_global_default(LogSubscriber::new(config))
+}
+
+/// Span of a request with a new request id, for `warp::trace`.
+pub fn request_span(info: Info) -> Span {
+    info_span!(
+        "request",
+        request_id = %generate_request_id(),
+        method = %info.method(),
+        path = %info.path(),
+    )
+}
+
+/// Access log line of a finished request, logged in its span so it carries the request id.
+pub fn log_request(info: warp::log::Info) {
+    info!(
+        target: "api",
+        "{} \"{} {} {:?}\" {} \"{}\" \"{}\" {:?}",
+        info.remote_addr().map(|addr| addr.to_string()).unwrap_or_else(|| "-".to_string()),
+        info.method(),
+        info.path(),
+        info.version(),
+        info.status().as_u16(),
+        info.referer().unwrap_or("-"),
+        info.user_agent().unwrap_or("-"),
+        info.elapsed(),
+    );
+}
+
+/// Returns the id of the request as `X-Request-Id`.
+pub fn add_request_id(mut response: Response<Body>) -> Response<Body> {
+    if let Some(value) = current_request_id().and_then(|id| HeaderValue::from_str(&id).ok()) {
+        response.headers_mut().insert("x-request-id", value);
+    }
+    response
+}
+
+/// Runs the handler `name` in a span of its own and logs how long it took,
+/// as a warning if it is slower than `log.slow_handler_ms`.
+pub async fn time_handler<F: Future>(name: &'static str, handler: F) -> F::Output {
+    let span = info_span!("handler", handler = name);
+    let start = Instant::now();
+    let output = handler.instrument(span.clone()).await;
+    let elapsed = start.elapsed();
+    let slow_handler = tracing::dispatcher::get_default(|dispatch| {
+        dispatch.downcast_ref::<LogSubscriber>().map(|subscriber| subscriber.slow_handler)
+    });
+    match slow_handler {
+        Some(slow_handler) if elapsed >= slow_handler => warn!(parent: &span, "Slow handler {} took {:?}", name, elapsed),
+        _ => debug!(parent: &span, "Handler {} took {:?}", name, elapsed),
+    }
+    output
+}
+
+/// Request id of the span currently entered on this thread, if any.
+pub fn current_request_id() -> Option<String> {
+    tracing::dispatcher::get_default(|dispatch| {
+        dispatch.downcast_ref::<LogSubscriber>().and_then(|subscriber| {
+            let current = current()?;
+            subscriber.get_field(&current, REQUEST_ID)
+        })
+    })
+}
+
+fn generate_request_id() -> String {
+    format!("{:016x}", rand::thread_rng().gen::<u64>())
+}
+
+fn current() -> Option<Id> {
+    ENTERED.with(|entered| entered.borrow().last().cloned())
+}
+
+impl LogSubscriber {
+    pub fn new(config: &LogConfig) -> Self {
+        Self {
+            slow_handler: config.get_slow_handler(),
+            next_id: AtomicU64::new(1),
+            spans: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn get_field(&self, id: &Id, name: &str) -> Option<String> {
+        let spans = self.spans.lock().ok()?;
+        spans.get(&id.into_u64())?
+            .fields.iter()
+            .rev()
+            .find(|(field, _)| *field == name)
+            .map(|(_, value)| value.clone())
+    }
+
+    fn get_context(&self, parent: Option<Id>) -> Vec<(&'static str, String)> {
+        match (parent, self.spans.lock()) {
+            (Some(parent), Ok(spans)) => spans.get(&parent.into_u64()).map(|span| span.fields.clone()).unwrap_or_default(),
+            _ => Vec::new(),
+        }
+    }
+}
+
+impl Subscriber for LogSubscriber {
+    fn register_callsite(&self, _metadata: &'static Metadata<'static>) -> Interest {
+        // The log level may change after a callsite was first hit, so nothing is cached.
+        Interest::sometimes()
+    }
+
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        // Request and query spans carry the request id even if only warnings are logged.
+        (metadata.is_span() && *metadata.level() <= Level::INFO) || to_log_level(*metadata.level()) <= log::max_level()
+    }
+
+    fn new_span(&self, span: &Attributes<'_>) -> Id {
+        let parent = if span.is_contextual() { current() } else { span.parent().cloned() };
+        let mut fields = self.get_context(parent);
+        let mut visitor = FieldVisitor::default();
+        span.record(&mut visitor);
+        fields.extend(visitor.fields);
+
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        if let Ok(mut spans) = self.spans.lock() {
+            spans.insert(id, SpanData { fields, references: 1 });
+        }
+        Id::from_u64(id)
+    }
+
+    fn record(&self, span: &Id, values: &Record<'_>) {
+        let mut visitor = FieldVisitor::default();
+        values.record(&mut visitor);
+        if let Some(span) = self.spans.lock().ok().as_mut().and_then(|spans| spans.get_mut(&span.into_u64())) {
+            span.fields.extend(visitor.fields);
+        }
+    }
+
+    fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+
+    fn event(&self, event: &Event<'_>) {
+        let metadata = event.metadata();
+        let parent = if event.is_contextual() { current() } else { event.parent().cloned() };
+        let context = self.get_context(parent);
+        let mut visitor = FieldVisitor::default();
+        event.record(&mut visitor);
+        let mut message = visitor.message;
+        for (name, value) in visitor.fields.iter() {
+            let _ = write!(message, " {}={}", name, value);
+        }
+
+        for (name, value) in context.iter() {
+            log_mdc::insert(*name, value.as_str());
+        }
+        log::logger().log(
+            &log::Record::builder()
+                .args(format_args!("{}", message))
+                .level(to_log_level(*metadata.level()))
+                .target(metadata.target())
+                .module_path(metadata.module_path())
+                .file(metadata.file())
+                .line(metadata.line())
+                .build(),
+        );
+        for (name, _) in context.iter() {
+            log_mdc::remove(*name);
+        }
+    }
+
+    fn enter(&self, span: &Id) {
+        ENTERED.with(|entered| entered.borrow_mut().push(span.clone()));
+    }
+
+    fn exit(&self, span: &Id) {
+        ENTERED.with(|entered| {
+            let mut entered = entered.borrow_mut();
+            if let Some(position) = entered.iter().rposition(|id| id == span) {
+                entered.remove(position);
+            }
+        });
+    }
+
+    fn clone_span(&self, span: &Id) -> Id {
+        if let Some(data) = self.spans.lock().ok().as_mut().and_then(|spans| spans.get_mut(&span.into_u64())) {
+            data.references += 1;
+        }
+        span.clone()
+    }
+
+    fn try_close(&self, span: Id) -> bool {
+        let mut spans = match self.spans.lock() {
+            Ok(spans) => spans,
+            Err(_) => return false,
+        };
+        let closed = match spans.get_mut(&span.into_u64()) {
+            Some(data) => {
+                data.references -= 1;
+                data.references == 0
+            }
+            None => false,
+        };
+        if closed {
+            spans.remove(&span.into_u64());
+        }
+        closed
+    }
+}
+
+impl Visit for FieldVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.record(field, value.to_string());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.record(field, format!("{:?}", value));
+    }
+}
+
+impl FieldVisitor {
+    fn record(&mut self, field: &Field, value: String) {
+        match field.name() {
+            "message" => self.message = value,
+            name => self.fields.push((name, value)),
+        }
+    }
+}
+
+fn to_log_level(level: Level) -> log::Level {
+    match level {
+        Level::ERROR => log::Level::Error,
+        Level::WARN => log::Level::Warn,
+        Level::INFO => log::Level::Info,
+        Level::DEBUG => log::Level::Debug,
+        Level::TRACE => log::Level::Trace,
+    }
+}
@@ -0,0 +1,61 @@
+use std::convert::Infallible;
+use warp::filters::path::FullPath;
+use warp::http::{HeaderValue, Response, StatusCode};
+use warp::hyper::Body;
+use warp::{Filter, Rejection};
+
+/// Versions of the API served side by side under `/api/{version}`, oldest first.
+const VERSIONS: [&str; 1] = ["v1"];
+/// Versions that keep working, but whose responses point clients at the next version.
+const DEPRECATED_VERSIONS: [&str; 0] = [];
+
+/// Matches the `/api/v1` prefix, and the unversioned `/api` prefix of the
+/// clients from before the API was versioned.
+pub fn v1() -> impl Filter<Extract = (), Error = Rejection> + Clone {
+    warp::path("api").and(warp::path("v1").or(warp::any()).unify())
+}
+
+/// Removes the version from a path, so `/api/v1/user/login` becomes `/api/user/login`.
+pub fn strip_version(path: &str) -> String {
+    for version in VERSIONS.iter() {
+        let prefix = format!("/api/{}", version);
+        if let Some(rest) = path.strip_prefix(&prefix).filter(|rest| rest.is_empty() || rest.starts_with('/')) {
+            return format!("/api{}", rest);
+        }
+    }
+    path.to_string()
+}
+
+/// Marks responses to deprecated paths with a `Deprecation` header and a
+/// `Link` to the same path in the successor version. Unversioned paths are
+/// deprecated in favour of `v1`. Paths no route matched are left alone.
+pub async fn apply_deprecation(path: FullPath, mut response: Response<Body>) -> Result<Response<Body>, Infallible> {
+    if response.status() == StatusCode::NOT_FOUND {
+        return Ok(response);
+    }
+    if let Some(successor) = get_successor(path.as_str()) {
+        if let Ok(link) = HeaderValue::from_str(&format!("<{}>; rel=\"successor-version\"", successor)) {
+            let headers = response.headers_mut();
+            headers.insert("deprecation", HeaderValue::from_static("true"));
+            headers.append("link", link);
+        }
+    }
+    Ok(response)
+}
+
+/// Path to use instead of `path`, `None` if it isn't deprecated.
+fn get_successor(path: &str) -> Option<String> {
+    let rest = path.strip_prefix("/api/")?;
+    let (first, tail) = rest.split_once('/').unwrap_or((rest, ""));
+    match VERSIONS.iter().position(|version| *version == first) {
+        Some(i) if DEPRECATED_VERSIONS.contains(&first) => VERSIONS.get(i + 1).map(|next| format!("/api/{}/{}", next, tail)),
+        Some(_) => None,
+        None if is_version(first) => None,
+        None => Some(format!("/api/{}/{}", VERSIONS[0], rest)),
+    }
+}
+
+/// Whether `segment` names a version like `v2`, served or not.
+fn is_version(segment: &str) -> bool {
+    segment.strip_prefix('v').map(|n| !n.is_empty() && n.chars().all(|c| c.is_ascii_digit())).unwrap_or(false)
+}
@@ -0,0 +1,53 @@
+use mongodb::bson::oid::ObjectId;
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use crate::model::Product;
+use crate::repository::WishlistRepository;
+use crate::Result;
+
+/// Counts how often products are shown in listings.
+///
+/// The counts are kept in memory and only added to the stored products by
+/// `flush`, so serving a listing doesn't write to the database.
+#[derive(Clone, Default)]
+pub struct ViewCounter {
+    pending: Arc<Mutex<BTreeMap<ObjectId, i64>>>,
+}
+
+impl ViewCounter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Counts one view of each of `products`.
+    pub fn record<'a, I: IntoIterator<Item = &'a Product>>(&self, products: I) {
+        let mut pending = self.lock();
+        for id in products.into_iter().filter_map(Product::get_id) {
+            *pending.entry(id.clone()).or_default() += 1;
+        }
+    }
+
+    /// Adds the views counted since the last flush to the products, returns
+    /// the number of views added. The views are kept if this fails.
+    pub async fn flush(&self, repo: &dyn WishlistRepository) -> Result<i64> {
+        let views: Vec<(ObjectId, i64)> = std::mem::take(&mut *self.lock()).into_iter().collect();
+        if views.is_empty() {
+            return Ok(0);
+        }
+        match repo.add_product_views(&views).await {
+            Ok(()) => Ok(views.iter().map(|(_, count)| count).sum()),
+            Err(e) => {
+                let mut pending = self.lock();
+                for (id, count) in views {
+                    *pending.entry(id).or_default() += count;
+                }
+                Err(e)
+            }
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, BTreeMap<ObjectId, i64>> {
+        self.pending.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
@@ -2,13 +2,15 @@ module Api.Product exposing (Product, decoder, list_decoder)
 
 import Api.Source as Source
 import Json.Decode as D
-import Json.Decode.Pipeline exposing (required)
+import Json.Decode.Pipeline exposing (optional, required)
 import Json.Encode as E
 
 
 type alias Product =
-    { name : String
+    { id : String
+    , name : String
     , price : Int
+    , at_all_time_low : Bool
     , quantity : Int
     , stars : Int
     , url : String
@@ -22,8 +24,10 @@ type alias Product =
 decoder : D.Decoder Product
 decoder =
     D.succeed Product
+        |> required "id" D.string
         |> required "name" D.string
         |> required "price" D.int
+        |> optional "at_all_time_low" D.bool False
         |> required "quantity" D.int
         |> required "stars" D.int
         |> required "url" D.string
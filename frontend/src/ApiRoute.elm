@@ -22,6 +22,8 @@ type alias ArchiveQuery =
 
 type alias CategoryQuery =
     { name : Maybe String
+    , offset : Maybe Int
+    , size : Maybe Int
     }
 
 
@@ -38,7 +40,7 @@ timeline_query =
 
 category_query : Query.Parser CategoryQuery
 category_query =
-    Query.map CategoryQuery (Query.string "category")
+    Query.map3 CategoryQuery (Query.string "category") (Query.int "offset") (Query.int "size")
 
 
 archive_query : Query.Parser ArchiveQuery
@@ -49,12 +51,13 @@ archive_query =
 parser : Parser (ApiRoute -> a) a
 parser =
     oneOf
-        [ map LastWishlist (s "api" </> s "wishlist" </> s "last")
-        , map NewProducts (s "api" </> s "product" </> s "newest")
-        , map ProductArchive (s "api" </> s "product" </> s "archive" <?> archive_query)
-        , map ProductsByCategory (s "api" </> s "product" </> s "category" <?> category_query)
-        , map ListCategories (s "api" </> s "category" </> s "list")
-        , map Timeline (s "api" </> s "timeline" </> s "points" <?> timeline_query)
+        [ map LastWishlist (s "api" </> s "v1" </> s "wishlist" </> s "last")
+        , map NewProducts (s "api" </> s "v1" </> s "product" </> s "newest")
+        , map ProductArchive (s "api" </> s "v1" </> s "product" </> s "archive" <?> archive_query)
+        , map ProductsByCategory (s "api" </> s "v1" </> s "product" </> s "category" <?> category_query)
+        , map (\q -> ProductsByCategory { name = Nothing, offset = q.offset, size = q.size }) (s "api" </> s "v1" </> s "product" </> s "uncategorized" <?> archive_query)
+        , map ListCategories (s "api" </> s "v1" </> s "category" </> s "list")
+        , map Timeline (s "api" </> s "v1" </> s "timeline" </> s "points" <?> timeline_query)
         ]
 
 
@@ -70,22 +73,27 @@ to_string route =
         path_pieces =
             case route of
                 LastWishlist ->
-                    [ "api", "wishlist", "last" ]
+                    [ "api", "v1", "wishlist", "last" ]
 
                 NewProducts ->
-                    [ "api", "product", "newest" ]
+                    [ "api", "v1", "product", "newest" ]
 
                 ProductArchive _ ->
-                    [ "api", "product", "archive" ]
+                    [ "api", "v1", "product", "archive" ]
 
-                ProductsByCategory _ ->
-                    [ "api", "product", "category" ]
+                ProductsByCategory q ->
+                    case q.name of
+                        Just _ ->
+                            [ "api", "v1", "product", "category" ]
+
+                        Nothing ->
+                            [ "api", "v1", "product", "uncategorized" ]
 
                 ListCategories ->
-                    [ "api", "category", "list" ]
+                    [ "api", "v1", "category", "list" ]
 
                 Timeline _ ->
-                    [ "api", "wishlist", "values" ]
+                    [ "api", "v1", "wishlist", "values" ]
 
         query_pieces =
             case route of
@@ -95,12 +103,18 @@ to_string route =
                     ]
 
                 ProductsByCategory q ->
+                    let
+                        page =
+                            [ ( "offset", String.fromInt <| Maybe.withDefault 0 q.offset )
+                            , ( "size", String.fromInt <| Maybe.withDefault 10 q.size )
+                            ]
+                    in
                     case q.name of
                         Just name ->
-                            [ ( "category", name ) ]
+                            ( "category", name ) :: page
 
                         Nothing ->
-                            []
+                            page
 
                 Timeline q ->
                     [ ( "from_timestamp", String.fromInt <| Maybe.withDefault 0 q.from_timestamp )
@@ -107,6 +107,13 @@ to_last_error model =
     model.last_error
 
 
+{-| Categories are shown on a single page, so request more than any category holds.
+-}
+category_page_size : Int
+category_page_size =
+    1000
+
+
 request_categories : Cmd Msg
 request_categories =
     Http.get
@@ -122,7 +129,7 @@ request_products maybe_category =
             Maybe.andThen (\c -> Just c.name) maybe_category
     in
     Http.get
-        { url = ApiRoute.to_string (ApiRoute.ProductsByCategory { name = cat_string })
+        { url = ApiRoute.to_string (ApiRoute.ProductsByCategory { name = cat_string, offset = Just 0, size = Just category_page_size })
         , expect = Http.expectJson GotProducts Api.Product.list_decoder
         }
 
@@ -31,14 +31,14 @@ view_item product =
                 ]
     in
     [ tr []
-        [ td [] [ img [ src product.url_img, class "img-responsive", alt "{{ PRODUCT_IMG_ALT }}" ] [] ]
+        [ td [] [ img [ src ("/api/v1/images/" ++ product.id ++ "/medium"), class "img-responsive", alt "{{ PRODUCT_IMG_ALT }}" ] [] ]
         , th [ attribute "colspan" "2", class "align-middle" ]
-            [ a [ href product.url, target "_blank" ]
+            [ a [ href ("/api/v1/go/" ++ product.id), target "_blank" ]
                 [ text <| shorten_product_name product.name
                 ]
             ]
         ]
-    , view_row "{{ LABEL.VALUE }}" <| text (get_price_quantity product)
+    , view_row "{{ LABEL.VALUE }}" <| view_price product
     , view_row "{{ LABEL.WISHLIST_NAME }}" <| a [ href product.source.url, target "_blank" ] [ text product.source.name ]
     , view_row "{{ LABEL.DATE_RANGE }}" <| text (get_date_range product)
     , view_row "{{ LABEL.DURATION }}" <| text (get_duration product)
@@ -71,6 +71,19 @@ shorten_product_name name =
            )
 
 
+view_price : Product -> Html msg
+view_price product =
+    case product.at_all_time_low of
+        True ->
+            span []
+                [ text (get_price_quantity product)
+                , span [ class "badge badge-success ml-2" ] [ text "{{ LABEL.ALL_TIME_LOW }}" ]
+                ]
+
+        False ->
+            text (get_price_quantity product)
+
+
 get_price_quantity : Product -> String
 get_price_quantity prod =
     let